@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use heck::ToSnakeCase;
+use heck::{ToShoutySnakeCase, ToSnakeCase};
 
 use crate::ast::{Attribute, Type};
 
@@ -34,10 +34,21 @@ impl Field {
         }
     }
 
+    pub fn const_name(&self) -> String {
+        self.field_name.to_shouty_snake_case()
+    }
+
     pub fn attributes(&self) -> &Vec<Attribute> {
         &self.field_attributes
     }
 
+    pub fn default_string(&self) -> Option<&str> {
+        match &self.field_default {
+            Some(FieldDefault::String { value }) => Some(value),
+            _ => None,
+        }
+    }
+
     pub fn default_value(&self) -> Option<String> {
         match (&self.field_default, self.field_type.enum_()) {
             (Some(FieldDefault::Integer { value }), None) => Some(format!("{}", value)),
@@ -2,6 +2,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::ast::{Attribute, Field, Namespace, Parameter, Type};
 
+/// Telemetry data types that are sent under one of their aliases instead of a declaration name, as
+/// `(declaration name, full name, sent name)`. Page view performance is only collected by the
+/// JavaScript SDK, so it is sent with the same casing, e.g. `PageviewPerformanceData`.
+const RENAMED_TELEMETRY_DATA: &[(&str, &str, &str)] =
+    &[("PageViewPerfData", "PageViewPerformanceData", "PageviewPerformanceData")];
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
@@ -39,7 +45,51 @@ impl Struct {
         &self.decl_attributes
     }
 
+    pub fn is_context_tag_keys(&self) -> bool {
+        self.name() == "ContextTagKeys"
+    }
+
     pub fn is_telemetry_data(&self) -> bool {
         self.name().ends_with("Data") && self.name().len() > 4
     }
+
+    /// Returns a full name of telemetry data, e.g. the one without abbreviations.
+    pub fn telemetry_name(&self) -> &str {
+        self.renamed().map_or(self.name(), |(_, name, _)| name)
+    }
+
+    /// Returns a name telemetry data is sent with as a base type of an envelope.
+    pub fn base_type(&self) -> &str {
+        self.renamed().map_or(self.name(), |(_, _, base_type)| base_type)
+    }
+
+    fn renamed(&self) -> Option<&(&str, &str, &str)> {
+        let renamed = RENAMED_TELEMETRY_DATA
+            .iter()
+            .find(|(declaration, _, _)| *declaration == self.name())?;
+
+        let (_, name, base_type) = renamed;
+        assert!(
+            self.aliases().any(|alias| alias == *name) && self.aliases().any(|alias| alias == *base_type),
+            "{} and {} must be declared as aliases of {}",
+            name,
+            base_type,
+            self.name()
+        );
+        Some(renamed)
+    }
+
+    fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.attributes()
+            .iter()
+            .filter(|attribute| attribute.names().iter().any(|name| name == "Alias"))
+            .flat_map(|attribute| attribute.value().split(';'))
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.attributes()
+            .iter()
+            .find(|attribute| attribute.names().iter().any(|name| name == "Description"))
+            .map(|attribute| attribute.value())
+    }
 }
@@ -0,0 +1,91 @@
+use std::fmt::{self, Display, Formatter};
+
+use heck::ToShoutySnakeCase;
+
+use crate::ast::{Attribute, Field, Struct};
+use crate::compiler::Visitor;
+
+/// Generates a `tags` module with a constant for each key of context tags.
+pub struct TagKeysGenerator {
+    constants: Vec<String>,
+}
+
+impl TagKeysGenerator {
+    pub fn new() -> Self {
+        Self {
+            constants: Vec::default(),
+        }
+    }
+
+    pub fn push_into(self, module: &mut codegen::Scope) {
+        module.raw(format!(
+            "/// Keys of context tags defined by the schema.\npub mod tags {{\n{}\n}}",
+            self.constants.join("\n\n")
+        ));
+    }
+}
+
+impl Visitor for TagKeysGenerator {
+    fn visit_field(&mut self, field: &Field) {
+        let key = field
+            .default_string()
+            .expect("Context tag key must have a default value");
+        let doc = field
+            .attributes()
+            .iter()
+            .find(|attribute| is_description(attribute))
+            .map(|attribute| format!("    /// {}\n", attribute.value()))
+            .unwrap_or_default();
+
+        self.constants.push(format!(
+            "{}    pub const {}: &str = \"{}\";",
+            doc,
+            field.const_name(),
+            key
+        ));
+    }
+}
+
+/// Generates a `names` module with a constant for a name of an envelope that carries each type of
+/// telemetry data.
+pub struct EnvelopeNamesGenerator {
+    constants: Vec<String>,
+}
+
+impl EnvelopeNamesGenerator {
+    pub fn new() -> Self {
+        Self {
+            constants: Vec::default(),
+        }
+    }
+}
+
+impl Visitor for EnvelopeNamesGenerator {
+    fn visit_struct(&mut self, declaration: &Struct) {
+        if declaration.is_telemetry_data() {
+            self.constants.push(format!(
+                "    /// A name of an envelope that carries [`{data}`](super::{data}).\n    pub const {constant}: &str = \"Microsoft.ApplicationInsights.{name}\";",
+                data = declaration.name(),
+                constant = declaration.telemetry_name().trim_end_matches("Data").to_shouty_snake_case(),
+                name = declaration.base_type().trim_end_matches("Data")
+            ));
+        }
+    }
+}
+
+impl Display for EnvelopeNamesGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut scope = codegen::Scope::new();
+        scope
+            .raw("// NOTE: This file was automatically generated.")
+            .raw(format!(
+                "/// Names of envelopes that carry each type of telemetry data.\npub mod names {{\n{}\n}}",
+                self.constants.join("\n\n")
+            ));
+        write!(f, "{}", scope.to_string())
+    }
+}
+
+fn is_description(attribute: &Attribute) -> bool {
+    attribute.names().iter().any(|name| name == "Description")
+}
@@ -0,0 +1,55 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::ast::Struct;
+use crate::compiler::Visitor;
+
+/// Generates a `Data` enum with a variant for each type of telemetry data, so an envelope carries
+/// any of them tagged with its base type.
+pub struct DataGenerator {
+    description: Option<String>,
+    variants: Vec<String>,
+}
+
+impl DataGenerator {
+    pub fn new() -> Self {
+        Self {
+            description: None,
+            variants: Vec::default(),
+        }
+    }
+}
+
+impl Visitor for DataGenerator {
+    fn visit_struct(&mut self, declaration: &Struct) {
+        if declaration.name() == "Data" {
+            self.description = declaration.description().map(String::from);
+        } else if declaration.is_telemetry_data() {
+            let rename = Some(declaration.base_type())
+                .filter(|base_type| *base_type != declaration.name())
+                .map(|base_type| format!("    #[serde(rename = \"{}\")]\n", base_type))
+                .unwrap_or_default();
+            self.variants
+                .push(format!("{}    {name}({name}),", rename, name = declaration.name()));
+        }
+    }
+}
+
+impl Display for DataGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut scope = codegen::Scope::new();
+        scope
+            .raw("use crate::contracts::*;\nuse serde::{Deserialize, Serialize};")
+            .raw("// NOTE: This file was automatically generated.")
+            .raw(format!(
+                "/// {}\n\
+                 #[allow(clippy::enum_variant_names)]\n\
+                 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]\n\
+                 #[cfg_attr(feature = \"json-schema\", derive(schemars::JsonSchema))]\n\
+                 #[serde(tag = \"baseType\", content = \"baseData\")]\n\
+                 pub enum Data {{\n{}\n}}",
+                self.description.as_deref().unwrap_or_default(),
+                self.variants.join("\n")
+            ));
+        write!(f, "{}", scope.to_string())
+    }
+}
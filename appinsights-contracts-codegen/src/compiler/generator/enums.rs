@@ -12,6 +12,8 @@ impl EnumGenerator {
             .derive("Debug")
             .derive("Clone")
             .derive("Serialize")
+            .derive("Deserialize")
+            .r#macro(r#"#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]"#)
             .vis("pub");
 
         Self { declaration }
@@ -1,9 +1,13 @@
+mod constants;
+mod data;
 mod enums;
 mod packages;
 mod schemas;
 mod structs;
 mod types;
 
+pub use constants::{EnvelopeNamesGenerator, TagKeysGenerator};
+pub use data::DataGenerator;
 pub use enums::EnumGenerator;
 pub use packages::PackageGenerator;
 pub use schemas::SchemaGenerator;
@@ -14,8 +14,12 @@ impl PackageGenerator {
     }
 
     pub fn visit_module(&mut self, module: &Module) {
-        self.modules.push(format!("mod {};", module.name()));
-        self.usages.push(format!("pub use {}::*;", module.name()));
+        self.visit_module_name(module.name());
+    }
+
+    pub fn visit_module_name(&mut self, name: &str) {
+        self.modules.push(format!("mod {};", name));
+        self.usages.push(format!("pub use {}::*;", name));
     }
 }
 
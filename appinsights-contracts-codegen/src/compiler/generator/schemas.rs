@@ -1,5 +1,7 @@
 use crate::ast::{Enum, Schema, Struct};
-use crate::compiler::generator::{BuilderGenerator, EnumGenerator, StructGenerator, TelemetryDataTraitGenerator};
+use crate::compiler::generator::{
+    BuilderGenerator, EnumGenerator, StructGenerator, TagKeysGenerator, TelemetryDataTraitGenerator,
+};
 use crate::compiler::Visitor;
 
 pub struct SchemaGenerator {
@@ -12,18 +14,33 @@ impl SchemaGenerator {
             body: codegen::Scope::new(),
         }
     }
+
+    /// Imports items that declarations of types depend on, so a module of constants only is left
+    /// without unused imports.
+    fn import_dependencies(&mut self) {
+        self.body.import("serde", "Serialize");
+        self.body.import("serde", "Deserialize");
+        self.body.import("crate::contracts", "*");
+    }
 }
 
 impl Visitor for SchemaGenerator {
     fn visit_schema(&mut self, schema: &Schema) {
         self.body.raw("// NOTE: This file was automatically generated.");
-        self.body.import("serde", "Serialize");
-        self.body.import("crate::contracts", "*");
-
         self.visit_declarations(schema.declarations());
     }
 
     fn visit_struct(&mut self, declaration: &Struct) {
+        // context tag keys are only defaults of string fields, so generate constants instead
+        if declaration.is_context_tag_keys() {
+            let mut tag_keys_generator = TagKeysGenerator::new();
+            tag_keys_generator.visit_struct(declaration);
+            tag_keys_generator.push_into(&mut self.body);
+            return;
+        }
+
+        self.import_dependencies();
+
         // generate struct declaration
         let mut struct_generator = StructGenerator::new(declaration.name());
         struct_generator.visit_struct(declaration);
@@ -44,6 +61,8 @@ impl Visitor for SchemaGenerator {
     }
 
     fn visit_enum(&mut self, declaration: &Enum) {
+        self.import_dependencies();
+
         let mut enum_generator = EnumGenerator::new(declaration.name());
         enum_generator.visit_enum(declaration);
         enum_generator.push_into(&mut self.body);
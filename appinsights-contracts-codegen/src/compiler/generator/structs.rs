@@ -16,6 +16,8 @@ impl StructGenerator {
             .derive("Debug")
             .derive("Clone")
             .derive("Serialize")
+            .derive("Deserialize")
+            .r#macro(r#"#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]"#)
             .vis("pub");
 
         Self {
@@ -51,12 +51,16 @@ impl From<ComplexType> for codegen::Type {
     fn from(type_: ComplexType) -> codegen::Type {
         match type_ {
             ComplexType::Map { key, element } => {
-                let mut type_ = codegen::Type::new("std::collections::BTreeMap");
-
                 let key = Type::from_str(&key).expect("unexpected type: key");
-                type_.generic(key);
-
                 let element = Type::from_str(&element).expect("unexpected type: element");
+
+                // maps of strings, i.e. tags and properties, share entries of a telemetry context
+                if is_string(&key) && is_string(&element) {
+                    return codegen::Type::new("StringMap");
+                }
+
+                let mut type_ = codegen::Type::new("std::collections::BTreeMap");
+                type_.generic(key);
                 type_.generic(element);
                 type_
             }
@@ -81,3 +85,7 @@ impl From<ComplexType> for codegen::Type {
         }
     }
 }
+
+fn is_string(type_: &Type) -> bool {
+    matches!(type_, Type::Basic(BasicType::String) | Type::Basic(BasicType::WString))
+}
@@ -9,23 +9,30 @@ use std::convert::TryFrom;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use crate::compiler::generator::{PackageGenerator, SchemaGenerator};
+use crate::compiler::generator::{DataGenerator, EnvelopeNamesGenerator, PackageGenerator, SchemaGenerator};
 use crate::parser::Parser;
 use crate::Result;
 
 pub fn compile_all(input_dir: PathBuf, output_dir: PathBuf) -> Result<()> {
-    let mut modules: Vec<_> = fs::read_dir(input_dir)?
-        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
-        .map(|path| Module::try_from((path, output_dir.clone())).expect("unable to read module path"))
-        .collect();
-    modules.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    let modules = read_modules(input_dir, &output_dir)?;
 
-    compile_files(modules.iter())?;
+    compile_files(modules.iter().filter(|module| module.name() != DATA_MODULE))?;
+    compile_data(modules.iter(), &output_dir.join(format!("{}.rs", DATA_MODULE)))?;
+    compile_names(modules.iter(), &output_dir.join(format!("{}.rs", NAMES_MODULE)))?;
     compile_package(modules.iter(), &output_dir.join("mod.rs"))?;
 
     Ok(())
 }
 
+fn read_modules(input_dir: PathBuf, output_dir: &Path) -> Result<Vec<Module>> {
+    let mut modules: Vec<_> = fs::read_dir(input_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .map(|path| Module::try_from((path, output_dir.to_path_buf())).expect("unable to read module path"))
+        .collect();
+    modules.sort_by(|a, b| a.file_name().cmp(b.file_name()));
+    Ok(modules)
+}
+
 fn compile_files<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<()> {
     for module in modules {
         if let Err(err) = compile(module) {
@@ -39,22 +46,114 @@ fn compile_files<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<()> {
 }
 
 fn compile(module: &Module) -> Result<()> {
+    fs::write(module.path(), generate(module)?)?;
+    Ok(())
+}
+
+fn generate(module: &Module) -> Result<String> {
     let parser = Parser;
     let schema = parser.parse(module.source_path())?;
 
     let mut generator = SchemaGenerator::new();
     generator.visit_schema(&schema);
 
-    fs::write(module.path(), generator.to_string())?;
+    Ok(format!("{}\n", generator.to_string()))
+}
+
+/// A name of a module with an enum of all types of telemetry data collected from all schema files.
+/// It replaces a generic struct declared by the schema of the module.
+const DATA_MODULE: &str = "data";
+
+fn compile_data<'a>(modules: impl Iterator<Item = &'a Module>, path: &Path) -> Result<()> {
+    fs::write(path, generate_data(modules)?)?;
+    Ok(())
+}
+
+fn generate_data<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<String> {
+    let parser = Parser;
+    let mut generator = DataGenerator::new();
+    for module in modules {
+        let schema = parser.parse(module.source_path())?;
+        generator.visit_schema(&schema);
+    }
+
+    Ok(format!("{}\n", generator))
+}
+
+/// A name of a module with names of envelopes collected from all schema files.
+const NAMES_MODULE: &str = "envelope_names";
+
+fn compile_names<'a>(modules: impl Iterator<Item = &'a Module>, path: &Path) -> Result<()> {
+    fs::write(path, generate_names(modules)?)?;
     Ok(())
 }
 
+fn generate_names<'a>(modules: impl Iterator<Item = &'a Module>) -> Result<String> {
+    let parser = Parser;
+    let mut generator = EnvelopeNamesGenerator::new();
+    for module in modules {
+        let schema = parser.parse(module.source_path())?;
+        generator.visit_schema(&schema);
+    }
+
+    Ok(format!("{}\n", generator))
+}
+
 fn compile_package<'a>(modules: impl Iterator<Item = &'a Module>, path: &Path) -> Result<()> {
     let mut generator = PackageGenerator::new();
     for module in modules {
         generator.visit_module(module);
     }
+    generator.visit_module_name(NAMES_MODULE);
 
-    fs::write(path, generator.to_string())?;
+    fs::write(path, format!("{}\n", generator.to_string()))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Modules that are generated only, so committed files must match the schema as is.
+    const GENERATED_MODULES: &[&str] = &["context_tag_keys"];
+
+    #[test]
+    fn it_reproduces_committed_modules() {
+        for module in modules()
+            .iter()
+            .filter(|module| GENERATED_MODULES.contains(&module.name()))
+        {
+            assert_generated(module.path(), generate(module).unwrap());
+        }
+    }
+
+    #[test]
+    fn it_reproduces_committed_data() {
+        let path = output_dir().join(format!("{}.rs", DATA_MODULE));
+        assert_generated(&path, generate_data(modules().iter()).unwrap());
+    }
+
+    #[test]
+    fn it_reproduces_committed_envelope_names() {
+        let path = output_dir().join(format!("{}.rs", NAMES_MODULE));
+        assert_generated(&path, generate_names(modules().iter()).unwrap());
+    }
+
+    fn assert_generated(path: &Path, generated: String) {
+        assert_eq!(
+            generated,
+            fs::read_to_string(path).unwrap(),
+            "{} is out of date, regenerate it from the schema",
+            path.display()
+        );
+    }
+
+    fn modules() -> Vec<Module> {
+        let input_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+        read_modules(input_dir, &output_dir()).unwrap()
+    }
+
+    fn output_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../appinsights-core/src/contracts")
+    }
+}
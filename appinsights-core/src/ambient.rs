@@ -0,0 +1,232 @@
+//! An ambient telemetry context of the current operation.
+//!
+//! Library code deep in a call stack often tracks telemetry items that belong to an operation
+//! started somewhere above it, e.g. a request handled by a web server. Instead of threading a
+//! context through every call, an operation context can be made current for a scope with
+//! [`TelemetryContext::enter`] or for a future with [`with_operation`]. A client combines every
+//! telemetry item it tracks with the [current](TelemetryContext::current) context on top of its own
+//! one, so items are correlated with the operation wherever they are tracked, the same way
+//! `Activity.Current` works in .NET.
+//!
+//! The current context is kept in a thread-local slot. A future wrapped with [`with_operation`]
+//! makes its context current every time it is polled, so it follows the future from one worker
+//! thread to another regardless of an async runtime it runs on, like a task-local would.
+//!
+//! # Examples
+//!
+//! ```rust, no_run
+//! # async fn run() {
+//! use appinsights::{ambient::with_operation, telemetry::EventTelemetry, TelemetryClient};
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let mut operation = client.context().clone();
+//! operation.operation_mut().set_id("4bf92f3577b34da6a3ce929d0e0e4736".into());
+//! operation.operation_mut().set_name("GET /orders".into());
+//!
+//! with_operation(operation, async {
+//!     // ... deep in the call stack
+//!     client.track(EventTelemetry::new("order cache refreshed"));
+//! })
+//! .await;
+//! # }
+//! ```
+use std::{
+    cell::RefCell,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use crate::{
+    telemetry::{ContextTags, Properties},
+    TelemetryContext,
+};
+
+thread_local!(static CURRENT: RefCell<Option<Arc<TelemetryContext>>> = const { RefCell::new(None) });
+
+impl TelemetryContext {
+    /// Returns a copy of the telemetry context of the current operation, if any.
+    pub fn current() -> Option<TelemetryContext> {
+        CURRENT.with(|current| current.borrow().as_deref().cloned())
+    }
+
+    /// Makes this context the current one on this thread until the returned guard is dropped.
+    /// The context that was current before is restored then.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use appinsights::{TelemetryConfig, TelemetryContext};
+    ///
+    /// let config = TelemetryConfig::new("instrumentation".to_string());
+    /// let mut operation = TelemetryContext::from_config(&config);
+    /// operation.operation_mut().set_id("4bf92f3577b34da6a3ce929d0e0e4736".into());
+    ///
+    /// let guard = operation.enter();
+    /// assert_eq!(
+    ///     TelemetryContext::current().and_then(|context| context.operation().id().map(String::from)),
+    ///     Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string())
+    /// );
+    ///
+    /// drop(guard);
+    /// assert!(TelemetryContext::current().is_none());
+    /// ```
+    pub fn enter(self) -> ContextGuard {
+        enter(Arc::new(self))
+    }
+
+    /// Combines this context with the current one if any. Tags and properties of the current
+    /// context override the ones of this context, while the instrumentation key and the sample rate
+    /// of this context are kept. A client does it for every telemetry item it tracks, so it is
+    /// needed only to create envelopes without a client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use appinsights::{processor::Envelope, telemetry::EventTelemetry, TelemetryConfig, TelemetryContext};
+    ///
+    /// let config = TelemetryConfig::new("instrumentation".to_string());
+    /// let mut operation = TelemetryContext::from_config(&config);
+    /// operation.operation_mut().set_id("4bf92f3577b34da6a3ce929d0e0e4736".into());
+    /// let _guard = operation.enter();
+    ///
+    /// let context = TelemetryContext::from_config(&config).with_current();
+    /// assert_eq!(context.operation().id(), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+    ///
+    /// let envelope = Envelope::from((context, EventTelemetry::new("device started")));
+    /// ```
+    pub fn with_current(self) -> Self {
+        CURRENT.with(|current| match current.borrow().as_deref() {
+            Some(current) => Self {
+                tags: ContextTags::combine(self.tags, current.tags.clone()),
+                properties: Properties::combine(self.properties, current.properties.clone()),
+                ..self
+            },
+            None => self,
+        })
+    }
+}
+
+/// Makes a given context the current one on this thread and returns a guard that restores the
+/// previous one.
+fn enter(context: Arc<TelemetryContext>) -> ContextGuard {
+    let previous = CURRENT.with(|current| current.replace(Some(context)));
+    ContextGuard {
+        previous,
+        _not_send: PhantomData,
+    }
+}
+
+/// Restores the context that was current before a context was [entered](TelemetryContext::enter)
+/// when dropped. A guard restores the context of the thread it was created on, so it can't be sent
+/// to another thread.
+#[must_use = "the context is current only until the guard is dropped"]
+pub struct ContextGuard {
+    previous: Option<Arc<TelemetryContext>>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Runs a future with a given telemetry context of an operation as the current one. Telemetry
+/// items tracked while the future is polled are combined with the context.
+pub fn with_operation<F: Future>(context: TelemetryContext, future: F) -> WithOperation<F> {
+    WithOperation {
+        context: Arc::new(context),
+        future: Box::pin(future),
+    }
+}
+
+/// A future that makes a telemetry context current every time it is polled. It is created with
+/// [`with_operation`].
+pub struct WithOperation<F> {
+    context: Arc<TelemetryContext>,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for WithOperation<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let _guard = enter(self.context.clone());
+        self.future.as_mut().poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::*;
+
+    #[test]
+    fn it_restores_previous_context_when_guard_dropped() {
+        let outer = context("outer").enter();
+        let inner = context("inner").enter();
+        assert_eq!(current_operation(), Some("inner".into()));
+
+        drop(inner);
+        assert_eq!(current_operation(), Some("outer".into()));
+
+        drop(outer);
+        assert_eq!(current_operation(), None);
+    }
+
+    #[test]
+    fn it_combines_context_with_current_one() {
+        let mut client = context("client");
+        client.cloud_mut().set_role("worker".into());
+        client.properties_mut().insert("tenant".into(), "contoso".into());
+
+        let mut operation = context("operation");
+        operation.properties_mut().insert("tenant".into(), "fabrikam".into());
+        operation.set_i_key("other");
+        let _guard = operation.enter();
+
+        let combined = client.with_current();
+        assert_eq!(combined.i_key(), "instrumentation");
+        assert_eq!(combined.operation().id(), Some("operation"));
+        assert_eq!(combined.cloud().role(), Some("worker"));
+        assert_eq!(combined.properties().get("tenant"), Some(&"fabrikam".to_string()));
+    }
+
+    #[test]
+    fn it_makes_context_current_while_future_is_polled() {
+        let mut future = with_operation(context("operation"), async { current_operation() });
+        assert_eq!(current_operation(), None);
+
+        let waker = noop_waker();
+        let poll = Pin::new(&mut future).poll(&mut Context::from_waker(&waker));
+        assert_eq!(poll, Poll::Ready(Some("operation".into())));
+        assert_eq!(current_operation(), None);
+    }
+
+    fn context(operation_id: &str) -> TelemetryContext {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.operation_mut().set_id(operation_id.into());
+        context
+    }
+
+    fn current_operation() -> Option<String> {
+        TelemetryContext::current().and_then(|context| context.operation().id().map(String::from))
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{
+    processor::RequestSuccess,
+    telemetry::{
+        ApplicationTags, ApplicationTagsMut, CloudTags, CloudTagsMut, ContextTags, DeviceTags, DeviceTagsMut,
+        ExceptionLimits, InternalTags, InternalTagsMut, LocationTags, LocationTagsMut, OperationTags, OperationTagsMut,
+        Properties, SessionTags, SessionTagsMut, UserTags, UserTagsMut,
+    },
+};
+
+/// Settings a telemetry context is created from. It is implemented by `TelemetryConfig` of the
+/// `appinsights` crate.
+pub trait ContextConfig {
+    /// Returns an instrumentation key telemetry items are submitted with.
+    fn i_key(&self) -> &str;
+
+    /// Returns a version of the SDK reported with every telemetry item.
+    fn sdk_version(&self) -> &str;
+
+    /// Returns a version of the application reported with every telemetry item if any.
+    fn application_version(&self) -> Option<&str>;
+
+    /// Returns a maximum number of chained exceptions submitted with an exception telemetry item.
+    fn max_exception_chain_depth(&self) -> usize;
+
+    /// Returns a maximum number of stack frames submitted per exception.
+    fn max_exception_stack_frames(&self) -> usize;
+
+    /// Returns criteria that decide whether requests succeeded based on their response codes.
+    fn request_success(&self) -> &RequestSuccess;
+}
+
+/// Generates accessors of well-known context tags grouped by their context on a telemetry context.
+macro_rules! context_tags {
+    ($($factory:ident, $factory_mut:ident: $name:ident, $name_mut:ident;)*) => {
+        impl TelemetryContext {
+            $(
+                #[doc = concat!("Returns common context fields grouped under '", stringify!($factory), "'.")]
+                pub fn $factory(&self) -> $name<'_> {
+                    self.tags.$factory()
+                }
+
+                #[doc = concat!("Returns mutable common context fields grouped under '", stringify!($factory), "'.")]
+                pub fn $factory_mut(&mut self) -> $name_mut<'_> {
+                    self.tags.$factory_mut()
+                }
+            )*
+        }
+    };
+}
+
+/// Encapsulates contextual data common to all telemetry submitted through a telemetry client.
+/// # Examples
+/// ```rust
+/// use appinsights::telemetry::{ContextTags, Properties};
+/// use appinsights::TelemetryContext;
+///
+/// let mut properties = Properties::default();
+/// properties.insert("Resource Group".to_string(), "my-rg".to_string());
+///
+/// let mut tags = ContextTags::default();
+/// tags.insert("account_id".to_string(), "123-345-777".to_string());
+///
+/// let context = TelemetryContext::new("instrumentation".to_string(), tags, properties);
+///
+/// assert_eq!(context.properties().get("Resource Group"), Some(&"my-rg".to_string()));
+/// assert_eq!(context.tags().get("account_id"), Some(&"123-345-777".to_string()));
+/// ```
+///
+/// Well-known context fields are available with typed accessors that map to the correct tags.
+/// ```rust
+/// use appinsights::{TelemetryConfig, TelemetryContext};
+///
+/// let config = TelemetryConfig::new("instrumentation".to_string());
+/// let mut context = TelemetryContext::from_config(&config);
+/// context.cloud_mut().set_role("ingestion-worker".to_string());
+/// context.application_mut().set_version("1.2.3".to_string());
+///
+/// assert_eq!(context.cloud().role(), Some("ingestion-worker"));
+/// assert_eq!(context.tags().get("ai.application.ver"), Some(&"1.2.3".to_string()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TelemetryContext {
+    /// An instrumentation key.
+    pub(crate) i_key: String,
+
+    // A collection of tags to attach to telemetry event.
+    pub(crate) tags: ContextTags,
+
+    // A collection of common properties to attach to telemetry event.
+    pub(crate) properties: Properties,
+
+    // A percentage of telemetry items represented by each submitted item.
+    pub(crate) sample_rate: f64,
+
+    // Limits of exception chains and stack traces to submit.
+    pub(crate) exception_limits: ExceptionLimits,
+
+    // Criteria that decide whether requests succeeded based on their response codes.
+    pub(crate) request_success: RequestSuccess,
+}
+
+impl TelemetryContext {
+    /// Creates a new instance of telemetry context from config
+    pub fn from_config(config: &impl ContextConfig) -> Self {
+        let i_key = config.i_key().into();
+
+        let mut tags = ContextTags::default();
+        tags.internal_mut().set_sdk_version(config.sdk_version().into());
+        tags.device_mut().set_os_version(os_version());
+        if let Some(version) = config.application_version() {
+            tags.application_mut().set_version(version.into());
+        }
+
+        if let Ok(Ok(host)) = &hostname::get().map(|host| host.into_string()) {
+            tags.device_mut().set_id(host.into());
+            tags.cloud_mut().set_role_instance(host.into());
+        }
+
+        let properties = Properties::default();
+        let mut context = Self::new(i_key, tags, properties);
+        context.exception_limits = ExceptionLimits::from_config(config);
+        context.request_success = config.request_success().clone();
+        context
+    }
+
+    /// Creates a new instance of telemetry context.
+    pub fn new(i_key: String, tags: ContextTags, properties: Properties) -> Self {
+        Self {
+            i_key,
+            tags,
+            properties,
+            sample_rate: 100.0,
+            exception_limits: ExceptionLimits::default(),
+            request_success: RequestSuccess::default(),
+        }
+    }
+
+    /// Returns an instrumentation key telemetry items are submitted with.
+    pub fn i_key(&self) -> &str {
+        &self.i_key
+    }
+
+    /// Replaces an instrumentation key telemetry items are submitted with.
+    pub fn set_i_key(&mut self, i_key: impl Into<String>) {
+        self.i_key = i_key.into();
+    }
+
+    /// Returns mutable reference to a collection of common properties to attach to telemetry event.
+    pub fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns immutable reference to a collection of common properties to attach to telemetry event.
+    pub fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to a collection of common tags to attach to telemetry event.
+    pub fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+
+    /// Returns immutable reference to a collection of common tags to attach to telemetry event.
+    pub fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns a percentage of telemetry items represented by each submitted telemetry event.
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Sets a percentage of telemetry items represented by each submitted telemetry event.
+    ///
+    /// Applications that sample telemetry themselves before submitting it should record the rate
+    /// they use, so that Application Insights can estimate original item counts. For instance,
+    /// a sample rate of 25 means each submitted item stands for 4 original items.
+    /// Defaults to 100, which means no sampling.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a sample rate is not within `(0, 100]` range.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use appinsights::{TelemetryConfig, TelemetryContext};
+    ///
+    /// let config = TelemetryConfig::new("instrumentation".to_string());
+    /// let mut context = TelemetryContext::from_config(&config);
+    /// context.set_sample_rate(25.0);
+    ///
+    /// assert_eq!(context.sample_rate(), 25.0);
+    /// ```
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        assert!(
+            sample_rate > 0.0 && sample_rate <= 100.0,
+            "sample rate must be within (0, 100] range: {}",
+            sample_rate
+        );
+        self.sample_rate = sample_rate;
+    }
+}
+
+context_tags! {
+    application, application_mut: ApplicationTags, ApplicationTagsMut;
+    device, device_mut: DeviceTags, DeviceTagsMut;
+    location, location_mut: LocationTags, LocationTagsMut;
+    operation, operation_mut: OperationTags, OperationTagsMut;
+    session, session_mut: SessionTags, SessionTagsMut;
+    user, user_mut: UserTags, UserTagsMut;
+    cloud, cloud_mut: CloudTags, CloudTagsMut;
+    internal, internal_mut: InternalTags, InternalTagsMut;
+}
+
+/// Returns a name and version of the operating system, e.g. `Ubuntu 22.04.3 LTS` on Linux
+/// distributions that describe themselves in `/etc/os-release`, or its family otherwise.
+fn os_version() -> String {
+    let family = if cfg!(target_os = "linux") {
+        "linux"
+    } else if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else {
+        "unknown"
+    };
+
+    if cfg!(target_os = "linux") {
+        if let Some(name) = std::fs::read_to_string("/etc/os-release")
+            .ok()
+            .and_then(|release| pretty_name(&release))
+        {
+            return name;
+        }
+    }
+
+    family.into()
+}
+
+/// Returns a `PRETTY_NAME` of an operating system from the content of an `os-release` file.
+fn pretty_name(release: &str) -> Option<String> {
+    release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+
+    use super::*;
+    use crate::{
+        contracts::Envelope,
+        telemetry::{EventTelemetry, DEFAULT_MAX_CHAIN_DEPTH, DEFAULT_MAX_STACK_FRAMES},
+    };
+
+    #[derive(Default)]
+    struct TestConfig {
+        application_version: Option<&'static str>,
+        request_success: RequestSuccess,
+    }
+
+    impl ContextConfig for TestConfig {
+        fn i_key(&self) -> &str {
+            "instrumentation"
+        }
+
+        fn sdk_version(&self) -> &str {
+            "rust:test"
+        }
+
+        fn application_version(&self) -> Option<&str> {
+            self.application_version
+        }
+
+        fn max_exception_chain_depth(&self) -> usize {
+            DEFAULT_MAX_CHAIN_DEPTH
+        }
+
+        fn max_exception_stack_frames(&self) -> usize {
+            DEFAULT_MAX_STACK_FRAMES
+        }
+
+        fn request_success(&self) -> &RequestSuccess {
+            &self.request_success
+        }
+    }
+
+    #[test]
+    fn it_updates_common_properties() {
+        let config = TestConfig::default();
+        let mut context = TelemetryContext::from_config(&config);
+        context.properties_mut().insert("Resource Group".into(), "my-rg".into());
+
+        assert_eq!(context.properties().len(), 1);
+        assert_eq!(context.properties().get("Resource Group"), Some(&"my-rg".to_string()));
+    }
+
+    #[test]
+    fn it_creates_a_context_with_default_values() {
+        let config = TestConfig::default();
+
+        let context = TelemetryContext::from_config(&config);
+
+        assert_eq!(&context.i_key, "instrumentation");
+        assert_matches!(&context.tags().internal().sdk_version(), Some(_));
+        assert_matches!(&context.tags().device().os_version(), Some(_));
+        assert_matches!(&context.tags().device().id(), Some(_));
+        assert_matches!(&context.tags().cloud().role_instance(), Some(_));
+        assert!(context.properties().is_empty());
+        assert_eq!(context.sample_rate(), 100.0);
+    }
+
+    #[test]
+    fn it_reports_application_version() {
+        let config = TestConfig {
+            application_version: Some("1.2.3"),
+            ..TestConfig::default()
+        };
+
+        let context = TelemetryContext::from_config(&config);
+
+        assert_eq!(context.application().version(), Some("1.2.3"));
+    }
+
+    #[test]
+    fn it_reads_pretty_name_of_operating_system() {
+        let release = "NAME=\"Ubuntu\"\nPRETTY_NAME=\"Ubuntu 22.04.3 LTS\"\nID=ubuntu\n";
+
+        assert_eq!(pretty_name(release), Some("Ubuntu 22.04.3 LTS".into()));
+        assert_eq!(pretty_name("NAME=Alpine\nPRETTY_NAME=\n"), None);
+    }
+
+    #[test]
+    fn it_sets_well_known_tags_with_typed_accessors() {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.cloud_mut().set_role("worker".into());
+        context.user_mut().set_id("user".into());
+        context.operation_mut().set_name("GET /items".into());
+
+        assert_eq!(context.cloud().role(), Some("worker"));
+        assert_eq!(context.tags().get("ai.cloud.role"), Some(&"worker".to_string()));
+        assert_eq!(context.tags().get("ai.user.id"), Some(&"user".to_string()));
+        assert_eq!(context.tags().get("ai.operation.name"), Some(&"GET /items".to_string()));
+    }
+
+    #[test]
+    fn it_sets_sample_rate_to_submitted_telemetry() {
+        let config = TestConfig::default();
+        let mut context = TelemetryContext::from_config(&config);
+        context.set_sample_rate(25.0);
+
+        let envelope = Envelope::from((context, EventTelemetry::new("test")));
+
+        assert_eq!(envelope.sample_rate, Some(25.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "sample rate must be within (0, 100] range")]
+    fn it_rejects_sample_rate_out_of_range() {
+        let config = TestConfig::default();
+        let mut context = TelemetryContext::from_config(&config);
+        context.set_sample_rate(0.0);
+    }
+}
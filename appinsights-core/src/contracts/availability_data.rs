@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Instances of AvailabilityData represent the result of executing an availability test.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct AvailabilityData {
     pub ver: i32,
@@ -14,7 +15,7 @@ pub struct AvailabilityData {
     pub success: bool,
     pub run_location: Option<String>,
     pub message: Option<String>,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
     pub measurements: Option<std::collections::BTreeMap<String, f64>>,
 }
 
@@ -0,0 +1,346 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{contracts::*, telemetry::envelope, time};
+
+impl Envelope {
+    /// Creates a new builder of an envelope that carries specified telemetry data.
+    pub fn builder(data: Data) -> EnvelopeBuilder {
+        EnvelopeBuilder::new(data)
+    }
+}
+
+impl Envelope {
+    /// Determines whether this envelope carries a trace or an exception with a lower severity than
+    /// a given level. Traces and exceptions without severity are considered errors.
+    #[doc(hidden)]
+    pub fn is_less_severe_than(&self, level: &SeverityLevel) -> bool {
+        match self.data.as_ref().and_then(|Base::Data(data)| data.severity_level()) {
+            Some(severity) => severity.rank() < level.rank(),
+            None => false,
+        }
+    }
+
+    /// Sets a context tag unless the envelope carries it already.
+    #[doc(hidden)]
+    pub fn insert_tag_if_missing(&mut self, key: &str, value: &str) {
+        let tags = self.tags.get_or_insert_with(StringMap::default);
+        if !tags.contains_key(key) {
+            tags.insert(key.into(), value.into());
+        }
+    }
+
+    /// Records that this envelope was kept by a sampler that keeps a given percentage of items.
+    /// A sample rate the envelope carries already is scaled by the percentage, so rates of
+    /// successive samplers combine, e.g. 50% of items sampled at 50% result in a sample rate of 25.
+    pub fn scale_sample_rate(&mut self, percentage: f64) {
+        let sample_rate = self.sample_rate.unwrap_or(100.0) * percentage / 100.0;
+        self.sample_rate = Some(sample_rate);
+    }
+}
+
+impl SeverityLevel {
+    /// Returns a position of a severity level in the order of increasing severity.
+    pub(crate) fn rank(&self) -> u8 {
+        match self {
+            SeverityLevel::Verbose => 0,
+            SeverityLevel::Information => 1,
+            SeverityLevel::Warning => 2,
+            SeverityLevel::Error => 3,
+            SeverityLevel::Critical => 4,
+        }
+    }
+}
+
+impl Data {
+    /// Returns a severity level of a trace or an exception. Traces and exceptions without severity
+    /// are considered errors. Returns `None` for other telemetry data.
+    #[doc(hidden)]
+    pub fn severity_level(&self) -> Option<SeverityLevel> {
+        match self {
+            Data::MessageData(data) => Some(data.severity_level.clone().unwrap_or(SeverityLevel::Error)),
+            Data::ExceptionData(data) => Some(data.severity_level.clone().unwrap_or(SeverityLevel::Error)),
+            _ => None,
+        }
+    }
+
+    /// Returns a name of an envelope that carries this telemetry data.
+    pub fn envelope_name(&self) -> &'static str {
+        match self {
+            Data::AvailabilityData(_) => names::AVAILABILITY,
+            Data::EventData(_) => names::EVENT,
+            Data::ExceptionData(_) => names::EXCEPTION,
+            Data::MessageData(_) => names::MESSAGE,
+            Data::MetricData(_) => names::METRIC,
+            Data::PageViewData(_) => names::PAGE_VIEW,
+            Data::PageViewPerfData(_) => names::PAGE_VIEW_PERFORMANCE,
+            Data::RemoteDependencyData(_) => names::REMOTE_DEPENDENCY,
+            Data::RequestData(_) => names::REQUEST,
+        }
+    }
+
+    /// Returns mutable reference to custom properties of this telemetry data.
+    #[doc(hidden)]
+    pub fn properties_mut(&mut self) -> &mut Option<StringMap> {
+        match self {
+            Data::AvailabilityData(data) => &mut data.properties,
+            Data::EventData(data) => &mut data.properties,
+            Data::ExceptionData(data) => &mut data.properties,
+            Data::MessageData(data) => &mut data.properties,
+            Data::MetricData(data) => &mut data.properties,
+            Data::PageViewData(data) => &mut data.properties,
+            Data::PageViewPerfData(data) => &mut data.properties,
+            Data::RemoteDependencyData(data) => &mut data.properties,
+            Data::RequestData(data) => &mut data.properties,
+        }
+    }
+}
+
+/// Constructs an [`Envelope`] for custom telemetry pipelines.
+///
+/// An envelope name is derived from telemetry data and the time is formatted the way the
+/// ingestion endpoint expects. Telemetry data is validated to contain all required fields.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::processor::{Data, Envelope, EventData};
+///
+/// let envelope = Envelope::builder(Data::EventData(EventData {
+///     name: "application started".into(),
+///     ..EventData::default()
+/// }))
+/// .i_key("<instrumentation key>")
+/// .tag("ai.cloud.role", "rust_server")
+/// .build()
+/// .unwrap();
+///
+/// assert_eq!(envelope.name, "Microsoft.ApplicationInsights.Event");
+/// ```
+#[derive(Debug)]
+pub struct EnvelopeBuilder {
+    data: Data,
+    i_key: Option<String>,
+    time: DateTime<Utc>,
+    sample_rate: f64,
+    tags: BTreeMap<String, String>,
+}
+
+impl EnvelopeBuilder {
+    /// Creates a new builder of an envelope that carries specified telemetry data and was measured right now.
+    pub fn new(data: Data) -> Self {
+        Self {
+            data,
+            i_key: None,
+            time: time::now(),
+            sample_rate: 100.0,
+            tags: BTreeMap::default(),
+        }
+    }
+
+    /// Sets an instrumentation key of a resource to submit an envelope to.
+    pub fn i_key(mut self, i_key: impl Into<String>) -> Self {
+        self.i_key = Some(i_key.into());
+        self
+    }
+
+    /// Sets the time when telemetry was measured.
+    pub fn time(mut self, time: DateTime<Utc>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Sets a percentage of telemetry items represented by this envelope. Defaults to 100.
+    pub fn sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Adds a context tag, e.g. `ai.operation.id`.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Validates telemetry data and constructs an envelope.
+    pub fn build(self) -> Result<Envelope, InvalidEnvelope> {
+        if self.i_key.as_deref().is_none_or(str::is_empty) {
+            return Err(InvalidEnvelope("instrumentation key is required"));
+        }
+
+        if !(self.sample_rate > 0.0 && self.sample_rate <= 100.0) {
+            return Err(InvalidEnvelope("sample rate must be within (0, 100] range"));
+        }
+
+        validate(&self.data)?;
+
+        let envelope = envelope(self.data.envelope_name(), self.time);
+        Ok(Envelope {
+            sample_rate: Some(self.sample_rate),
+            i_key: self.i_key,
+            tags: Some(self.tags.into()),
+            data: Some(Base::Data(self.data)),
+            ..envelope
+        })
+    }
+}
+
+/// An error returned when an envelope misses required data or the data is malformed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEnvelope(&'static str);
+
+impl Display for InvalidEnvelope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid envelope: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidEnvelope {}
+
+/// Checks that telemetry data contains all fields required by the ingestion endpoint.
+fn validate(data: &Data) -> Result<(), InvalidEnvelope> {
+    let error = match data {
+        Data::AvailabilityData(data) if data.id.is_empty() => Some("availability id is required"),
+        Data::AvailabilityData(data) if data.name.is_empty() => Some("availability name is required"),
+        Data::AvailabilityData(data) if !is_valid_duration(&data.duration) => {
+            Some("availability duration is malformed")
+        }
+        Data::EventData(data) if data.name.is_empty() => Some("event name is required"),
+        Data::ExceptionData(data) if data.exceptions.is_empty() => Some("at least one exception is required"),
+        Data::MessageData(data) if data.message.is_empty() => Some("message is required"),
+        Data::MetricData(data) if data.metrics.is_empty() => Some("at least one metric is required"),
+        Data::PageViewData(data) if data.name.is_empty() => Some("page view name is required"),
+        Data::PageViewData(data) if !data.duration.as_deref().is_none_or(is_valid_duration) => {
+            Some("page view duration is malformed")
+        }
+        Data::PageViewPerfData(data) if data.name.is_empty() => Some("page view name is required"),
+        Data::PageViewPerfData(data)
+            if ![
+                &data.duration,
+                &data.perf_total,
+                &data.network_connect,
+                &data.sent_request,
+                &data.received_response,
+                &data.dom_processing,
+            ]
+            .iter()
+            .all(|duration| duration.as_deref().is_none_or(is_valid_duration)) =>
+        {
+            Some("page view performance duration is malformed")
+        }
+        Data::RemoteDependencyData(data) if data.name.is_empty() => Some("dependency name is required"),
+        Data::RemoteDependencyData(data) if !is_valid_duration(&data.duration) => {
+            Some("dependency duration is malformed")
+        }
+        Data::RequestData(data) if data.id.is_empty() => Some("request id is required"),
+        Data::RequestData(data) if data.response_code.is_empty() => Some("request response code is required"),
+        Data::RequestData(data) if !is_valid_duration(&data.duration) => Some("request duration is malformed"),
+        _ => None,
+    };
+
+    match error {
+        Some(error) => Err(InvalidEnvelope(error)),
+        None => Ok(()),
+    }
+}
+
+/// Determines whether a duration is formatted as `d.hh:mm:ss.fffffff`.
+fn is_valid_duration(duration: &str) -> bool {
+    let is_number = |value: &str, len: Option<usize>| {
+        !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) && len.is_none_or(|len| value.len() == len)
+    };
+
+    let (days, rest) = match duration.split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let (clock, fraction) = match rest.split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let clock: Vec<_> = clock.split(':').collect();
+
+    is_number(days, None)
+        && clock.len() == 3
+        && clock.iter().all(|part| is_number(part, Some(2)))
+        && is_number(fraction, Some(7))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_builds_envelope_with_name_derived_from_data() {
+        let data = Data::MessageData(MessageData {
+            message: "message".into(),
+            ..MessageData::default()
+        });
+
+        let envelope = Envelope::builder(data.clone())
+            .i_key("instrumentation")
+            .time(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600))
+            .sample_rate(25.0)
+            .tag("ai.operation.id", "operation")
+            .build()
+            .unwrap();
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Message".into(),
+            time: "2019-01-02T03:04:05.600Z".into(),
+            sample_rate: Some(25.0),
+            i_key: Some("instrumentation".into()),
+            tags: Some(StringMap::from([("ai.operation.id".into(), "operation".into())])),
+            data: Some(Base::Data(data)),
+            ..Envelope::default()
+        };
+        assert_eq!(envelope, expected);
+    }
+
+    #[test_case(Data::EventData(EventData::default()), "event name is required"; "event without name")]
+    #[test_case(Data::MetricData(MetricData::default()), "at least one metric is required"; "metric without data points")]
+    #[test_case(request("0.00:00:01.0000000", ""), "request response code is required"; "request without response code")]
+    #[test_case(request("00:00:01", "200"), "request duration is malformed"; "request with malformed duration")]
+    #[test_case(page_view_perf("00:00:01"), "page view performance duration is malformed"; "page view performance with malformed duration")]
+    fn it_rejects_invalid_data(data: Data, error: &'static str) {
+        let result = Envelope::builder(data).i_key("instrumentation").build();
+
+        assert_eq!(result, Err(InvalidEnvelope(error)));
+    }
+
+    #[test]
+    fn it_rejects_envelope_without_instrumentation_key() {
+        let data = Data::EventData(EventData {
+            name: "event".into(),
+            ..EventData::default()
+        });
+
+        let result = Envelope::builder(data).build();
+
+        assert_eq!(result, Err(InvalidEnvelope("instrumentation key is required")));
+    }
+
+    fn request(duration: &str, response_code: &str) -> Data {
+        Data::RequestData(RequestData {
+            id: "id".into(),
+            duration: duration.into(),
+            response_code: response_code.into(),
+            ..RequestData::default()
+        })
+    }
+
+    fn page_view_perf(perf_total: &str) -> Data {
+        Data::PageViewPerfData(PageViewPerfData {
+            name: "page".into(),
+            perf_total: Some(perf_total.into()),
+            ..PageViewPerfData::default()
+        })
+    }
+}
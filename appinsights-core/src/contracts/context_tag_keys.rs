@@ -0,0 +1,86 @@
+// NOTE: This file was automatically generated.
+
+/// Keys of context tags defined by the schema.
+pub mod tags {
+    /// Application version. Information in the application context fields is always about the application that is sending the telemetry.
+    pub const APPLICATION_VERSION: &str = "ai.application.ver";
+
+    /// Unique client device id. Computer name in most cases.
+    pub const DEVICE_ID: &str = "ai.device.id";
+
+    /// Device locale using <language>-<REGION> pattern, following RFC 5646. Example 'en-US'.
+    pub const DEVICE_LOCALE: &str = "ai.device.locale";
+
+    /// Model of the device the end user of the application is using. Used for client scenarios. If this field is empty then it is derived from the user agent.
+    pub const DEVICE_MODEL: &str = "ai.device.model";
+
+    /// Client device OEM name taken from the browser.
+    pub const DEVICE_OEM_NAME: &str = "ai.device.oemName";
+
+    /// Operating system name and version of the device the end user of the application is using. If this field is empty then it is derived from the user agent. Example 'Windows 10 Pro 10.0.10586.0'
+    pub const DEVICE_OS_VERSION: &str = "ai.device.osVersion";
+
+    /// The type of the device the end user of the application is using. Used primarily to distinguish JavaScript telemetry from server side telemetry. Examples: 'PC', 'Phone', 'Browser'. 'PC' is the default value.
+    pub const DEVICE_TYPE: &str = "ai.device.type";
+
+    /// The IP address of the client device. IPv4 and IPv6 are supported. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
+    pub const LOCATION_IP: &str = "ai.location.ip";
+
+    /// The country of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
+    pub const LOCATION_COUNTRY: &str = "ai.location.country";
+
+    /// The province/state of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
+    pub const LOCATION_PROVINCE: &str = "ai.location.province";
+
+    /// The city of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
+    pub const LOCATION_CITY: &str = "ai.location.city";
+
+    /// A unique identifier for the operation instance. The operation.id is created by either a request or a page view. All other telemetry sets this to the value for the containing request or page view. Operation.id is used for finding all the telemetry items for a specific operation instance.
+    pub const OPERATION_ID: &str = "ai.operation.id";
+
+    /// The name (group) of the operation. The operation.name is created by either a request or a page view. All other telemetry items set this to the value for the containing request or page view. Operation.name is used for finding all the telemetry items for a group of operations (i.e. 'GET Home/Index').
+    pub const OPERATION_NAME: &str = "ai.operation.name";
+
+    /// The unique identifier of the telemetry item's immediate parent.
+    pub const OPERATION_PARENT_ID: &str = "ai.operation.parentId";
+
+    /// Name of synthetic source. Some telemetry from the application may represent a synthetic traffic. It may be web crawler indexing the web site, site availability tests or traces from diagnostic libraries like Application Insights SDK itself.
+    pub const OPERATION_SYNTHETIC_SOURCE: &str = "ai.operation.syntheticSource";
+
+    /// The correlation vector is a light weight vector clock which can be used to identify and order related events across clients and services.
+    pub const OPERATION_CORRELATION_VECTOR: &str = "ai.operation.correlationVector";
+
+    /// Session ID - the instance of the user's interaction with the app. Information in the session context fields is always about the end user. When telemetry is sent from a service, the session context is about the user that initiated the operation in the service.
+    pub const SESSION_ID: &str = "ai.session.id";
+
+    /// Boolean value indicating whether the session identified by ai.session.id is first for the user or not.
+    pub const SESSION_IS_FIRST: &str = "ai.session.isFirst";
+
+    /// In multi-tenant applications this is the account ID or name which the user is acting with. Examples may be subscription ID for Azure portal or blog name blogging platform.
+    pub const USER_ACCOUNT_ID: &str = "ai.user.accountId";
+
+    /// Anonymous user id. Represents the end user of the application. When telemetry is sent from a service, the user context is about the user that initiated the operation in the service.
+    pub const USER_ID: &str = "ai.user.id";
+
+    /// Authenticated user id. The opposite of ai.user.id, this represents the user with a friendly name. Since it's PII information it is not collected by default by most SDKs.
+    pub const USER_AUTH_USER_ID: &str = "ai.user.authUserId";
+
+    /// Name of the role the application is a part of. Maps directly to the role name in azure.
+    pub const CLOUD_ROLE: &str = "ai.cloud.role";
+
+    pub const CLOUD_ROLE_VER: &str = "ai.cloud.roleVer";
+
+    /// Name of the instance where the application is running. Computer name for on-premisis, instance name for Azure.
+    pub const CLOUD_ROLE_INSTANCE: &str = "ai.cloud.roleInstance";
+
+    pub const CLOUD_LOCATION: &str = "ai.cloud.location";
+
+    /// SDK version. See https://github.com/Microsoft/ApplicationInsights-Home/blob/master/SDK-AUTHORING.md#sdk-version-specification for information.
+    pub const INTERNAL_SDK_VERSION: &str = "ai.internal.sdkVersion";
+
+    /// Agent version. Used to indicate the version of StatusMonitor installed on the computer if it is used for data collection.
+    pub const INTERNAL_AGENT_VERSION: &str = "ai.internal.agentVersion";
+
+    /// This is the node name used for billing purposes. Use it to override the standard detection of nodes.
+    pub const INTERNAL_NODE_NAME: &str = "ai.internal.nodeName";
+}
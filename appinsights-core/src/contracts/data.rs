@@ -1,11 +1,12 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Data struct to contain both B and C sections.
 #[allow(clippy::enum_variant_names)]
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(tag = "baseType", content = "baseData")]
 pub enum Data {
     AvailabilityData(AvailabilityData),
@@ -14,6 +15,8 @@ pub enum Data {
     MessageData(MessageData),
     MetricData(MetricData),
     PageViewData(PageViewData),
+    #[serde(rename = "PageviewPerformanceData")]
+    PageViewPerfData(PageViewPerfData),
     RemoteDependencyData(RemoteDependencyData),
     RequestData(RequestData),
 }
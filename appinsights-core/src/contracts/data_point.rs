@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Metric data single measurement.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct DataPoint {
     pub ns: Option<String>,
@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Type of the metric data measurement.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum DataPointType {
     Measurement,
     Aggregation,
@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// System variables for a telemetry item.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct Envelope {
     pub ver: Option<i32>,
@@ -14,7 +15,7 @@ pub struct Envelope {
     pub seq: Option<String>,
     pub i_key: Option<String>,
     pub flags: Option<i64>,
-    pub tags: Option<std::collections::BTreeMap<String, String>>,
+    pub tags: Option<StringMap>,
     pub data: Option<Base>,
 }
 
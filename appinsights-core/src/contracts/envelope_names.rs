@@ -0,0 +1,31 @@
+// NOTE: This file was automatically generated.
+
+/// Names of envelopes that carry each type of telemetry data.
+pub mod names {
+    /// A name of an envelope that carries [`AvailabilityData`](super::AvailabilityData).
+    pub const AVAILABILITY: &str = "Microsoft.ApplicationInsights.Availability";
+
+    /// A name of an envelope that carries [`EventData`](super::EventData).
+    pub const EVENT: &str = "Microsoft.ApplicationInsights.Event";
+
+    /// A name of an envelope that carries [`ExceptionData`](super::ExceptionData).
+    pub const EXCEPTION: &str = "Microsoft.ApplicationInsights.Exception";
+
+    /// A name of an envelope that carries [`MessageData`](super::MessageData).
+    pub const MESSAGE: &str = "Microsoft.ApplicationInsights.Message";
+
+    /// A name of an envelope that carries [`MetricData`](super::MetricData).
+    pub const METRIC: &str = "Microsoft.ApplicationInsights.Metric";
+
+    /// A name of an envelope that carries [`PageViewData`](super::PageViewData).
+    pub const PAGE_VIEW: &str = "Microsoft.ApplicationInsights.PageView";
+
+    /// A name of an envelope that carries [`PageViewPerfData`](super::PageViewPerfData).
+    pub const PAGE_VIEW_PERFORMANCE: &str = "Microsoft.ApplicationInsights.PageviewPerformance";
+
+    /// A name of an envelope that carries [`RemoteDependencyData`](super::RemoteDependencyData).
+    pub const REMOTE_DEPENDENCY: &str = "Microsoft.ApplicationInsights.RemoteDependency";
+
+    /// A name of an envelope that carries [`RequestData`](super::RequestData).
+    pub const REQUEST: &str = "Microsoft.ApplicationInsights.Request";
+}
@@ -1,15 +1,16 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Instances of Event represent structured event records that can be grouped and searched by their properties. Event data item also creates a metric of event count by name.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct EventData {
     pub ver: i32,
     pub name: String,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
     pub measurements: Option<std::collections::BTreeMap<String, f64>>,
 }
 
@@ -1,17 +1,18 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// An instance of Exception represents a handled or unhandled exception that occurred during execution of the monitored application.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct ExceptionData {
     pub ver: i32,
     pub exceptions: Vec<ExceptionDetails>,
     pub severity_level: Option<SeverityLevel>,
     pub problem_id: Option<String>,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
     pub measurements: Option<std::collections::BTreeMap<String, f64>>,
 }
 
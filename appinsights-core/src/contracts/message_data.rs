@@ -1,16 +1,17 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// Instances of Message represent printf-like trace statements that are text-searched. Log4Net, NLog and other text-based log file entries are translated into intances of this type. The message does not have measurements.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct MessageData {
     pub ver: i32,
     pub message: String,
     pub severity_level: Option<SeverityLevel>,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
     pub measurements: Option<std::collections::BTreeMap<String, f64>>,
 }
 
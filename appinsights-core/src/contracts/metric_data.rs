@@ -1,15 +1,16 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// An instance of the Metric item is a list of measurements (single data points) and/or aggregations.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct MetricData {
     pub ver: i32,
     pub metrics: Vec<DataPoint>,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
 }
 
 impl Default for MetricData {
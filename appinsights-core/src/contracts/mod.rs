@@ -1,39 +1,52 @@
 // NOTE: This file was automatically generated.
 
 #![allow(unused_imports)]
+#![allow(missing_docs)]
 
 mod availability_data;
 mod base;
+mod builder;
+mod context_tag_keys;
 mod data;
 mod data_point;
 mod data_point_type;
 mod envelope;
+mod envelope_names;
 mod event_data;
 mod exception_data;
 mod exception_details;
 mod message_data;
 mod metric_data;
 mod page_view_data;
+mod page_view_perf_data;
 mod remote_dependency_data;
 mod request_data;
 mod response;
+mod session_state;
 mod severity_level;
 mod stack_frame;
+mod string_map;
 
 pub use availability_data::*;
 pub use base::*;
+pub use builder::*;
+pub use context_tag_keys::*;
 pub use data::*;
 pub use data_point::*;
 pub use data_point_type::*;
 pub use envelope::*;
+pub use envelope_names::*;
 pub use event_data::*;
 pub use exception_data::*;
 pub use exception_details::*;
 pub use message_data::*;
 pub use metric_data::*;
 pub use page_view_data::*;
+pub use page_view_perf_data::*;
 pub use remote_dependency_data::*;
 pub use request_data::*;
 pub use response::*;
+pub use session_state::*;
 pub use severity_level::*;
 pub use stack_frame::*;
+pub use string_map::*;
@@ -1,10 +1,11 @@
 use crate::contracts::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // NOTE: This file was automatically generated.
 
 /// An instance of PageView represents a generic action on a page like a button click. It is also the base type for PageView.
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 #[serde(rename_all = "camelCase")]
 pub struct PageViewData {
     pub ver: i32,
@@ -13,7 +14,7 @@ pub struct PageViewData {
     pub duration: Option<String>,
     pub referrer_uri: Option<String>,
     pub id: String,
-    pub properties: Option<std::collections::BTreeMap<String, String>>,
+    pub properties: Option<StringMap>,
     pub measurements: Option<std::collections::BTreeMap<String, f64>>,
 }
 
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+/// A response of the server that describes which telemetry items of a submission it accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transmission {
+    /// The number of telemetry items the server received.
+    pub items_received: usize,
+
+    /// The number of telemetry items the server accepted.
+    pub items_accepted: usize,
+
+    /// Telemetry items the server did not accept.
+    pub errors: Vec<TransmissionItem>,
+}
+
+/// A telemetry item of a submission the server did not accept.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransmissionItem {
+    /// A position of the telemetry item in a submitted batch.
+    pub index: usize,
+
+    /// A status code the server assigned to the telemetry item.
+    pub status_code: u16,
+
+    /// A message of the server that explains why the telemetry item was not accepted.
+    pub message: String,
+}
@@ -0,0 +1,27 @@
+use crate::contracts::*;
+use serde::{Deserialize, Serialize};
+
+// NOTE: This file was automatically generated.
+
+/// Current state of the session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum SessionState {
+    Start,
+    End,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::to_string;
+
+    use super::*;
+
+    #[test]
+    fn it_json_serializes_valid_constants() {
+        // The JSON-serialized values must match the value of `constantName` in
+        // `schema/SessionState.json`.
+        assert_eq!(to_string(&SessionState::Start).unwrap(), r#""Start""#);
+        assert_eq!(to_string(&SessionState::End).unwrap(), r#""End""#);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{
+    cmp::Ordering,
+    collections::{btree_map, BTreeMap},
+    fmt::{Debug, Formatter},
+    iter::{FromIterator, Peekable},
+    mem,
+    ops::Index,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A map of strings, e.g. tags or custom properties of a telemetry item, that overlays entries of
+/// its own over entries shared with other maps.
+///
+/// Every telemetry item tracked by a client is combined with tags and properties of the client
+/// telemetry context, which are the same for all of them. An envelope shares them with the context
+/// instead of copying them, and entries of the item itself override shared ones. Both are merged
+/// only when a map is iterated or serialized. A shared map is copied only when one of its entries
+/// is removed.
+#[derive(Clone, Default)]
+pub struct StringMap {
+    shared: Arc<BTreeMap<String, String>>,
+    own: BTreeMap<String, String>,
+}
+
+impl StringMap {
+    /// Creates a new map of entries of its own over a given shared map.
+    pub fn overlay(shared: Arc<BTreeMap<String, String>>, own: BTreeMap<String, String>) -> Self {
+        Self { shared, own }
+    }
+
+    /// Returns a value of a given key.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.own.get(key).or_else(|| self.shared.get(key))
+    }
+
+    /// Returns a mutable value of a given key. A shared value is copied into entries of this map.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut String> {
+        if !self.own.contains_key(key) {
+            let value = self.shared.get(key)?.clone();
+            self.own.insert(key.into(), value);
+        }
+        self.own.get_mut(key)
+    }
+
+    /// Returns `true` if the map contains a value of a given key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.own.contains_key(key) || self.shared.contains_key(key)
+    }
+
+    /// Inserts a value of a given key and returns a value it overrides if any.
+    pub fn insert(&mut self, key: String, value: String) -> Option<String> {
+        if let Some(previous) = self.own.get_mut(&key) {
+            return Some(mem::replace(previous, value));
+        }
+        let shadowed = self.shared.get(&key).cloned();
+        self.own.insert(key, value);
+        shadowed
+    }
+
+    /// Removes a value of a given key and returns it if any. A shared map is copied if it contains
+    /// the key.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let own = self.own.remove(key);
+        let shared = if self.shared.contains_key(key) {
+            Arc::make_mut(&mut self.shared).remove(key)
+        } else {
+            None
+        };
+        own.or(shared)
+    }
+
+    /// Returns the number of entries of the map.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.own.is_empty() && self.shared.is_empty()
+    }
+
+    /// Returns an iterator over entries of the map sorted by keys.
+    pub fn iter(&self) -> StringMapIter<'_> {
+        StringMapIter {
+            own: self.own.iter().peekable(),
+            shared: self.shared.iter().peekable(),
+        }
+    }
+
+    /// Returns an iterator over keys of the map in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over values of the map sorted by keys.
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+/// An iterator over entries of a [`StringMap`] sorted by keys.
+pub struct StringMapIter<'a> {
+    own: Peekable<btree_map::Iter<'a, String, String>>,
+    shared: Peekable<btree_map::Iter<'a, String, String>>,
+}
+
+impl<'a> Iterator for StringMapIter<'a> {
+    type Item = (&'a String, &'a String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.own.peek(), self.shared.peek()) {
+            (Some((own, _)), Some((shared, _))) => own.cmp(shared),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        match order {
+            Ordering::Less => self.own.next(),
+            Ordering::Greater => self.shared.next(),
+            Ordering::Equal => {
+                // an entry of its own overrides a shared one
+                self.shared.next();
+                self.own.next()
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a StringMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = StringMapIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for StringMap {
+    type Item = (String, String);
+    type IntoIter = btree_map::IntoIter<String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BTreeMap::from(self).into_iter()
+    }
+}
+
+impl Extend<(String, String)> for StringMap {
+    fn extend<T: IntoIterator<Item = (String, String)>>(&mut self, entries: T) {
+        self.own.extend(entries)
+    }
+}
+
+impl FromIterator<(String, String)> for StringMap {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(entries: T) -> Self {
+        Self::from(entries.into_iter().collect::<BTreeMap<_, _>>())
+    }
+}
+
+impl From<BTreeMap<String, String>> for StringMap {
+    fn from(own: BTreeMap<String, String>) -> Self {
+        Self {
+            shared: Arc::default(),
+            own,
+        }
+    }
+}
+
+impl<const N: usize> From<[(String, String); N]> for StringMap {
+    fn from(entries: [(String, String); N]) -> Self {
+        Self::from(BTreeMap::from(entries))
+    }
+}
+
+impl From<StringMap> for BTreeMap<String, String> {
+    fn from(map: StringMap) -> Self {
+        let mut merged = Arc::try_unwrap(map.shared).unwrap_or_else(|shared| (*shared).clone());
+        merged.extend(map.own);
+        merged
+    }
+}
+
+impl Index<&str> for StringMap {
+    type Output = String;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl PartialEq for StringMap {
+    fn eq(&self, other: &Self) -> bool {
+        // maps of items tracked by the same client usually share the same entries
+        (Arc::ptr_eq(&self.shared, &other.shared) && self.own == other.own) || self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for StringMap {}
+
+impl PartialEq<BTreeMap<String, String>> for StringMap {
+    fn eq(&self, other: &BTreeMap<String, String>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Debug for StringMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for StringMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for StringMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BTreeMap::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(feature = "json-schema")]
+impl schemars::JsonSchema for StringMap {
+    fn schema_name() -> String {
+        BTreeMap::<String, String>::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        BTreeMap::<String, String>::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn shared() -> Arc<BTreeMap<String, String>> {
+        Arc::new(BTreeMap::from([
+            ("a".to_string(), "shared".to_string()),
+            ("c".to_string(), "shared".to_string()),
+        ]))
+    }
+
+    fn own() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("b".to_string(), "own".to_string()),
+            ("c".to_string(), "own".to_string()),
+        ])
+    }
+
+    #[test]
+    fn it_overlays_own_entries_over_shared_ones() {
+        let map = StringMap::overlay(shared(), own());
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["a"], "shared");
+        assert_eq!(map["b"], "own");
+        assert_eq!(map["c"], "own");
+        assert_eq!(map.keys().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(BTreeMap::from(map.clone()), map.into_iter().collect::<BTreeMap<_, _>>());
+    }
+
+    #[test]
+    fn it_keeps_shared_entries_shared_until_removed() {
+        let shared = shared();
+        let mut map = StringMap::overlay(shared.clone(), BTreeMap::default());
+
+        assert_eq!(map.insert("a".into(), "own".into()), Some("shared".into()));
+        map.get_mut("c").unwrap().push_str(" and modified");
+        assert!(Arc::ptr_eq(&shared, &map.shared));
+        assert_eq!(shared["a"], "shared");
+        assert_eq!(map["c"], "shared and modified");
+
+        assert_eq!(map.remove("a"), Some("own".into()));
+        assert!(!Arc::ptr_eq(&shared, &map.shared));
+        assert!(!map.contains_key("a"));
+        assert!(shared.contains_key("a"));
+    }
+
+    #[test]
+    fn it_compares_merged_entries() {
+        let map = StringMap::overlay(shared(), own());
+        let merged = BTreeMap::from([
+            ("a".to_string(), "shared".to_string()),
+            ("b".to_string(), "own".to_string()),
+            ("c".to_string(), "own".to_string()),
+        ]);
+
+        assert_eq!(map, merged);
+        assert_eq!(map, StringMap::from(merged));
+        assert_ne!(map, StringMap::overlay(shared(), BTreeMap::default()));
+    }
+
+    #[test]
+    fn it_serializes_merged_entries() {
+        let map = StringMap::overlay(shared(), own());
+
+        let json = serde_json::to_value(&map).unwrap();
+
+        assert_eq!(json, json!({ "a": "shared", "b": "own", "c": "own" }));
+        assert_eq!(serde_json::from_value::<StringMap>(json).unwrap(), map);
+    }
+}
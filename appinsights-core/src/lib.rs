@@ -0,0 +1,54 @@
+//! # Application Insights data model for Rust
+//! The data model of the [`appinsights`](https://docs.rs/appinsights) SDK: telemetry items, a
+//! telemetry context they are combined with, processors applied to them and envelopes they are
+//! submitted in.
+//!
+//! The crate does not depend on an async runtime or an HTTP client, so it builds for embedded and
+//! WASM targets. Applications that send telemetry themselves, e.g. through a host-provided
+//! transport, create envelopes with it directly. Applications that want telemetry to be batched
+//! and sent to Application Insights use the `appinsights` crate, which re-exports everything
+//! provided here.
+//!
+//! ```rust
+//! use appinsights_core::{
+//!     processor::Envelope,
+//!     telemetry::{ContextTags, EventTelemetry, Properties},
+//!     TelemetryContext,
+//! };
+//!
+//! let context = TelemetryContext::new("<instrumentation key>".to_string(), ContextTags::default(), Properties::default());
+//! let envelope = Envelope::from((context, EventTelemetry::new("device started")));
+//! let payload = serde_json::to_string(&envelope).unwrap();
+//! ```
+//!
+//! ## Features
+//!
+//! * `tracing` enables conversions from `tracing` types.
+//! * `json-schema` enables a [`schema`](schema) of telemetry items.
+//! * `pool` enables a [`pool`](pool) of envelopes that reuses allocations of delivered telemetry
+//!   items.
+//! * `test-util` enables hooks to set the current time and identifiers of telemetry items in
+//!   tests. Enable it for dev-dependencies only, since the hooks replace the system clock and the
+//!   random identifier generator.
+#![deny(unused_extern_crates)]
+#![deny(missing_docs)]
+
+pub mod ambient;
+#[doc(hidden)]
+pub mod context;
+pub use context::{ContextConfig, TelemetryContext};
+
+#[doc(hidden)]
+pub mod contracts;
+#[cfg(feature = "pool")]
+pub mod pool;
+pub mod processor;
+#[cfg(feature = "json-schema")]
+pub mod schema;
+#[doc(hidden)]
+pub mod shared;
+pub mod telemetry;
+#[doc(hidden)]
+pub mod time;
+#[doc(hidden)]
+pub mod uuid;
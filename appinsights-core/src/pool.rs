@@ -0,0 +1,116 @@
+//! A pool of envelopes that reuses allocations of telemetry items that were delivered already.
+//!
+//! Envelopes created from telemetry items take a recycled envelope from the pool if there is one
+//! and write their name and timestamp into its buffers instead of allocating new ones. A channel
+//! returns envelopes of a batch to the pool once the batch is delivered. The pool keeps at most
+//! [`CAPACITY`] envelopes, so memory taken by a burst of telemetry is not held forever.
+//!
+//! Pooling pays off for applications that submit many small telemetry items, e.g. metrics, at a
+//! high rate. The `serialization` benchmark of the `appinsights` crate compares both strategies.
+use std::{fmt::Write, sync::Mutex};
+
+use chrono::{DateTime, Utc};
+
+use crate::contracts::Envelope;
+
+/// A maximum number of envelopes the pool keeps for reuse.
+pub const CAPACITY: usize = 1024;
+
+static POOL: EnvelopePool = EnvelopePool::new(CAPACITY);
+
+/// Returns an envelope with a given name and timestamp, reusing a recycled envelope if any.
+pub(crate) fn take(name: &str, timestamp: DateTime<Utc>) -> Envelope {
+    POOL.take(name, timestamp)
+}
+
+/// Returns envelopes to the pool, so new telemetry items reuse their allocations. Envelopes that
+/// don't fit into the pool are dropped.
+pub fn recycle(envelopes: impl IntoIterator<Item = Envelope>) {
+    POOL.recycle(envelopes)
+}
+
+/// Returns a number of envelopes available for reuse.
+pub fn available() -> usize {
+    POOL.available()
+}
+
+/// Envelopes kept for reuse.
+struct EnvelopePool {
+    envelopes: Mutex<Vec<Envelope>>,
+    capacity: usize,
+}
+
+impl EnvelopePool {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            envelopes: Mutex::new(Vec::new()),
+            capacity,
+        }
+    }
+
+    fn take(&self, name: &str, timestamp: DateTime<Utc>) -> Envelope {
+        let recycled = self.envelopes.lock().unwrap_or_else(|err| err.into_inner()).pop();
+        let mut envelope = recycled.unwrap_or_default();
+
+        envelope.name.clear();
+        envelope.name.push_str(name);
+        envelope.time.clear();
+        // the same format as `to_rfc3339_opts(SecondsFormat::Millis, true)` without an allocation
+        write!(envelope.time, "{}", timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ")).expect("write to string");
+        envelope
+    }
+
+    fn recycle(&self, envelopes: impl IntoIterator<Item = Envelope>) {
+        let mut pool = self.envelopes.lock().unwrap_or_else(|err| err.into_inner());
+        let vacant = self.capacity.saturating_sub(pool.len());
+        for mut envelope in envelopes.into_iter().take(vacant) {
+            // data of an item is released right away, only buffers of the envelope itself are kept
+            envelope.ver = Some(1);
+            envelope.sample_rate = Some(100.0);
+            envelope.seq = None;
+            envelope.i_key = None;
+            envelope.flags = None;
+            envelope.tags = None;
+            envelope.data = None;
+            pool.push(envelope);
+        }
+    }
+
+    fn available(&self) -> usize {
+        self.envelopes.lock().unwrap_or_else(|err| err.into_inner()).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{SecondsFormat, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn it_reuses_recycled_envelopes() {
+        let pool = EnvelopePool::new(10);
+        let timestamp = Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 6);
+        let mut envelope = pool.take("Microsoft.ApplicationInsights.Event", timestamp);
+        assert_eq!(envelope.time, timestamp.to_rfc3339_opts(SecondsFormat::Millis, true));
+
+        envelope.seq = Some("1".into());
+        envelope.sample_rate = Some(50.0);
+        let buffer = envelope.name.as_ptr();
+        pool.recycle(vec![envelope]);
+
+        let recycled = pool.take("Microsoft.ApplicationInsights.Trace", timestamp);
+        assert_eq!(recycled.name.as_ptr(), buffer);
+        assert_eq!(recycled.name, "Microsoft.ApplicationInsights.Trace");
+        assert_eq!(recycled.time, "2019-01-02T03:04:05.006Z");
+        assert_eq!(recycled.seq, None);
+        assert_eq!(recycled.sample_rate, Some(100.0));
+    }
+
+    #[test]
+    fn it_keeps_at_most_capacity_envelopes() {
+        let pool = EnvelopePool::new(10);
+        pool.recycle((0..15).map(|_| Envelope::default()));
+        assert_eq!(pool.available(), 10);
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Display, Formatter},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::processor::{Base, Data, Envelope, ProcessingContext, TelemetryProcessor};
+
+/// A name of a property that contains recent trace messages.
+pub(crate) const BREADCRUMBS_PROPERTY: &str = "breadcrumbs";
+
+/// Default maximum number of operations to keep trace messages for.
+const DEFAULT_MAX_OPERATIONS: usize = 1000;
+
+/// A processor that keeps recent trace messages of every operation and attaches them to exceptions
+/// of the same operation in a `breadcrumbs` property. It makes traces leading to a crash available
+/// even when verbose traces are not shipped on their own, e.g. when they are dropped by a
+/// following processor.
+///
+/// Operations are identified by an operation id tag of telemetry items. Items without an operation
+/// id share a single buffer. Only a limited number of operations is tracked at a time; buffers of
+/// the least recently started operations are discarded first.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::{processor::ExceptionBreadcrumbs, TelemetryConfig};
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .processor(ExceptionBreadcrumbs::new(20).max_operations(500))
+///     .build();
+/// ```
+pub struct ExceptionBreadcrumbs {
+    capacity: usize,
+    max_operations: usize,
+    operations: Mutex<Operations>,
+}
+
+impl ExceptionBreadcrumbs {
+    /// Creates a new processor that keeps a given number of the most recent trace messages of
+    /// every operation.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            max_operations: DEFAULT_MAX_OPERATIONS,
+            operations: Mutex::default(),
+        }
+    }
+
+    /// Sets a maximum number of operations to keep trace messages for. Defaults to 1000.
+    pub fn max_operations(mut self, max_operations: usize) -> Self {
+        self.max_operations = max_operations;
+        self
+    }
+}
+
+impl TelemetryProcessor for ExceptionBreadcrumbs {
+    fn process(&self, envelope: &mut Envelope, context: &ProcessingContext) -> bool {
+        let operation_id = context.operation_id().unwrap_or_default();
+        let mut operations = self.operations.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match &mut envelope.data {
+            Some(Base::Data(Data::MessageData(trace))) => {
+                let message = match &trace.severity_level {
+                    Some(severity) => format!("{:?}: {}", severity, trace.message),
+                    None => trace.message.clone(),
+                };
+                operations
+                    .get_or_insert(operation_id, self.capacity, self.max_operations)
+                    .push(message);
+            }
+            Some(Base::Data(Data::ExceptionData(exception))) => {
+                if let Some(breadcrumbs) = operations
+                    .get(operation_id)
+                    .filter(|breadcrumbs| !breadcrumbs.is_empty())
+                {
+                    exception
+                        .properties
+                        .get_or_insert_with(Default::default)
+                        .insert(BREADCRUMBS_PROPERTY.into(), breadcrumbs.to_string());
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+}
+
+/// Trace messages of recent operations.
+#[derive(Default)]
+struct Operations {
+    order: VecDeque<String>,
+    breadcrumbs: HashMap<String, Breadcrumbs>,
+}
+
+impl Operations {
+    fn get(&self, operation_id: &str) -> Option<&Breadcrumbs> {
+        self.breadcrumbs.get(operation_id)
+    }
+
+    /// Returns trace messages of an operation. When an operation is seen for the first time and
+    /// too many operations are tracked already, the oldest one is discarded.
+    fn get_or_insert(&mut self, operation_id: &str, capacity: usize, max_operations: usize) -> &mut Breadcrumbs {
+        if !self.breadcrumbs.contains_key(operation_id) {
+            while self.order.len() >= max_operations.max(1) {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.breadcrumbs.remove(&oldest);
+                }
+            }
+            self.order.push_back(operation_id.to_string());
+        }
+
+        self.breadcrumbs
+            .entry(operation_id.to_string())
+            .or_insert_with(|| Breadcrumbs::new(capacity))
+    }
+}
+
+/// A ring buffer of the most recent trace messages.
+pub(crate) struct Breadcrumbs {
+    capacity: usize,
+    messages: VecDeque<String>,
+}
+
+impl Breadcrumbs {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a message and discards the oldest one if the buffer is full.
+    pub(crate) fn push(&mut self, message: String) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(message);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+impl Display for Breadcrumbs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<_> = self.messages.iter().map(String::as_str).collect();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{ExceptionData, MessageData, SeverityLevel};
+
+    #[test]
+    fn it_attaches_recent_traces_of_the_same_operation_to_exceptions() {
+        let processor = ExceptionBreadcrumbs::new(2);
+
+        processor.process(&mut trace("connecting"), &context("a"));
+        processor.process(&mut trace("connected"), &context("a"));
+        processor.process(&mut trace("unrelated"), &context("b"));
+        processor.process(&mut trace("request sent"), &context("a"));
+
+        let mut exception = exception();
+        assert!(processor.process(&mut exception, &context("a")));
+
+        assert_eq!(
+            breadcrumbs(&exception),
+            Some("Information: connected\nInformation: request sent".into())
+        );
+    }
+
+    #[test]
+    fn it_discards_traces_of_the_oldest_operations() {
+        let processor = ExceptionBreadcrumbs::new(2).max_operations(1);
+
+        processor.process(&mut trace("first"), &context("a"));
+        processor.process(&mut trace("second"), &context("b"));
+
+        let mut first = exception();
+        processor.process(&mut first, &context("a"));
+        let mut second = exception();
+        processor.process(&mut second, &context("b"));
+
+        assert_eq!(breadcrumbs(&first), None);
+        assert_eq!(breadcrumbs(&second), Some("Information: second".into()));
+    }
+
+    fn context(operation_id: &str) -> ProcessingContext {
+        let mut context = ProcessingContext::new(None);
+        context.operation_id = Some(operation_id.into());
+        context
+    }
+
+    fn trace(message: &str) -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::MessageData(MessageData {
+                message: message.into(),
+                severity_level: Some(SeverityLevel::Information),
+                ..MessageData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+
+    fn exception() -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData::default()))),
+            ..Envelope::default()
+        }
+    }
+
+    fn breadcrumbs(envelope: &Envelope) -> Option<String> {
+        match &envelope.data {
+            Some(Base::Data(Data::ExceptionData(data))) => data.properties.as_ref()?.get(BREADCRUMBS_PROPERTY).cloned(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::processor::{truncation::hash, Base, Data, Envelope, ProcessingContext, TelemetryProcessor};
+
+/// A name of a measurement that contains a number of duplicates suppressed before an item.
+const SUPPRESSED_DUPLICATES_MEASUREMENT: &str = "suppressedDuplicates";
+
+/// Default maximum number of distinct items to track duplicates of.
+const DEFAULT_MAX_KEYS: usize = 1000;
+
+/// A processor that suppresses identical exceptions and traces within a time window, so an error
+/// storm does not flood the channel and the ingestion quota with millions of identical items.
+///
+/// Exceptions are identical when they share a problem id, or the type and the message of the
+/// outermost exception when there is no problem id. Traces are identical when they share a
+/// severity and a message. The first item starts a window and is submitted; identical items
+/// tracked before the window is over are dropped. The next identical item after that is submitted
+/// with a `suppressedDuplicates` measurement that tells how many items were dropped in between,
+/// and starts a new window.
+///
+/// Only a limited number of distinct items is tracked at a time. When the limit is reached, items
+/// of expired windows are forgotten, and new distinct items are submitted without being tracked
+/// until there is room again.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use appinsights::{processor::Deduplication, TelemetryConfig};
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .processor(Deduplication::new(Duration::from_secs(60)).max_keys(500))
+///     .build();
+/// ```
+pub struct Deduplication {
+    window: Duration,
+    max_keys: usize,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl Deduplication {
+    /// Creates a new processor that suppresses identical items within a given window.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            max_keys: DEFAULT_MAX_KEYS,
+            windows: Mutex::default(),
+        }
+    }
+
+    /// Sets a maximum number of distinct items to track duplicates of. Defaults to 1000.
+    pub fn max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = max_keys;
+        self
+    }
+
+    /// Returns `true` if a given window is over at a given time.
+    fn is_expired(&self, window: &Window, now: DateTime<Utc>) -> bool {
+        (now - window.started)
+            .to_std()
+            .is_ok_and(|elapsed| elapsed >= self.window)
+    }
+}
+
+impl TelemetryProcessor for Deduplication {
+    fn process(&self, envelope: &mut Envelope, context: &ProcessingContext) -> bool {
+        let key = match key(envelope) {
+            Some(key) => key,
+            None => return true,
+        };
+        let now = context.enqueued_at();
+        let mut windows = self.windows.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let suppressed = match windows.get_mut(&key) {
+            Some(window) if !self.is_expired(window, now) => {
+                window.suppressed += 1;
+                return false;
+            }
+            Some(window) => std::mem::replace(window, Window::new(now)).suppressed,
+            None => {
+                if windows.len() >= self.max_keys {
+                    windows.retain(|_, window| !self.is_expired(window, now));
+                }
+                if windows.len() < self.max_keys {
+                    windows.insert(key, Window::new(now));
+                }
+                0
+            }
+        };
+
+        if suppressed > 0 {
+            if let Some(measurements) = measurements_mut(envelope) {
+                measurements
+                    .get_or_insert_with(Default::default)
+                    .insert(SUPPRESSED_DUPLICATES_MEASUREMENT.into(), suppressed as f64);
+            }
+        }
+
+        true
+    }
+}
+
+/// A period identical items are suppressed within.
+struct Window {
+    started: DateTime<Utc>,
+    suppressed: u64,
+}
+
+impl Window {
+    fn new(started: DateTime<Utc>) -> Self {
+        Self { started, suppressed: 0 }
+    }
+}
+
+/// Returns a key identical exceptions and traces share. Returns `None` for other items.
+fn key(envelope: &Envelope) -> Option<String> {
+    match &envelope.data {
+        Some(Base::Data(Data::ExceptionData(exception))) => {
+            let identity = match (&exception.problem_id, exception.exceptions.first()) {
+                (Some(problem_id), _) => problem_id.clone(),
+                (None, Some(details)) => format!("{}: {}", details.type_name, details.message),
+                (None, None) => String::default(),
+            };
+            Some(format!("exception {}", hash(&identity)))
+        }
+        Some(Base::Data(Data::MessageData(trace))) => {
+            let identity = format!("{:?}: {}", trace.severity_level, trace.message);
+            Some(format!("trace {}", hash(&identity)))
+        }
+        _ => None,
+    }
+}
+
+/// Returns a mutable reference to measurements of an exception or a trace.
+fn measurements_mut(envelope: &mut Envelope) -> Option<&mut Option<BTreeMap<String, f64>>> {
+    match &mut envelope.data {
+        Some(Base::Data(Data::ExceptionData(exception))) => Some(&mut exception.measurements),
+        Some(Base::Data(Data::MessageData(trace))) => Some(&mut trace.measurements),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        contracts::{EventData, ExceptionData, ExceptionDetails, MessageData, SeverityLevel},
+        time,
+    };
+
+    #[test]
+    fn it_suppresses_identical_traces_within_window() {
+        let deduplication = Deduplication::new(Duration::from_secs(60));
+
+        let passed: Vec<_> = [0, 10, 59, 60, 70]
+            .iter()
+            .map(|seconds| process(&deduplication, trace("disk is full"), *seconds))
+            .collect();
+
+        assert_eq!(
+            passed.iter().map(Option::is_some).collect::<Vec<_>>(),
+            vec![true, false, false, true, false]
+        );
+        assert_eq!(suppressed(passed[0].as_ref().unwrap()), None);
+        assert_eq!(suppressed(passed[3].as_ref().unwrap()), Some(2.0));
+    }
+
+    #[test]
+    fn it_tells_different_items_apart() {
+        let deduplication = Deduplication::new(Duration::from_secs(60));
+
+        assert!(process(&deduplication, trace("disk is full"), 0).is_some());
+        assert!(process(&deduplication, trace("network is down"), 0).is_some());
+        assert!(process(&deduplication, exception(Some("problem"), "disk is full"), 0).is_some());
+        assert!(process(&deduplication, exception(Some("problem"), "disk is still full"), 0).is_none());
+        assert!(process(&deduplication, exception(None, "disk is full"), 0).is_some());
+        assert!(process(&deduplication, exception(None, "disk is full"), 0).is_none());
+        assert!(process(&deduplication, event(), 0).is_some());
+        assert!(process(&deduplication, event(), 0).is_some());
+    }
+
+    #[test]
+    fn it_submits_untracked_items_when_too_many_keys() {
+        let deduplication = Deduplication::new(Duration::from_secs(60)).max_keys(1);
+
+        assert!(process(&deduplication, trace("disk is full"), 0).is_some());
+        assert!(process(&deduplication, trace("network is down"), 0).is_some());
+        assert!(process(&deduplication, trace("network is down"), 0).is_some());
+        assert!(process(&deduplication, trace("disk is full"), 0).is_none());
+
+        assert!(process(&deduplication, trace("network is down"), 60).is_some());
+        assert!(process(&deduplication, trace("network is down"), 61).is_none());
+    }
+
+    fn process(deduplication: &Deduplication, mut envelope: Envelope, seconds: i64) -> Option<Envelope> {
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0) + chrono::Duration::seconds(seconds));
+        let context = ProcessingContext::new(None);
+        time::reset();
+
+        if deduplication.process(&mut envelope, &context) {
+            Some(envelope)
+        } else {
+            None
+        }
+    }
+
+    fn suppressed(envelope: &Envelope) -> Option<f64> {
+        match &envelope.data {
+            Some(Base::Data(Data::MessageData(trace))) => trace
+                .measurements
+                .as_ref()
+                .and_then(|measurements| measurements.get(SUPPRESSED_DUPLICATES_MEASUREMENT))
+                .copied(),
+            _ => None,
+        }
+    }
+
+    fn trace(message: &str) -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::MessageData(MessageData {
+                message: message.into(),
+                severity_level: Some(SeverityLevel::Error),
+                ..MessageData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+
+    fn exception(problem_id: Option<&str>, message: &str) -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                problem_id: problem_id.map(Into::into),
+                exceptions: vec![ExceptionDetails {
+                    type_name: "IoError".into(),
+                    message: message.into(),
+                    ..ExceptionDetails::default()
+                }],
+                ..ExceptionData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+
+    fn event() -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::EventData(EventData {
+                name: "event".into(),
+                ..EventData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+}
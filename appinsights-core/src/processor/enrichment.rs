@@ -0,0 +1,177 @@
+use std::sync::{Mutex, PoisonError};
+
+use crate::processor::{
+    breadcrumbs::{Breadcrumbs, BREADCRUMBS_PROPERTY},
+    Base, Data, Envelope, ProcessingContext, SeverityLevel, TelemetryProcessor,
+};
+
+/// A value of a property attached to enriched telemetry items.
+type PropertyValue = Box<dyn Fn() -> String + Send + Sync>;
+
+/// A processor that attaches additional properties to traces and exceptions of a given severity or
+/// higher. Exceptions without severity are considered errors.
+///
+/// Besides static properties and properties computed at the moment a severe item is tracked, it
+/// can keep a ring buffer of recent trace messages (breadcrumbs) and attach them to severe items
+/// in a `breadcrumbs` property, so traces that lead to an error are available even when they are
+/// not shipped on their own.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::{
+///     processor::{SeverityEnrichment, SeverityLevel},
+///     TelemetryConfig,
+/// };
+///
+/// let enrichment = SeverityEnrichment::new(SeverityLevel::Error)
+///     .property("host", "edge-node-7")
+///     .property_with("free_memory", || "512MB".to_string())
+///     .breadcrumbs(20);
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .processor(enrichment)
+///     .build();
+/// ```
+pub struct SeverityEnrichment {
+    threshold: SeverityLevel,
+    properties: Vec<(String, PropertyValue)>,
+    breadcrumbs: Option<Mutex<Breadcrumbs>>,
+}
+
+impl SeverityEnrichment {
+    /// Creates a new processor that enriches traces and exceptions with a given severity or higher.
+    pub fn new(threshold: SeverityLevel) -> Self {
+        Self {
+            threshold,
+            properties: Vec::new(),
+            breadcrumbs: None,
+        }
+    }
+
+    /// Attaches a property with a fixed value.
+    pub fn property(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let value = value.into();
+        self.property_with(name, move || value.clone())
+    }
+
+    /// Attaches a property with a value computed every time a severe item is tracked, e.g. a
+    /// snapshot of host diagnostics.
+    pub fn property_with<F>(mut self, name: impl Into<String>, value: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.properties.push((name.into(), Box::new(value)));
+        self
+    }
+
+    /// Keeps a given number of the most recent trace messages and attaches them to severe items.
+    pub fn breadcrumbs(mut self, capacity: usize) -> Self {
+        self.breadcrumbs = Some(Mutex::new(Breadcrumbs::new(capacity)));
+        self
+    }
+}
+
+impl TelemetryProcessor for SeverityEnrichment {
+    fn process(&self, envelope: &mut Envelope, _: &ProcessingContext) -> bool {
+        let data = match &mut envelope.data {
+            Some(Base::Data(data)) => data,
+            None => return true,
+        };
+
+        let (severity, message) = match data {
+            Data::MessageData(trace) => (trace.severity_level.clone(), Some(trace.message.clone())),
+            Data::ExceptionData(exception) => (exception.severity_level.clone(), None),
+            _ => return true,
+        };
+        let severity = severity.unwrap_or(SeverityLevel::Error);
+
+        if severity.rank() >= self.threshold.rank() {
+            let properties = data.properties_mut().get_or_insert_with(Default::default);
+            for (name, value) in &self.properties {
+                properties.insert(name.clone(), value());
+            }
+
+            if let Some(breadcrumbs) = &self.breadcrumbs {
+                let breadcrumbs = breadcrumbs.lock().unwrap_or_else(PoisonError::into_inner);
+                if !breadcrumbs.is_empty() {
+                    properties.insert(BREADCRUMBS_PROPERTY.into(), breadcrumbs.to_string());
+                }
+            }
+        }
+
+        if let (Some(breadcrumbs), Some(message)) = (&self.breadcrumbs, message) {
+            let mut breadcrumbs = breadcrumbs.lock().unwrap_or_else(PoisonError::into_inner);
+            breadcrumbs.push(format!("{:?}: {}", severity, message));
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{ExceptionData, MessageData};
+
+    #[test]
+    fn it_enriches_severe_traces_and_exceptions() {
+        let enrichment = SeverityEnrichment::new(SeverityLevel::Error).property("host", "edge");
+
+        let mut warning = trace(SeverityLevel::Warning, "disk is almost full");
+        let mut error = trace(SeverityLevel::Error, "disk is full");
+        let mut exception = Envelope {
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData::default()))),
+            ..Envelope::default()
+        };
+
+        for envelope in [&mut warning, &mut error, &mut exception] {
+            assert!(enrichment.process(envelope, &ProcessingContext::new(None)));
+        }
+
+        assert_eq!(property(&warning, "host"), None);
+        assert_eq!(property(&error, "host"), Some("edge".into()));
+        assert_eq!(property(&exception, "host"), Some("edge".into()));
+    }
+
+    #[test]
+    fn it_attaches_recent_traces_to_severe_items() {
+        let enrichment = SeverityEnrichment::new(SeverityLevel::Error).breadcrumbs(2);
+
+        let mut envelopes = vec![
+            trace(SeverityLevel::Verbose, "connecting"),
+            trace(SeverityLevel::Information, "connected"),
+            trace(SeverityLevel::Warning, "slow response"),
+            trace(SeverityLevel::Critical, "connection lost"),
+        ];
+        for envelope in &mut envelopes {
+            enrichment.process(envelope, &ProcessingContext::new(None));
+        }
+
+        assert_eq!(property(&envelopes[2], BREADCRUMBS_PROPERTY), None);
+        assert_eq!(
+            property(&envelopes[3], BREADCRUMBS_PROPERTY),
+            Some("Information: connected\nWarning: slow response".into())
+        );
+    }
+
+    fn trace(severity: SeverityLevel, message: &str) -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::MessageData(MessageData {
+                message: message.into(),
+                severity_level: Some(severity),
+                ..MessageData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+
+    fn property(envelope: &Envelope, name: &str) -> Option<String> {
+        match &envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => data.properties.as_ref()?.get(name).cloned(),
+            Some(Base::Data(Data::ExceptionData(data))) => data.properties.as_ref()?.get(name).cloned(),
+            _ => None,
+        }
+    }
+}
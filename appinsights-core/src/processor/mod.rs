@@ -0,0 +1,244 @@
+//! Processing of telemetry items before they are queued for submission.
+//!
+//! A [`TelemetryProcessor`] is invoked for every telemetry item tracked by a client. It is able to
+//! modify an [`Envelope`] or drop it altogether. Besides the envelope itself, each processor receives
+//! a [`ProcessingContext`] with metadata that is not a part of submitted data, like the time an item
+//! was tracked or an integration that produced it.
+//!
+//! Processors are registered with `TelemetryConfig::builder` of the `appinsights` crate and
+//! run in the order they were added.
+//!
+//! A processor that drops a share of items to sample them, like [`Sampling`], records the
+//! percentage of items it keeps with [`Envelope::scale_sample_rate`], so the portal scales counts
+//! of the items that survived correctly.
+//!
+//! ```rust
+//! use appinsights::{
+//!     processor::{Base, Data, Envelope, ProcessingContext},
+//!     TelemetryConfig,
+//! };
+//!
+//! /// Drops health check requests tracked by the tower middleware.
+//! fn skip_health_checks(envelope: &mut Envelope, context: &ProcessingContext) -> bool {
+//!     match &envelope.data {
+//!         Some(Base::Data(Data::RequestData(request))) if context.integration() == Some("tower") => {
+//!             request.url.as_deref().map_or(true, |url| !url.ends_with("/health"))
+//!         }
+//!         _ => true,
+//!     }
+//! }
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .processor(skip_health_checks)
+//!     .build();
+//! ```
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use http::Extensions;
+
+pub use crate::contracts::{
+    names, tags, AvailabilityData, Base, Data, DataPoint, DataPointType, Envelope, EnvelopeBuilder, EventData,
+    ExceptionData, ExceptionDetails, InvalidEnvelope, MessageData, MetricData, PageViewData, PageViewPerfData,
+    RemoteDependencyData, RequestData, SessionState, SeverityLevel, StackFrame,
+};
+use crate::{shared::Shared, time};
+
+mod breadcrumbs;
+pub use breadcrumbs::ExceptionBreadcrumbs;
+
+mod deduplication;
+pub use deduplication::Deduplication;
+
+mod enrichment;
+pub use enrichment::SeverityEnrichment;
+
+mod redaction;
+pub use redaction::{QueryRedaction, UrlRedaction};
+
+mod sampling;
+pub use sampling::Sampling;
+
+mod success;
+#[doc(hidden)]
+pub use success::{DependencySuccess, RequestSuccess};
+
+mod truncation;
+pub use truncation::{DependencyField, DependencyTruncation};
+
+/// Modifies or filters out telemetry items before they are queued for submission.
+pub trait TelemetryProcessor: Send + Sync {
+    /// Processes a telemetry item. Returns `false` if the item should be dropped.
+    fn process(&self, envelope: &mut Envelope, context: &ProcessingContext) -> bool;
+}
+
+impl<F> TelemetryProcessor for F
+where
+    F: Fn(&mut Envelope, &ProcessingContext) -> bool + Send + Sync,
+{
+    fn process(&self, envelope: &mut Envelope, context: &ProcessingContext) -> bool {
+        self(envelope, context)
+    }
+}
+
+/// Metadata of a telemetry item being processed.
+///
+/// Integrations attach additional typed values to [`extensions`](ProcessingContext::extensions),
+/// for instance the `tower` and `reqwest-middleware` integrations attach a
+/// [`TraceParent`](crate::telemetry::TraceParent) of the tracked request.
+#[derive(Debug)]
+pub struct ProcessingContext {
+    enqueued_at: DateTime<Utc>,
+    integration: Option<&'static str>,
+    operation_id: Option<String>,
+    extensions: Extensions,
+}
+
+impl ProcessingContext {
+    /// Creates a new context for a telemetry item tracked right now by a given integration.
+    #[doc(hidden)]
+    pub fn new(integration: Option<&'static str>) -> Self {
+        Self {
+            enqueued_at: time::now(),
+            integration,
+            operation_id: None,
+            extensions: Extensions::new(),
+        }
+    }
+
+    /// Returns the time when a telemetry item was tracked by a client.
+    pub fn enqueued_at(&self) -> DateTime<Utc> {
+        self.enqueued_at
+    }
+
+    /// Returns a name of an integration that produced a telemetry item, e.g. `tower` or
+    /// `reqwest-middleware`. Returns `None` for items tracked by an application directly.
+    pub fn integration(&self) -> Option<&str> {
+        self.integration
+    }
+
+    /// Returns an operation id of a telemetry item if any.
+    pub fn operation_id(&self) -> Option<&str> {
+        self.operation_id.as_deref()
+    }
+
+    /// Returns a score within `[0, 100]` range derived from an operation id. All telemetry items of
+    /// the same operation share the same score, so sampling decisions based on it keep related items
+    /// together. Returns `None` when a telemetry item has no operation id.
+    pub fn sampling_score(&self) -> Option<f64> {
+        self.operation_id.as_deref().map(sampling_score)
+    }
+
+    /// Returns typed values attached to a telemetry item by an integration.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Returns mutable reference to typed values attached to a telemetry item.
+    #[doc(hidden)]
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+}
+
+/// A list of processors to apply to telemetry items. It makes processors comparable and printable
+/// as part of a configuration.
+#[derive(Clone, Default, PartialEq)]
+#[doc(hidden)]
+pub struct Processors(Vec<Shared<dyn TelemetryProcessor>>);
+
+impl Processors {
+    /// Adds a processor to the end of the list.
+    pub fn push(&mut self, processor: Arc<dyn TelemetryProcessor>) {
+        self.0.push(processor.into());
+    }
+
+    /// Runs all processors for a telemetry item. Returns `false` if any of them decided to drop it.
+    pub fn process(&self, envelope: &mut Envelope, mut context: ProcessingContext) -> bool {
+        if self.0.is_empty() {
+            return true;
+        }
+
+        context.operation_id = envelope
+            .tags
+            .as_ref()
+            .and_then(|tags| tags.get(tags::OPERATION_ID))
+            .cloned();
+
+        self.0.iter().all(|processor| processor.process(envelope, &context))
+    }
+}
+
+impl Debug for Processors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Processors({})", self.0.len())
+    }
+}
+
+/// Calculates a sampling score of an operation id the same way other Application Insights SDKs do.
+fn sampling_score(operation_id: &str) -> f64 {
+    if operation_id.is_empty() {
+        return 0.0;
+    }
+
+    let mut input = operation_id.to_string();
+    while input.len() < 8 {
+        input.push_str(operation_id);
+    }
+
+    let hash = input.chars().fold(5381_i32, |hash, c| {
+        (hash << 5).wrapping_add(hash).wrapping_add(c as i32)
+    });
+    let hash = if hash == i32::MIN { i32::MAX } else { hash.abs() };
+
+    f64::from(hash) / f64::from(i32::MAX) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::StringMap;
+
+    #[test]
+    fn it_passes_metadata_to_processors() {
+        let mut processors = Processors::default();
+        processors.push(Arc::new(|envelope: &mut Envelope, context: &ProcessingContext| {
+            envelope.name = format!("{:?} {:?}", context.integration(), context.operation_id());
+            context.extensions().get::<u32>() == Some(&42)
+        }));
+
+        let mut envelope = Envelope {
+            tags: Some(StringMap::from([(tags::OPERATION_ID.into(), "operation".into())])),
+            ..Envelope::default()
+        };
+        let mut context = ProcessingContext::new(Some("tower"));
+        context.extensions_mut().insert(42_u32);
+
+        assert!(processors.process(&mut envelope, context));
+        assert_eq!(envelope.name, r#"Some("tower") Some("operation")"#);
+    }
+
+    #[test]
+    fn it_stops_processing_when_item_dropped() {
+        let mut processors = Processors::default();
+        processors.push(Arc::new(|_: &mut Envelope, _: &ProcessingContext| false));
+        processors.push(Arc::new(|_: &mut Envelope, _: &ProcessingContext| -> bool {
+            panic!("item should be dropped already")
+        }));
+
+        assert!(!processors.process(&mut Envelope::default(), ProcessingContext::new(None)));
+    }
+
+    #[test]
+    fn it_calculates_the_same_sampling_score_for_the_same_operation() {
+        let score = sampling_score("0af7651916cd43dd8448eb211c80319c");
+
+        assert!((0.0..=100.0).contains(&score));
+        assert_eq!(sampling_score("0af7651916cd43dd8448eb211c80319c"), score);
+        assert_ne!(sampling_score("b7ad6b7169203331"), score);
+    }
+}
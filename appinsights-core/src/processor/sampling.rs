@@ -0,0 +1,125 @@
+use crate::{
+    processor::{sampling_score, Base, Data, Envelope, ProcessingContext, TelemetryProcessor},
+    uuid,
+};
+
+/// A processor that keeps a fixed percentage of telemetry items and drops the rest.
+///
+/// Items of the same operation share a sampling score, so they are either all kept or all dropped
+/// and a sampled request is still shown along with its dependencies and exceptions. Items without
+/// an operation id are sampled at random. Metrics are never sampled, because they are aggregated
+/// already.
+///
+/// Items that are kept carry a sample rate scaled by the percentage, e.g. 25 for one of every 4
+/// items, so the portal estimates the original number of items correctly. A rate an item carries
+/// already, e.g. one of a [`TelemetryContext`](crate::TelemetryContext) or of another sampling
+/// processor, is scaled further.
+///
+/// # Panics
+///
+/// Panics if a percentage is not within `(0, 100]` range.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::{processor::Sampling, TelemetryConfig};
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .processor(Sampling::new(25.0))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Sampling {
+    percentage: f64,
+}
+
+impl Sampling {
+    /// Creates a new processor that keeps a given percentage of telemetry items.
+    pub fn new(percentage: f64) -> Self {
+        if !(percentage > 0.0 && percentage <= 100.0) {
+            panic!("Sampling percentage should be within (0, 100] range: {}", percentage);
+        }
+        Self { percentage }
+    }
+
+    /// Returns a percentage of telemetry items to keep.
+    pub fn percentage(&self) -> f64 {
+        self.percentage
+    }
+}
+
+impl TelemetryProcessor for Sampling {
+    fn process(&self, envelope: &mut Envelope, context: &ProcessingContext) -> bool {
+        if matches!(envelope.data, Some(Base::Data(Data::MetricData(_)))) {
+            return true;
+        }
+
+        let score = context
+            .sampling_score()
+            .unwrap_or_else(|| sampling_score(&uuid::new().as_simple().to_string()));
+        if score >= self.percentage {
+            return false;
+        }
+
+        envelope.scale_sample_rate(self.percentage);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        contracts::StringMap,
+        processor::{tags, EventData, MetricData, Processors},
+    };
+
+    #[test]
+    fn it_keeps_items_of_sampled_operations_and_scales_sample_rate() {
+        let mut processors = Processors::default();
+        processors.push(std::sync::Arc::new(Sampling::new(50.0)));
+
+        let operations: Vec<_> = (0..200).map(|id| format!("operation-{}", id)).collect();
+        let kept: Vec<_> = operations
+            .iter()
+            .filter_map(|operation| {
+                let mut envelope = envelope(Data::EventData(EventData::default()), operation);
+                envelope.sample_rate = Some(50.0);
+                let kept = processors.process(&mut envelope, ProcessingContext::new(None));
+                kept.then_some((operation, envelope.sample_rate))
+            })
+            .collect();
+
+        assert!(kept.len() > 50 && kept.len() < 150, "kept {} items", kept.len());
+        assert!(kept.iter().all(|(_, rate)| *rate == Some(25.0)));
+
+        // items of the same operation share a decision
+        let (operation, _) = kept[0];
+        let mut envelope = envelope(Data::EventData(EventData::default()), operation);
+        assert!(processors.process(&mut envelope, ProcessingContext::new(None)));
+    }
+
+    #[test]
+    fn it_keeps_metrics() {
+        let sampling = Sampling::new(0.001);
+        let mut envelope = envelope(Data::MetricData(MetricData::default()), "operation");
+
+        assert!(sampling.process(&mut envelope, &ProcessingContext::new(None)));
+        assert_eq!(envelope.sample_rate, Some(100.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_rejects_percentage_out_of_range() {
+        Sampling::new(0.0);
+    }
+
+    fn envelope(data: Data, operation_id: &str) -> Envelope {
+        Envelope {
+            tags: Some(StringMap::from([(tags::OPERATION_ID.into(), operation_id.into())])),
+            data: Some(Base::Data(data)),
+            ..Envelope::default()
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+};
+
+use http::Uri;
+
+use crate::{
+    processor::{Base, Data, Envelope},
+    shared::Shared,
+};
+
+type SuccessFn = dyn Fn(&str) -> Option<bool> + Send + Sync;
+
+/// Decides whether calls to dependencies of a given type succeeded based on their result codes,
+/// e.g. Redis `MOVED` replies or HTTP `404` of existence checks. It makes criteria comparable and
+/// printable as part of a configuration.
+#[derive(Clone, Default, PartialEq)]
+pub struct DependencySuccess(Vec<(String, Shared<SuccessFn>)>);
+
+impl DependencySuccess {
+    /// Adds criteria for dependencies of a given type. Criteria added later take precedence unless
+    /// they do not know a result code.
+    pub fn push(&mut self, dependency_type: String, success: Arc<SuccessFn>) {
+        self.0.push((dependency_type, success.into()));
+    }
+
+    /// Overrides the success flag of a dependency telemetry item with a result code when criteria
+    /// for its type know the code.
+    pub fn apply(&self, envelope: &mut Envelope) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        let dependency = match &mut envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(dependency))) => dependency,
+            _ => return,
+        };
+
+        let (dependency_type, result_code) = match (&dependency.type_, &dependency.result_code) {
+            (Some(dependency_type), Some(result_code)) => (dependency_type, result_code),
+            _ => return,
+        };
+
+        let success = self
+            .0
+            .iter()
+            .rev()
+            .filter(|(criteria_type, _)| criteria_type.eq_ignore_ascii_case(dependency_type))
+            .find_map(|(_, success)| success(result_code));
+        if success.is_some() {
+            dependency.success = success;
+        }
+    }
+}
+
+impl Debug for DependencySuccess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let types: Vec<_> = self.0.iter().map(|(dependency_type, _)| dependency_type).collect();
+        write!(f, "DependencySuccess({:?})", types)
+    }
+}
+
+/// Decides whether requests to given routes succeeded based on their response codes, e.g. `404`
+/// of lookups or `401` of login attempts that are expected to fail. It makes criteria comparable
+/// and printable as part of a configuration.
+#[derive(Clone, Default, PartialEq)]
+pub struct RequestSuccess(Arc<Vec<(String, Shared<SuccessFn>)>>);
+
+impl RequestSuccess {
+    /// Adds criteria for requests to a given route, i.e. to a URL path equal to the route or
+    /// nested under it. Criteria added later take precedence unless they do not know a response
+    /// code.
+    pub fn push(&mut self, route: String, success: Arc<SuccessFn>) {
+        Arc::make_mut(&mut self.0).push((route, success.into()));
+    }
+
+    /// Decides whether a request to a given URL that returned a given response code succeeded.
+    /// Returns `None` when no criteria know the code.
+    pub fn decide(&self, uri: &Uri, response_code: &str) -> Option<bool> {
+        self.0
+            .iter()
+            .rev()
+            .filter(|(route, _)| matches_route(uri.path(), route))
+            .find_map(|(_, success)| success(response_code))
+    }
+}
+
+impl Debug for RequestSuccess {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let routes: Vec<_> = self.0.iter().map(|(route, _)| route).collect();
+        write!(f, "RequestSuccess({:?})", routes)
+    }
+}
+
+/// Returns `true` if a path is equal to a route or nested under it.
+fn matches_route(path: &str, route: &str) -> bool {
+    let route = route.trim_end_matches('/');
+    match path.strip_prefix(route) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+    use crate::processor::RemoteDependencyData;
+
+    #[test_case("https://example.com/users/42", "404", Some(true) ; "nested route")]
+    #[test_case("https://example.com/users", "404", Some(true) ; "exact route")]
+    #[test_case("https://example.com/usersettings", "404", None ; "route prefix only")]
+    #[test_case("https://example.com/login", "401", Some(true) ; "route of later criteria")]
+    #[test_case("https://example.com/login", "403", Some(false) ; "code of earlier criteria")]
+    #[test_case("https://example.com/orders", "500", None ; "unknown code")]
+    fn it_decides_request_success_by_route_and_response_code(uri: &str, code: &str, expected: Option<bool>) {
+        let mut criteria = RequestSuccess::default();
+        criteria.push("/".into(), Arc::new(|code| (code == "403").then_some(false)));
+        criteria.push("/users/".into(), Arc::new(|code| (code == "404").then_some(true)));
+        criteria.push("/login".into(), Arc::new(|code| (code == "401").then_some(true)));
+
+        assert_eq!(criteria.decide(&uri.parse().unwrap(), code), expected);
+    }
+
+    #[test_case("HTTP", Some("404"), Some(true) ; "configured code")]
+    #[test_case("http", Some("204"), Some(true) ; "code of earlier criteria")]
+    #[test_case("HTTP", Some("500"), Some(false) ; "unknown code")]
+    #[test_case("Redis", Some("MOVED"), Some(true) ; "other type")]
+    #[test_case("SQL", Some("404"), Some(false) ; "no criteria")]
+    #[test_case("HTTP", None, Some(false) ; "no result code")]
+    fn it_decides_success_by_result_code(dependency_type: &str, result_code: Option<&str>, expected: Option<bool>) {
+        let mut criteria = DependencySuccess::default();
+        criteria.push("HTTP".into(), Arc::new(|code| Some(code.starts_with('2'))));
+        criteria.push("HTTP".into(), Arc::new(|code| (code == "404").then_some(true)));
+        criteria.push("Redis".into(), Arc::new(|code| Some(code == "OK" || code == "MOVED")));
+
+        let mut envelope = Envelope {
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                type_: Some(dependency_type.into()),
+                result_code: result_code.map(Into::into),
+                success: Some(false),
+                ..RemoteDependencyData::default()
+            }))),
+            ..Envelope::default()
+        };
+        criteria.apply(&mut envelope);
+
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(dependency))) => assert_eq!(dependency.success, expected),
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+    }
+}
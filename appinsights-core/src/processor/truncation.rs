@@ -0,0 +1,206 @@
+use crate::processor::{Base, Data, Envelope, ProcessingContext, RemoteDependencyData, TelemetryProcessor};
+
+/// A field of a remote dependency telemetry item that can be truncated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyField {
+    /// A name of the command that initiated a dependency call.
+    Name,
+
+    /// A command initiated by a dependency call, e.g. SQL statement or HTTP URL.
+    Data,
+
+    /// A target site of a dependency call.
+    Target,
+}
+
+impl DependencyField {
+    /// Returns a name of the property that contains a hash of the full field value.
+    fn hash_property(self) -> &'static str {
+        match self {
+            DependencyField::Name => "name_hash",
+            DependencyField::Data => "data_hash",
+            DependencyField::Target => "target_hash",
+        }
+    }
+
+    /// Returns a mutable reference to the field value of a dependency, if any.
+    fn value_mut(self, dependency: &mut RemoteDependencyData) -> Option<&mut String> {
+        match self {
+            DependencyField::Name => Some(&mut dependency.name),
+            DependencyField::Data => dependency.data.as_mut(),
+            DependencyField::Target => dependency.target.as_mut(),
+        }
+    }
+}
+
+/// A processor that truncates large fields of remote dependency telemetry items, like SQL
+/// statements or URLs with long query strings, to a configured number of characters.
+///
+/// A hash of the full value of every configured field is stored in a companion property named
+/// after the field, e.g. `data_hash`. It is added whether a value was truncated or not, so items
+/// are still grouped by the full statement after truncation. The hash is a 64-bit FNV-1a hash of
+/// the UTF-8 bytes formatted as 16 lowercase hex digits.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::{
+///     processor::{DependencyField, DependencyTruncation},
+///     TelemetryConfig,
+/// };
+///
+/// let truncation = DependencyTruncation::new()
+///     .field(DependencyField::Data, 1024)
+///     .field(DependencyField::Name, 256);
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .processor(truncation)
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct DependencyTruncation {
+    fields: Vec<(DependencyField, usize)>,
+}
+
+impl DependencyTruncation {
+    /// Creates a new processor that leaves all fields intact.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Truncates a given field to a maximum number of characters. A truncated value ends with `...`.
+    pub fn field(mut self, field: DependencyField, max_len: usize) -> Self {
+        self.fields.retain(|(existing, _)| *existing != field);
+        self.fields.push((field, max_len));
+        self
+    }
+}
+
+impl TelemetryProcessor for DependencyTruncation {
+    fn process(&self, envelope: &mut Envelope, _: &ProcessingContext) -> bool {
+        let dependency = match &mut envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(dependency))) => dependency,
+            _ => return true,
+        };
+
+        for (field, max_len) in &self.fields {
+            let value = match field.value_mut(dependency) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let hash = hash(value);
+            truncate(value, *max_len);
+
+            dependency
+                .properties
+                .get_or_insert_with(Default::default)
+                .insert(field.hash_property().into(), hash);
+        }
+
+        true
+    }
+}
+
+/// Truncates a text to a given number of characters. It ends with `...` to indicate that the text
+/// was truncated.
+fn truncate(text: &mut String, max_len: usize) {
+    if let Some((end, _)) = text.char_indices().nth(max_len) {
+        let keep = max_len.saturating_sub(3);
+        let end = text.char_indices().nth(keep).map_or(end, |(index, _)| index);
+        text.truncate(end);
+        text.push_str(&"..."[..max_len.min(3)]);
+    }
+}
+
+/// Calculates a 64-bit FNV-1a hash of a text and formats it as hex digits.
+pub(super) fn hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_truncates_configured_fields_and_keeps_hash_of_full_value() {
+        let truncation = DependencyTruncation::new().field(DependencyField::Data, 20);
+        let statement = format!("SELECT * FROM users WHERE id IN ({})", vec!["1"; 100].join(","));
+
+        let mut envelope = dependency("users", Some(&statement));
+        assert!(truncation.process(&mut envelope, &ProcessingContext::new(None)));
+
+        let data = data(&envelope);
+        assert_eq!(data.name, "users");
+        assert_eq!(data.data, Some("SELECT * FROM use...".into()));
+        assert_eq!(property(data, "data_hash"), Some(hash(&statement).as_str()));
+        assert_eq!(property(data, "name_hash"), None);
+    }
+
+    #[test]
+    fn it_keeps_short_values_intact() {
+        let truncation = DependencyTruncation::new().field(DependencyField::Data, 20);
+
+        let mut envelope = dependency("users", Some("SELECT 1"));
+        truncation.process(&mut envelope, &ProcessingContext::new(None));
+
+        let data = data(&envelope);
+        assert_eq!(data.data, Some("SELECT 1".into()));
+        assert_eq!(property(data, "data_hash"), Some(hash("SELECT 1").as_str()));
+    }
+
+    #[test]
+    fn it_skips_missing_fields() {
+        let truncation = DependencyTruncation::new().field(DependencyField::Data, 20);
+
+        let mut envelope = dependency("users", None);
+        truncation.process(&mut envelope, &ProcessingContext::new(None));
+
+        assert_eq!(data(&envelope).properties, None);
+    }
+
+    #[test_case("abcdef", 6, "abcdef" ; "fits")]
+    #[test_case("abcdefg", 6, "abc..." ; "ascii")]
+    #[test_case("ŝŝŝŝŝŝŝ", 5, "ŝŝ..." ; "multibyte")]
+    #[test_case("abcdef", 2, ".." ; "shorter than ellipsis")]
+    fn it_truncates_text(text: &str, max_len: usize, expected: &str) {
+        let mut text = text.to_string();
+        truncate(&mut text, max_len);
+
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn it_calculates_fnv_hash() {
+        assert_eq!(hash(""), "cbf29ce484222325");
+        assert_eq!(hash("a"), "af63dc4c8601ec8c");
+    }
+
+    fn dependency(name: &str, statement: Option<&str>) -> Envelope {
+        Envelope {
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                name: name.into(),
+                data: statement.map(Into::into),
+                ..RemoteDependencyData::default()
+            }))),
+            ..Envelope::default()
+        }
+    }
+
+    fn data(envelope: &Envelope) -> &RemoteDependencyData {
+        match &envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => data,
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+    }
+
+    fn property<'a>(data: &'a RemoteDependencyData, name: &str) -> Option<&'a str> {
+        data.properties.as_ref()?.get(name).map(String::as_str)
+    }
+}
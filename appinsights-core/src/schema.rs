@@ -0,0 +1,56 @@
+//! JSON schema of telemetry items.
+//!
+//! Every telemetry item is sent to the server as an [`Envelope`](crate::processor::Envelope). The
+//! schema describes an envelope along with every kind of data it carries, so payloads produced by
+//! different versions of the SDK can be validated against it, e.g. by a gateway or a local
+//! collector in front of the server.
+//!
+//! ```rust
+//! let schema = appinsights::schema::envelope_schema();
+//! let json = serde_json::to_string_pretty(&schema).unwrap();
+//!
+//! assert!(json.contains("\"Envelope\""));
+//! ```
+use schemars::schema::RootSchema;
+
+use crate::contracts::Envelope;
+
+/// Returns the JSON schema of an envelope with definitions of all data it may carry.
+pub fn envelope_schema() -> RootSchema {
+    schemars::schema_for!(Envelope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_defines_all_kinds_of_data() {
+        let schema = envelope_schema();
+
+        let definitions: Vec<_> = schema.definitions.keys().map(String::as_str).collect();
+        for name in &[
+            "AvailabilityData",
+            "EventData",
+            "ExceptionData",
+            "MessageData",
+            "MetricData",
+            "PageViewData",
+            "PageViewPerfData",
+            "RemoteDependencyData",
+            "RequestData",
+        ] {
+            assert!(definitions.contains(name), "{} is not defined", name);
+        }
+    }
+
+    #[test]
+    fn it_validates_serialized_envelope_fields() {
+        let schema = serde_json::to_value(envelope_schema()).unwrap();
+
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("iKey"));
+        assert!(properties.contains_key("sampleRate"));
+        assert_eq!(schema["required"], serde_json::json!(["name", "time"]));
+    }
+}
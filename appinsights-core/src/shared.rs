@@ -0,0 +1,86 @@
+//! A shared value, e.g. a trait object an application configures, that is comparable and printable
+//! as part of a configuration.
+use std::{
+    any,
+    fmt::{Debug, Formatter},
+    ops::Deref,
+    sync::Arc,
+};
+
+/// A reference-counted value that is equal only to clones of itself and prints its type.
+///
+/// Configurations hold custom implementations of traits and closures that neither compare nor
+/// print, so they keep them as `Shared` values in order to derive `PartialEq` and `Debug`.
+pub struct Shared<T: ?Sized>(Arc<T>);
+
+impl<T> Shared<T> {
+    /// Creates a new shared value.
+    pub fn new(value: T) -> Self {
+        Self(Arc::new(value))
+    }
+}
+
+impl<T: ?Sized> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: ?Sized> From<Arc<T>> for Shared<T> {
+    fn from(value: Arc<T>) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: ?Sized> From<Shared<T>> for Arc<T> {
+    fn from(value: Shared<T>) -> Self {
+        value.0
+    }
+}
+
+impl<T: ?Sized> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> PartialEq for Shared<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T: ?Sized> Debug for Shared<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Shared<{}>", any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    trait Named {}
+
+    struct Name;
+
+    impl Named for Name {}
+
+    #[test]
+    fn it_equals_only_clones_of_itself() {
+        let shared: Shared<dyn Named> = Shared::from(Arc::new(Name) as Arc<dyn Named>);
+        let other: Shared<dyn Named> = Shared::from(Arc::new(Name) as Arc<dyn Named>);
+
+        assert_eq!(shared, shared.clone());
+        assert_ne!(shared, other);
+    }
+
+    #[test]
+    fn it_prints_type() {
+        let shared = Shared::new(42_u8);
+
+        assert_eq!(format!("{:?}", shared), "Shared<u8>");
+    }
+}
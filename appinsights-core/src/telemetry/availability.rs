@@ -1,11 +1,11 @@
 use std::time::Duration as StdDuration;
 
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 
 use crate::{
     context::TelemetryContext,
-    contracts::{AvailabilityData, Base, Data, Envelope},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    contracts::{names, AvailabilityData, Base, Data, Envelope},
+    telemetry::{envelope, ContextTags, Measurements, Properties, Telemetry},
     time::{self, Duration},
     uuid::Uuid,
 };
@@ -94,6 +94,21 @@ impl AvailabilityTelemetry {
     pub fn measurements_mut(&mut self) -> &mut Measurements {
         &mut self.measurements
     }
+
+    /// Returns mutable reference to the timestamp.
+    pub fn timestamp_mut(&mut self) -> &mut DateTime<Utc> {
+        &mut self.timestamp
+    }
+
+    /// Sets a name of the location where the test was run.
+    pub fn set_run_location(&mut self, run_location: impl Into<String>) {
+        self.run_location = Some(run_location.into());
+    }
+
+    /// Sets a diagnostic message for the result.
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some(message.into());
+    }
 }
 
 impl Telemetry for AvailabilityTelemetry {
@@ -126,10 +141,9 @@ impl Telemetry for AvailabilityTelemetry {
 impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, AvailabilityTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.Availability".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::AvailabilityData(AvailabilityData {
                 id: telemetry
                     .id
@@ -140,11 +154,15 @@ impl From<(TelemetryContext, AvailabilityTelemetry)> for Envelope {
                 success: telemetry.success,
                 run_location: telemetry.run_location,
                 message: telemetry.message,
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
                 measurements: Some(telemetry.measurements.into()),
                 ..AvailabilityData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::AVAILABILITY, telemetry.timestamp)
         }
     }
 }
@@ -156,6 +174,7 @@ mod tests {
     use chrono::TimeZone;
 
     use super::*;
+    use crate::contracts::StringMap;
 
     #[test]
     fn it_overrides_properties_from_context() {
@@ -177,14 +196,14 @@ mod tests {
             name: "Microsoft.ApplicationInsights.Availability".into(),
             time: "2019-01-02T03:04:05.800Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::AvailabilityData(AvailabilityData {
                 name: "GET https://example.com/main.html".into(),
                 duration: "0.00:00:02.0000000".into(),
                 success: true,
                 message: None,
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -221,7 +240,7 @@ mod tests {
             time: "2019-01-02T03:04:05.700Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
@@ -231,7 +250,7 @@ mod tests {
                 duration: "0.00:00:02.0000000".into(),
                 success: true,
                 message: None,
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..AvailabilityData::default()
             }))),
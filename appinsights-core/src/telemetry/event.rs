@@ -1,9 +1,9 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 
 use crate::{
     context::TelemetryContext,
-    contracts::{Base, Data, Envelope, EventData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    contracts::{names, Base, Data, Envelope, EventData},
+    telemetry::{envelope, ContextTags, Measurements, Properties, Telemetry},
     time,
 };
 
@@ -44,6 +44,62 @@ pub struct EventTelemetry {
     measurements: Measurements,
 }
 
+/// A builder of [EventTelemetry] items.
+// This struct is a Option'fied version of EventTelemetry
+#[derive(Debug, Default)]
+pub struct EventTelemetryBuilder {
+    name: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    properties: Option<Properties>,
+    tags: Option<ContextTags>,
+    measurements: Option<Measurements>,
+}
+
+impl EventTelemetryBuilder {
+    /// Sets the name of the event.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the time when this telemetry was measured.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets custom properties to submit with the telemetry item.
+    pub fn with_properties(mut self, properties: Properties) -> Self {
+        self.properties = Some(properties);
+        self
+    }
+
+    /// Sets context tags that override values found on client telemetry context.
+    pub fn with_tags(mut self, tags: ContextTags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Sets custom measurements to submit with the telemetry item.
+    pub fn with_measurements(mut self, measurements: Measurements) -> Self {
+        self.measurements = Some(measurements);
+        self
+    }
+
+    /// Builds a [EventTelemetry] item.
+    ///
+    /// If no name is provided, an empty string is passed.
+    pub fn build(self) -> EventTelemetry {
+        EventTelemetry {
+            name: self.name.unwrap_or_default(),
+            timestamp: self.timestamp.unwrap_or_else(time::now),
+            properties: self.properties.unwrap_or_default(),
+            tags: self.tags.unwrap_or_default(),
+            measurements: self.measurements.unwrap_or_default(),
+        }
+    }
+}
+
 impl EventTelemetry {
     /// Creates an event telemetry item with specified name.
     pub fn new(name: impl Into<String>) -> Self {
@@ -56,6 +112,11 @@ impl EventTelemetry {
         }
     }
 
+    /// Create a new [EventTelemetryBuilder], used to construct [EventTelemetry].
+    pub fn builder() -> EventTelemetryBuilder {
+        EventTelemetryBuilder::default()
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -97,17 +158,20 @@ impl Telemetry for EventTelemetry {
 impl From<(TelemetryContext, EventTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, EventTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.Event".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::EventData(EventData {
                 name: telemetry.name,
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
                 measurements: Some(telemetry.measurements.into()),
                 ..EventData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::EVENT, telemetry.timestamp)
         }
     }
 }
@@ -119,7 +183,7 @@ mod tests {
     use chrono::TimeZone;
 
     use super::*;
-    use crate::time;
+    use crate::{contracts::StringMap, time};
 
     #[test]
     fn it_overrides_properties_from_context() {
@@ -140,11 +204,11 @@ mod tests {
             name: "Microsoft.ApplicationInsights.Event".into(),
             time: "2019-01-02T03:04:05.600Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::EventData(EventData {
                 name: "test".into(),
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -181,14 +245,14 @@ mod tests {
             time: "2019-01-02T03:04:05.700Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
             }),
             data: Some(Base::Data(Data::EventData(EventData {
                 name: "test".into(),
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..EventData::default()
             }))),
@@ -197,4 +261,48 @@ mod tests {
 
         assert_eq!(envelop, expected)
     }
+
+    #[test]
+    fn it_overrides_i_key_from_context() {
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+
+        let mut telemetry = EventTelemetry::new("test");
+        telemetry.tags_mut().set_i_key("tenant");
+
+        let envelop = Envelope::from((context, telemetry));
+
+        assert_eq!(envelop.i_key, Some("tenant".into()));
+        assert_eq!(envelop.tags, Some(StringMap::default()));
+    }
+
+    #[test]
+    fn it_builds_event_telemetry() {
+        let timestamp = Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800);
+
+        let mut properties = Properties::default();
+        properties.insert("component".into(), "data_processor".into());
+
+        let mut tags = ContextTags::default();
+        tags.insert("os_version".into(), "linux x86_64".into());
+
+        let mut measurements = Measurements::default();
+        measurements.insert("records_count".into(), 115.0);
+
+        let telemetry = EventTelemetry::builder()
+            .with_name("test")
+            .with_timestamp(timestamp)
+            .with_properties(properties)
+            .with_tags(tags)
+            .with_measurements(measurements)
+            .build();
+
+        assert_eq!(telemetry.name, "test");
+        assert_eq!(telemetry.timestamp(), timestamp);
+        assert_eq!(
+            telemetry.properties().get("component"),
+            Some(&"data_processor".to_string())
+        );
+        assert_eq!(telemetry.tags().get("os_version"), Some(&"linux x86_64".to_string()));
+        assert_eq!(telemetry.measurements().get("records_count"), Some(&115.0));
+    }
 }
@@ -0,0 +1,443 @@
+// TODO implement exception collection telemetry item
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    contracts::{names, Base, Data, Envelope, ExceptionData, ExceptionDetails},
+    telemetry::{envelope, ContextTags, Measurements, Properties, SeverityLevel, Telemetry},
+    time, ContextConfig, TelemetryContext,
+};
+
+/// Represents errors that occur during application execution.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{ExceptionTelemetry, SeverityLevel, Telemetry};
+///
+/// // create a telemetry item with an exception caused by another one
+/// let mut telemetry = ExceptionTelemetry::new(
+///     Some(SeverityLevel::Error),
+///     Some(format!{"FileNotFound: {}:{}:{}", file!(), line!(), column!()})
+/// )
+/// .with_message("Failed to load configuration", "ConfigError", None::<String>)
+/// .with_message("File does not exist", "FileNotFound", None::<String>);
+///
+/// // attach custom properties and context tags
+/// telemetry.properties_mut().insert("component".to_string(), "data_processor".to_string());
+/// telemetry.tags_mut().insert("os_version".to_string(), "linux x86_64".to_string());
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct ExceptionTelemetry {
+    /// Exception chain - list of inner exceptions.
+    /// TODO: should this be a Vec? Currently only one exception, not exceptions.
+    exceptions: Vec<ExceptionDetails>,
+
+    /// Severity level. Mostly used to indicate exception severity level
+    /// when it is reported by logging library.
+    severity_level: Option<SeverityLevel>,
+
+    /// Identifier of where the exception was thrown in code.
+    /// Used for exceptions grouping. Typically a combination of exception type
+    /// and a function from the call stack.
+    problem_id: Option<String>,
+
+    /// Collection of custom properties.
+    properties: Properties,
+
+    /// Collection of custom measurements.
+    measurements: Measurements,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+}
+
+impl ExceptionTelemetry {
+    /// Create an empty exception telemetry item.
+    pub fn new(severity_level: Option<SeverityLevel>, problem_id: Option<impl Into<String>>) -> Self {
+        Self {
+            exceptions: vec![],
+            severity_level,
+            problem_id: problem_id.map(|id| id.into()),
+            timestamp: time::now(),
+            properties: Properties::default(),
+            measurements: Measurements::default(),
+            tags: ContextTags::default(),
+        }
+    }
+
+    /// Add a new exception with the given parameters to the exception chain
+    /// of this exception telemetry item.
+    ///
+    /// The first exception added is the outermost one. Every next exception
+    /// is the cause of the one added before it.
+    pub fn with_message(
+        mut self,
+        message: impl Into<String>,
+        type_name: impl Into<String>,
+        stack_trace: Option<impl Into<String>>,
+    ) -> Self {
+        chain(
+            &mut self.exceptions,
+            ExceptionDetails {
+                message: message.into(),
+                type_name: type_name.into(),
+                stack: stack_trace.map(|s| s.into()),
+                ..Default::default()
+            },
+        );
+        self
+    }
+
+    /// Add an exception to the exception chain of this exception telemetry
+    /// item.
+    ///
+    /// The first exception added is the outermost one. Every next exception
+    /// is the cause of the one added before it. Identifiers of the exception
+    /// are overwritten to link it to the chain.
+    pub fn with_exception(mut self, exception: ExceptionDetails) -> Self {
+        chain(&mut self.exceptions, exception);
+        self
+    }
+
+    /// Returns mutable reference to the timestamp.
+    pub fn timestamp_mut(&mut self) -> &mut DateTime<Utc> {
+        &mut self.timestamp
+    }
+
+    /// Create a new [ExceptionTelemetryBuilder], used to construct an [ExceptionTelemetry].
+    pub fn builder() -> ExceptionTelemetryBuilder {
+        ExceptionTelemetryBuilder::default()
+    }
+}
+
+impl Telemetry for ExceptionTelemetry {
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+}
+
+impl From<(TelemetryContext, ExceptionTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, ExceptionTelemetry)) -> Self {
+        Self {
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
+            data: Some(Base::Data(Data::ExceptionData(ExceptionData {
+                exceptions: context.exception_limits.apply(telemetry.exceptions),
+                problem_id: telemetry.problem_id,
+                severity_level: telemetry.severity_level.map(|s| s.into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
+                measurements: Some(telemetry.measurements.into()),
+                ..Default::default()
+            }))),
+            ..envelope(names::EXCEPTION, telemetry.timestamp)
+        }
+    }
+}
+
+/// Appends an exception to a chain as the cause of the last exception in it. Exceptions are
+/// identified by their position in the chain, so the portal is able to render the nesting. An
+/// exception without any stack trace is marked as not having a full stack.
+fn chain(exceptions: &mut Vec<ExceptionDetails>, mut exception: ExceptionDetails) {
+    exception.id = Some(exceptions.len() as i32);
+    exception.outer_id = exceptions.last().and_then(|outer| outer.id);
+
+    let has_stack = exception.stack.is_some() || !exception.parsed_stack.is_empty();
+    exception.has_full_stack = Some(has_stack && exception.has_full_stack != Some(false));
+
+    exceptions.push(exception);
+}
+
+/// Limits the size of exception chains and stack traces submitted with exception telemetry items.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ExceptionLimits {
+    /// Maximum number of chained exceptions per telemetry item.
+    pub max_chain_depth: usize,
+
+    /// Maximum number of stack frames per exception.
+    pub max_stack_frames: usize,
+}
+
+impl ExceptionLimits {
+    /// Creates exception limits configured with specified configuration.
+    pub fn from_config(config: &impl ContextConfig) -> Self {
+        Self {
+            max_chain_depth: config.max_exception_chain_depth(),
+            max_stack_frames: config.max_exception_stack_frames(),
+        }
+    }
+
+    /// Truncates an exception chain and stack traces of each exception in it. Exceptions with a
+    /// truncated stack trace are marked as not having a full stack. When a chain is truncated an
+    /// extra exception is appended to indicate how many exceptions were dropped.
+    fn apply(&self, mut exceptions: Vec<ExceptionDetails>) -> Vec<ExceptionDetails> {
+        for exception in &mut exceptions {
+            self.truncate_stack(exception);
+        }
+
+        let count = exceptions.len();
+        if count > self.max_chain_depth {
+            exceptions.truncate(self.max_chain_depth);
+            exceptions.push(ExceptionDetails {
+                outer_id: exceptions.last().and_then(|exception| exception.id),
+                type_name: "ExceptionChainTruncated".into(),
+                message: format!(
+                    "The number of chained exceptions was {} which is larger than {}, the maximum number allowed \
+                     during transmission. All but the first {} exceptions were dropped.",
+                    count, self.max_chain_depth, self.max_chain_depth
+                ),
+                ..ExceptionDetails::default()
+            });
+        }
+
+        exceptions
+    }
+
+    fn truncate_stack(&self, exception: &mut ExceptionDetails) {
+        let max = self.max_stack_frames;
+
+        if exception.parsed_stack.len() > max {
+            exception.parsed_stack.truncate(max);
+            exception.has_full_stack = Some(false);
+        }
+
+        if let Some(stack) = &mut exception.stack {
+            let frames = stack.lines().count();
+            if frames > max {
+                let mut truncated = String::new();
+                for line in stack.lines().take(max) {
+                    truncated.push_str(line);
+                    truncated.push('\n');
+                }
+                truncated.push_str(&format!("... {} more frames", frames - max));
+                *stack = truncated;
+                exception.has_full_stack = Some(false);
+            }
+        }
+    }
+}
+
+impl Default for ExceptionLimits {
+    fn default() -> Self {
+        Self {
+            max_chain_depth: DEFAULT_MAX_CHAIN_DEPTH,
+            max_stack_frames: DEFAULT_MAX_STACK_FRAMES,
+        }
+    }
+}
+
+/// Default maximum number of chained exceptions per telemetry item.
+pub const DEFAULT_MAX_CHAIN_DEPTH: usize = 10;
+
+/// Default maximum number of stack frames per exception.
+pub const DEFAULT_MAX_STACK_FRAMES: usize = 200;
+
+/// A builder of [ExceptionTelemetry] items.
+#[derive(Debug, Default)]
+pub struct ExceptionTelemetryBuilder {
+    exceptions: Vec<ExceptionDetails>,
+    severity_level: Option<SeverityLevel>,
+    problem_id: Option<String>,
+    properties: Option<Properties>,
+    measurements: Option<Measurements>,
+    timestamp: Option<DateTime<Utc>>,
+    tags: Option<ContextTags>,
+}
+
+impl ExceptionTelemetryBuilder {
+    /// Sets the severity level.
+    pub fn with_severity(mut self, severity: SeverityLevel) -> Self {
+        self.severity_level = Some(severity);
+        self
+    }
+
+    /// Sets the time when this telemetry was measured.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets custom properties to submit with the telemetry item.
+    pub fn with_properties(mut self, properties: Properties) -> Self {
+        self.properties = Some(properties);
+        self
+    }
+
+    /// Sets context tags that override values found on client telemetry context.
+    pub fn with_tags(mut self, tags: ContextTags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    /// Sets custom measurements to submit with the telemetry item.
+    pub fn with_measurements(mut self, measurements: Measurements) -> Self {
+        self.measurements = Some(measurements);
+        self
+    }
+
+    /// Sets an identifier of where the exception was thrown in code.
+    pub fn with_problem_id(mut self, problem_id: impl Into<String>) -> Self {
+        self.problem_id = Some(problem_id.into());
+        self
+    }
+
+    /// Can be called multiple times to add several exceptions to the exception
+    /// chain of the `ExceptionTelemetry`. The first exception added is the
+    /// outermost one, every next exception is the cause of the previous one.
+    pub fn with_exception(mut self, exception: ExceptionDetails) -> Self {
+        chain(&mut self.exceptions, exception);
+        self
+    }
+
+    /// Builds an [ExceptionTelemetry] item.
+    pub fn build(self) -> ExceptionTelemetry {
+        ExceptionTelemetry {
+            severity_level: self.severity_level,
+            exceptions: self.exceptions,
+            timestamp: self.timestamp.unwrap_or_else(time::now),
+            properties: self.properties.unwrap_or_default(),
+            tags: self.tags.unwrap_or_default(),
+            measurements: self.measurements.unwrap_or_default(),
+            problem_id: self.problem_id,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contracts::StackFrame;
+
+    #[test]
+    fn it_links_exception_chain() {
+        let telemetry = ExceptionTelemetry::new(None, None::<String>)
+            .with_message("failed to load config", "ConfigError", Some("frame 0\nframe 1"))
+            .with_exception(ExceptionDetails {
+                id: Some(42),
+                type_name: "IoError".into(),
+                message: "permission denied".into(),
+                has_full_stack: Some(false),
+                stack: Some("frame 0".into()),
+                ..ExceptionDetails::default()
+            })
+            .with_message("file does not exist", "FileNotFound", None::<String>);
+
+        let chain: Vec<_> = telemetry
+            .exceptions
+            .iter()
+            .map(|exception| (exception.id, exception.outer_id, exception.has_full_stack))
+            .collect();
+        assert_eq!(
+            chain,
+            vec![
+                (Some(0), None, Some(true)),
+                (Some(1), Some(0), Some(false)),
+                (Some(2), Some(1), Some(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_links_exception_chain_with_builder() {
+        let telemetry = ExceptionTelemetry::builder()
+            .with_exception(ExceptionDetails::default())
+            .with_exception(ExceptionDetails::default())
+            .build();
+
+        assert_eq!(telemetry.exceptions[0].id, Some(0));
+        assert_eq!(telemetry.exceptions[1].id, Some(1));
+        assert_eq!(telemetry.exceptions[1].outer_id, Some(0));
+    }
+
+    #[test]
+    fn it_truncates_exception_chain() {
+        let limits = ExceptionLimits {
+            max_chain_depth: 2,
+            max_stack_frames: 10,
+        };
+        let exceptions = (0..4)
+            .map(|id| ExceptionDetails {
+                id: Some(id),
+                type_name: format!("error {}", id),
+                ..ExceptionDetails::default()
+            })
+            .collect();
+
+        let exceptions = limits.apply(exceptions);
+
+        assert_eq!(exceptions.len(), 3);
+        assert_eq!(exceptions[1].type_name, "error 1");
+        assert_eq!(exceptions[2].type_name, "ExceptionChainTruncated");
+        assert_eq!(exceptions[2].outer_id, Some(1));
+        assert!(exceptions[2]
+            .message
+            .starts_with("The number of chained exceptions was 4"));
+    }
+
+    #[test]
+    fn it_truncates_stack_frames() {
+        let limits = ExceptionLimits {
+            max_chain_depth: 10,
+            max_stack_frames: 2,
+        };
+        let exceptions = vec![
+            ExceptionDetails {
+                stack: Some("frame 0\nframe 1\nframe 2\nframe 3".into()),
+                ..ExceptionDetails::default()
+            },
+            ExceptionDetails {
+                parsed_stack: (0..3)
+                    .map(|level| StackFrame {
+                        level,
+                        ..StackFrame::default()
+                    })
+                    .collect(),
+                ..ExceptionDetails::default()
+            },
+            ExceptionDetails {
+                stack: Some("frame 0\nframe 1".into()),
+                ..ExceptionDetails::default()
+            },
+        ];
+
+        let exceptions = limits.apply(exceptions);
+
+        assert_eq!(
+            exceptions[0].stack.as_deref(),
+            Some("frame 0\nframe 1\n... 2 more frames")
+        );
+        assert_eq!(exceptions[0].has_full_stack, Some(false));
+        assert_eq!(exceptions[1].parsed_stack.len(), 2);
+        assert_eq!(exceptions[1].has_full_stack, Some(false));
+        assert_eq!(exceptions[2].stack.as_deref(), Some("frame 0\nframe 1"));
+        assert_eq!(exceptions[2].has_full_stack, Some(true));
+    }
+}
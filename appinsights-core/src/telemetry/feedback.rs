@@ -0,0 +1,232 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::Envelope,
+    telemetry::{ContextTags, EventTelemetry, Measurements, Properties, Telemetry},
+    time,
+};
+
+/// Name of an event telemetry item a user feedback is submitted as.
+const FEEDBACK_EVENT_NAME: &str = "UserFeedback";
+
+/// Name of a measurement that contains a score given by a user.
+const FEEDBACK_SCORE: &str = "feedback.score";
+
+/// Name of a property that contains a comment left by a user.
+const FEEDBACK_COMMENT: &str = "feedback.comment";
+
+/// Name of a property that contains a place in the application a user gave feedback about.
+const FEEDBACK_CONTEXT: &str = "feedback.context";
+
+/// Represents a feedback a user gave in the application, e.g. a net promoter score survey answer
+/// or a rating of a feature.
+///
+/// A feedback is submitted as an event named `UserFeedback` so it can be queried along with other
+/// custom events. A score is stored in the `feedback.score` measurement, while optional comment
+/// and context are stored in the `feedback.comment` and `feedback.context` properties. The event
+/// carries session, user and operation tags of the client context, so the feedback is linked to
+/// the session, the user and the operation it was given in.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{FeedbackTelemetry, Telemetry};
+///
+/// // create a telemetry item
+/// let mut telemetry = FeedbackTelemetry::new(9.0);
+/// telemetry.set_comment("Checkout was quick and easy");
+/// telemetry.set_context("checkout");
+///
+/// // attach custom properties and context tags
+/// telemetry.properties_mut().insert("survey".to_string(), "nps-2024-q1".to_string());
+/// telemetry.tags_mut().user_mut().set_id("user-42".to_string());
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct FeedbackTelemetry {
+    /// A score given by a user.
+    score: f64,
+
+    /// A comment left by a user.
+    comment: Option<String>,
+
+    /// A place in the application a user gave feedback about.
+    context: Option<String>,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+
+    /// Custom measurements.
+    measurements: Measurements,
+}
+
+impl FeedbackTelemetry {
+    /// Creates a new feedback telemetry item with a score given by a user.
+    pub fn new(score: f64) -> Self {
+        Self {
+            score,
+            comment: None,
+            context: None,
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Returns a score given by a user.
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    /// Returns a comment left by a user.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Sets a comment left by a user.
+    pub fn set_comment(&mut self, comment: impl Into<String>) {
+        self.comment = Some(comment.into());
+    }
+
+    /// Returns a place in the application a user gave feedback about.
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    /// Sets a place in the application a user gave feedback about, e.g. a page or a feature name.
+    pub fn set_context(&mut self, context: impl Into<String>) {
+        self.context = Some(context.into());
+    }
+
+    /// Returns custom measurements to submit with the telemetry item.
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Returns mutable reference to custom measurements.
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+}
+
+impl Telemetry for FeedbackTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+}
+
+impl From<(TelemetryContext, FeedbackTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, FeedbackTelemetry)) -> Self {
+        let mut properties = telemetry.properties;
+        if let Some(comment) = telemetry.comment {
+            properties.insert(FEEDBACK_COMMENT.into(), comment);
+        }
+        if let Some(feedback_context) = telemetry.context {
+            properties.insert(FEEDBACK_CONTEXT.into(), feedback_context);
+        }
+
+        let mut measurements = telemetry.measurements;
+        measurements.insert(FEEDBACK_SCORE.into(), telemetry.score);
+
+        let event = EventTelemetry::builder()
+            .with_name(FEEDBACK_EVENT_NAME)
+            .with_timestamp(telemetry.timestamp)
+            .with_properties(properties)
+            .with_tags(telemetry.tags)
+            .with_measurements(measurements)
+            .build();
+        Envelope::from((context, event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::contracts::{Base, Data, EventData, StringMap};
+
+    #[test]
+    fn it_submits_feedback_as_event_with_property_convention() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.tags_mut().session_mut().set_id("session".into());
+        context.tags_mut().operation_mut().set_id("operation".into());
+        context.properties_mut().insert("app".into(), "shop".into());
+
+        let mut telemetry = FeedbackTelemetry::new(9.0);
+        telemetry.set_comment("quick and easy");
+        telemetry.set_context("checkout");
+        telemetry.tags_mut().user_mut().set_id("user".into());
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Event".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some({
+                let mut tags = StringMap::default();
+                tags.insert("ai.session.id".into(), "session".into());
+                tags.insert("ai.operation.id".into(), "operation".into());
+                tags.insert("ai.user.id".into(), "user".into());
+                tags
+            }),
+            data: Some(Base::Data(Data::EventData(EventData {
+                name: "UserFeedback".into(),
+                properties: Some({
+                    let mut properties = StringMap::default();
+                    properties.insert("app".into(), "shop".into());
+                    properties.insert("feedback.comment".into(), "quick and easy".into());
+                    properties.insert("feedback.context".into(), "checkout".into());
+                    properties
+                }),
+                measurements: Some({
+                    let mut measurements = BTreeMap::default();
+                    measurements.insert("feedback.score".into(), 9.0);
+                    measurements
+                }),
+                ..EventData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+}
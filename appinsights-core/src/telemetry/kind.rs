@@ -0,0 +1,49 @@
+use crate::contracts::{Base, Data, Envelope};
+
+/// A category of telemetry items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TelemetryKind {
+    /// [Availability telemetry](struct.AvailabilityTelemetry.html).
+    Availability,
+
+    /// [Event telemetry](struct.EventTelemetry.html).
+    Event,
+
+    /// [Exception telemetry](struct.ExceptionTelemetry.html).
+    Exception,
+
+    /// Any kind of metric telemetry, e.g. [`MetricTelemetry`](struct.MetricTelemetry.html) or
+    /// [`AggregateMetricTelemetry`](struct.AggregateMetricTelemetry.html).
+    Metric,
+
+    /// [Page view telemetry](struct.PageViewTelemetry.html) and
+    /// [page view performance telemetry](struct.PageViewPerformanceTelemetry.html).
+    PageView,
+
+    /// [Remote dependency telemetry](struct.RemoteDependencyTelemetry.html).
+    RemoteDependency,
+
+    /// [Request telemetry](struct.RequestTelemetry.html).
+    Request,
+
+    /// [Trace telemetry](struct.TraceTelemetry.html).
+    Trace,
+}
+
+impl TelemetryKind {
+    /// Returns a category of a telemetry item carried by an envelope.
+    #[doc(hidden)]
+    pub fn of(envelope: &Envelope) -> Option<Self> {
+        match envelope.data.as_ref()? {
+            Base::Data(Data::AvailabilityData(_)) => Some(TelemetryKind::Availability),
+            Base::Data(Data::EventData(_)) => Some(TelemetryKind::Event),
+            Base::Data(Data::ExceptionData(_)) => Some(TelemetryKind::Exception),
+            Base::Data(Data::MessageData(_)) => Some(TelemetryKind::Trace),
+            Base::Data(Data::MetricData(_)) => Some(TelemetryKind::Metric),
+            Base::Data(Data::PageViewData(_)) => Some(TelemetryKind::PageView),
+            Base::Data(Data::PageViewPerfData(_)) => Some(TelemetryKind::PageView),
+            Base::Data(Data::RemoteDependencyData(_)) => Some(TelemetryKind::RemoteDependency),
+            Base::Data(Data::RequestData(_)) => Some(TelemetryKind::Request),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    ops::{Deref, DerefMut},
+};
+
+use crate::telemetry::Properties;
+
+/// A suffix of a property that contains a unit of a measurement with the same name.
+const UNIT_PROPERTY_SUFFIX: &str = "_unit";
+
+/// Contains all measurements for telemetry to submit.
+#[derive(Debug, Clone, Default)]
+pub struct Measurements {
+    values: BTreeMap<String, f64>,
+    units: BTreeMap<String, Unit>,
+}
+
+impl Measurements {
+    /// Inserts a measurement with a unit it was measured in. The unit is submitted as a custom
+    /// property named after the measurement with `_unit` suffix, e.g. `latency_unit`, so dashboards
+    /// are able to render values properly.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use appinsights::telemetry::{EventTelemetry, Telemetry, Unit};
+    ///
+    /// let mut telemetry = EventTelemetry::new("request processed");
+    /// telemetry.measurements_mut().insert_with_unit("latency", 12.3, Unit::Milliseconds);
+    /// ```
+    pub fn insert_with_unit(&mut self, name: impl Into<String>, value: f64, unit: Unit) -> Option<f64> {
+        let name = name.into();
+        self.units.insert(name.clone(), unit);
+        self.values.insert(name, value)
+    }
+
+    /// Returns a unit of a measurement with specified name if any.
+    pub fn unit(&self, name: &str) -> Option<&Unit> {
+        self.units.get(name).filter(|_| self.values.contains_key(name))
+    }
+
+    /// Returns properties that describe units of measurements.
+    pub(crate) fn unit_properties(&self) -> Properties {
+        let mut properties = Properties::default();
+        for (name, unit) in &self.units {
+            if self.values.contains_key(name) {
+                properties.insert(format!("{}{}", name, UNIT_PROPERTY_SUFFIX), unit.to_string());
+            }
+        }
+        properties
+    }
+}
+
+impl From<Measurements> for BTreeMap<String, f64> {
+    fn from(measurements: Measurements) -> Self {
+        measurements.values
+    }
+}
+
+impl Deref for Measurements {
+    type Target = BTreeMap<String, f64>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl DerefMut for Measurements {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.values
+    }
+}
+
+/// A unit of a measurement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unit {
+    /// Number of occurrences.
+    Count,
+
+    /// Number of occurrences per second.
+    CountPerSecond,
+
+    /// Percentage within `[0, 100]` range.
+    Percent,
+
+    /// Duration in milliseconds.
+    Milliseconds,
+
+    /// Duration in seconds.
+    Seconds,
+
+    /// Size in bytes.
+    Bytes,
+
+    /// Throughput in bytes per second.
+    BytesPerSecond,
+
+    /// Any other unit.
+    Custom(String),
+}
+
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unit = match self {
+            Unit::Count => "Count",
+            Unit::CountPerSecond => "CountPerSecond",
+            Unit::Percent => "Percent",
+            Unit::Milliseconds => "Milliseconds",
+            Unit::Seconds => "Seconds",
+            Unit::Bytes => "Bytes",
+            Unit::BytesPerSecond => "BytesPerSecond",
+            Unit::Custom(unit) => unit,
+        };
+        write!(f, "{}", unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_describes_units_of_measurements_as_properties() {
+        let mut measurements = Measurements::default();
+        measurements.insert_with_unit("latency", 12.3, Unit::Milliseconds);
+        measurements.insert_with_unit("payload", 512.0, Unit::Custom("KiB".into()));
+        measurements.insert_with_unit("removed", 1.0, Unit::Count);
+        measurements.insert("count".into(), 5.0);
+        measurements.remove("removed");
+
+        let properties: BTreeMap<_, _> = measurements.unit_properties().into();
+
+        assert_eq!(
+            properties,
+            BTreeMap::from([
+                ("latency_unit".into(), "Milliseconds".into()),
+                ("payload_unit".into(), "KiB".into()),
+            ])
+        );
+        assert_eq!(measurements.unit("latency"), Some(&Unit::Milliseconds));
+        assert_eq!(measurements.unit("removed"), None);
+    }
+}
@@ -1,9 +1,9 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 
 use crate::{
     context::TelemetryContext,
-    contracts::{Base, Data, DataPoint, DataPointType, Envelope, MetricData},
-    telemetry::{ContextTags, Properties, Stats, Telemetry},
+    contracts::{names, Base, Data, DataPoint, DataPointType, Envelope, MetricData},
+    telemetry::{envelope, ContextTags, Properties, Stats, Telemetry},
     time,
 };
 
@@ -99,10 +99,9 @@ impl Telemetry for AggregateMetricTelemetry {
 impl From<(TelemetryContext, AggregateMetricTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, AggregateMetricTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.Metric".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: telemetry.name,
@@ -114,22 +113,20 @@ impl From<(TelemetryContext, AggregateMetricTelemetry)> for Envelope {
                     std_dev: Some(telemetry.stats.std_dev),
                     ..DataPoint::default()
                 }],
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted(context.properties, telemetry.properties)),
                 ..MetricData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::METRIC, telemetry.timestamp)
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-
     use chrono::TimeZone;
 
     use super::*;
-    use crate::time;
+    use crate::{contracts::StringMap, time};
 
     #[test]
     fn it_overrides_properties_from_context() {
@@ -150,7 +147,7 @@ mod tests {
             name: "Microsoft.ApplicationInsights.Metric".into(),
             time: "2019-01-02T03:04:05.100Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: "test".into(),
@@ -163,7 +160,7 @@ mod tests {
                     ..DataPoint::default()
                 }],
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -196,7 +193,7 @@ mod tests {
             time: "2019-01-02T03:04:05.101Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
@@ -212,7 +209,7 @@ mod tests {
                     std_dev: Some(2.0),
                     ..DataPoint::default()
                 }],
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 ..MetricData::default()
             }))),
             ..Envelope::default()
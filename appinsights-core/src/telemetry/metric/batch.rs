@@ -0,0 +1,228 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::{names, Base, Data, DataPoint, DataPointType, Envelope, MetricData},
+    telemetry::{envelope, ContextTags, Properties, Stats, Telemetry},
+    time,
+};
+
+/// Metric telemetry item that carries several data points in a single envelope.
+/// It reduces ingestion cost of high frequency metrics that share properties and tags, since
+/// all data points are submitted as a single telemetry item.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{MetricBatchTelemetry, Stats, Telemetry};
+///
+/// // create a telemetry item
+/// let mut telemetry = MetricBatchTelemetry::new();
+/// telemetry.add_measurement("queue_length", 42.0);
+///
+/// let mut stats = Stats::default();
+/// stats.add_data(&[113.0, 250.0, 316.0]);
+/// telemetry.add_aggregation("message_latency", stats);
+///
+/// // assign custom properties shared by all data points
+/// telemetry.properties_mut().insert("component".to_string(), "external_device".to_string());
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct MetricBatchTelemetry {
+    /// Data points to submit.
+    metrics: Vec<Metric>,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+}
+
+/// A single data point of a metric batch.
+#[derive(Debug)]
+enum Metric {
+    Measurement { name: String, value: f64 },
+    Aggregation { name: String, stats: Stats },
+}
+
+impl MetricBatchTelemetry {
+    /// Creates an empty metric batch telemetry item.
+    pub fn new() -> Self {
+        Self {
+            metrics: Vec::default(),
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+        }
+    }
+
+    /// Adds a single measured value of a metric with specified name.
+    pub fn add_measurement(&mut self, name: impl Into<String>, value: f64) {
+        self.metrics.push(Metric::Measurement {
+            name: name.into(),
+            value,
+        });
+    }
+
+    /// Adds an aggregation of values of a metric with specified name.
+    pub fn add_aggregation(&mut self, name: impl Into<String>, stats: Stats) {
+        self.metrics.push(Metric::Aggregation {
+            name: name.into(),
+            stats,
+        });
+    }
+
+    /// Returns the number of data points in this telemetry item.
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Returns `true` if this telemetry item contains no data points.
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+}
+
+impl Default for MetricBatchTelemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Telemetry for MetricBatchTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+}
+
+impl Metric {
+    /// Converts a metric into a data point to submit.
+    fn into_data_point(self) -> DataPoint {
+        match self {
+            Metric::Measurement { name, value } => DataPoint {
+                name,
+                kind: Some(DataPointType::Measurement),
+                value,
+                count: Some(1),
+                ..DataPoint::default()
+            },
+            Metric::Aggregation { name, stats } => DataPoint {
+                name,
+                kind: Some(DataPointType::Aggregation),
+                value: stats.value,
+                count: Some(stats.count),
+                min: Some(stats.min),
+                max: Some(stats.max),
+                std_dev: Some(stats.std_dev),
+                ..DataPoint::default()
+            },
+        }
+    }
+}
+
+impl From<(TelemetryContext, MetricBatchTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, MetricBatchTelemetry)) -> Self {
+        Self {
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
+            data: Some(Base::Data(Data::MetricData(MetricData {
+                metrics: telemetry.metrics.into_iter().map(Metric::into_data_point).collect(),
+                properties: Some(Properties::submitted(context.properties, telemetry.properties)),
+                ..MetricData::default()
+            }))),
+            ..envelope(names::METRIC, telemetry.timestamp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{contracts::StringMap, time};
+
+    #[test]
+    fn it_submits_all_data_points_in_single_envelope() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 102));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.properties_mut().insert("test".into(), "ok".into());
+
+        let mut telemetry = MetricBatchTelemetry::new();
+        telemetry.add_measurement("measurement", 42.0);
+        let mut stats = Stats::default();
+        stats.add_data(&[9.0, 10.0, 11.0, 7.0, 13.0]);
+        telemetry.add_aggregation("aggregation", stats);
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.Metric".into(),
+            time: "2019-01-02T03:04:05.102Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(StringMap::default()),
+            data: Some(Base::Data(Data::MetricData(MetricData {
+                metrics: vec![
+                    DataPoint {
+                        name: "measurement".into(),
+                        kind: Some(DataPointType::Measurement),
+                        value: 42.0,
+                        count: Some(1),
+                        ..DataPoint::default()
+                    },
+                    DataPoint {
+                        name: "aggregation".into(),
+                        kind: Some(DataPointType::Aggregation),
+                        value: 50.0,
+                        count: Some(5),
+                        min: Some(7.0),
+                        max: Some(13.0),
+                        std_dev: Some(2.0),
+                        ..DataPoint::default()
+                    },
+                ],
+                properties: Some({
+                    let mut properties = StringMap::default();
+                    properties.insert("test".into(), "ok".into());
+                    properties
+                }),
+                ..MetricData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+}
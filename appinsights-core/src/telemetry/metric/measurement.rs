@@ -1,9 +1,9 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 
 use crate::{
     context::TelemetryContext,
-    contracts::{Base, Data, DataPoint, DataPointType, Envelope, MetricData},
-    telemetry::{ContextTags, Properties, Telemetry},
+    contracts::{names, Base, Data, DataPoint, DataPointType, Envelope, MetricData},
+    telemetry::{envelope, ContextTags, Properties, Telemetry},
     time,
 };
 
@@ -86,10 +86,9 @@ impl Telemetry for MetricTelemetry {
 impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, MetricTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.Metric".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: telemetry.name,
@@ -98,22 +97,20 @@ impl From<(TelemetryContext, MetricTelemetry)> for Envelope {
                     count: Some(1),
                     ..DataPoint::default()
                 }],
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted(context.properties, telemetry.properties)),
                 ..MetricData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::METRIC, telemetry.timestamp)
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-
     use chrono::TimeZone;
 
     use super::*;
-    use crate::time;
+    use crate::{contracts::StringMap, time};
 
     #[test]
     fn it_overrides_properties_from_context() {
@@ -133,7 +130,7 @@ mod tests {
             name: "Microsoft.ApplicationInsights.Metric".into(),
             time: "2019-01-02T03:04:05.100Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::MetricData(MetricData {
                 metrics: vec![DataPoint {
                     name: "test".into(),
@@ -143,7 +140,7 @@ mod tests {
                     ..DataPoint::default()
                 }],
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -175,7 +172,7 @@ mod tests {
             time: "2019-01-02T03:04:05.101Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
@@ -188,7 +185,7 @@ mod tests {
                     count: Some(1),
                     ..DataPoint::default()
                 }],
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 ..MetricData::default()
             }))),
             ..Envelope::default()
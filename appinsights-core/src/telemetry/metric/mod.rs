@@ -1,7 +1,9 @@
 mod aggregation;
+mod batch;
 mod measurement;
 mod stats;
 
 pub use aggregation::*;
+pub use batch::*;
 pub use measurement::*;
 pub use stats::*;
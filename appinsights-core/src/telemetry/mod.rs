@@ -0,0 +1,166 @@
+//! Module for Application Insights telemetry items.
+mod availability;
+mod event;
+mod exception;
+mod feedback;
+mod kind;
+mod measurements;
+mod metric;
+mod operation;
+mod page_view;
+mod page_view_performance;
+mod priority;
+mod progress;
+mod properties;
+mod remote_dependency;
+mod request;
+mod severity_level;
+mod synthetic;
+mod tags;
+mod timeline;
+mod timer;
+mod trace;
+mod trace_parent;
+
+/// Keys of all context tags known to Application Insights, e.g. [`OPERATION_ID`](tag_keys::OPERATION_ID).
+pub use crate::contracts::tags as tag_keys;
+pub use availability::AvailabilityTelemetry;
+pub use event::{EventTelemetry, EventTelemetryBuilder};
+pub(crate) use exception::ExceptionLimits;
+pub use exception::{ExceptionTelemetry, ExceptionTelemetryBuilder};
+#[doc(hidden)]
+pub use exception::{DEFAULT_MAX_CHAIN_DEPTH, DEFAULT_MAX_STACK_FRAMES};
+pub use feedback::FeedbackTelemetry;
+pub use kind::TelemetryKind;
+pub use measurements::{Measurements, Unit};
+pub use metric::{AggregateMetricTelemetry, MetricBatchTelemetry, MetricTelemetry, Stats};
+pub use operation::LongRunningOperation;
+pub use page_view::PageViewTelemetry;
+pub use page_view_performance::PageViewPerformanceTelemetry;
+pub use priority::Priority;
+pub use progress::OperationProgressTracker;
+pub use properties::{InvalidPropertyValue, Properties, PropertiesExt, PropertyValue};
+pub use remote_dependency::{DependencyTimer, RemoteDependencyTelemetry};
+pub use request::{RequestTelemetry, RequestTimer};
+pub use severity_level::{InvalidSeverityLevel, SeverityLevel};
+pub use synthetic::SyntheticTraffic;
+pub use tags::{
+    ApplicationTags, ApplicationTagsMut, CloudTags, CloudTagsMut, ContextTags, DeviceTags, DeviceTagsMut, InternalTags,
+    InternalTagsMut, LocationTags, LocationTagsMut, OperationTags, OperationTagsMut, SessionTags, SessionTagsMut,
+    UserTags, UserTagsMut,
+};
+pub use timeline::OperationTimeline;
+pub(crate) use timer::Timer;
+pub use trace::{TraceTelemetry, TraceTelemetryBuilder};
+pub use trace_parent::{InvalidTraceParent, TraceParent};
+
+use chrono::{DateTime, Utc};
+
+use crate::{contracts::Envelope, TelemetryContext};
+
+/// Creates an envelope of a telemetry item with a given name and timestamp. It reuses an envelope
+/// from the [`pool`](crate::pool) when pooling is enabled.
+#[cfg(feature = "pool")]
+pub(crate) fn envelope(name: &str, timestamp: DateTime<Utc>) -> Envelope {
+    crate::pool::take(name, timestamp)
+}
+
+/// Creates an envelope of a telemetry item with a given name and timestamp.
+#[cfg(not(feature = "pool"))]
+pub(crate) fn envelope(name: &str, timestamp: DateTime<Utc>) -> Envelope {
+    Envelope {
+        name: name.into(),
+        time: timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        ..Envelope::default()
+    }
+}
+
+/// A trait that provides Application Insights telemetry items.
+pub trait Telemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc>;
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties;
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties;
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags;
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags;
+}
+
+/// An object-safe conversion of a telemetry item into an envelope. It allows to store telemetry
+/// items of different types together, e.g. `Vec<Box<dyn IntoEnvelope>>`, and submit them later with
+/// [`Tracker::track_boxed`], e.g. of a `TelemetryClient`.
+///
+/// It is implemented for every telemetry item the client can submit.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{EventTelemetry, IntoEnvelope, SeverityLevel, TraceTelemetry};
+///
+/// let pending: Vec<Box<dyn IntoEnvelope>> = vec![
+///     Box::new(EventTelemetry::new("plugin loaded")),
+///     Box::new(TraceTelemetry::new("plugin is ready", SeverityLevel::Information)),
+/// ];
+///
+/// for telemetry in pending {
+///     client.track_boxed(telemetry);
+/// }
+/// ```
+pub trait IntoEnvelope: Send {
+    /// Converts this telemetry item into an envelope combined with a given telemetry context.
+    fn into_envelope(self: Box<Self>, context: TelemetryContext) -> Envelope;
+}
+
+impl<T> IntoEnvelope for T
+where
+    T: Telemetry + Send,
+    (TelemetryContext, T): Into<Envelope>,
+{
+    fn into_envelope(self: Box<Self>, context: TelemetryContext) -> Envelope {
+        (context, *self).into()
+    }
+}
+
+/// A destination of telemetry items, e.g. a `TelemetryClient` of the `appinsights` crate. Timers,
+/// like [`RequestTimer`], submit telemetry items to it when they finish.
+pub trait Tracker: Send + Sync {
+    /// Submits a telemetry item.
+    fn track_boxed(&self, event: Box<dyn IntoEnvelope>);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+
+    /// A tracker that combines telemetry items with an empty context and collects envelopes.
+    #[derive(Clone)]
+    pub(crate) struct TestTracker {
+        events: Arc<SegQueue<Envelope>>,
+    }
+
+    impl TestTracker {
+        pub(crate) fn new(events: Arc<SegQueue<Envelope>>) -> Self {
+            Self { events }
+        }
+    }
+
+    impl Tracker for TestTracker {
+        fn track_boxed(&self, event: Box<dyn IntoEnvelope>) {
+            let context =
+                TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+            self.events.push(event.into_envelope(context));
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use http::Uri;
+
+use crate::{
+    telemetry::{EventTelemetry, RequestTelemetry, Telemetry},
+    time, uuid,
+};
+
+/// A name of a measurement that contains the time elapsed since an operation has started in milliseconds.
+const ELAPSED_MEASUREMENT: &str = "elapsed";
+
+/// A name of a property that contains a sequence number of a heartbeat within an operation.
+const SEQUENCE_PROPERTY: &str = "sequence";
+
+/// Tracks an operation that runs for a long time, e.g. a streaming job that lasts for many minutes
+/// or hours.
+///
+/// A request telemetry item is only sent when an operation finishes, so while it runs nothing shows
+/// up on the portal. Periodic [`heartbeat`](LongRunningOperation::heartbeat) events correlated with
+/// the operation report that it is still alive and how long it is running. The final request
+/// telemetry item created by [`finish`](LongRunningOperation::finish) is stamped with the time the
+/// operation has started and its duration is measured by the wall clock.
+///
+/// An operation can be persisted and continued after an application restart with
+/// [`resume`](LongRunningOperation::resume), so the final request covers the whole operation.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::LongRunningOperation;
+///
+/// let mut operation = LongRunningOperation::start("process stream");
+///
+/// // ... periodically while processing
+/// client.track(operation.heartbeat());
+///
+/// let request = operation.finish("https://example.com/streams/42".parse().unwrap(), "200");
+/// client.track(request);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongRunningOperation {
+    /// An operation id that correlates heartbeats with the final request.
+    id: String,
+
+    /// An operation name.
+    name: String,
+
+    /// The time stamp when the operation has started.
+    started: DateTime<Utc>,
+
+    /// A number of heartbeats emitted so far.
+    heartbeats: u64,
+}
+
+impl LongRunningOperation {
+    /// Starts a new operation with a given name at the current moment.
+    pub fn start(name: impl Into<String>) -> Self {
+        Self::resume(uuid::new().as_simple().to_string(), name, time::now())
+    }
+
+    /// Continues an operation that was started earlier, e.g. before an application restart, with
+    /// the id, name and start time it was originally started with.
+    pub fn resume(id: impl Into<String>, name: impl Into<String>, started: DateTime<Utc>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            started,
+            heartbeats: 0,
+        }
+    }
+
+    /// Returns an operation id.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns an operation name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the time when this operation has started.
+    pub fn started(&self) -> DateTime<Utc> {
+        self.started
+    }
+
+    /// Returns the time elapsed since this operation has started.
+    pub fn elapsed(&self) -> std::time::Duration {
+        (time::now() - self.started).to_std().unwrap_or_default()
+    }
+
+    /// Creates a heartbeat event that reports this operation is still running. The event carries the
+    /// time elapsed since the operation has started in milliseconds as an `elapsed` measurement and
+    /// a sequence number of the heartbeat as a `sequence` property.
+    pub fn heartbeat(&mut self) -> EventTelemetry {
+        self.heartbeats += 1;
+
+        let mut event = EventTelemetry::new(format!("{} heartbeat", self.name));
+        event
+            .measurements_mut()
+            .insert(ELAPSED_MEASUREMENT.into(), self.elapsed().as_secs_f64() * 1000.0);
+        event
+            .properties_mut()
+            .insert(SEQUENCE_PROPERTY.into(), self.heartbeats.to_string());
+
+        let mut operation = event.tags_mut().operation_mut();
+        operation.set_id(self.id.clone());
+        operation.set_parent_id(self.id.clone());
+        operation.set_name(self.name.clone());
+
+        event
+    }
+
+    /// Finishes this operation and creates a request telemetry item that starts at the time the
+    /// operation has started and lasts until now.
+    pub fn finish(self, uri: Uri, response_code: impl Into<String>) -> RequestTelemetry {
+        let duration = self.elapsed();
+        let mut request = RequestTelemetry::new(self.name, uri, duration, response_code);
+        *request.timestamp_mut() = self.started;
+        request.set_id(self.id.clone());
+        request.tags_mut().operation_mut().set_id(self.id);
+
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data, Envelope, StringMap},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_emits_heartbeats_correlated_with_operation() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let mut operation = LongRunningOperation::start("process stream");
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 5, 0, 0));
+        operation.heartbeat();
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 10, 0, 0));
+        let heartbeat = operation.heartbeat();
+
+        let envelope = Envelope::from((context(), heartbeat));
+        assert_eq!(envelope.time, "2019-01-02T03:10:00.000Z");
+        assert_eq!(tag(&envelope, "ai.operation.id"), Some(operation.id()));
+        assert_eq!(tag(&envelope, "ai.operation.parentId"), Some(operation.id()));
+        assert_eq!(tag(&envelope, "ai.operation.name"), Some("process stream"));
+
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                assert_eq!(data.name, "process stream heartbeat");
+                assert_eq!(data.measurements.unwrap_or_default().get("elapsed"), Some(&600000.0));
+                assert_eq!(
+                    data.properties.unwrap_or_default().get("sequence").map(String::as_str),
+                    Some("2")
+                );
+            }
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+        time::reset();
+    }
+
+    #[test]
+    fn it_measures_duration_of_operation_resumed_after_restart() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let operation = LongRunningOperation::start("process stream");
+        let (id, started) = (operation.id().to_string(), operation.started());
+        drop(operation);
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(4, 30, 15, 0));
+        let operation = LongRunningOperation::resume(id.clone(), "process stream", started);
+        let request = operation.finish("https://example.com/streams/42".parse().unwrap(), "200");
+
+        let envelope = Envelope::from((context(), request));
+        assert_eq!(envelope.time, "2019-01-02T03:00:00.000Z");
+        assert_eq!(tag(&envelope, "ai.operation.id"), Some(id.as_str()));
+
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.id, id);
+                assert_eq!(data.name, Some("process stream".into()));
+                assert_eq!(data.duration, "0.01:30:15.0000000");
+            }
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+        time::reset();
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::new("instrumentation".into(), Default::default(), Default::default())
+    }
+
+    fn tag<'a>(envelope: &'a Envelope, name: &str) -> Option<&'a str> {
+        envelope
+            .tags
+            .as_ref()
+            .and_then(|tags: &StringMap| tags.get(name))
+            .map(String::as_str)
+    }
+}
@@ -1,10 +1,10 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 use http::Uri;
 
 use crate::{
     context::TelemetryContext,
-    contracts::{Base, Data, Envelope, PageViewData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    contracts::{names, Base, Data, Envelope, PageViewData},
+    telemetry::{envelope, ContextTags, Measurements, Properties, Telemetry},
     time::{self, Duration},
     uuid::Uuid,
 };
@@ -117,10 +117,9 @@ impl Telemetry for PageViewTelemetry {
 impl From<(TelemetryContext, PageViewTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, PageViewTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.PageView".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::PageViewData(PageViewData {
                 name: telemetry.name,
                 url: Some(telemetry.uri.to_string()),
@@ -130,11 +129,15 @@ impl From<(TelemetryContext, PageViewTelemetry)> for Envelope {
                     .id
                     .map(|id| id.as_hyphenated().to_string())
                     .unwrap_or_default(),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
                 measurements: Some(telemetry.measurements.into()),
                 ..PageViewData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::PAGE_VIEW, telemetry.timestamp)
         }
     }
 }
@@ -146,6 +149,7 @@ mod tests {
     use chrono::TimeZone;
 
     use super::*;
+    use crate::contracts::StringMap;
 
     #[test]
     fn it_overrides_properties_from_context() {
@@ -166,12 +170,12 @@ mod tests {
             name: "Microsoft.ApplicationInsights.PageView".into(),
             time: "2019-01-02T03:04:05.800Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::PageViewData(PageViewData {
                 name: "page updated".into(),
                 url: Some("https://example.com/main.html".into()),
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -208,7 +212,7 @@ mod tests {
             time: "2019-01-02T03:04:05.700Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
@@ -216,7 +220,7 @@ mod tests {
             data: Some(Base::Data(Data::PageViewData(PageViewData {
                 name: "page updated".into(),
                 url: Some("https://example.com/main.html".into()),
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..PageViewData::default()
             }))),
@@ -0,0 +1,255 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Utc};
+use http::Uri;
+
+use crate::{
+    context::TelemetryContext,
+    contracts::{names, Base, Data, Envelope, PageViewPerfData},
+    telemetry::{envelope, ContextTags, Measurements, Properties, Telemetry},
+    time::{self, Duration},
+    uuid::Uuid,
+};
+
+/// Represents a page view along with the timings of how the page was loaded.
+///
+/// The total page load time is reported as the duration of the page view. Other timings break
+/// it down into the time spent on establishing a connection, sending a request, receiving a
+/// response and processing the page document.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{PageViewPerformanceTelemetry, Telemetry};
+/// use http::Uri;
+/// use std::time::Duration;
+///
+/// // create a telemetry item
+/// let mut telemetry = PageViewPerformanceTelemetry::new(
+///     "check github repo page",
+///     "https://github.com/dmolokanov/appinsights-rs".parse::<Uri>().unwrap(),
+///     Duration::from_millis(1250),
+/// );
+///
+/// // break the page load time down
+/// telemetry.set_network_connect(Duration::from_millis(150));
+/// telemetry.set_sent_request(Duration::from_millis(50));
+/// telemetry.set_received_response(Duration::from_millis(400));
+/// telemetry.set_dom_processing(Duration::from_millis(650));
+///
+/// // attach custom properties and context tags
+/// telemetry.properties_mut().insert("component".to_string(), "data_processor".to_string());
+/// telemetry.tags_mut().insert("os_version".to_string(), "linux x86_64".to_string());
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct PageViewPerformanceTelemetry {
+    /// Identifier of a page view instance.
+    /// It is used to correlate a page view and telemetry generated by the service.
+    id: Option<Uuid>,
+
+    /// Event name.
+    name: String,
+
+    /// Request URL with all query string parameters.
+    uri: Uri,
+
+    /// Total time it took to load the page.
+    perf_total: Duration,
+
+    /// Time it took to establish a network connection.
+    network_connect: Option<Duration>,
+
+    /// Time it took to send a request.
+    sent_request: Option<Duration>,
+
+    /// Time it took to receive a response.
+    received_response: Option<Duration>,
+
+    /// Time it took to process the page document.
+    dom_processing: Option<Duration>,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+
+    /// Custom measurements.
+    measurements: Measurements,
+}
+
+impl PageViewPerformanceTelemetry {
+    /// Creates a new page view performance telemetry item with the specified name, url and the
+    /// total time it took to load the page.
+    pub fn new(name: impl Into<String>, uri: Uri, perf_total: StdDuration) -> Self {
+        Self {
+            id: Option::default(),
+            name: name.into(),
+            uri,
+            perf_total: perf_total.into(),
+            network_connect: Option::default(),
+            sent_request: Option::default(),
+            received_response: Option::default(),
+            dom_processing: Option::default(),
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Sets time it took to establish a network connection.
+    pub fn set_network_connect(&mut self, duration: StdDuration) {
+        self.network_connect = Some(duration.into());
+    }
+
+    /// Sets time it took to send a request.
+    pub fn set_sent_request(&mut self, duration: StdDuration) {
+        self.sent_request = Some(duration.into());
+    }
+
+    /// Sets time it took to receive a response.
+    pub fn set_received_response(&mut self, duration: StdDuration) {
+        self.received_response = Some(duration.into());
+    }
+
+    /// Sets time it took to process the page document.
+    pub fn set_dom_processing(&mut self, duration: StdDuration) {
+        self.dom_processing = Some(duration.into());
+    }
+
+    /// Returns custom measurements to submit with the telemetry item.
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Returns mutable reference to custom measurements.
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+}
+
+impl Telemetry for PageViewPerformanceTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+}
+
+impl From<(TelemetryContext, PageViewPerformanceTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, PageViewPerformanceTelemetry)) -> Self {
+        Self {
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
+            data: Some(Base::Data(Data::PageViewPerfData(PageViewPerfData {
+                name: telemetry.name,
+                url: Some(telemetry.uri.to_string()),
+                duration: Some(telemetry.perf_total.to_string()),
+                id: telemetry
+                    .id
+                    .map(|id| id.as_hyphenated().to_string())
+                    .unwrap_or_default(),
+                perf_total: Some(telemetry.perf_total.to_string()),
+                network_connect: telemetry.network_connect.map(|duration| duration.to_string()),
+                sent_request: telemetry.sent_request.map(|duration| duration.to_string()),
+                received_response: telemetry.received_response.map(|duration| duration.to_string()),
+                dom_processing: telemetry.dom_processing.map(|duration| duration.to_string()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
+                measurements: Some(telemetry.measurements.into()),
+                ..PageViewPerfData::default()
+            }))),
+            ..envelope(names::PAGE_VIEW_PERFORMANCE, telemetry.timestamp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::contracts::StringMap;
+
+    #[test]
+    fn it_converts_page_load_timings() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.properties_mut().insert("test".into(), "ok".into());
+
+        let mut telemetry = PageViewPerformanceTelemetry::new(
+            "page loaded",
+            "https://example.com/main.html".parse().unwrap(),
+            StdDuration::from_millis(1250),
+        );
+        telemetry.set_network_connect(StdDuration::from_millis(150));
+        telemetry.set_dom_processing(StdDuration::from_millis(650));
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.PageviewPerformance".into(),
+            time: "2019-01-02T03:04:05.600Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(StringMap::default()),
+            data: Some(Base::Data(Data::PageViewPerfData(PageViewPerfData {
+                name: "page loaded".into(),
+                url: Some("https://example.com/main.html".into()),
+                duration: Some("0.00:00:01.2500000".into()),
+                perf_total: Some("0.00:00:01.2500000".into()),
+                network_connect: Some("0.00:00:00.1500000".into()),
+                dom_processing: Some("0.00:00:00.6500000".into()),
+                properties: Some(StringMap::from([("test".into(), "ok".into())])),
+                measurements: Some(BTreeMap::default()),
+                ..PageViewPerfData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test]
+    fn it_serializes_page_view_performance_base_type() {
+        let data = Data::PageViewPerfData(PageViewPerfData::default());
+
+        let json = serde_json::to_value(&data).unwrap();
+
+        assert_eq!(json["baseType"], "PageviewPerformanceData");
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{contracts::Envelope, telemetry::TelemetryKind};
+
+/// A name of the internal tag that carries a priority of a telemetry item. It is not sent to the
+/// server.
+const PRIORITY_TAG: &str = "ai.internal.priority";
+
+/// A priority class of a telemetry item. When a channel cannot send all telemetry items, e.g. when
+/// its queue is full during an outage or when the server throttles submissions, it sends items of a
+/// higher priority first and drops items of a lower priority first.
+///
+/// By default exceptions are of a high priority, traces are of a low priority and all other items
+/// are of a normal one. A priority of a category is configured with
+/// `TelemetryConfig::builder().priority(..)`, and a priority of a single item is set with its tags.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{EventTelemetry, Priority, Telemetry};
+///
+/// let mut telemetry = EventTelemetry::new("payment declined");
+/// telemetry.tags_mut().set_priority(Priority::High);
+///
+/// client.track(telemetry);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Items dropped first, e.g. verbose traces.
+    Low,
+
+    /// Items of a regular importance.
+    #[default]
+    Normal,
+
+    /// Items kept as long as possible, e.g. exceptions.
+    High,
+}
+
+impl Priority {
+    /// Returns a default priority of telemetry items of a given category.
+    pub fn of_kind(kind: TelemetryKind) -> Self {
+        match kind {
+            TelemetryKind::Exception => Priority::High,
+            TelemetryKind::Trace => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// Returns a priority an envelope is stamped with if any.
+    #[doc(hidden)]
+    pub fn of(envelope: &Envelope) -> Option<Self> {
+        Self::parse(envelope.tags.as_ref()?.get(PRIORITY_TAG)?)
+    }
+
+    /// Stamps an envelope with a given priority unless it carries one already.
+    #[doc(hidden)]
+    pub fn stamp_if_missing(self, envelope: &mut Envelope) {
+        envelope.insert_tag_if_missing(PRIORITY_TAG, self.as_str());
+    }
+
+    /// Removes a priority stamp from an envelope before it is sent and returns it.
+    #[doc(hidden)]
+    pub fn take(envelope: &mut Envelope) -> Option<Self> {
+        Self::parse(&envelope.tags.as_mut()?.remove(PRIORITY_TAG)?)
+    }
+
+    /// Returns the name of the internal tag that carries a priority.
+    pub(crate) fn tag() -> &'static str {
+        PRIORITY_TAG
+    }
+
+    /// Parses a priority stored in a tag.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+impl Display for Priority {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_stamps_and_takes_priority_of_envelope() {
+        let mut envelope = Envelope::default();
+        assert_eq!(Priority::of(&envelope), None);
+
+        Priority::High.stamp_if_missing(&mut envelope);
+        Priority::Low.stamp_if_missing(&mut envelope);
+        assert_eq!(Priority::of(&envelope), Some(Priority::High));
+
+        assert_eq!(Priority::take(&mut envelope), Some(Priority::High));
+        assert_eq!(envelope.tags.map(|tags| tags.len()), Some(0));
+    }
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    telemetry::{EventTelemetry, LongRunningOperation, Telemetry, Tracker},
+    time,
+};
+
+/// A name of a property that contains an operation id a checkpoint belongs to.
+const OPERATION_ID_PROPERTY: &str = "operationId";
+
+/// A name of a property and a measurement that contain progress of an operation in percent.
+const PROGRESS_PROPERTY: &str = "progress";
+
+/// A name of a property that contains a phase an operation is in.
+const PHASE_PROPERTY: &str = "phase";
+
+/// A name of a measurement that contains the time elapsed since an operation has started in milliseconds.
+const ELAPSED_MEASUREMENT: &str = "elapsed";
+
+/// Reports progress of an operation that runs for a long time, e.g. a batch job that lasts for
+/// hours, with periodic checkpoint events.
+///
+/// An application reports progress as often as it likes, but a checkpoint event is only submitted
+/// when a given period has elapsed or progress has advanced by a given number of percentage points
+/// since the previous checkpoint, or when the operation enters another phase. Every checkpoint
+/// carries an `operationId`, `progress` and `phase` properties, the progress and the time elapsed
+/// since the operation has started as `progress` and `elapsed` measurements, and it is correlated
+/// with the operation, so progress of jobs can be charted on the portal the same way.
+///
+/// By default a checkpoint is submitted every minute or every 10 percentage points.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use std::time::Duration;
+///
+/// use appinsights::telemetry::OperationProgressTracker;
+///
+/// let mut progress = OperationProgressTracker::start(&client, "nightly import")
+///     .every(Duration::from_secs(5 * 60))
+///     .every_percent(5.0);
+///
+/// progress.set_phase("download");
+/// for file in 0..100 {
+///     // ... import a file
+///     progress.report(file as f64 + 1.0);
+/// }
+/// progress.finish();
+/// ```
+pub struct OperationProgressTracker {
+    client: Box<dyn Tracker>,
+    operation: LongRunningOperation,
+    phase: Option<String>,
+    period: Duration,
+    step: f64,
+    last: Option<Checkpoint>,
+}
+
+/// The previous checkpoint submitted for an operation.
+struct Checkpoint {
+    time: DateTime<Utc>,
+    progress: f64,
+    phase: Option<String>,
+}
+
+impl OperationProgressTracker {
+    /// Starts a new operation with a given name and reports its progress to a given client.
+    pub fn start<C>(client: &C, name: impl Into<String>) -> Self
+    where
+        C: Tracker + Clone + 'static,
+    {
+        Self::new(client, LongRunningOperation::start(name))
+    }
+
+    /// Reports progress of a given operation to a given client, e.g. of an operation resumed
+    /// after an application restart, so checkpoints are correlated with it.
+    pub fn new<C>(client: &C, operation: LongRunningOperation) -> Self
+    where
+        C: Tracker + Clone + 'static,
+    {
+        Self {
+            client: Box::new(client.clone()),
+            operation,
+            phase: None,
+            period: Duration::from_secs(60),
+            step: 10.0,
+            last: None,
+        }
+    }
+
+    /// Submits a checkpoint at least once per given period while progress is reported.
+    pub fn every(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Submits a checkpoint whenever progress advances by a given number of percentage points.
+    pub fn every_percent(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Returns the operation which progress is reported.
+    pub fn operation(&self) -> &LongRunningOperation {
+        &self.operation
+    }
+
+    /// Returns a phase the operation is in, if any.
+    pub fn phase(&self) -> Option<&str> {
+        self.phase.as_deref()
+    }
+
+    /// Sets a phase the operation is in. The next reported progress is submitted as a checkpoint
+    /// if the phase has changed.
+    pub fn set_phase(&mut self, phase: impl Into<String>) {
+        self.phase = Some(phase.into());
+    }
+
+    /// Reports progress of the operation in percent and submits a checkpoint if one is due.
+    /// Returns `true` if a checkpoint was submitted.
+    pub fn report(&mut self, progress: f64) -> bool {
+        let progress = progress.clamp(0.0, 100.0);
+        let now = time::now();
+        let due = match &self.last {
+            Some(last) => {
+                last.phase != self.phase
+                    || progress - last.progress >= self.step
+                    || (now - last.time).to_std().unwrap_or_default() >= self.period
+            }
+            None => true,
+        };
+        if due {
+            self.submit(progress, now);
+        }
+        due
+    }
+
+    /// Finishes the operation and submits the final checkpoint with the complete progress.
+    pub fn finish(mut self) -> LongRunningOperation {
+        self.submit(100.0, time::now());
+        self.operation
+    }
+
+    /// Submits a checkpoint event with a given progress.
+    fn submit(&mut self, progress: f64, now: DateTime<Utc>) {
+        let mut event = EventTelemetry::new(format!("{} progress", self.operation.name()));
+
+        let properties = event.properties_mut();
+        properties.insert(OPERATION_ID_PROPERTY.into(), self.operation.id().into());
+        properties.insert(PROGRESS_PROPERTY.into(), progress.to_string());
+        if let Some(phase) = &self.phase {
+            properties.insert(PHASE_PROPERTY.into(), phase.clone());
+        }
+
+        let measurements = event.measurements_mut();
+        measurements.insert(PROGRESS_PROPERTY.into(), progress);
+        measurements.insert(
+            ELAPSED_MEASUREMENT.into(),
+            self.operation.elapsed().as_secs_f64() * 1000.0,
+        );
+
+        let mut operation = event.tags_mut().operation_mut();
+        operation.set_id(self.operation.id().into());
+        operation.set_parent_id(self.operation.id().into());
+        operation.set_name(self.operation.name().into());
+
+        self.client.track_boxed(Box::new(event));
+        self.last = Some(Checkpoint {
+            time: now,
+            progress,
+            phase: self.phase.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::TimeZone;
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data, Envelope},
+        telemetry::tests::TestTracker,
+    };
+
+    #[test]
+    fn it_submits_checkpoints_when_progress_advances_by_step() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let events = Arc::new(SegQueue::new());
+        let mut progress = OperationProgressTracker::start(&TestTracker::new(events.clone()), "import");
+
+        let reported: Vec<_> = [0.0, 5.0, 9.9, 10.0, 15.0, 20.5]
+            .iter()
+            .map(|p| progress.report(*p))
+            .collect();
+        assert_eq!(reported, vec![true, false, false, true, false, true]);
+        assert_eq!(progresses(&events), vec!["0", "10", "20.5"]);
+        time::reset();
+    }
+
+    #[test]
+    fn it_submits_checkpoints_when_period_elapses_or_phase_changes() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let events = Arc::new(SegQueue::new());
+        let mut progress =
+            OperationProgressTracker::start(&TestTracker::new(events.clone()), "import").every(Duration::from_secs(30));
+
+        assert!(progress.report(1.0));
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 29, 0));
+        assert!(!progress.report(2.0));
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 30, 0));
+        assert!(progress.report(3.0));
+
+        progress.set_phase("upload");
+        assert!(progress.report(3.5));
+        assert!(!progress.report(4.0));
+        assert_eq!(progresses(&events), vec!["1", "3", "3.5"]);
+        time::reset();
+    }
+
+    #[test]
+    fn it_submits_checkpoints_with_standard_properties() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let events = Arc::new(SegQueue::new());
+        let mut progress = OperationProgressTracker::start(&TestTracker::new(events.clone()), "import");
+        let id = progress.operation().id().to_string();
+        progress.set_phase("download");
+        progress.report(42.0);
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 10, 0, 0));
+        progress.finish();
+
+        events.pop().unwrap();
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.clone().unwrap_or_default();
+        assert_eq!(tags.get("ai.operation.id"), Some(&id));
+        assert_eq!(tags.get("ai.operation.name"), Some(&"import".to_string()));
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                assert_eq!(data.name, "import progress");
+                let properties = data.properties.unwrap_or_default();
+                assert_eq!(properties.get("operationId"), Some(&id));
+                assert_eq!(properties.get("progress"), Some(&"100".to_string()));
+                assert_eq!(properties.get("phase"), Some(&"download".to_string()));
+                let measurements = data.measurements.unwrap_or_default();
+                assert_eq!(measurements.get("progress"), Some(&100.0));
+                assert_eq!(measurements.get("elapsed"), Some(&600000.0));
+            }
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+        time::reset();
+    }
+
+    fn progresses(events: &SegQueue<Envelope>) -> Vec<String> {
+        let mut progresses = Vec::new();
+        while let Some(envelope) = events.pop() {
+            if let Some(Base::Data(Data::EventData(data))) = envelope.data {
+                progresses.push(data.properties.unwrap_or_default()["progress"].clone());
+            }
+        }
+        progresses
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use serde::Serialize;
+
+use crate::{
+    contracts::StringMap,
+    telemetry::{tags::overlay, Measurements},
+};
+
+/// Maximum length of a property value accepted by the server.
+const MAX_VALUE_LENGTH: usize = 8192;
+
+/// Contains all properties for telemetry to submit.
+///
+/// Clones share the same properties until one of them is modified, so a client telemetry context
+/// is cloned for every telemetry item tracked with it without copying its properties. An envelope
+/// of the item keeps sharing them and overlays properties of the item over them.
+#[derive(Debug, Clone, Default)]
+pub struct Properties(Arc<BTreeMap<String, String>>);
+
+impl Properties {
+    /// Combines all properties from two objects. It can override some properties with values found
+    /// in the second properties bag.
+    pub fn combine(a: Properties, b: Properties) -> Self {
+        Self(overlay(a.0, b.0))
+    }
+
+    /// Overlays properties of a telemetry item over properties of a context into properties to
+    /// submit. Properties of the context stay shared with it instead of being copied for every item.
+    pub(crate) fn submitted(context: Properties, telemetry: Properties) -> StringMap {
+        StringMap::overlay(context.0, telemetry.into())
+    }
+
+    /// Overlays properties of a telemetry item along with units of its measurements over properties
+    /// of a context into properties to submit.
+    pub(crate) fn submitted_with_units(
+        context: Properties,
+        telemetry: Properties,
+        measurements: &Measurements,
+    ) -> StringMap {
+        Properties::submitted(context, Properties::combine(telemetry, measurements.unit_properties()))
+    }
+}
+
+impl From<BTreeMap<String, String>> for Properties {
+    fn from(properties: BTreeMap<String, String>) -> Self {
+        Self(Arc::new(properties))
+    }
+}
+
+impl From<StringMap> for Properties {
+    fn from(properties: StringMap) -> Self {
+        Self::from(BTreeMap::from(properties))
+    }
+}
+
+impl From<Properties> for StringMap {
+    fn from(properties: Properties) -> Self {
+        StringMap::overlay(properties.0, BTreeMap::default())
+    }
+}
+
+impl From<Properties> for BTreeMap<String, String> {
+    fn from(properties: Properties) -> Self {
+        Arc::try_unwrap(properties.0).unwrap_or_else(|properties| (*properties).clone())
+    }
+}
+
+impl Deref for Properties {
+    type Target = BTreeMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Properties {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+/// Inserts structured values into custom properties in a consistent format.
+///
+/// It is implemented for [`Properties`] and any `BTreeMap<String, String>`.
+///
+/// # Examples
+/// ```rust
+/// use appinsights::telemetry::{EventTelemetry, PropertiesExt, Telemetry};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Order {
+///     id: u64,
+///     items: Vec<&'static str>,
+/// }
+///
+/// let mut telemetry = EventTelemetry::new("order placed");
+/// let properties = telemetry.properties_mut();
+/// properties.insert_json("order", &Order { id: 42, items: vec!["book"] }).unwrap();
+/// properties.insert_value("express", true);
+///
+/// assert_eq!(properties["order"], r#"{"id":42,"items":["book"]}"#);
+/// assert_eq!(properties["express"], "true");
+/// ```
+pub trait PropertiesExt {
+    /// Serializes a value to compact JSON and inserts it as a property. Fails if a value cannot be
+    /// serialized or its JSON is longer than 8192 characters the server accepts, so it is never
+    /// submitted cut in the middle.
+    fn insert_json<T>(&mut self, key: impl Into<String>, value: &T) -> Result<(), InvalidPropertyValue>
+    where
+        T: Serialize + ?Sized;
+
+    /// Inserts a typed value as a property formatted as [`PropertyValue`] describes.
+    fn insert_value(&mut self, key: impl Into<String>, value: impl Into<PropertyValue>);
+}
+
+impl PropertiesExt for BTreeMap<String, String> {
+    fn insert_json<T>(&mut self, key: impl Into<String>, value: &T) -> Result<(), InvalidPropertyValue>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = key.into();
+        let json = serde_json::to_string(value)
+            .map_err(|err| InvalidPropertyValue(format!("{} cannot be serialized: {}", key, err)))?;
+
+        let len = json.chars().count();
+        if len > MAX_VALUE_LENGTH {
+            return Err(InvalidPropertyValue(format!(
+                "{} is {} characters long, at most {} allowed",
+                key, len, MAX_VALUE_LENGTH
+            )));
+        }
+
+        self.insert(key, json);
+        Ok(())
+    }
+
+    fn insert_value(&mut self, key: impl Into<String>, value: impl Into<PropertyValue>) {
+        self.insert(key.into(), value.into().to_string());
+    }
+}
+
+/// A typed value of a custom property. Properties are submitted as strings, so each kind of value
+/// is formatted the same way regardless of where it is inserted: strings as is, booleans and
+/// numbers in their shortest form, and structured values as compact JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    /// A text value.
+    String(String),
+
+    /// A boolean value.
+    Bool(bool),
+
+    /// An integer value.
+    Integer(i64),
+
+    /// A floating point value.
+    Float(f64),
+
+    /// A structured value.
+    Json(serde_json::Value),
+}
+
+impl Display for PropertyValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PropertyValue::String(value) => f.write_str(value),
+            PropertyValue::Bool(value) => write!(f, "{}", value),
+            PropertyValue::Integer(value) => write!(f, "{}", value),
+            PropertyValue::Float(value) => write!(f, "{}", value),
+            PropertyValue::Json(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<String> for PropertyValue {
+    fn from(value: String) -> Self {
+        PropertyValue::String(value)
+    }
+}
+
+impl From<&str> for PropertyValue {
+    fn from(value: &str) -> Self {
+        PropertyValue::String(value.into())
+    }
+}
+
+impl From<bool> for PropertyValue {
+    fn from(value: bool) -> Self {
+        PropertyValue::Bool(value)
+    }
+}
+
+impl From<i64> for PropertyValue {
+    fn from(value: i64) -> Self {
+        PropertyValue::Integer(value)
+    }
+}
+
+impl From<i32> for PropertyValue {
+    fn from(value: i32) -> Self {
+        PropertyValue::Integer(value.into())
+    }
+}
+
+impl From<u32> for PropertyValue {
+    fn from(value: u32) -> Self {
+        PropertyValue::Integer(value.into())
+    }
+}
+
+impl From<f64> for PropertyValue {
+    fn from(value: f64) -> Self {
+        PropertyValue::Float(value)
+    }
+}
+
+impl From<serde_json::Value> for PropertyValue {
+    fn from(value: serde_json::Value) -> Self {
+        PropertyValue::Json(value)
+    }
+}
+
+/// An error returned when a structured value cannot be inserted as a property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPropertyValue(String);
+
+impl Display for InvalidPropertyValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid property value: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidPropertyValue {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_shares_properties_until_modified() {
+        let mut properties = Properties::default();
+        properties.insert("foo".into(), "foo".into());
+
+        let mut clone = properties.clone();
+        assert!(Arc::ptr_eq(&properties.0, &clone.0));
+
+        clone.insert("bar".into(), "bar".into());
+        assert!(!Arc::ptr_eq(&properties.0, &clone.0));
+        assert_eq!(properties.len(), 1);
+        assert_eq!(BTreeMap::from(Properties::combine(properties, clone)).len(), 2);
+    }
+
+    #[test]
+    fn it_shares_context_properties_with_submitted_properties() {
+        let mut context = Properties::default();
+        context.insert("foo".into(), "foo".into());
+        let mut telemetry = Properties::default();
+        telemetry.insert("bar".into(), "bar".into());
+
+        let submitted = Properties::submitted(context.clone(), telemetry);
+
+        assert_eq!(Arc::strong_count(&context.0), 2);
+        assert_eq!(submitted["foo"], "foo");
+        assert_eq!(submitted["bar"], "bar");
+    }
+
+    #[test]
+    fn it_inserts_json_properties() {
+        let mut properties = Properties::default();
+
+        properties.insert_json("tags", &["a", "b"]).unwrap();
+        properties
+            .insert_json("limits", &json!({ "max": 5, "min": null }))
+            .unwrap();
+
+        assert_eq!(properties["tags"], r#"["a","b"]"#);
+        assert_eq!(properties["limits"], r#"{"max":5,"min":null}"#);
+    }
+
+    #[test]
+    fn it_rejects_json_properties_too_long() {
+        let mut properties = Properties::default();
+
+        assert!(properties.insert_json("text", &"a".repeat(8190)).is_ok());
+        assert!(properties.insert_json("text", &"a".repeat(8191)).is_err());
+        assert_eq!(properties["text"].len(), 8192);
+    }
+
+    #[test]
+    fn it_rejects_json_properties_that_cannot_be_serialized() {
+        let mut properties = Properties::default();
+        let map: HashMap<_, _> = vec![((1, 2), "tuple key")].into_iter().collect();
+
+        assert!(properties.insert_json("map", &map).is_err());
+        assert!(!properties.contains_key("map"));
+    }
+
+    #[test]
+    fn it_formats_typed_values() {
+        let mut properties = Properties::default();
+
+        properties.insert_value("text", "value");
+        properties.insert_value("flag", false);
+        properties.insert_value("count", -3);
+        properties.insert_value("ratio", 0.5);
+        properties.insert_value("json", json!([1, "two"]));
+
+        assert_eq!(properties["text"], "value");
+        assert_eq!(properties["flag"], "false");
+        assert_eq!(properties["count"], "-3");
+        assert_eq!(properties["ratio"], "0.5");
+        assert_eq!(properties["json"], r#"[1,"two"]"#);
+    }
+}
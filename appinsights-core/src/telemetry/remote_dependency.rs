@@ -0,0 +1,656 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::{names, Base, Data, Envelope, RemoteDependencyData},
+    telemetry::{envelope, ContextTags, Measurements, Properties, Telemetry, Timer, Tracker},
+    time::{self, Duration},
+};
+
+/// Represents interactions of the monitored component with a remote component/service like SQL or an HTTP endpoint.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{Telemetry, RemoteDependencyTelemetry};
+/// use std::time::Duration;
+///
+/// // create a telemetry item
+/// let mut telemetry = RemoteDependencyTelemetry::new(
+///     "GET https://api.github.com/dmolokanov/appinsights-rs",
+///     "HTTP",
+///     Duration::from_secs(2),
+///     "api.github.com",
+///     true,
+/// );
+///
+/// // attach custom properties, measurements and context tags
+/// telemetry.properties_mut().insert("component".to_string(), "data_processor".to_string());
+/// telemetry.tags_mut().insert("os_version".to_string(), "linux x86_64".to_string());
+/// telemetry.measurements_mut().insert("body_size".to_string(), 115.0);
+///
+/// // submit telemetry item to server
+/// client.track(telemetry);
+/// ```
+#[derive(Debug)]
+pub struct RemoteDependencyTelemetry {
+    /// Identifier of a dependency call instance.
+    /// It is used for correlation with the request telemetry item corresponding to this dependency call.
+    id: Option<String>,
+
+    /// Name of the command that initiated this dependency call. Low cardinality value.
+    /// Examples are stored procedure name and URL path template.
+    name: String,
+
+    /// Duration of the remote call.
+    duration: Duration,
+
+    /// Result code of a dependency call.
+    /// Examples are SQL error code and HTTP status code.
+    result_code: Option<String>,
+
+    /// Indication of successful or unsuccessful call.
+    success: bool,
+
+    /// Command initiated by this dependency call.
+    /// Examples are SQL statement and HTTP URL's with all the query parameters.
+    data: Option<String>,
+
+    /// Dependency type name. Very low cardinality.
+    /// Examples are SQL, Azure table and HTTP.
+    dependency_type: String,
+
+    /// Target site of a dependency call.
+    /// Examples are server name, host address.
+    target: String,
+
+    /// The time stamp when this telemetry was measured.
+    timestamp: DateTime<Utc>,
+
+    /// Custom properties.
+    properties: Properties,
+
+    /// Telemetry context containing extra, optional tags.
+    tags: ContextTags,
+
+    /// Custom measurements.
+    measurements: Measurements,
+}
+
+impl RemoteDependencyTelemetry {
+    /// Creates a new telemetry item with specified name, dependency type, target site and success status.
+    pub fn new(
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        duration: StdDuration,
+        target: impl Into<String>,
+        success: bool,
+    ) -> Self {
+        Self {
+            id: Option::default(),
+            name: name.into(),
+            duration: duration.into(),
+            result_code: Option::default(),
+            success,
+            data: Option::default(),
+            dependency_type: dependency_type.into(),
+            target: target.into(),
+            timestamp: time::now(),
+            properties: Properties::default(),
+            tags: ContextTags::default(),
+            measurements: Measurements::default(),
+        }
+    }
+
+    /// Starts timing a dependency call with specified name, dependency type and target site. The
+    /// returned [`DependencyTimer`] measures the call duration and submits the telemetry item with a
+    /// given client once the call is [finished](DependencyTimer::finish). A timer dropped before
+    /// that, e.g. when `?` returns early from a function awaiting the call, submits the call as
+    /// failed.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::RemoteDependencyTelemetry;
+    ///
+    /// # async fn fetch() -> Result<String, Box<dyn std::error::Error>> { Ok(String::default()) }
+    /// # async fn run(client: &TelemetryClient) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut timer = RemoteDependencyTelemetry::start(client, "GET /users", "HTTP", "api.example.com");
+    /// timer.telemetry_mut().set_data("https://api.example.com/users");
+    ///
+    /// // submitted as failed if fetching the users returns an error
+    /// fetch().await?;
+    ///
+    /// timer.finish(true, "200");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn start<C>(
+        client: &C,
+        name: impl Into<String>,
+        dependency_type: impl Into<String>,
+        target: impl Into<String>,
+    ) -> DependencyTimer
+    where
+        C: Tracker + Clone + 'static,
+    {
+        DependencyTimer(Timer::start(
+            client,
+            Self::new(name, dependency_type, StdDuration::default(), target, false),
+        ))
+    }
+
+    /// Creates a new telemetry item that represents a SQL command executed against a database on
+    /// a given server. It sets dependency type to `SQL` and uses `<server> | <database>` as a target
+    /// so that calls are shown against the right node of the application map. The command text is
+    /// submitted as dependency data. It is truncated when it exceeds the maximum length accepted by
+    /// the service. Call [`sanitize_sql`](#method.sanitize_sql) to strip literal values from it.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::RemoteDependencyTelemetry;
+    /// use std::time::Duration;
+    ///
+    /// let mut telemetry = RemoteDependencyTelemetry::sql(
+    ///     "db.example.com",
+    ///     "users",
+    ///     "SELECT * FROM users WHERE name = 'John' AND age > 42",
+    ///     Duration::from_millis(15),
+    ///     true,
+    /// );
+    ///
+    /// // submit "SELECT * FROM users WHERE name = ? AND age > ?" instead
+    /// telemetry.sanitize_sql();
+    ///
+    /// client.track(telemetry);
+    /// ```
+    pub fn sql(
+        server: impl Into<String>,
+        database: impl Into<String>,
+        command: impl Into<String>,
+        duration: StdDuration,
+        success: bool,
+    ) -> Self {
+        let database = database.into();
+        let target = format!("{} | {}", server.into(), database);
+
+        let mut telemetry = Self::new(database, "SQL", duration, target, success);
+        telemetry.set_data(truncate(command.into(), MAX_DATA_LENGTH));
+        telemetry
+    }
+
+    /// Normalizes the `data` field as a SQL command text. String and numeric literals are replaced
+    /// with `?` placeholders, comments are removed and whitespaces are collapsed. It prevents
+    /// sensitive values from being submitted and groups the same commands with different arguments.
+    pub fn sanitize_sql(&mut self) {
+        if let Some(data) = &self.data {
+            self.data = Some(sanitize_sql(data));
+        }
+    }
+
+    /// Returns custom measurements to submit with the telemetry item.
+    pub fn measurements(&self) -> &Measurements {
+        &self.measurements
+    }
+
+    /// Returns mutable reference to custom measurements.
+    pub fn measurements_mut(&mut self) -> &mut Measurements {
+        &mut self.measurements
+    }
+
+    /// Returns mutable reference to the timestamp.
+    pub fn timestamp_mut(&mut self) -> &mut DateTime<Utc> {
+        &mut self.timestamp
+    }
+
+    /// Returns the result code of the dependency call.
+    pub fn result_code(&self) -> &Option<String> {
+        &self.result_code
+    }
+
+    /// Returns mutable reference to the result code.
+    pub fn result_code_mut(&mut self) -> &mut Option<String> {
+        &mut self.result_code
+    }
+
+    /// Sets the dependency id. Use this to link other telemetry to this dependency by setting their operation
+    /// parent id to this id.
+    ///
+    /// ```rust,no_run
+    /// # use appinsights::TelemetryClient;
+    /// # use appinsights::telemetry::{RemoteDependencyTelemetry, SeverityLevel, Telemetry, TraceTelemetry};
+    /// # use http::{Method, Uri};
+    /// # use std::time::Duration;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let operation_id = "...".to_string();
+    /// let dependency_id = "...".to_string();
+    ///
+    /// let mut dependency = RemoteDependencyTelemetry::new(
+    ///     "Data processing",
+    ///     "InProc",
+    ///     Duration::from_millis(42),
+    ///     "internal",
+    ///     true,
+    /// );
+    /// dependency.set_id(dependency_id.clone());
+    /// dependency.tags_mut().operation_mut().set_id(operation_id.clone());
+    /// client.track(dependency);
+    ///
+    /// let mut trace = TraceTelemetry::new("Start", SeverityLevel::Information);
+    /// trace.tags_mut().operation_mut().set_id(operation_id);
+    /// trace.tags_mut().operation_mut().set_parent_id(dependency_id);
+    /// client.track(trace);
+    /// ```
+    pub fn set_id(&mut self, id: impl Into<String>) {
+        self.id = Some(id.into());
+    }
+
+    /// Set the `data` field of the dependency trace.
+    pub fn set_data(&mut self, data: impl Into<String>) {
+        self.data = Some(data.into());
+    }
+
+    /// Set the `tags` fields of the telemetry, replacing the existing one.
+    pub fn set_tags(&mut self, tags: ContextTags) {
+        self.tags = tags;
+    }
+}
+
+/// Measures the duration of a dependency call started with
+/// [`RemoteDependencyTelemetry::start`] and submits it when the call finishes.
+pub struct DependencyTimer(Timer<RemoteDependencyTelemetry>);
+
+impl DependencyTimer {
+    /// Returns mutable reference to the telemetry item being timed, e.g. to set the dependency data
+    /// or custom properties before it is submitted.
+    pub fn telemetry_mut(&mut self) -> &mut RemoteDependencyTelemetry {
+        self.0.telemetry_mut().expect("dependency call is finished already")
+    }
+
+    /// Finishes the dependency call with a given success status and result code and submits it.
+    pub fn finish(mut self, success: bool, result_code: impl Into<String>) {
+        self.submit(success, Some(result_code.into()));
+    }
+
+    /// Stamps the telemetry item with the time elapsed since the call has started and submits it.
+    fn submit(&mut self, success: bool, result_code: Option<String>) {
+        self.0.stop(|telemetry, elapsed| {
+            telemetry.duration = elapsed.into();
+            telemetry.success = success;
+            if result_code.is_some() {
+                telemetry.result_code = result_code;
+            }
+        });
+    }
+}
+
+impl Drop for DependencyTimer {
+    fn drop(&mut self) {
+        self.submit(false, None);
+    }
+}
+
+impl Telemetry for RemoteDependencyTelemetry {
+    /// Returns the time when this telemetry was measured.
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp
+    }
+
+    /// Returns custom properties to submit with the telemetry item.
+    fn properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    /// Returns mutable reference to custom properties.
+    fn properties_mut(&mut self) -> &mut Properties {
+        &mut self.properties
+    }
+
+    /// Returns context data containing extra, optional tags. Overrides values found on client telemetry context.
+    fn tags(&self) -> &ContextTags {
+        &self.tags
+    }
+
+    /// Returns mutable reference to custom tags.
+    fn tags_mut(&mut self) -> &mut ContextTags {
+        &mut self.tags
+    }
+}
+
+impl From<(TelemetryContext, RemoteDependencyTelemetry)> for Envelope {
+    fn from((context, telemetry): (TelemetryContext, RemoteDependencyTelemetry)) -> Self {
+        Self {
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                name: telemetry.name,
+                id: telemetry.id,
+                result_code: telemetry.result_code,
+                duration: telemetry.duration.to_string(),
+                success: Some(telemetry.success),
+                data: telemetry.data,
+                target: Some(telemetry.target),
+                type_: Some(telemetry.dependency_type),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
+                measurements: Some(telemetry.measurements.into()),
+                ..RemoteDependencyData::default()
+            }))),
+            ..envelope(names::REMOTE_DEPENDENCY, telemetry.timestamp)
+        }
+    }
+}
+
+/// Maximum length of the dependency data accepted by the service.
+const MAX_DATA_LENGTH: usize = 8192;
+
+/// Truncates a text to a given number of characters. It ends with `...` to indicate that the text
+/// was truncated.
+fn truncate(text: String, max_len: usize) -> String {
+    if text.chars().count() <= max_len {
+        text
+    } else {
+        let mut truncated: String = text.chars().take(max_len - 3).collect();
+        truncated.push_str("...");
+        truncated
+    }
+}
+
+/// Replaces literals in a SQL command text with `?` placeholders, removes comments and collapses whitespaces.
+/// Positional parameters such as `$1` are preserved.
+fn sanitize_sql(command: &str) -> String {
+    let mut sanitized = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // string literal with '' as an escaped quote
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    if c == '\'' && chars.next_if_eq(&'\'').is_none() {
+                        break;
+                    }
+                }
+                sanitized.push('?');
+            }
+            // single line comment
+            '-' if chars.next_if_eq(&'-').is_some() => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                sanitized.push(' ');
+            }
+            // multi line comment
+            '/' if chars.next_if_eq(&'*').is_some() => {
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.next_if_eq(&'/').is_some() {
+                        break;
+                    }
+                }
+                sanitized.push(' ');
+            }
+            // numeric literal that is not a part of an identifier
+            c if c.is_ascii_digit() && !sanitized.ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '$') => {
+                while chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.').is_some() {}
+                sanitized.push('?');
+            }
+            c if c.is_whitespace() => sanitized.push(' '),
+            c => sanitized.push(c),
+        }
+    }
+
+    sanitized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use chrono::TimeZone;
+    use crossbeam_queue::SegQueue;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{contracts::StringMap, telemetry::tests::TestTracker};
+
+    #[test]
+    fn it_creates_sql_dependency() {
+        let telemetry = RemoteDependencyTelemetry::sql(
+            "db.example.com",
+            "users",
+            "SELECT * FROM users",
+            StdDuration::from_millis(15),
+            true,
+        );
+
+        assert_eq!(telemetry.name, "users");
+        assert_eq!(telemetry.dependency_type, "SQL");
+        assert_eq!(telemetry.target, "db.example.com | users");
+        assert_eq!(telemetry.data, Some("SELECT * FROM users".into()));
+    }
+
+    #[test]
+    fn it_truncates_long_sql_command() {
+        let command = format!("SELECT * FROM users WHERE id IN ({})", vec!["1"; 5000].join(","));
+
+        let telemetry = RemoteDependencyTelemetry::sql("server", "db", command, StdDuration::from_millis(1), true);
+
+        let data = telemetry.data.unwrap();
+        assert_eq!(data.chars().count(), MAX_DATA_LENGTH);
+        assert!(data.ends_with("..."));
+    }
+
+    #[test_case("SELECT * FROM users WHERE name = 'John'", "SELECT * FROM users WHERE name = ?" ; "string")]
+    #[test_case("SELECT * FROM users WHERE name = 'O''Brien' AND id = 1", "SELECT * FROM users WHERE name = ? AND id = ?" ; "escaped quote")]
+    #[test_case("SELECT * FROM t1 WHERE price > 42.5 LIMIT 10", "SELECT * FROM t1 WHERE price > ? LIMIT ?" ; "numbers")]
+    #[test_case("SELECT col_1 FROM t WHERE id=0x1F", "SELECT col_1 FROM t WHERE id=?" ; "identifiers with digits")]
+    #[test_case("SELECT *\n  FROM users -- all of them\n  WHERE id = $1", "SELECT * FROM users WHERE id = $1" ; "line comment")]
+    #[test_case("SELECT /* secret */ * FROM users", "SELECT * FROM users" ; "block comment")]
+    fn it_sanitizes_sql_command(command: &str, expected: &str) {
+        let mut telemetry = RemoteDependencyTelemetry::sql("server", "db", command, StdDuration::from_millis(1), true);
+
+        telemetry.sanitize_sql();
+
+        assert_eq!(telemetry.data, Some(expected.into()));
+    }
+
+    #[test]
+    fn it_uses_specified_id() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+
+        let context = TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            "GET https://example.com/main.html",
+            "HTTP",
+            StdDuration::from_secs(2),
+            "example.com",
+            true,
+        );
+        telemetry.set_id("specified-id");
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.RemoteDependency".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(StringMap::default()),
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                id: Some("specified-id".into()),
+                name: "GET https://example.com/main.html".into(),
+                duration: "0.00:00:02.0000000".into(),
+                success: Some(true),
+                target: Some("example.com".into()),
+                type_: Some("HTTP".into()),
+                properties: Some(StringMap::default()),
+                measurements: Some(BTreeMap::default()),
+                ..RemoteDependencyData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test]
+    fn it_submits_timed_dependency_when_finished() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+        let mut timer = RemoteDependencyTelemetry::start(&client, "GET /users", "HTTP", "example.com");
+        timer.telemetry_mut().set_data("https://example.com/users");
+        // the wall clock is adjusted while the call is in progress
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(4, 4, 5, 800));
+        timer.finish(true, "200");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.time, "2019-01-02T03:04:05.800Z");
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert!(data.duration.starts_with("0.00:00:00."), "{}", data.duration);
+                assert_eq!(data.success, Some(true));
+                assert_eq!(data.result_code, Some("200".into()));
+                assert_eq!(data.data, Some("https://example.com/users".into()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_submits_failed_dependency_when_timer_dropped() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let call = || -> Result<(), &str> {
+            let timer = RemoteDependencyTelemetry::start(&client, "GET /users", "HTTP", "example.com");
+            Err("connection refused")?;
+            timer.finish(true, "200");
+            Ok(())
+        };
+        assert!(call().is_err());
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.success, Some(false));
+                assert_eq!(data.result_code, None);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert!(events.is_empty());
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TestTracker {
+        TestTracker::new(events)
+    }
+
+    #[test]
+    fn it_overrides_properties_from_context() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.properties_mut().insert("test".into(), "ok".into());
+        context.properties_mut().insert("no-write".into(), "fail".into());
+
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            "GET https://example.com/main.html",
+            "HTTP",
+            StdDuration::from_secs(2),
+            "example.com",
+            true,
+        );
+        telemetry.properties_mut().insert("no-write".into(), "ok".into());
+        telemetry.measurements_mut().insert("latency".into(), 200.0);
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.RemoteDependency".into(),
+            time: "2019-01-02T03:04:05.800Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some(StringMap::default()),
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                name: "GET https://example.com/main.html".into(),
+                duration: "0.00:00:02.0000000".into(),
+                success: Some(true),
+                target: Some("example.com".into()),
+                type_: Some("HTTP".into()),
+                properties: Some({
+                    let mut properties = StringMap::default();
+                    properties.insert("test".into(), "ok".into());
+                    properties.insert("no-write".into(), "ok".into());
+                    properties
+                }),
+                measurements: Some({
+                    let mut measurements = BTreeMap::default();
+                    measurements.insert("latency".into(), 200.0);
+                    measurements
+                }),
+                ..RemoteDependencyData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+
+    #[test]
+    fn it_overrides_tags_from_context() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 700));
+
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.tags_mut().insert("test".into(), "ok".into());
+        context.tags_mut().insert("no-write".into(), "fail".into());
+
+        let mut telemetry = RemoteDependencyTelemetry::new(
+            "GET https://example.com/main.html",
+            "HTTP",
+            StdDuration::from_secs(2),
+            "example.com",
+            true,
+        );
+        telemetry.tags_mut().insert("no-write".into(), "ok".into());
+
+        let envelop = Envelope::from((context, telemetry));
+
+        let expected = Envelope {
+            name: "Microsoft.ApplicationInsights.RemoteDependency".into(),
+            time: "2019-01-02T03:04:05.700Z".into(),
+            i_key: Some("instrumentation".into()),
+            tags: Some({
+                let mut tags = StringMap::default();
+                tags.insert("test".into(), "ok".into());
+                tags.insert("no-write".into(), "ok".into());
+                tags
+            }),
+            data: Some(Base::Data(Data::RemoteDependencyData(RemoteDependencyData {
+                name: "GET https://example.com/main.html".into(),
+                duration: "0.00:00:02.0000000".into(),
+                success: Some(true),
+                target: Some("example.com".into()),
+                type_: Some("HTTP".into()),
+                properties: Some(StringMap::default()),
+                measurements: Some(BTreeMap::default()),
+                ..RemoteDependencyData::default()
+            }))),
+            ..Envelope::default()
+        };
+
+        assert_eq!(envelop, expected)
+    }
+}
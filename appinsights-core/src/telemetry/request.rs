@@ -1,12 +1,13 @@
-use std::{str::FromStr, time::Duration as StdDuration};
+use std::{collections::BTreeMap, str::FromStr, time::Duration as StdDuration};
 
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 use http::{StatusCode, Uri};
 
 use crate::{
     context::TelemetryContext,
-    contracts::{Base, Data, Envelope, RequestData},
-    telemetry::{ContextTags, Measurements, Properties, Telemetry},
+    contracts::{names, Base, Data, Envelope, RequestData},
+    processor::RequestSuccess,
+    telemetry::{envelope, ContextTags, Measurements, OperationTimeline, Properties, Telemetry, Timer, Tracker},
     time::{self, Duration},
     uuid,
 };
@@ -59,6 +60,9 @@ pub struct RequestTelemetry {
     /// Results of a request execution. HTTP status code for HTTP requests.
     response_code: String,
 
+    /// Indication of successful or unsuccessful call that overrides the one derived from the response code.
+    success: Option<bool>,
+
     /// The time stamp when this telemetry was measured.
     timestamp: DateTime<Utc>,
 
@@ -103,6 +107,7 @@ impl RequestTelemetry {
             uri,
             duration: duration.into(),
             response_code: response_code.into(),
+            success: None,
             timestamp: time::now(),
             properties: Properties::default(),
             tags,
@@ -110,6 +115,35 @@ impl RequestTelemetry {
         }
     }
 
+    /// Starts timing a request with specified name and URL. The returned [`RequestTimer`] measures
+    /// the time spent to serve the request and submits the telemetry item with a given client once
+    /// the request is [finished](RequestTimer::finish) with a response code. A timer dropped before
+    /// that, e.g. when a handler panics, submits the request as failed with a `500` response code.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{RequestTelemetry, Telemetry};
+    ///
+    /// let uri = "https://example.com/users/42".parse().unwrap();
+    /// let mut timer = RequestTelemetry::start(&client, "GET /users/{id}", uri);
+    /// timer.telemetry_mut().properties_mut().insert("tenant".to_string(), "contoso".to_string());
+    ///
+    /// // ... serve the request
+    ///
+    /// timer.finish("200");
+    /// ```
+    pub fn start<C>(client: &C, name: impl Into<String>, uri: Uri) -> RequestTimer
+    where
+        C: Tracker + Clone + 'static,
+    {
+        RequestTimer(Timer::start(
+            client,
+            Self::new(name.into(), uri, StdDuration::default(), ""),
+        ))
+    }
+
     /// Returns custom measurements to submit with the telemetry item.
     pub fn measurements(&self) -> &Measurements {
         &self.measurements
@@ -125,13 +159,31 @@ impl RequestTelemetry {
         &mut self.timestamp
     }
 
-    /// Returns an indication of successful or unsuccessful call.
+    /// Returns an indication of successful or unsuccessful call. Unless it is overridden, a call
+    /// with a response code below 400 or 401 is considered successful.
+    ///
+    /// Criteria configured with `TelemetryConfig::builder().request_success(..)` are not known to a
+    /// telemetry item, so they are applied only when the item is submitted and an indication sent
+    /// may differ from the one returned here.
     pub fn is_success(&self) -> bool {
-        if let Ok(response_code) = StatusCode::from_str(&self.response_code) {
-            response_code < StatusCode::BAD_REQUEST || response_code == StatusCode::UNAUTHORIZED
-        } else {
-            true
-        }
+        self.decide_success(&RequestSuccess::default())
+    }
+
+    /// Decides whether a call succeeded, i.e. by an overridden indication, or by given criteria, or
+    /// by a response code otherwise.
+    fn decide_success(&self, criteria: &RequestSuccess) -> bool {
+        self.success.unwrap_or_else(|| {
+            criteria
+                .decide(&self.uri, &self.response_code)
+                .unwrap_or_else(|| is_success_code(&self.response_code))
+        })
+    }
+
+    /// Overrides an indication of successful or unsuccessful call, e.g. for protocols which
+    /// response codes are not HTTP status codes or for a single call that is expected to fail. It
+    /// takes precedence over criteria configured with `TelemetryConfig::builder().request_success(..)`.
+    pub fn set_success(&mut self, success: bool) {
+        self.success = Some(success);
     }
 
     /// Sets the request id. Use this to link other telemetry to this request by setting their operation
@@ -167,6 +219,44 @@ impl RequestTelemetry {
     pub fn set_id(&mut self, id: impl Into<String>) {
         self.id = Some(id.into());
     }
+
+    /// Attaches time spent between checkpoints of the operation timeline as custom measurements
+    /// in milliseconds. Measurements with the same names are overridden.
+    pub fn set_timeline(&mut self, timeline: OperationTimeline) {
+        let measurements: BTreeMap<String, f64> = Measurements::from(timeline).into();
+        self.measurements.extend(measurements);
+    }
+}
+
+/// Measures the time spent to serve a request started with [`RequestTelemetry::start`] and submits
+/// it when the request finishes.
+pub struct RequestTimer(Timer<RequestTelemetry>);
+
+impl RequestTimer {
+    /// Returns mutable reference to the telemetry item being timed, e.g. to set the request id or
+    /// custom properties before it is submitted.
+    pub fn telemetry_mut(&mut self) -> &mut RequestTelemetry {
+        self.0.telemetry_mut().expect("request is finished already")
+    }
+
+    /// Finishes the request with a given response code and submits it.
+    pub fn finish(mut self, response_code: impl Into<String>) {
+        self.submit(response_code.into());
+    }
+
+    /// Stamps the telemetry item with the time elapsed since the request has started and submits it.
+    fn submit(&mut self, response_code: String) {
+        self.0.stop(|telemetry, elapsed| {
+            telemetry.duration = elapsed.into();
+            telemetry.response_code = response_code;
+        });
+    }
+}
+
+impl Drop for RequestTimer {
+    fn drop(&mut self) {
+        self.submit(StatusCode::INTERNAL_SERVER_ERROR.as_str().into());
+    }
 }
 
 impl Telemetry for RequestTelemetry {
@@ -198,12 +288,11 @@ impl Telemetry for RequestTelemetry {
 
 impl From<(TelemetryContext, RequestTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, RequestTelemetry)) -> Self {
-        let success = telemetry.is_success();
+        let success = telemetry.decide_success(&context.request_success);
         Self {
-            name: "Microsoft.ApplicationInsights.Request".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::RequestData(RequestData {
                 id: telemetry.id.unwrap_or_else(|| uuid::new().as_hyphenated().to_string()),
                 name: Some(telemetry.name),
@@ -211,25 +300,41 @@ impl From<(TelemetryContext, RequestTelemetry)> for Envelope {
                 response_code: telemetry.response_code,
                 success,
                 url: Some(telemetry.uri.to_string()),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
                 measurements: Some(telemetry.measurements.into()),
                 ..RequestData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::REQUEST, telemetry.timestamp)
         }
     }
 }
 
+/// Returns `true` if a response code denotes a successful call unless configured otherwise.
+fn is_success_code(response_code: &str) -> bool {
+    match StatusCode::from_str(response_code) {
+        Ok(code) => code < StatusCode::BAD_REQUEST || code == StatusCode::UNAUTHORIZED,
+        Err(_) => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
-    use std::str::FromStr;
+    use std::{panic::AssertUnwindSafe, str::FromStr, sync::Arc, thread};
 
     use chrono::TimeZone;
+    use crossbeam_queue::SegQueue;
     use http::Method;
 
     use super::*;
-    use crate::uuid::{self, Uuid};
+    use crate::{
+        contracts::StringMap,
+        telemetry::tests::TestTracker,
+        uuid::{self, Uuid},
+    };
 
     #[test]
     fn it_uses_specified_id() {
@@ -251,7 +356,7 @@ mod tests {
             time: "2019-01-02T03:04:05.800Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("ai.operation.name".into(), "GET /main.html".into());
                 tags
             }),
@@ -262,7 +367,7 @@ mod tests {
                 response_code: "200".into(),
                 success: true,
                 url: Some("https://example.com/main.html".into()),
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..RequestData::default()
             }))),
@@ -272,6 +377,99 @@ mod tests {
         assert_eq!(envelop, expected)
     }
 
+    #[test]
+    fn it_decides_success_by_configured_criteria_unless_overridden() {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context
+            .request_success
+            .push("/users".into(), Arc::new(|code| (code == "404").then_some(true)));
+
+        let success = |uri: &str, code: &str, success: Option<bool>| {
+            let mut telemetry = RequestTelemetry::new("GET".into(), uri.parse().unwrap(), StdDuration::default(), code);
+            if let Some(success) = success {
+                telemetry.set_success(success);
+            }
+            match Envelope::from((context.clone(), telemetry)).data {
+                Some(Base::Data(Data::RequestData(data))) => data.success,
+                data => panic!("unexpected data: {:?}", data),
+            }
+        };
+
+        assert!(success("https://example.com/users/42", "404", None));
+        assert!(!success("https://example.com/users/42", "404", Some(false)));
+        assert!(!success("https://example.com/orders/42", "404", None));
+        assert!(!success("https://example.com/users/42", "500", None));
+        assert!(success("https://example.com/orders/42", "401", None));
+
+        // criteria are not known until an item is submitted
+        let mut telemetry = RequestTelemetry::new(
+            "GET".into(),
+            "https://example.com/users/42".parse().unwrap(),
+            StdDuration::default(),
+            "404",
+        );
+        assert!(!telemetry.is_success());
+        telemetry.set_success(true);
+        assert!(telemetry.is_success());
+    }
+
+    #[test]
+    fn it_submits_timed_request_when_finished() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
+        let timer = RequestTelemetry::start(
+            &client,
+            "GET /users/{id}",
+            "https://example.com/users/42".parse().unwrap(),
+        );
+        // the wall clock is adjusted back while the request is being served
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(2, 4, 5, 800));
+        thread::sleep(StdDuration::from_millis(20));
+        timer.finish("404");
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.time, "2019-01-02T03:04:05.800Z");
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /users/{id}".into()));
+                let duration = data.duration.strip_prefix("0.00:00:00.").unwrap();
+                assert!(duration >= "0200000", "{}", data.duration);
+                assert_eq!(data.response_code, "404");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_submits_failed_request_when_handler_panics() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let handler = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let _timer = RequestTelemetry::start(&client, "GET /", "https://example.com/".parse().unwrap());
+            panic!("handler failed");
+        }));
+        assert!(handler.is_err());
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.response_code, "500");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert!(events.is_empty());
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TestTracker {
+        TestTracker::new(events)
+    }
+
     #[test]
     fn it_overrides_properties_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 800));
@@ -296,7 +494,7 @@ mod tests {
             time: "2019-01-02T03:04:05.800Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("ai.operation.name".into(), "GET /main.html".into());
                 tags
             }),
@@ -308,7 +506,7 @@ mod tests {
                 success: true,
                 url: Some("https://example.com/main.html".into()),
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -326,6 +524,22 @@ mod tests {
         assert_eq!(envelop, expected)
     }
 
+    #[test]
+    fn it_attaches_timeline_checkpoints_as_measurements() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 0));
+        let mut timeline = OperationTimeline::start();
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 300));
+        timeline.checkpoint("fetched-data");
+
+        let uri: Uri = "https://example.com/main.html".parse().unwrap();
+        let mut telemetry = RequestTelemetry::new("GET /main.html".into(), uri, StdDuration::from_secs(2), "200");
+        telemetry.measurements_mut().insert("latency".into(), 200.0);
+        telemetry.set_timeline(timeline);
+
+        assert_eq!(telemetry.measurements().get("latency"), Some(&200.0));
+        assert_eq!(telemetry.measurements().get("fetched-data"), Some(&300.0));
+    }
+
     #[test]
     fn it_overrides_tags_from_context() {
         time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 700));
@@ -348,7 +562,7 @@ mod tests {
             time: "2019-01-02T03:04:05.700Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("ai.operation.name".into(), "GET /main.html".into());
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
@@ -361,7 +575,7 @@ mod tests {
                 response_code: "200".into(),
                 success: true,
                 url: Some("https://example.com/main.html".into()),
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..RequestData::default()
             }))),
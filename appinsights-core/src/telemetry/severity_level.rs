@@ -0,0 +1,203 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::contracts::SeverityLevel as ContractsSeverityLevel;
+
+/// Defines the level of severity for the event.
+///
+/// Levels are ordered by increasing severity, so `Verbose` is the lowest one and `Critical` is the
+/// highest one. A level is formatted by its name and parsed from it ignoring case. Short forms
+/// `info` and `warn` as well as `debug` and `trace` for `Verbose` are accepted too, so levels of
+/// other logging libraries can be used in configuration files.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::telemetry::SeverityLevel;
+///
+/// let level: SeverityLevel = "warn".parse().unwrap();
+/// assert_eq!(level, SeverityLevel::Warning);
+/// assert_eq!(level.to_string(), "Warning");
+/// assert!(SeverityLevel::Error > level);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SeverityLevel {
+    /// Verbose severity level.
+    Verbose,
+
+    /// Information severity level.
+    Information,
+
+    /// Warning severity level.
+    Warning,
+
+    /// Error severity level.
+    Error,
+
+    /// Critical severity level.
+    Critical,
+}
+
+impl SeverityLevel {
+    /// Returns a name of a severity level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeverityLevel::Verbose => "Verbose",
+            SeverityLevel::Information => "Information",
+            SeverityLevel::Warning => "Warning",
+            SeverityLevel::Error => "Error",
+            SeverityLevel::Critical => "Critical",
+        }
+    }
+}
+
+impl Display for SeverityLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SeverityLevel {
+    type Err = InvalidSeverityLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "verbose" | "debug" | "trace" => Ok(SeverityLevel::Verbose),
+            "information" | "info" => Ok(SeverityLevel::Information),
+            "warning" | "warn" => Ok(SeverityLevel::Warning),
+            "error" => Ok(SeverityLevel::Error),
+            "critical" => Ok(SeverityLevel::Critical),
+            _ => Err(InvalidSeverityLevel(s.into())),
+        }
+    }
+}
+
+impl Serialize for SeverityLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SeverityLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let level = String::deserialize(deserializer)?;
+        level.parse().map_err(de::Error::custom)
+    }
+}
+
+impl From<SeverityLevel> for ContractsSeverityLevel {
+    fn from(severity: SeverityLevel) -> Self {
+        match severity {
+            SeverityLevel::Verbose => ContractsSeverityLevel::Verbose,
+            SeverityLevel::Information => ContractsSeverityLevel::Information,
+            SeverityLevel::Warning => ContractsSeverityLevel::Warning,
+            SeverityLevel::Error => ContractsSeverityLevel::Error,
+            SeverityLevel::Critical => ContractsSeverityLevel::Critical,
+        }
+    }
+}
+
+impl From<log::Level> for SeverityLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => SeverityLevel::Error,
+            log::Level::Warn => SeverityLevel::Warning,
+            log::Level::Info => SeverityLevel::Information,
+            log::Level::Debug | log::Level::Trace => SeverityLevel::Verbose,
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl From<tracing::Level> for SeverityLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::ERROR => SeverityLevel::Error,
+            tracing::Level::WARN => SeverityLevel::Warning,
+            tracing::Level::INFO => SeverityLevel::Information,
+            _ => SeverityLevel::Verbose,
+        }
+    }
+}
+
+/// An error returned when a severity level cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSeverityLevel(String);
+
+impl Display for InvalidSeverityLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid severity level: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidSeverityLevel {}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("Verbose", SeverityLevel::Verbose ; "verbose")]
+    #[test_case("debug", SeverityLevel::Verbose ; "debug")]
+    #[test_case("information", SeverityLevel::Information ; "information")]
+    #[test_case(" INFO ", SeverityLevel::Information ; "info")]
+    #[test_case("Warn", SeverityLevel::Warning ; "warn")]
+    #[test_case("error", SeverityLevel::Error ; "error")]
+    #[test_case("Critical", SeverityLevel::Critical ; "critical")]
+    fn it_parses_severity_level(value: &str, expected: SeverityLevel) {
+        assert_eq!(value.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn it_fails_to_parse_unknown_severity_level() {
+        assert_eq!(
+            "fatal".parse::<SeverityLevel>(),
+            Err(InvalidSeverityLevel("fatal".into()))
+        );
+    }
+
+    #[test]
+    fn it_orders_severity_levels_by_increasing_severity() {
+        let mut levels = [
+            SeverityLevel::Error,
+            SeverityLevel::Verbose,
+            SeverityLevel::Critical,
+            SeverityLevel::Warning,
+            SeverityLevel::Information,
+        ];
+        levels.sort();
+
+        let names: Vec<_> = levels.iter().map(ToString::to_string).collect();
+        assert_eq!(names, vec!["Verbose", "Information", "Warning", "Error", "Critical"]);
+    }
+
+    #[test]
+    fn it_serializes_severity_level_by_name() {
+        assert_eq!(serde_json::to_string(&SeverityLevel::Warning).unwrap(), r#""Warning""#);
+        assert_eq!(
+            serde_json::from_str::<SeverityLevel>(r#""warn""#).unwrap(),
+            SeverityLevel::Warning
+        );
+        assert!(serde_json::from_str::<SeverityLevel>(r#""fatal""#).is_err());
+    }
+
+    #[test]
+    fn it_converts_log_level() {
+        assert_eq!(SeverityLevel::from(log::Level::Trace), SeverityLevel::Verbose);
+        assert_eq!(SeverityLevel::from(log::Level::Warn), SeverityLevel::Warning);
+        assert_eq!(SeverityLevel::from(log::Level::Error), SeverityLevel::Error);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn it_converts_tracing_level() {
+        assert_eq!(SeverityLevel::from(tracing::Level::DEBUG), SeverityLevel::Verbose);
+        assert_eq!(SeverityLevel::from(tracing::Level::INFO), SeverityLevel::Information);
+        assert_eq!(SeverityLevel::from(tracing::Level::ERROR), SeverityLevel::Error);
+    }
+}
@@ -0,0 +1,92 @@
+/// Rules that recognize synthetic traffic, like availability tests, health probes or web crawlers,
+/// by the `User-Agent` header of an incoming request.
+///
+/// A request recognized by a rule is marked with a synthetic source (the `ai.operation.syntheticSource`
+/// tag), so the portal can separate it from real users in analytics. Rules are checked in the order
+/// they were added and the first one that matches wins. A pattern matches when the user agent
+/// contains it regardless of case.
+///
+/// Telemetry can also be marked explicitly, e.g. for a whole client that runs smoke tests with
+/// `context.operation_mut().set_synthetic_source(...)`.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::telemetry::SyntheticTraffic;
+///
+/// let synthetic = SyntheticTraffic::well_known().user_agent("load-tester", "Load Test");
+///
+/// assert_eq!(synthetic.detect("kube-probe/1.27"), Some("Health Probe"));
+/// assert_eq!(synthetic.detect("Load-Tester/2.0"), Some("Load Test"));
+/// assert_eq!(synthetic.detect("Mozilla/5.0 (X11; Linux x86_64)"), None);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntheticTraffic {
+    rules: Vec<(String, String)>,
+}
+
+impl SyntheticTraffic {
+    /// Creates a new set of rules that recognizes no traffic as synthetic.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new set of rules that recognizes Application Insights availability tests, common
+    /// load balancer and orchestrator health probes and web crawlers.
+    pub fn well_known() -> Self {
+        Self::new()
+            .user_agent("AlwaysOn", "Application Insights Availability Monitoring")
+            .user_agent("kube-probe", "Health Probe")
+            .user_agent("ELB-HealthChecker", "Health Probe")
+            .user_agent("GoogleHC", "Health Probe")
+            .user_agent("Azure Traffic Manager Endpoint Monitor", "Health Probe")
+            .user_agent("HealthCheck", "Health Probe")
+            .user_agent("bot", "Bot")
+            .user_agent("crawl", "Bot")
+            .user_agent("spider", "Bot")
+            .user_agent("slurp", "Bot")
+    }
+
+    /// Adds a rule that marks requests with a user agent containing a given pattern as coming from
+    /// a given synthetic source.
+    pub fn user_agent(mut self, pattern: impl Into<String>, source: impl Into<String>) -> Self {
+        self.rules.push((pattern.into().to_lowercase(), source.into()));
+        self
+    }
+
+    /// Returns a synthetic source of a request with a given user agent, or `None` if it comes from
+    /// a real user.
+    pub fn detect(&self, user_agent: &str) -> Option<&str> {
+        let user_agent = user_agent.to_lowercase();
+        self.rules
+            .iter()
+            .find(|(pattern, _)| user_agent.contains(pattern.as_str()))
+            .map(|(_, source)| source.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("Mozilla/5.0 (compatible; MSIE 9.0; Windows NT 6.1; Trident/5.0; AppInsights) AlwaysOn", Some("Application Insights Availability Monitoring") ; "availability test")]
+    #[test_case("kube-probe/1.27", Some("Health Probe") ; "kubernetes probe")]
+    #[test_case("ELB-HealthChecker/2.0", Some("Health Probe") ; "load balancer")]
+    #[test_case("Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)", Some("Bot") ; "crawler")]
+    #[test_case("Mozilla/5.0 (Windows NT 10.0; Win64; x64) Firefox/118.0", None ; "browser")]
+    fn it_detects_well_known_synthetic_traffic(user_agent: &str, expected: Option<&str>) {
+        assert_eq!(SyntheticTraffic::well_known().detect(user_agent), expected);
+    }
+
+    #[test]
+    fn it_applies_first_matching_rule() {
+        let synthetic = SyntheticTraffic::new()
+            .user_agent("Smoke", "Smoke Test")
+            .user_agent("smoke-bot", "Bot");
+
+        assert_eq!(synthetic.detect("smoke-bot/1.0"), Some("Smoke Test"));
+        assert_eq!(SyntheticTraffic::new().detect("smoke-bot/1.0"), None);
+    }
+}
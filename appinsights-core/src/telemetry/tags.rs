@@ -1,24 +1,167 @@
 use std::{
     collections::BTreeMap,
     ops::{Deref, DerefMut},
+    sync::Arc,
+};
+
+use crate::{
+    contracts::{tags, StringMap},
+    telemetry::Priority,
 };
 
 /// Contains all tags for telemetry to submit.
+///
+/// Clones share the same tags until one of them is modified, so a client telemetry context is
+/// cloned for every telemetry item tracked with it without copying its tags. An envelope of the
+/// item keeps sharing them and overlays tags of the item over them.
 #[derive(Debug, Clone, Default)]
-pub struct ContextTags(BTreeMap<String, String>);
+pub struct ContextTags {
+    tags: Arc<BTreeMap<String, String>>,
+    i_key: Option<String>,
+}
 
 impl ContextTags {
     /// Combines all tags from two bags. It can override some tags with values found
     /// in the second tags bag.
     pub fn combine(a: ContextTags, b: ContextTags) -> Self {
-        let items = a.0.into_iter().chain(b.0).collect();
-        Self(items)
+        Self {
+            tags: overlay(a.tags, b.tags),
+            i_key: b.i_key.or(a.i_key),
+        }
+    }
+
+    /// Overlays tags of a telemetry item over tags of a context into tags to submit. Tags of the
+    /// context stay shared with it instead of being copied for every item.
+    pub(crate) fn submitted(context: ContextTags, telemetry: ContextTags) -> StringMap {
+        StringMap::overlay(context.tags, telemetry.into())
+    }
+
+    /// Returns an instrumentation key that overrides the one of a client telemetry context if any.
+    pub fn i_key(&self) -> Option<&str> {
+        self.i_key.as_deref()
+    }
+
+    /// Sets an instrumentation key that overrides the one of a client telemetry context, so a
+    /// single client can route telemetry items of different tenants to resources they own.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{EventTelemetry, Telemetry};
+    ///
+    /// let mut telemetry = EventTelemetry::new("order placed");
+    /// telemetry.tags_mut().set_i_key("<tenant instrumentation key>");
+    ///
+    /// client.track(telemetry);
+    /// ```
+    pub fn set_i_key(&mut self, i_key: impl Into<String>) {
+        self.i_key = Some(i_key.into());
+    }
+
+    /// Returns an instrumentation key of a telemetry item with these tags, which is a key set on
+    /// the tags if any or a given default one otherwise.
+    pub(crate) fn resolve_i_key(&self, default: String) -> String {
+        self.i_key.clone().unwrap_or(default)
+    }
+
+    /// Returns an identifier of the operation a telemetry item belongs to.
+    pub fn operation_id(&self) -> Option<&str> {
+        self.get_tag(tags::OPERATION_ID)
+    }
+
+    /// Sets an identifier of the operation a telemetry item belongs to.
+    ///
+    /// # Examples
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{EventTelemetry, Telemetry};
+    ///
+    /// let mut telemetry = EventTelemetry::new("order placed");
+    /// telemetry.tags_mut().set_operation_id("4bf92f3577b34da6a3ce929d0e0e4736");
+    /// telemetry.tags_mut().set_operation_parent_id("00f067aa0ba902b7");
+    ///
+    /// client.track(telemetry);
+    /// ```
+    pub fn set_operation_id(&mut self, id: impl Into<String>) {
+        self.set_tag(tags::OPERATION_ID, id.into());
+    }
+
+    /// Returns a name of the operation a telemetry item belongs to.
+    pub fn operation_name(&self) -> Option<&str> {
+        self.get_tag(tags::OPERATION_NAME)
+    }
+
+    /// Sets a name of the operation a telemetry item belongs to, e.g. `GET /users/{id}`.
+    pub fn set_operation_name(&mut self, name: impl Into<String>) {
+        self.set_tag(tags::OPERATION_NAME, name.into());
+    }
+
+    /// Returns an identifier of the immediate parent of a telemetry item.
+    pub fn operation_parent_id(&self) -> Option<&str> {
+        self.get_tag(tags::OPERATION_PARENT_ID)
+    }
+
+    /// Sets an identifier of the immediate parent of a telemetry item.
+    pub fn set_operation_parent_id(&mut self, parent_id: impl Into<String>) {
+        self.set_tag(tags::OPERATION_PARENT_ID, parent_id.into());
+    }
+
+    /// Returns a name of the synthetic source the operation was initiated by.
+    pub fn operation_synthetic_source(&self) -> Option<&str> {
+        self.get_tag(tags::OPERATION_SYNTHETIC_SOURCE)
+    }
+
+    /// Sets a name of the synthetic source the operation was initiated by, e.g. a bot or a test.
+    pub fn set_operation_synthetic_source(&mut self, source: impl Into<String>) {
+        self.set_tag(tags::OPERATION_SYNTHETIC_SOURCE, source.into());
+    }
+
+    /// Returns a priority of a telemetry item if it overrides the one of its category.
+    pub fn priority(&self) -> Option<Priority> {
+        self.get_tag(Priority::tag()).and_then(Priority::parse)
+    }
+
+    /// Sets a priority of a telemetry item that overrides the one of its category. See [`Priority`]
+    /// for details.
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.set_tag(Priority::tag(), priority.to_string());
+    }
+
+    fn get_tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(String::as_str)
+    }
+
+    fn set_tag(&mut self, key: &str, value: String) {
+        Arc::make_mut(&mut self.tags).insert(key.into(), value);
+    }
+}
+
+impl From<BTreeMap<String, String>> for ContextTags {
+    fn from(tags: BTreeMap<String, String>) -> Self {
+        Self {
+            tags: Arc::new(tags),
+            i_key: None,
+        }
+    }
+}
+
+impl From<StringMap> for ContextTags {
+    fn from(tags: StringMap) -> Self {
+        Self::from(BTreeMap::from(tags))
+    }
+}
+
+impl From<ContextTags> for StringMap {
+    fn from(tags: ContextTags) -> Self {
+        StringMap::overlay(tags.tags, BTreeMap::default())
     }
 }
 
 impl From<ContextTags> for BTreeMap<String, String> {
     fn from(tags: ContextTags) -> Self {
-        tags.0
+        Arc::try_unwrap(tags.tags).unwrap_or_else(|tags| (*tags).clone())
     }
 }
 
@@ -26,16 +169,35 @@ impl Deref for ContextTags {
     type Target = BTreeMap<String, String>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.tags
     }
 }
 
 impl DerefMut for ContextTags {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        Arc::make_mut(&mut self.tags)
     }
 }
 
+/// Overlays entries of one shared map over another one. A map is copied only when both of them
+/// have entries and the base map is shared.
+pub(crate) fn overlay(
+    base: Arc<BTreeMap<String, String>>,
+    overlay: Arc<BTreeMap<String, String>>,
+) -> Arc<BTreeMap<String, String>> {
+    if overlay.is_empty() {
+        return base;
+    }
+    if base.is_empty() {
+        return overlay;
+    }
+
+    let mut combined = base;
+    let entries = Arc::try_unwrap(overlay).unwrap_or_else(|overlay| (*overlay).clone());
+    Arc::make_mut(&mut combined).extend(entries);
+    combined
+}
+
 /// Macros to generate well-known context tags.
 #[macro_export]
 macro_rules! tags {
@@ -43,13 +205,13 @@ macro_rules! tags {
         impl ContextTags{
             $(#[$attr_factory])*
             pub fn $factory(&self) -> $name<'_> {
-                $name::new(&self.0)
+                $name::new(&self.tags)
             }
 
             paste::item! {
                 $(#[$attr_factory])*
                 pub fn [<$factory _mut>](&mut self) -> [<$name Mut>]<'_> {
-                    [<$name Mut>]::new(&mut self.0)
+                    [<$name Mut>]::new(std::sync::Arc::make_mut(&mut self.tags))
                 }
             }
         }
@@ -101,7 +263,7 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'location'.
     ApplicationTags {
         /// Application version. Information in the application context fields is always about the application that is sending the telemetry.
-        version: "ai.application.ver"
+        version: tags::APPLICATION_VERSION
     }
 );
 
@@ -111,17 +273,17 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'device'.
     DeviceTags {
         /// Unique client device id. Computer name in most cases.
-        id: "ai.device.id",
+        id: tags::DEVICE_ID,
         /// Device locale using <language>-<REGION> pattern, following RFC 5646. Example 'en-US'.
-        locale: "ai.device.locale",
+        locale: tags::DEVICE_LOCALE,
         /// Model of the device the end user of the application is using. Used for client scenarios. If this field is empty then it is derived from the user agent.
-        model: "ai.device.model",
+        model: tags::DEVICE_MODEL,
         /// Client device OEM name taken from the browser.
-        oem_name: "ai.device.oemName",
+        oem_name: tags::DEVICE_OEM_NAME,
         /// Operating system name and version of the device the end user of the application is using. If this field is empty then it is derived from the user agent. Example 'Windows 10 Pro 10.0.10586.0'
-        os_version: "ai.device.osVersion",
+        os_version: tags::DEVICE_OS_VERSION,
         /// The type of the device the end user of the application is using. Used primarily to distinguish JavaScript telemetry from server side telemetry. Examples: 'PC', 'Phone', 'Browser'. 'PC' is the default value.
-        r#type: "ai.device.type"
+        r#type: tags::DEVICE_TYPE
     }
 );
 
@@ -131,13 +293,13 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'location'.
     LocationTags {
         /// The IP address of the client device. IPv4 and IPv6 are supported. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        ip: "ai.location.ip",
+        ip: tags::LOCATION_IP,
         /// The country of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        country: "ai.location.country",
+        country: tags::LOCATION_COUNTRY,
         /// The province/state of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        province: "ai.location.province",
+        province: tags::LOCATION_PROVINCE,
         /// The city of the client device. If any of Country, Province, or City is specified, those values will be preferred over geolocation of the IP address field. Information in the location context fields is always about the end user. When telemetry is sent from a service, the location context is about the user that initiated the operation in the service.
-        city: "ai.location.city"
+        city: tags::LOCATION_CITY
     }
 );
 
@@ -147,15 +309,15 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'operation'.
     OperationTags {
         /// A unique identifier for the operation instance. The operation.id is created by either a request or a page view. All other telemetry sets this to the value for the containing request or page view. Operation.id is used for finding all the telemetry items for a specific operation instance.
-        id: "ai.operation.id",
+        id: tags::OPERATION_ID,
         /// The name (group) of the operation. The operation.name is created by either a request or a page view. All other telemetry items set this to the value for the containing request or page view. Operation.name is used for finding all the telemetry items for a group of operations (i.e. 'GET Home/Index').
-        name: "ai.operation.name",
+        name: tags::OPERATION_NAME,
         /// The unique identifier of the telemetry item's immediate parent.
-        parent_id: "ai.operation.parentId",
+        parent_id: tags::OPERATION_PARENT_ID,
         /// Name of synthetic source. Some telemetry from the application may represent a synthetic traffic. It may be web crawler indexing the web site, site availability tests or traces from diagnostic libraries like Application Insights SDK itself.
-        synthetic_source: "ai.operation.syntheticSource",
+        synthetic_source: tags::OPERATION_SYNTHETIC_SOURCE,
         /// The correlation vector is a light weight vector clock which can be used to identify and order related events across clients and services.
-        correlation_vector: "ai.operation.correlationVector"
+        correlation_vector: tags::OPERATION_CORRELATION_VECTOR
     }
 );
 
@@ -165,9 +327,9 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'session'.
     SessionTags {
         /// Session ID - the instance of the user's interaction with the app. Information in the session context fields is always about the end user. When telemetry is sent from a service, the session context is about the user that initiated the operation in the service.
-        id: "ai.session.id",
+        id: tags::SESSION_ID,
         /// Boolean value indicating whether the session identified by ai.session.id is first for the user or not.
-        is_first: "ai.session.isFirst"
+        is_first: tags::SESSION_IS_FIRST
     }
 );
 
@@ -177,11 +339,11 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'user'.
     UserTags {
         /// In multi-tenant applications this is the account ID or name which the user is acting with. Examples may be subscription ID for Azure portal or blog name blogging platform.
-        account_id: "ai.user.accountId",
+        account_id: tags::USER_ACCOUNT_ID,
         /// Anonymous user id. Represents the end user of the application. When telemetry is sent from a service, the user context is about the user that initiated the operation in the service.
-        id: "ai.user.id",
+        id: tags::USER_ID,
         /// Authenticated user id. The opposite of ai.user.id, this represents the user with a friendly name. Since it's PII information it is not collected by default by most SDKs.
-        auth_user_id: "ai.user.authUserId"
+        auth_user_id: tags::USER_AUTH_USER_ID
     }
 );
 
@@ -191,13 +353,13 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'cloud'.
     CloudTags {
         /// Name of the role the application is a part of. Maps directly to the role name in azure.
-        role: "ai.cloud.role",
+        role: tags::CLOUD_ROLE,
         /// Version of the role the application is a part of.
-        role_ver: "ai.cloud.roleVer",
+        role_ver: tags::CLOUD_ROLE_VER,
         /// Name of the instance where the application is running. Computer name for on-premisis, instance name for Azure.
-        role_instance: "ai.cloud.roleInstance",
+        role_instance: tags::CLOUD_ROLE_INSTANCE,
         /// Location of the role the application is a part of.
-        location: "ai.cloud.location"
+        location: tags::CLOUD_LOCATION
     }
 );
 
@@ -207,17 +369,18 @@ tags!(
     /// Tag helper type that provides access to context fields grouped under 'internal'.
     InternalTags {
         /// SDK version. See `https://github.com/Microsoft/ApplicationInsights-Home/blob/master/SDK-AUTHORING.md#sdk-version-specification` for information.
-        sdk_version: "ai.internal.sdkVersion",
+        sdk_version: tags::INTERNAL_SDK_VERSION,
         /// Agent version. Used to indicate the version of StatusMonitor installed on the computer if it is used for data collection.
-        agent_version: "ai.internal.agentVersion",
+        agent_version: tags::INTERNAL_AGENT_VERSION,
         /// This is the node name used for billing purposes. Use it to override the standard detection of nodes.
-        node_name: "ai.internal.nodeName"
+        node_name: tags::INTERNAL_NODE_NAME
     }
 );
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::telemetry::tag_keys;
 
     #[test]
     fn it_updates_example_tags() {
@@ -243,6 +406,66 @@ mod tests {
         assert_eq!(example.bar(), Some("bar"));
     }
 
+    #[test]
+    fn it_shares_tags_until_modified() {
+        let mut tags = ContextTags::default();
+        tags.insert("foo".into(), "foo".into());
+
+        let mut clone = tags.clone();
+        assert!(Arc::ptr_eq(&tags.tags, &clone.tags));
+
+        clone.insert("bar".into(), "bar".into());
+        assert!(!Arc::ptr_eq(&tags.tags, &clone.tags));
+        assert_eq!(tags.len(), 1);
+        assert_eq!(clone.len(), 2);
+    }
+
+    #[test]
+    fn it_combines_tags_without_copying_empty_bag() {
+        let mut tags = ContextTags::default();
+        tags.insert("foo".into(), "foo".into());
+
+        let combined = ContextTags::combine(tags.clone(), ContextTags::default());
+        assert!(Arc::ptr_eq(&tags.tags, &combined.tags));
+
+        let mut overlay = ContextTags::default();
+        overlay.insert("foo".into(), "bar".into());
+        overlay.insert("bar".into(), "bar".into());
+        let combined = ContextTags::combine(tags.clone(), overlay);
+        assert_eq!(tags.get("foo"), Some(&"foo".into()));
+        assert_eq!(combined.get("foo"), Some(&"bar".into()));
+        assert_eq!(combined.len(), 2);
+    }
+
+    #[test]
+    fn it_overrides_i_key_with_the_second_tags_bag() {
+        let mut a = ContextTags::default();
+        a.set_i_key("a");
+        let mut b = ContextTags::default();
+        b.set_i_key("b");
+
+        assert_eq!(ContextTags::combine(a.clone(), b).i_key(), Some("b"));
+        assert_eq!(ContextTags::combine(a, ContextTags::default()).i_key(), Some("a"));
+        assert_eq!(ContextTags::default().resolve_i_key("default".into()), "default");
+    }
+
+    #[test]
+    fn it_updates_operation_tags() {
+        let mut tags = ContextTags::default();
+
+        tags.set_operation_id("id");
+        tags.set_operation_name("GET /users/{id}");
+        tags.set_operation_parent_id("parent");
+        tags.set_operation_synthetic_source("bot");
+
+        assert_eq!(tags.operation_id(), Some("id"));
+        assert_eq!(tags.operation_name(), Some("GET /users/{id}"));
+        assert_eq!(tags.operation_parent_id(), Some("parent"));
+        assert_eq!(tags.operation_synthetic_source(), Some("bot"));
+        assert_eq!(tags.get(tag_keys::OPERATION_ID), Some(&"id".into()));
+        assert_eq!(tags.operation().parent_id(), Some("parent"));
+    }
+
     tags!(
         /// Returns example wrapper
         example,
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Utc};
+
+use crate::{telemetry::Measurements, time};
+
+/// Records named checkpoints within an operation and summarizes time spent between them.
+///
+/// Each checkpoint stores the time elapsed since the previous checkpoint (or since the timeline
+/// was started for the first one). The summary can be attached to a request telemetry item as
+/// measurements in milliseconds, which gives a coarse breakdown of where an operation spent its time.
+/// Checkpoints recorded several times with the same name accumulate their durations.
+///
+/// # Examples
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// use appinsights::telemetry::{OperationTimeline, RequestTelemetry};
+/// use std::time::Duration;
+///
+/// let mut operation = OperationTimeline::start();
+///
+/// // ... fetch data
+/// operation.checkpoint("fetched-data");
+///
+/// // ... render response
+/// operation.checkpoint("rendered");
+///
+/// let mut telemetry = RequestTelemetry::new(
+///     "GET /index.html".to_string(),
+///     "https://example.com/index.html".parse().unwrap(),
+///     operation.elapsed(),
+///     "200",
+/// );
+///
+/// // attach "fetched-data" and "rendered" measurements to the request
+/// telemetry.set_timeline(operation);
+///
+/// client.track(telemetry);
+/// ```
+#[derive(Debug, Clone)]
+pub struct OperationTimeline {
+    /// The time stamp when the operation has started.
+    started: DateTime<Utc>,
+
+    /// The time stamp of the most recent checkpoint.
+    last: DateTime<Utc>,
+
+    /// Names of checkpoints with the time elapsed since the previous one, in the order they were recorded.
+    checkpoints: Vec<(String, std::time::Duration)>,
+}
+
+impl OperationTimeline {
+    /// Creates a new timeline that starts at the current moment.
+    pub fn start() -> Self {
+        let now = time::now();
+        Self {
+            started: now,
+            last: now,
+            checkpoints: Vec::default(),
+        }
+    }
+
+    /// Records a new checkpoint with specified name and the time elapsed since the previous one.
+    pub fn checkpoint(&mut self, name: impl Into<String>) {
+        let now = time::now();
+        let delta = (now - self.last).to_std().unwrap_or_default();
+        self.last = now;
+        self.checkpoints.push((name.into(), delta));
+    }
+
+    /// Returns checkpoint names with the time elapsed since the previous checkpoint in the order
+    /// they were recorded.
+    pub fn checkpoints(&self) -> &[(String, std::time::Duration)] {
+        &self.checkpoints
+    }
+
+    /// Returns the time elapsed since this timeline has started.
+    pub fn elapsed(&self) -> std::time::Duration {
+        (time::now() - self.started).to_std().unwrap_or_default()
+    }
+}
+
+impl From<OperationTimeline> for Measurements {
+    fn from(timeline: OperationTimeline) -> Self {
+        let mut measurements = Measurements::default();
+        for (name, delta) in timeline.checkpoints {
+            *measurements.entry(name).or_default() += delta.as_secs_f64() * 1000.0;
+        }
+        measurements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn it_records_time_elapsed_between_checkpoints() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 0));
+        let mut timeline = OperationTimeline::start();
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 150));
+        timeline.checkpoint("fetched-data");
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 400));
+        timeline.checkpoint("rendered");
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 500));
+        assert_eq!(timeline.elapsed(), std::time::Duration::from_millis(500));
+
+        let measurements = Measurements::from(timeline);
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements.get("fetched-data"), Some(&150.0));
+        assert_eq!(measurements.get("rendered"), Some(&250.0));
+    }
+
+    #[test]
+    fn it_accumulates_checkpoints_with_the_same_name() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 0));
+        let mut timeline = OperationTimeline::start();
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 100));
+        timeline.checkpoint("query");
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 120));
+        timeline.checkpoint("parse");
+
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 220));
+        timeline.checkpoint("query");
+
+        assert_eq!(timeline.checkpoints().len(), 3);
+
+        let measurements = Measurements::from(timeline);
+        assert_eq!(measurements.get("query"), Some(&200.0));
+        assert_eq!(measurements.get("parse"), Some(&20.0));
+    }
+}
@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use crate::telemetry::{IntoEnvelope, Tracker};
+
+/// Times a telemetry item from the moment it starts until it is stopped and submits it with a
+/// given client.
+///
+/// The elapsed time is measured with a monotonic clock, so adjustments of the wall clock while an
+/// operation is in progress don't skew its duration. The wall clock is used only for a timestamp of
+/// the telemetry item.
+pub(crate) struct Timer<T> {
+    client: Box<dyn Tracker>,
+    started: Instant,
+    telemetry: Option<T>,
+}
+
+impl<T> Timer<T>
+where
+    T: IntoEnvelope + 'static,
+{
+    /// Starts timing a given telemetry item.
+    pub(crate) fn start<C>(client: &C, telemetry: T) -> Self
+    where
+        C: Tracker + Clone + 'static,
+    {
+        Self {
+            client: Box::new(client.clone()),
+            started: Instant::now(),
+            telemetry: Some(telemetry),
+        }
+    }
+
+    /// Returns mutable reference to the telemetry item being timed, if it is not submitted yet.
+    pub(crate) fn telemetry_mut(&mut self) -> Option<&mut T> {
+        self.telemetry.as_mut()
+    }
+
+    /// Completes the telemetry item with the time elapsed since it has started and submits it.
+    /// Does nothing once the item is submitted.
+    pub(crate) fn stop(&mut self, complete: impl FnOnce(&mut T, Duration)) {
+        if let Some(mut telemetry) = self.telemetry.take() {
+            complete(&mut telemetry, self.started.elapsed());
+            self.client.track_boxed(Box::new(telemetry));
+        }
+    }
+}
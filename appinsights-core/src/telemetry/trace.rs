@@ -1,9 +1,9 @@
-use chrono::{DateTime, SecondsFormat, Utc};
+use chrono::{DateTime, Utc};
 
 use crate::{
     context::TelemetryContext,
     contracts::*,
-    telemetry::{ContextTags, Measurements, Properties, SeverityLevel, Telemetry},
+    telemetry::{envelope, ContextTags, Measurements, Properties, SeverityLevel, Telemetry},
     time,
 };
 
@@ -48,6 +48,7 @@ pub struct TraceTelemetry {
     measurements: Measurements,
 }
 
+/// A builder of [TraceTelemetry] items.
 // This struct is a Option'fied version of TraceTelemetry
 #[derive(Debug, Default)]
 pub struct TraceTelemetryBuilder {
@@ -60,36 +61,44 @@ pub struct TraceTelemetryBuilder {
 }
 
 impl TraceTelemetryBuilder {
+    /// Sets the trace message.
     pub fn with_message(mut self, message: impl Into<String>) -> Self {
         self.message = Some(message.into());
         self
     }
 
+    /// Sets the severity level.
     pub fn with_severity(mut self, severity: SeverityLevel) -> Self {
         self.severity = Some(severity);
         self
     }
 
+    /// Sets the time when this telemetry was measured.
     pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
         self.timestamp = Some(timestamp);
         self
     }
 
+    /// Sets custom properties to submit with the telemetry item.
     pub fn with_properties(mut self, properties: Properties) -> Self {
         self.properties = Some(properties);
         self
     }
 
+    /// Sets context tags that override values found on client telemetry context.
     pub fn with_tags(mut self, tags: ContextTags) -> Self {
         self.tags = Some(tags);
         self
     }
 
+    /// Sets custom measurements to submit with the telemetry item.
     pub fn with_measurements(mut self, measurements: Measurements) -> Self {
         self.measurements = Some(measurements);
         self
     }
 
+    /// Builds a [TraceTelemetry] item.
+    ///
     /// If no message is provided, an empty string is passed.
     /// If no severity is provided, SeverityLevel::Verbose is used.
     pub fn build(self) -> TraceTelemetry {
@@ -173,18 +182,21 @@ impl Telemetry for TraceTelemetry {
 impl From<(TelemetryContext, TraceTelemetry)> for Envelope {
     fn from((context, telemetry): (TelemetryContext, TraceTelemetry)) -> Self {
         Self {
-            name: "Microsoft.ApplicationInsights.Message".into(),
-            time: telemetry.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
-            i_key: Some(context.i_key),
-            tags: Some(ContextTags::combine(context.tags, telemetry.tags).into()),
+            i_key: Some(telemetry.tags.resolve_i_key(context.i_key)),
+            sample_rate: Some(context.sample_rate),
+            tags: Some(ContextTags::submitted(context.tags, telemetry.tags)),
             data: Some(Base::Data(Data::MessageData(MessageData {
                 message: telemetry.message,
                 severity_level: Some(telemetry.severity.into()),
-                properties: Some(Properties::combine(context.properties, telemetry.properties).into()),
+                properties: Some(Properties::submitted_with_units(
+                    context.properties,
+                    telemetry.properties,
+                    &telemetry.measurements,
+                )),
                 measurements: Some(telemetry.measurements.into()),
                 ..MessageData::default()
             }))),
-            ..Envelope::default()
+            ..envelope(names::MESSAGE, telemetry.timestamp)
         }
     }
 }
@@ -197,7 +209,7 @@ mod tests {
 
     use super::{SeverityLevel, TraceTelemetry};
     use crate::{
-        contracts::{Base, Data, Envelope, MessageData},
+        contracts::{Base, Data, Envelope, MessageData, StringMap},
         telemetry::{ContextTags, Properties, Telemetry},
         time, TelemetryContext,
     };
@@ -221,12 +233,12 @@ mod tests {
             name: "Microsoft.ApplicationInsights.Message".into(),
             time: "2019-01-02T03:04:05.800Z".into(),
             i_key: Some("instrumentation".into()),
-            tags: Some(BTreeMap::default()),
+            tags: Some(StringMap::default()),
             data: Some(Base::Data(Data::MessageData(MessageData {
                 message: "message".into(),
                 severity_level: Some(crate::contracts::SeverityLevel::Information),
                 properties: Some({
-                    let mut properties = BTreeMap::default();
+                    let mut properties = StringMap::default();
                     properties.insert("test".into(), "ok".into());
                     properties.insert("no-write".into(), "ok".into());
                     properties
@@ -263,7 +275,7 @@ mod tests {
             time: "2019-01-02T03:04:05.700Z".into(),
             i_key: Some("instrumentation".into()),
             tags: Some({
-                let mut tags = BTreeMap::default();
+                let mut tags = StringMap::default();
                 tags.insert("test".into(), "ok".into());
                 tags.insert("no-write".into(), "ok".into());
                 tags
@@ -271,7 +283,7 @@ mod tests {
             data: Some(Base::Data(Data::MessageData(MessageData {
                 message: "message".into(),
                 severity_level: Some(crate::contracts::SeverityLevel::Information),
-                properties: Some(BTreeMap::default()),
+                properties: Some(StringMap::default()),
                 measurements: Some(BTreeMap::default()),
                 ..MessageData::default()
             }))),
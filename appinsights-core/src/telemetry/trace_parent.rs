@@ -0,0 +1,176 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use crate::uuid;
+
+/// Represents a W3C [`traceparent`](https://www.w3.org/TR/trace-context/#traceparent-header) value
+/// that correlates telemetry items of a distributed operation.
+///
+/// # Examples
+/// ```rust
+/// use appinsights::telemetry::TraceParent;
+///
+/// let incoming: TraceParent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap();
+/// let current = incoming.child();
+///
+/// assert_eq!(current.trace_id(), incoming.trace_id());
+/// assert_ne!(current.span_id(), incoming.span_id());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    /// Identifier of the whole distributed trace, 32 lowercase hex characters.
+    trace_id: String,
+
+    /// Identifier of the current span, 16 lowercase hex characters.
+    span_id: String,
+
+    /// Trace flags.
+    flags: u8,
+}
+
+impl TraceParent {
+    /// The name of the HTTP header that carries trace parent value.
+    pub const HEADER: &'static str = "traceparent";
+
+    /// Creates a new trace parent that starts a new distributed trace.
+    pub fn new() -> Self {
+        Self {
+            trace_id: uuid::new().as_simple().to_string(),
+            span_id: new_span_id(),
+            flags: 1,
+        }
+    }
+
+    /// Creates a new trace parent that belongs to the same distributed trace with a new span id.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: new_span_id(),
+            flags: self.flags,
+        }
+    }
+
+    /// Returns an identifier of the distributed trace. It is used as an operation id.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns an identifier of the current span. It is used as a telemetry item id.
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// Returns trace flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+}
+
+impl Default for TraceParent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "00-{}-{}-{:02x}", self.trace_id, self.span_id, self.flags)
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = InvalidTraceParent;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('-');
+        let (version, trace_id, span_id, flags) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(version), Some(trace_id), Some(span_id), Some(flags)) => (version, trace_id, span_id, flags),
+            _ => return Err(InvalidTraceParent),
+        };
+
+        if version != "00" || parts.next().is_some() {
+            return Err(InvalidTraceParent);
+        }
+
+        if !is_valid_id(trace_id, 32) || !is_valid_id(span_id, 16) || flags.len() != 2 {
+            return Err(InvalidTraceParent);
+        }
+
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| InvalidTraceParent)?;
+
+        Ok(Self {
+            trace_id: trace_id.into(),
+            span_id: span_id.into(),
+            flags,
+        })
+    }
+}
+
+/// An error returned when a `traceparent` value cannot be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTraceParent;
+
+impl Display for InvalidTraceParent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid traceparent value")
+    }
+}
+
+impl std::error::Error for InvalidTraceParent {}
+
+/// Determines whether an id consists of specified number of lowercase hex characters and is not all zeros.
+fn is_valid_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+        && id.chars().any(|c| c != '0')
+}
+
+/// Generates a new span id.
+fn new_span_id() -> String {
+    let mut id = uuid::new().as_simple().to_string();
+    id.truncate(16);
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn it_parses_and_formats_trace_parent() {
+        let value = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+        let trace_parent: TraceParent = value.parse().unwrap();
+
+        assert_eq!(trace_parent.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(trace_parent.span_id(), "b7ad6b7169203331");
+        assert_eq!(trace_parent.flags(), 1);
+        assert_eq!(trace_parent.to_string(), value);
+    }
+
+    #[test_case("" ; "empty")]
+    #[test_case("01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01" ; "unknown version")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319-b7ad6b7169203331-01" ; "short trace id")]
+    #[test_case("00-00000000000000000000000000000000-b7ad6b7169203331-01" ; "zero trace id")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01" ; "zero span id")]
+    #[test_case("00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01" ; "uppercase")]
+    #[test_case("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-00" ; "extra part")]
+    fn it_rejects_invalid_trace_parent(value: &str) {
+        assert_eq!(value.parse::<TraceParent>(), Err(InvalidTraceParent));
+    }
+
+    #[test]
+    fn it_creates_child_within_the_same_trace() {
+        let parent = TraceParent::new();
+
+        let child = parent.child();
+
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_eq!(child.span_id().len(), 16);
+        assert_eq!(child.to_string().parse::<TraceParent>(), Ok(child));
+    }
+}
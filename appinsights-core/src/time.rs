@@ -6,7 +6,7 @@ use std::{
     time::Duration as StdDuration,
 };
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "test-util")))]
 mod imp {
     use chrono::{DateTime, Utc};
 
@@ -16,20 +16,23 @@ mod imp {
     }
 }
 
-#[cfg(test)]
+/// A clock that tests can stop at a given time. It is compiled only for tests and with the
+/// `test-util` feature, which is meant to be enabled for dev-dependencies only, so a release build
+/// always reads the system clock.
+#[cfg(any(test, feature = "test-util"))]
 mod imp {
     use std::cell::RefCell;
 
     use chrono::{DateTime, Utc};
 
-    thread_local!(static NOW: RefCell<Option<DateTime<Utc>>> = RefCell::new(None));
+    thread_local!(static NOW: RefCell<Option<DateTime<Utc>>> = const { RefCell::new(None) });
 
     /// Returns a DateTime which corresponds to a current date or the value user set in advance.
     pub fn now() -> DateTime<Utc> {
         NOW.with(|ts| if let Some(now) = *ts.borrow() { now } else { Utc::now() })
     }
 
-    /// Sets known DateTime value as now to assert test against it.
+    /// Sets known DateTime value as now on the current thread to assert test against it.
     pub fn set(now: DateTime<Utc>) {
         NOW.with(|ts| *ts.borrow_mut() = Some(now))
     }
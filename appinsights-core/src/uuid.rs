@@ -1,7 +1,7 @@
 pub use imp::*;
 pub use uuid::Uuid;
 
-#[cfg(not(test))]
+#[cfg(not(any(test, feature = "test-util")))]
 mod imp {
     use uuid::Uuid;
 
@@ -11,13 +11,16 @@ mod imp {
     }
 }
 
-#[cfg(test)]
+/// An identifier generator that tests can make return a given identifier. It is compiled only for
+/// tests and with the `test-util` feature, which is meant to be enabled for dev-dependencies only,
+/// so a release build always generates random identifiers.
+#[cfg(any(test, feature = "test-util"))]
 mod imp {
     use std::cell::RefCell;
 
     use uuid::Uuid;
 
-    thread_local!(static ID: RefCell<Option<Uuid>> = RefCell::new(None));
+    thread_local!(static ID: RefCell<Option<Uuid>> = const { RefCell::new(None) });
 
     /// Generates a new instance of unique identifier or predefined value to test against it.
     pub fn new() -> Uuid {
@@ -30,7 +33,7 @@ mod imp {
         })
     }
 
-    /// Sets known Uuid value as now to assert test against it.
+    /// Sets known Uuid value on the current thread to assert test against it.
     pub fn set(uuid: Uuid) {
         ID.with(|is| *is.borrow_mut() = Some(uuid))
     }
@@ -0,0 +1,53 @@
+//! Measures combining a telemetry context with telemetry items into envelopes.
+//!
+//! Tags and properties of a context are shared between its clones, so cloning a context for every
+//! tracked item doesn't copy them. An envelope keeps sharing them as well and overlays tags and
+//! properties of the item over them, so the cost of combining doesn't grow with the number of
+//! context tags and properties. They are merged only when the envelope is serialized.
+//!
+//! ```sh
+//! cargo bench --features test-util --bench context
+//! ```
+use appinsights::{
+    telemetry::{EventTelemetry, Telemetry},
+    test::Envelope,
+    TelemetryConfig, TelemetryContext,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn context(size: usize) -> TelemetryContext {
+    let mut context = TelemetryContext::from_config(&TelemetryConfig::new("instrumentation".into()));
+    for i in 0..size {
+        context.tags_mut().insert(format!("tag {}", i), "value".into());
+        context
+            .properties_mut()
+            .insert(format!("property {}", i), "value".into());
+    }
+    context
+}
+
+fn combination(c: &mut Criterion) {
+    let mut group = c.benchmark_group("combine context");
+
+    for size in [5, 20, 50] {
+        let context = context(size);
+
+        group.bench_with_input(BenchmarkId::new("item without tags", size), &context, |b, context| {
+            b.iter(|| Envelope::from((context.clone(), EventTelemetry::new("event"))))
+        });
+
+        group.bench_with_input(BenchmarkId::new("item with tags", size), &context, |b, context| {
+            b.iter(|| {
+                let mut event = EventTelemetry::new("event");
+                event.tags_mut().set_operation_id("4bf92f3577b34da6a3ce929d0e0e4736");
+                event.properties_mut().insert("component".into(), "benchmark".into());
+                Envelope::from((context.clone(), event))
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, combination);
+criterion_main!(benches);
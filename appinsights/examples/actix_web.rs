@@ -0,0 +1,16 @@
+use actix_web::{web, App, HttpServer};
+use appinsights::{actix_web::AppInsightsMiddleware, TelemetryClient};
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let client = TelemetryClient::new("<instrumentation key>".to_string());
+
+    HttpServer::new(move || {
+        App::new()
+            .wrap(AppInsightsMiddleware::new(client.clone()))
+            .route("/users/{id}", web::get().to(|| async { "Hello, World!" }))
+    })
+    .bind(("127.0.0.1", 8080))?
+    .run()
+    .await
+}
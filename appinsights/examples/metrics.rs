@@ -0,0 +1,25 @@
+use std::env;
+
+use appinsights::TelemetryClient;
+use metrics_util::debugging::DebuggingRecorder;
+
+#[tokio::main]
+async fn main() {
+    let i_key = env::var("APPINSIGHTS_INSTRUMENTATIONKEY").expect("Set APPINSIGHTS_INSTRUMENTATIONKEY first");
+
+    // counters and histograms of the submission pipeline are recorded with the global recorder
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    recorder.install().expect("Unable to install metrics recorder");
+
+    let ai = TelemetryClient::new(i_key);
+    for x in 1..=5 {
+        ai.track_event(format!("Client connected: {}", x));
+    }
+    ai.close_channel().await;
+
+    // e.g. appinsights_items_sent: Counter(5)
+    for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+        println!("{}: {:?}", key.key().name(), value);
+    }
+}
@@ -0,0 +1,563 @@
+//! An [`actix-web`](https://docs.rs/actix-web) middleware that tracks incoming HTTP requests.
+//!
+//! [`AppInsightsMiddleware`] measures the time spent to serve each request and submits a
+//! [`RequestTelemetry`](crate::telemetry::RequestTelemetry) item once a response is produced, the
+//! same way the [`tower`](crate::tower) middleware does for `tower` based HTTP stacks.
+//!
+//! * A request is named by its route template, e.g. `GET /users/{id}`, so that requests of the
+//!   same route are grouped together. A request that matched no route is named by its path.
+//! * When a request contains a `traceparent` header, the telemetry is correlated with the caller
+//!   operation. The [`TraceParent`] of the request and a [`TelemetryContext`] of the operation are
+//!   inserted into request extensions, so handlers can correlate their own telemetry and outbound
+//!   calls with it. The operation context is also [current](crate::ambient) while a handler runs,
+//!   so telemetry items tracked by a client are correlated with the request without it.
+//! * A handler that panics is tracked as an [`ExceptionTelemetry`] and a request with `500`
+//!   response code, before the panic is resumed.
+//! * A request whose response future is dropped before a response is produced, e.g. because a
+//!   client closed the connection, is tracked with `499` response code.
+//! * A request an inner service fails with an error instead of a response, e.g. another
+//!   middleware, is tracked with a response code of the error.
+//!
+//! The example below is built as the `actix_web` example of the crate.
+//!
+//! ```rust, no_run
+#![doc = include_str!("../examples/actix_web.rs")]
+//! ```
+use std::{
+    future::{ready, Future, Ready},
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use chrono::{DateTime, Utc};
+use http::{StatusCode, Uri};
+use pin_project_lite::pin_project;
+
+use crate::{
+    ambient::{with_operation, WithOperation},
+    internal_logger::panic_message,
+    processor::ProcessingContext,
+    telemetry::{
+        ContextTags, ExceptionTelemetry, Properties, RequestTelemetry, SeverityLevel, SyntheticTraffic, Telemetry,
+        TraceParent,
+    },
+    time, TelemetryClient, TelemetryContext,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "actix-web";
+
+/// A response code of requests cancelled before a response is produced, as reported by `nginx`
+/// for requests closed by a client.
+const CANCELLED: &str = "499";
+
+/// A type name of exceptions tracked for panics of handlers.
+const PANIC: &str = "panic";
+
+/// A [`Transform`] that wraps services with [`AppInsightsMiddlewareService`] to track incoming
+/// requests.
+#[derive(Clone)]
+pub struct AppInsightsMiddleware {
+    client: TelemetryClient,
+    synthetic: Arc<SyntheticTraffic>,
+}
+
+impl AppInsightsMiddleware {
+    /// Creates a new middleware that submits request telemetry with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self {
+            client,
+            synthetic: Arc::default(),
+        }
+    }
+
+    /// Marks requests recognized by given rules as synthetic traffic. By default no request is
+    /// considered synthetic.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{actix_web::AppInsightsMiddleware, TelemetryClient};
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::SyntheticTraffic;
+    ///
+    /// let synthetic = SyntheticTraffic::well_known().user_agent("load-tester", "Load Test");
+    /// let middleware = AppInsightsMiddleware::new(client).with_synthetic_traffic(synthetic);
+    /// ```
+    pub fn with_synthetic_traffic(mut self, synthetic: SyntheticTraffic) -> Self {
+        self.synthetic = Arc::new(synthetic);
+        self
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AppInsightsMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AppInsightsMiddlewareService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AppInsightsMiddlewareService {
+            service,
+            client: self.client.clone(),
+            synthetic: self.synthetic.clone(),
+        }))
+    }
+}
+
+/// A middleware service that submits a [`RequestTelemetry`](crate::telemetry::RequestTelemetry)
+/// for every request handled by the inner service.
+pub struct AppInsightsMiddlewareService<S> {
+    service: S,
+    client: TelemetryClient,
+    synthetic: Arc<SyntheticTraffic>,
+}
+
+impl<S, B> Service<ServiceRequest> for AppInsightsMiddlewareService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let incoming = header(&req, TraceParent::HEADER).and_then(|value| value.parse::<TraceParent>().ok());
+        let trace_parent = incoming.as_ref().map_or_else(TraceParent::new, TraceParent::child);
+
+        let route = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+        let name = format!("{} {}", req.method(), route);
+        let synthetic_source = header(&req, http::header::USER_AGENT.as_str())
+            .and_then(|user_agent| self.synthetic.detect(user_agent))
+            .map(String::from);
+
+        let mut operation = TelemetryContext::new(
+            self.client.context().i_key().to_string(),
+            ContextTags::default(),
+            Properties::default(),
+        );
+        let mut tags = operation.operation_mut();
+        tags.set_id(trace_parent.trace_id().into());
+        tags.set_parent_id(trace_parent.span_id().into());
+        tags.set_name(name.clone());
+        if let Some(synthetic_source) = &synthetic_source {
+            tags.set_synthetic_source(synthetic_source.clone());
+        }
+
+        let tracker = RequestTracker {
+            client: self.client.clone(),
+            name,
+            uri: absolute_uri(&req),
+            started: time::now(),
+            parent_id: incoming.map(|incoming| incoming.span_id().to_string()),
+            synthetic_source,
+            trace_parent: trace_parent.clone(),
+        };
+
+        req.extensions_mut().insert(trace_parent);
+        req.extensions_mut().insert(operation.clone());
+
+        ResponseFuture {
+            inner: with_operation(operation, self.service.call(req)),
+            tracker: Some(tracker),
+        }
+    }
+}
+
+/// Returns an absolute URI of a request. A server receives only a path and a query of it, so a
+/// scheme and a host are taken from connection info, which respects `Forwarded` headers.
+fn absolute_uri(req: &ServiceRequest) -> Uri {
+    let connection = req.connection_info();
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+    Uri::builder()
+        .scheme(connection.scheme())
+        .authority(connection.host())
+        .path_and_query(path_and_query)
+        .build()
+        .unwrap_or_else(|_| req.uri().clone())
+}
+
+/// Returns the value of a request header with a given name if it is a valid string.
+fn header<'a>(req: &'a ServiceRequest, name: &str) -> Option<&'a str> {
+    req.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+pin_project! {
+    /// Response future of [`AppInsightsMiddlewareService`].
+    ///
+    /// A request is tracked as cancelled if the future is dropped before it completes.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: WithOperation<F>,
+        tracker: Option<RequestTracker>,
+    }
+
+    impl<F> PinnedDrop for ResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(tracker) = this.project().tracker.take() {
+                tracker.track(CANCELLED.into());
+            }
+        }
+    }
+}
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<ServiceResponse<B>, Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| this.inner.as_mut().poll(cx))) {
+            Ok(Poll::Ready(result)) => result,
+            Ok(Poll::Pending) => return Poll::Pending,
+            Err(panic) => {
+                if let Some(tracker) = this.tracker.take() {
+                    tracker.track_panic(panic_message(panic.as_ref()));
+                }
+                panic::resume_unwind(panic)
+            }
+        };
+
+        if let Some(tracker) = this.tracker.take() {
+            let response_code = match &result {
+                Ok(response) => response.status().as_str().to_string(),
+                Err(err) => err.as_response_error().status_code().as_str().to_string(),
+            };
+            tracker.track(response_code);
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+/// Collects request data required to submit a telemetry item when a response is ready.
+struct RequestTracker {
+    client: TelemetryClient,
+    name: String,
+    uri: Uri,
+    started: DateTime<Utc>,
+    parent_id: Option<String>,
+    synthetic_source: Option<String>,
+    trace_parent: TraceParent,
+}
+
+impl RequestTracker {
+    fn track(self, response_code: String) {
+        let duration = (time::now() - self.started).to_std().unwrap_or_default();
+
+        let mut telemetry = RequestTelemetry::new(self.name, self.uri, duration, response_code);
+        *telemetry.timestamp_mut() = self.started;
+        telemetry.set_id(self.trace_parent.span_id());
+
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.trace_parent.trace_id().into());
+        if let Some(parent_id) = self.parent_id {
+            operation.set_parent_id(parent_id);
+        }
+        if let Some(synthetic_source) = self.synthetic_source {
+            operation.set_synthetic_source(synthetic_source);
+        }
+
+        let mut processing = ProcessingContext::new(Some(INTEGRATION));
+        processing.extensions_mut().insert(self.trace_parent);
+        self.client.track_with_context(telemetry, processing);
+    }
+
+    /// Tracks a panic of a handler as an exception of the request operation followed by the
+    /// request itself, which failed with `500` response code.
+    fn track_panic(self, message: String) {
+        let mut exception = ExceptionTelemetry::new(Some(SeverityLevel::Critical), None::<String>).with_message(
+            message,
+            PANIC,
+            None::<String>,
+        );
+        let mut operation = exception.tags_mut().operation_mut();
+        operation.set_id(self.trace_parent.trace_id().into());
+        operation.set_parent_id(self.trace_parent.span_id().into());
+        operation.set_name(self.name.clone());
+        self.client
+            .track_with_context(exception, ProcessingContext::new(Some(INTEGRATION)));
+
+        self.track(StatusCode::INTERNAL_SERVER_ERROR.as_str().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{
+        dev::ServiceFactory,
+        error,
+        test::{self, TestRequest},
+        web, App, HttpRequest, HttpResponse,
+    };
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[actix_web::test]
+    async fn it_tracks_request_named_by_route_template() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let request = TestRequest::get().uri("/users/42?q=1").to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let echo: Echo = test::read_body_json(response).await;
+        assert_eq!(events.len(), 1);
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /users/{id}".into()));
+                assert_eq!(data.response_code, "404");
+                assert!(!data.success);
+                assert_eq!(data.url, Some("http://localhost:8080/users/42".into()));
+                assert_eq!(echo.span_id, data.id);
+                assert_eq!(tags.get("ai.operation.id"), Some(&echo.trace_id));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(tags.get("ai.operation.parentId"), None);
+    }
+
+    #[actix_web::test]
+    async fn it_names_request_by_path_when_no_route_matched() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let response = test::call_service(&app, TestRequest::get().uri("/missing").to_request()).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => assert_eq!(data.name, Some("GET /missing".into())),
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[actix_web::test]
+    async fn it_correlates_request_with_incoming_trace_parent() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let request = TestRequest::get()
+            .uri("/users/42")
+            .insert_header(("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"))
+            .to_request();
+        let echo: Echo = test::call_and_read_body_json(&app, request).await;
+
+        assert_eq!(echo.trace_id, "0af7651916cd43dd8448eb211c80319c");
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+    }
+
+    #[actix_web::test]
+    async fn it_puts_operation_context_into_request_extensions() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let request = TestRequest::get().uri("/users/42").to_request();
+        let echo: Echo = test::call_and_read_body_json(&app, request).await;
+
+        assert_eq!(echo.i_key, Some("instrumentation".into()));
+        assert_eq!(echo.operation_id, Some(echo.trace_id.clone()));
+        assert_eq!(echo.operation_parent_id, Some(echo.span_id.clone()));
+        assert_eq!(echo.operation_name, Some("GET /users/{id}".into()));
+        assert_eq!(echo.current, Some(echo.trace_id.clone()));
+    }
+
+    #[actix_web::test]
+    async fn it_marks_synthetic_traffic_by_user_agent() {
+        let events = Arc::new(SegQueue::default());
+        let middleware = AppInsightsMiddleware::new(create_client(events.clone()))
+            .with_synthetic_traffic(SyntheticTraffic::well_known());
+        let app = test::init_service(App::new().wrap(middleware).route("/", web::get().to(HttpResponse::Ok))).await;
+
+        let probe = TestRequest::get()
+            .insert_header(("user-agent", "kube-probe/1.27"))
+            .to_request();
+        test::call_service(&app, probe).await;
+        let user = TestRequest::get()
+            .insert_header(("user-agent", "Mozilla/5.0 (X11; Linux x86_64)"))
+            .to_request();
+        test::call_service(&app, user).await;
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.syntheticSource"),
+            Some(&"Health Probe".to_string())
+        );
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(tags.get("ai.operation.syntheticSource"), None);
+    }
+
+    #[actix_web::test]
+    async fn it_tracks_failed_request_with_response_code_of_error() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let response = test::call_service(&app, TestRequest::get().uri("/failing").to_request()).await;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /failing".into()));
+                assert_eq!(data.response_code, "503");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[actix_web::test]
+    async fn it_tracks_panic_of_handler_as_exception() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let future = app.call(TestRequest::get().uri("/panicking/42").to_request());
+        let panic = futures_util::FutureExt::catch_unwind(AssertUnwindSafe(future))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(panic_message(panic.as_ref()), "handler failed");
+        assert_eq!(events.len(), 2);
+
+        let exception = events.pop().unwrap();
+        let exception_tags = exception.tags.unwrap();
+        match exception.data {
+            Some(Base::Data(Data::ExceptionData(data))) => {
+                assert_eq!(data.exceptions[0].message, "handler failed");
+                assert_eq!(data.exceptions[0].type_name, "panic");
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+
+        let request = events.pop().unwrap();
+        match request.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /panicking/{id}".into()));
+                assert_eq!(data.response_code, "500");
+                assert!(!data.success);
+                assert_eq!(exception_tags.get("ai.operation.parentId"), Some(&data.id));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(
+            exception_tags.get("ai.operation.id"),
+            request.tags.unwrap().get("ai.operation.id")
+        );
+    }
+
+    #[actix_web::test]
+    async fn it_tracks_request_as_cancelled_when_response_future_dropped() {
+        let events = Arc::new(SegQueue::default());
+        let app = test::init_service(create_app(events.clone())).await;
+
+        let response = app.call(TestRequest::get().uri("/pending").to_request());
+        assert_eq!(events.len(), 0);
+
+        drop(response);
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /pending".into()));
+                assert_eq!(data.response_code, "499");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(events.len(), 0);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+
+    fn create_app(
+        events: Arc<SegQueue<Envelope>>,
+    ) -> App<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl actix_web::body::MessageBody>,
+            Error = Error,
+            InitError = (),
+        >,
+    > {
+        App::new()
+            .wrap(AppInsightsMiddleware::new(create_client(events)))
+            .route("/users/{id}", web::get().to(echo))
+            .route(
+                "/failing",
+                web::get().to(|| async { Err::<HttpResponse, _>(error::ErrorServiceUnavailable("unavailable")) }),
+            )
+            .route("/panicking/{id}", web::get().to(panicking))
+            .route("/pending", web::get().to(std::future::pending::<HttpResponse>))
+    }
+
+    /// Panics while handling a request.
+    async fn panicking() -> HttpResponse {
+        panic!("handler failed")
+    }
+
+    /// What a handler found in request extensions and the current operation.
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Echo {
+        trace_id: String,
+        span_id: String,
+        i_key: Option<String>,
+        operation_id: Option<String>,
+        operation_parent_id: Option<String>,
+        operation_name: Option<String>,
+        current: Option<String>,
+    }
+
+    /// Responds with 404 and echoes a trace parent and an operation context found in request
+    /// extensions and the current operation.
+    async fn echo(req: HttpRequest) -> HttpResponse {
+        let extensions = req.extensions();
+        let trace_parent = extensions.get::<TraceParent>().unwrap();
+        let operation = extensions.get::<TelemetryContext>();
+        let echo = Echo {
+            trace_id: trace_parent.trace_id().into(),
+            span_id: trace_parent.span_id().into(),
+            i_key: operation.map(|operation| operation.i_key().into()),
+            operation_id: operation.and_then(|operation| operation.operation().id().map(String::from)),
+            operation_parent_id: operation.and_then(|operation| operation.operation().parent_id().map(String::from)),
+            operation_name: operation.and_then(|operation| operation.operation().name().map(String::from)),
+            current: TelemetryContext::current().and_then(|context| context.operation().id().map(String::from)),
+        };
+        HttpResponse::NotFound().json(echo)
+    }
+}
@@ -0,0 +1,65 @@
+//! Forwarding of telemetry items to a local agent.
+//!
+//! Many platforms forbid applications to connect to the internet directly, so telemetry is
+//! collected by an agent running next to an application, e.g. an OpenTelemetry collector or a
+//! custom sidecar, and the agent sends it further. With an [`AgentEndpoint`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder), a channel forwards batches of
+//! telemetry items to the agent instead of the ingestion endpoint.
+//!
+//! Batches are written to a single connection one after another. Each batch is a frame that
+//! consists of the length of a payload in bytes as a 32-bit big-endian unsigned integer followed by
+//! the payload itself. A payload is a JSON array of envelopes, the same one the ingestion endpoint
+//! receives. The agent does not reply. A batch is considered forwarded once it is written to a
+//! connection, so the agent is expected to take over the delivery.
+//!
+//! A connection is established when the first batch is sent and it is kept open afterwards. When a
+//! batch cannot be written, the connection is dropped and the batch is retried over a new one
+//! later as any other batch that could not be sent. The agent may have received a part of such a
+//! batch before the connection was lost, so it should discard a frame cut short by a closed
+//! connection.
+//!
+//! ```rust, no_run
+//! use appinsights::{agent::AgentEndpoint, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .agent_endpoint(AgentEndpoint::tcp("127.0.0.1:4319"))
+//!     .build();
+//! ```
+use std::fmt::{Display, Formatter};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// An address of a local agent telemetry items are forwarded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AgentEndpoint {
+    /// A TCP address of an agent in a `host:port` form.
+    Tcp(String),
+
+    /// A path of a Unix domain socket an agent listens on.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl AgentEndpoint {
+    /// Creates an endpoint of an agent that listens on a given TCP address in a `host:port` form.
+    pub fn tcp(address: impl Into<String>) -> Self {
+        Self::Tcp(address.into())
+    }
+
+    /// Creates an endpoint of an agent that listens on a Unix domain socket at a given path.
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Self::Unix(path.into())
+    }
+}
+
+impl Display for AgentEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "tcp://{}", address),
+            #[cfg(unix)]
+            Self::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
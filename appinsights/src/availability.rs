@@ -0,0 +1,361 @@
+//! Custom availability tests run on a schedule.
+//!
+//! An application that checks its own dependencies or endpoints, e.g. a health endpoint behind a
+//! load balancer, reports results with
+//! [`track_availability`](crate::TelemetryClient::track_availability). An [`AvailabilityRunner`]
+//! takes care of the rest: it runs every registered [`Probe`] at a given cadence from every
+//! configured run location, cancels a probe that exceeds a timeout and submits an
+//! [`AvailabilityTelemetry`] with the duration, the outcome and a diagnostic message of each run.
+//! A probe that panics fails its run, while other probes keep running on schedule.
+//!
+//! A run location is a logical name of the place a test runs from, e.g. a region or a network
+//! segment. A probe receives the name, so it can pick an endpoint or a proxy accordingly. Results
+//! of different locations are shown separately on the portal.
+//!
+//! ```rust, no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! use std::time::Duration;
+//!
+//! use appinsights::{availability::AvailabilityRunner, TelemetryClient};
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let runner = AvailabilityRunner::new(client)
+//!     .every(Duration::from_secs(5 * 60))
+//!     .timeout(Duration::from_secs(30))
+//!     .locations(["west-europe", "north-europe"])
+//!     .probe("orders health", |location: String| async move {
+//!         let response = reqwest::get(format!("https://orders.{}.example.com/health", location))
+//!             .await
+//!             .map_err(|err| err.to_string())?;
+//!         match response.status().is_success() {
+//!             true => Ok(()),
+//!             false => Err(format!("unexpected status: {}", response.status())),
+//!         }
+//!     })
+//!     .start();
+//!
+//! // ... stop running tests when the application shuts down
+//! runner.stop().await;
+//! # }
+//! ```
+use std::{
+    fmt::Display,
+    future::Future,
+    panic::AssertUnwindSafe,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures_util::{future::join_all, FutureExt};
+use tokio::{sync::oneshot, task::JoinHandle, time};
+
+use crate::{
+    internal_logger::panic_message,
+    telemetry::{AvailabilityTelemetry, Telemetry},
+    TelemetryClient,
+};
+
+/// A name of the run location used when none is configured.
+const DEFAULT_LOCATION: &str = "default";
+
+/// A user-defined availability test.
+#[async_trait]
+pub trait Probe: Send + Sync {
+    /// Runs the test once from a given run location. Returns a diagnostic message of a failure
+    /// as an error.
+    async fn run(&self, location: &str) -> Result<(), String>;
+}
+
+#[async_trait]
+impl<F, Fut, E> Probe for F
+where
+    F: Fn(String) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(), E>> + Send,
+    E: Display,
+{
+    async fn run(&self, location: &str) -> Result<(), String> {
+        self(location.into()).await.map_err(|err| err.to_string())
+    }
+}
+
+/// Runs availability tests on a schedule and submits their results.
+///
+/// By default every test runs once per 5 minutes from the location named after the cloud role
+/// instance of the client, and a run that lasts longer than 30 seconds fails.
+pub struct AvailabilityRunner {
+    client: TelemetryClient,
+    probes: Vec<(String, Arc<dyn Probe>)>,
+    locations: Vec<String>,
+    period: Duration,
+    timeout: Duration,
+}
+
+impl AvailabilityRunner {
+    /// Creates a new runner without tests that submits results with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        let location = client
+            .context()
+            .tags()
+            .cloud()
+            .role_instance()
+            .unwrap_or(DEFAULT_LOCATION)
+            .to_string();
+
+        Self {
+            client,
+            probes: Vec::default(),
+            locations: vec![location],
+            period: Duration::from_secs(5 * 60),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Registers a test with a given name. Results are submitted with this name.
+    pub fn probe(mut self, name: impl Into<String>, probe: impl Probe + 'static) -> Self {
+        self.probes.push((name.into(), Arc::new(probe)));
+        self
+    }
+
+    /// Runs every test from each of given run locations instead of the default one.
+    pub fn locations<I, L>(mut self, locations: I) -> Self
+    where
+        I: IntoIterator<Item = L>,
+        L: Into<String>,
+    {
+        self.locations = locations.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Runs tests once per given period. A period starts when the previous round of tests starts,
+    /// so it does not drift by the time tests take.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a period is zero.
+    pub fn every(mut self, period: Duration) -> Self {
+        assert!(!period.is_zero(), "period of availability tests must be non-zero");
+        self.period = period;
+        self
+    }
+
+    /// Fails a test run that lasts longer than a given timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs every test from every location once and submits results. All runs proceed
+    /// concurrently. Completes when all of them are done.
+    pub async fn run_once(&self) {
+        let runs = self.probes.iter().flat_map(|(name, probe)| {
+            self.locations
+                .iter()
+                .map(move |location| self.run_probe(name, probe.as_ref(), location))
+        });
+        join_all(runs).await;
+    }
+
+    /// Spawns a task on the current tokio runtime that runs tests until the returned handle is
+    /// stopped. The first round of tests starts right away.
+    pub fn start(self) -> AvailabilityRunnerHandle {
+        let (stop, mut stopped) = oneshot::channel();
+        let join = tokio::spawn(async move {
+            let mut interval = time::interval(self.period);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => self.run_once().await,
+                    _ = &mut stopped => break,
+                }
+            }
+        });
+
+        AvailabilityRunnerHandle { stop, join }
+    }
+
+    /// Runs a test once from a given location and submits its result.
+    async fn run_probe(&self, name: &str, probe: &dyn Probe, location: &str) {
+        let timestamp = crate::time::now();
+        let started = Instant::now();
+        let run = AssertUnwindSafe(probe.run(location)).catch_unwind();
+        let result = match time::timeout(self.timeout, run).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(panic)) => Err(format!("Panicked: {}", panic_message(panic.as_ref()))),
+            Err(_) => Err(format!("Timed out after {} ms", self.timeout.as_millis())),
+        };
+
+        let mut telemetry = AvailabilityTelemetry::new(name, started.elapsed(), result.is_ok());
+        *telemetry.timestamp_mut() = timestamp;
+        telemetry.set_run_location(location);
+        if let Err(message) = result {
+            telemetry.set_message(message);
+        }
+        telemetry.tags_mut().operation_mut().set_name(name.into());
+
+        self.client.track(telemetry);
+    }
+}
+
+/// A handle of an [`AvailabilityRunner`] running in background.
+pub struct AvailabilityRunnerHandle {
+    stop: oneshot::Sender<()>,
+    join: JoinHandle<()>,
+}
+
+impl AvailabilityRunnerHandle {
+    /// Stops running tests. A round of tests in progress is completed and its results are
+    /// submitted before the returned future completes.
+    pub async fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.join.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_runs_every_probe_from_every_location() {
+        let events = Arc::new(SegQueue::default());
+        let runner = AvailabilityRunner::new(create_client(events.clone()))
+            .locations(["west", "north"])
+            .probe("up", |_: String| async { Ok::<_, String>(()) })
+            .probe("down", |location: String| async move {
+                Err(format!("{} is unreachable", location))
+            });
+
+        runner.run_once().await;
+
+        let mut results = BTreeMap::new();
+        while let Some(envelope) = events.pop() {
+            if let Some(Base::Data(Data::AvailabilityData(data))) = envelope.data {
+                results.insert(
+                    (data.name, data.run_location.unwrap_or_default()),
+                    (data.success, data.message),
+                );
+            }
+        }
+        assert_eq!(
+            results.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    ("down".into(), "north".into()),
+                    (false, Some("north is unreachable".into()))
+                ),
+                (
+                    ("down".into(), "west".into()),
+                    (false, Some("west is unreachable".into()))
+                ),
+                (("up".into(), "north".into()), (true, None)),
+                (("up".into(), "west".into()), (true, None)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_probe_that_exceeds_timeout() {
+        let events = Arc::new(SegQueue::default());
+        let runner = AvailabilityRunner::new(create_client(events.clone()))
+            .timeout(Duration::from_millis(10))
+            .probe("slow", |_: String| async {
+                time::sleep(Duration::from_secs(10)).await;
+                Ok::<_, String>(())
+            });
+
+        runner.run_once().await;
+
+        match events.pop().and_then(|envelope| envelope.data) {
+            Some(Base::Data(Data::AvailabilityData(data))) => {
+                assert!(!data.success);
+                assert_eq!(data.message, Some("Timed out after 10 ms".into()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_fails_probe_that_panics_and_keeps_running_others() {
+        let events = Arc::new(SegQueue::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let handle = AvailabilityRunner::new(create_client(events.clone()))
+            .every(Duration::from_millis(20))
+            .probe("broken", |_: String| async {
+                panic!("probe failed");
+                #[allow(unreachable_code)]
+                Ok::<_, String>(())
+            })
+            .probe("counted", move |_: String| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, String>(()) }
+            })
+            .start();
+
+        time::sleep(Duration::from_millis(50)).await;
+        handle.stop().await;
+
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        let mut panicked = 0;
+        while let Some(envelope) = events.pop() {
+            if let Some(Base::Data(Data::AvailabilityData(data))) = envelope.data {
+                if data.name == "broken" {
+                    assert!(!data.success);
+                    assert_eq!(data.message, Some("Panicked: probe failed".into()));
+                    panicked += 1;
+                }
+            }
+        }
+        assert!(panicked >= 2, "panicking probe ran {} times", panicked);
+    }
+
+    #[test]
+    #[should_panic(expected = "period of availability tests must be non-zero")]
+    fn it_rejects_zero_period() {
+        let events = Arc::new(SegQueue::default());
+        let _ = AvailabilityRunner::new(create_client(events)).every(Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn it_runs_probes_periodically_until_stopped() {
+        let events = Arc::new(SegQueue::default());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let handle = AvailabilityRunner::new(create_client(events.clone()))
+            .every(Duration::from_millis(20))
+            .probe("counted", move |_: String| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Ok::<_, String>(()) }
+            })
+            .start();
+
+        time::sleep(Duration::from_millis(50)).await;
+        handle.stop().await;
+
+        let count = runs.load(Ordering::SeqCst);
+        assert!(count >= 2, "probe ran {} times", count);
+        time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), count);
+        assert_eq!(events.len(), count);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+}
@@ -22,18 +22,23 @@
 //! client.close_channel();
 //! ```
 
-use std::{fmt::Display, time::Duration};
+use std::{fmt::Display, sync::Arc, time::Duration};
 
 use http::{Method, Uri};
 use log::debug;
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time};
 
 use crate::{
     channel::{InMemoryChannel, TelemetryChannel},
-    contracts::Envelope,
+    client,
+    clock::{self, SharedClock, Timestamping},
+    contracts::{tags, Envelope, SeverityLevel as ContractsSeverityLevel},
+    daily_cap::DailyCapGuard,
+    processor::{DependencySuccess, ProcessingContext, Processors, UrlRedaction},
+    standard_metrics::{self, StandardMetrics},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, MetricTelemetry, RemoteDependencyTelemetry, RequestTelemetry,
-        SeverityLevel, Telemetry, TraceTelemetry,
+        AvailabilityTelemetry, EventTelemetry, FeedbackTelemetry, IntoEnvelope, MetricTelemetry, Priority,
+        RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, Telemetry, TelemetryKind, TraceTelemetry,
     },
     TelemetryConfig, TelemetryContext,
 };
@@ -83,6 +88,21 @@ impl TelemetryClient {
         &mut self.inner.context
     }
 
+    /// Replaces an instrumentation key telemetry items are submitted with. Items tracked from now on
+    /// get the new key, while items queued already keep the old one.
+    pub fn rotate_instrumentation_key(&mut self, i_key: impl Into<String>) {
+        self.inner.context.set_i_key(i_key);
+    }
+
+    /// Replaces an instrumentation key telemetry items are submitted with and stamps items queued
+    /// with the old key with the new one when they are sent.
+    pub fn rotate_instrumentation_key_and_restamp(&mut self, i_key: impl Into<String>) {
+        let i_key = i_key.into();
+        let from = self.inner.context.i_key().to_string();
+        self.inner.context.set_i_key(i_key.clone());
+        self.inner.inner.restamp(from, i_key);
+    }
+
     /// Logs a user action with the specified name.
     pub fn track_event(&self, name: impl Into<String>) {
         let event = EventTelemetry::new(name);
@@ -126,13 +146,36 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs a feedback a user gave in the application with a score and an optional comment.
+    pub fn track_feedback(&self, score: f64, comment: Option<&str>) {
+        let mut event = FeedbackTelemetry::new(score);
+        if let Some(comment) = comment {
+            event.set_comment(comment);
+        }
+        self.track(event)
+    }
+
     /// Submits a specific telemetry event.
     pub fn track<E>(&self, event: E)
     where
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
-        self.inner.track(event);
+        self.inner.track(|context| (context, event).into());
+    }
+
+    /// Submits a telemetry event which type is not known at compile time.
+    pub fn track_boxed(&self, event: Box<dyn IntoEnvelope>) {
+        self.inner.track(|context| event.into_envelope(context));
+    }
+
+    /// Submits an envelope as is without combining it with the client context, e.g. an envelope
+    /// received by a relay service or read from a dead letter file. See
+    /// [`track_envelope`](crate::TelemetryClient::track_envelope) of the async client for details.
+    pub fn track_envelope(&self, envelope: Envelope) {
+        if self.inner.is_enabled() {
+            self.inner.send(envelope);
+        }
     }
 
     /// Forces all pending telemetry items to be submitted. The current thread will not be blocked.
@@ -196,6 +239,15 @@ impl TelemetryClient {
 struct ChannelHandle {
     enabled: bool,
     context: TelemetryContext,
+    processors: Processors,
+    disabled_types: Vec<TelemetryKind>,
+    min_severity: Option<ContractsSeverityLevel>,
+    url_redaction: UrlRedaction,
+    dependency_success: DependencySuccess,
+    clock: Option<SharedClock>,
+    sdk_version: String,
+    standard_metrics: Option<Arc<StandardMetrics>>,
+    daily_cap: Option<DailyCapGuard>,
     inner: InnerChannelHandle,
 }
 
@@ -206,6 +258,17 @@ impl ChannelHandle {
         F: FnOnce(&TelemetryConfig) -> C + Send + 'static,
     {
         let context = TelemetryContext::from_config(&config);
+        let processors = config.processors().clone();
+        let disabled_types = config.disabled_types().to_vec();
+        let min_severity = config.min_severity().map(Into::into);
+        let url_redaction = config.url_redaction().clone();
+        let dependency_success = config.dependency_success().clone();
+        let clock = config.clock_at(Timestamping::OnTrack).cloned();
+        let sdk_version: String = config.sdk_version().into();
+        let standard_metrics = StandardMetrics::from_config(&config).map(Arc::new);
+        // periods of standard metrics are drained on the same thread that sends items
+        let drained = standard_metrics.clone().map(|metrics| (metrics, sdk_version.clone()));
+        let daily_cap = DailyCapGuard::from_config(&config);
 
         let (tx, mut rx) = mpsc::unbounded_channel::<(ClientCommand, OneshotResponse)>();
 
@@ -218,14 +281,36 @@ impl ChannelHandle {
                     .expect("tokio runtime");
 
                 let f = async move {
-                    let mut channel = channel(&config);
-
-                    while let Some((command, req_tx)) = rx.recv().await {
+                    let channel = channel(&config);
+                    let mut drain = time::interval(standard_metrics::DRAIN_INTERVAL);
+
+                    loop {
+                        let (command, req_tx) = tokio::select! {
+                            received = rx.recv() => match received {
+                                Some(received) => received,
+                                None => break,
+                            },
+                            _ = drain.tick(), if drained.is_some() => {
+                                if let Some((metrics, sdk_version)) = &drained {
+                                    client::send_standard_metrics(&channel, sdk_version, metrics.take_expired());
+                                }
+                                continue;
+                            }
+                        };
                         match command {
-                            ClientCommand::Envelope(envelop) => channel.send(envelop),
+                            ClientCommand::Envelope(mut envelop) => {
+                                // a priority is passed to a channel aside, so it is never sent to the server
+                                let priority = Priority::take(&mut envelop);
+                                channel.send_with_priority(envelop, priority);
+                            }
                             ClientCommand::Flush => channel.flush(),
-                            ClientCommand::Stop => channel.close().await,
-                            ClientCommand::Terminate => channel.terminate().await,
+                            ClientCommand::Restamp(from, to) => channel.restamp_i_key(&from, &to),
+                            ClientCommand::Stop => {
+                                channel.close().await;
+                            }
+                            ClientCommand::Terminate => {
+                                channel.terminate().await;
+                            }
                         }
                         let _ = req_tx.send(());
                     }
@@ -243,6 +328,15 @@ impl ChannelHandle {
             inner,
             enabled: true,
             context,
+            processors,
+            disabled_types,
+            min_severity,
+            url_redaction,
+            dependency_success,
+            clock,
+            sdk_version,
+            standard_metrics,
+            daily_cap,
         }
     }
 
@@ -254,33 +348,99 @@ impl ChannelHandle {
         self.enabled = enabled;
     }
 
-    fn track<E>(&self, event: E)
+    fn track<C>(&self, convert: C)
     where
-        E: Telemetry,
-        (TelemetryContext, E): Into<Envelope>,
+        C: FnOnce(TelemetryContext) -> Envelope,
     {
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
-            let command = ClientCommand::Envelope(envelop);
+            let mut envelop = convert(self.context.clone());
+            self.url_redaction.apply(&mut envelop);
+            self.dependency_success.apply(&mut envelop);
+            if let Some(offset) = self.clock.as_ref().and_then(SharedClock::offset) {
+                clock::shift(&mut envelop, offset);
+            }
+            self.send(envelop);
+        }
+    }
 
-            let (tx, mut rx) = mpsc::channel(1);
+    fn send(&self, mut envelop: Envelope) {
+        if TelemetryKind::of(&envelop).is_some_and(|kind| self.disabled_types.contains(&kind)) {
+            return;
+        }
+        if self
+            .min_severity
+            .as_ref()
+            .is_some_and(|min| envelop.is_less_severe_than(min))
+        {
+            return;
+        }
 
-            self.inner
-                .tx
-                .as_ref()
-                .expect("sync thread exited early")
-                .send((command, tx))
-                .expect("sync thread panicked");
+        if let Some(standard_metrics) = &self.standard_metrics {
+            self.send_standard_metrics(standard_metrics.extract(&mut envelop));
+        }
+
+        envelop.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, &self.sdk_version);
+        if !self.processors.process(&mut envelop, ProcessingContext::new(None)) {
+            return;
+        }
 
-            let _ = rx.blocking_recv();
+        if let Some(daily_cap) = &self.daily_cap {
+            let admission = daily_cap.admit(&mut envelop);
+            self.send_daily_cap_summary(admission.summary);
+            if !admission.admitted {
+                return;
+            }
         }
+
+        self.submit(envelop);
+    }
+
+    fn send_daily_cap_summary(&self, summary: Option<EventTelemetry>) {
+        if let Some(summary) = summary {
+            let mut envelop: Envelope = (self.context.clone(), summary).into();
+            envelop.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, &self.sdk_version);
+            self.submit(envelop);
+        }
+    }
+
+    fn send_standard_metrics(&self, metrics: Vec<Envelope>) {
+        for mut metric in metrics {
+            metric.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, &self.sdk_version);
+            self.submit(metric);
+        }
+    }
+
+    fn take_standard_metrics(&self) {
+        if let Some(standard_metrics) = &self.standard_metrics {
+            self.send_standard_metrics(standard_metrics.take());
+        }
+    }
+
+    fn submit(&self, envelop: Envelope) {
+        let command = ClientCommand::Envelope(envelop);
+
+        let (tx, mut rx) = mpsc::channel(1);
+
+        self.inner
+            .tx
+            .as_ref()
+            .expect("sync thread exited early")
+            .send((command, tx))
+            .expect("sync thread panicked");
+
+        let _ = rx.blocking_recv();
     }
 
     fn flush(&self) {
+        self.take_standard_metrics();
         self.inner.flush();
     }
 
     fn close(mut self) {
+        self.take_standard_metrics();
+        if let Some(daily_cap) = &self.daily_cap {
+            self.send_daily_cap_summary(daily_cap.take_summary());
+        }
         self.inner.shutdown(ClientCommand::Stop)
     }
 }
@@ -301,6 +461,12 @@ impl InnerChannelHandle {
         }
     }
 
+    fn restamp(&self, from: String, to: String) {
+        if let Some(sender) = &self.tx {
+            send_command(sender, ClientCommand::Restamp(from, to));
+        }
+    }
+
     fn shutdown(&mut self, command: ClientCommand) {
         if let Some(sender) = self.tx.take() {
             send_command(&sender, command);
@@ -328,6 +494,7 @@ fn send_command(sender: &ThreadSender, command: ClientCommand) {
 enum ClientCommand {
     Envelope(Envelope),
     Flush,
+    Restamp(String, String),
     Stop,
     Terminate,
 }
@@ -337,6 +504,7 @@ impl Display for ClientCommand {
         let message = match self {
             ClientCommand::Envelope(_) => "event",
             ClientCommand::Flush => "flush",
+            ClientCommand::Restamp(..) => "restamp",
             ClientCommand::Stop => "stop",
             ClientCommand::Terminate => "terminate",
         };
@@ -347,13 +515,13 @@ impl Display for ClientCommand {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{collections::BTreeMap, sync::Arc};
 
     use crossbeam_queue::SegQueue;
     use matches::assert_matches;
 
     use super::*;
-    use crate::client::tests::{TestChannel, TestTelemetry};
+    use crate::client::tests::TestChannel;
 
     #[test]
     fn it_enabled_by_default() {
@@ -375,7 +543,7 @@ mod tests {
         let events = Arc::new(SegQueue::default());
         let client = create_client(events.clone());
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert_eq!(events.len(), 1)
     }
@@ -386,11 +554,28 @@ mod tests {
         let mut client = create_client(events.clone());
         client.enabled(false);
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert!(events.is_empty())
     }
 
+    #[test]
+    fn it_submits_envelope_without_client_context() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let envelope = Envelope {
+            name: "Microsoft.ApplicationInsights.Event".into(),
+            i_key: Some("relayed".into()),
+            tags: Some(BTreeMap::from([(tags::INTERNAL_SDK_VERSION.into(), "c:1.2.0".into())]).into()),
+            ..Envelope::default()
+        };
+        client.track_envelope(envelope.clone());
+
+        assert_eq!(events.pop(), Some(envelope));
+        assert!(events.is_empty());
+    }
+
     #[test]
     fn it_creates_client_with_default_tags() {
         let client = TelemetryClient::new("instrumentation".into());
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use crate::{clock::SharedClock, time, uuid, TelemetryConfig};
+
+/// Calculates how long to wait until the next batch of telemetry items is sent.
+///
+/// A random jitter spreads submissions of many instances started at the same time, while
+/// alignment makes submissions happen at wall-clock multiples of the interval.
+#[derive(Debug, Clone)]
+pub struct Interval {
+    period: Duration,
+    jitter: Option<Duration>,
+    aligned: bool,
+    manual: bool,
+    clock: Option<SharedClock>,
+}
+
+impl Interval {
+    pub fn from_config(config: &TelemetryConfig) -> Self {
+        Self {
+            period: config.interval(),
+            jitter: config.interval_jitter(),
+            aligned: config.is_interval_aligned(),
+            manual: config.is_manual_flush_only(),
+            clock: config.clock().cloned(),
+        }
+    }
+
+    /// Returns how long to wait until the next batch is sent. Returns `None` if batches are sent
+    /// only when an application flushes them.
+    pub fn next(&self) -> Option<Duration> {
+        if self.manual {
+            return None;
+        }
+
+        let mut timeout = if self.aligned {
+            self.until_next_boundary()
+        } else {
+            self.period
+        };
+
+        if let Some(jitter) = self.jitter.filter(|jitter| !jitter.is_zero()) {
+            let nanos = uuid::new().as_u128() % jitter.as_nanos();
+            timeout += Duration::from_nanos(nanos as u64);
+        }
+
+        Some(timeout)
+    }
+
+    /// Returns the time remaining until the next wall-clock multiple of the period.
+    fn until_next_boundary(&self) -> Duration {
+        let period = self.period.as_nanos();
+        if period == 0 {
+            return self.period;
+        }
+
+        let now = self.clock.as_ref().map_or_else(time::now, SharedClock::now);
+        let since_epoch = now.timestamp() as u128 * 1_000_000_000 + now.timestamp_subsec_nanos() as u128;
+        Duration::from_nanos((period - since_epoch % period) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::*;
+    use crate::clock::Clock;
+
+    #[test]
+    fn it_uses_configured_interval_by_default() {
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .interval(Duration::from_secs(2))
+            .build();
+
+        let interval = Interval::from_config(&config);
+
+        assert_eq!(interval.next(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn it_adds_jitter_within_configured_range() {
+        uuid::set(uuid::Uuid::from_u128(1_500_000_042));
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .interval(Duration::from_secs(2))
+            .interval_jitter(Duration::from_secs(1))
+            .build();
+
+        let interval = Interval::from_config(&config);
+
+        assert_eq!(
+            interval.next(),
+            Some(Duration::from_secs(2) + Duration::from_nanos(500_000_042))
+        );
+        uuid::reset();
+    }
+
+    #[test]
+    fn it_aligns_interval_to_wall_clock() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600));
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .interval(Duration::from_secs(10))
+            .align_interval(true)
+            .build();
+
+        let interval = Interval::from_config(&config);
+
+        assert_eq!(interval.next(), Some(Duration::from_millis(4400)));
+        time::reset();
+    }
+
+    #[test]
+    fn it_aligns_interval_to_custom_clock() {
+        struct FixedClock;
+
+        impl Clock for FixedClock {
+            fn now(&self) -> Option<DateTime<Utc>> {
+                Some(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 7, 250))
+            }
+        }
+
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .interval(Duration::from_secs(10))
+            .align_interval(true)
+            .clock(FixedClock)
+            .build();
+
+        let interval = Interval::from_config(&config);
+
+        assert_eq!(interval.next(), Some(Duration::from_millis(2750)));
+    }
+
+    #[test]
+    fn it_does_not_expire_when_flushed_manually() {
+        let config = TelemetryConfig::builder()
+            .i_key("key")
+            .interval(Duration::from_secs(2))
+            .manual_flush_only(true)
+            .build();
+
+        let interval = Interval::from_config(&config);
+
+        assert_eq!(interval.next(), None);
+    }
+}
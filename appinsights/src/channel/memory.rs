@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use async_trait::async_trait;
 use crossbeam_queue::SegQueue;
@@ -7,74 +7,161 @@ use log::{debug, trace, warn};
 use tokio::task::JoinHandle;
 
 use crate::{
-    channel::{command::Command, state::Worker, TelemetryChannel},
+    channel::{
+        command::Command, interval::Interval, pending::HeldItems, snapshot::Snapshot, state::Worker, ShutdownReport,
+        TelemetryChannel,
+    },
     contracts::Envelope,
-    transmitter::Transmitter,
+    instrumentation,
+    internal_logger::{InternalEvent, InternalLogger},
+    queue::Priorities,
+    sequence::{Sequencer, SessionSequence},
+    telemetry::Priority,
+    transmitter::{KeyRotations, Transmitter},
     TelemetryConfig,
 };
 
 /// A telemetry channel that stores events exclusively in memory.
 pub struct InMemoryChannel {
-    items: Arc<SegQueue<Envelope>>,
-    command_sender: Option<UnboundedSender<Command>>,
-    join: Option<JoinHandle<()>>,
+    items: Arc<SegQueue<(Priority, Envelope)>>,
+    command_sender: Mutex<Option<UnboundedSender<Command>>>,
+    join: Mutex<Option<JoinHandle<ShutdownReport>>>,
+    key_rotations: Arc<KeyRotations>,
+    held: Arc<HeldItems>,
+    priorities: Priorities,
+    sequence: Option<SessionSequence>,
+    logger: InternalLogger,
 }
 
 impl InMemoryChannel {
     /// Creates a new instance of in-memory channel and starts a submission routine.
     pub fn new(config: &TelemetryConfig) -> Self {
         let items = Arc::new(SegQueue::new());
+        let logger = InternalLogger::from_config(config);
 
+        let transmitter = Transmitter::from_config(config, logger.clone());
+        let key_rotations = transmitter.key_rotations();
+
+        let held = Arc::new(HeldItems::default());
         let (command_sender, command_receiver) = futures_channel::mpsc::unbounded();
         let worker = Worker::new(
-            Transmitter::new(config.endpoint()),
+            transmitter,
             items.clone(),
             command_receiver,
-            config.interval(),
+            Interval::from_config(config),
+            config.time_to_live(),
+            config.max_concurrent_transmissions(),
+            config.max_batch_size(),
+            config.drain_pace(),
+            config.max_queue_size(),
+            config.drop_policy().cloned().unwrap_or_default(),
+            config.clock().cloned(),
+            config.timestamping(),
+            config.scheduler().cloned(),
+            config
+                .sequence_store()
+                .map(|store| Arc::new(Sequencer::new(store.clone(), logger.clone()))),
+            config.throttle_store().cloned(),
+            config.dead_letter_sink().cloned(),
+            config.worker_tasks().to_vec(),
+            Snapshot::from_config(config),
+            held.clone(),
+            logger.clone(),
         );
 
-        let handle = tokio::spawn(worker.run());
+        let handle = tokio::spawn(instrumentation::in_worker_span(worker.run()));
 
         Self {
             items,
-            command_sender: Some(command_sender),
-            join: Some(handle),
+            command_sender: Mutex::new(Some(command_sender)),
+            join: Mutex::new(Some(handle)),
+            key_rotations,
+            held,
+            priorities: config.priorities().clone(),
+            sequence: config.sequence_store().is_none().then(SessionSequence::new),
+            logger,
         }
     }
 
-    async fn shutdown(&mut self, command: Command) {
+    async fn shutdown(&self, command: Command) -> ShutdownReport {
         // send shutdown command
-        if let Some(sender) = self.command_sender.take() {
+        let sender = self
+            .command_sender
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(sender) = sender {
             send_command(&sender, command);
         }
 
         // wait until worker is finished
-        if let Some(handle) = self.join.take() {
-            debug!("Shutting down worker");
-            handle.await.unwrap();
-        }
+        let handle = self.join.lock().unwrap_or_else(PoisonError::into_inner).take();
+        let report = match handle {
+            Some(handle) => {
+                debug!("Shutting down worker");
+                handle.await.unwrap()
+            }
+            None => ShutdownReport::default(),
+        };
+
+        // submit diagnostics events reported by the worker
+        self.logger.close().await;
+
+        report
     }
 }
 
 #[async_trait]
 impl TelemetryChannel for InMemoryChannel {
-    fn send(&self, envelop: Envelope) {
+    fn send(&self, mut envelop: Envelope) {
+        let priority = Priority::take(&mut envelop);
+        self.send_with_priority(envelop, priority);
+    }
+
+    fn send_with_priority(&self, mut envelop: Envelope, priority: Option<Priority>) {
         trace!("Sending telemetry to channel");
-        self.items.push(envelop);
+        // a worker sends items of a higher priority first and drops items of a lower priority first
+        let priority = self.priorities.of(&envelop, priority);
+        // a sequence number is assigned in the order items are accepted unless batches are numbered
+        if let Some(sequence) = &self.sequence {
+            sequence.stamp(&mut envelop);
+        }
+        self.items.push((priority, envelop));
     }
 
     fn flush(&self) {
-        if let Some(sender) = &self.command_sender {
+        if let Some(sender) = &*self.command_sender.lock().unwrap_or_else(PoisonError::into_inner) {
             send_command(sender, Command::Flush);
         }
     }
 
-    async fn close(&mut self) {
+    fn restamp_i_key(&self, from: &str, to: &str) {
+        self.key_rotations.rotate(from, to);
+    }
+
+    fn pending(&self) -> usize {
+        self.items.len() + self.held.count()
+    }
+
+    fn clear_pending(&self) -> usize {
+        let mut count = self.held.count();
+        self.held.clear();
+        while self.items.pop().is_some() {
+            count += 1;
+        }
+
+        if count > 0 {
+            self.logger.log(InternalEvent::PendingCleared { count });
+        }
+        count
+    }
+
+    async fn close(&self) -> ShutdownReport {
         self.shutdown(Command::Close).await
     }
 
-    async fn terminate(&mut self) {
-        self.shutdown(Command::Terminate).await;
+    async fn terminate(&self) -> ShutdownReport {
+        self.shutdown(Command::Terminate).await
     }
 }
 
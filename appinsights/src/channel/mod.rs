@@ -1,15 +1,24 @@
 mod command;
 
+mod interval;
+
 mod memory;
 pub use memory::InMemoryChannel;
 
+mod pending;
+
+mod report;
+pub use report::{ShutdownReport, TransmissionStatus};
+
 mod retry;
 
+mod snapshot;
+
 mod state;
 
 use async_trait::async_trait;
 
-use crate::contracts::Envelope;
+use crate::{contracts::Envelope, telemetry::Priority};
 
 /// An implementation of [TelemetryChannel](trait.TelemetryChannel.html) is responsible for queueing
 /// and periodically submitting telemetry events.
@@ -18,18 +27,41 @@ pub trait TelemetryChannel: Send + Sync {
     /// Queues a single telemetry item.
     fn send(&self, envelop: Envelope);
 
+    /// Queues a single telemetry item with a priority it overrides the one of its category with, if
+    /// any. A client takes the priority off an item, so it is never sent to the server. Channels
+    /// that do not prioritize items queue it with [send](#tymethod.send).
+    fn send_with_priority(&self, envelop: Envelope, _priority: Option<Priority>) {
+        self.send(envelop)
+    }
+
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
     fn flush(&self);
 
+    /// Stamps pending telemetry items having one instrumentation key with another one when they
+    /// are submitted. Channels that do not keep pending items ignore it.
+    fn restamp_i_key(&self, _from: &str, _to: &str) {}
+
+    /// Returns the number of telemetry items waiting to be sent. Channels that do not keep pending
+    /// items return zero.
+    fn pending(&self) -> usize {
+        0
+    }
+
+    /// Discards telemetry items waiting to be sent and returns the number of them. Items being
+    /// sent already are not affected. Channels that do not keep pending items return zero.
+    fn clear_pending(&self) -> usize {
+        0
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
-    async fn close(&mut self);
+    async fn close(&self) -> ShutdownReport;
 
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
     /// Tears down the submission flow and closes internal channels. Any telemetry waiting to be sent is discarded.
     /// This is a more abrupt version of [close](#method.close).
-    async fn terminate(&mut self);
+    async fn terminate(&self) -> ShutdownReport;
 }
@@ -0,0 +1,164 @@
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::{contracts::Envelope, telemetry::Priority};
+
+/// Telemetry items a submission worker has taken off the queue but not sent yet, like a backlog
+/// of a queue being drained or a batch waiting to be retried. It is shared with a channel, so an
+/// application can inspect and discard them.
+#[derive(Debug, Default)]
+pub(crate) struct HeldItems {
+    count: AtomicUsize,
+    cleared: AtomicBool,
+}
+
+impl HeldItems {
+    /// Returns the number of items the worker holds.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Acquire)
+    }
+
+    /// Publishes the number of items the worker holds.
+    pub fn update(&self, count: usize) {
+        self.count.store(count, Ordering::Release);
+    }
+
+    /// Asks the worker to discard items it holds.
+    pub fn clear(&self) {
+        self.cleared.store(true, Ordering::Release);
+        self.count.store(0, Ordering::Release);
+    }
+
+    /// Returns `true` once if items were asked to be discarded since the last call.
+    pub fn take_cleared(&self) -> bool {
+        self.cleared.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Telemetry items a submission worker has moved aside from the queue, in queues of their
+/// priorities, so they are collected from the highest priority to the lowest without sorting them.
+/// Items put back, e.g. those that did not fit into a batch, are collected before all others.
+#[derive(Debug, Default)]
+pub(crate) struct Backlog {
+    front: VecDeque<(Priority, Envelope)>,
+    queues: [VecDeque<Envelope>; 3],
+}
+
+impl Backlog {
+    /// Queues an item of a given priority after items of the same priority.
+    pub fn push(&mut self, priority: Priority, item: Envelope) {
+        self.queues[index(priority)].push_back(item);
+    }
+
+    /// Puts items back in front of all others in a given order.
+    pub fn put_back(&mut self, items: Vec<(Priority, Envelope)>) {
+        items.into_iter().rev().for_each(|item| self.front.push_front(item));
+    }
+
+    /// Takes the next item to collect along with its priority.
+    pub fn pop(&mut self) -> Option<(Priority, Envelope)> {
+        self.front.pop_front().or_else(|| {
+            self.queues
+                .iter_mut()
+                .zip([Priority::Low, Priority::Normal, Priority::High])
+                .rev()
+                .find_map(|(queue, priority)| queue.pop_front().map(|item| (priority, item)))
+        })
+    }
+
+    /// Takes all items with their priorities in the order they are collected.
+    pub fn take_all(&mut self) -> Vec<(Priority, Envelope)> {
+        let mut items: Vec<_> = self.front.drain(..).collect();
+        for (queue, priority) in self
+            .queues
+            .iter_mut()
+            .zip([Priority::Low, Priority::Normal, Priority::High])
+            .rev()
+        {
+            items.extend(queue.drain(..).map(|item| (priority, item)));
+        }
+        items
+    }
+
+    /// Returns the number of items in the backlog.
+    pub fn len(&self) -> usize {
+        self.front.len() + self.queues.iter().map(VecDeque::len).sum::<usize>()
+    }
+
+    /// Discards all items.
+    pub fn clear(&mut self) {
+        self.front.clear();
+        self.queues.iter_mut().for_each(VecDeque::clear);
+    }
+}
+
+/// Returns a position of a queue of items of a given priority.
+fn index(priority: Priority) -> usize {
+    match priority {
+        Priority::Low => 0,
+        Priority::Normal => 1,
+        Priority::High => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_collects_items_put_back_first_then_by_priority_in_order_they_were_queued() {
+        let mut backlog = Backlog::default();
+        backlog.push(Priority::Low, envelope("low"));
+        backlog.push(Priority::Normal, envelope("normal 1"));
+        backlog.push(Priority::High, envelope("high"));
+        backlog.push(Priority::Normal, envelope("normal 2"));
+        backlog.put_back(vec![
+            (Priority::Low, envelope("rest 1")),
+            (Priority::Normal, envelope("rest 2")),
+        ]);
+        assert_eq!(backlog.len(), 6);
+
+        let items = backlog.take_all();
+        let taken: Vec<_> = items
+            .iter()
+            .map(|(priority, item)| (*priority, item.name.as_str()))
+            .collect();
+        assert_eq!(
+            taken,
+            vec![
+                (Priority::Low, "rest 1"),
+                (Priority::Normal, "rest 2"),
+                (Priority::High, "high"),
+                (Priority::Normal, "normal 1"),
+                (Priority::Normal, "normal 2"),
+                (Priority::Low, "low"),
+            ]
+        );
+        assert_eq!(backlog.len(), 0);
+
+        backlog.put_back(items);
+        let popped: Vec<_> = std::iter::from_fn(|| backlog.pop())
+            .map(|(priority, item)| (priority, item.name))
+            .collect();
+        assert_eq!(
+            popped,
+            vec![
+                (Priority::Low, "rest 1".into()),
+                (Priority::Normal, "rest 2".into()),
+                (Priority::High, "high".into()),
+                (Priority::Normal, "normal 1".into()),
+                (Priority::Normal, "normal 2".into()),
+                (Priority::Low, "low".into()),
+            ]
+        );
+    }
+
+    fn envelope(name: &str) -> Envelope {
+        Envelope {
+            name: name.into(),
+            ..Envelope::default()
+        }
+    }
+}
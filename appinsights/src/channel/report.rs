@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use crate::{transmitter::Response, Error};
+
+/// A summary of a telemetry channel shutdown. It tells whether telemetry tracked before a client
+/// was closed has made it out, so an application can log it or retry on its own.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # async fn run() {
+/// use appinsights::TelemetryClient;
+///
+/// let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// client.track_event("batch job finished");
+///
+/// let report = client.close_channel().await;
+/// if !report.is_complete() {
+///     eprintln!("{} telemetry items were not sent: {:?}", report.abandoned(), report.status());
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    flushed: usize,
+    abandoned: usize,
+    expired: usize,
+    acked_sequence: Option<u64>,
+    status: Option<TransmissionStatus>,
+}
+
+impl ShutdownReport {
+    pub(crate) fn new(flushed: usize, abandoned: usize, status: Option<TransmissionStatus>) -> Self {
+        Self {
+            flushed,
+            abandoned,
+            expired: 0,
+            acked_sequence: None,
+            status,
+        }
+    }
+
+    /// Sets the number of telemetry items dropped because they were older than time to live.
+    pub(crate) fn with_expired(mut self, expired: usize) -> Self {
+        self.expired = expired;
+        self
+    }
+
+    /// Sets the highest sequence number of a batch acknowledged by the server.
+    pub(crate) fn with_acked_sequence(mut self, acked_sequence: Option<u64>) -> Self {
+        self.acked_sequence = acked_sequence;
+        self
+    }
+
+    /// Returns the number of telemetry items submitted to the server during shutdown.
+    pub fn flushed(&self) -> usize {
+        self.flushed
+    }
+
+    /// Returns the number of telemetry items discarded without being submitted to the server.
+    pub fn abandoned(&self) -> usize {
+        self.abandoned
+    }
+
+    /// Returns the number of telemetry items dropped during the lifetime of the channel because they
+    /// were older than a configured [time to live](crate::TelemetryConfig::time_to_live).
+    pub fn expired(&self) -> usize {
+        self.expired
+    }
+
+    /// Returns the highest sequence number of a batch acknowledged by the server if a
+    /// [sequence store](crate::sequence) is configured and any batch was acknowledged.
+    pub fn acked_sequence(&self) -> Option<u64> {
+        self.acked_sequence
+    }
+
+    /// Returns the status of the most recent submission to the server, if any.
+    pub fn status(&self) -> Option<&TransmissionStatus> {
+        self.status.as_ref()
+    }
+
+    /// Returns `true` if no telemetry items were discarded during shutdown.
+    pub fn is_complete(&self) -> bool {
+        self.abandoned == 0
+    }
+}
+
+/// A status of a submission of telemetry items to the server.
+#[derive(Debug, Clone)]
+pub enum TransmissionStatus {
+    /// The server accepted all telemetry items.
+    Accepted,
+
+    /// The server rejected telemetry items that cannot be sent again.
+    Rejected,
+
+    /// The server asked to send some of telemetry items again later.
+    RetryRequested,
+
+    /// The endpoint host could not be resolved.
+    Unreachable,
+
+    /// Telemetry items could not be sent due to an error.
+    Failed(Arc<Error>),
+}
+
+impl TransmissionStatus {
+    /// Returns the status of a submission of a given number of telemetry items together with the
+    /// number of items that are not going to be sent again.
+    pub(crate) fn of<T>(count: usize, response: &Result<Response<T>, Arc<Error>>) -> (Self, usize) {
+        match response {
+            Ok(Response::Success) => (TransmissionStatus::Accepted, count),
+            Ok(Response::NoRetry) => (TransmissionStatus::Rejected, count),
+            Ok(Response::Retry(items)) | Ok(Response::Throttled(_, items)) => {
+                (TransmissionStatus::RetryRequested, count.saturating_sub(items.len()))
+            }
+            Ok(Response::ResolutionFailed(_)) => (TransmissionStatus::Unreachable, 0),
+            Err(err) => (TransmissionStatus::Failed(err.clone()), 0),
+        }
+    }
+}
+
+impl PartialEq for TransmissionStatus {
+    /// Compares statuses of submissions. Errors are equal when they are described the same way.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Failed(left), Self::Failed(right)) => left.to_string() == right.to_string(),
+            (left, right) => std::mem::discriminant(left) == std::mem::discriminant(right),
+        }
+    }
+}
+
+impl Eq for TransmissionStatus {}
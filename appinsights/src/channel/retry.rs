@@ -1,13 +1,23 @@
 use std::time::Duration;
 
 /// Encapsulates retry logic for submit telemetry items operation.
+///
+/// Failures to resolve the endpoint host are retried on their own schedule, so they don't
+/// exhaust attempts reserved for HTTP errors.
 #[derive(Default, Debug)]
-pub struct Retry(Vec<Duration>);
+pub struct Retry {
+    timeouts: Vec<Duration>,
+    resolution_timeouts: Vec<Duration>,
+}
 
 impl Retry {
     pub fn exponential() -> Self {
         let timeouts = vec![Duration::from_secs(16), Duration::from_secs(4), Duration::from_secs(2)];
-        Self(timeouts)
+        let resolution_timeouts = vec![Duration::from_secs(10), Duration::from_secs(5), Duration::from_secs(1)];
+        Self {
+            timeouts,
+            resolution_timeouts,
+        }
     }
 
     pub fn once() -> Self {
@@ -15,6 +25,10 @@ impl Retry {
     }
 
     pub fn next(&mut self) -> Option<Duration> {
-        self.0.pop()
+        self.timeouts.pop()
+    }
+
+    pub fn next_resolution(&mut self) -> Option<Duration> {
+        self.resolution_timeouts.pop()
     }
 }
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    context::TelemetryContext,
+    contracts::Envelope,
+    telemetry::{EventTelemetry, Telemetry},
+    TelemetryConfig,
+};
+
+/// A name of an event that describes how a deployed instance is configured.
+const SNAPSHOT_EVENT_NAME: &str = "Application Insights SDK Snapshot";
+
+/// Optional features the SDK was compiled with.
+const FEATURES: &[(&str, bool)] = &[
+    ("reqwest", cfg!(feature = "reqwest")),
+    ("rustls", cfg!(feature = "rustls")),
+    ("hyper-client", cfg!(feature = "hyper-client")),
+    ("agent", cfg!(feature = "agent")),
+    ("blocking", cfg!(feature = "blocking")),
+    ("tower", cfg!(feature = "tower")),
+    ("reqwest-middleware", cfg!(feature = "reqwest-middleware")),
+    ("tonic", cfg!(feature = "tonic")),
+    ("log", cfg!(feature = "log")),
+    ("sqlx", cfg!(feature = "sqlx")),
+    ("test-util", cfg!(feature = "test-util")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("diagnostics", cfg!(feature = "diagnostics")),
+    ("json-schema", cfg!(feature = "json-schema")),
+    ("pool", cfg!(feature = "pool")),
+];
+
+/// Cumulative counters of a submission worker since it has started.
+#[derive(Debug, Default)]
+pub(crate) struct PipelineStats {
+    /// The number of submission intervals elapsed.
+    pub ticks: u64,
+
+    /// The number of items waiting to be sent.
+    pub pending: usize,
+
+    /// The number of items the server has received.
+    pub sent: usize,
+
+    /// The number of items dropped without being received by the server.
+    pub lost: usize,
+
+    /// The number of items dropped because they were older than time to live.
+    pub expired: usize,
+
+    /// The number of items dropped because the queue was full.
+    pub overflowed: usize,
+}
+
+/// Periodically describes the effective configuration of a channel and the state of its pipeline
+/// with a self-describing event.
+pub(crate) struct Snapshot {
+    period: Duration,
+    taken_at: Option<DateTime<Utc>>,
+    context: TelemetryContext,
+    sdk_version: String,
+    config: Vec<(&'static str, String)>,
+}
+
+impl Snapshot {
+    /// Creates a new snapshot of a given configuration. Returns `None` if snapshots are disabled.
+    pub fn from_config(config: &TelemetryConfig) -> Option<Self> {
+        let period = config.snapshot_interval()?;
+        let optional = |value: Option<Duration>| value.map_or_else(|| "none".into(), |value| format!("{:?}", value));
+
+        Some(Self {
+            period,
+            taken_at: None,
+            context: TelemetryContext::from_config(config),
+            sdk_version: config.sdk_version().into(),
+            config: vec![
+                ("endpoint", config.endpoint().into()),
+                ("interval", format!("{:?}", config.interval())),
+                ("intervalJitter", optional(config.interval_jitter())),
+                ("intervalAligned", config.is_interval_aligned().to_string()),
+                ("manualFlushOnly", config.is_manual_flush_only().to_string()),
+                ("timeToLive", optional(config.time_to_live())),
+                (
+                    "maxConcurrentTransmissions",
+                    config.max_concurrent_transmissions().to_string(),
+                ),
+                ("maxBatchSize", config.max_batch_size().to_string()),
+                (
+                    "maxBatchBytes",
+                    config
+                        .max_batch_bytes()
+                        .map_or_else(|| "none".into(), |max| max.to_string()),
+                ),
+                (
+                    "maxQueueSize",
+                    config
+                        .max_queue_size()
+                        .map_or_else(|| "none".into(), |max| max.to_string()),
+                ),
+                ("requestTimeout", optional(config.request_timeout())),
+                ("maxErrorBodyBytes", config.max_error_body_bytes().to_string()),
+                ("internalLogLevel", config.internal_log_level().to_string()),
+                ("disabledTypes", format!("{:?}", config.disabled_types())),
+                (
+                    "minSeverity",
+                    config
+                        .min_severity()
+                        .map_or_else(|| "none".into(), |severity| format!("{:?}", severity)),
+                ),
+                ("priorities", format!("{:?}", config.priorities().0)),
+                ("timestamping", format!("{:?}", config.timestamping())),
+                ("standardMetrics", config.are_standard_metrics_extracted().to_string()),
+                (
+                    "dailyCap",
+                    config.daily_cap().map_or_else(
+                        || "none".into(),
+                        |cap| format!("{} {:?}", cap.limit(), cap.unit()).to_lowercase(),
+                    ),
+                ),
+            ],
+        })
+    }
+
+    /// Returns a snapshot event if the period is over at a given time. The first snapshot is taken
+    /// on the first elapsed submission interval.
+    pub fn take(&mut self, now: DateTime<Utc>, stats: &PipelineStats) -> Option<Envelope> {
+        let is_due = self
+            .taken_at
+            .is_none_or(|taken_at| (now - taken_at).to_std().is_ok_and(|elapsed| elapsed >= self.period));
+        if !is_due {
+            return None;
+        }
+        self.taken_at = Some(now);
+
+        let mut event = EventTelemetry::new(SNAPSHOT_EVENT_NAME);
+
+        let properties = event.properties_mut();
+        properties.insert("sdkVersion".into(), self.sdk_version.clone());
+        let features: Vec<_> = FEATURES.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect();
+        properties.insert("features".into(), features.join(","));
+        for (name, value) in &self.config {
+            properties.insert(format!("config.{}", name), value.clone());
+        }
+
+        let measurements = event.measurements_mut();
+        measurements.insert("ticks".into(), stats.ticks as f64);
+        measurements.insert("pending".into(), stats.pending as f64);
+        measurements.insert("sent".into(), stats.sent as f64);
+        measurements.insert("lost".into(), stats.lost as f64);
+        measurements.insert("expired".into(), stats.expired as f64);
+        measurements.insert("overflowed".into(), stats.overflowed as f64);
+
+        Some((self.context.clone(), event).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::contracts::{Base, Data};
+
+    #[test]
+    fn it_takes_snapshot_once_per_period() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .snapshot_interval(Duration::from_secs(3600))
+            .max_batch_size(512)
+            .build();
+        let mut snapshot = Snapshot::from_config(&config).unwrap();
+        let stats = PipelineStats {
+            ticks: 7,
+            sent: 42,
+            ..PipelineStats::default()
+        };
+        let started = Utc.ymd(2023, 5, 1).and_hms(12, 0, 0);
+
+        let envelope = snapshot.take(started, &stats).unwrap();
+        assert!(snapshot.take(started + chrono::Duration::minutes(59), &stats).is_none());
+        assert!(snapshot.take(started + chrono::Duration::minutes(60), &stats).is_some());
+
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                assert_eq!(data.name, SNAPSHOT_EVENT_NAME);
+                let properties = data.properties.unwrap();
+                assert_eq!(properties["sdkVersion"], config.sdk_version());
+                assert_eq!(properties["config.maxBatchSize"], "512");
+                assert_eq!(properties["config.maxQueueSize"], "none");
+                let measurements = data.measurements.unwrap();
+                assert_eq!(measurements["ticks"], 7.0);
+                assert_eq!(measurements["sent"], 42.0);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_does_not_take_snapshots_by_default() {
+        assert!(Snapshot::from_config(&TelemetryConfig::new("instrumentation".into())).is_none());
+    }
+}
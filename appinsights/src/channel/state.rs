@@ -1,40 +1,61 @@
-use std::{mem, sync::Arc, time::Duration};
+use std::{mem, panic::AssertUnwindSafe, sync::Arc, time::Duration};
 
+use chrono::{DateTime, Utc};
 use crossbeam_queue::SegQueue;
 use futures_channel::mpsc::UnboundedReceiver;
-use futures_util::{Future, Stream, StreamExt};
+use futures_util::{future, FutureExt, StreamExt};
 use log::{debug, error, trace};
 use sm::{sm, Event};
+use tokio::task::JoinHandle;
 
 use crate::{
     channel::command::Command,
+    channel::interval::Interval,
+    channel::pending::{Backlog, HeldItems},
+    channel::report::{ShutdownReport, TransmissionStatus},
     channel::retry::Retry,
+    channel::snapshot::{PipelineStats, Snapshot},
     channel::state::worker::{Variant::*, *},
+    clock::{self, SharedClock, Timestamping},
     contracts::Envelope,
-    timeout,
-    transmitter::{Response, Transmitter},
+    dead_letter::{DeadLetterReason, SharedDeadLetterSink},
+    instrumentation,
+    internal_logger::{panic_message, InternalEvent, InternalLogger},
+    queue::SharedDropPolicy,
+    scheduler::{self, SharedScheduler},
+    sequence::{self, Sequencer},
+    telemetry::Priority,
+    throttle::SharedThrottleStore,
+    time,
+    transmitter::{is_expired, BatchItem, Response, Transmitter},
+    worker_task::{ScheduledTask, WorkerTick},
+    Error,
 };
 
+// A worker is idle while it collects items into the next batch, sends a batch, waits to send a
+// batch the server did not accept again, or waits for the server to accept submissions again
+// after it throttled them. A batch awaiting retry is kept apart from items queued in the meantime,
+// so the same item is never sent twice, even when a flush is requested while a retry is pending.
 sm! {
     worker {
-        InitialStates { Receiving }
+        InitialStates { Idle }
 
         TimeoutExpired {
-            Receiving => Sending,
-            Waiting => Sending
+            Idle => Sending
         }
 
         FlushRequested {
-            Receiving => Sending
+            Idle => Sending
         }
 
         CloseRequested {
-            Receiving => Sending,
-            Waiting => Stopped
+            Idle => Sending,
+            Retrying => Stopped,
+            Throttled => Stopped
         }
 
         ItemsSentAndContinue {
-            Sending => Receiving
+            Sending => Idle
         }
 
         ItemsSentAndStop {
@@ -42,123 +63,384 @@ sm! {
         }
 
         RetryRequested {
-            Sending => Waiting
+            Sending => Retrying
+        }
+
+        ResolutionRetryRequested {
+            Sending => Retrying
+        }
+
+        ThrottleRequested {
+            Sending => Throttled
+        }
+
+        RetryTimeoutExpired {
+            Retrying => Sending,
+            Throttled => Sending
         }
 
         RetryExhausted {
-            Waiting => Receiving
+            Retrying => Idle,
+            Throttled => Idle
         }
 
         TerminateRequested {
-            Receiving => Stopped,
+            Idle => Stopped,
             Sending => Stopped,
-            Waiting => Stopped
+            Retrying => Stopped,
+            Throttled => Stopped
         }
     }
 }
 
+/// Only every n-th elapsed submission interval is reported to diagnostics.
+const INTERVAL_SAMPLING_RATE: u64 = 30;
+
 pub struct Worker {
-    transmitter: Transmitter,
-    items: Arc<SegQueue<Envelope>>,
+    transmitter: Arc<Transmitter>,
+    items: Arc<SegQueue<(Priority, Envelope)>>,
     command_receiver: UnboundedReceiver<Command>,
-    interval: Duration,
+    interval: Interval,
+    time_to_live: Option<Duration>,
+    expired: usize,
+    max_transmissions: usize,
+    max_batch_size: usize,
+    backlog: Backlog,
+    drain_pace: Duration,
+    draining: bool,
+    max_queue_size: Option<usize>,
+    drop_policy: SharedDropPolicy,
+    overflowed: usize,
+    throttled_until: Option<DateTime<Utc>>,
+    flush_requested: bool,
+    transmissions: Vec<(usize, JoinHandle<Delivery>)>,
+    delivery: Delivery,
+    final_flush: Option<Delivery>,
+    ticks: u64,
+    clock: Option<SharedClock>,
+    timestamping: Timestamping,
+    scheduler: Option<SharedScheduler>,
+    sequencer: Option<Arc<Sequencer>>,
+    throttle_store: Option<SharedThrottleStore>,
+    dead_letter_sink: Option<SharedDeadLetterSink>,
+    tasks: Vec<ScheduledTask>,
+    snapshot: Option<Snapshot>,
+    held: Arc<HeldItems>,
+    logger: InternalLogger,
 }
 
 impl Worker {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         transmitter: Transmitter,
-        items: Arc<SegQueue<Envelope>>,
+        items: Arc<SegQueue<(Priority, Envelope)>>,
         command_receiver: UnboundedReceiver<Command>,
-        interval: Duration,
+        interval: Interval,
+        time_to_live: Option<Duration>,
+        max_transmissions: usize,
+        max_batch_size: usize,
+        drain_pace: Duration,
+        max_queue_size: Option<usize>,
+        drop_policy: SharedDropPolicy,
+        clock: Option<SharedClock>,
+        timestamping: Timestamping,
+        scheduler: Option<SharedScheduler>,
+        sequencer: Option<Arc<Sequencer>>,
+        throttle_store: Option<SharedThrottleStore>,
+        dead_letter_sink: Option<SharedDeadLetterSink>,
+        tasks: Vec<ScheduledTask>,
+        snapshot: Option<Snapshot>,
+        held: Arc<HeldItems>,
+        logger: InternalLogger,
     ) -> Self {
         Self {
-            transmitter,
+            transmitter: Arc::new(transmitter),
             items,
             command_receiver,
             interval,
+            time_to_live,
+            expired: 0,
+            max_transmissions,
+            max_batch_size,
+            backlog: Backlog::default(),
+            drain_pace,
+            draining: false,
+            max_queue_size,
+            drop_policy,
+            overflowed: 0,
+            // a throttle window the server set before a restart may not be over yet
+            throttled_until: throttle_store.as_ref().and_then(|store| store.load(&logger)),
+            flush_requested: false,
+            transmissions: Vec::new(),
+            delivery: Delivery::default(),
+            final_flush: None,
+            ticks: 0,
+            clock,
+            timestamping,
+            scheduler,
+            sequencer,
+            throttle_store,
+            dead_letter_sink,
+            tasks,
+            snapshot,
+            held,
+            logger,
         }
     }
 
-    pub async fn run(mut self) {
-        let mut state = Machine::new(Receiving).as_enum();
+    pub async fn run(mut self) -> ShutdownReport {
+        let mut state = Machine::new(Idle).as_enum();
 
-        let mut items: Vec<Envelope> = Default::default();
+        // a batch the server is asked to accept again, kept apart from items queued in the meantime
+        let mut batch: Vec<(Priority, Envelope)> = Default::default();
         let mut retry = Retry::default();
 
+        self.logger.log(InternalEvent::WorkerStarted);
+
         loop {
+            self.sync_held(&mut batch);
             state = match state {
-                InitialReceiving(m) => self.handle_receiving(m, &mut items).await,
-                ReceivingByItemsSentAndContinue(m) => self.handle_receiving(m, &mut items).await,
-                ReceivingByRetryExhausted(m) => self.handle_receiving(m, &mut items).await,
-                SendingByTimeoutExpired(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
-                SendingByFlushRequested(m) => self.handle_sending_with_retry(m, &mut items, &mut retry).await,
-                SendingByCloseRequested(m) => self.handle_sending_once_and_terminate(m, &mut items, &mut retry).await,
-                WaitingByRetryRequested(m) => self.handle_waiting(m, &mut retry).await,
+                InitialIdle(m) => self.handle_idle(m, &mut batch).await,
+                IdleByItemsSentAndContinue(m) => self.handle_idle(m, &mut batch).await,
+                IdleByRetryExhausted(m) => self.handle_idle(m, &mut batch).await,
+                SendingByTimeoutExpired(m) => self.handle_sending_with_retry(m, &mut batch, &mut retry).await,
+                SendingByFlushRequested(m) => self.handle_sending_with_retry(m, &mut batch, &mut retry).await,
+                SendingByRetryTimeoutExpired(m) => self.handle_resending(m, &mut batch).await,
+                SendingByCloseRequested(m) => self.handle_sending_once_and_terminate(m, &mut batch, &mut retry).await,
+                RetryingByRetryRequested(m) => {
+                    let timeout = retry.next().map(|timeout| self.throttle(timeout));
+                    self.handle_retrying(m, timeout).await
+                }
+                RetryingByResolutionRetryRequested(m) => self.handle_retrying(m, retry.next_resolution()).await,
+                ThrottledByThrottleRequested(m) => {
+                    let timeout = retry.next().map(|timeout| self.throttle(timeout));
+                    self.handle_throttled(m, timeout).await
+                }
                 StoppedByItemsSentAndStop(_) => break,
                 StoppedByCloseRequested(_) => break,
                 StoppedByTerminateRequested(_) => break,
             }
         }
+
+        // discard batches that are still being sent concurrently
+        let mut abandoned = batch.len() + self.pending();
+        for (count, transmission) in mem::take(&mut self.transmissions) {
+            transmission.abort();
+            match transmission.await {
+                Ok(delivery) => self.delivery.merge(delivery),
+                Err(_) => {
+                    abandoned += count;
+                    self.delivery.status = Some(TransmissionStatus::Failed(Arc::new(Error::Shutdown)));
+                }
+            }
+        }
+
+        // letters deposited on the way are written before the channel reports it stopped
+        if let Some(sink) = &self.dead_letter_sink {
+            sink.drained().await;
+        }
+
+        let flush = self.final_flush.take().unwrap_or_default();
+        let report = ShutdownReport::new(
+            flush.sent,
+            flush.lost + abandoned,
+            flush.status.or(self.delivery.status),
+        )
+        .with_expired(self.expired)
+        .with_acked_sequence(self.sequencer.as_ref().and_then(|sequencer| sequencer.acked()));
+
+        self.logger.log(InternalEvent::WorkerStopped {
+            flushed: report.flushed(),
+            abandoned: report.abandoned(),
+        });
+
+        report
     }
 
-    async fn handle_receiving<E: Event>(&mut self, m: Machine<Receiving, E>, items: &mut Vec<Envelope>) -> Variant {
+    async fn handle_idle<E: Event>(&mut self, m: Machine<Idle, E>, items: &mut Vec<(Priority, Envelope)>) -> Variant {
         debug!("Receiving messages triggered by {:?}", m.trigger());
 
-        let timeout = timeout::sleep(self.interval);
-        items.clear();
-
-        tokio::select! {
-            command = self.command_receiver.next() => {
-                match command {
-                    Some(command) => {
-                        trace!("Command received: {}", command);
-                        match command {
-                            Command::Flush => m.transition(FlushRequested).as_enum(),
-                            Command::Terminate => m.transition(TerminateRequested).as_enum(),
-                            Command::Close => m.transition(CloseRequested).as_enum(),
+        // the next batch of a queue being drained is sent after a short pause instead of the interval
+        let timeout = if self.draining {
+            Some(self.throttle(self.drain_pace))
+        } else {
+            // a batch is not sent before the server accepts submissions again if it throttled them
+            self.interval.next().map(|timeout| self.throttle(timeout))
+        };
+        // items are only sent when flushed if there is no interval
+        let scheduler = self.scheduler.clone();
+        let timeout = async move {
+            match timeout {
+                Some(timeout) => scheduler::sleep(scheduler, timeout).await,
+                None => future::pending().await,
+            }
+        };
+        tokio::pin!(timeout);
+
+        // items left from the previous attempt could not be sent despite all retries
+        if !items.is_empty() {
+            self.logger.log(InternalEvent::RetriesExhausted { count: items.len() });
+            self.delivery.lost += items.len();
+            self.dead_letter(DeadLetterReason::RetriesExhausted, mem::take(items));
+        }
+
+        // a flush requested while a batch was awaiting retry is handled once the batch is settled
+        if mem::take(&mut self.flush_requested) && !self.draining {
+            return m.transition(FlushRequested).as_enum();
+        }
+
+        loop {
+            tokio::select! {
+                command = self.command_receiver.next() => {
+                    match command {
+                        Some(command) => {
+                            trace!("Command received: {}", command);
+                            self.logger.log(InternalEvent::CommandReceived { command: command.to_string() });
+                            match command {
+                                // a queue being drained is flushed already, so keep the pace
+                                Command::Flush if self.draining => debug!("Queue is being drained already"),
+                                Command::Flush => return m.transition(FlushRequested).as_enum(),
+                                Command::Terminate => return m.transition(TerminateRequested).as_enum(),
+                                Command::Close => return m.transition(CloseRequested).as_enum(),
+                            }
+                        },
+                        None => {
+                            error!("commands channel closed");
+                            return m.transition(TerminateRequested).as_enum();
+                        },
+                    }
+                },
+                _ = &mut timeout => {
+                    if self.draining {
+                        debug!("Drain pause expired");
+                    } else {
+                        debug!("Timeout expired");
+                        self.ticks += 1;
+                        if (self.ticks - 1).is_multiple_of(INTERVAL_SAMPLING_RATE) {
+                            self.logger.log(InternalEvent::IntervalElapsed {
+                                ticks: self.ticks,
+                                pending: items.len() + self.pending(),
+                            });
                         }
-                    },
-                    None => {
-                        error!("commands channel closed");
-                        m.transition(TerminateRequested).as_enum()
-                    },
-                }
-            },
-            _ = timeout => {
-                debug!("Timeout expired");
-                m.transition(TimeoutExpired).as_enum()
-            },
+                        self.run_tasks(&WorkerTick::new(self.ticks, items.len() + self.pending()));
+                        self.take_snapshot(items.len());
+                    }
+                    return m.transition(TimeoutExpired).as_enum();
+                },
+            }
         }
     }
 
     async fn handle_sending_with_retry<E: Event>(
         &mut self,
         m: Machine<Sending, E>,
-        items: &mut Vec<Envelope>,
+        items: &mut Vec<(Priority, Envelope)>,
         retry: &mut Retry,
     ) -> Variant {
         *retry = Retry::exponential();
+        if self.max_transmissions > 1 {
+            self.handle_sending_concurrently(m, items).await
+        } else {
+            self.collect_pending(items);
+            self.handle_sending(m, items).await
+        }
+    }
+
+    /// Sends a batch awaiting retry on its own. Items queued in the meantime are left for the
+    /// next batch, and attempts left for the batch are not reset.
+    async fn handle_resending<E: Event>(
+        &mut self,
+        m: Machine<Sending, E>,
+        items: &mut Vec<(Priority, Envelope)>,
+    ) -> Variant {
+        self.drop_expired(items);
         self.handle_sending(m, items).await
     }
 
     async fn handle_sending_once_and_terminate<E: Event>(
         &mut self,
         m: Machine<Sending, E>,
-        items: &mut Vec<Envelope>,
+        items: &mut Vec<(Priority, Envelope)>,
         retry: &mut Retry,
     ) -> Variant {
         *retry = Retry::once();
-        let cloned = m.clone(); // clone here
-        self.handle_sending(m, items).await;
-        cloned.transition(TerminateRequested).as_enum()
+
+        // account items submitted from now on separately
+        let previous = mem::take(&mut self.delivery);
+
+        // wait for batches being sent concurrently before the last attempt
+        for (_, transmission) in mem::take(&mut self.transmissions) {
+            self.complete(transmission).await;
+        }
+
+        // send all pending items in batches until one of them fails
+        loop {
+            self.collect_pending(items);
+            if items.is_empty() || self.send_batch(items).await != Outcome::Sent {
+                break;
+            }
+        }
+
+        // items the server did not accept in the last attempt are abandoned
+        if let Some(sink) = self.dead_letter_sink.as_ref().filter(|_| !items.is_empty()) {
+            let items = items.iter().map(|(_, item)| item.clone()).collect();
+            sink.deposit(DeadLetterReason::RetriesExhausted, items, &self.logger);
+        }
+        self.final_flush = Some(mem::replace(&mut self.delivery, previous));
+
+        m.transition(TerminateRequested).as_enum()
     }
 
-    async fn handle_sending<E: Event>(&mut self, m: Machine<Sending, E>, items: &mut Vec<Envelope>) -> Variant {
-        // read pending items from a channel
-        while let Some(item) = self.items.pop() {
-            items.push(item);
+    async fn handle_sending_concurrently<E: Event>(
+        &mut self,
+        m: Machine<Sending, E>,
+        items: &mut Vec<(Priority, Envelope)>,
+    ) -> Variant {
+        self.collect_pending(items);
+
+        if items.is_empty() {
+            debug!("Nothing to send. Continue to wait");
+        } else {
+            // wait for the oldest batch to complete when too many batches are being sent already
+            let (finished, running) = mem::take(&mut self.transmissions)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(_, transmission)| transmission.is_finished());
+            self.transmissions = running;
+            for (_, transmission) in finished {
+                self.complete(transmission).await;
+            }
+            if self.transmissions.len() >= self.max_transmissions {
+                let (_, transmission) = self.transmissions.remove(0);
+                self.complete(transmission).await;
+            }
+
+            debug!(
+                "Sending {} telemetry items concurrently triggered by {:?}",
+                items.len(),
+                m.trigger().unwrap()
+            );
+            let count = items.len();
+            let transmission = transmit(
+                self.transmitter.clone(),
+                mem::take(items).into_iter().map(BatchItem::into_envelope).collect(),
+                self.sequencer.clone(),
+                self.clock.clone(),
+                self.scheduler.clone(),
+                self.throttle_store.clone(),
+                self.dead_letter_sink.clone(),
+                self.logger.clone(),
+            );
+            self.transmissions.push((count, tokio::spawn(transmission)));
         }
 
+        m.transition(ItemsSentAndContinue).as_enum()
+    }
+
+    async fn handle_sending<E: Event>(
+        &mut self,
+        m: Machine<Sending, E>,
+        items: &mut Vec<(Priority, Envelope)>,
+    ) -> Variant {
         debug!(
             "Sending {} telemetry items triggered by {:?}",
             items.len(),
@@ -170,78 +452,628 @@ impl Worker {
             debug!("Nothing to send. Continue to wait");
             m.transition(ItemsSentAndContinue).as_enum()
         } else {
-            // attempt to send items
-            match self.transmitter.send(mem::take(items)).await {
-                Ok(Response::Success) => m.transition(ItemsSentAndContinue).as_enum(),
-                Ok(Response::Retry(retry_items)) => {
-                    *items = retry_items;
-                    m.transition(RetryRequested).as_enum()
+            match self.send_batch(items).await {
+                Outcome::Sent => m.transition(ItemsSentAndContinue).as_enum(),
+                Outcome::Retry => m.transition(RetryRequested).as_enum(),
+                Outcome::Throttled => m.transition(ThrottleRequested).as_enum(),
+                Outcome::ResolutionRetry => m.transition(ResolutionRetryRequested).as_enum(),
+            }
+        }
+    }
+
+    /// Sends a batch of telemetry items and keeps items the server asked to send again.
+    async fn send_batch(&mut self, items: &mut Vec<(Priority, Envelope)>) -> Outcome {
+        let count = items.len();
+        let batch = assign_sequence(self.sequencer.as_deref(), items);
+        let (response, failed, rest) = send_catching_panic(&self.transmitter, mem::take(items), &self.logger).await;
+        let count = count - rest.len();
+        self.put_back(rest);
+        self.delivery.record(count, &response);
+        ack_sequence(self.sequencer.as_deref(), batch, &response).await;
+        match response {
+            Ok(Response::Success) | Ok(Response::NoRetry) => Outcome::Sent,
+            Ok(Response::Retry(retry_items)) => {
+                *items = retry_items;
+                Outcome::Retry
+            }
+            Ok(Response::Throttled(retry_after, retry_items)) => {
+                *items = retry_items;
+                self.throttled_until = Some(retry_after);
+                if let Some(store) = &self.throttle_store {
+                    store.save(retry_after, &self.logger).await;
                 }
-                Ok(Response::Throttled(_retry_after, retry_items)) => {
-                    *items = retry_items;
-                    // TODO implement throttling instead
-                    m.transition(RetryRequested).as_enum()
+                Outcome::Throttled
+            }
+            Ok(Response::ResolutionFailed(retry_items)) => {
+                *items = retry_items;
+                Outcome::ResolutionRetry
+            }
+            Err(err) => {
+                self.logger.log(InternalEvent::TransmissionFailed {
+                    count,
+                    error: err.to_string(),
+                });
+                self.dead_letter(
+                    DeadLetterReason::Failed {
+                        message: err.to_string(),
+                    },
+                    failed,
+                );
+                Outcome::Sent
+            }
+        }
+    }
+
+    /// Extends a given timeout until the server accepts submissions again if it throttled them.
+    fn throttle(&mut self, timeout: Duration) -> Duration {
+        let now = self.clock.as_ref().map_or_else(time::now, SharedClock::now);
+        match self.throttled_until {
+            Some(until) => match (until - now).to_std() {
+                Ok(remaining) => timeout.max(remaining),
+                Err(_) => {
+                    self.throttled_until = None;
+                    timeout
                 }
-                Ok(Response::NoRetry) => m.transition(ItemsSentAndContinue).as_enum(),
-                Err(err) => {
-                    debug!("Error occurred during sending telemetry items: {}", err);
-                    m.transition(RetryRequested).as_enum()
+            },
+            None => timeout,
+        }
+    }
+
+    /// Waits for a batch being sent concurrently and accounts its delivery.
+    async fn complete(&mut self, transmission: JoinHandle<Delivery>) {
+        if let Ok(delivery) = transmission.await {
+            self.delivery.merge(delivery);
+        }
+    }
+
+    fn collect_pending(&mut self, items: &mut Vec<(Priority, Envelope)>) {
+        // drop pending items a queue cannot hold before they are collected into a batch
+        self.drop_overflowed();
+
+        // send items of a higher priority first when they do not fit into a single batch
+        if self.pending() > self.max_batch_size.saturating_sub(items.len()) {
+            self.prioritize();
+        }
+
+        match self
+            .clock
+            .as_ref()
+            .filter(|_| self.timestamping == Timestamping::OnTransmission)
+        {
+            Some(shared) => match shared.offset() {
+                // read pending items from a channel and stamp them with the time of a custom clock
+                Some(offset) => {
+                    while let Some(mut item) = self.pop_pending(items) {
+                        clock::shift(item.envelope_mut(), offset);
+                        items.push(item);
+                    }
+                }
+                None => debug!("Clock does not know the time yet. Keep pending items in the queue"),
+            },
+            None => {
+                // read pending items from a channel
+                while let Some(item) = self.pop_pending(items) {
+                    items.push(item);
                 }
             }
         }
+
+        // keep draining a queue in paced batches while items do not fit into a single batch
+        self.draining = items.len() >= self.max_batch_size && self.pending() > 0;
+
+        // drop items that are too old to be useful anymore
+        self.drop_expired(items);
+
+        // send items of a batch in the order they were accepted unless batches are numbered
+        if self.sequencer.is_none() {
+            sequence::order_by_session(items);
+        }
     }
 
-    async fn handle_waiting<E: Event>(&mut self, m: Machine<Waiting, E>, retry: &mut Retry) -> Variant {
-        if let Some(timeout) = retry.next() {
-            debug!(
-                "Waiting for retry timeout {:?} or stop command triggered by {:?}",
-                timeout,
-                m.state()
-            );
-            // sleep until next sending attempt
-            let timeout = timeout::sleep(timeout);
+    /// Reads the next pending item from a channel unless a batch is full already.
+    fn pop_pending(&mut self, items: &[(Priority, Envelope)]) -> Option<(Priority, Envelope)> {
+        if items.len() >= self.max_batch_size {
+            return None;
+        }
+
+        self.backlog.pop().or_else(|| self.items.pop())
+    }
+
+    /// Keeps items that did not fit into a batch of a limited size aside to start the next batch
+    /// with, and drains a queue in paced batches until they are sent. Items are put back with
+    /// priorities they were queued with.
+    fn put_back(&mut self, rest: Vec<(Priority, Envelope)>) {
+        if !rest.is_empty() {
+            self.backlog.put_back(rest);
+            self.draining = true;
+        }
+    }
 
-            // wait for either retry timeout expired or stop command received
+    /// Discards items the worker holds if an application cleared pending items, and publishes the
+    /// number of items it holds otherwise.
+    fn sync_held(&mut self, items: &mut Vec<(Priority, Envelope)>) {
+        if self.held.take_cleared() {
+            debug!("Pending items cleared");
+            items.clear();
+            self.backlog.clear();
+        }
+        self.held.update(items.len() + self.backlog.len());
+    }
+
+    /// Returns the number of items waiting to be collected into a batch.
+    fn pending(&self) -> usize {
+        self.items.len() + self.backlog.len()
+    }
+
+    /// Drops pending items selected by a drop policy when a queue holds more items than allowed.
+    /// The rest of pending items is kept aside in the order they were queued to be collected first.
+    fn drop_overflowed(&mut self) {
+        let max_queue_size = match self.max_queue_size {
+            Some(max) if self.pending() > max => max,
+            _ => return,
+        };
+
+        self.prioritize();
+        let pending = self.backlog.take_all();
+
+        let now = self.clock.as_ref().map_or_else(time::now, SharedClock::now);
+        let (kept, dropped) = self.drop_policy.apply(pending, max_queue_size, now, &self.logger);
+        self.backlog.put_back(kept);
+
+        if !dropped.is_empty() {
+            self.overflowed += dropped.len();
+            self.logger.log(InternalEvent::QueueOverflowed {
+                count: dropped.len(),
+                total: self.overflowed,
+            });
+            self.dead_letter(DeadLetterReason::QueueFull, dropped);
+        }
+    }
+
+    /// Moves all pending items aside into queues of their priorities, so they are collected from
+    /// the highest priority to the lowest. Items of the same priority keep the order they were
+    /// queued in.
+    fn prioritize(&mut self) {
+        while let Some((priority, item)) = self.items.pop() {
+            self.backlog.push(priority, item);
+        }
+    }
+
+    /// Runs custom tasks scheduled for an elapsed interval. A panicking task is reported and does
+    /// not stop the worker.
+    fn run_tasks(&self, tick: &WorkerTick) {
+        for task in &self.tasks {
+            if let Err(panic) = std::panic::catch_unwind(AssertUnwindSafe(|| task.run(tick))) {
+                self.logger.log(InternalEvent::WorkerTaskPanicked {
+                    message: panic_message(panic.as_ref()),
+                });
+            }
+        }
+    }
+
+    /// Queues an event that describes the configuration and the state of the pipeline if it is due.
+    fn take_snapshot(&mut self, retried: usize) {
+        let stats = PipelineStats {
+            ticks: self.ticks,
+            pending: retried + self.pending(),
+            sent: self.delivery.sent,
+            lost: self.delivery.lost,
+            expired: self.expired,
+            overflowed: self.overflowed,
+        };
+        let now = self.clock.as_ref().map_or_else(time::now, SharedClock::now);
+        if let Some(envelope) = self.snapshot.as_mut().and_then(|snapshot| snapshot.take(now, &stats)) {
+            self.items.push((Priority::default(), envelope));
+        }
+    }
+
+    fn drop_expired(&mut self, items: &mut Vec<(Priority, Envelope)>) {
+        if let Some(time_to_live) = self.time_to_live {
+            let now = self.clock.as_ref().map_or_else(time::now, SharedClock::now);
+            let (expired, alive) = mem::take(items)
+                .into_iter()
+                .partition::<Vec<_>, _>(|item| is_expired(item.envelope(), time_to_live, now));
+            *items = alive;
+
+            if !expired.is_empty() {
+                self.expired += expired.len();
+                self.logger.log(InternalEvent::ItemsExpired {
+                    count: expired.len(),
+                    total: self.expired,
+                });
+                self.dead_letter(DeadLetterReason::Expired, expired);
+            }
+        }
+    }
+
+    /// Deposits telemetry items that could not be delivered to a dead letter sink if any.
+    fn dead_letter<T: BatchItem>(&self, reason: DeadLetterReason, items: Vec<T>) {
+        if let Some(sink) = &self.dead_letter_sink {
+            let items = items.into_iter().map(BatchItem::into_envelope).collect();
+            sink.deposit(reason, items, &self.logger);
+        }
+    }
+
+    async fn handle_retrying<E: Event>(&mut self, m: Machine<Retrying, E>, timeout: Option<Duration>) -> Variant {
+        debug!("Retrying triggered by {:?}", m.trigger());
+        match self.wait_for_retry(timeout).await {
+            Wakeup::TimeoutExpired => m.transition(RetryTimeoutExpired).as_enum(),
+            Wakeup::RetryExhausted => m.transition(RetryExhausted).as_enum(),
+            Wakeup::CloseRequested => m.transition(CloseRequested).as_enum(),
+            Wakeup::TerminateRequested => m.transition(TerminateRequested).as_enum(),
+        }
+    }
+
+    async fn handle_throttled<E: Event>(&mut self, m: Machine<Throttled, E>, timeout: Option<Duration>) -> Variant {
+        debug!("Throttled triggered by {:?}", m.trigger());
+        match self.wait_for_retry(timeout).await {
+            Wakeup::TimeoutExpired => m.transition(RetryTimeoutExpired).as_enum(),
+            Wakeup::RetryExhausted => m.transition(RetryExhausted).as_enum(),
+            Wakeup::CloseRequested => m.transition(CloseRequested).as_enum(),
+            Wakeup::TerminateRequested => m.transition(TerminateRequested).as_enum(),
+        }
+    }
+
+    /// Waits until a batch awaiting retry is to be sent again or a stop command is received. A
+    /// flush requested in the meantime is deferred until the batch is settled.
+    async fn wait_for_retry(&mut self, timeout: Option<Duration>) -> Wakeup {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => {
+                debug!("All retries exhausted");
+                return Wakeup::RetryExhausted;
+            }
+        };
+        debug!("Waiting for retry timeout {:?} or stop command", timeout);
+
+        // sleep until next sending attempt
+        let timeout = scheduler::sleep(self.scheduler.clone(), timeout);
+        tokio::pin!(timeout);
+
+        // wait for either retry timeout expired or stop command received
+        loop {
             tokio::select! {
-                command = skip_flush(&mut self.command_receiver) => {
+                command = self.command_receiver.next() => {
+                    if let Some(command) = &command {
+                        self.logger.log(InternalEvent::CommandReceived { command: command.to_string() });
+                    }
                     match command {
-                        Some(Command::Terminate) => m.transition(TerminateRequested).as_enum(),
-                        Some(Command::Close) => m.transition(CloseRequested).as_enum(),
-                        Some(Command::Flush) => panic!("whoops Flush is not supported here"),
+                        Some(Command::Flush) => {
+                            debug!("Flush deferred until the batch awaiting retry is settled");
+                            self.flush_requested = true;
+                        }
+                        Some(Command::Terminate) => return Wakeup::TerminateRequested,
+                        Some(Command::Close) => return Wakeup::CloseRequested,
                         None => {
                             error!("commands channel closed");
-                            m.transition(TerminateRequested).as_enum()
+                            return Wakeup::TerminateRequested;
                         }
                     }
                 },
-                _ = timeout => {
+                _ = &mut timeout => {
                     debug!("Retry timeout expired");
-                    m.transition(TimeoutExpired).as_enum()
+                    return Wakeup::TimeoutExpired;
                 },
             }
-        } else {
-            debug!("All retries exhausted by {:?}", m.state());
-            m.transition(RetryExhausted).as_enum()
         }
     }
 }
 
-fn skip_flush<St>(stream: &mut St) -> SkipFlush<'_, St> {
-    SkipFlush { stream }
+/// A reason a worker stops waiting for a batch awaiting retry.
+#[derive(Debug, PartialEq)]
+enum Wakeup {
+    /// The batch is to be sent again.
+    TimeoutExpired,
+
+    /// No attempts are left for the batch.
+    RetryExhausted,
+
+    /// A channel is to be closed.
+    CloseRequested,
+
+    /// A channel is to be terminated.
+    TerminateRequested,
 }
 
-struct SkipFlush<'a, St: ?Sized> {
-    stream: &'a mut St,
+/// Sends a batch of telemetry items and retries it on its own schedule independently of other
+/// batches being sent at the same time.
+#[allow(clippy::too_many_arguments)]
+async fn transmit(
+    transmitter: Arc<Transmitter>,
+    mut items: Vec<Envelope>,
+    sequencer: Option<Arc<Sequencer>>,
+    clock: Option<SharedClock>,
+    scheduler: Option<SharedScheduler>,
+    throttle_store: Option<SharedThrottleStore>,
+    dead_letter_sink: Option<SharedDeadLetterSink>,
+    logger: InternalLogger,
+) -> Delivery {
+    let mut retry = Retry::exponential();
+    let mut delivery = Delivery::default();
+
+    loop {
+        let count = items.len();
+        let batch = assign_sequence(sequencer.as_deref(), &mut items);
+
+        // the response is not Send, so it must be dropped before waiting for a retry
+        let timeout = {
+            let (response, failed, mut rest) = send_catching_panic(&transmitter, mem::take(&mut items), &logger).await;
+            let count = count - rest.len();
+            delivery.record(count, &response);
+            ack_sequence(sequencer.as_deref(), batch, &response).await;
+
+            // items that did not fit into a request of a limited size are sent right away
+            match response {
+                Ok(Response::Success) | Ok(Response::NoRetry) if rest.is_empty() => return delivery,
+                Ok(Response::Success) | Ok(Response::NoRetry) => {
+                    items = rest;
+                    continue;
+                }
+                Ok(Response::Retry(retry_items)) => {
+                    items = retry_items;
+                    items.append(&mut rest);
+                    retry.next()
+                }
+                Ok(Response::Throttled(retry_after, retry_items)) => {
+                    items = retry_items;
+                    items.append(&mut rest);
+                    if let Some(store) = &throttle_store {
+                        store.save(retry_after, &logger).await;
+                    }
+                    let now = clock.as_ref().map_or_else(time::now, SharedClock::now);
+                    let remaining = (retry_after - now).to_std().unwrap_or_default();
+                    retry.next().map(|timeout| timeout.max(remaining))
+                }
+                Ok(Response::ResolutionFailed(retry_items)) => {
+                    items = retry_items;
+                    items.append(&mut rest);
+                    retry.next_resolution()
+                }
+                Err(err) => {
+                    logger.log(InternalEvent::TransmissionFailed {
+                        count,
+                        error: err.to_string(),
+                    });
+                    if let Some(sink) = &dead_letter_sink {
+                        sink.deposit(
+                            DeadLetterReason::Failed {
+                                message: err.to_string(),
+                            },
+                            failed,
+                            &logger,
+                        );
+                    }
+                    if rest.is_empty() {
+                        return delivery;
+                    }
+                    items = rest;
+                    continue;
+                }
+            }
+        };
+
+        match timeout {
+            Some(timeout) => scheduler::sleep(scheduler.clone(), timeout).await,
+            None => {
+                logger.log(InternalEvent::RetriesExhausted { count: items.len() });
+                delivery.lost += items.len();
+                if let Some(sink) = dead_letter_sink {
+                    sink.deposit(DeadLetterReason::RetriesExhausted, items, &logger);
+                }
+                return delivery;
+            }
+        }
+    }
+}
+
+/// Stamps telemetry items with sequence numbers if sequence acknowledgement is enabled and returns
+/// the highest batch sequence number among them.
+fn assign_sequence<T: BatchItem>(sequencer: Option<&Sequencer>, items: &mut [T]) -> Option<u64> {
+    sequencer.and_then(|sequencer| sequencer.assign(items))
 }
 
-impl<St: ?Sized + Stream<Item = Command> + Unpin> Future for SkipFlush<'_, St> {
-    type Output = Option<St::Item>;
+/// Acknowledges a batch of telemetry items if the server has accepted all of them.
+async fn ack_sequence<T>(
+    sequencer: Option<&Sequencer>,
+    batch: Option<u64>,
+    response: &Result<Response<T>, Arc<Error>>,
+) {
+    if let (Some(sequencer), Some(batch), Ok(Response::Success)) = (sequencer, batch, response) {
+        sequencer.ack(batch).await;
+    }
+}
+
+/// An outcome of an attempt to send a batch of telemetry items.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Items are not going to be sent again.
+    Sent,
+
+    /// Some of items are to be sent again later.
+    Retry,
+
+    /// Some of items are to be sent again when the server accepts submissions again.
+    Throttled,
+
+    /// Items are to be sent again when the endpoint host can be resolved.
+    ResolutionRetry,
+}
 
-    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
-        match self.stream.poll_next_unpin(cx) {
-            std::task::Poll::Ready(Some(Command::Flush)) => std::task::Poll::Pending,
-            std::task::Poll::Ready(command) => std::task::Poll::Ready(command),
-            std::task::Poll::Pending => std::task::Poll::Pending,
+/// Keeps track of telemetry items submitted to the server.
+#[derive(Debug, Default)]
+struct Delivery {
+    /// The number of items the server has received and which are not going to be sent again.
+    sent: usize,
+
+    /// The number of items dropped without being received by the server.
+    lost: usize,
+
+    /// The status of the most recent submission.
+    status: Option<TransmissionStatus>,
+}
+
+impl Delivery {
+    /// Accounts a submission of a given number of items.
+    fn record<T>(&mut self, count: usize, response: &Result<Response<T>, Arc<Error>>) {
+        let (status, sent) = TransmissionStatus::of(count, response);
+        self.sent += sent;
+        if response.is_err() {
+            self.lost += count;
+        }
+        self.status = Some(status);
+    }
+
+    /// Accounts submissions of another batch that completed afterwards.
+    fn merge(&mut self, other: Delivery) {
+        self.sent += other.sent;
+        self.lost += other.lost;
+        if other.status.is_some() {
+            self.status = other.status;
         }
     }
 }
+
+/// Sends a batch of telemetry items and returns a response along with items of the batch that
+/// failed with an error and items that did not fit into a request of a limited size. When
+/// serialization or transmission of the batch panics or fails with a retryable error, the batch is
+/// handed back for retry, so it isn't lost along with the unwound send path. Retries are limited as
+/// usual, so a batch that fails every time is eventually dropped. Items of a batch that failed with
+/// an error retries would not fix are handed back alongside the error, so they can be dead-lettered.
+async fn send_catching_panic<T: BatchItem>(
+    transmitter: &Transmitter,
+    mut items: Vec<T>,
+    logger: &InternalLogger,
+) -> (Result<Response<T>, Arc<Error>>, Vec<T>, Vec<T>) {
+    let count = items.len();
+    let mut rest = Vec::new();
+    let result = AssertUnwindSafe(instrumentation::in_batch_span(
+        count,
+        transmitter.send_from(&mut items, &mut rest),
+    ))
+    .catch_unwind()
+    .await;
+    let (response, failed) = match result {
+        Ok(Ok(response)) => (Ok(response), Vec::new()),
+        Ok(Err(err)) if err.is_retryable() => {
+            logger.log(InternalEvent::TransmissionFailed {
+                count,
+                error: err.to_string(),
+            });
+            (Ok(Response::Retry(items)), Vec::new())
+        }
+        Ok(Err(err)) => (Err(Arc::new(err)), items),
+        Err(panic) => {
+            // items are taken from the batch only once a response is known, so the batch is kept
+            logger.log(InternalEvent::TransmissionPanicked {
+                count,
+                message: panic_message(panic.as_ref()),
+            });
+            (Ok(Response::Retry(items)), Vec::new())
+        }
+    };
+    (response, failed, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+    use sm::{AsEnum, Initializer, Transition};
+
+    use super::worker::{Variant::*, *};
+
+    #[test]
+    fn it_sends_from_idle_when_timeout_expired_or_flush_or_close_requested() {
+        assert_matches!(Machine::new(Idle).as_enum(), InitialIdle(_));
+        assert_matches!(
+            Machine::new(Idle).transition(TimeoutExpired).as_enum(),
+            SendingByTimeoutExpired(_)
+        );
+        assert_matches!(
+            Machine::new(Idle).transition(FlushRequested).as_enum(),
+            SendingByFlushRequested(_)
+        );
+        assert_matches!(
+            Machine::new(Idle).transition(CloseRequested).as_enum(),
+            SendingByCloseRequested(_)
+        );
+        assert_matches!(
+            Machine::new(Idle).transition(TerminateRequested).as_enum(),
+            StoppedByTerminateRequested(_)
+        );
+    }
+
+    #[test]
+    fn it_leaves_sending_depending_on_outcome() {
+        let sending = || Machine::new(Idle).transition(TimeoutExpired);
+
+        assert_matches!(
+            sending().transition(ItemsSentAndContinue).as_enum(),
+            IdleByItemsSentAndContinue(_)
+        );
+        assert_matches!(
+            sending().transition(ItemsSentAndStop).as_enum(),
+            StoppedByItemsSentAndStop(_)
+        );
+        assert_matches!(
+            sending().transition(RetryRequested).as_enum(),
+            RetryingByRetryRequested(_)
+        );
+        assert_matches!(
+            sending().transition(ResolutionRetryRequested).as_enum(),
+            RetryingByResolutionRetryRequested(_)
+        );
+        assert_matches!(
+            sending().transition(ThrottleRequested).as_enum(),
+            ThrottledByThrottleRequested(_)
+        );
+        assert_matches!(
+            sending().transition(TerminateRequested).as_enum(),
+            StoppedByTerminateRequested(_)
+        );
+    }
+
+    #[test]
+    fn it_leaves_retrying_when_timeout_expired_or_retries_exhausted_or_stop_requested() {
+        let retrying = || Machine::new(Idle).transition(TimeoutExpired).transition(RetryRequested);
+
+        assert_matches!(
+            retrying().transition(RetryTimeoutExpired).as_enum(),
+            SendingByRetryTimeoutExpired(_)
+        );
+        assert_matches!(retrying().transition(RetryExhausted).as_enum(), IdleByRetryExhausted(_));
+        assert_matches!(
+            retrying().transition(CloseRequested).as_enum(),
+            StoppedByCloseRequested(_)
+        );
+        assert_matches!(
+            retrying().transition(TerminateRequested).as_enum(),
+            StoppedByTerminateRequested(_)
+        );
+    }
+
+    #[test]
+    fn it_leaves_throttled_when_timeout_expired_or_retries_exhausted_or_stop_requested() {
+        let throttled = || {
+            Machine::new(Idle)
+                .transition(TimeoutExpired)
+                .transition(ThrottleRequested)
+        };
+
+        assert_matches!(
+            throttled().transition(RetryTimeoutExpired).as_enum(),
+            SendingByRetryTimeoutExpired(_)
+        );
+        assert_matches!(
+            throttled().transition(RetryExhausted).as_enum(),
+            IdleByRetryExhausted(_)
+        );
+        assert_matches!(
+            throttled().transition(CloseRequested).as_enum(),
+            StoppedByCloseRequested(_)
+        );
+        assert_matches!(
+            throttled().transition(TerminateRequested).as_enum(),
+            StoppedByTerminateRequested(_)
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    channel::{InMemoryChannel, TelemetryChannel},
+    context::TelemetryContext,
+    processor::TelemetryProcessor,
+    TelemetryClient, TelemetryConfig,
+};
+
+/// A channel a telemetry client submits telemetry items with.
+enum ChannelKind {
+    InMemory,
+    Custom(Arc<dyn TelemetryChannel>),
+}
+
+/// Constructs a [`TelemetryClient`] with an explicitly chosen channel, a seeded telemetry context
+/// and additional processors.
+///
+/// Channels keep pending items in memory, so items that are not sent yet are lost when an
+/// application exits without [closing](TelemetryClient::close_channel) the channel. There is no
+/// channel that persists pending items on disk, but a [dead letter sink](crate::dead_letter) of
+/// the configuration keeps items that could not be delivered.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use appinsights::{processor::{Envelope, ProcessingContext}, TelemetryClient, TelemetryConfig};
+///
+/// let config = TelemetryConfig::new("<instrumentation key>".to_string());
+/// let client = TelemetryClient::builder(config)
+///     .in_memory()
+///     .processor(|envelope: &mut Envelope, _: &ProcessingContext| !envelope.name.ends_with("Metric"))
+///     .build();
+/// ```
+pub struct TelemetryClientBuilder {
+    config: TelemetryConfig,
+    channel: ChannelKind,
+    context: Option<TelemetryContext>,
+    processors: Vec<Arc<dyn TelemetryProcessor>>,
+}
+
+impl TelemetryClientBuilder {
+    /// Creates a new builder of a client configured with a given configuration.
+    pub(crate) fn new(config: TelemetryConfig) -> Self {
+        Self {
+            config,
+            channel: ChannelKind::InMemory,
+            context: None,
+            processors: Vec::default(),
+        }
+    }
+
+    /// Submits telemetry items with a channel that keeps pending items in memory. It is used
+    /// unless another channel is chosen.
+    pub fn in_memory(mut self) -> Self {
+        self.channel = ChannelKind::InMemory;
+        self
+    }
+
+    /// Submits telemetry items with a custom channel.
+    pub fn custom(mut self, channel: impl TelemetryChannel + 'static) -> Self {
+        self.channel = ChannelKind::Custom(Arc::new(channel));
+        self
+    }
+
+    /// Seeds a client with a telemetry context instead of the one created from the configuration.
+    pub fn context(mut self, context: TelemetryContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Adds a processor to apply to telemetry items after the processors of the configuration.
+    pub fn processor<P>(mut self, processor: P) -> Self
+    where
+        P: TelemetryProcessor + 'static,
+    {
+        self.processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Constructs a new instance of a [`TelemetryClient`]. A channel other than a custom one
+    /// starts submitting telemetry items right away.
+    pub fn build(self) -> TelemetryClient {
+        let channel: Arc<dyn TelemetryChannel> = match self.channel {
+            ChannelKind::InMemory => Arc::new(InMemoryChannel::new(&self.config)),
+            ChannelKind::Custom(channel) => channel,
+        };
+
+        let mut client = TelemetryClient::with_channel(&self.config, channel);
+        if let Some(context) = self.context {
+            client.context = Arc::new(RwLock::new(context));
+        }
+        for processor in self.processors {
+            client.processors.push(processor);
+        }
+        client
+    }
+}
@@ -1,4 +1,5 @@
 use std::{
+    net::SocketAddr,
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -6,7 +7,10 @@ use std::{
     time::Duration,
 };
 
-use chrono::{DateTime, Utc};
+use async_trait::async_trait;
+use chrono::{DateTime, SubsecRound, Utc};
+#[cfg(feature = "reqwest")]
+use hyper::client::connect::dns::Name;
 use hyper::{
     body::Buf,
     service::{make_service_fn, service_fn},
@@ -15,13 +19,25 @@ use hyper::{
 use lazy_static::lazy_static;
 use matches::assert_matches;
 use parking_lot::Mutex;
+#[cfg(feature = "reqwest")]
+use reqwest::dns::{Addrs, Resolve, Resolving};
 use serde_json::json;
 use tokio::sync::{
     mpsc::{self, Receiver},
     oneshot,
 };
 
-use crate::{timeout, TelemetryClient, TelemetryConfig};
+use crate::{
+    dead_letter::FileDeadLetterSink,
+    queue::QueueView,
+    scheduler::{ManualScheduler, Scheduler},
+    sequence::{FileSequenceStore, SequenceStore},
+    telemetry::{EventTelemetry, Priority, SeverityLevel, Telemetry, TelemetryKind},
+    throttle::{FileThrottleStore, ThrottleStore},
+    time, timeout,
+    worker_task::WorkerTick,
+    ShutdownReport, TelemetryClient, TelemetryConfig, TransmissionStatus,
+};
 
 lazy_static! {
     /// A global lock since most tests need to run in serial.
@@ -29,7 +45,8 @@ lazy_static! {
 }
 
 macro_rules! manual_timeout_test {
-    (async fn $name: ident() $body: block) => {
+    ($(#[$attr: meta])* async fn $name: ident() $body: block) => {
+        $(#[$attr])*
         #[test]
         fn $name() {
             let _guard = SERIAL_TEST_MUTEX.lock();
@@ -88,6 +105,34 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_only_when_flushed_manually() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .manual_flush_only(true)
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+
+        // verify no items is sent after interval expired
+        timeout::expire();
+        assert_matches!(
+            server.next_request_timeout().await,
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        // verify items are sent once flushed
+        client.flush_channel();
+        assert_matches!(server.next_request_timeout().await, Ok(_));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_sends_telemetry_items_in_several_batches() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -160,6 +205,156 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_drains_pending_telemetry_items_in_paced_batches_on_flush() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_batch_size(10)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        for i in 0..25 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // force client to send all items to the server
+        client.flush_channel();
+
+        // verify only the first batch is sent immediately even if flush is requested again
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(count_items(&requests[0], 0..25), 10);
+
+        client.flush_channel();
+        assert_matches!(server.next_request_timeout().await, Err(_));
+
+        // "wait" until the drain pause expired twice
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(count_items(&requests[0], 10..20), 10);
+
+        timeout::expire();
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(count_items(&requests[0], 20..25), 5);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_pending_telemetry_items_in_batches_when_close_channel_requested() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_batch_size(10)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        for i in 0..15 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        let report = client.close_channel().await;
+        assert_eq!(report.flushed(), 15);
+
+        // verify all items are sent in 2 batches
+        let requests = server.wait_for_requests(2).await;
+        assert_eq!(requests.len(), 2);
+        assert_eq!(count_items(&requests[0], 0..10), 10);
+        assert_eq!(count_items(&requests[1], 10..15), 5);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_cuts_batches_when_they_exceed_max_batch_bytes() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_batch_bytes(25_000)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // every item takes a bit more than 10 KB, so only 2 of them fit into a batch
+        let padding = "x".repeat(10_000);
+        for i in 0..5 {
+            client.track_event(format!("--event {}--{}", i, padding));
+        }
+
+        let report = client.close_channel().await;
+        assert_eq!(report.flushed(), 5);
+
+        // verify all items are sent in 3 batches within the limit
+        let requests = server.wait_for_requests(3).await;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(count_items(&requests[0], 0..2), 2);
+        assert_eq!(count_items(&requests[1], 2..4), 2);
+        assert_eq!(count_items(&requests[2], 4..5), 1);
+        assert!(requests.iter().all(|request| request.len() <= 25_000));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_keeps_priority_of_items_that_did_not_fit_into_batch() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_batch_bytes(25_000)
+            .max_queue_size(3)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // every item takes a bit more than 10 KB, so the important one does not fit into a batch
+        let padding = "x".repeat(10_000);
+        for i in 0..2 {
+            client.track_event(format!("--event {}--{}", i, padding));
+        }
+        let mut important = EventTelemetry::new(format!("--important--{}", padding));
+        important.tags_mut().set_priority(Priority::High);
+        client.track(important);
+
+        // "wait" until interval expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert_eq!(count_items(&request, 0..2), 2);
+        assert!(!request.contains("--important--"));
+
+        // overflow a queue with newer items of a lower priority
+        for i in 2..5 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // verify the oldest item of a lower priority is dropped instead of the important one
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--important--"));
+        assert_eq!(count_items(&request, 2..3), 0);
+        assert_eq!(count_items(&request, 3..5), 2);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_does_not_send_any_pending_telemetry_items_when_drop_client() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -198,7 +393,8 @@ manual_timeout_test! {
 
         // close internal channel means that client will make an attempt to send telemetry items once
         // and then tear down submission flow
-        client.close_channel().await;
+        let report = client.close_channel().await;
+        assert_eq!(report, ShutdownReport::new(15, 0, Some(TransmissionStatus::Accepted)));
 
         // NOTE no timeout expired
         // verify that 1 request has been sent
@@ -220,6 +416,53 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_reports_telemetry_items_abandoned_when_close_channel_requested() {
+        let server = server().status(StatusCode::SERVICE_UNAVAILABLE).create();
+
+        let client = create_client(server.url());
+        for i in 0..5 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // the only attempt to send items on close fails
+        let report = client.close_channel().await;
+        assert_eq!(report.flushed(), 0);
+        assert_eq!(report.abandoned(), 5);
+        assert_eq!(report.status(), Some(&TransmissionStatus::RetryRequested));
+        assert!(!report.is_complete());
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_pending_telemetry_items_when_shutdown_signal_received() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        let (signal_send, signal_recv) = oneshot::channel::<()>();
+        let shutdown = tokio::spawn(client.shutdown_handle().close_on(async {
+            signal_recv.await.ok();
+        }));
+
+        client.track_event("--event--");
+
+        // verify nothing is sent until shutdown signal is received
+        assert_matches!(server.next_request_timeout().await, Err(RecvTimeoutError::Timeout));
+
+        signal_send.send(()).unwrap();
+        shutdown.await.unwrap();
+
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_does_not_try_to_send_pending_telemetry_items_when_client_terminated() {
         let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
@@ -232,7 +475,8 @@ manual_timeout_test! {
         }
 
         // terminate client
-        client.terminate().await;
+        let report = client.terminate().await;
+        assert_eq!(report, ShutdownReport::new(0, 15, None));
 
         // NOTE no timeout expired
         // verify that no request has been sent
@@ -283,6 +527,141 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_reports_items_lost_when_retries_exhausted_in_snapshot() {
+        let mut server = server()
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .status(StatusCode::OK)
+            .create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .snapshot_interval(Duration::from_secs(0))
+            .build();
+        let client = TelemetryClient::from_config(config);
+        client.track_event("--event--");
+
+        // "wait" until interval expired and all retries exhausted
+        for _ in 0..4 {
+            timeout::expire();
+        }
+        let requests = server.wait_for_requests(4).await;
+        assert_eq!(requests.len(), 4);
+
+        // "wait" until interval expired and the next snapshot sent
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        let envelopes: Vec<serde_json::Value> = serde_json::from_str(&request).unwrap();
+        let snapshot = envelopes
+            .iter()
+            .find(|envelope| envelope["data"]["baseData"]["name"] == json!("Application Insights SDK Snapshot"))
+            .unwrap();
+        // an event and the first snapshot sent along with it
+        assert_eq!(snapshot["data"]["baseData"]["measurements"]["lost"], json!(2.0));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_delivers_items_after_connection_was_lost_once() {
+        // the first connection is closed before the request is answered
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let lost = tokio::task::spawn_blocking(move || {
+            use std::io::Read;
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+        });
+
+        let client = create_client(&format!("http://{}/track", addr));
+        client.track_event("--event--");
+
+        // "wait" until interval expired
+        timeout::expire();
+        lost.await.unwrap();
+        let mut server = server().bind(addr).status(StatusCode::OK).create();
+
+        // "wait" until retry timeout expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_deposits_items_that_failed_with_non_retryable_error_to_dead_letter_sink() {
+        let path = crate::test::temp_path("ndjson");
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint("not a url")
+            .interval(Duration::from_millis(300))
+            .dead_letter_sink(FileDeadLetterSink::new(&path))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+        let report = client.close_channel().await;
+
+        let letters: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(letters.len(), 1);
+        assert_eq!(letters[0]["reason"], json!("failed"));
+        assert_eq!(letters[0]["envelope"]["data"]["baseData"]["name"], json!("--event--"));
+        assert_eq!(report.abandoned(), 1);
+    }
+}
+
+manual_timeout_test! {
+    async fn it_does_not_resend_items_when_flush_requested_while_retry_pending() {
+        let mut server = server()
+            .response(StatusCode::INTERNAL_SERVER_ERROR, json!({}), None)
+            .status(StatusCode::OK)
+            .status(StatusCode::OK)
+            .create();
+
+        let client = create_client(server.url());
+
+        // send 3 items and then interval expired, so the first attempt fails
+        for i in 0..3 {
+            client.track_event(format!("--event {}--", i));
+        }
+        timeout::expire();
+        assert_eq!(server.wait_for_requests(1).await.len(), 1);
+
+        // queue another item and flush while the batch is awaiting retry
+        client.track_event("--event 3--");
+        client.flush_channel();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // "wait" until retry logic handled
+        timeout::expire();
+
+        // verify the retry contains the failed batch only and the flush sends the new item afterwards
+        let requests = server.wait_for_requests(2).await;
+        assert_eq!(requests.len(), 2);
+        assert!((0..3).all(|i| requests[0].contains(&format!("--event {}--", i))));
+        assert!(!requests[0].contains("--event 3--"));
+        assert!((0..3).all(|i| !requests[1].contains(&format!("--event {}--", i))));
+        assert!(requests[1].contains("--event 3--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 manual_timeout_test! {
     async fn it_retries_when_partial_content() {
         let mut server = server()
@@ -369,8 +748,606 @@ manual_timeout_test! {
     }
 }
 
+manual_timeout_test! {
+    async fn it_submits_queued_telemetry_items_with_rotated_instrumentation_keys() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        client.track_event("--event 0--");
+
+        // keep a key of queued items
+        client.rotate_instrumentation_key("second key");
+        client.track_event("--event 1--");
+
+        // replace a key of queued items
+        client.rotate_instrumentation_key_and_restamp("third key");
+        client.track_event("--event 2--");
+
+        // "wait" until interval expired
+        timeout::expire();
+
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].matches(r#""iKey":"instrumentation key""#).count(), 1);
+        assert_eq!(requests[0].matches(r#""iKey":"second key""#).count(), 0);
+        assert_eq!(requests[0].matches(r#""iKey":"third key""#).count(), 2);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_drops_telemetry_items_older_than_time_to_live() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .time_to_live(Duration::from_secs(3600))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // send an item created 2 hours ago and a fresh one
+        time::set(Utc::now() - chrono::Duration::hours(2));
+        client.track_event("--stale event--");
+        time::reset();
+        client.track_event("--fresh event--");
+
+        // "wait" until interval expired
+        timeout::expire();
+
+        // verify only a fresh item is sent
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains("--fresh event--"));
+        assert!(!requests[0].contains("--stale event--"));
+
+        // verify a stale item is accounted
+        let report = client.close_channel().await;
+        assert_eq!(report.expired(), 1);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_deposits_undeliverable_telemetry_items_to_dead_letter_sink() {
+        let mut server = server()
+            .response(
+                StatusCode::PARTIAL_CONTENT,
+                json!(
+                {
+                    "itemsAccepted": 0,
+                    "itemsReceived": 2,
+                    "errors": [
+                        {
+                            "index": 0,
+                            "statusCode": StatusCode::BAD_REQUEST.as_u16(),
+                            "message": "Invalid field"
+                        },
+                        {
+                            "index": 1,
+                            "statusCode": StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                            "message": "Service Unavailable"
+                        }
+                    ],
+                }),
+                None,
+            )
+            .create();
+
+        let path = crate::test::temp_path("ndjson");
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .time_to_live(Duration::from_secs(3600))
+            .dead_letter_sink(FileDeadLetterSink::new(&path))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // send an item created 2 hours ago and two fresh ones
+        time::set(Utc::now() - chrono::Duration::hours(2));
+        client.track_event("--event 0--");
+        time::reset();
+        client.track_event("--event 1--");
+        client.track_event("--event 2--");
+
+        // make the only attempt to send items
+        client.close_channel().await;
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+
+        // verify every undeliverable item is deposited with a reason
+        let letters: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let reasons: Vec<_> = letters
+            .iter()
+            .map(|letter| (letter["reason"].as_str(), letter["envelope"]["data"]["baseData"]["name"].as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (Some("expired"), Some("--event 0--")),
+                (Some("rejected"), Some("--event 1--")),
+                (Some("retriesExhausted"), Some("--event 2--")),
+            ]
+        );
+        assert_eq!(letters[1]["statusCode"], json!(400));
+        assert_eq!(letters[1]["message"], json!("Invalid field"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_acknowledges_sequence_of_accepted_batches() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let path = crate::test::temp_path("seq");
+        let store = FileSequenceStore::new(&path);
+        store.save(4).unwrap();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .sequence_store(store.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event 0--");
+        client.track_event("--event 1--");
+
+        // "wait" until interval expired
+        timeout::expire();
+
+        // verify items are numbered after the sequence saved before
+        let requests = server.wait_for_requests(1).await;
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contains(r#""seq":"5:0""#));
+        assert!(requests[0].contains(r#""seq":"5:1""#));
+
+        // verify an accepted batch is acknowledged
+        let report = client.close_channel().await;
+        assert_eq!(report.acked_sequence(), Some(5));
+        assert_eq!(store.load().unwrap(), Some(5));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_next_batch_while_previous_one_is_in_flight() {
+        let mut server = server()
+            .status(StatusCode::OK)
+            .delayed(Duration::from_secs(5))
+            .status(StatusCode::OK)
+            .create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_concurrent_transmissions(2)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // first batch is stuck waiting for a response
+        client.track_event("--event 1--");
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event 1--"));
+
+        // second batch is sent regardless
+        client.track_event("--event 2--");
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event 2--"));
+
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    #[cfg(feature = "reqwest")]
+    async fn it_retries_items_when_sending_panicked() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        // the first attempt to resolve the endpoint host panics
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url().replace("0.0.0.0", "appinsights.test"))
+            .interval(Duration::from_millis(300))
+            .dns_resolver(Arc::new(PanickingResolver(AtomicUsize::new(0))))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+
+        // "wait" until interval expired
+        timeout::expire();
+        assert_matches!(server.next_request_timeout().await, Err(RecvTimeoutError::Timeout));
+
+        // "wait" until retry timeout expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        server.terminate().await;
+    }
+}
+
+/// Panics on the first attempt to resolve a host name and resolves it to the local host afterwards.
+#[cfg(feature = "reqwest")]
+struct PanickingResolver(AtomicUsize);
+
+#[cfg(feature = "reqwest")]
+impl Resolve for PanickingResolver {
+    fn resolve(&self, _: Name) -> Resolving {
+        if self.0.fetch_add(1, Ordering::SeqCst) == 0 {
+            panic!("resolver panicked");
+        }
+
+        let addrs: Addrs = Box::new(std::iter::once(([127, 0, 0, 1], 0).into()));
+        Box::pin(async move { Ok(addrs) })
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_to_ipv6_endpoint() {
+        let mut server = server().status(StatusCode::OK).bind(([0, 0, 0, 0, 0, 0, 0, 1], 0)).create();
+        assert!(server.url().starts_with("http://[::1]:"));
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(format!("{}/v2/track", server.url()))
+            .interval(Duration::from_millis(300))
+            .try_build()
+            .unwrap();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+
+        // "wait" until interval expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_runs_worker_tasks_on_elapsed_intervals() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let (tick_sender, mut tick_receiver) = mpsc::channel(10);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .worker_task(1, |_: &WorkerTick| panic!("task panicked"))
+            .worker_task(1, move |tick: &WorkerTick| {
+                let _ = tick_sender.try_send(tick.clone());
+            })
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+
+        // "wait" until interval expired
+        timeout::expire();
+        let tick = tokio::time::timeout(Duration::from_secs(1), tick_receiver.recv()).await;
+        assert_eq!(tick.unwrap(), Some(WorkerTick::new(1, 1)));
+
+        // a panicking task does not stop the worker
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_telemetry_items_on_manual_scheduler_ticks() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let scheduler = ManualScheduler::new();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(50))
+            .scheduler(scheduler.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+
+        // neither the interval nor the emulated timeout sends items without a tick
+        timeout::expire();
+        assert_matches!(server.next_request_timeout().await, Err(RecvTimeoutError::Timeout));
+
+        scheduler.tick();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--event--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_deposits_items_dropped_from_full_queue_without_their_priority() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let path = crate::test::temp_path("ndjson");
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_queue_size(1)
+            .dead_letter_sink(FileDeadLetterSink::new(&path))
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        let mut important = EventTelemetry::new("--important--");
+        important.tags_mut().set_priority(Priority::High);
+        client.track_event("--event--");
+        client.track(important);
+
+        // "wait" until interval expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--important--"));
+        assert!(!request.contains("ai.internal.priority"));
+
+        // verify a dropped item is deposited without an internal tag
+        client.close_channel().await;
+        let letters = std::fs::read_to_string(&path).unwrap();
+        assert!(letters.contains("--event--"));
+        assert!(letters.contains("queueFull"));
+        assert!(!letters.contains("ai.internal.priority"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_drops_items_selected_by_drop_policy_when_queue_is_full() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_queue_size(2)
+            .drop_policy(|queue: &mut QueueView<'_>| {
+                // keep exceptions, drop the newest events instead
+                let events: Vec<_> = queue
+                    .iter()
+                    .filter(|item| item.kind() == Some(TelemetryKind::Event))
+                    .map(|item| item.index())
+                    .collect();
+                for index in events.into_iter().rev().take(queue.excess()) {
+                    queue.select(index);
+                }
+            })
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_exception("--exception--", "Error", None::<String>, None::<String>);
+        for i in 0..3 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        // "wait" until interval expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--exception--"));
+        assert_eq!(count_items(&request, 0..1), 1);
+        assert_eq!(count_items(&request, 1..3), 0);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_sends_items_of_higher_priority_first_in_order_they_were_accepted() {
+        let mut server = server().status(StatusCode::OK).status(StatusCode::OK).create();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .max_batch_size(2)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_trace("--trace--", SeverityLevel::Verbose);
+        client.track_event("--event--");
+        client.track_exception("--exception--", "Error", None::<String>, None::<String>);
+
+        // "wait" until interval expired
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--exception--"));
+        assert!(request.contains("--event--"));
+        assert!(!request.contains("--trace--"));
+        assert!(!request.contains("ai.internal.priority"));
+
+        // items of a batch are sent in the order they were accepted
+        assert!(request.find("--event--") < request.find("--exception--"));
+        assert!(request.contains(r#""seq":"#));
+
+        // "wait" until interval expired again
+        timeout::expire();
+        let request = server.next_request_timeout().await.unwrap();
+        assert!(request.contains("--trace--"));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_discards_pending_items_when_cleared() {
+        let mut server = server().status(StatusCode::OK).create();
+
+        let client = create_client(server.url());
+        for i in 0..3 {
+            client.track_event(format!("--event {}--", i));
+        }
+
+        assert_eq!(client.pending_items(), 3);
+        assert_eq!(client.clear_pending(), 3);
+        assert_eq!(client.pending_items(), 0);
+
+        // verify cleared items are not sent after interval expired
+        timeout::expire();
+        assert_matches!(
+            server.next_request_timeout().await,
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        // verify items tracked afterwards are sent as usual
+        client.track_event("--event 3--");
+        timeout::expire();
+        let content = server.next_request_timeout().await.unwrap();
+        assert_eq!(count_items(&content, 0..4), 1);
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_saves_throttle_deadline() {
+        let retry_after = (Utc::now() + chrono::Duration::hours(1)).trunc_subsecs(0);
+        let mut server = server()
+            .response(StatusCode::TOO_MANY_REQUESTS, "", Some(retry_after))
+            .create();
+
+        let path = crate::test::temp_path("throttle");
+        let store = FileThrottleStore::new(&path);
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(server.url())
+            .interval(Duration::from_millis(300))
+            .throttle_store(store.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event 0--");
+
+        // "wait" until interval expired
+        timeout::expire();
+        assert_eq!(server.wait_for_requests(1).await.len(), 1);
+
+        // verify the deadline the server throttled submissions until is saved
+        for _ in 0..20 {
+            if store.load().unwrap().is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(store.load().unwrap(), Some(retry_after));
+        client.terminate().await;
+        server.terminate().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_waits_for_saved_throttle_deadline_on_startup() {
+        let path = crate::test::temp_path("throttle");
+        let store = FileThrottleStore::new(&path);
+        store.save(Utc::now() + chrono::Duration::hours(1)).unwrap();
+
+        let scheduler = Arc::new(RecordingScheduler::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .interval(Duration::from_millis(50))
+            .scheduler(scheduler.clone())
+            .throttle_store(store)
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        // verify the worker waits for the throttle window to be over before the first submission
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let periods = scheduler.0.lock().clone();
+        assert_eq!(periods.len(), 1);
+        assert!(periods[0] > Duration::from_secs(59 * 60));
+
+        client.terminate().await;
+    }
+}
+
+/// A scheduler that records periods a worker waits for and never completes them.
+#[derive(Default)]
+struct RecordingScheduler(Mutex<Vec<Duration>>);
+
+#[async_trait]
+impl Scheduler for Arc<RecordingScheduler> {
+    async fn sleep(&self, period: Duration) {
+        self.0.lock().push(period);
+        futures_util::future::pending::<()>().await;
+    }
+}
+
+manual_timeout_test! {
+    async fn it_waits_for_throttle_deadline_before_sending_next_batch() {
+        let retry_after = Utc::now() + chrono::Duration::hours(1);
+        let mut server = server()
+            .response(StatusCode::TOO_MANY_REQUESTS, "", Some(retry_after))
+            .status(StatusCode::OK)
+            .create();
+
+        let client = create_client(server.url());
+        client.track_event("--event 0--");
+
+        // "wait" until interval expired and then until the throttled batch is retried
+        timeout::expire();
+        assert_eq!(server.wait_for_requests(1).await.len(), 1);
+        timeout::expire();
+        assert_eq!(server.wait_for_requests(1).await.len(), 1);
+
+        // verify both the retry and the next interval last until the server accepts submissions again
+        for _ in 0..20 {
+            if timeout::periods().len() > 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let periods = timeout::periods();
+        assert_eq!(periods.len(), 3);
+        assert!(periods[1] > Duration::from_secs(59 * 60));
+        assert!(periods[2] > Duration::from_secs(59 * 60));
+
+        // terminate server
+        server.terminate().await;
+    }
+}
+
 // TODO Check case when all retries exhausted. Pending items should not be lost
 
+/// Counts events with given numbers in a request body.
+fn count_items(content: &str, events: std::ops::Range<usize>) -> usize {
+    events.filter(|i| content.contains(&format!("--event {}--", i))).count()
+}
+
 fn create_client(endpoint: &str) -> TelemetryClient {
     let config = TelemetryConfig::builder()
         .i_key("instrumentation key")
@@ -382,7 +1359,10 @@ fn create_client(endpoint: &str) -> TelemetryClient {
 }
 
 fn server() -> Builder {
-    Builder { responses: Vec::new() }
+    Builder {
+        responses: Vec::new(),
+        addr: ([0, 0, 0, 0], 0).into(),
+    }
 }
 
 struct HyperTestServer {
@@ -434,8 +1414,13 @@ enum RecvTimeoutError {
 
 struct Builder {
     responses: Vec<Response<String>>,
+    addr: SocketAddr,
 }
 
+/// A time to wait before a server responds.
+#[derive(Clone, Copy)]
+struct Delay(Duration);
+
 impl Builder {
     fn response(mut self, status: StatusCode, body: impl ToString, retry_after: Option<DateTime<Utc>>) -> Self {
         let mut builder = Response::builder().status(status);
@@ -451,6 +1436,20 @@ impl Builder {
         self
     }
 
+    /// Delays the last added response for a given duration.
+    fn delayed(mut self, delay: Duration) -> Self {
+        if let Some(response) = self.responses.last_mut() {
+            response.extensions_mut().insert(Delay(delay));
+        }
+        self
+    }
+
+    /// Binds a server to a given address instead of a random port of all IPv4 interfaces.
+    fn bind(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
     fn status(self, status: StatusCode) -> Self {
         self.response(
             status,
@@ -493,10 +1492,15 @@ impl Builder {
                         let count = counter.fetch_add(1, Ordering::AcqRel);
 
                         let response = if let Some(response) = responses.get(count) {
-                            Response::builder()
-                                .status(response.status())
-                                .body(Body::from(response.body().clone()))
-                                .unwrap()
+                            if let Some(Delay(delay)) = response.extensions().get::<Delay>() {
+                                tokio::time::sleep(*delay).await;
+                            }
+
+                            let mut builder = Response::builder().status(response.status());
+                            for (name, value) in response.headers() {
+                                builder = builder.header(name, value);
+                            }
+                            builder.body(Body::from(response.body().clone())).unwrap()
                         } else {
                             Response::builder()
                                 .status(StatusCode::NOT_FOUND)
@@ -510,7 +1514,7 @@ impl Builder {
             }
         });
 
-        let server = Server::bind(&([0, 0, 0, 0], 0).into()).serve(make_service);
+        let server = Server::bind(&self.addr).serve(make_service);
 
         let url = format!("http://{}", server.local_addr());
 
@@ -1,23 +1,69 @@
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak,
+    },
+    time::Duration,
+};
 
 use http::Uri;
 
 use crate::{
-    channel::{InMemoryChannel, TelemetryChannel},
+    channel::{InMemoryChannel, ShutdownReport, TelemetryChannel},
+    clock::{self, SharedClock, Timestamping},
     context::TelemetryContext,
-    contracts::Envelope,
+    contracts::{tags, Base, Envelope, SeverityLevel as ContractsSeverityLevel},
+    daily_cap::DailyCapGuard,
+    processor::{DependencySuccess, ProcessingContext, Processors, UrlRedaction},
+    standard_metrics::{self, StandardMetrics},
     telemetry::{
-        AvailabilityTelemetry, EventTelemetry, ExceptionTelemetry, MetricTelemetry, RemoteDependencyTelemetry,
-        RequestTelemetry, SeverityLevel, Telemetry, TraceTelemetry,
+        AvailabilityTelemetry, ContextTags, EventTelemetry, ExceptionTelemetry, FeedbackTelemetry, IntoEnvelope,
+        MetricTelemetry, Priority, Properties, RemoteDependencyTelemetry, RequestTelemetry, SeverityLevel, Telemetry,
+        TelemetryKind, TraceTelemetry, Tracker,
     },
     TelemetryConfig,
 };
 
+mod builder;
+pub use builder::TelemetryClientBuilder;
+
+mod shutdown;
+pub use shutdown::ShutdownHandle;
+
 /// Application Insights telemetry client provides an interface to track telemetry items.
+///
+/// The client is cheap to clone. All clones share the same submission channel, telemetry context
+/// and enabled flag, so a client can be stored in a web framework state or moved into spawned
+/// tasks without wrapping it into `Arc<Mutex<_>>`.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # use appinsights::TelemetryClient;
+/// let client = TelemetryClient::new("<instrumentation key>".to_string());
+///
+/// let worker = client.clone();
+/// tokio::spawn(async move {
+///     worker.track_event("task started");
+/// });
+///
+/// // tags set via any clone are visible to all of them
+/// client.context_mut().tags_mut().cloud_mut().set_role("rust_server".to_string());
+/// ```
+#[derive(Clone)]
 pub struct TelemetryClient {
-    enabled: bool,
-    context: TelemetryContext,
-    channel: Box<dyn TelemetryChannel>,
+    enabled: Arc<AtomicBool>,
+    context: Arc<RwLock<TelemetryContext>>,
+    processors: Processors,
+    disabled_types: Vec<TelemetryKind>,
+    min_severity: Option<ContractsSeverityLevel>,
+    url_redaction: UrlRedaction,
+    dependency_success: DependencySuccess,
+    clock: Option<SharedClock>,
+    sdk_version: Arc<str>,
+    standard_metrics: Option<Arc<StandardMetrics>>,
+    daily_cap: Option<Arc<DailyCapGuard>>,
+    channel: Arc<dyn TelemetryChannel>,
 }
 
 impl TelemetryClient {
@@ -31,13 +77,35 @@ impl TelemetryClient {
         Self::create(&config, InMemoryChannel::new(&config))
     }
 
+    /// Creates a new builder of a telemetry client configured with specified configuration. It
+    /// allows to choose a channel, seed a telemetry context and add processors.
+    pub fn builder(config: TelemetryConfig) -> TelemetryClientBuilder {
+        TelemetryClientBuilder::new(config)
+    }
+
     /// Creates a new telemetry client with custom telemetry channel.
     pub(crate) fn create<C: TelemetryChannel + 'static>(config: &TelemetryConfig, channel: C) -> Self {
-        Self {
-            enabled: true,
-            context: TelemetryContext::from_config(config),
-            channel: Box::new(channel),
-        }
+        Self::with_channel(config, Arc::new(channel))
+    }
+
+    /// Creates a new telemetry client with a shared telemetry channel.
+    fn with_channel(config: &TelemetryConfig, channel: Arc<dyn TelemetryChannel>) -> Self {
+        let client = Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            context: Arc::new(RwLock::new(TelemetryContext::from_config(config))),
+            processors: config.processors().clone(),
+            disabled_types: config.disabled_types().to_vec(),
+            min_severity: config.min_severity().map(Into::into),
+            url_redaction: config.url_redaction().clone(),
+            dependency_success: config.dependency_success().clone(),
+            clock: config.clock_at(Timestamping::OnTrack).cloned(),
+            sdk_version: config.sdk_version().into(),
+            standard_metrics: StandardMetrics::from_config(config).map(Arc::new),
+            daily_cap: DailyCapGuard::from_config(config).map(Arc::new),
+            channel,
+        };
+        client.drain_standard_metrics(standard_metrics::DRAIN_INTERVAL);
+        client
     }
 
     /// Determines whether this client is enabled and will accept telemetry.
@@ -50,55 +118,137 @@ impl TelemetryClient {
     /// assert!(client.is_enabled());
     /// ```
     pub fn is_enabled(&self) -> bool {
-        self.enabled
+        self.enabled.load(Ordering::Acquire)
     }
 
     /// Enables or disables telemetry client. When disabled, telemetry is silently swallowed by the client. Defaults to enabled.
+    /// The flag is shared between all clones of this client.
     ///
     /// # Examples
     ///
     /// ```rust
     /// # use appinsights::TelemetryClient;
-    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// assert!(client.is_enabled());
     ///
     /// client.enabled(false);
     /// assert_eq!(client.is_enabled(), false);
     /// ```
-    pub fn enabled(&mut self, enabled: bool) {
-        self.enabled = enabled;
+    pub fn enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Release);
     }
 
-    /// Returns an immutable reference to a collection of tag data to attach to the telemetry item.
+    /// Returns a read guard to a collection of tag data to attach to the telemetry item.
+    ///
+    /// The context is shared between all clones of this client. Telemetry submission is blocked
+    /// while the guard is alive, so it should not be held for longer than needed.
     ///
     /// # Examples
     ///
     /// ```rust
     /// # use appinsights::TelemetryClient;
-    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// client.context_mut().tags_mut().cloud_mut().set_role("rust_server".to_string());
     ///
     /// assert_eq!(client.context().tags().cloud().role(), Some("rust_server"));
     /// ```
-    pub fn context(&self) -> &TelemetryContext {
-        &self.context
+    pub fn context(&self) -> RwLockReadGuard<'_, TelemetryContext> {
+        self.context.read().unwrap_or_else(PoisonError::into_inner)
     }
 
-    /// Returns a mutable reference to a collection of tag data to attach to the telemetry item.
+    /// Returns a write guard to a collection of tag data to attach to the telemetry item.
+    ///
+    /// The context is shared between all clones of this client. Changes made through the guard
+    /// become visible atomically once it is dropped: every telemetry item tracked afterwards by any
+    /// clone observes all of them, while items tracked before that observe none of them.
     ///
     /// # Examples
     ///
     /// ```rust
     /// # use appinsights::TelemetryClient;
-    /// let mut client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
     /// client.context_mut().tags_mut().insert("app_version".into(), "v0.1.1".to_string());
     /// client.context_mut().properties_mut().insert("Resource Group".into(), "my-rg".to_string());
     ///
     /// assert_eq!(client.context().tags().get("app_version"), Some(&"v0.1.1".to_string()));
     /// assert_eq!(client.context().properties().get("Resource Group"), Some(&"my-rg".to_string()));
     /// ```
-    pub fn context_mut(&mut self) -> &mut TelemetryContext {
-        &mut self.context
+    pub fn context_mut(&self) -> RwLockWriteGuard<'_, TelemetryContext> {
+        self.context.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replaces an instrumentation key telemetry items are submitted with without recreating the
+    /// client, e.g. when credentials are rotated. Items tracked from now on by this client and
+    /// its clones get the new key, while items queued already keep the old one. Child clients
+    /// created before keep their own keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.rotate_instrumentation_key("<new instrumentation key>");
+    ///
+    /// assert_eq!(client.context().i_key(), "<new instrumentation key>");
+    /// ```
+    pub fn rotate_instrumentation_key(&self, i_key: impl Into<String>) {
+        self.context_mut().set_i_key(i_key);
+    }
+
+    /// Replaces an instrumentation key telemetry items are submitted with like
+    /// [`rotate_instrumentation_key`](#method.rotate_instrumentation_key) does, but also stamps
+    /// items queued or waiting for a retry with the old key with the new one when they are sent.
+    /// Use it when the old key is revoked right away. Items of a single batch may carry different
+    /// keys, so no items are held back by a rotation.
+    pub fn rotate_instrumentation_key_and_restamp(&self, i_key: impl Into<String>) {
+        let i_key = i_key.into();
+        let mut context = self.context_mut();
+        self.channel.restamp_i_key(context.i_key(), &i_key);
+        context.set_i_key(i_key);
+    }
+
+    /// Creates a child client that submits telemetry through the same channel, but with its own
+    /// context. The child context starts as a copy of this client's context and then
+    /// `configure` overrides it, e.g. with extra tags, properties or a different role. It makes it
+    /// cheap to attribute telemetry to subsystems of a single process.
+    ///
+    /// Changes made to either context afterwards are not visible to the other client, while the
+    /// enabled flag is shared with this client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// let client = TelemetryClient::new("<instrumentation key>".to_string());
+    ///
+    /// let billing = client.child_with(|context| {
+    ///     context.tags_mut().cloud_mut().set_role("billing".to_string());
+    ///     context.properties_mut().insert("subsystem".into(), "billing".to_string());
+    /// });
+    ///
+    /// billing.track_event("invoice issued");
+    /// ```
+    pub fn child_with<F>(&self, configure: F) -> Self
+    where
+        F: FnOnce(&mut TelemetryContext),
+    {
+        let mut context = self.context().clone();
+        configure(&mut context);
+
+        Self {
+            enabled: self.enabled.clone(),
+            context: Arc::new(RwLock::new(context)),
+            processors: self.processors.clone(),
+            disabled_types: self.disabled_types.clone(),
+            min_severity: self.min_severity.clone(),
+            url_redaction: self.url_redaction.clone(),
+            dependency_success: self.dependency_success.clone(),
+            clock: self.clock.clone(),
+            sdk_version: self.sdk_version.clone(),
+            standard_metrics: self.standard_metrics.clone(),
+            daily_cap: self.daily_cap.clone(),
+            channel: self.channel.clone(),
+        }
     }
 
     /// Logs a user action with the specified name.
@@ -209,6 +359,26 @@ impl TelemetryClient {
         self.track(event)
     }
 
+    /// Logs a feedback a user gave in the application with a score and an optional comment.
+    /// The feedback is linked to the session, the user and the operation of the client context.
+    /// Use [`FeedbackTelemetry`](crate::telemetry::FeedbackTelemetry) directly to describe a
+    /// place in the application the feedback is about.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_feedback(9.0, Some("Checkout was quick and easy"));
+    /// ```
+    pub fn track_feedback(&self, score: f64, comment: Option<&str>) {
+        let mut event = FeedbackTelemetry::new(score);
+        if let Some(comment) = comment {
+            event.set_comment(comment);
+        }
+        self.track(event)
+    }
+
     /// Logs an exception with the specified message, exception type name,
     /// severity level, and problem ID.
     /// # Examples
@@ -259,12 +429,252 @@ impl TelemetryClient {
         E: Telemetry,
         (TelemetryContext, E): Into<Envelope>,
     {
+        self.track_with_context(event, ProcessingContext::new(None));
+    }
+
+    /// Submits a specific telemetry event and lets `customize` modify tags and properties of the
+    /// telemetry item once they have been combined with the client context. It allows to override
+    /// a value the client context would otherwise force, e.g. a cloud role of a tenant.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::EventTelemetry;
+    ///
+    /// client.context_mut().tags_mut().cloud_mut().set_role("rust_server".to_string());
+    ///
+    /// client.track_with(EventTelemetry::new("invoice issued"), |tags, properties| {
+    ///     tags.cloud_mut().set_role("tenant-42".to_string());
+    ///     properties.remove("internal");
+    /// });
+    /// ```
+    pub fn track_with<E, F>(&self, event: E, customize: F)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+        F: FnOnce(&mut ContextTags, &mut Properties),
+    {
+        let convert = |context| (context, event).into();
+        self.submit(convert, ProcessingContext::new(None), |envelope| {
+            let mut tags = ContextTags::from(envelope.tags.take().unwrap_or_default());
+            match &mut envelope.data {
+                Some(Base::Data(data)) => {
+                    let properties = data.properties_mut();
+                    let mut combined = Properties::from(properties.take().unwrap_or_default());
+                    customize(&mut tags, &mut combined);
+                    *properties = Some(combined.into());
+                }
+                None => customize(&mut tags, &mut Properties::default()),
+            }
+            envelope.tags = Some(tags.into());
+        });
+    }
+
+    /// Submits a telemetry event which type is not known at compile time. It allows to collect
+    /// telemetry items of different types and submit them later.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::{EventTelemetry, IntoEnvelope};
+    ///
+    /// let telemetry: Box<dyn IntoEnvelope> = Box::new(EventTelemetry::new("plugin loaded"));
+    /// client.track_boxed(telemetry);
+    /// ```
+    pub fn track_boxed(&self, event: Box<dyn IntoEnvelope>) {
+        self.submit(
+            |context| event.into_envelope(context),
+            ProcessingContext::new(None),
+            |_| {},
+        );
+    }
+
+    /// Submits a specific telemetry event produced by an integration with a given processing context.
+    pub(crate) fn track_with_context<E>(&self, event: E, processing: ProcessingContext)
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.submit(|context| (context, event).into(), processing, |_| {});
+    }
+
+    /// Converts a telemetry event into an envelope with a snapshot of the client context without
+    /// submitting it. The envelope can be serialized and stored by an application, e.g. to survive
+    /// a restart of a device without connectivity, and submitted later with
+    /// [`track_envelope`](#method.track_envelope).
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::{processor::Envelope, telemetry::EventTelemetry};
+    ///
+    /// let envelope = client.prepare(EventTelemetry::new("measurement taken"));
+    /// let stored = serde_json::to_string(&envelope).unwrap();
+    ///
+    /// // after a restart
+    /// let envelope: Envelope = serde_json::from_str(&stored).unwrap();
+    /// client.track_envelope(envelope);
+    /// ```
+    pub fn prepare<E>(&self, event: E) -> Envelope
+    where
+        E: Telemetry,
+        (TelemetryContext, E): Into<Envelope>,
+    {
+        self.convert(|context| (context, event).into())
+    }
+
+    /// Submits an envelope as is without combining it with the client context. It accepts an
+    /// envelope prepared with [`prepare`](#method.prepare) earlier as well as a fully-formed
+    /// envelope produced elsewhere, e.g. received by a relay service or read from a
+    /// [dead letter](crate::dead_letter) file, so it is sent with the same batching and retries as
+    /// any other telemetry item.
+    ///
+    /// The instrumentation key, tags and time of the envelope are kept intact. The envelope is
+    /// still dropped when its category is disabled or its severity is below the minimum one, and
+    /// processors are applied to it before it is queued for submission.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::processor::Envelope;
+    ///
+    /// // an envelope received from a device that has no connectivity of its own
+    /// let payload = r#"{"name":"Microsoft.ApplicationInsights.Event","time":"2024-01-02T03:04:05Z","iKey":"<device key>"}"#;
+    /// let envelope: Envelope = serde_json::from_str(payload).unwrap();
+    ///
+    /// client.track_envelope(envelope);
+    /// ```
+    pub fn track_envelope(&self, envelope: Envelope) {
         if self.is_enabled() {
-            let envelop = (self.context.clone(), event).into();
+            self.send(envelope, ProcessingContext::new(None));
+        }
+    }
+
+    /// Converts a telemetry event into an envelope, applies processors and queues it for submission.
+    fn submit<C, F>(&self, convert: C, processing: ProcessingContext, customize: F)
+    where
+        C: FnOnce(TelemetryContext) -> Envelope,
+        F: FnOnce(&mut Envelope),
+    {
+        if self.is_enabled() {
+            let mut envelop = self.convert(convert);
+            customize(&mut envelop);
+            self.send(envelop, processing);
+        }
+    }
+
+    /// Converts a telemetry event into an envelope with a snapshot of the client context combined
+    /// with the [current](crate::ambient) one, redacts its URL, decides whether a dependency call
+    /// succeeded and stamps it with the time of the client clock.
+    fn convert<C>(&self, convert: C) -> Envelope
+    where
+        C: FnOnce(TelemetryContext) -> Envelope,
+    {
+        // telemetry items are correlated with an operation of the current ambient context if any
+        let context = self.context().clone().with_current();
+        let mut envelop = convert(context);
+        self.url_redaction.apply(&mut envelop);
+        self.dependency_success.apply(&mut envelop);
+        if let Some(offset) = self.clock.as_ref().and_then(SharedClock::offset) {
+            clock::shift(&mut envelop, offset);
+        }
+        envelop
+    }
+
+    /// Drops an envelope of disabled type or severity, aggregates standard metrics, reports the SDK
+    /// version unless the envelope carries one already, applies processors and queues it for
+    /// submission unless the daily cap suppresses it.
+    fn send(&self, mut envelop: Envelope, processing: ProcessingContext) {
+        if TelemetryKind::of(&envelop).is_some_and(|kind| self.disabled_types.contains(&kind)) {
+            return;
+        }
+
+        if self
+            .min_severity
+            .as_ref()
+            .is_some_and(|min| envelop.is_less_severe_than(min))
+        {
+            return;
+        }
+
+        if let Some(standard_metrics) = &self.standard_metrics {
+            self.send_standard_metrics(standard_metrics.extract(&mut envelop));
+        }
+
+        envelop.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, &self.sdk_version);
+        if !self.processors.process(&mut envelop, processing) {
+            return;
+        }
+
+        if let Some(daily_cap) = &self.daily_cap {
+            let admission = daily_cap.admit(&mut envelop);
+            self.send_daily_cap_summary(admission.summary);
+            if !admission.admitted {
+                return;
+            }
+        }
+
+        // a priority is passed to a channel aside, so a channel never sends it to the server
+        let priority = Priority::take(&mut envelop);
+        self.channel.send_with_priority(envelop, priority);
+    }
+
+    /// Queues a summary of telemetry items suppressed by the daily cap for submission. It bypasses
+    /// processors and the cap itself, so it is not suppressed.
+    fn send_daily_cap_summary(&self, summary: Option<EventTelemetry>) {
+        if let Some(summary) = summary {
+            let mut envelop = self.convert(|context| (context, summary).into());
+            envelop.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, &self.sdk_version);
             self.channel.send(envelop);
         }
     }
 
+    /// Queues aggregated standard metrics for submission. They bypass processors, so they are not
+    /// sampled out.
+    fn send_standard_metrics(&self, metrics: Vec<Envelope>) {
+        send_standard_metrics(self.channel.as_ref(), &self.sdk_version, metrics);
+    }
+
+    /// Checks every given interval whether a period of standard metrics is over and queues its
+    /// metrics for submission, so they are not held back until the next request or dependency is
+    /// tracked. The check stops once all clones of this client are dropped. It needs a tokio
+    /// runtime, so without one periods are drained only when items are tracked or flushed.
+    fn drain_standard_metrics(&self, every: Duration) {
+        let (standard_metrics, runtime) = match (&self.standard_metrics, tokio::runtime::Handle::try_current()) {
+            (Some(standard_metrics), Ok(runtime)) => (Arc::downgrade(standard_metrics), runtime),
+            _ => return,
+        };
+        let channel: Weak<dyn TelemetryChannel> = Arc::downgrade(&self.channel);
+        let sdk_version = self.sdk_version.clone();
+
+        runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(every).await;
+                match (standard_metrics.upgrade(), channel.upgrade()) {
+                    (Some(standard_metrics), Some(channel)) => {
+                        send_standard_metrics(channel.as_ref(), &sdk_version, standard_metrics.take_expired())
+                    }
+                    _ => break,
+                }
+            }
+        });
+    }
+
+    /// Queues standard metrics aggregated so far for submission if they are extracted.
+    fn take_standard_metrics(&self) {
+        if let Some(standard_metrics) = &self.standard_metrics {
+            self.send_standard_metrics(standard_metrics.take());
+        }
+    }
+
     /// Forces all pending telemetry items to be submitted. The current task will not be blocked.
     ///
     /// # Examples
@@ -290,14 +700,48 @@ impl TelemetryClient {
     /// }
     /// ```
     pub fn flush_channel(&self) {
+        self.take_standard_metrics();
         self.channel.flush();
     }
 
+    /// Returns the number of telemetry items tracked by this client and all of its clones that are
+    /// waiting to be sent, e.g. to export the queue depth as a health metric.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// client.track_metric("telemetry_queue_depth", client.pending_items() as f64);
+    /// ```
+    pub fn pending_items(&self) -> usize {
+        self.channel.pending()
+    }
+
+    /// Discards telemetry items waiting to be sent and returns the number of them, e.g. to shed
+    /// load deliberately during an incident. Batches being sent already are not affected. Items
+    /// tracked afterwards are sent as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::TelemetryClient;
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// if client.pending_items() > 100_000 {
+    ///     let discarded = client.clear_pending();
+    ///     eprintln!("{} telemetry items discarded", discarded);
+    /// }
+    /// ```
+    pub fn clear_pending(&self) -> usize {
+        self.channel.clear_pending()
+    }
+
     /// Flushes and tears down the submission flow and closes internal channels.
     /// It blocks the current task until all pending telemetry items have been submitted and it is safe to
     /// shutdown without losing telemetry.
     /// This method consumes the value of client so it makes impossible to use a client with close
-    /// channel.
+    /// channel. The channel is shared with all clones of this client, so telemetry tracked via the
+    /// remaining clones afterwards is not sent.
     ///
     /// # Examples
     ///
@@ -311,19 +755,25 @@ impl TelemetryClient {
     /// }
     ///
     /// // wait until pending telemetry is sent at most once and tear down submission flow
-    /// client.close_channel().await;
+    /// let report = client.close_channel().await;
+    /// println!("{} telemetry items sent, {} discarded", report.flushed(), report.abandoned());
     ///
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
-    pub async fn close_channel(mut self) {
-        self.channel.close().await;
+    pub async fn close_channel(self) -> ShutdownReport {
+        self.take_standard_metrics();
+        if let Some(daily_cap) = &self.daily_cap {
+            self.send_daily_cap_summary(daily_cap.take_summary());
+        }
+        self.channel.close().await
     }
 
     /// Tears down the submission flow and closes internal channels.
     /// Any telemetry waiting to be sent is discarded. This is a more abrupt version of [`close_channel`](#method.close_channel).
     /// This method consumes the value of client so it makes impossible to use a client with close
-    /// channel.
+    /// channel. The channel is shared with all clones of this client, so telemetry tracked via the
+    /// remaining clones afterwards is not sent.
     ///
     /// This method should be used in cases when the client should be stopped. It is a separate function until
     /// `async_drop` is implemented in rust.
@@ -345,32 +795,70 @@ impl TelemetryClient {
     /// // unable to sent any telemetry after client closes its channel
     /// // client.track_event("app is stopped".to_string());
     /// ```
-    pub async fn terminate(mut self) {
-        self.channel.terminate().await;
+    pub async fn terminate(self) -> ShutdownReport {
+        self.channel.terminate().await
+    }
+
+    /// Returns a handle to tear down the submission flow shared by this client and all of its
+    /// clones as a part of an application shutdown sequence. See [`ShutdownHandle`] for details.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.channel.clone())
+    }
+}
+
+/// Queues aggregated standard metrics for submission to a given channel along with an SDK version.
+pub(crate) fn send_standard_metrics(channel: &dyn TelemetryChannel, sdk_version: &str, metrics: Vec<Envelope>) {
+    for mut metric in metrics {
+        metric.insert_tag_if_missing(tags::INTERNAL_SDK_VERSION, sdk_version);
+        channel.send(metric);
+    }
+}
+
+impl Tracker for TelemetryClient {
+    fn track_boxed(&self, event: Box<dyn IntoEnvelope>) {
+        TelemetryClient::track_boxed(self, event)
     }
 }
 
 impl From<(TelemetryConfig, TelemetryContext)> for TelemetryClient {
     fn from((config, context): (TelemetryConfig, TelemetryContext)) -> Self {
-        Self {
-            enabled: true,
-            context,
-            channel: Box::new(InMemoryChannel::new(&config)),
-        }
+        let client = Self {
+            enabled: Arc::new(AtomicBool::new(true)),
+            context: Arc::new(RwLock::new(context)),
+            processors: config.processors().clone(),
+            disabled_types: config.disabled_types().to_vec(),
+            min_severity: config.min_severity().map(Into::into),
+            url_redaction: config.url_redaction().clone(),
+            dependency_success: config.dependency_success().clone(),
+            clock: config.clock_at(Timestamping::OnTrack).cloned(),
+            sdk_version: config.sdk_version().into(),
+            standard_metrics: StandardMetrics::from_config(&config).map(Arc::new),
+            daily_cap: DailyCapGuard::from_config(&config).map(Arc::new),
+            channel: Arc::new(InMemoryChannel::new(&config)),
+        };
+        client.drain_standard_metrics(standard_metrics::DRAIN_INTERVAL);
+        client
     }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use std::sync::Arc;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
 
     use async_trait::async_trait;
-    use chrono::{DateTime, Utc};
+    use chrono::{DateTime, TimeZone, Utc};
     use crossbeam_queue::SegQueue;
     use matches::assert_matches;
 
     use super::*;
-    use crate::telemetry::{ContextTags, Properties};
+    use crate::{
+        contracts::{Base, Data},
+        daily_cap::DailyCap,
+        telemetry::{ContextTags, Properties},
+    };
 
     #[tokio::test]
     async fn it_enabled_by_default() {
@@ -380,7 +868,7 @@ pub(crate) mod tests {
 
     #[tokio::test]
     async fn it_disables_telemetry() {
-        let mut client = TelemetryClient::new("key".into());
+        let client = TelemetryClient::new("key".into());
 
         client.enabled(false);
 
@@ -392,100 +880,498 @@ pub(crate) mod tests {
         let events = Arc::new(SegQueue::default());
         let client = create_client(events.clone());
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert_eq!(events.len(), 1)
     }
 
+    #[tokio::test]
+    async fn it_reports_telemetry_submitted_when_channel_closed() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        client.track(EventTelemetry::new("test"));
+
+        let report = client.close_channel().await;
+
+        assert_eq!(report.flushed(), events.len());
+        assert_eq!(report.abandoned(), 0);
+        assert!(report.is_complete());
+    }
+
     #[tokio::test]
     async fn it_swallows_telemetry_when_disabled() {
         let events = Arc::new(SegQueue::default());
-        let mut client = create_client(events.clone());
+        let client = create_client(events.clone());
         client.enabled(false);
 
-        client.track(TestTelemetry {});
+        client.track(EventTelemetry::new("test"));
 
         assert!(events.is_empty())
     }
 
     #[tokio::test]
-    async fn it_creates_client_with_default_tags() {
-        let client = TelemetryClient::new("instrumentation".into());
+    async fn it_applies_processors_to_telemetry() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .processor(|envelope: &mut Envelope, _: &ProcessingContext| {
+                envelope.name = "processed".into();
+                true
+            })
+            .processor(|_: &mut Envelope, context: &ProcessingContext| context.integration().is_some())
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
 
-        let tags = client.context().tags();
-        assert_matches!(tags.internal().sdk_version(), Some(version) if version.starts_with("rust"));
-        assert_matches!(tags.device().os_version(), Some(_))
+        client.track(EventTelemetry::new("test"));
+        client.track_with_context(EventTelemetry::new("test"), ProcessingContext::new(Some("test")));
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.pop().unwrap().name, "processed");
     }
 
     #[tokio::test]
-    async fn it_does_not_fail_with_tokio() {
-        let client = TelemetryClient::new("instrumentation".into());
-        assert!(client.is_enabled())
+    async fn it_extracts_standard_metrics_from_items_sampled_out() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .extract_standard_metrics(true)
+            .processor(|envelope: &mut Envelope, _: &ProcessingContext| {
+                TelemetryKind::of(envelope) != Some(TelemetryKind::Request)
+            })
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        crate::time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let uri: Uri = "https://example.com/".parse().unwrap();
+        client.track_request("GET /".into(), uri.clone(), Duration::from_millis(100), "200");
+        client.track_request("GET /".into(), uri, Duration::from_millis(200), "200");
+        assert!(events.is_empty());
+
+        crate::time::set(Utc.ymd(2023, 5, 1).and_hms(12, 1, 0));
+        client.track_event("event");
+
+        assert_eq!(events.len(), 2);
+        let metric = events.pop().unwrap();
+        assert_eq!(metric.tags.unwrap()[tags::INTERNAL_SDK_VERSION], config.sdk_version());
+        match metric.data {
+            Some(Base::Data(Data::MetricData(data))) => {
+                assert_eq!(data.metrics[0].count, Some(2));
+                assert_eq!(data.properties.unwrap()["_MS.MetricId"], "requests/duration");
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(events.pop().unwrap().name, "Microsoft.ApplicationInsights.Event");
     }
 
-    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
-        let config = TelemetryConfig::new("instrumentation".into());
-        TelemetryClient::create(&config, TestChannel::new(events))
+    #[tokio::test]
+    async fn it_sends_standard_metrics_once_period_is_over_without_tracking_more_items() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .extract_standard_metrics(true)
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.drain_standard_metrics(Duration::from_millis(10));
+
+        crate::time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let uri: Uri = "https://example.com/".parse().unwrap();
+        client.track_request("GET /".into(), uri, Duration::from_millis(100), "200");
+        events.pop().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(events.is_empty());
+
+        crate::time::set(Utc.ymd(2023, 5, 1).and_hms(12, 1, 0));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(events.len(), 1);
+        let metric = events.pop().unwrap();
+        assert_eq!(metric.name, "Microsoft.ApplicationInsights.Metric");
+        assert_eq!(metric.tags.unwrap()[tags::INTERNAL_SDK_VERSION], config.sdk_version());
+        crate::time::reset();
     }
 
-    pub(crate) struct TestTelemetry {}
+    #[tokio::test]
+    async fn it_builds_client_with_custom_channel_context_and_processors() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .processor(|envelope: &mut Envelope, _: &ProcessingContext| {
+                envelope.name = "configured".into();
+                true
+            })
+            .build();
+        let mut tags = ContextTags::default();
+        tags.set_operation_name("seeded");
+        let context = TelemetryContext::new("seeded".into(), tags, Properties::default());
 
-    impl Telemetry for TestTelemetry {
-        fn timestamp(&self) -> DateTime<Utc> {
-            unimplemented!()
-        }
+        let client = TelemetryClient::builder(config)
+            .custom(TestChannel::new(events.clone()))
+            .context(context)
+            .processor(|envelope: &mut Envelope, _: &ProcessingContext| {
+                envelope.name.push_str(" and built");
+                true
+            })
+            .build();
+        client.track_event("event");
 
-        fn properties(&self) -> &Properties {
-            unimplemented!()
-        }
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.name, "configured and built");
+        assert_eq!(envelope.i_key, Some("seeded".into()));
+        assert_eq!(envelope.tags.unwrap()[tags::OPERATION_NAME], "seeded");
+    }
+
+    #[tokio::test]
+    async fn it_drops_telemetry_of_disabled_types() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .disable_types(&[TelemetryKind::Trace])
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        client.track_trace("trace", SeverityLevel::Information);
+        client.track_event("event");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.pop().unwrap().name, "Microsoft.ApplicationInsights.Event");
+    }
+
+    #[tokio::test]
+    async fn it_drops_traces_and_exceptions_below_min_severity() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .min_severity(SeverityLevel::Warning)
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        client.track_trace("verbose", SeverityLevel::Verbose);
+        client.track_trace("warning", SeverityLevel::Warning);
+        client.track_event("event");
+
+        let names: Vec<_> = std::iter::from_fn(|| events.pop()).map(|event| event.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Microsoft.ApplicationInsights.Message",
+                "Microsoft.ApplicationInsights.Event"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_suppresses_traces_over_daily_cap_and_reports_summary_next_day() {
+        crate::time::set(Utc.ymd(2019, 1, 2).and_hms_milli(23, 59, 0, 0));
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_cap(DailyCap::items(1))
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        client.track_trace("first", SeverityLevel::Information);
+        client.track_trace("second", SeverityLevel::Information);
+        client.track_event("event");
+
+        crate::time::set(Utc.ymd(2019, 1, 3).and_hms_milli(0, 0, 1, 0));
+        client.track_trace("third", SeverityLevel::Information);
+        crate::time::reset();
 
-        fn properties_mut(&mut self) -> &mut Properties {
-            unimplemented!()
+        let names: Vec<_> = std::iter::from_fn(|| events.pop())
+            .map(|envelope| match envelope.data {
+                Some(Base::Data(Data::MessageData(data))) => data.message,
+                Some(Base::Data(Data::EventData(data))) => data.name,
+                data => panic!("unexpected data: {:?}", data),
+            })
+            .collect();
+        assert_eq!(names, vec!["first", "event", "Daily cap exceeded", "third"]);
+    }
+
+    #[tokio::test]
+    async fn it_submits_boxed_telemetry_of_different_types() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let pending: Vec<Box<dyn IntoEnvelope>> = vec![
+            Box::new(EventTelemetry::new("event")),
+            Box::new(TraceTelemetry::new("trace", SeverityLevel::Information)),
+        ];
+        for telemetry in pending {
+            client.track_boxed(telemetry);
         }
 
-        fn tags(&self) -> &ContextTags {
-            unimplemented!()
+        assert_eq!(events.pop().unwrap().name, "Microsoft.ApplicationInsights.Event");
+        assert_eq!(events.pop().unwrap().name, "Microsoft.ApplicationInsights.Message");
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_submits_prepared_envelope_later() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .processor(|envelope: &mut Envelope, _: &ProcessingContext| {
+                envelope.name = "processed".into();
+                true
+            })
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+        client.context_mut().tags_mut().cloud_mut().set_role("before".into());
+
+        let envelope = client.prepare(EventTelemetry::new("event"));
+        client.context_mut().tags_mut().cloud_mut().set_role("after".into());
+        assert!(events.is_empty());
+
+        let stored = serde_json::to_string(&envelope).unwrap();
+        client.track_envelope(serde_json::from_str(&stored).unwrap());
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.name, "processed");
+        assert_eq!(envelope.tags.unwrap().get("ai.cloud.role"), Some(&"before".to_string()));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_submits_relayed_envelope_without_client_context() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        client.context_mut().tags_mut().cloud_mut().set_role("relay".into());
+
+        let payload = r#"{"name":"Microsoft.ApplicationInsights.Event","time":"2019-01-02T03:04:05.800Z","iKey":"device","tags":{"ai.cloud.role":"device","ai.internal.sdkVersion":"c:1.2.0"}}"#;
+        let envelope: Envelope = serde_json::from_str(payload).unwrap();
+        client.track_envelope(envelope.clone());
+
+        assert_eq!(events.pop(), Some(envelope));
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_reports_configured_sdk_version_of_envelope_without_one() {
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .sdk_version("rust-vendored:1.0.0")
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        client.track_event("event");
+        client.track_envelope(Envelope::default());
+
+        for _ in 0..2 {
+            let tags = events.pop().and_then(|envelope| envelope.tags).unwrap_or_default();
+            assert_eq!(
+                tags.get(tags::INTERNAL_SDK_VERSION).map(String::as_str),
+                Some("rust-vendored:1.0.0")
+            );
         }
+    }
 
-        fn tags_mut(&mut self) -> &mut ContextTags {
-            unimplemented!()
+    #[tokio::test]
+    async fn it_stamps_telemetry_with_time_of_custom_clock() {
+        struct SyncedClock;
+
+        impl clock::Clock for SyncedClock {
+            fn now(&self) -> Option<DateTime<Utc>> {
+                Some(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600))
+            }
         }
+
+        let events = Arc::new(SegQueue::default());
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .clock(SyncedClock)
+            .build();
+        let client = TelemetryClient::create(&config, TestChannel::new(events.clone()));
+
+        crate::time::set(Utc.ymd(1970, 1, 1).and_hms_milli(0, 0, 30, 0));
+        let event = EventTelemetry::new("event");
+        crate::time::set(Utc.ymd(1970, 1, 1).and_hms_milli(0, 0, 40, 0));
+        client.track(event);
+        crate::time::reset();
+
+        assert_eq!(events.pop().unwrap().time, "2019-01-02T03:03:55.600Z");
+    }
+
+    #[tokio::test]
+    async fn it_customizes_combined_tags_and_properties() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        client.context_mut().tags_mut().cloud_mut().set_role("server".into());
+        client
+            .context_mut()
+            .properties_mut()
+            .insert("internal".into(), "value".into());
+
+        let mut telemetry = EventTelemetry::new("event");
+        telemetry.properties_mut().insert("item".into(), "value".into());
+        client.track_with(telemetry, |tags, properties| {
+            assert_eq!(tags.cloud().role(), Some("server"));
+            tags.cloud_mut().set_role("tenant".into());
+            properties.remove("internal");
+        });
+
+        let envelope = events.pop().unwrap();
+        assert_eq!(
+            envelope.tags.as_ref().and_then(|tags| tags.get("ai.cloud.role")),
+            Some(&"tenant".to_string())
+        );
+        assert_eq!(property_names(envelope), vec!["item"]);
+    }
+
+    #[tokio::test]
+    async fn it_shares_channel_between_clones() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let cloned = client.clone();
+
+        client.track(EventTelemetry::new("test"));
+        cloned.track(EventTelemetry::new("test"));
+
+        assert_eq!(events.len(), 2)
+    }
+
+    #[tokio::test]
+    async fn it_shares_enabled_flag_between_clones() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        let cloned = client.clone();
+
+        client.enabled(false);
+        cloned.track(EventTelemetry::new("test"));
+
+        assert!(!cloned.is_enabled());
+        assert!(events.is_empty())
+    }
+
+    #[tokio::test]
+    async fn it_overrides_context_of_child_client() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+        client
+            .context_mut()
+            .properties_mut()
+            .insert("app".into(), "server".into());
+
+        let child = client.child_with(|context| {
+            context.properties_mut().insert("subsystem".into(), "billing".into());
+        });
+        client
+            .context_mut()
+            .properties_mut()
+            .insert("late".into(), "parent".into());
+
+        child.track_event("child");
+        client.track_event("parent");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(property_names(events.pop().unwrap()), vec!["app", "subsystem"]);
+        assert_eq!(property_names(events.pop().unwrap()), vec!["app", "late"]);
     }
 
-    #[derive(Clone)]
-    pub(crate) struct TestData;
+    #[tokio::test]
+    async fn it_correlates_telemetry_with_ambient_operation() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut operation = client.context().clone();
+        operation.operation_mut().set_id("operation".into());
+        crate::ambient::with_operation(operation, async { client.track_event("inside") }).await;
+        client.track_event("outside");
 
-    impl From<(TelemetryContext, TestTelemetry)> for Envelope {
-        fn from((_, _): (TelemetryContext, TestTelemetry)) -> Self {
-            Envelope::default()
+        let operation_id = |envelope: Envelope| envelope.tags.unwrap_or_default().get("ai.operation.id").cloned();
+        assert_eq!(events.len(), 2);
+        assert_eq!(operation_id(events.pop().unwrap()), Some("operation".into()));
+        assert_eq!(operation_id(events.pop().unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn it_passes_priority_of_item_to_channel_aside() {
+        let events = Arc::new(SegQueue::default());
+        let client = create_client(events.clone());
+
+        let mut event = EventTelemetry::new("important");
+        event.tags_mut().set_priority(Priority::High);
+        client.track(event);
+
+        let tags = events.pop().unwrap().tags.unwrap_or_default();
+        assert!(!tags.contains_key("ai.internal.priority"));
+    }
+
+    fn property_names(envelope: Envelope) -> Vec<String> {
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => data.properties.unwrap_or_default().keys().cloned().collect(),
+            _ => panic!("event expected"),
         }
     }
 
+    #[tokio::test]
+    async fn it_shares_context_between_clones() {
+        let client = TelemetryClient::new("instrumentation".into());
+        let cloned = client.clone();
+
+        client
+            .context_mut()
+            .tags_mut()
+            .cloud_mut()
+            .set_role("rust_server".into());
+
+        let context = cloned.context();
+        assert_eq!(context.tags().cloud().role(), Some("rust_server"))
+    }
+
+    #[tokio::test]
+    async fn it_creates_client_with_default_tags() {
+        let client = TelemetryClient::new("instrumentation".into());
+
+        let context = client.context();
+        let tags = context.tags();
+        assert_matches!(tags.internal().sdk_version(), Some(version) if version.starts_with("rust"));
+        assert_matches!(tags.device().os_version(), Some(_))
+    }
+
+    #[tokio::test]
+    async fn it_does_not_fail_with_tokio() {
+        let client = TelemetryClient::new("instrumentation".into());
+        assert!(client.is_enabled())
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+
+    /// Collects telemetry items into a queue as if they were sent right away.
     pub(crate) struct TestChannel {
         events: Arc<SegQueue<Envelope>>,
+        sent: AtomicUsize,
     }
 
     impl TestChannel {
         pub(crate) fn new(events: Arc<SegQueue<Envelope>>) -> Self {
-            Self { events }
+            Self {
+                events,
+                sent: AtomicUsize::default(),
+            }
         }
     }
 
     #[async_trait]
     impl TelemetryChannel for TestChannel {
         fn send(&self, envelop: Envelope) {
+            self.sent.fetch_add(1, Ordering::SeqCst);
             self.events.push(envelop);
         }
 
-        fn flush(&self) {
-            unimplemented!()
-        }
+        fn flush(&self) {}
 
-        async fn close(&mut self) {
-            unimplemented!()
+        async fn close(&self) -> ShutdownReport {
+            ShutdownReport::new(self.sent.load(Ordering::SeqCst), 0, None)
         }
 
-        async fn terminate(&mut self) {}
+        async fn terminate(&self) -> ShutdownReport {
+            ShutdownReport::default()
+        }
     }
 }
 
@@ -0,0 +1,63 @@
+use std::{future::Future, sync::Arc};
+
+use crate::channel::{ShutdownReport, TelemetryChannel};
+
+/// A handle to tear down the submission flow of a [`TelemetryClient`](crate::TelemetryClient)
+/// as a part of an application shutdown sequence.
+///
+/// The handle doesn't keep a client itself, so it can be handed over to a shutdown orchestration
+/// while clients are still in use. It is cheap to clone, and only the first request to close or
+/// terminate the submission flow takes effect.
+///
+/// # Examples
+///
+/// Flush pending telemetry once a shutdown signal is received, for instance the future returned by
+/// `SubsystemHandle::on_shutdown_requested` of `tokio-graceful-shutdown` or
+/// `CancellationToken::cancelled` of `tokio-util`.
+///
+/// ```rust, no_run
+/// # async fn run(shutdown_requested: impl std::future::Future<Output = ()> + Send + 'static) {
+/// use appinsights::TelemetryClient;
+///
+/// let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let shutdown = client.shutdown_handle();
+///
+/// let telemetry = tokio::spawn(shutdown.close_on(shutdown_requested));
+///
+/// client.track_event("application started");
+///
+/// // wait until pending telemetry is sent after shutdown signal
+/// telemetry.await.unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    channel: Arc<dyn TelemetryChannel>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(channel: Arc<dyn TelemetryChannel>) -> Self {
+        Self { channel }
+    }
+
+    /// Flushes and tears down the submission flow. It blocks the current task until all pending
+    /// telemetry items have been submitted at most once. Telemetry tracked by clients afterwards is not sent.
+    pub async fn close(self) -> ShutdownReport {
+        self.channel.close().await
+    }
+
+    /// Tears down the submission flow and discards any telemetry waiting to be sent.
+    pub async fn terminate(self) -> ShutdownReport {
+        self.channel.terminate().await
+    }
+
+    /// Waits for a shutdown signal, then flushes and tears down the submission flow.
+    /// See [`close`](#method.close) for details.
+    pub async fn close_on<F>(self, signal: F) -> ShutdownReport
+    where
+        F: Future<Output = ()>,
+    {
+        signal.await;
+        self.close().await
+    }
+}
@@ -0,0 +1,142 @@
+//! Wall clock time of telemetry items.
+//!
+//! Telemetry items are timestamped by the system clock when they are created. Some platforms,
+//! like embedded targets, have no reliable wall clock: the system time starts at an arbitrary
+//! point on boot and only advances monotonically. Such applications can supply the current time
+//! from an external source, e.g. GPS or a host sync, with a custom [`Clock`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder).
+//!
+//! The time elapsed between the creation of a telemetry item and the moment it is stamped is
+//! measured by the system clock and subtracted from the time the [`Clock`] reports, so items keep
+//! their relative order and spacing. Depending on [`Timestamping`] items are stamped when they are
+//! tracked or when they are about to be sent, so items tracked before the external time source
+//! becomes available still get a correct timestamp.
+//!
+//! The channel uses the same clock to align
+//! [submission intervals](crate::TelemetryConfig::is_interval_aligned), to drop items older than
+//! [time to live](crate::TelemetryConfig::time_to_live) and to wait until the time the server
+//! asked to retry after when it throttles submissions. A clock that reports a
+//! fixed time makes tests of an application deterministic without mutating process-global state,
+//! so such tests can run in parallel.
+//!
+//! ```rust
+//! use std::sync::{Arc, RwLock};
+//!
+//! use appinsights::{
+//!     clock::{Clock, Timestamping},
+//!     TelemetryConfig,
+//! };
+//! use chrono::{DateTime, Utc};
+//!
+//! #[derive(Clone, Default)]
+//! struct GpsClock(Arc<RwLock<Option<DateTime<Utc>>>>);
+//!
+//! impl Clock for GpsClock {
+//!     fn now(&self) -> Option<DateTime<Utc>> {
+//!         // a real implementation would extrapolate the last fix
+//!         *self.0.read().unwrap()
+//!     }
+//! }
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .clock(GpsClock::default())
+//!     .timestamping(Timestamping::OnTransmission)
+//!     .build();
+//! ```
+use std::{fmt::Debug, sync::Arc};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+
+use crate::{contracts::Envelope, shared::Shared, time};
+
+/// A source of wall clock time used to timestamp telemetry items.
+pub trait Clock: Send + Sync {
+    /// Returns the current time or `None` if it is not known yet, e.g. before the first sync with
+    /// an external time source.
+    fn now(&self) -> Option<DateTime<Utc>>;
+}
+
+/// Defines when telemetry items are stamped with the time of a custom [`Clock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timestamping {
+    /// Items are stamped when they are tracked. Items tracked while the clock does not know the
+    /// time keep the time of the system clock.
+    #[default]
+    OnTrack,
+
+    /// Items are stamped when they are collected to be sent to the server. Items wait in the
+    /// queue until the clock knows the time.
+    OnTransmission,
+}
+
+/// A custom clock shared between a client and its channel. It makes a clock comparable and
+/// printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedClock(Shared<dyn Clock>);
+
+impl SharedClock {
+    pub(crate) fn new(clock: impl Clock + 'static) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+        Self(clock.into())
+    }
+
+    /// Returns the difference between the time of this clock and the system clock if this clock
+    /// knows the time.
+    pub(crate) fn offset(&self) -> Option<Duration> {
+        self.0.now().map(|now| now - time::now())
+    }
+
+    /// Returns the current time of this clock or the system clock if this clock does not know it.
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.0.now().unwrap_or_else(time::now)
+    }
+}
+
+/// Shifts the time of a telemetry item measured by the system clock by a given offset.
+pub(crate) fn shift(envelope: &mut Envelope, offset: Duration) {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(&envelope.time) {
+        let timestamp = timestamp.with_timezone(&Utc) + offset;
+        envelope.time = timestamp.to_rfc3339_opts(SecondsFormat::Millis, true);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    struct FixedClock(Option<DateTime<Utc>>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> Option<DateTime<Utc>> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn it_shifts_time_of_telemetry_item_by_clock_offset() {
+        time::set(Utc.ymd(1970, 1, 1).and_hms_milli(0, 10, 0, 0));
+        let clock = SharedClock::new(FixedClock(Some(Utc.ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600))));
+
+        let mut envelope = Envelope {
+            time: "1970-01-01T00:09:30.000Z".into(),
+            ..Envelope::default()
+        };
+        shift(&mut envelope, clock.offset().unwrap());
+
+        assert_eq!(envelope.time, "2019-01-02T03:03:35.600Z");
+        time::reset();
+    }
+
+    #[test]
+    fn it_has_no_offset_when_clock_does_not_know_time() {
+        time::set(Utc.ymd(1970, 1, 1).and_hms_milli(0, 10, 0, 0));
+        let clock = SharedClock::new(FixedClock(None));
+
+        assert_eq!(clock.offset(), None);
+        assert_eq!(clock.now(), Utc.ymd(1970, 1, 1).and_hms_milli(0, 10, 0, 0));
+        time::reset();
+    }
+}
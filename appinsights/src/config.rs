@@ -1,5 +1,45 @@
 //! Module for telemetry client configuration.
-use std::time::Duration;
+use std::{
+    fmt::{Debug, Display, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+use appinsights_core::ContextConfig;
+use http::{HeaderValue, Uri};
+use log::LevelFilter;
+#[cfg(feature = "reqwest")]
+use reqwest::{dns::Resolve, ClientBuilder};
+
+#[cfg(feature = "agent")]
+use crate::agent::AgentEndpoint;
+#[cfg(feature = "metrics")]
+use crate::pipeline_metrics::{MetricsRecorder, SharedMetricsRecorder};
+#[cfg(feature = "test-util")]
+use crate::test::FaultScript;
+use crate::{
+    clock::{Clock, SharedClock, Timestamping},
+    contracts::Envelope,
+    daily_cap::DailyCap,
+    dead_letter::{DeadLetterSink, SharedDeadLetterSink},
+    processor::{DependencySuccess, Processors, RequestSuccess, TelemetryProcessor, UrlRedaction},
+    queue::{DropPolicy, Priorities, SharedDropPolicy},
+    scheduler::{Scheduler, SharedScheduler},
+    sequence::{SequenceStore, SharedSequenceStore},
+    shared::Shared,
+    telemetry::{Priority, SeverityLevel, TelemetryKind, DEFAULT_MAX_CHAIN_DEPTH, DEFAULT_MAX_STACK_FRAMES},
+    throttle::{SharedThrottleStore, ThrottleStore},
+    worker_task::{ScheduledTask, WorkerTask},
+};
+
+/// Version of the SDK reported with telemetry items unless configured otherwise.
+const DEFAULT_SDK_VERSION: &str = concat!("rust:", env!("CARGO_PKG_VERSION"));
+
+/// Value of the `User-Agent` header unless configured otherwise.
+const DEFAULT_USER_AGENT: &str = concat!("appinsights-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Maximum number of bytes of an error response body read from the server unless configured otherwise.
+const DEFAULT_MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
 
 /// Configuration data used to initialize a new [`TelemetryClient`](../struct.TelemetryClient.html) with.
 ///
@@ -30,6 +70,145 @@ pub struct TelemetryConfig {
 
     /// Maximum time to wait until send a batch of telemetry.
     interval: Duration,
+
+    /// Maximum random delay added to the interval.
+    interval_jitter: Option<Duration>,
+
+    /// Whether submissions are aligned to wall-clock multiples of the interval.
+    interval_aligned: bool,
+
+    /// Whether telemetry items are sent only when an application flushes them.
+    manual_flush_only: bool,
+
+    /// Maximum age of a telemetry item after which it is dropped instead of being sent.
+    time_to_live: Option<Duration>,
+
+    /// Maximum age of telemetry items of given categories after which they are not sent again
+    /// when a submission fails.
+    max_retry_ages: Vec<(TelemetryKind, Duration)>,
+
+    /// Maximum number of batches being sent to the server at the same time.
+    max_concurrent_transmissions: usize,
+
+    /// Maximum number of telemetry items sent to the server in a single batch.
+    max_batch_size: usize,
+
+    /// Maximum size of a serialized batch in bytes.
+    max_batch_bytes: Option<usize>,
+
+    /// Time to wait between batches when a queue holds more items than fit into a single batch.
+    drain_pace: Duration,
+
+    /// Maximum number of telemetry items waiting to be sent.
+    max_queue_size: Option<usize>,
+
+    /// Policy that selects telemetry items to drop when a queue holds more items than allowed.
+    drop_policy: Option<SharedDropPolicy>,
+
+    /// Priorities of telemetry items of categories that override the default ones.
+    priorities: Priorities,
+
+    /// Maximum time to wait for the server to respond to a single submission.
+    request_timeout: Option<Duration>,
+
+    /// Maximum number of bytes of an error response body read from the server.
+    max_error_body_bytes: usize,
+
+    /// Maximum number of chained exceptions submitted with an exception telemetry item.
+    max_exception_chain_depth: usize,
+
+    /// Maximum number of stack frames submitted for each exception.
+    max_exception_stack_frames: usize,
+
+    /// Maximum severity of SDK self-diagnostics events to report.
+    internal_log_level: LevelFilter,
+
+    /// Instrumentation key of a resource to submit SDK self-diagnostics events to.
+    diagnostics_i_key: Option<String>,
+
+    /// Version of the SDK reported with every telemetry item.
+    sdk_version: String,
+
+    /// Version of the application reported with every telemetry item.
+    application_version: Option<String>,
+
+    /// Value of the `User-Agent` header sent with every submission.
+    user_agent: String,
+
+    /// Custom DNS resolver used to resolve the endpoint host.
+    #[cfg(feature = "reqwest")]
+    dns_resolver: Option<Shared<dyn Resolve>>,
+
+    /// HTTP client of an application submissions are sent with, or a customization of a HTTP
+    /// client created for submissions.
+    #[cfg(feature = "reqwest")]
+    http_client: Option<HttpClientSource>,
+
+    /// Local agent telemetry items are forwarded to instead of the endpoint.
+    #[cfg(feature = "agent")]
+    agent_endpoint: Option<AgentEndpoint>,
+
+    /// Recorder of counters and histograms of the submission pipeline.
+    #[cfg(feature = "metrics")]
+    metrics_recorder: Option<SharedMetricsRecorder>,
+
+    /// Handler of telemetry items the server rejected and which are not going to be sent again.
+    rejection_handler: Option<RejectionHandler>,
+
+    /// Processors to apply to telemetry items before they are queued for submission.
+    processors: Processors,
+
+    /// Categories of telemetry items that are dropped instead of being submitted.
+    disabled_types: Vec<TelemetryKind>,
+
+    /// Minimum severity of traces and exceptions to submit.
+    min_severity: Option<SeverityLevel>,
+
+    /// Redaction of credentials and query strings from URLs of tracked requests and dependencies.
+    url_redaction: UrlRedaction,
+
+    /// Criteria that decide whether calls to dependencies succeeded based on their result codes.
+    dependency_success: DependencySuccess,
+
+    /// Criteria that decide whether requests succeeded based on their response codes.
+    request_success: RequestSuccess,
+
+    /// Custom source of wall clock time to timestamp telemetry items with.
+    clock: Option<SharedClock>,
+
+    /// When telemetry items are stamped with the time of a custom clock.
+    timestamping: Timestamping,
+
+    /// Whether times of telemetry items are corrected by the difference to the server clock.
+    clock_skew_corrected: bool,
+
+    /// Custom timer a submission worker waits on before it sends or retries telemetry items.
+    scheduler: Option<SharedScheduler>,
+
+    /// Storage of the highest sequence number of a batch acknowledged by the server.
+    sequence_store: Option<SharedSequenceStore>,
+
+    /// Storage of the time until which the server throttles submissions.
+    throttle_store: Option<SharedThrottleStore>,
+
+    /// Storage of telemetry items that could not be delivered to the server.
+    dead_letter_sink: Option<SharedDeadLetterSink>,
+
+    /// Custom tasks run on the schedule of a submission worker.
+    worker_tasks: Vec<ScheduledTask>,
+
+    /// Period of an event that describes the effective configuration and the state of a pipeline.
+    snapshot_interval: Option<Duration>,
+
+    /// Whether standard metrics are pre-aggregated from tracked requests and dependencies.
+    standard_metrics_extracted: bool,
+
+    /// A maximum volume of telemetry submitted during a UTC day before low-priority items are suppressed.
+    daily_cap: Option<DailyCap>,
+
+    /// Faults injected into submissions to test resilience of an application.
+    #[cfg(feature = "test-util")]
+    fault_injection: Option<FaultScript>,
 }
 
 impl TelemetryConfig {
@@ -57,6 +236,325 @@ impl TelemetryConfig {
     pub fn interval(&self) -> Duration {
         self.interval
     }
+
+    /// Returns maximum random delay added to the interval.
+    pub fn interval_jitter(&self) -> Option<Duration> {
+        self.interval_jitter
+    }
+
+    /// Returns whether submissions are aligned to wall-clock multiples of the interval.
+    pub fn is_interval_aligned(&self) -> bool {
+        self.interval_aligned
+    }
+
+    /// Returns whether telemetry items are sent only when an application flushes them.
+    pub fn is_manual_flush_only(&self) -> bool {
+        self.manual_flush_only
+    }
+
+    /// Returns maximum age of a telemetry item after which it is dropped instead of being sent.
+    pub fn time_to_live(&self) -> Option<Duration> {
+        self.time_to_live
+    }
+
+    /// Returns maximum age of telemetry items of a given category after which they are not sent
+    /// again when a submission fails. Returns `None` if items of this category are always retried.
+    pub fn max_retry_age(&self, kind: TelemetryKind) -> Option<Duration> {
+        self.max_retry_ages
+            .iter()
+            .find(|(retry_kind, _)| *retry_kind == kind)
+            .map(|(_, age)| *age)
+    }
+
+    /// Returns maximum ages of telemetry items of categories that are not always retried.
+    pub(crate) fn max_retry_ages(&self) -> &[(TelemetryKind, Duration)] {
+        &self.max_retry_ages
+    }
+
+    /// Returns maximum number of batches being sent to the server at the same time.
+    pub fn max_concurrent_transmissions(&self) -> usize {
+        self.max_concurrent_transmissions
+    }
+
+    /// Returns maximum number of telemetry items sent to the server in a single batch.
+    pub fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    /// Returns maximum size of a serialized batch in bytes.
+    pub fn max_batch_bytes(&self) -> Option<usize> {
+        self.max_batch_bytes
+    }
+
+    /// Returns time to wait between batches when a queue holds more items than fit into a single batch.
+    pub fn drain_pace(&self) -> Duration {
+        self.drain_pace
+    }
+
+    /// Returns maximum number of telemetry items waiting to be sent.
+    pub fn max_queue_size(&self) -> Option<usize> {
+        self.max_queue_size
+    }
+
+    /// Returns maximum time to wait for the server to respond to a single submission.
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    /// Returns maximum number of bytes of an error response body read from the server.
+    pub fn max_error_body_bytes(&self) -> usize {
+        self.max_error_body_bytes
+    }
+
+    /// Returns maximum number of chained exceptions submitted with an exception telemetry item.
+    pub fn max_exception_chain_depth(&self) -> usize {
+        self.max_exception_chain_depth
+    }
+
+    /// Returns maximum number of stack frames submitted for each exception.
+    pub fn max_exception_stack_frames(&self) -> usize {
+        self.max_exception_stack_frames
+    }
+
+    /// Returns maximum severity of SDK self-diagnostics events to report.
+    pub fn internal_log_level(&self) -> LevelFilter {
+        self.internal_log_level
+    }
+
+    /// Returns instrumentation key of a resource to submit SDK self-diagnostics events to.
+    pub fn diagnostics_i_key(&self) -> Option<&str> {
+        self.diagnostics_i_key.as_deref()
+    }
+
+    /// Returns version of the SDK reported with every telemetry item.
+    pub fn sdk_version(&self) -> &str {
+        &self.sdk_version
+    }
+
+    /// Returns a version of the application reported with every telemetry item.
+    pub fn application_version(&self) -> Option<&str> {
+        self.application_version.as_deref()
+    }
+
+    /// Returns value of the `User-Agent` header sent with every submission.
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// Returns a local agent telemetry items are forwarded to instead of the endpoint if any.
+    #[cfg(feature = "agent")]
+    pub fn agent_endpoint(&self) -> Option<&AgentEndpoint> {
+        self.agent_endpoint.as_ref()
+    }
+
+    /// Returns a recorder of counters and histograms of the submission pipeline if any.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn metrics_recorder(&self) -> Option<&SharedMetricsRecorder> {
+        self.metrics_recorder.as_ref()
+    }
+
+    /// Returns the `User-Agent` header value. Falls back to the default one when a configured value
+    /// is not a valid header value.
+    pub(crate) fn user_agent_header(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.user_agent).unwrap_or_else(|_| HeaderValue::from_static(DEFAULT_USER_AGENT))
+    }
+
+    /// Returns a custom DNS resolver of the endpoint host if any.
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn dns_resolver(&self) -> Option<Arc<dyn Resolve>> {
+        self.dns_resolver.clone().map(Arc::from)
+    }
+
+    /// Returns a HTTP client of an application to send submissions with, if any.
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn shared_http_client(&self) -> Option<&reqwest::Client> {
+        match &self.http_client {
+            Some(HttpClientSource::Shared(client)) => Some(client),
+            _ => None,
+        }
+    }
+
+    /// Applies a customization if any to a HTTP client builder.
+    #[cfg(feature = "reqwest")]
+    pub(crate) fn configure_http_client(&self, builder: ClientBuilder) -> ClientBuilder {
+        match &self.http_client {
+            Some(HttpClientSource::Custom(configure)) => configure(builder),
+            _ => builder,
+        }
+    }
+
+    /// Returns a handler of telemetry items the server rejected and which are not going to be sent again.
+    pub(crate) fn rejection_handler(&self) -> Option<&RejectionHandler> {
+        self.rejection_handler.as_ref()
+    }
+
+    /// Returns processors to apply to telemetry items before they are queued for submission.
+    pub(crate) fn processors(&self) -> &Processors {
+        &self.processors
+    }
+
+    /// Returns categories of telemetry items that are dropped instead of being submitted.
+    pub fn disabled_types(&self) -> &[TelemetryKind] {
+        &self.disabled_types
+    }
+
+    /// Returns a minimum severity of traces and exceptions to submit. Less severe ones are dropped.
+    pub fn min_severity(&self) -> Option<SeverityLevel> {
+        self.min_severity
+    }
+
+    /// Returns a redaction of credentials and query strings from URLs of tracked requests and
+    /// dependencies.
+    pub fn url_redaction(&self) -> &UrlRedaction {
+        &self.url_redaction
+    }
+
+    /// Returns when telemetry items are stamped with the time of a custom clock.
+    pub fn timestamping(&self) -> Timestamping {
+        self.timestamping
+    }
+
+    /// Returns whether times of telemetry items are corrected by the difference between the local
+    /// clock and the server clock when they are sent.
+    pub fn is_clock_skew_corrected(&self) -> bool {
+        self.clock_skew_corrected
+    }
+
+    /// Returns criteria that decide whether calls to dependencies succeeded based on their result
+    /// codes.
+    pub(crate) fn dependency_success(&self) -> &DependencySuccess {
+        &self.dependency_success
+    }
+
+    /// Returns criteria that decide whether requests succeeded based on their response codes.
+    pub(crate) fn request_success(&self) -> &RequestSuccess {
+        &self.request_success
+    }
+
+    /// Returns a custom source of wall clock time to timestamp telemetry items with.
+    pub(crate) fn clock(&self) -> Option<&SharedClock> {
+        self.clock.as_ref()
+    }
+
+    /// Returns a custom clock if telemetry items are stamped with its time at a given moment.
+    pub(crate) fn clock_at(&self, timestamping: Timestamping) -> Option<&SharedClock> {
+        self.clock.as_ref().filter(|_| self.timestamping == timestamping)
+    }
+
+    /// Returns a custom timer a submission worker waits on before it sends or retries telemetry
+    /// items.
+    pub(crate) fn scheduler(&self) -> Option<&SharedScheduler> {
+        self.scheduler.as_ref()
+    }
+
+    /// Returns a policy that selects telemetry items to drop when a queue holds more items than
+    /// allowed.
+    pub(crate) fn drop_policy(&self) -> Option<&SharedDropPolicy> {
+        self.drop_policy.as_ref()
+    }
+
+    /// Returns a priority of telemetry items of a given category.
+    pub fn priority(&self, kind: TelemetryKind) -> Priority {
+        self.priorities.of_kind(kind)
+    }
+
+    /// Returns priorities of telemetry items of categories that override the default ones.
+    pub(crate) fn priorities(&self) -> &Priorities {
+        &self.priorities
+    }
+
+    /// Returns a storage of the highest sequence number of a batch acknowledged by the server.
+    pub(crate) fn sequence_store(&self) -> Option<&SharedSequenceStore> {
+        self.sequence_store.as_ref()
+    }
+
+    /// Returns a storage of the time until which the server throttles submissions.
+    pub(crate) fn throttle_store(&self) -> Option<&SharedThrottleStore> {
+        self.throttle_store.as_ref()
+    }
+
+    /// Returns a storage of telemetry items that could not be delivered to the server.
+    pub(crate) fn dead_letter_sink(&self) -> Option<&SharedDeadLetterSink> {
+        self.dead_letter_sink.as_ref()
+    }
+
+    /// Returns custom tasks run on the schedule of a submission worker.
+    pub(crate) fn worker_tasks(&self) -> &[ScheduledTask] {
+        &self.worker_tasks
+    }
+
+    /// Returns a period of an event that describes the effective configuration and the state of a
+    /// pipeline if enabled.
+    pub fn snapshot_interval(&self) -> Option<Duration> {
+        self.snapshot_interval
+    }
+
+    /// Returns whether standard metrics are pre-aggregated from tracked requests and dependencies.
+    pub fn are_standard_metrics_extracted(&self) -> bool {
+        self.standard_metrics_extracted
+    }
+
+    /// Returns a maximum volume of telemetry submitted during a UTC day before low-priority items
+    /// are suppressed if any.
+    pub fn daily_cap(&self) -> Option<DailyCap> {
+        self.daily_cap
+    }
+
+    /// Returns faults injected into submissions to test resilience of an application.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn fault_injection(&self) -> Option<&FaultScript> {
+        self.fault_injection.as_ref()
+    }
+}
+
+impl ContextConfig for TelemetryConfig {
+    fn i_key(&self) -> &str {
+        TelemetryConfig::i_key(self)
+    }
+
+    fn sdk_version(&self) -> &str {
+        TelemetryConfig::sdk_version(self)
+    }
+
+    fn application_version(&self) -> Option<&str> {
+        TelemetryConfig::application_version(self)
+    }
+
+    fn max_exception_chain_depth(&self) -> usize {
+        TelemetryConfig::max_exception_chain_depth(self)
+    }
+
+    fn max_exception_stack_frames(&self) -> usize {
+        TelemetryConfig::max_exception_stack_frames(self)
+    }
+
+    fn request_success(&self) -> &RequestSuccess {
+        TelemetryConfig::request_success(self)
+    }
+}
+
+/// Either a HTTP client shared with an application or a customization of a builder of a HTTP client
+/// created for submissions. It makes a client comparable and printable as part of a configuration.
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Debug, PartialEq)]
+enum HttpClientSource {
+    Shared(Shared<reqwest::Client>),
+    Custom(Shared<dyn Fn(ClientBuilder) -> ClientBuilder + Send + Sync>),
+}
+
+/// Receives telemetry items rejected by the server together with a status code and a message of the
+/// server for each of them. It makes a handler comparable and printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RejectionHandler(Shared<dyn Fn(Vec<RejectedItem>) + Send + Sync>);
+
+/// A telemetry item rejected by the server with a status code and a message of the server.
+pub(crate) type RejectedItem = (Envelope, u16, String);
+
+impl RejectionHandler {
+    /// Hands rejected telemetry items over to a handler.
+    pub(crate) fn handle(&self, items: Vec<RejectedItem>) {
+        (self.0)(items)
+    }
 }
 
 /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with required
@@ -74,6 +572,55 @@ impl DefaultTelemetryConfigBuilder {
             i_key: i_key.into(),
             endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
             interval: Duration::from_secs(2),
+            interval_jitter: None,
+            interval_aligned: false,
+            manual_flush_only: false,
+            time_to_live: None,
+            max_retry_ages: Vec::default(),
+            max_concurrent_transmissions: 1,
+            max_batch_size: 500,
+            max_batch_bytes: None,
+            drain_pace: Duration::from_millis(100),
+            max_queue_size: None,
+            drop_policy: None,
+            priorities: Priorities::default(),
+            request_timeout: None,
+            max_error_body_bytes: DEFAULT_MAX_ERROR_BODY_BYTES,
+            max_exception_chain_depth: DEFAULT_MAX_CHAIN_DEPTH,
+            max_exception_stack_frames: DEFAULT_MAX_STACK_FRAMES,
+            internal_log_level: LevelFilter::Warn,
+            diagnostics_i_key: None,
+            sdk_version: DEFAULT_SDK_VERSION.into(),
+            application_version: None,
+            user_agent: DEFAULT_USER_AGENT.into(),
+            #[cfg(feature = "reqwest")]
+            dns_resolver: None,
+            #[cfg(feature = "reqwest")]
+            http_client: None,
+            #[cfg(feature = "agent")]
+            agent_endpoint: None,
+            #[cfg(feature = "metrics")]
+            metrics_recorder: None,
+            rejection_handler: None,
+            processors: Processors::default(),
+            disabled_types: Vec::default(),
+            min_severity: None,
+            url_redaction: UrlRedaction::default(),
+            dependency_success: DependencySuccess::default(),
+            request_success: RequestSuccess::default(),
+            clock: None,
+            timestamping: Timestamping::default(),
+            clock_skew_corrected: false,
+            scheduler: None,
+            sequence_store: None,
+            throttle_store: None,
+            dead_letter_sink: None,
+            worker_tasks: Vec::default(),
+            snapshot_interval: None,
+            standard_metrics_extracted: false,
+            daily_cap: None,
+            #[cfg(feature = "test-util")]
+            fault_injection: None,
         }
     }
 }
@@ -83,6 +630,55 @@ pub struct TelemetryConfigBuilder {
     i_key: String,
     endpoint: String,
     interval: Duration,
+    interval_jitter: Option<Duration>,
+    interval_aligned: bool,
+    manual_flush_only: bool,
+    time_to_live: Option<Duration>,
+    max_retry_ages: Vec<(TelemetryKind, Duration)>,
+    max_concurrent_transmissions: usize,
+    max_batch_size: usize,
+    max_batch_bytes: Option<usize>,
+    drain_pace: Duration,
+    max_queue_size: Option<usize>,
+    drop_policy: Option<SharedDropPolicy>,
+    priorities: Priorities,
+    request_timeout: Option<Duration>,
+    max_error_body_bytes: usize,
+    max_exception_chain_depth: usize,
+    max_exception_stack_frames: usize,
+    internal_log_level: LevelFilter,
+    diagnostics_i_key: Option<String>,
+    sdk_version: String,
+    application_version: Option<String>,
+    user_agent: String,
+    #[cfg(feature = "reqwest")]
+    dns_resolver: Option<Shared<dyn Resolve>>,
+    #[cfg(feature = "reqwest")]
+    http_client: Option<HttpClientSource>,
+    #[cfg(feature = "agent")]
+    agent_endpoint: Option<AgentEndpoint>,
+    #[cfg(feature = "metrics")]
+    metrics_recorder: Option<SharedMetricsRecorder>,
+    rejection_handler: Option<RejectionHandler>,
+    processors: Processors,
+    disabled_types: Vec<TelemetryKind>,
+    min_severity: Option<SeverityLevel>,
+    url_redaction: UrlRedaction,
+    dependency_success: DependencySuccess,
+    request_success: RequestSuccess,
+    clock: Option<SharedClock>,
+    timestamping: Timestamping,
+    clock_skew_corrected: bool,
+    scheduler: Option<SharedScheduler>,
+    sequence_store: Option<SharedSequenceStore>,
+    throttle_store: Option<SharedThrottleStore>,
+    dead_letter_sink: Option<SharedDeadLetterSink>,
+    worker_tasks: Vec<ScheduledTask>,
+    snapshot_interval: Option<Duration>,
+    standard_metrics_extracted: bool,
+    daily_cap: Option<DailyCap>,
+    #[cfg(feature = "test-util")]
+    fault_injection: Option<FaultScript>,
 }
 
 impl TelemetryConfigBuilder {
@@ -110,18 +706,969 @@ impl TelemetryConfigBuilder {
         self
     }
 
+    /// Initializes a builder with a maximum random delay added to each interval. It spreads
+    /// submissions of many instances started at the same time, so they don't send telemetry in
+    /// synchronized bursts that get throttled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .interval(Duration::from_secs(10))
+    ///     .interval_jitter(Duration::from_secs(5))
+    ///     .build();
+    /// ```
+    pub fn interval_jitter(mut self, jitter: Duration) -> Self {
+        self.interval_jitter = Some(jitter);
+        self
+    }
+
+    /// Initializes a builder with a flag whether submissions happen at wall-clock multiples of the
+    /// interval, e.g. at every full minute for a 1 minute interval. Combine it with
+    /// [`interval_jitter`](#method.interval_jitter) to spread aligned submissions of many instances.
+    pub fn align_interval(mut self, aligned: bool) -> Self {
+        self.interval_aligned = aligned;
+        self
+    }
+
+    /// Initializes a builder with a flag whether telemetry items are sent only when an application
+    /// calls [`flush_channel`](crate::TelemetryClient::flush_channel) or closes the channel. A
+    /// channel does not wake up every [`interval`](#method.interval) then, which suits short-lived
+    /// CLI tools and serverless functions that send telemetry at known points. A batch that fails
+    /// is still retried after a delay. [Worker tasks](#method.worker_task) and
+    /// [snapshots](#method.snapshot_interval) never run, since no interval elapses.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{TelemetryClient, TelemetryConfig};
+    /// # async fn run() {
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .manual_flush_only(true)
+    ///     .build();
+    /// let client = TelemetryClient::from_config(config);
+    ///
+    /// client.track_event("function invoked");
+    /// client.close_channel().await;
+    /// # }
+    /// ```
+    pub fn manual_flush_only(mut self, manual: bool) -> Self {
+        self.manual_flush_only = manual;
+        self
+    }
+
+    /// Initializes a builder with a maximum age of a telemetry item. Items that have been waiting
+    /// in a queue or for retries longer than that are dropped instead of being sent. By default
+    /// telemetry items never expire.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .time_to_live(Duration::from_secs(3600))
+    ///     .build();
+    /// ```
+    pub fn time_to_live(mut self, time_to_live: Duration) -> Self {
+        self.time_to_live = Some(time_to_live);
+        self
+    }
+
+    /// Initializes a builder with a maximum age of telemetry items of a given category to send them
+    /// again after a transient failure. Older items are dropped instead of being retried, e.g.
+    /// availability results or metrics that are useless when they arrive late, while other
+    /// categories are retried as usual. A zero age disables retries of a category altogether.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::telemetry::TelemetryKind;
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_retry_age(TelemetryKind::Availability, Duration::ZERO)
+    ///     .max_retry_age(TelemetryKind::Metric, Duration::from_secs(60))
+    ///     .build();
+    /// ```
+    pub fn max_retry_age(mut self, kind: TelemetryKind, age: Duration) -> Self {
+        self.max_retry_ages.retain(|(retry_kind, _)| *retry_kind != kind);
+        self.max_retry_ages.push((kind, age));
+        self
+    }
+
+    /// Initializes a builder with a maximum number of batches being sent to the server at the same
+    /// time. Each batch is retried on its own schedule, so a slow or failing request doesn't hold
+    /// back batches collected afterwards, although they may arrive out of order.
+    ///
+    /// Defaults to 1, which sends batches one after another in strict order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_concurrent_transmissions(4)
+    ///     .build();
+    /// ```
+    pub fn max_concurrent_transmissions(mut self, max: usize) -> Self {
+        self.max_concurrent_transmissions = max.max(1);
+        self
+    }
+
+    /// Initializes a builder with a maximum number of telemetry items sent to the server in a
+    /// single batch. When more items are queued, e.g. on [`flush`](crate::TelemetryClient::flush_channel)
+    /// or after a burst, the queue is drained in several batches paced by
+    /// [`drain_pace`](#method.drain_pace). Defaults to 500.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_batch_size(100)
+    ///     .drain_pace(Duration::from_millis(250))
+    ///     .build();
+    /// ```
+    pub fn max_batch_size(mut self, max: usize) -> Self {
+        self.max_batch_size = max.max(1);
+        self
+    }
+
+    /// Initializes a builder with a maximum size of a serialized batch in bytes, e.g. to keep
+    /// requests within ingestion limits when telemetry items are large. Items are serialized into a
+    /// request one by one, and a batch is cut as soon as the next item doesn't fit into it. The rest
+    /// of the batch and the queue is drained in further batches
+    /// the same way as with [`max_batch_size`](#method.max_batch_size). A single item larger than
+    /// the limit is still sent in a batch of its own. There is no limit by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_batch_bytes(1024 * 1024)
+    ///     .build();
+    /// ```
+    pub fn max_batch_bytes(mut self, max: usize) -> Self {
+        self.max_batch_bytes = Some(max);
+        self
+    }
+
+    /// Initializes a builder with a time to wait between batches while a queue is drained. When the
+    /// server throttles submissions, the next batch waits until the server accepts them again.
+    /// Defaults to 100ms.
+    pub fn drain_pace(mut self, pace: Duration) -> Self {
+        self.drain_pace = pace;
+        self
+    }
+
+    /// Initializes a builder with a maximum number of telemetry items waiting to be sent. Items
+    /// exceeding the bound are selected by a [`drop_policy`](#method.drop_policy) and dropped when
+    /// the submission worker collects pending items. The bound is not enforced when items are
+    /// tracked, so a queue may exceed it until then. There is no limit by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::{queue::DropNewest, TelemetryConfig};
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_queue_size(10_000)
+    ///     .drop_policy(DropNewest)
+    ///     .build();
+    /// ```
+    pub fn max_queue_size(mut self, max: usize) -> Self {
+        self.max_queue_size = Some(max);
+        self
+    }
+
+    /// Initializes a builder with a policy that selects telemetry items to drop when a queue holds
+    /// more items than [allowed](#method.max_queue_size). Defaults to
+    /// [`DropOldest`](crate::queue::DropOldest). See [`queue`](crate::queue) module for details.
+    pub fn drop_policy<P>(mut self, policy: P) -> Self
+    where
+        P: DropPolicy + 'static,
+    {
+        self.drop_policy = Some(SharedDropPolicy::new(policy));
+        self
+    }
+
+    /// Initializes a builder with a priority of telemetry items of a given category. When a queue
+    /// holds more items than [allowed](#method.max_queue_size), items of a lower priority are dropped
+    /// first, and when items pile up, e.g. while the server throttles submissions, items of a higher
+    /// priority are sent first. A single item overrides it with
+    /// [`set_priority`](crate::telemetry::ContextTags::set_priority). By default exceptions are of
+    /// a high priority, traces are of a low one and other items are of a normal one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::telemetry::{Priority, TelemetryKind};
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_queue_size(10_000)
+    ///     .priority(TelemetryKind::Request, Priority::High)
+    ///     .priority(TelemetryKind::Metric, Priority::Low)
+    ///     .build();
+    /// ```
+    pub fn priority(mut self, kind: TelemetryKind, priority: Priority) -> Self {
+        self.priorities.set(kind, priority);
+        self
+    }
+
+    /// Initializes a builder with a maximum time to wait for the server to respond to a single
+    /// submission, including connecting to the server. A submission that times out is sent again
+    /// later. There is no timeout by default.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Initializes a builder with a maximum number of bytes of an error response body read from
+    /// the server. The rest of a larger body is discarded, so a misbehaving endpoint or proxy can't
+    /// make the client buffer an arbitrary amount of memory. Bodies that describe which telemetry
+    /// items the server accepted may additionally take up to 1 KiB per item sent. Defaults to 64 KiB.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .max_error_body_bytes(4 * 1024)
+    ///     .build();
+    /// ```
+    pub fn max_error_body_bytes(mut self, max: usize) -> Self {
+        self.max_error_body_bytes = max;
+        self
+    }
+
+    /// Initializes a builder with a maximum number of chained exceptions submitted with an
+    /// exception telemetry item. Exceptions beyond the limit are replaced with a single exception
+    /// that tells how many of them were dropped. Defaults to 10.
+    ///
+    /// Deeply nested error chains with full backtraces may produce telemetry items exceeding
+    /// ingestion size limits, which are rejected by the server.
+    pub fn max_exception_chain_depth(mut self, max: usize) -> Self {
+        self.max_exception_chain_depth = max;
+        self
+    }
+
+    /// Initializes a builder with a maximum number of stack frames submitted for each exception.
+    /// Truncated stack traces end with a line that tells how many frames were dropped, and
+    /// an exception is marked as not having a full stack. Defaults to 200.
+    pub fn max_exception_stack_frames(mut self, max: usize) -> Self {
+        self.max_exception_stack_frames = max;
+        self
+    }
+
+    /// Initializes a builder with a maximum severity of SDK self-diagnostics events to report.
+    /// Events are reported to the `log` crate with the
+    /// [`appinsights::internal`](crate::internal_logger::TARGET) target. Defaults to `Warn`.
+    pub fn internal_log_level(mut self, level: LevelFilter) -> Self {
+        self.internal_log_level = level;
+        self
+    }
+
+    /// Initializes a builder with an instrumentation key of a separate resource to submit SDK
+    /// self-diagnostics events to as trace telemetry. It helps to find out why telemetry doesn't
+    /// show up without access to application logs.
+    pub fn diagnostics_i_key<I>(mut self, i_key: I) -> Self
+    where
+        I: Into<String>,
+    {
+        self.diagnostics_i_key = Some(i_key.into());
+        self
+    }
+
+    /// Initializes a builder with a version of the SDK reported in the `ai.internal.sdkVersion` tag
+    /// of every telemetry item. Vendored or patched builds can use it to tell their telemetry
+    /// apart. Defaults to `rust:<crate version>`.
+    pub fn sdk_version<V>(mut self, version: V) -> Self
+    where
+        V: Into<String>,
+    {
+        self.sdk_version = version.into();
+        self
+    }
+
+    /// Initializes a builder with a version of the application reported in the
+    /// `ai.application.ver` tag of every telemetry item. Use the
+    /// [`application_version!`](crate::application_version) macro to report the version of the
+    /// crate the client is created in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .application_version(appinsights::application_version!())
+    ///     .build();
+    ///
+    /// assert_eq!(config.application_version(), Some(env!("CARGO_PKG_VERSION")));
+    /// ```
+    pub fn application_version<V>(mut self, version: V) -> Self
+    where
+        V: Into<String>,
+    {
+        self.application_version = Some(version.into());
+        self
+    }
+
+    /// Initializes a builder with a value of the `User-Agent` header sent with every submission.
+    /// Defaults to `appinsights-rs/<crate version>`.
+    pub fn user_agent<U>(mut self, user_agent: U) -> Self
+    where
+        U: Into<String>,
+    {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Initializes a builder with a custom DNS resolver used to resolve the endpoint host.
+    /// It is useful in environments with unreliable system resolvers, for instance to use a
+    /// resolver with static fallback entries.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::{net::SocketAddr, sync::Arc};
+    /// # use appinsights::TelemetryConfig;
+    /// use hyper::client::connect::dns::Name;
+    /// use reqwest::dns::{Addrs, Resolve, Resolving};
+    ///
+    /// struct StaticResolver(SocketAddr);
+    ///
+    /// impl Resolve for StaticResolver {
+    ///     fn resolve(&self, _: Name) -> Resolving {
+    ///         let addrs: Addrs = Box::new(std::iter::once(self.0));
+    ///         Box::pin(async move { Ok(addrs) })
+    ///     }
+    /// }
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .dns_resolver(Arc::new(StaticResolver(([127, 0, 0, 1], 443).into())))
+    ///     .build();
+    /// ```
+    #[cfg(feature = "reqwest")]
+    pub fn dns_resolver<R>(mut self, resolver: Arc<R>) -> Self
+    where
+        R: Resolve + 'static,
+    {
+        let resolver: Arc<dyn Resolve> = resolver;
+        self.dns_resolver = Some(resolver.into());
+        self
+    }
+
+    /// Initializes a builder with a HTTP client of an application submissions are sent with
+    /// instead of a client of their own. Submissions reuse the connection pool, the proxy and the
+    /// TLS settings of the client, which matters for processes with a strict budget of sockets or
+    /// a common egress policy. A custom DNS resolver is not applied to such a client.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let client = reqwest::Client::builder()
+    ///     .pool_max_idle_per_host(2)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .http_client(client)
+    ///     .build();
+    /// ```
+    #[cfg(feature = "reqwest")]
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(HttpClientSource::Shared(Shared::new(client)));
+        self
+    }
+
+    /// Initializes a builder with a customization of a builder of a HTTP client created for
+    /// submissions, e.g. to set a proxy or a root certificate. It is applied after the `User-Agent`
+    /// header and a custom DNS resolver are set, and replaces a HTTP client of an application if
+    /// any.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .http_client_with(|builder| builder.proxy(reqwest::Proxy::https("http://proxy:3128").unwrap()))
+    ///     .build();
+    /// ```
+    #[cfg(feature = "reqwest")]
+    pub fn http_client_with<F>(mut self, configure: F) -> Self
+    where
+        F: Fn(ClientBuilder) -> ClientBuilder + Send + Sync + 'static,
+    {
+        let configure: Arc<dyn Fn(ClientBuilder) -> ClientBuilder + Send + Sync> = Arc::new(configure);
+        self.http_client = Some(HttpClientSource::Custom(configure.into()));
+        self
+    }
+
+    /// Initializes a builder with a local agent, e.g. an OpenTelemetry collector or a sidecar,
+    /// telemetry items are forwarded to instead of the endpoint. See [`agent`](crate::agent) for
+    /// details of the protocol.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::agent::AgentEndpoint;
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .agent_endpoint(AgentEndpoint::unix("/var/run/telemetry-agent.sock"))
+    ///     .build();
+    ///
+    /// assert_eq!(config.agent_endpoint(), Some(&AgentEndpoint::unix("/var/run/telemetry-agent.sock")));
+    /// ```
+    #[cfg(feature = "agent")]
+    pub fn agent_endpoint(mut self, endpoint: AgentEndpoint) -> Self {
+        self.agent_endpoint = Some(endpoint);
+        self
+    }
+
+    /// Initializes a builder with a custom recorder of counters and histograms of the submission
+    /// pipeline instead of the global recorder of the `metrics` crate. See
+    /// [`pipeline_metrics`](crate::pipeline_metrics) module for details.
+    #[cfg(feature = "metrics")]
+    pub fn metrics_recorder<R>(mut self, recorder: R) -> Self
+    where
+        R: MetricsRecorder + 'static,
+    {
+        self.metrics_recorder = Some(SharedMetricsRecorder::new(recorder));
+        self
+    }
+
+    /// Initializes a builder with a handler of telemetry items the server rejected and which are not
+    /// going to be sent again, e.g. items rejected as invalid or a whole batch rejected with
+    /// `400 Bad Request`. The handler
+    /// receives rejected items together with a status code and a message of the server, so they can
+    /// be logged, fixed or written to a file instead of being lost invisibly.
+    ///
+    /// The handler is called by the submission worker, so it should return quickly.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .on_items_rejected(|items| {
+    ///         for (envelope, status_code, message) in items {
+    ///             eprintln!("{} rejected with {}: {}", envelope.name, status_code, message);
+    ///         }
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn on_items_rejected<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(Vec<(Envelope, u16, String)>) + Send + Sync + 'static,
+    {
+        let handler: Arc<dyn Fn(Vec<RejectedItem>) + Send + Sync> = Arc::new(handler);
+        self.rejection_handler = Some(RejectionHandler(handler.into()));
+        self
+    }
+
+    /// Adds a processor to apply to every telemetry item before it is queued for submission.
+    /// Processors run in the order they were added. See [`processor`](crate::processor) module
+    /// for details.
+    pub fn processor<P>(mut self, processor: P) -> Self
+    where
+        P: TelemetryProcessor + 'static,
+    {
+        self.processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Initializes a builder with categories of telemetry items to drop instead of submitting them.
+    /// It allows to switch off whole categories of telemetry, e.g. traces in production, without
+    /// touching the code that tracks them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::telemetry::TelemetryKind;
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .disable_types(&[TelemetryKind::Trace, TelemetryKind::Metric])
+    ///     .build();
+    /// ```
+    pub fn disable_types(mut self, kinds: &[TelemetryKind]) -> Self {
+        for kind in kinds {
+            if !self.disabled_types.contains(kind) {
+                self.disabled_types.push(*kind);
+            }
+        }
+        self
+    }
+
+    /// Initializes a builder with a minimum severity of traces and exceptions to submit. Less
+    /// severe ones are dropped by a client before they are queued, so verbose traces can stay in the
+    /// code and be switched on by configuration when needed. Exceptions without severity are
+    /// considered errors. By default items of any severity are submitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::telemetry::SeverityLevel;
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .min_severity(SeverityLevel::Warning)
+    ///     .build();
+    /// ```
+    pub fn min_severity(mut self, severity: SeverityLevel) -> Self {
+        self.min_severity = Some(severity);
+        self
+    }
+
+    /// Initializes a builder with a redaction of credentials and query strings from URLs of
+    /// tracked requests and dependencies. URLs are redacted by default, use
+    /// [`UrlRedaction::disabled`](crate::processor::UrlRedaction::disabled) to submit them as is.
+    /// See [`UrlRedaction`](crate::processor::UrlRedaction) for details.
+    pub fn url_redaction(mut self, redaction: UrlRedaction) -> Self {
+        self.url_redaction = redaction;
+        self
+    }
+
+    /// Adds criteria that decide whether calls to dependencies of a given type succeeded based on
+    /// their result codes. The criteria return `None` for a result code they do not know, so the
+    /// success flag computed by an integration or passed to
+    /// [`track_remote_dependency`](crate::TelemetryClient::track_remote_dependency) is kept.
+    ///
+    /// Dependency types are compared ignoring case. Criteria added later for the same type take
+    /// precedence. Dependency telemetry items without a result code are left intact.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     // existence checks of blobs answer with 404 when a blob is missing
+    ///     .dependency_success("Azure blob", |code| (code == "404").then_some(true))
+    ///     .build();
+    /// ```
+    pub fn dependency_success<F>(mut self, dependency_type: impl Into<String>, success: F) -> Self
+    where
+        F: Fn(&str) -> Option<bool> + Send + Sync + 'static,
+    {
+        self.dependency_success.push(dependency_type.into(), Arc::new(success));
+        self
+    }
+
+    /// Adds a table of result codes of dependencies of a given type along with whether a call that
+    /// returned them succeeded. Result codes missing in the table keep the success flag computed
+    /// by an integration. See [`dependency_success`](#method.dependency_success) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .dependency_success_codes("Redis", &[("MOVED", true), ("ASK", true), ("ERR", false)])
+    ///     .dependency_success_codes("HTTP", &[("404", true)])
+    ///     .build();
+    /// ```
+    pub fn dependency_success_codes(self, dependency_type: impl Into<String>, codes: &[(&str, bool)]) -> Self {
+        let codes: Vec<(String, bool)> = codes
+            .iter()
+            .map(|(code, success)| (code.to_string(), *success))
+            .collect();
+        self.dependency_success(dependency_type, move |result_code| {
+            codes
+                .iter()
+                .find(|(code, _)| code == result_code)
+                .map(|(_, success)| *success)
+        })
+    }
+
+    /// Adds criteria that decide whether requests to a given route succeeded based on their
+    /// response codes, e.g. to treat `404` of lookups as success instead of skewing failure rates.
+    /// A route matches a URL path equal to it or nested under it, so `/` matches all requests. The
+    /// criteria return `None` for a response code they do not know, so requests with a response
+    /// code below 400 or 401 are considered successful.
+    ///
+    /// Criteria added later for a matching route take precedence. A request marked with
+    /// [`set_success`](crate::telemetry::RequestTelemetry::set_success) keeps its own flag.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     // clients check whether a user exists by looking it up
+    ///     .request_success("/api/users", |code| (code == "404").then_some(true))
+    ///     .build();
+    /// ```
+    pub fn request_success<F>(mut self, route: impl Into<String>, success: F) -> Self
+    where
+        F: Fn(&str) -> Option<bool> + Send + Sync + 'static,
+    {
+        self.request_success.push(route.into(), Arc::new(success));
+        self
+    }
+
+    /// Adds a table of response codes of requests to a given route along with whether a request
+    /// that returned them succeeded. Response codes missing in the table are decided by earlier
+    /// criteria or by default. See [`request_success`](#method.request_success) for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .request_success_codes("/", &[("401", false)])
+    ///     .request_success_codes("/api/login", &[("401", true), ("403", true)])
+    ///     .build();
+    /// ```
+    pub fn request_success_codes(self, route: impl Into<String>, codes: &[(&str, bool)]) -> Self {
+        let codes: Vec<(String, bool)> = codes
+            .iter()
+            .map(|(code, success)| (code.to_string(), *success))
+            .collect();
+        self.request_success(route, move |response_code| {
+            codes
+                .iter()
+                .find(|(code, _)| code == response_code)
+                .map(|(_, success)| *success)
+        })
+    }
+
+    /// Initializes a builder with a custom source of wall clock time for platforms without a
+    /// reliable system clock or for deterministic tests. See [`clock`](crate::clock) module for
+    /// details.
+    pub fn clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(SharedClock::new(clock));
+        self
+    }
+
+    /// Initializes a builder with a moment telemetry items are stamped with the time of a custom
+    /// [`clock`](#method.clock). By default items are stamped when they are tracked.
+    pub fn timestamping(mut self, timestamping: Timestamping) -> Self {
+        self.timestamping = timestamping;
+        self
+    }
+
+    /// Initializes a builder with a flag whether times of telemetry items are corrected by the
+    /// difference between the local clock and the server clock when they are sent. The difference
+    /// is learned from the `Date` header of server responses, so items of the first batch are sent
+    /// with the local time. A throttle deadline is always translated to the local clock.
+    pub fn correct_clock_skew(mut self, corrected: bool) -> Self {
+        self.clock_skew_corrected = corrected;
+        self
+    }
+
+    /// Initializes a builder with a custom timer a submission worker waits on before it sends or
+    /// retries telemetry items, e.g. to drive submission from the main loop of an application.
+    /// See [`scheduler`](crate::scheduler) module for details.
+    pub fn scheduler<S>(mut self, scheduler: S) -> Self
+    where
+        S: Scheduler + 'static,
+    {
+        self.scheduler = Some(SharedScheduler::new(scheduler));
+        self
+    }
+
+    /// Initializes a builder with a storage of the highest sequence number of a batch acknowledged
+    /// by the server. It enables sequence numbers of telemetry items. See [`sequence`](crate::sequence)
+    /// module for details.
+    pub fn sequence_store<S>(mut self, store: S) -> Self
+    where
+        S: SequenceStore + 'static,
+    {
+        self.sequence_store = Some(SharedSequenceStore::new(store));
+        self
+    }
+
+    /// Initializes a builder with a storage of the time until which the server throttles
+    /// submissions, so the throttle window is respected after a restart. See
+    /// [`throttle`](crate::throttle) module for details.
+    pub fn throttle_store<S>(mut self, store: S) -> Self
+    where
+        S: ThrottleStore + 'static,
+    {
+        self.throttle_store = Some(SharedThrottleStore::new(store));
+        self
+    }
+
+    /// Initializes a builder with a storage of telemetry items that could not be delivered to the
+    /// server. See [`dead_letter`](crate::dead_letter) module for details.
+    pub fn dead_letter_sink<S>(mut self, sink: S) -> Self
+    where
+        S: DeadLetterSink + 'static,
+    {
+        self.dead_letter_sink = Some(SharedDeadLetterSink::new(sink));
+        self
+    }
+
+    /// Adds a task to run every `every` elapsed submission intervals on the schedule of a
+    /// submission worker. See [`worker_task`](crate::worker_task) module for details.
+    pub fn worker_task<T>(mut self, every: u64, task: T) -> Self
+    where
+        T: WorkerTask + 'static,
+    {
+        self.worker_tasks.push(ScheduledTask::new(every, task));
+        self
+    }
+
+    /// Initializes a builder with a period of an event that describes how this instance is
+    /// configured, so support can query the portal for it. The event reports the effective
+    /// configuration, the SDK version, enabled features and cumulative counters of items sent,
+    /// lost, expired and dropped because the queue was full. The first event is sent on the first
+    /// elapsed submission interval. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .snapshot_interval(Duration::from_secs(3600))
+    ///     .build();
+    /// ```
+    pub fn snapshot_interval(mut self, period: Duration) -> Self {
+        self.snapshot_interval = Some(period);
+        self
+    }
+
+    /// Initializes a builder with a flag whether standard metrics are pre-aggregated from tracked
+    /// requests and dependencies, the way other Application Insights SDKs do. Durations are
+    /// aggregated by result, result code, performance bucket, dependency type and target, cloud role
+    /// and synthetic source for a minute and sent as `requests/duration` and
+    /// `dependencies/duration` metrics. Portal performance charts are built from these metrics, so
+    /// they stay accurate when raw items are sampled by processors. Disabled by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .extract_standard_metrics(true)
+    ///     .build();
+    /// ```
+    pub fn extract_standard_metrics(mut self, extracted: bool) -> Self {
+        self.standard_metrics_extracted = extracted;
+        self
+    }
+
+    /// Initializes a builder with a maximum volume of telemetry submitted during a UTC day. Once it
+    /// is exceeded, low-priority items, i.e. traces unless configured otherwise with
+    /// [`priority`](#method.priority), are dropped or sampled until the day is over, while other
+    /// telemetry items are still submitted. See [`daily_cap`](crate::daily_cap) for details.
+    /// Unlimited by default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// use appinsights::daily_cap::DailyCap;
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .daily_cap(DailyCap::items(1_000_000))
+    ///     .build();
+    /// ```
+    pub fn daily_cap(mut self, cap: DailyCap) -> Self {
+        self.daily_cap = Some(cap);
+        self
+    }
+
+    /// Initializes a builder with a script of faults injected into submissions to chaos-test an
+    /// application. See [`FaultScript`] for details.
+    #[cfg(feature = "test-util")]
+    pub fn fault_injection(mut self, faults: FaultScript) -> Self {
+        self.fault_injection = Some(faults);
+        self
+    }
+
+    /// Validates custom settings and constructs a new instance of a
+    /// [`TelemetryConfig`](struct.TelemetryConfig.html) with them.
+    ///
+    /// The endpoint must be an absolute `http` or `https` URL. Host names, IPv4 and IPv6 literals
+    /// and any valid port are supported, e.g. `http://[::1]:8080/v2/track` for a private ingestion
+    /// gateway. The user agent must be a valid HTTP header value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use appinsights::TelemetryConfig;
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .endpoint("https://[2001:db8::1]:8443/v2/track")
+    ///     .try_build();
+    /// assert!(config.is_ok());
+    ///
+    /// let config = TelemetryConfig::builder()
+    ///     .i_key("<instrumentation key>")
+    ///     .endpoint("ingestion.example.com:8443")
+    ///     .try_build();
+    /// assert!(config.is_err());
+    /// ```
+    pub fn try_build(self) -> Result<TelemetryConfig, InvalidConfig> {
+        validate_endpoint(&self.endpoint)?;
+        HeaderValue::from_str(&self.user_agent)
+            .map_err(|_| InvalidConfig(format!("user agent {} is not a valid header value", self.user_agent)))?;
+        Ok(self.build())
+    }
+
     /// Constructs a new instance of a [`TelemetryConfig`](struct.TelemetryConfig.html) with custom settings.
     pub fn build(self) -> TelemetryConfig {
         TelemetryConfig {
             i_key: self.i_key,
             endpoint: self.endpoint,
             interval: self.interval,
+            interval_jitter: self.interval_jitter,
+            interval_aligned: self.interval_aligned,
+            manual_flush_only: self.manual_flush_only,
+            time_to_live: self.time_to_live,
+            max_retry_ages: self.max_retry_ages,
+            max_concurrent_transmissions: self.max_concurrent_transmissions,
+            max_batch_size: self.max_batch_size,
+            max_batch_bytes: self.max_batch_bytes,
+            drain_pace: self.drain_pace,
+            max_queue_size: self.max_queue_size,
+            drop_policy: self.drop_policy,
+            priorities: self.priorities,
+            request_timeout: self.request_timeout,
+            max_error_body_bytes: self.max_error_body_bytes,
+            max_exception_chain_depth: self.max_exception_chain_depth,
+            max_exception_stack_frames: self.max_exception_stack_frames,
+            internal_log_level: self.internal_log_level,
+            diagnostics_i_key: self.diagnostics_i_key,
+            sdk_version: self.sdk_version,
+            application_version: self.application_version,
+            user_agent: self.user_agent,
+            #[cfg(feature = "reqwest")]
+            dns_resolver: self.dns_resolver,
+            #[cfg(feature = "reqwest")]
+            http_client: self.http_client,
+            #[cfg(feature = "agent")]
+            agent_endpoint: self.agent_endpoint,
+            #[cfg(feature = "metrics")]
+            metrics_recorder: self.metrics_recorder,
+            rejection_handler: self.rejection_handler,
+            processors: self.processors,
+            disabled_types: self.disabled_types,
+            min_severity: self.min_severity,
+            url_redaction: self.url_redaction,
+            dependency_success: self.dependency_success,
+            request_success: self.request_success,
+            clock: self.clock,
+            timestamping: self.timestamping,
+            clock_skew_corrected: self.clock_skew_corrected,
+            scheduler: self.scheduler,
+            sequence_store: self.sequence_store,
+            throttle_store: self.throttle_store,
+            dead_letter_sink: self.dead_letter_sink,
+            worker_tasks: self.worker_tasks,
+            snapshot_interval: self.snapshot_interval,
+            standard_metrics_extracted: self.standard_metrics_extracted,
+            daily_cap: self.daily_cap,
+            #[cfg(feature = "test-util")]
+            fault_injection: self.fault_injection,
         }
     }
 }
 
+/// An error returned when a telemetry configuration contains invalid settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidConfig(String);
+
+impl Display for InvalidConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid telemetry configuration: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl InvalidConfig {
+    /// Creates a new error with a given description of invalid settings.
+    #[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+/// Checks that an endpoint is an absolute `http` or `https` URL with a host and a valid port.
+fn validate_endpoint(endpoint: &str) -> Result<(), InvalidConfig> {
+    let uri: Uri = endpoint
+        .parse()
+        .map_err(|err| InvalidConfig(format!("endpoint {} is not a valid URL: {}", endpoint, err)))?;
+
+    if !matches!(uri.scheme_str(), Some("http") | Some("https")) {
+        return Err(InvalidConfig(format!(
+            "endpoint {} must be an absolute http or https URL",
+            endpoint
+        )));
+    }
+
+    let authority = match uri.authority() {
+        Some(authority) if !authority.host().is_empty() => authority.as_str(),
+        _ => return Err(InvalidConfig(format!("endpoint {} has no host", endpoint))),
+    };
+
+    // a port follows the last colon unless it is a part of an IPv6 literal
+    let host_and_port = authority.rsplit('@').next().unwrap_or(authority);
+    if let Some((_, port)) = host_and_port.rsplit_once(':').filter(|_| !host_and_port.ends_with(']')) {
+        if port.parse::<u16>().is_err() {
+            return Err(InvalidConfig(format!(
+                "endpoint {} has invalid port {}",
+                endpoint, port
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Expands to the version of the crate it is used in, i.e. the `CARGO_PKG_VERSION` of the
+/// application rather than the one of this SDK. It is meant to be passed to the
+/// `application_version` method of a [`TelemetryConfig::builder`](crate::TelemetryConfig::builder).
+#[macro_export]
+macro_rules! application_version {
+    () => {
+        env!("CARGO_PKG_VERSION")
+    };
+}
+
 #[cfg(test)]
 mod tests {
+    use test_case::test_case;
+
     use super::*;
 
     #[test]
@@ -132,7 +1679,56 @@ mod tests {
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://dc.services.visualstudio.com/v2/track".into(),
-                interval: Duration::from_secs(2)
+                interval: Duration::from_secs(2),
+                interval_jitter: None,
+                interval_aligned: false,
+                manual_flush_only: false,
+                time_to_live: None,
+                max_retry_ages: Vec::default(),
+                max_concurrent_transmissions: 1,
+                max_batch_size: 500,
+                max_batch_bytes: None,
+                drain_pace: Duration::from_millis(100),
+                max_queue_size: None,
+                drop_policy: None,
+                priorities: Priorities::default(),
+                request_timeout: None,
+                max_error_body_bytes: 65536,
+                max_exception_chain_depth: 10,
+                max_exception_stack_frames: 200,
+                internal_log_level: LevelFilter::Warn,
+                diagnostics_i_key: None,
+                sdk_version: format!("rust:{}", env!("CARGO_PKG_VERSION")),
+                application_version: None,
+                user_agent: format!("appinsights-rs/{}", env!("CARGO_PKG_VERSION")),
+                #[cfg(feature = "reqwest")]
+                dns_resolver: None,
+                #[cfg(feature = "reqwest")]
+                http_client: None,
+                #[cfg(feature = "agent")]
+                agent_endpoint: None,
+                #[cfg(feature = "metrics")]
+                metrics_recorder: None,
+                rejection_handler: None,
+                processors: Processors::default(),
+                disabled_types: Vec::default(),
+                min_severity: None,
+                url_redaction: UrlRedaction::default(),
+                dependency_success: DependencySuccess::default(),
+                request_success: RequestSuccess::default(),
+                clock: None,
+                timestamping: Timestamping::OnTrack,
+                clock_skew_corrected: false,
+                scheduler: None,
+                sequence_store: None,
+                throttle_store: None,
+                dead_letter_sink: None,
+                worker_tasks: Vec::default(),
+                snapshot_interval: None,
+                standard_metrics_extracted: false,
+                daily_cap: None,
+                #[cfg(feature = "test-util")]
+                fault_injection: None,
             },
             config
         )
@@ -144,15 +1740,118 @@ mod tests {
             .i_key("instrumentation key")
             .endpoint("https://google.com")
             .interval(Duration::from_micros(100))
+            .interval_jitter(Duration::from_micros(50))
+            .align_interval(true)
+            .manual_flush_only(true)
+            .time_to_live(Duration::from_secs(3600))
+            .max_retry_age(TelemetryKind::Availability, Duration::from_secs(10))
+            .max_retry_age(TelemetryKind::Availability, Duration::ZERO)
+            .max_concurrent_transmissions(4)
+            .max_batch_size(100)
+            .max_batch_bytes(65536)
+            .drain_pace(Duration::from_secs(1))
+            .max_queue_size(1000)
+            .priority(TelemetryKind::Trace, Priority::High)
+            .request_timeout(Duration::from_secs(30))
+            .max_error_body_bytes(1024)
+            .max_exception_chain_depth(3)
+            .max_exception_stack_frames(50)
+            .internal_log_level(LevelFilter::Debug)
+            .diagnostics_i_key("diagnostics key")
+            .sdk_version("rust-vendored:1.0.0")
+            .application_version("2.4.1")
+            .user_agent("vendored/1.0.0")
+            .disable_types(&[TelemetryKind::Trace, TelemetryKind::Metric, TelemetryKind::Trace])
+            .min_severity(SeverityLevel::Warning)
+            .url_redaction(UrlRedaction::disabled())
+            .timestamping(Timestamping::OnTransmission)
+            .correct_clock_skew(true)
+            .snapshot_interval(Duration::from_secs(3600))
+            .extract_standard_metrics(true)
+            .daily_cap(DailyCap::items(1000))
             .build();
 
         assert_eq!(
             TelemetryConfig {
                 i_key: "instrumentation key".into(),
                 endpoint: "https://google.com".into(),
-                interval: Duration::from_micros(100)
+                interval: Duration::from_micros(100),
+                interval_jitter: Some(Duration::from_micros(50)),
+                interval_aligned: true,
+                manual_flush_only: true,
+                time_to_live: Some(Duration::from_secs(3600)),
+                max_retry_ages: vec![(TelemetryKind::Availability, Duration::ZERO)],
+                max_concurrent_transmissions: 4,
+                max_batch_size: 100,
+                max_batch_bytes: Some(65536),
+                drain_pace: Duration::from_secs(1),
+                max_queue_size: Some(1000),
+                drop_policy: None,
+                priorities: Priorities(vec![(TelemetryKind::Trace, Priority::High)]),
+                request_timeout: Some(Duration::from_secs(30)),
+                max_error_body_bytes: 1024,
+                max_exception_chain_depth: 3,
+                max_exception_stack_frames: 50,
+                internal_log_level: LevelFilter::Debug,
+                diagnostics_i_key: Some("diagnostics key".into()),
+                sdk_version: "rust-vendored:1.0.0".into(),
+                application_version: Some("2.4.1".into()),
+                user_agent: "vendored/1.0.0".into(),
+                #[cfg(feature = "reqwest")]
+                dns_resolver: None,
+                #[cfg(feature = "reqwest")]
+                http_client: None,
+                #[cfg(feature = "agent")]
+                agent_endpoint: None,
+                #[cfg(feature = "metrics")]
+                metrics_recorder: None,
+                rejection_handler: None,
+                processors: Processors::default(),
+                disabled_types: vec![TelemetryKind::Trace, TelemetryKind::Metric],
+                min_severity: Some(SeverityLevel::Warning),
+                url_redaction: UrlRedaction::disabled(),
+                dependency_success: DependencySuccess::default(),
+                request_success: RequestSuccess::default(),
+                clock: None,
+                timestamping: Timestamping::OnTransmission,
+                clock_skew_corrected: true,
+                scheduler: None,
+                sequence_store: None,
+                throttle_store: None,
+                dead_letter_sink: None,
+                worker_tasks: Vec::default(),
+                snapshot_interval: Some(Duration::from_secs(3600)),
+                standard_metrics_extracted: true,
+                daily_cap: Some(DailyCap::items(1000)),
+                #[cfg(feature = "test-util")]
+                fault_injection: None,
             },
             config
         );
     }
+
+    #[test_case("https://dc.services.visualstudio.com/v2/track"; "default endpoint")]
+    #[test_case("http://127.0.0.1:8080/v2/track"; "ipv4 with port")]
+    #[test_case("http://[::1]/v2/track"; "ipv6")]
+    #[test_case("https://[2001:db8::1]:8443/v2/track"; "ipv6 with port")]
+    #[test_case("https://ingestion.example.com:65535"; "host with max port")]
+    fn it_accepts_valid_endpoint(endpoint: &str) {
+        let config = TelemetryConfig::builder().i_key("key").endpoint(endpoint).try_build();
+
+        assert_eq!(
+            config.map(|config| config.endpoint().to_string()),
+            Ok(endpoint.to_string())
+        );
+    }
+
+    #[test_case("ingestion.example.com/v2/track"; "no scheme")]
+    #[test_case("ftp://ingestion.example.com"; "unsupported scheme")]
+    #[test_case("http://ingestion.example.com:65536"; "port out of range")]
+    #[test_case("http://[::1]:port"; "ipv6 with malformed port")]
+    #[test_case("http://[::1"; "unclosed ipv6 literal")]
+    fn it_rejects_invalid_endpoint(endpoint: &str) {
+        let config = TelemetryConfig::builder().i_key("key").endpoint(endpoint).try_build();
+
+        assert!(config.is_err());
+    }
 }
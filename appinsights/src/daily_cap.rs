@@ -0,0 +1,436 @@
+//! Client-side cap of telemetry volume per UTC day.
+//!
+//! The server charges for every item it ingests, so a bug that logs in a tight loop or an
+//! unexpected spike of traffic ends up on the bill. With a [`DailyCap`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder), a client counts telemetry items
+//! or their estimated size in bytes it submits during a UTC day. Once the cap is exceeded,
+//! low-priority items are dropped or heavily sampled until the day is over, while other items are
+//! still submitted, so failures remain visible. An item is of a low priority when it is
+//! [stamped](crate::telemetry::ContextTags::set_priority) so or when its category is configured so
+//! with `TelemetryConfig::builder().priority(..)`, which makes traces low-priority items by
+//! default. Sampled items carry a sample rate scaled accordingly, so the portal still
+//! estimates their original number. Metrics are never sampled, because they are aggregated
+//! already, so low-priority metrics are dropped once the cap is exceeded.
+//!
+//! A single summary event records how many items of each type were suppressed during a day. It is
+//! submitted when the next day starts or when the client is closed.
+//!
+//! ```rust
+//! use appinsights::{daily_cap::DailyCap, TelemetryConfig};
+//!
+//! // keep one of every 100 traces once 500 MB were submitted during a day
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .daily_cap(DailyCap::bytes(500 * 1024 * 1024).sample_over_cap(100))
+//!     .build();
+//! ```
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+use chrono::NaiveDate;
+
+use crate::{
+    contracts::Envelope,
+    queue::Priorities,
+    telemetry::{EventTelemetry, Priority, Telemetry, TelemetryKind},
+    time,
+    transmitter::serialized_len,
+};
+
+/// A name of a summary event of telemetry items suppressed during a day.
+const SUMMARY_EVENT: &str = "Daily cap exceeded";
+
+/// A unit a daily volume of telemetry is measured in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapUnit {
+    /// A number of telemetry items.
+    Items,
+
+    /// An estimated number of bytes of serialized telemetry items.
+    Bytes,
+}
+
+/// A maximum volume of telemetry a client submits during a UTC day before it suppresses
+/// low-priority items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyCap {
+    limit: u64,
+    unit: CapUnit,
+    sampling: Option<u32>,
+}
+
+impl DailyCap {
+    /// Creates a cap of a given number of telemetry items per day.
+    pub fn items(limit: u64) -> Self {
+        Self {
+            limit,
+            unit: CapUnit::Items,
+            sampling: None,
+        }
+    }
+
+    /// Creates a cap of a given estimated number of bytes per day.
+    pub fn bytes(limit: u64) -> Self {
+        Self {
+            limit,
+            unit: CapUnit::Bytes,
+            sampling: None,
+        }
+    }
+
+    /// Keeps one of every given number of low-priority items once the cap is exceeded instead of
+    /// dropping all of them.
+    pub fn sample_over_cap(mut self, every: u32) -> Self {
+        self.sampling = Some(every.max(1));
+        self
+    }
+
+    /// Returns the maximum volume of telemetry per day.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// Returns a unit the volume is measured in.
+    pub fn unit(&self) -> CapUnit {
+        self.unit
+    }
+
+    /// Returns how many low-priority items one item is kept of once the cap is exceeded, or `None`
+    /// if all of them are dropped.
+    pub fn sampling(&self) -> Option<u32> {
+        self.sampling
+    }
+}
+
+/// Volume of telemetry submitted during a day.
+#[derive(Debug)]
+struct Usage {
+    day: NaiveDate,
+    used: u64,
+    sample_phases: BTreeMap<Option<TelemetryKind>, u32>,
+    suppressed: BTreeMap<TelemetryKind, u64>,
+}
+
+impl Usage {
+    fn new(day: NaiveDate) -> Self {
+        Self {
+            day,
+            used: 0,
+            sample_phases: BTreeMap::default(),
+            suppressed: BTreeMap::default(),
+        }
+    }
+}
+
+/// A decision whether to submit a telemetry item along with a summary of the previous day if it
+/// has just ended.
+pub(crate) struct Admission {
+    pub(crate) admitted: bool,
+    pub(crate) summary: Option<EventTelemetry>,
+}
+
+/// Enforces a daily cap on telemetry items submitted by a client and all of its clones.
+#[derive(Debug)]
+pub(crate) struct DailyCapGuard {
+    cap: DailyCap,
+    priorities: Priorities,
+    usage: Mutex<Usage>,
+}
+
+impl DailyCapGuard {
+    /// Creates a new guard if a daily cap is configured.
+    pub(crate) fn from_config(config: &crate::TelemetryConfig) -> Option<Self> {
+        config.daily_cap().map(|cap| Self {
+            cap,
+            priorities: config.priorities().clone(),
+            usage: Mutex::new(Usage::new(today())),
+        })
+    }
+
+    /// Decides whether to submit a telemetry item and counts it towards the cap if so.
+    pub(crate) fn admit(&self, envelope: &mut Envelope) -> Admission {
+        // an item is measured before the lock is taken, so serializing it does not block others
+        let size = match self.cap.unit {
+            CapUnit::Items => 1,
+            CapUnit::Bytes => serialized_len(envelope) as u64,
+        };
+        let kind = TelemetryKind::of(envelope);
+        let low_priority = self.is_low_priority(envelope, kind);
+
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let today = today();
+        let summary = if usage.day != today {
+            let summary = self.summary(&usage);
+            *usage = Usage::new(today);
+            summary
+        } else {
+            None
+        };
+
+        let admitted = if usage.used < self.cap.limit || !low_priority {
+            true
+        } else if let Some(every) = self.cap.sampling.filter(|_| kind != Some(TelemetryKind::Metric)) {
+            // each type is sampled on its own, so a sample rate matches a share of items kept
+            let phase = usage.sample_phases.entry(kind).or_default();
+            let sampled = *phase == 0;
+            *phase = (*phase + 1) % every;
+            if sampled {
+                envelope.scale_sample_rate(100.0 / f64::from(every));
+            }
+            sampled
+        } else {
+            false
+        };
+
+        if admitted {
+            usage.used += size;
+        } else if let Some(kind) = kind {
+            *usage.suppressed.entry(kind).or_default() += 1;
+        }
+
+        Admission { admitted, summary }
+    }
+
+    /// Returns `true` for an item that is suppressed once the cap is exceeded, i.e. an item stamped
+    /// with a low priority or an item of a category of a low priority unless it overrides it.
+    fn is_low_priority(&self, envelope: &Envelope, kind: Option<TelemetryKind>) -> bool {
+        let priority = Priority::of(envelope).or_else(|| kind.map(|kind| self.priorities.of_kind(kind)));
+        priority == Some(Priority::Low)
+    }
+
+    /// Takes a summary of items suppressed during the current day so far, e.g. when a client is
+    /// closed. The counters of suppressed items are reset, so they are not reported twice.
+    pub(crate) fn take_summary(&self) -> Option<EventTelemetry> {
+        let mut usage = self.usage.lock().unwrap_or_else(PoisonError::into_inner);
+        let summary = self.summary(&usage);
+        usage.suppressed.clear();
+        summary
+    }
+
+    /// Creates a summary event of items suppressed during a day if any.
+    fn summary(&self, usage: &Usage) -> Option<EventTelemetry> {
+        if usage.suppressed.is_empty() {
+            return None;
+        }
+
+        let mut event = EventTelemetry::new(SUMMARY_EVENT);
+
+        let properties = event.properties_mut();
+        properties.insert("day".into(), usage.day.to_string());
+        properties.insert("limit".into(), self.cap.limit.to_string());
+        properties.insert("unit".into(), format!("{:?}", self.cap.unit).to_lowercase());
+
+        let measurements = event.measurements_mut();
+        measurements.insert("used".into(), usage.used as f64);
+        let mut total = 0;
+        for (kind, count) in &usage.suppressed {
+            measurements.insert(format!("suppressed{:?}", kind), *count as f64);
+            total += count;
+        }
+        measurements.insert("suppressed".into(), total as f64);
+
+        Some(event)
+    }
+}
+
+/// Returns the current UTC day.
+fn today() -> NaiveDate {
+    time::now().naive_utc().date()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data},
+        telemetry::{MetricTelemetry, RequestTelemetry, SeverityLevel, TraceTelemetry},
+        TelemetryConfig, TelemetryContext,
+    };
+
+    #[test]
+    fn it_drops_low_priority_items_over_cap() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let guard = guard(DailyCap::items(2));
+
+        let admitted: Vec<_> = [trace(), trace(), trace(), request(), trace()]
+            .iter_mut()
+            .map(|envelope| guard.admit(envelope).admitted)
+            .collect();
+        assert_eq!(admitted, vec![true, true, false, true, false]);
+
+        let summary = guard.take_summary().unwrap();
+        assert_eq!(summary.measurements().get("suppressedTrace"), Some(&2.0));
+        assert_eq!(summary.measurements().get("used"), Some(&3.0));
+        assert!(guard.take_summary().is_none());
+        time::reset();
+    }
+
+    #[test]
+    fn it_drops_items_of_configured_low_priority_over_cap() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_cap(DailyCap::items(1))
+            .priority(TelemetryKind::Request, Priority::Low)
+            .priority(TelemetryKind::Trace, Priority::Normal)
+            .build();
+        let guard = DailyCapGuard::from_config(&config).unwrap();
+
+        let mut verbose = trace();
+        Priority::Low.stamp_if_missing(&mut verbose);
+
+        let admitted: Vec<_> = [request(), request(), trace(), verbose]
+            .iter_mut()
+            .map(|envelope| guard.admit(envelope).admitted)
+            .collect();
+        assert_eq!(admitted, vec![true, false, true, false]);
+
+        let summary = guard.take_summary().unwrap();
+        assert_eq!(summary.measurements().get("suppressedRequest"), Some(&1.0));
+        assert_eq!(summary.measurements().get("suppressedTrace"), Some(&1.0));
+        time::reset();
+    }
+
+    #[test]
+    fn it_samples_low_priority_items_over_cap() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let guard = guard(DailyCap::items(1).sample_over_cap(3));
+
+        let sample_rates: Vec<_> = (0..8)
+            .map(|_| {
+                let mut envelope = trace();
+                guard
+                    .admit(&mut envelope)
+                    .admitted
+                    .then(|| envelope.sample_rate.unwrap())
+            })
+            .collect();
+        assert_eq!(
+            sample_rates,
+            vec![
+                Some(100.0),
+                Some(100.0 / 3.0),
+                None,
+                None,
+                Some(100.0 / 3.0),
+                None,
+                None,
+                Some(100.0 / 3.0)
+            ]
+        );
+        time::reset();
+    }
+
+    #[test]
+    fn it_samples_low_priority_items_of_each_type_separately() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_cap(DailyCap::items(0).sample_over_cap(2))
+            .priority(TelemetryKind::Event, Priority::Low)
+            .build();
+        let guard = DailyCapGuard::from_config(&config).unwrap();
+
+        let sample_rates: Vec<_> = [trace(), event(), trace(), event(), trace(), event()]
+            .iter_mut()
+            .map(|envelope| guard.admit(envelope).admitted.then(|| envelope.sample_rate.unwrap()))
+            .collect();
+        assert_eq!(
+            sample_rates,
+            vec![Some(50.0), Some(50.0), None, None, Some(50.0), Some(50.0)]
+        );
+        time::reset();
+    }
+
+    #[test]
+    fn it_drops_low_priority_metrics_over_cap_instead_of_sampling_them() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(3, 0, 0, 0));
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_cap(DailyCap::items(0).sample_over_cap(2))
+            .priority(TelemetryKind::Metric, Priority::Low)
+            .build();
+        let guard = DailyCapGuard::from_config(&config).unwrap();
+
+        let mut envelope = metric();
+        assert!(!guard.admit(&mut envelope).admitted);
+        assert_eq!(envelope.sample_rate, Some(100.0));
+
+        let admitted: Vec<_> = [trace(), trace()]
+            .iter_mut()
+            .map(|envelope| guard.admit(envelope).admitted)
+            .collect();
+        assert_eq!(admitted, vec![true, false]);
+
+        let summary = guard.take_summary().unwrap();
+        assert_eq!(summary.measurements().get("suppressedMetric"), Some(&1.0));
+        time::reset();
+    }
+
+    #[test]
+    fn it_resets_cap_and_reports_summary_when_day_is_over() {
+        time::set(Utc.ymd(2019, 1, 2).and_hms_milli(23, 59, 0, 0));
+        let guard = guard(DailyCap::items(1));
+        assert!(guard.admit(&mut trace()).admitted);
+        assert!(!guard.admit(&mut trace()).admitted);
+
+        time::set(Utc.ymd(2019, 1, 3).and_hms_milli(0, 0, 1, 0));
+        let admission = guard.admit(&mut trace());
+        assert!(admission.admitted);
+
+        let summary = admission.summary.unwrap();
+        let envelope: Envelope = (context(), summary).into();
+        match envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                assert_eq!(data.name, "Daily cap exceeded");
+                let properties = data.properties.unwrap_or_default();
+                assert_eq!(properties.get("day"), Some(&"2019-01-02".to_string()));
+                assert_eq!(properties.get("limit"), Some(&"1".to_string()));
+                assert_eq!(properties.get("unit"), Some(&"items".to_string()));
+                let measurements = data.measurements.unwrap_or_default();
+                assert_eq!(measurements.get("suppressed"), Some(&1.0));
+            }
+            data => panic!("unexpected telemetry data: {:?}", data),
+        }
+        time::reset();
+    }
+
+    fn guard(cap: DailyCap) -> DailyCapGuard {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .daily_cap(cap)
+            .build();
+        DailyCapGuard::from_config(&config).unwrap()
+    }
+
+    fn context() -> TelemetryContext {
+        TelemetryContext::from_config(&TelemetryConfig::new("instrumentation".into()))
+    }
+
+    fn trace() -> Envelope {
+        (context(), TraceTelemetry::new("message", SeverityLevel::Information)).into()
+    }
+
+    fn event() -> Envelope {
+        (context(), EventTelemetry::new("event")).into()
+    }
+
+    fn metric() -> Envelope {
+        (context(), MetricTelemetry::new("metric", 1.0)).into()
+    }
+
+    fn request() -> Envelope {
+        let request = RequestTelemetry::new(
+            "GET /".into(),
+            "https://example.com/".parse().unwrap(),
+            std::time::Duration::from_millis(10),
+            "200",
+        );
+        (context(), request).into()
+    }
+}
@@ -0,0 +1,347 @@
+//! Storage of telemetry items that could not be delivered to the server.
+//!
+//! A channel drops telemetry items that exhausted all retries, became older than
+//! [time to live](crate::TelemetryConfig::time_to_live) while the server throttled submissions,
+//! exceeded a [maximum queue size](crate::TelemetryConfig::max_queue_size), were rejected by the
+//! server as invalid or failed to be sent with an error retries would not fix. With a [`DeadLetterSink`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder), such items are deposited to the
+//! sink together with a reason instead, so they can be reconciled or ingested again later.
+//!
+//! [`FileDeadLetterSink`] appends dead letters to a file, one JSON object per line.
+//!
+//! ```rust, no_run
+//! use appinsights::{dead_letter::FileDeadLetterSink, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .dead_letter_sink(FileDeadLetterSink::new("/var/lib/my-app/telemetry.dead.ndjson"))
+//!     .build();
+//! ```
+use std::{
+    fmt::Debug,
+    fs::OpenOptions,
+    io::{self, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+};
+
+use log::error;
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::{
+    contracts::Envelope,
+    internal_logger::{InternalEvent, InternalLogger},
+    shared::Shared,
+};
+
+/// A reason a telemetry item could not be delivered to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeadLetterReason {
+    /// The server did not accept an item despite all retries.
+    RetriesExhausted,
+
+    /// An item became older than time to live before it could be sent.
+    Expired,
+
+    /// An item was dropped from a queue that held more items than allowed.
+    QueueFull,
+
+    /// The server rejected an item as invalid with a given status code and message.
+    Rejected {
+        /// A status code the server rejected an item with.
+        status_code: u16,
+
+        /// A message the server rejected an item with.
+        message: String,
+    },
+
+    /// Sending an item failed with an error retries would not fix, e.g. a misconfigured endpoint.
+    Failed {
+        /// A message of the error.
+        message: String,
+    },
+}
+
+/// A telemetry item that could not be delivered to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    reason: DeadLetterReason,
+    envelope: Envelope,
+}
+
+impl DeadLetter {
+    /// Creates a new dead letter for a telemetry item.
+    pub fn new(reason: DeadLetterReason, envelope: Envelope) -> Self {
+        Self { reason, envelope }
+    }
+
+    /// Returns a reason a telemetry item could not be delivered.
+    pub fn reason(&self) -> &DeadLetterReason {
+        &self.reason
+    }
+
+    /// Returns a telemetry item that could not be delivered.
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    /// Returns a telemetry item, e.g. to track it again.
+    pub fn into_envelope(self) -> Envelope {
+        self.envelope
+    }
+}
+
+/// A storage of telemetry items that could not be delivered to the server.
+pub trait DeadLetterSink: Send + Sync {
+    /// Deposits telemetry items that could not be delivered.
+    fn deposit(&self, letters: &[DeadLetter]) -> io::Result<()>;
+}
+
+/// A dead letter sink that appends telemetry items to a file in
+/// [NDJSON](http://ndjson.org) format.
+///
+/// Every line is a JSON object with a `reason` field that is one of `retriesExhausted`, `expired`
+/// or `rejected`, `statusCode` and `message` fields of the server for rejected items and an
+/// `envelope` field with a telemetry item the way it is sent to the server.
+#[derive(Debug)]
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileDeadLetterSink {
+    /// Creates a new dead letter sink that appends telemetry items to a file at a given path. The
+    /// file is created when the first item is deposited.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::default(),
+        }
+    }
+}
+
+impl DeadLetterSink for FileDeadLetterSink {
+    fn deposit(&self, letters: &[DeadLetter]) -> io::Result<()> {
+        let mut content = Vec::new();
+        for letter in letters {
+            serde_json::to_writer(&mut content, &Line::from(letter))?;
+            content.push(b'\n');
+        }
+
+        // lines of batches deposited concurrently must not interleave
+        let _lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(&content)?;
+        file.flush()
+    }
+}
+
+/// A line of a dead letter file.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Line<'a> {
+    reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+    envelope: &'a Envelope,
+}
+
+impl<'a> From<&'a DeadLetter> for Line<'a> {
+    fn from(letter: &'a DeadLetter) -> Self {
+        let (reason, status_code, message) = match &letter.reason {
+            DeadLetterReason::RetriesExhausted => ("retriesExhausted", None, None),
+            DeadLetterReason::Expired => ("expired", None, None),
+            DeadLetterReason::QueueFull => ("queueFull", None, None),
+            DeadLetterReason::Rejected { status_code, message } => {
+                ("rejected", Some(*status_code), Some(message.as_str()))
+            }
+            DeadLetterReason::Failed { message } => ("failed", None, Some(message.as_str())),
+        };
+
+        Self {
+            reason,
+            status_code,
+            message,
+            envelope: &letter.envelope,
+        }
+    }
+}
+
+/// A dead letter sink shared between a configuration, a channel and a transmitter. It makes a sink
+/// comparable and printable as part of a configuration.
+///
+/// Letters are deposited on a dedicated thread in the order they are handed over, so file IO of a
+/// sink doesn't block the async runtime a channel runs on. The thread is started with the first
+/// deposit.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedDeadLetterSink(Shared<DeadLetterWriter>);
+
+impl SharedDeadLetterSink {
+    pub(crate) fn new(sink: impl DeadLetterSink + 'static) -> Self {
+        Self(Shared::new(DeadLetterWriter {
+            sink: Arc::new(sink),
+            commands: Mutex::default(),
+        }))
+    }
+
+    /// Deposits telemetry items with the same reason and reports when the sink fails.
+    pub(crate) fn deposit(&self, reason: DeadLetterReason, items: Vec<Envelope>, logger: &InternalLogger) {
+        let letters = items
+            .into_iter()
+            .map(|envelope| DeadLetter::new(reason.clone(), envelope))
+            .collect();
+        self.deposit_letters(letters, logger);
+    }
+
+    /// Deposits dead letters and reports when the sink fails.
+    pub(crate) fn deposit_letters(&self, letters: Vec<DeadLetter>, logger: &InternalLogger) {
+        if letters.is_empty() {
+            return;
+        }
+
+        self.0.send(Command::Deposit(letters, logger.clone()));
+    }
+
+    /// Waits until all letters handed over so far are deposited.
+    pub(crate) async fn drained(&self) {
+        let (sender, receiver) = oneshot::channel();
+        if self.0.send_if_started(Command::Drain(sender)) {
+            let _ = receiver.await;
+        }
+    }
+}
+
+/// A sink along with a thread that deposits letters to it.
+struct DeadLetterWriter {
+    sink: Arc<dyn DeadLetterSink>,
+    commands: Mutex<Option<mpsc::Sender<Command>>>,
+}
+
+impl DeadLetterWriter {
+    /// Hands a command over to the thread. The thread is started if it is not running yet.
+    fn send(&self, command: Command) {
+        let mut commands = self.commands.lock().unwrap_or_else(PoisonError::into_inner);
+        let command = match commands.as_ref() {
+            Some(sender) => match sender.send(command) {
+                Ok(()) => return,
+                Err(mpsc::SendError(command)) => command,
+            },
+            None => command,
+        };
+
+        let sender = self.start();
+        let _ = sender.send(command);
+        *commands = Some(sender);
+    }
+
+    /// Hands a command over to the thread if it is running. Returns `false` otherwise.
+    fn send_if_started(&self, command: Command) -> bool {
+        let commands = self.commands.lock().unwrap_or_else(PoisonError::into_inner);
+        commands.as_ref().is_some_and(|commands| commands.send(command).is_ok())
+    }
+
+    fn start(&self) -> mpsc::Sender<Command> {
+        let (sender, receiver) = mpsc::channel();
+        let sink = self.sink.clone();
+        let spawned = thread::Builder::new()
+            .name("appinsights-dead-letters".into())
+            .spawn(move || {
+                for command in receiver {
+                    match command {
+                        Command::Deposit(letters, logger) => {
+                            let deposited = panic::catch_unwind(AssertUnwindSafe(|| sink.deposit(&letters)))
+                                .unwrap_or_else(|_| Err(io::Error::other("dead letter sink panicked")));
+                            if let Err(err) = deposited {
+                                logger.log(InternalEvent::DeadLettersNotDeposited {
+                                    count: letters.len(),
+                                    error: err.to_string(),
+                                });
+                            }
+                        }
+                        Command::Drain(drained) => {
+                            let _ = drained.send(());
+                        }
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            error!("Unable to start a thread to deposit dead letters: {}", err);
+        }
+        sender
+    }
+}
+
+/// A command to a thread that deposits dead letters.
+enum Command {
+    Deposit(Vec<DeadLetter>, InternalLogger),
+    Drain(oneshot::Sender<()>),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[test]
+    fn it_appends_dead_letters_to_file() {
+        let path = crate::test::temp_path("ndjson");
+        let sink = FileDeadLetterSink::new(&path);
+
+        sink.deposit(&[letter(DeadLetterReason::RetriesExhausted, "first")])
+            .unwrap();
+        sink.deposit(&[
+            letter(DeadLetterReason::Expired, "second"),
+            letter(
+                DeadLetterReason::Rejected {
+                    status_code: 400,
+                    message: "invalid".into(),
+                },
+                "third",
+            ),
+            letter(
+                DeadLetterReason::Failed {
+                    message: "invalid endpoint".into(),
+                },
+                "fourth",
+            ),
+        ])
+        .unwrap();
+
+        let lines: Vec<Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["reason"], json!("retriesExhausted"));
+        assert_eq!(lines[0]["envelope"]["name"], json!("first"));
+        assert_eq!(lines[1]["reason"], json!("expired"));
+        assert_eq!(lines[1].get("statusCode"), None);
+        assert_eq!(lines[2]["reason"], json!("rejected"));
+        assert_eq!(lines[2]["statusCode"], json!(400));
+        assert_eq!(lines[2]["message"], json!("invalid"));
+        assert_eq!(lines[2]["envelope"]["name"], json!("third"));
+        assert_eq!(lines[3]["reason"], json!("failed"));
+        assert_eq!(lines[3].get("statusCode"), None);
+        assert_eq!(lines[3]["message"], json!("invalid endpoint"));
+    }
+
+    fn letter(reason: DeadLetterReason, name: &str) -> DeadLetter {
+        DeadLetter::new(
+            reason,
+            Envelope {
+                name: name.into(),
+                ..Envelope::default()
+            },
+        )
+    }
+}
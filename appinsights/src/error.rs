@@ -0,0 +1,174 @@
+//! Errors reported by the telemetry pipeline.
+use std::{error::Error as StdError, io};
+
+use http::StatusCode;
+
+pub use crate::contracts::{Transmission, TransmissionItem};
+use crate::InvalidConfig;
+
+/// A result of an operation of the telemetry pipeline.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error reported by the telemetry pipeline, e.g. as the
+/// [status](crate::ShutdownReport::status) of a submission that failed.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # async fn run() {
+/// use appinsights::{Error, TelemetryClient, TransmissionStatus};
+///
+/// let client = TelemetryClient::new("<instrumentation key>".to_string());
+/// let report = client.close_channel().await;
+/// if let Some(TransmissionStatus::Failed(error)) = report.status() {
+///     match error.as_ref() {
+///         Error::Response { status, .. } => eprintln!("server responded with {}", status),
+///         error if error.is_retryable() => eprintln!("temporary failure: {}", error),
+///         error => eprintln!("telemetry was not sent: {}", error),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A telemetry configuration contains invalid settings.
+    #[error(transparent)]
+    Config(#[from] InvalidConfig),
+
+    /// Telemetry items or a response of the server could not be serialized or deserialized.
+    #[error("serialization failed: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// Telemetry items could not be delivered to the server or a local agent.
+    #[error("transport failed: {source}")]
+    Transport {
+        /// Whether the same telemetry items may be delivered when sent again.
+        retryable: bool,
+
+        /// The underlying error of an HTTP client or a connection.
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// The server responded in a way telemetry items cannot be accounted for.
+    #[error("unexpected response of the server with status {status}: {message}")]
+    Response {
+        /// A status code of the response.
+        status: StatusCode,
+
+        /// A description of what is wrong with the response.
+        message: String,
+
+        /// Telemetry items the server accepted and rejected, if the response reported them.
+        transmission: Option<Transmission>,
+    },
+
+    /// A telemetry channel was shut down before telemetry items were sent.
+    #[error("telemetry channel is shut down")]
+    Shutdown,
+}
+
+impl Error {
+    /// Returns `true` if sending the same telemetry items again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport { retryable, .. } => *retryable,
+            Self::Response { status, .. } => status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS,
+            Self::Config(_) | Self::Serialization(_) | Self::Shutdown => false,
+        }
+    }
+
+    /// Returns a response of the server that describes which telemetry items it accepted, if any.
+    pub fn transmission(&self) -> Option<&Transmission> {
+        match self {
+            Self::Response { transmission, .. } => transmission.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        let retryable = !matches!(err.kind(), io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData);
+        Self::Transport {
+            retryable,
+            source: err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Transport {
+            retryable: !err.is_builder(),
+            source: err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "hyper-client")]
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Self {
+        Self::Transport {
+            retryable: !err.is_user(),
+            source: err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "hyper-client")]
+impl From<http::Error> for Error {
+    fn from(err: http::Error) -> Self {
+        Self::Transport {
+            retryable: false,
+            source: err.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_classifies_retryable_errors() {
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert!(Error::from(refused).is_retryable());
+
+        let too_large = io::Error::new(io::ErrorKind::InvalidInput, "too large");
+        assert!(!Error::from(too_large).is_retryable());
+
+        let unavailable = Error::Response {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "unavailable".into(),
+            transmission: None,
+        };
+        assert!(unavailable.is_retryable());
+        assert!(!Error::Shutdown.is_retryable());
+    }
+
+    #[test]
+    fn it_exposes_transmission_of_response() {
+        let transmission = Transmission {
+            items_received: 2,
+            items_accepted: 1,
+            errors: vec![TransmissionItem {
+                index: 1,
+                status_code: 400,
+                message: "invalid".into(),
+            }],
+        };
+        let error = Error::Response {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            message: "invalid Retry-After header".into(),
+            transmission: Some(transmission.clone()),
+        };
+
+        assert_eq!(error.transmission(), Some(&transmission));
+        assert_eq!(
+            error.to_string(),
+            "unexpected response of the server with status 429 Too Many Requests: invalid Retry-After header"
+        );
+    }
+}
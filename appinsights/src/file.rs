@@ -0,0 +1,74 @@
+//! Atomic writes of small files that keep state of a pipeline across restarts.
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Replaces the content of a file at a given path atomically.
+///
+/// The content is written to a temporary file with a unique name next to the target one first and
+/// then renamed, so the file always contains complete content and concurrent writes never share a
+/// temporary file. The temporary file is flushed to disk before it is renamed when `sync` is set, so
+/// the content survives a power loss as well.
+pub(crate) fn write_atomically(path: &Path, content: &[u8], sync: bool) -> io::Result<()> {
+    let temp = temp_path(path);
+    let written = File::create(&temp).and_then(|mut file| {
+        file.write_all(content)?;
+        if sync {
+            file.sync_all()?;
+        }
+        fs::rename(&temp, path)
+    });
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Returns a unique path of a temporary file next to a given one, e.g.
+/// `telemetry.seq.<uuid>.tmp` for `telemetry.seq`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(format!(".{}.tmp", crate::uuid::new().as_simple()));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn it_writes_temporary_file_next_to_target_one() {
+        let temp = temp_path(Path::new("/var/lib/app/telemetry.seq"));
+
+        assert_eq!(temp.parent(), Some(Path::new("/var/lib/app")));
+        let name = temp.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with("telemetry.seq."), "{}", name);
+        assert!(name.ends_with(".tmp"), "{}", name);
+        assert_ne!(temp, temp_path(Path::new("/var/lib/app/telemetry.seq")));
+    }
+
+    #[test]
+    fn it_replaces_file_with_content_of_one_of_concurrent_writes() {
+        let path = Arc::new(crate::test::temp_path("state"));
+
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || write_atomically(&path, format!("{:04}", i).as_bytes(), false))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+
+        let content = fs::read_to_string(&**path).unwrap();
+        assert_eq!(content.len(), 4);
+        assert!(content.parse::<u32>().unwrap() < 8);
+    }
+}
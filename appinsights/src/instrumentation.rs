@@ -0,0 +1,69 @@
+//! Instrumentation of a submission pipeline with `tracing` spans and events.
+//!
+//! With the `diagnostics` feature enabled, a submission worker runs in a span, every batch is
+//! sent in a child span and every request to the server or a local agent is reported with an
+//! event. Fields of the event follow naming conventions of metrics layers, e.g.
+//! `tracing-opentelemetry`, so counters of batches and bytes sent and a histogram of latencies
+//! are available without a separate metrics dependency. Without the feature, all functions are
+//! no-ops. The same counters and histogram are reported to a recorder of a metrics library with
+//! the `metrics` feature, see [`pipeline_metrics`](crate::pipeline_metrics).
+use std::{future::Future, time::Duration};
+
+use crate::{transmitter::HttpResponse, Result};
+
+/// A target of all spans and events of a submission pipeline.
+#[cfg(feature = "diagnostics")]
+const TARGET: &str = "appinsights::pipeline";
+
+/// Runs a submission worker in a span.
+#[cfg(feature = "diagnostics")]
+pub(crate) fn in_worker_span<F: Future>(worker: F) -> impl Future<Output = F::Output> {
+    use tracing::Instrument;
+    worker.instrument(tracing::info_span!(target: TARGET, "appinsights.worker"))
+}
+
+/// Runs a submission worker in a span.
+#[cfg(not(feature = "diagnostics"))]
+pub(crate) fn in_worker_span<F: Future>(worker: F) -> F {
+    worker
+}
+
+/// Sends a batch of a given number of items in a span.
+#[cfg(feature = "diagnostics")]
+pub(crate) fn in_batch_span<F: Future>(count: usize, send: F) -> impl Future<Output = F::Output> {
+    use tracing::Instrument;
+    send.instrument(tracing::debug_span!(target: TARGET, "appinsights.batch", items = count))
+}
+
+/// Sends a batch of a given number of items in a span.
+#[cfg(not(feature = "diagnostics"))]
+pub(crate) fn in_batch_span<F: Future>(_count: usize, send: F) -> F {
+    send
+}
+
+/// Reports a request with a payload of a given number of items and bytes and how long it took.
+#[cfg_attr(not(feature = "diagnostics"), allow(unused_variables))]
+pub(crate) fn record_request(count: usize, bytes: usize, latency: Duration, result: &Result<HttpResponse>) {
+    #[cfg(feature = "diagnostics")]
+    match result {
+        Ok(response) => tracing::debug!(
+            target: TARGET,
+            status = response.status().as_u16(),
+            items = count,
+            monotonic_counter.appinsights_batches_sent = 1_u64,
+            monotonic_counter.appinsights_items_sent = count as u64,
+            monotonic_counter.appinsights_bytes_sent = bytes as u64,
+            histogram.appinsights_request_latency_ms = latency.as_secs_f64() * 1000.0,
+            "batch sent"
+        ),
+        Err(err) => tracing::warn!(
+            target: TARGET,
+            error = %err,
+            retryable = err.is_retryable(),
+            items = count,
+            monotonic_counter.appinsights_batches_failed = 1_u64,
+            histogram.appinsights_request_latency_ms = latency.as_secs_f64() * 1000.0,
+            "batch not sent"
+        ),
+    }
+}
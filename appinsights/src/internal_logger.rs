@@ -0,0 +1,427 @@
+//! SDK self-diagnostics.
+//!
+//! Notable events of the telemetry submission flow, like dropped items or failed transmissions,
+//! are reported to the [`log`](https://docs.rs/log) crate with the [`TARGET`] target, so they can
+//! be filtered separately from application logs. Events can also be submitted as trace telemetry
+//! to a separate diagnostics resource configured with `diagnostics_i_key` option of
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder).
+//!
+//! ```rust
+//! use appinsights::TelemetryConfig;
+//! use log::LevelFilter;
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .internal_log_level(LevelFilter::Info)
+//!     .diagnostics_i_key("<diagnostics instrumentation key>")
+//!     .build();
+//! ```
+use std::{any::Any, fmt::Display, sync::Arc};
+
+use log::{Level, LevelFilter};
+
+use crate::{
+    channel::{InMemoryChannel, TelemetryChannel},
+    telemetry::{SeverityLevel, Telemetry, TraceTelemetry},
+    TelemetryConfig, TelemetryContext,
+};
+
+/// The `log` target of SDK self-diagnostics events.
+pub const TARGET: &str = "appinsights::internal";
+
+/// An event that happened within the telemetry submission flow.
+#[derive(Debug)]
+pub(crate) enum InternalEvent {
+    /// A submission worker has started.
+    WorkerStarted,
+
+    /// A submission worker has stopped.
+    WorkerStopped { flushed: usize, abandoned: usize },
+
+    /// A submission worker received a command.
+    CommandReceived { command: String },
+
+    /// A submission interval elapsed. Only every few ticks are reported to keep the noise down.
+    IntervalElapsed { ticks: u64, pending: usize },
+
+    /// Items were dropped because they waited to be sent longer than a configured time to live.
+    ItemsExpired { count: usize, total: usize },
+
+    /// Items were dropped because a queue held more items than allowed.
+    QueueOverflowed { count: usize, total: usize },
+
+    /// Items waiting to be sent were discarded by an application.
+    PendingCleared { count: usize },
+
+    /// Items were dropped after all retry attempts have been exhausted.
+    RetriesExhausted { count: usize },
+
+    /// Items were dropped instead of being retried because they were older than a maximum age to
+    /// retry items of their category.
+    RetriesSkipped { count: usize },
+
+    /// Items could not be sent to the server.
+    TransmissionFailed { count: usize, error: String },
+
+    /// Items were dropped because they could not be serialized.
+    SerializationFailed { count: usize, error: String },
+
+    /// Sending items panicked. Items are retried later.
+    TransmissionPanicked { count: usize, message: String },
+
+    /// The highest acknowledged sequence number could not be loaded. Numbering starts over.
+    SequenceNotLoaded { error: String },
+
+    /// An acknowledged sequence number could not be saved.
+    SequenceNotSaved { sequence: u64, error: String },
+
+    /// A throttle deadline saved before could not be loaded. Items are sent right away.
+    ThrottleNotLoaded { error: String },
+
+    /// A throttle deadline could not be saved.
+    ThrottleNotSaved { until: String, error: String },
+
+    /// Telemetry items that could not be delivered could not be deposited to a dead letter sink.
+    DeadLettersNotDeposited { count: usize, error: String },
+
+    /// A custom worker task panicked. The worker keeps running.
+    WorkerTaskPanicked { message: String },
+
+    /// A custom drop policy panicked. The oldest items are dropped instead.
+    DropPolicyPanicked { message: String },
+
+    /// The local clock differs from the server clock by a given number of milliseconds.
+    ClockSkewDetected { skew_ms: i64 },
+}
+
+impl InternalEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            InternalEvent::WorkerStarted => "WorkerStarted",
+            InternalEvent::WorkerStopped { .. } => "WorkerStopped",
+            InternalEvent::CommandReceived { .. } => "CommandReceived",
+            InternalEvent::IntervalElapsed { .. } => "IntervalElapsed",
+            InternalEvent::ItemsExpired { .. } => "ItemsExpired",
+            InternalEvent::QueueOverflowed { .. } => "QueueOverflowed",
+            InternalEvent::PendingCleared { .. } => "PendingCleared",
+            InternalEvent::RetriesExhausted { .. } => "RetriesExhausted",
+            InternalEvent::RetriesSkipped { .. } => "RetriesSkipped",
+            InternalEvent::TransmissionFailed { .. } => "TransmissionFailed",
+            InternalEvent::SerializationFailed { .. } => "SerializationFailed",
+            InternalEvent::TransmissionPanicked { .. } => "TransmissionPanicked",
+            InternalEvent::SequenceNotLoaded { .. } => "SequenceNotLoaded",
+            InternalEvent::SequenceNotSaved { .. } => "SequenceNotSaved",
+            InternalEvent::ThrottleNotLoaded { .. } => "ThrottleNotLoaded",
+            InternalEvent::ThrottleNotSaved { .. } => "ThrottleNotSaved",
+            InternalEvent::DeadLettersNotDeposited { .. } => "DeadLettersNotDeposited",
+            InternalEvent::WorkerTaskPanicked { .. } => "WorkerTaskPanicked",
+            InternalEvent::DropPolicyPanicked { .. } => "DropPolicyPanicked",
+            InternalEvent::ClockSkewDetected { .. } => "ClockSkewDetected",
+        }
+    }
+
+    fn level(&self) -> Level {
+        match self {
+            InternalEvent::WorkerStarted | InternalEvent::WorkerStopped { .. } => Level::Info,
+            InternalEvent::CommandReceived { .. } => Level::Debug,
+            InternalEvent::IntervalElapsed { .. } => Level::Trace,
+            InternalEvent::ItemsExpired { .. } | InternalEvent::RetriesExhausted { .. } => Level::Warn,
+            InternalEvent::QueueOverflowed { .. } | InternalEvent::PendingCleared { .. } => Level::Warn,
+            InternalEvent::RetriesSkipped { .. } => Level::Warn,
+            InternalEvent::TransmissionFailed { .. } => Level::Warn,
+            InternalEvent::SequenceNotLoaded { .. } | InternalEvent::SequenceNotSaved { .. } => Level::Warn,
+            InternalEvent::ThrottleNotLoaded { .. } | InternalEvent::ThrottleNotSaved { .. } => Level::Warn,
+            InternalEvent::SerializationFailed { .. } | InternalEvent::TransmissionPanicked { .. } => Level::Error,
+            InternalEvent::DeadLettersNotDeposited { .. } | InternalEvent::WorkerTaskPanicked { .. } => Level::Error,
+            InternalEvent::DropPolicyPanicked { .. } => Level::Error,
+            InternalEvent::ClockSkewDetected { .. } => Level::Warn,
+        }
+    }
+
+    /// Returns event fields as key-value pairs, so an event can be found without parsing its message.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            InternalEvent::WorkerStarted => vec![],
+            InternalEvent::WorkerStopped { flushed, abandoned } => {
+                vec![("flushed", flushed.to_string()), ("abandoned", abandoned.to_string())]
+            }
+            InternalEvent::CommandReceived { command } => vec![("command", command.clone())],
+            InternalEvent::IntervalElapsed { ticks, pending } => {
+                vec![("ticks", ticks.to_string()), ("pending", pending.to_string())]
+            }
+            InternalEvent::ItemsExpired { count, total } | InternalEvent::QueueOverflowed { count, total } => {
+                vec![("count", count.to_string()), ("total", total.to_string())]
+            }
+            InternalEvent::PendingCleared { count }
+            | InternalEvent::RetriesExhausted { count }
+            | InternalEvent::RetriesSkipped { count } => {
+                vec![("count", count.to_string())]
+            }
+            InternalEvent::TransmissionFailed { count, error }
+            | InternalEvent::SerializationFailed { count, error }
+            | InternalEvent::DeadLettersNotDeposited { count, error } => {
+                vec![("count", count.to_string()), ("error", error.clone())]
+            }
+            InternalEvent::TransmissionPanicked { count, message } => {
+                vec![("count", count.to_string()), ("message", message.clone())]
+            }
+            InternalEvent::SequenceNotLoaded { error } | InternalEvent::ThrottleNotLoaded { error } => {
+                vec![("error", error.clone())]
+            }
+            InternalEvent::SequenceNotSaved { sequence, error } => {
+                vec![("sequence", sequence.to_string()), ("error", error.clone())]
+            }
+            InternalEvent::ThrottleNotSaved { until, error } => {
+                vec![("until", until.clone()), ("error", error.clone())]
+            }
+            InternalEvent::WorkerTaskPanicked { message } | InternalEvent::DropPolicyPanicked { message } => {
+                vec![("message", message.clone())]
+            }
+            InternalEvent::ClockSkewDetected { skew_ms } => vec![("skew_ms", skew_ms.to_string())],
+        }
+    }
+}
+
+impl Display for InternalEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InternalEvent::WorkerStarted => write!(f, "Submission worker started"),
+            InternalEvent::WorkerStopped { flushed, abandoned } => write!(
+                f,
+                "Submission worker stopped ({} telemetry items flushed, {} abandoned)",
+                flushed, abandoned
+            ),
+            InternalEvent::CommandReceived { command } => write!(f, "Command received: {}", command),
+            InternalEvent::IntervalElapsed { ticks, pending } => write!(
+                f,
+                "Submission interval elapsed {} times ({} telemetry items pending)",
+                ticks, pending
+            ),
+            InternalEvent::ItemsExpired { count, total } => write!(
+                f,
+                "Dropped {} telemetry items older than time to live ({} dropped in total)",
+                count, total
+            ),
+            InternalEvent::QueueOverflowed { count, total } => write!(
+                f,
+                "Dropped {} telemetry items exceeding maximum queue size ({} dropped in total)",
+                count, total
+            ),
+            InternalEvent::PendingCleared { count } => {
+                write!(f, "Discarded {} pending telemetry items on request", count)
+            }
+            InternalEvent::RetriesExhausted { count } => {
+                write!(f, "Dropped {} telemetry items after all retries exhausted", count)
+            }
+            InternalEvent::RetriesSkipped { count } => {
+                write!(f, "Dropped {} telemetry items too old to be sent again", count)
+            }
+            InternalEvent::TransmissionFailed { count, error } => {
+                write!(f, "Unable to send {} telemetry items: {}", count, error)
+            }
+            InternalEvent::SerializationFailed { count, error } => {
+                write!(
+                    f,
+                    "Dropped {} telemetry items that could not be serialized: {}",
+                    count, error
+                )
+            }
+            InternalEvent::TransmissionPanicked { count, message } => {
+                write!(
+                    f,
+                    "Sending {} telemetry items panicked: {}. Retry them later",
+                    count, message
+                )
+            }
+            InternalEvent::SequenceNotLoaded { error } => {
+                write!(f, "Unable to load acknowledged sequence number: {}", error)
+            }
+            InternalEvent::SequenceNotSaved { sequence, error } => {
+                write!(f, "Unable to save acknowledged sequence number {}: {}", sequence, error)
+            }
+            InternalEvent::ThrottleNotLoaded { error } => {
+                write!(f, "Unable to load throttle deadline: {}", error)
+            }
+            InternalEvent::ThrottleNotSaved { until, error } => {
+                write!(f, "Unable to save throttle deadline {}: {}", until, error)
+            }
+            InternalEvent::DeadLettersNotDeposited { count, error } => {
+                write!(
+                    f,
+                    "Unable to deposit {} undeliverable telemetry items to dead letter sink: {}",
+                    count, error
+                )
+            }
+            InternalEvent::WorkerTaskPanicked { message } => write!(f, "Worker task panicked: {}", message),
+            InternalEvent::DropPolicyPanicked { message } => {
+                write!(f, "Drop policy panicked: {}. Drop the oldest items instead", message)
+            }
+            InternalEvent::ClockSkewDetected { skew_ms } => {
+                write!(f, "Local clock differs from the server clock by {} ms", skew_ms)
+            }
+        }
+    }
+}
+
+/// Reports SDK self-diagnostics events with a severity filter.
+#[derive(Clone)]
+pub(crate) struct InternalLogger {
+    level: LevelFilter,
+    diagnostics: Option<Diagnostics>,
+}
+
+/// A destination for self-diagnostics events submitted as trace telemetry.
+#[derive(Clone)]
+struct Diagnostics {
+    context: TelemetryContext,
+    channel: Arc<dyn TelemetryChannel>,
+}
+
+impl InternalLogger {
+    /// Creates a new logger configured with a specified configuration. When a diagnostics
+    /// instrumentation key is configured, it starts a separate channel to submit events.
+    pub fn from_config(config: &TelemetryConfig) -> Self {
+        let diagnostics = config.diagnostics_i_key().map(|i_key| {
+            let config = TelemetryConfig::builder()
+                .i_key(i_key)
+                .endpoint(config.endpoint())
+                .interval(config.interval())
+                .manual_flush_only(config.is_manual_flush_only())
+                .internal_log_level(config.internal_log_level())
+                .build();
+            let context = TelemetryContext::from_config(&config);
+            let channel: Arc<dyn TelemetryChannel> = Arc::new(InMemoryChannel::new(&config));
+            Diagnostics { context, channel }
+        });
+
+        Self {
+            level: config.internal_log_level(),
+            diagnostics,
+        }
+    }
+
+    /// Reports an event if its severity passes a configured filter.
+    pub fn log(&self, event: InternalEvent) {
+        let level = event.level();
+        if level > self.level {
+            return;
+        }
+
+        log::log!(target: TARGET, level, "{}", event);
+
+        if let Some(diagnostics) = &self.diagnostics {
+            let mut trace = TraceTelemetry::new(event.to_string(), severity(level));
+            trace.properties_mut().insert("event".into(), event.name().into());
+            for (name, value) in event.fields() {
+                trace.properties_mut().insert(name.into(), value);
+            }
+            diagnostics.channel.send((diagnostics.context.clone(), trace).into());
+        }
+    }
+
+    /// Submits pending diagnostics events and closes a diagnostics channel if any.
+    pub async fn close(&self) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.channel.close().await;
+        }
+    }
+}
+
+/// Converts a log level to a corresponding telemetry severity level.
+fn severity(level: Level) -> SeverityLevel {
+    match level {
+        Level::Error => SeverityLevel::Error,
+        Level::Warn => SeverityLevel::Warning,
+        Level::Info => SeverityLevel::Information,
+        Level::Debug | Level::Trace => SeverityLevel::Verbose,
+    }
+}
+
+/// Extracts a message from a panic payload.
+pub(crate) fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+    };
+
+    #[test]
+    fn it_submits_events_to_diagnostics_channel() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(LevelFilter::Warn, events.clone());
+
+        logger.log(InternalEvent::RetriesExhausted { count: 3 });
+
+        let envelope: Envelope = events.pop().unwrap();
+        assert_eq!(envelope.i_key, Some("diagnostics".into()));
+        match envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => {
+                assert_eq!(data.message, "Dropped 3 telemetry items after all retries exhausted");
+                assert_eq!(data.properties.unwrap().get("event"), Some(&"RetriesExhausted".into()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_submits_event_fields_as_properties() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(LevelFilter::Debug, events.clone());
+
+        logger.log(InternalEvent::CommandReceived {
+            command: "close".into(),
+        });
+        logger.log(InternalEvent::WorkerStopped {
+            flushed: 5,
+            abandoned: 2,
+        });
+
+        let properties = |envelope: Envelope| match envelope.data {
+            Some(Base::Data(Data::MessageData(data))) => data.properties.unwrap(),
+            data => panic!("unexpected data: {:?}", data),
+        };
+
+        let command = properties(events.pop().unwrap());
+        assert_eq!(command.get("event"), Some(&"CommandReceived".into()));
+        assert_eq!(command.get("command"), Some(&"close".into()));
+
+        let stopped = properties(events.pop().unwrap());
+        assert_eq!(stopped.get("event"), Some(&"WorkerStopped".into()));
+        assert_eq!(stopped.get("flushed"), Some(&"5".into()));
+        assert_eq!(stopped.get("abandoned"), Some(&"2".into()));
+    }
+
+    #[test]
+    fn it_skips_events_below_configured_level() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(LevelFilter::Warn, events.clone());
+
+        logger.log(InternalEvent::WorkerStarted);
+
+        assert!(events.is_empty());
+    }
+
+    fn create_logger(level: LevelFilter, events: Arc<SegQueue<Envelope>>) -> InternalLogger {
+        let config = TelemetryConfig::new("diagnostics".into());
+        InternalLogger {
+            level,
+            diagnostics: Some(Diagnostics {
+                context: TelemetryContext::from_config(&config),
+                channel: Arc::new(TestChannel::new(events)),
+            }),
+        }
+    }
+}
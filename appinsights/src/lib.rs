@@ -10,6 +10,7 @@
 //! * [Availability telemetry](telemetry/struct.AvailabilityTelemetry.html)
 //! * [Event telemetry](telemetry/struct.EventTelemetry.html)
 //! * [Page view telemetry](telemetry/struct.PageViewTelemetry.html)
+//! * [Page view performance telemetry](telemetry/struct.PageViewPerformanceTelemetry.html)
 //! * [Remote dependency telemetry](telemetry/struct.RemoteDependencyTelemetry.html)
 //! * [Request telemetry](telemetry/struct.RequestTelemetry.html)
 //! * [Trace telemetry](telemetry/struct.TraceTelemetry.html)
@@ -26,6 +27,47 @@
 //! Obtain Instrumentation Key by creating a new instance of [Application Insights](https://docs.microsoft.com/en-us/azure/azure-monitor/app/create-new-resource)
 //! service.
 //!
+//! The data model, i.e. telemetry items, a telemetry context and processors, is provided by the
+//! `appinsights-core` crate and re-exported here. It does not depend on Tokio or an HTTP client,
+//! so applications that only build envelopes, e.g. on embedded or WASM targets, can depend on it
+//! alone.
+//!
+//! ## Features
+//!
+//! Integrations are opt-in, so applications that don't use them don't pay for their dependencies.
+//! * `default` uses the default TLS implementation of `reqwest`.
+//! * `rustls` uses `rustls` instead of the default TLS implementation.
+//! * `hyper-client` sends telemetry with a lightweight client built on `hyper` and `rustls`. It is
+//!   used instead of `reqwest` when default features are disabled, e.g.
+//!   `appinsights = { version = "*", default-features = false, features = ["hyper-client"] }`.
+//!   The client connects through a proxy configured with `HTTPS_PROXY`, `HTTP_PROXY` and
+//!   `NO_PROXY` environment variables. Proxy authentication and a custom DNS resolver are not
+//!   supported.
+//! * `blocking` enables a [`blocking`](blocking) client for applications without Tokio runtime.
+//! * `tower` enables a middleware that tracks requests handled by `tower` services.
+//! * `reqwest-middleware` enables a middleware that tracks requests sent by `reqwest` clients.
+//! * `tonic` enables middlewares that track gRPC calls handled and sent by `tonic` servers and
+//!   channels.
+//! * `actix-web` enables an [`actix-web`](actix_web) middleware that tracks requests named by
+//!   their route templates and panics of handlers.
+//! * `log` enables a [`logger`](log) that tracks `log` records as trace telemetry.
+//! * `sqlx` tracks statements executed by `sqlx` as [`SQL dependencies`](sqlx) with the `log`
+//!   integration.
+//! * `tracing` enables a [`layer`](tracing) that tracks `tracing` spans as in-process
+//!   dependencies, optionally only those longer than a threshold, and conversions from `tracing`
+//!   levels to severity levels.
+//! * `full` enables all integrations listed above.
+//! * `agent` forwards telemetry items to a local [`agent`](agent), e.g. an OpenTelemetry
+//!   collector or a sidecar, over TCP or a Unix domain socket instead of sending them to the server.
+//! * `diagnostics` instruments the submission pipeline with `tracing` spans and events, incl.
+//!   counters of batches and bytes sent and a histogram of request latencies, so it can be
+//!   observed with a standard observability stack.
+//! * `metrics` reports the same counters and histogram with the [`metrics`](pipeline_metrics)
+//!   crate.
+//! * `json-schema` enables a [`schema`](schema) of telemetry items sent to the server.
+//! * `pool` enables a [`pool`](pool) of envelopes. Envelopes of delivered telemetry items are
+//!   recycled and reused by new items instead of allocating them anew.
+//!
 //! ## Examples
 //!
 //! 1. Create an new instance of [`TelemetryClient`](struct.TelemetryClient.html) with an
@@ -74,6 +116,10 @@
 //! client.close_channel().await
 //! ```
 //!
+//! A [`TelemetryClientBuilder`](struct.TelemetryClientBuilder.html) created with
+//! [`TelemetryClient::builder`](struct.TelemetryClient.html#method.builder) additionally allows to
+//! choose a channel, seed a telemetry context and add processors.
+//!
 //! ## Telemetry submission
 //!
 //! A [`TelemetryClient`](struct.TelemetryClient.html) has several convenient methods to submit telemetry items.
@@ -108,7 +154,7 @@
 //! use appinsights::telemetry::{RequestTelemetry, Telemetry};
 //!
 //! // configure telemetry with default settings
-//! let mut client = TelemetryClient::new("instrumentation".to_string());
+//! let client = TelemetryClient::new("instrumentation".to_string());
 //!
 //! // set role instance name globally. This is usually the name of the service submitting the telemetry
 //! client.context_mut().tags_mut().cloud_mut().set_role("rust_server".to_string());
@@ -145,7 +191,7 @@
 //! use appinsights::telemetry::{RequestTelemetry, Telemetry};
 //!
 //! // configure telemetry with default settings
-//! let mut client = TelemetryClient::new("instrumentation".to_string());
+//! let client = TelemetryClient::new("instrumentation".to_string());
 //!
 //! // set custom telemetry item property globally
 //! client.context_mut().properties_mut().insert("Resource Group".to_string(), "my-rg".to_string());
@@ -170,28 +216,74 @@
 #![deny(unused_extern_crates)]
 #![deny(missing_docs)]
 
+#[cfg(not(any(feature = "reqwest", feature = "hyper-client")))]
+compile_error!("either `reqwest` (enabled by default) or `hyper-client` feature must be enabled to send telemetry");
+
+#[cfg(feature = "actix-web")]
+pub mod actix_web;
+#[cfg(feature = "agent")]
+pub mod agent;
+#[doc(inline)]
+pub use appinsights_core::ambient;
+pub mod availability;
 #[cfg(feature = "blocking")]
 pub mod blocking;
 
 mod channel;
+pub use channel::{ShutdownReport, TelemetryChannel, TransmissionStatus};
 
 mod client;
-pub use client::TelemetryClient;
+pub use client::{ShutdownHandle, TelemetryClient, TelemetryClientBuilder};
+
+pub mod clock;
 
 mod config;
 #[doc(inline)]
-pub use config::TelemetryConfig;
+pub use config::{InvalidConfig, TelemetryConfig};
 
-mod context;
-pub use context::TelemetryContext;
+pub use appinsights_core::TelemetryContext;
+use appinsights_core::{context, contracts, shared, time, uuid};
 
-mod contracts;
-pub mod telemetry;
-mod time;
+pub mod daily_cap;
+pub mod dead_letter;
+pub mod error;
+#[doc(inline)]
+pub use error::{Error, Result};
+mod file;
+mod instrumentation;
+pub mod internal_logger;
+#[cfg(feature = "log")]
+pub mod log;
+#[cfg(feature = "metrics")]
+pub mod pipeline_metrics;
+#[cfg(feature = "pool")]
+#[doc(inline)]
+pub use appinsights_core::pool;
+#[doc(inline)]
+pub use appinsights_core::processor;
+pub mod queue;
+#[cfg(feature = "json-schema")]
+#[doc(inline)]
+pub use appinsights_core::schema;
+
+#[cfg(feature = "reqwest-middleware")]
+pub mod reqwest_middleware;
+pub mod scheduler;
+pub mod sequence;
+#[cfg(feature = "sqlx")]
+pub mod sqlx;
+mod standard_metrics;
+#[doc(inline)]
+pub use appinsights_core::telemetry;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test;
+pub mod throttle;
 mod timeout;
+#[cfg(feature = "tonic")]
+pub mod tonic;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "tracing")]
+pub mod tracing;
 mod transmitter;
-mod uuid;
-
-use std::error::Error;
-
-type Result<T> = std::result::Result<T, Box<dyn Error>>;
+pub mod worker_task;
@@ -0,0 +1,197 @@
+//! A [`log`](https://docs.rs/log) integration that tracks log records as trace telemetry.
+//!
+//! [`AppInsightsLogger`] submits a [`TraceTelemetry`](crate::telemetry::TraceTelemetry) item for
+//! every record it is enabled for. A severity level of an item follows a level of a record, while a
+//! target, a module and a source location of the record are submitted as custom properties. Records
+//! of the `appinsights` crate itself are never tracked, so its internal diagnostics don't feed back
+//! into the channel that reports them.
+//!
+//! An item tracked within an [ambient](crate::ambient) operation becomes a part of that operation.
+//!
+//! ```rust, no_run
+//! use appinsights::{log::AppInsightsLogger, TelemetryClient};
+//! use log::LevelFilter;
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! AppInsightsLogger::new(client)
+//!     .with_level(LevelFilter::Warn)
+//!     .init()
+//!     .expect("no other logger installed");
+//!
+//! log::warn!("disk usage is above 90%");
+//! ```
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{
+    processor::ProcessingContext,
+    telemetry::{Telemetry, TraceTelemetry},
+    TelemetryClient,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "log";
+
+/// A target prefix of records of this crate that are never tracked.
+const OWN_TARGET: &str = "appinsights";
+
+/// A logger that submits a [`TraceTelemetry`](crate::telemetry::TraceTelemetry) for every log
+/// record.
+pub struct AppInsightsLogger {
+    client: TelemetryClient,
+    level: LevelFilter,
+    #[cfg(feature = "sqlx")]
+    database: crate::sqlx::Database,
+}
+
+impl AppInsightsLogger {
+    /// Creates a new logger that submits records of `Info` level and above with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self {
+            client,
+            level: LevelFilter::Info,
+            #[cfg(feature = "sqlx")]
+            database: crate::sqlx::Database::default(),
+        }
+    }
+
+    /// Sets a maximum level of records to submit.
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets a server and a database that statements executed by `sqlx` are tracked against. See
+    /// [`sqlx`](crate::sqlx) for details.
+    #[cfg(feature = "sqlx")]
+    pub fn with_sqlx_database(mut self, server: impl Into<String>, database: impl Into<String>) -> Self {
+        self.database = crate::sqlx::Database::new(server.into(), database.into());
+        self
+    }
+
+    /// Installs this logger as a global logger and sets the maximum level of the `log` crate.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl Log for AppInsightsLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level && !metadata.target().starts_with(OWN_TARGET) && self.client.is_enabled()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        #[cfg(feature = "sqlx")]
+        if let Some(telemetry) = self.database.dependency(record) {
+            self.client
+                .track_with_context(telemetry, ProcessingContext::new(Some(crate::sqlx::INTEGRATION)));
+            return;
+        }
+
+        let mut telemetry = TraceTelemetry::new(record.args().to_string(), record.level().into());
+        let properties = telemetry.properties_mut();
+        properties.insert("target".into(), record.target().into());
+        if let Some(module_path) = record.module_path() {
+            properties.insert("module".into(), module_path.into());
+        }
+        if let (Some(file), Some(line)) = (record.file(), record.line()) {
+            properties.insert("location".into(), format!("{}:{}", file, line));
+        }
+
+        self.client
+            .track_with_context(telemetry, ProcessingContext::new(Some(INTEGRATION)));
+    }
+
+    fn flush(&self) {
+        self.client.flush_channel();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+    use log::Level;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope, SeverityLevel},
+        TelemetryConfig,
+    };
+
+    #[test]
+    fn it_tracks_record_as_trace() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(events.clone());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("disk usage is {}%", 93))
+                .level(Level::Warn)
+                .target("storage")
+                .module_path(Some("storage::disk"))
+                .file(Some("src/disk.rs"))
+                .line(Some(42))
+                .build(),
+        );
+
+        assert_eq!(events.len(), 1);
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::MessageData(data))) => {
+                assert_eq!(data.message, "disk usage is 93%");
+                assert_eq!(data.severity_level, Some(SeverityLevel::Warning));
+                let properties = data.properties.unwrap();
+                assert_eq!(properties.get("target"), Some(&"storage".to_string()));
+                assert_eq!(properties.get("module"), Some(&"storage::disk".to_string()));
+                assert_eq!(properties.get("location"), Some(&"src/disk.rs:42".to_string()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_skips_records_above_level() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(events.clone());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("cache hit"))
+                .level(Level::Debug)
+                .build(),
+        );
+
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_skips_own_records() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(events.clone());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("sending telemetry"))
+                .level(Level::Error)
+                .target("appinsights::channel")
+                .build(),
+        );
+
+        assert!(events.is_empty());
+    }
+
+    pub(crate) fn create_logger(events: Arc<SegQueue<Envelope>>) -> AppInsightsLogger {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = TelemetryClient::create(&config, TestChannel::new(events));
+        AppInsightsLogger::new(client)
+    }
+}
@@ -0,0 +1,183 @@
+//! Counters and histograms of a submission pipeline reported with the [`metrics`] crate.
+//!
+//! Every request to the server or a local agent is reported with counters of batches, items and
+//! bytes sent, a counter of batches that failed and a histogram of request latencies. Metrics are
+//! named the same way fields of `tracing` events of the `diagnostics` feature are, so dashboards
+//! work with either of them.
+//!
+//! They are recorded with the global recorder of the `metrics` crate an application installs, e.g.
+//! an exporter of Prometheus metrics, so they end up wherever other metrics of the application do.
+//! Nothing is recorded until a recorder is installed.
+//!
+//! ```rust, no_run
+//! use appinsights::TelemetryClient;
+//! use metrics_util::debugging::DebuggingRecorder;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let recorder = DebuggingRecorder::new();
+//! let snapshotter = recorder.snapshotter();
+//! recorder.install().unwrap();
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//! client.track_event("started");
+//! client.close_channel().await;
+//!
+//! for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+//!     println!("{} = {:?}", key.key().name(), value);
+//! }
+//! # }
+//! ```
+//!
+//! A custom [`MetricsRecorder`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder) receives them instead, e.g. to
+//! report them to another metrics library.
+use std::{sync::Arc, time::Duration};
+
+use crate::{shared::Shared, transmitter::HttpResponse, Result};
+
+/// A counter of batches sent to the server or a local agent.
+pub const BATCHES_SENT: &str = "appinsights_batches_sent";
+
+/// A counter of telemetry items sent to the server or a local agent.
+pub const ITEMS_SENT: &str = "appinsights_items_sent";
+
+/// A counter of bytes of payloads sent to the server or a local agent.
+pub const BYTES_SENT: &str = "appinsights_bytes_sent";
+
+/// A counter of batches that could not be sent because of a transport error.
+pub const BATCHES_FAILED: &str = "appinsights_batches_failed";
+
+/// A histogram of latencies of requests in milliseconds.
+pub const REQUEST_LATENCY_MS: &str = "appinsights_request_latency_ms";
+
+/// Receives counters and histograms of a submission pipeline.
+pub trait MetricsRecorder: Send + Sync {
+    /// Increments a monotonic counter with a given name by a given value.
+    fn increment_counter(&self, name: &'static str, value: u64);
+
+    /// Records a value of a histogram with a given name.
+    fn record_histogram(&self, name: &'static str, value: f64);
+}
+
+/// A metrics recorder that reports counters and histograms to the global recorder of the
+/// [`metrics`] crate. It is used unless a custom one is configured.
+struct GlobalMetricsRecorder;
+
+impl MetricsRecorder for GlobalMetricsRecorder {
+    fn increment_counter(&self, name: &'static str, value: u64) {
+        metrics::counter!(name).increment(value);
+    }
+
+    fn record_histogram(&self, name: &'static str, value: f64) {
+        metrics::histogram!(name).record(value);
+    }
+}
+
+/// A metrics recorder shared between a configuration and a transmitter. It makes a recorder
+/// comparable and printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedMetricsRecorder(Shared<dyn MetricsRecorder>);
+
+impl Default for SharedMetricsRecorder {
+    fn default() -> Self {
+        Self::new(GlobalMetricsRecorder)
+    }
+}
+
+impl SharedMetricsRecorder {
+    pub(crate) fn new(recorder: impl MetricsRecorder + 'static) -> Self {
+        let recorder: Arc<dyn MetricsRecorder> = Arc::new(recorder);
+        Self(recorder.into())
+    }
+
+    /// Reports a request with a payload of a given number of items and bytes and how long it took.
+    pub(crate) fn record_request(&self, count: usize, bytes: usize, latency: Duration, result: &Result<HttpResponse>) {
+        if result.is_ok() {
+            self.0.increment_counter(BATCHES_SENT, 1);
+            self.0.increment_counter(ITEMS_SENT, count as u64);
+            self.0.increment_counter(BYTES_SENT, bytes as u64);
+        } else {
+            self.0.increment_counter(BATCHES_FAILED, 1);
+        }
+        self.0
+            .record_histogram(REQUEST_LATENCY_MS, latency.as_secs_f64() * 1000.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use http::StatusCode;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn it_records_counters_and_latency_of_requests() {
+        let recorder = Arc::new(RecordingRecorder::default());
+        let shared = SharedMetricsRecorder::new(recorder.clone());
+
+        let response = HttpResponse::new(StatusCode::OK, Vec::new());
+        shared.record_request(5, 120, Duration::from_millis(250), &Ok(response));
+        shared.record_request(3, 80, Duration::from_millis(500), &Err(Error::Shutdown));
+
+        assert_eq!(
+            *recorder.counters.lock().unwrap(),
+            vec![
+                (BATCHES_SENT, 1),
+                (ITEMS_SENT, 5),
+                (BYTES_SENT, 120),
+                (BATCHES_FAILED, 1)
+            ]
+        );
+        assert_eq!(
+            *recorder.histograms.lock().unwrap(),
+            vec![(REQUEST_LATENCY_MS, 250.0), (REQUEST_LATENCY_MS, 500.0)]
+        );
+    }
+
+    #[test]
+    fn it_records_counters_and_latency_with_global_recorder_by_default() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let response = HttpResponse::new(StatusCode::OK, Vec::new());
+            SharedMetricsRecorder::default().record_request(5, 120, Duration::from_millis(250), &Ok(response));
+        });
+
+        let values: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key.key().name().to_string(), value))
+            .collect();
+        assert_eq!(values.len(), 4);
+        assert!(values.contains(&(ITEMS_SENT.to_string(), DebugValue::Counter(5))));
+        assert!(values.contains(&(BYTES_SENT.to_string(), DebugValue::Counter(120))));
+        assert!(values.contains(&(
+            REQUEST_LATENCY_MS.to_string(),
+            DebugValue::Histogram(vec![250.0.into()])
+        )));
+    }
+
+    /// A recorder that keeps every value it receives.
+    #[derive(Default)]
+    struct RecordingRecorder {
+        counters: Mutex<Vec<(&'static str, u64)>>,
+        histograms: Mutex<Vec<(&'static str, f64)>>,
+    }
+
+    impl MetricsRecorder for Arc<RecordingRecorder> {
+        fn increment_counter(&self, name: &'static str, value: u64) {
+            self.counters.lock().unwrap().push((name, value));
+        }
+
+        fn record_histogram(&self, name: &'static str, value: f64) {
+            self.histograms.lock().unwrap().push((name, value));
+        }
+    }
+}
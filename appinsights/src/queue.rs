@@ -0,0 +1,421 @@
+//! Policies that decide which telemetry items to drop when a queue grows too long.
+//!
+//! By default a channel queues any number of telemetry items while it waits for the next
+//! submission, e.g. when the server is unreachable for a while. With a
+//! [maximum queue size](crate::TelemetryConfig::max_queue_size) configured, a submission worker
+//! drops items exceeding the bound whenever it collects pending items into a batch. The bound is
+//! enforced only then, so a queue may hold more items in between, e.g. when an application tracks
+//! a burst of items within a submission interval. Dropped items are deposited to a
+//! [dead letter sink](crate::dead_letter) if any.
+//!
+//! Pending items are ordered the way they are collected into batches: items of a higher
+//! [`Priority`] go first, and items of the same priority go from the oldest to the newest. So when
+//! items pile up, e.g. while the server throttles submissions, exceptions are sent before traces.
+//!
+//! [`DropOldest`] is used unless another policy is configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder). Both built-in policies drop items
+//! of the lowest priority first. A custom [`DropPolicy`] inspects the [`QueueView`] of pending
+//! items, i.e. their type, priority, age and size, and selects victims to keep the items that
+//! matter most for a domain.
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use appinsights::{queue::QueueView, telemetry::TelemetryKind, TelemetryConfig};
+//!
+//! // drop the oldest traces first, then other items
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .max_queue_size(10_000)
+//!     .drop_policy(|queue: &mut QueueView<'_>| {
+//!         let traces: Vec<_> = queue
+//!             .iter()
+//!             .filter(|item| item.kind() == Some(TelemetryKind::Trace))
+//!             .map(|item| item.index())
+//!             .take(queue.excess())
+//!             .collect();
+//!         for index in traces {
+//!             queue.select(index);
+//!         }
+//!     })
+//!     .build();
+//! ```
+use std::{
+    cmp::Reverse,
+    fmt::{Debug, Formatter},
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    contracts::Envelope,
+    internal_logger::{panic_message, InternalEvent, InternalLogger},
+    shared::Shared,
+    telemetry::{Priority, TelemetryKind},
+    transmitter::serialized_len,
+};
+
+/// A policy that selects telemetry items to drop from a queue that holds more items than allowed.
+pub trait DropPolicy: Send + Sync {
+    /// Selects items to drop from a queue. A policy is expected to select at least
+    /// [`excess`](QueueView::excess) items. When it selects fewer, the oldest of the remaining
+    /// items are dropped as well, so a queue never stays above its bound. When a policy panics,
+    /// items are selected with [`DropOldest`] instead.
+    fn select(&self, queue: &mut QueueView<'_>);
+}
+
+impl<F> DropPolicy for F
+where
+    F: Fn(&mut QueueView<'_>) + Send + Sync,
+{
+    fn select(&self, queue: &mut QueueView<'_>) {
+        self(queue)
+    }
+}
+
+/// A policy that drops the oldest telemetry items of the lowest priority, so the most recent state
+/// of an application is reported. This is the default policy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DropOldest;
+
+impl DropPolicy for DropOldest {
+    fn select(&self, queue: &mut QueueView<'_>) {
+        let mut victims: Vec<_> = queue.iter().map(|item| (item.priority(), item.index())).collect();
+        victims.sort();
+        for (_, index) in victims.into_iter().take(queue.excess()) {
+            queue.select(index);
+        }
+    }
+}
+
+/// A policy that drops the newest telemetry items of the lowest priority, so the events that led
+/// to a spike are kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DropNewest;
+
+impl DropPolicy for DropNewest {
+    fn select(&self, queue: &mut QueueView<'_>) {
+        let mut victims: Vec<_> = queue
+            .iter()
+            .map(|item| (item.priority(), Reverse(item.index())))
+            .collect();
+        victims.sort();
+        for (_, Reverse(index)) in victims.into_iter().take(queue.excess()) {
+            queue.select(index);
+        }
+    }
+}
+
+/// A read-only view of telemetry items waiting in a queue, ordered from the highest priority to
+/// the lowest and from the oldest to the newest within the same priority. It lets a policy inspect
+/// pending items and select those to drop.
+pub struct QueueView<'a> {
+    items: &'a [(Priority, Envelope)],
+    excess: usize,
+    now: DateTime<Utc>,
+    selected: Vec<bool>,
+}
+
+impl<'a> QueueView<'a> {
+    /// Creates a new view of pending items that exceed a given maximum size.
+    pub(crate) fn new(items: &'a [(Priority, Envelope)], max_size: usize, now: DateTime<Utc>) -> Self {
+        Self {
+            items,
+            excess: items.len().saturating_sub(max_size),
+            now,
+            selected: vec![false; items.len()],
+        }
+    }
+
+    /// Returns the number of items waiting in a queue.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if a queue holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the minimum number of items to drop to bring a queue back within its bound.
+    pub fn excess(&self) -> usize {
+        self.excess
+    }
+
+    /// Returns an iterator over metadata of pending items in the order they are sent.
+    pub fn iter(&self) -> impl Iterator<Item = QueuedItem<'_>> + '_ {
+        self.items
+            .iter()
+            .enumerate()
+            .map(move |(index, (priority, envelope))| QueuedItem {
+                index,
+                priority: *priority,
+                envelope,
+                now: self.now,
+                selected: self.selected[index],
+            })
+    }
+
+    /// Selects an item at a given position to be dropped. Positions out of range are ignored.
+    pub fn select(&mut self, index: usize) {
+        if let Some(selected) = self.selected.get_mut(index) {
+            *selected = true;
+        }
+    }
+
+    /// Returns the number of items selected to be dropped so far.
+    pub fn selected(&self) -> usize {
+        self.selected.iter().filter(|selected| **selected).count()
+    }
+
+    /// Returns a mask of items to drop. When a policy selected fewer items than required, the
+    /// oldest of the remaining items of the lowest priority are added to it.
+    pub(crate) fn into_selection(mut self) -> Vec<bool> {
+        let missing = self.excess.saturating_sub(self.selected());
+        let mut remaining: Vec<_> = self
+            .iter()
+            .filter(|item| !item.is_selected())
+            .map(|item| (item.priority(), item.index()))
+            .collect();
+        remaining.sort();
+        for (_, index) in remaining.into_iter().take(missing) {
+            self.selected[index] = true;
+        }
+        self.selected
+    }
+}
+
+/// Metadata of a telemetry item waiting in a queue.
+#[derive(Clone, Copy)]
+pub struct QueuedItem<'a> {
+    index: usize,
+    priority: Priority,
+    envelope: &'a Envelope,
+    now: DateTime<Utc>,
+    selected: bool,
+}
+
+impl QueuedItem<'_> {
+    /// Returns a position of the item in a queue to [`select`](QueueView::select) it with.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns a name of the item type, e.g. `Microsoft.ApplicationInsights.Event`.
+    pub fn name(&self) -> &str {
+        &self.envelope.name
+    }
+
+    /// Returns a category of the item if it carries telemetry data.
+    pub fn kind(&self) -> Option<TelemetryKind> {
+        TelemetryKind::of(self.envelope)
+    }
+
+    /// Returns a priority of the item.
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Returns the time elapsed since the item was measured. An item with an invalid timestamp is
+    /// considered to be new.
+    pub fn age(&self) -> Duration {
+        DateTime::parse_from_rfc3339(&self.envelope.time)
+            .ok()
+            .and_then(|time| (self.now - time.with_timezone(&Utc)).to_std().ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the size of the item serialized to JSON in bytes.
+    pub fn size(&self) -> usize {
+        serialized_len(self.envelope)
+    }
+
+    /// Returns `true` if the item is selected to be dropped already.
+    pub fn is_selected(&self) -> bool {
+        self.selected
+    }
+}
+
+impl Debug for QueuedItem<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueuedItem")
+            .field("index", &self.index)
+            .field("name", &self.name())
+            .field("priority", &self.priority())
+            .field("age", &self.age())
+            .field("selected", &self.selected)
+            .finish()
+    }
+}
+
+/// Priorities of telemetry items of categories that override the default ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Priorities(pub(crate) Vec<(TelemetryKind, Priority)>);
+
+impl Priorities {
+    /// Overrides a priority of telemetry items of a given category.
+    pub(crate) fn set(&mut self, kind: TelemetryKind, priority: Priority) {
+        self.0.retain(|(priority_kind, _)| *priority_kind != kind);
+        self.0.push((kind, priority));
+    }
+
+    /// Returns a priority of telemetry items of a given category.
+    pub(crate) fn of_kind(&self, kind: TelemetryKind) -> Priority {
+        self.0
+            .iter()
+            .find(|(priority_kind, _)| *priority_kind == kind)
+            .map_or_else(|| Priority::of_kind(kind), |(_, priority)| *priority)
+    }
+
+    /// Returns a priority of an item, i.e. a given one it overrides the priority of its category
+    /// with if any, or the priority of its category otherwise.
+    pub(crate) fn of(&self, envelope: &Envelope, priority: Option<Priority>) -> Priority {
+        priority
+            .unwrap_or_else(|| TelemetryKind::of(envelope).map_or_else(Priority::default, |kind| self.of_kind(kind)))
+    }
+}
+
+/// A drop policy shared between a configuration and a channel. It makes a policy comparable and
+/// printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedDropPolicy(Shared<dyn DropPolicy>);
+
+impl SharedDropPolicy {
+    pub(crate) fn new(policy: impl DropPolicy + 'static) -> Self {
+        let policy: Arc<dyn DropPolicy> = Arc::new(policy);
+        Self(policy.into())
+    }
+
+    /// Splits pending items into those to keep and those to drop to fit into a given maximum size.
+    /// A policy that panics is reported, and the oldest items are dropped instead.
+    pub(crate) fn apply(
+        &self,
+        items: Vec<(Priority, Envelope)>,
+        max_size: usize,
+        now: DateTime<Utc>,
+        logger: &InternalLogger,
+    ) -> (Vec<(Priority, Envelope)>, Vec<Envelope>) {
+        let mut view = QueueView::new(&items, max_size, now);
+        let selection = match panic::catch_unwind(AssertUnwindSafe(|| self.0.select(&mut view))) {
+            Ok(()) => view.into_selection(),
+            Err(panic) => {
+                logger.log(InternalEvent::DropPolicyPanicked {
+                    message: panic_message(panic.as_ref()),
+                });
+                let mut view = QueueView::new(&items, max_size, now);
+                DropOldest.select(&mut view);
+                view.into_selection()
+            }
+        };
+
+        let (dropped, kept): (Vec<_>, Vec<_>) = items.into_iter().zip(selection).partition(|(_, selected)| *selected);
+        (
+            kept.into_iter().map(|(item, _)| item).collect(),
+            dropped.into_iter().map(|((_, item), _)| item).collect(),
+        )
+    }
+}
+
+impl Default for SharedDropPolicy {
+    fn default() -> Self {
+        Self::new(DropOldest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{SecondsFormat, TimeZone};
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case(SharedDropPolicy::new(DropOldest), &["4", "5", "6"] ; "drop oldest")]
+    #[test_case(SharedDropPolicy::new(DropNewest), &["1", "2", "3"] ; "drop newest")]
+    #[test_case(SharedDropPolicy::new(|_: &mut QueueView<'_>| {}), &["4", "5", "6"] ; "nothing selected")]
+    #[test_case(SharedDropPolicy::new(|queue: &mut QueueView<'_>| queue.select(4)), &["3", "4", "6"] ; "too few selected")]
+    #[test_case(SharedDropPolicy::new(|queue: &mut QueueView<'_>| { queue.select(5); panic!("no victims") }), &["4", "5", "6"] ; "panicked")]
+    fn it_drops_items_exceeding_bound(policy: SharedDropPolicy, expected: &[&str]) {
+        let items = (1..=6)
+            .map(|id| (Priority::Normal, envelope(&id.to_string(), 0)))
+            .collect();
+
+        let (kept, dropped) = policy.apply(items, 3, now(), &logger());
+
+        let kept: Vec<_> = kept.iter().map(|(_, item)| item.name.as_str()).collect();
+        assert_eq!(kept, expected);
+        assert_eq!(dropped.len(), 3);
+    }
+
+    #[test_case(SharedDropPolicy::new(DropOldest), &["1", "5", "6"] ; "drop oldest")]
+    #[test_case(SharedDropPolicy::new(DropNewest), &["1", "3", "5"] ; "drop newest")]
+    fn it_drops_items_of_lower_priority_first(policy: SharedDropPolicy, expected: &[&str]) {
+        let items = (1..=6)
+            .map(|id| {
+                let priority = match id {
+                    1 => Priority::High,
+                    2 | 4 => Priority::Low,
+                    _ => Priority::Normal,
+                };
+                (priority, envelope(&id.to_string(), 0))
+            })
+            .collect();
+
+        let (kept, _) = policy.apply(items, 3, now(), &logger());
+
+        let kept: Vec<_> = kept.iter().map(|(_, item)| item.name.as_str()).collect();
+        assert_eq!(kept, expected);
+    }
+
+    #[test]
+    fn it_exposes_metadata_of_queued_items() {
+        let items = vec![
+            (Priority::High, envelope("old", 30)),
+            (Priority::Low, envelope("new", 5)),
+        ];
+        let mut queue = QueueView::new(&items, 1, now());
+        queue.select(1);
+
+        let metadata: Vec<_> = queue
+            .iter()
+            .map(|item| {
+                (
+                    item.index(),
+                    item.name().to_string(),
+                    item.priority(),
+                    item.age(),
+                    item.is_selected(),
+                )
+            })
+            .collect();
+
+        assert_eq!(queue.excess(), 1);
+        assert_eq!(queue.selected(), 1);
+        assert_eq!(
+            metadata,
+            vec![
+                (0, "old".into(), Priority::High, Duration::from_secs(30), false),
+                (1, "new".into(), Priority::Low, Duration::from_secs(5), true),
+            ]
+        );
+        assert_eq!(queue.iter().next().unwrap().size(), serialized_len(&items[0].1));
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2023, 5, 1).and_hms(12, 0, 0)
+    }
+
+    fn logger() -> InternalLogger {
+        InternalLogger::from_config(&crate::TelemetryConfig::new("instrumentation".into()))
+    }
+
+    fn envelope(name: &str, age: i64) -> Envelope {
+        Envelope {
+            name: name.into(),
+            time: (now() - chrono::Duration::seconds(age)).to_rfc3339_opts(SecondsFormat::Millis, true),
+            ..Envelope::default()
+        }
+    }
+}
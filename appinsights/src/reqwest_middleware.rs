@@ -0,0 +1,274 @@
+//! A [`reqwest-middleware`](https://docs.rs/reqwest-middleware) integration that tracks outgoing
+//! HTTP calls.
+//!
+//! [`AppInsightsDependencyMiddleware`] measures the time spent on each outgoing request and submits
+//! a [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry) item once a response
+//! is received or the request fails.
+//!
+//! Every request is extended with `traceparent` and `Request-Id` headers so that the remote side is
+//! able to correlate its telemetry with the current operation. When the request extensions contain a
+//! [`TraceParent`](crate::telemetry::TraceParent), for instance the one inserted by the
+//! [`tower`](crate::tower) middleware for an incoming request, the dependency becomes a part of that
+//! operation. Otherwise every call starts a new distributed trace.
+//!
+//! ```rust, no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use appinsights::{reqwest_middleware::AppInsightsDependencyMiddleware, telemetry::TraceParent, TelemetryClient};
+//! use reqwest_middleware::ClientBuilder;
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let http = ClientBuilder::new(reqwest::Client::new())
+//!     .with(AppInsightsDependencyMiddleware::new(client))
+//!     .build();
+//!
+//! // trace parent of the current operation, e.g. extracted from the incoming request extensions
+//! let trace_parent = TraceParent::new();
+//!
+//! http.get("https://example.com/").with_extension(trace_parent).send().await?;
+//! # Ok(())
+//! # }
+//! ```
+use http::{HeaderValue, Method};
+use reqwest::{Request, Response, Url};
+use reqwest_middleware::{Middleware, Next, Result};
+use task_local_extensions::Extensions;
+
+use crate::{
+    processor::ProcessingContext,
+    telemetry::{RemoteDependencyTelemetry, Telemetry, TraceParent},
+    time, TelemetryClient,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "reqwest-middleware";
+
+/// A middleware that submits a [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry)
+/// for every outgoing HTTP request.
+#[derive(Clone)]
+pub struct AppInsightsDependencyMiddleware {
+    client: TelemetryClient,
+}
+
+impl AppInsightsDependencyMiddleware {
+    /// The name of the legacy HTTP header that carries request id of the caller.
+    pub const REQUEST_ID_HEADER: &'static str = "Request-Id";
+
+    /// Creates a new middleware that submits dependency telemetry with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for AppInsightsDependencyMiddleware {
+    async fn handle(&self, mut request: Request, extensions: &mut Extensions, next: Next<'_>) -> Result<Response> {
+        let parent = extensions.get::<TraceParent>().cloned();
+        let trace_parent = parent.as_ref().map_or_else(TraceParent::new, TraceParent::child);
+
+        let headers = request.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&trace_parent.to_string()) {
+            headers.insert(TraceParent::HEADER, value);
+        }
+        let request_id = format!("|{}.{}.", trace_parent.trace_id(), trace_parent.span_id());
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            headers.insert(Self::REQUEST_ID_HEADER, value);
+        }
+
+        let tracker = DependencyTracker {
+            client: &self.client,
+            method: request.method().clone(),
+            url: request.url().clone(),
+            started: time::now(),
+            parent_id: parent.map(|parent| parent.span_id().to_string()),
+            trace_parent,
+        };
+
+        let result = next.run(request, extensions).await;
+
+        let response_code = result.as_ref().ok().map(|response| response.status());
+        tracker.track(response_code.map(|status| status.as_u16()));
+
+        result
+    }
+}
+
+/// Collects outgoing request data required to submit a telemetry item when a response is ready.
+struct DependencyTracker<'a> {
+    client: &'a TelemetryClient,
+    method: Method,
+    url: Url,
+    started: chrono::DateTime<chrono::Utc>,
+    parent_id: Option<String>,
+    trace_parent: TraceParent,
+}
+
+impl DependencyTracker<'_> {
+    fn track(self, status: Option<u16>) {
+        let duration = (time::now() - self.started).to_std().unwrap_or_default();
+        let name = format!("{} {}", self.method, self.url.path());
+        let success = matches!(status, Some(status) if status < 400);
+
+        let mut telemetry = RemoteDependencyTelemetry::new(name, "HTTP", duration, target(&self.url), success);
+        *telemetry.timestamp_mut() = self.started;
+        *telemetry.result_code_mut() = status.map(|status| status.to_string());
+        telemetry.set_id(self.trace_parent.span_id());
+        telemetry.set_data(self.url.as_str());
+
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.trace_parent.trace_id().into());
+        if let Some(parent_id) = self.parent_id {
+            operation.set_parent_id(parent_id);
+        }
+
+        let mut processing = ProcessingContext::new(Some(INTEGRATION));
+        processing.extensions_mut().insert(self.trace_parent);
+        self.client.track_with_context(telemetry, processing);
+    }
+}
+
+/// Returns a host with an explicitly specified port of the request URL.
+fn target(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+    use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Error};
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_tracks_dependency_with_response_status() {
+        let events = Arc::new(SegQueue::default());
+        let http = create_http_client(events.clone(), TestResponder(Some(404)));
+
+        let response = http.get("http://example.com:8080/users/42?q=1").send().await.unwrap();
+
+        assert_eq!(events.len(), 1);
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "GET /users/42");
+                assert_eq!(data.type_, Some("HTTP".into()));
+                assert_eq!(data.target, Some("example.com:8080".into()));
+                assert_eq!(data.result_code, Some("404".into()));
+                assert_eq!(data.data, Some("http://example.com:8080/users/42".into()));
+                assert_eq!(data.success, Some(false));
+
+                let trace_parent: TraceParent = response.headers()[TraceParent::HEADER]
+                    .to_str()
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                assert_eq!(data.id, Some(trace_parent.span_id().into()));
+                assert_eq!(tags.get("ai.operation.id"), Some(&trace_parent.trace_id().to_string()));
+                assert_eq!(
+                    response.headers()[AppInsightsDependencyMiddleware::REQUEST_ID_HEADER],
+                    format!("|{}.{}.", trace_parent.trace_id(), trace_parent.span_id())
+                );
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(tags.get("ai.operation.parentId"), None);
+    }
+
+    #[tokio::test]
+    async fn it_correlates_dependency_with_current_operation() {
+        let events = Arc::new(SegQueue::default());
+        let http = create_http_client(events.clone(), TestResponder(Some(200)));
+        let current: TraceParent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+            .parse()
+            .unwrap();
+
+        let response = http
+            .get("https://example.com/")
+            .with_extension(current)
+            .send()
+            .await
+            .unwrap();
+
+        let trace_parent: TraceParent = response.headers()[TraceParent::HEADER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(trace_parent.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_ne!(trace_parent.span_id(), "b7ad6b7169203331");
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => assert_eq!(data.success, Some(true)),
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_tracks_failed_dependency_when_request_fails() {
+        let events = Arc::new(SegQueue::default());
+        let http = create_http_client(events.clone(), TestResponder(None));
+
+        let result = http.post("https://example.com/users").send().await;
+
+        assert!(result.is_err());
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "POST /users");
+                assert_eq!(data.target, Some("example.com".into()));
+                assert_eq!(data.result_code, None);
+                assert_eq!(data.success, Some(false));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    fn create_http_client(events: Arc<SegQueue<Envelope>>, responder: TestResponder) -> ClientWithMiddleware {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = TelemetryClient::create(&config, TestChannel::new(events));
+
+        ClientBuilder::new(reqwest::Client::new())
+            .with(AppInsightsDependencyMiddleware::new(client))
+            .with(responder)
+            .build()
+    }
+
+    /// Responds with a given status code without sending a request and echoes correlation headers
+    /// via response headers. Fails a request when no status code specified.
+    struct TestResponder(Option<u16>);
+
+    #[async_trait::async_trait]
+    impl Middleware for TestResponder {
+        async fn handle(&self, request: Request, _: &mut Extensions, _: Next<'_>) -> Result<Response> {
+            let status = self
+                .0
+                .ok_or_else(|| Error::Middleware(anyhow::anyhow!("connection refused")))?;
+
+            let mut response = http::Response::builder().status(status);
+            for name in [TraceParent::HEADER, AppInsightsDependencyMiddleware::REQUEST_ID_HEADER] {
+                if let Some(value) = request.headers().get(name) {
+                    response = response.header(name, value);
+                }
+            }
+            Ok(Response::from(response.body("").unwrap()))
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Timer of a submission worker.
+//!
+//! A submission worker waits for the [submission interval](crate::TelemetryConfig::interval) to
+//! elapse before it sends pending telemetry items, and for a back-off period before it retries a
+//! batch the server did not accept. By default it waits with a tokio timer. Applications that run
+//! their own main loop, like game engines or single-threaded simulators, can drive the worker from
+//! that loop instead with a custom [`Scheduler`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder).
+//!
+//! [`ManualScheduler`] lets an application decide when the worker checks whether the time is up
+//! by calling [`tick`](ManualScheduler::tick), e.g. once per simulation step. A period is never
+//! completed before it is over, so the worker still keeps the submission interval and waits as
+//! long as the server asked with a `Retry-After` header.
+//!
+//! ```rust, no_run
+//! use appinsights::{scheduler::ManualScheduler, TelemetryClient, TelemetryConfig};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let scheduler = ManualScheduler::new();
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .scheduler(scheduler.clone())
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//!
+//! for frame in 0..1000 {
+//!     client.track_metric("frame", frame as f64);
+//!
+//!     // send pending telemetry items at most every 100 frames once the interval is over
+//!     if frame % 100 == 0 {
+//!         scheduler.tick();
+//!     }
+//! }
+//! # }
+//! ```
+use std::{
+    fmt::Debug,
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tokio::sync::oneshot;
+
+use crate::{shared::Shared, timeout};
+
+/// A timer a submission worker waits on before it sends pending telemetry items or retries them.
+#[async_trait]
+pub trait Scheduler: Send + Sync {
+    /// Completes when a given period is over. A scheduler is free to decide when it is over, the
+    /// period is only a hint of how long the worker is going to wait.
+    async fn sleep(&self, period: Duration);
+}
+
+/// A scheduler that completes a period only when an application calls [`tick`](Self::tick).
+///
+/// Every clone shares the same timer. Every period is waited for on its own, e.g. a submission
+/// interval of the worker along with back-off periods of batches being retried, and a tick
+/// completes all periods that are over at the moment. A period that is not over yet is completed
+/// by one of the following ticks.
+#[derive(Debug, Clone, Default)]
+pub struct ManualScheduler(Arc<Mutex<Vec<Sleep>>>);
+
+impl ManualScheduler {
+    /// Creates a new scheduler that waits for ticks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Completes all periods waited for that are over.
+    pub fn tick(&self) {
+        let now = Instant::now();
+        let mut sleeps = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let (over, waiting) = mem::take(&mut *sleeps)
+            .into_iter()
+            .partition::<Vec<_>, _>(|sleep| sleep.until <= now);
+        *sleeps = waiting;
+        drop(sleeps);
+
+        for sleep in over {
+            // the period is not waited for anymore if a receiver is gone
+            let _ = sleep.wake.send(());
+        }
+    }
+}
+
+#[async_trait]
+impl Scheduler for ManualScheduler {
+    async fn sleep(&self, period: Duration) {
+        let (wake, woken) = oneshot::channel();
+        {
+            let mut sleeps = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            sleeps.retain(|sleep| !sleep.wake.is_closed());
+            sleeps.push(Sleep {
+                until: Instant::now() + period,
+                wake,
+            });
+        }
+        let _ = woken.await;
+    }
+}
+
+/// A period a [`ManualScheduler`] is waited for along with a notification to complete it.
+#[derive(Debug)]
+struct Sleep {
+    until: Instant,
+    wake: oneshot::Sender<()>,
+}
+
+/// A custom scheduler shared between a channel and its batches being sent. It makes a scheduler
+/// comparable and printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedScheduler(Shared<dyn Scheduler>);
+
+impl SharedScheduler {
+    pub(crate) fn new(scheduler: impl Scheduler + 'static) -> Self {
+        let scheduler: Arc<dyn Scheduler> = Arc::new(scheduler);
+        Self(scheduler.into())
+    }
+}
+
+/// Waits for a given period with a custom scheduler if any or with a tokio timer otherwise.
+pub(crate) async fn sleep(scheduler: Option<SharedScheduler>, period: Duration) {
+    match scheduler {
+        Some(scheduler) => scheduler.0.sleep(period).await,
+        None => timeout::sleep(period).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_completes_period_on_tick() {
+        let scheduler = ManualScheduler::new();
+
+        let mut sleep = scheduler.sleep(Duration::ZERO);
+        assert_eq!((&mut sleep).now_or_never(), None);
+
+        scheduler.clone().tick();
+        assert_eq!(sleep.now_or_never(), Some(()));
+    }
+
+    #[tokio::test]
+    async fn it_completes_every_period_that_is_over_on_tick() {
+        let scheduler = ManualScheduler::new();
+
+        let mut interval = scheduler.sleep(Duration::ZERO);
+        let mut retry = scheduler.sleep(Duration::ZERO);
+        let mut retry_after = scheduler.sleep(Duration::from_secs(3600));
+        assert_eq!((&mut interval).now_or_never(), None);
+        assert_eq!((&mut retry).now_or_never(), None);
+        assert_eq!((&mut retry_after).now_or_never(), None);
+
+        scheduler.tick();
+        assert_eq!(interval.now_or_never(), Some(()));
+        assert_eq!(retry.now_or_never(), Some(()));
+        assert_eq!(retry_after.now_or_never(), None);
+    }
+
+    #[tokio::test]
+    async fn it_completes_period_on_tick_once_it_is_over() {
+        let scheduler = ManualScheduler::new();
+
+        let mut sleep = scheduler.sleep(Duration::from_millis(50));
+        assert_eq!((&mut sleep).now_or_never(), None);
+
+        scheduler.tick();
+        assert_eq!((&mut sleep).now_or_never(), None);
+
+        std::thread::sleep(Duration::from_millis(60));
+        scheduler.tick();
+        assert_eq!(sleep.now_or_never(), Some(()));
+    }
+
+    #[tokio::test]
+    async fn it_does_not_keep_tick_until_next_period() {
+        let scheduler = ManualScheduler::new();
+
+        scheduler.tick();
+
+        assert_eq!(scheduler.sleep(Duration::ZERO).now_or_never(), None);
+    }
+}
@@ -0,0 +1,321 @@
+//! Delivery semantics, sequence numbers and sequence acknowledgement of telemetry items.
+//!
+//! # Delivery semantics
+//!
+//! Telemetry items are kept in memory until they are sent, so items that are still queued when an
+//! application crashes are lost. A batch the server has not accepted is retried a limited number of
+//! times, and when a response of the server is lost on the way back, a batch the server has
+//! already accepted is sent again. Without further measures an item is therefore delivered at
+//! most once per attempt but may reach the server more than once in total.
+//!
+//! # Sequence numbers
+//!
+//! Every telemetry item a channel accepts is stamped with a `seq` field of the form
+//! `<session>:<number>`. A session is a random identifier of a channel, and numbers increase by
+//! one with every item starting from 1. The server uses the field to detect duplicates, and a gap
+//! between numbers of the same session means an item was lost on its way, e.g. dropped from a full
+//! queue or after all retries were exhausted, so client-side loss can be quantified with a query.
+//!
+//! Items of a batch are sent in the order they were accepted, even if items of a higher
+//! [priority](crate::telemetry::Priority) were collected into the batch first. Items of different
+//! batches may still arrive out of order, e.g. when a batch is retried or several batches are
+//! sent concurrently.
+//!
+//! # Sequence acknowledgement
+//!
+//! With a [`SequenceStore`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder), every batch gets a sequence
+//! number and every item of a batch is stamped with a `seq` field of the form `<batch>:<index>`
+//! instead of a session sequence number.
+//! The field stays the same when an item is retried, so downstream consumers can drop duplicates
+//! and process every item at most once.
+//!
+//! When the server accepts a whole batch, its sequence number is acknowledged and, if it is the
+//! highest one so far, saved to the store. After a restart numbering continues after the saved
+//! sequence number, so items of different runs do not share the same `seq` field. The highest
+//! acknowledged sequence number is also available from a
+//! [`ShutdownReport`](crate::ShutdownReport::acked_sequence). Batches with lower sequence numbers
+//! may still have been dropped, e.g. when all retries were exhausted.
+//!
+//! ```rust, no_run
+//! use appinsights::{sequence::FileSequenceStore, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .sequence_store(FileSequenceStore::new("/var/lib/my-app/telemetry.seq"))
+//!     .build();
+//! ```
+use std::{
+    fmt::Debug,
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{
+    contracts::Envelope,
+    file,
+    internal_logger::{InternalEvent, InternalLogger},
+    shared::Shared,
+    transmitter::BatchItem,
+};
+
+/// A persistent storage of the highest sequence number of a batch acknowledged by the server.
+pub trait SequenceStore: Send + Sync {
+    /// Returns the sequence number saved before or `None` if nothing has been saved yet.
+    fn load(&self) -> io::Result<Option<u64>>;
+
+    /// Saves the highest acknowledged sequence number.
+    fn save(&self, sequence: u64) -> io::Result<()>;
+}
+
+/// A sequence store that keeps the highest acknowledged sequence number in a file.
+///
+/// A new number is written to a temporary file with a unique name next to the target one first,
+/// flushed to disk and then renamed, so the file always contains a complete number even after a
+/// power loss.
+#[derive(Debug, Clone)]
+pub struct FileSequenceStore {
+    path: PathBuf,
+}
+
+impl FileSequenceStore {
+    /// Creates a new sequence store that keeps a sequence number in a file at a given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SequenceStore for FileSequenceStore {
+    fn load(&self) -> io::Result<Option<u64>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => content
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, sequence: u64) -> io::Result<()> {
+        file::write_atomically(&self.path, sequence.to_string().as_bytes(), true)
+    }
+}
+
+/// A sequence store shared between a configuration and a channel. It makes a store comparable
+/// and printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedSequenceStore(Shared<dyn SequenceStore>);
+
+impl SharedSequenceStore {
+    pub(crate) fn new(store: impl SequenceStore + 'static) -> Self {
+        let store: Arc<dyn SequenceStore> = Arc::new(store);
+        Self(store.into())
+    }
+}
+
+/// Assigns sequence numbers to batches of telemetry items and keeps track of the highest one
+/// acknowledged by the server.
+pub(crate) struct Sequencer {
+    store: SharedSequenceStore,
+    next: AtomicU64,
+    acked: Mutex<Option<u64>>,
+    saving: AsyncMutex<()>,
+    logger: InternalLogger,
+}
+
+impl Sequencer {
+    /// Creates a new sequencer that continues numbering after a sequence number saved in a store.
+    pub(crate) fn new(store: SharedSequenceStore, logger: InternalLogger) -> Self {
+        let acked = store.0.load().unwrap_or_else(|err| {
+            logger.log(InternalEvent::SequenceNotLoaded { error: err.to_string() });
+            None
+        });
+
+        Self {
+            store,
+            next: AtomicU64::new(acked.map_or(1, |acked| acked + 1)),
+            acked: Mutex::new(acked),
+            saving: AsyncMutex::new(()),
+            logger,
+        }
+    }
+
+    /// Stamps items without a sequence number with the next batch sequence number and returns the
+    /// highest batch sequence number among all items, if any.
+    pub(crate) fn assign<T: BatchItem>(&self, items: &mut [T]) -> Option<u64> {
+        if items.iter().any(|item| item.envelope().seq.is_none()) {
+            let batch = self.next.fetch_add(1, Ordering::Relaxed);
+            let unstamped = items
+                .iter_mut()
+                .map(BatchItem::envelope_mut)
+                .filter(|item| item.seq.is_none());
+            for (index, item) in unstamped.enumerate() {
+                item.seq = Some(format!("{}:{}", batch, index));
+            }
+        }
+
+        items.iter().map(BatchItem::envelope).filter_map(batch_of).max()
+    }
+
+    /// Acknowledges a batch accepted by the server and saves its sequence number if it is the
+    /// highest one so far. A store is called on a blocking thread, one save at a time, so a lower
+    /// number never overwrites a higher one.
+    pub(crate) async fn ack(&self, batch: u64) {
+        let _saving = self.saving.lock().await;
+        if self.acked().is_some_and(|acked| acked >= batch) {
+            return;
+        }
+
+        let store = self.store.clone();
+        let saved = tokio::task::spawn_blocking(move || store.0.save(batch))
+            .await
+            .unwrap_or_else(|err| Err(io::Error::other(err)));
+        match saved {
+            Ok(()) => *self.acked.lock().unwrap_or_else(PoisonError::into_inner) = Some(batch),
+            Err(err) => self.logger.log(InternalEvent::SequenceNotSaved {
+                sequence: batch,
+                error: err.to_string(),
+            }),
+        }
+    }
+
+    /// Returns the highest sequence number acknowledged by the server, if any.
+    pub(crate) fn acked(&self) -> Option<u64> {
+        *self.acked.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Stamps telemetry items with monotonically increasing sequence numbers of a session.
+#[derive(Debug)]
+pub(crate) struct SessionSequence {
+    session: String,
+    next: AtomicU64,
+}
+
+impl SessionSequence {
+    /// Creates a new sequence of a random session.
+    pub(crate) fn new() -> Self {
+        Self {
+            session: crate::uuid::new().as_simple().to_string(),
+            next: AtomicU64::new(1),
+        }
+    }
+
+    /// Stamps an item with the next sequence number unless it carries one already.
+    pub(crate) fn stamp(&self, item: &mut Envelope) {
+        if item.seq.is_none() {
+            let number = self.next.fetch_add(1, Ordering::Relaxed);
+            item.seq = Some(format!("{}:{}", self.session, number));
+        }
+    }
+}
+
+/// Orders items of a batch by their session sequence numbers. Items without a number keep their
+/// position relative to each other and go first.
+pub(crate) fn order_by_session<T: BatchItem>(items: &mut [T]) {
+    items.sort_by_key(|item| number_of(item.envelope()));
+}
+
+/// Returns a batch sequence number of a telemetry item.
+fn batch_of(item: &Envelope) -> Option<u64> {
+    item.seq.as_deref()?.split(':').next()?.parse().ok()
+}
+
+/// Returns a session sequence number of a telemetry item.
+fn number_of(item: &Envelope) -> Option<u64> {
+    item.seq.as_deref()?.rsplit(':').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TelemetryConfig;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<Option<u64>>);
+
+    impl SequenceStore for Arc<MemoryStore> {
+        fn load(&self) -> io::Result<Option<u64>> {
+            Ok(*self.0.lock().unwrap())
+        }
+
+        fn save(&self, sequence: u64) -> io::Result<()> {
+            *self.0.lock().unwrap() = Some(sequence);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_continues_numbering_after_saved_sequence() {
+        let store = Arc::new(MemoryStore(Mutex::new(Some(41))));
+        let sequencer = sequencer(store.clone());
+
+        let mut items = vec![Envelope::default(), Envelope::default()];
+        assert_eq!(sequencer.assign(&mut items), Some(42));
+        assert_eq!(items[0].seq, Some("42:0".into()));
+        assert_eq!(items[1].seq, Some("42:1".into()));
+
+        // retried items keep their sequence numbers and new items get the next one
+        items.remove(0);
+        items.push(Envelope::default());
+        assert_eq!(sequencer.assign(&mut items), Some(43));
+        assert_eq!(items[0].seq, Some("42:1".into()));
+        assert_eq!(items[1].seq, Some("43:0".into()));
+    }
+
+    #[tokio::test]
+    async fn it_saves_highest_acknowledged_sequence() {
+        let store = Arc::new(MemoryStore::default());
+        let sequencer = sequencer(store.clone());
+
+        sequencer.ack(2).await;
+        sequencer.ack(1).await;
+
+        assert_eq!(sequencer.acked(), Some(2));
+        assert_eq!(*store.0.lock().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn it_stamps_and_orders_items_by_session_sequence() {
+        let sequence = SessionSequence::new();
+
+        let mut items: Vec<_> = (0..11)
+            .map(|_| {
+                let mut item = Envelope::default();
+                sequence.stamp(&mut item);
+                item
+            })
+            .collect();
+        sequence.stamp(&mut items[0]);
+        items.reverse();
+
+        order_by_session(&mut items);
+
+        let numbers: Vec<_> = items.iter().filter_map(number_of).collect();
+        assert_eq!(numbers, (1..=11).collect::<Vec<_>>());
+        assert!(items[0].seq.as_deref().unwrap().starts_with(&sequence.session));
+    }
+
+    #[test]
+    fn it_keeps_sequence_in_file() {
+        let path = crate::test::temp_path("seq");
+        let store = FileSequenceStore::new(&path);
+
+        assert_eq!(store.load().unwrap(), None);
+        store.save(7).unwrap();
+        assert_eq!(store.load().unwrap(), Some(7));
+    }
+
+    fn sequencer(store: Arc<MemoryStore>) -> Sequencer {
+        let logger = InternalLogger::from_config(&TelemetryConfig::new("instrumentation".into()));
+        Sequencer::new(SharedSequenceStore::new(store), logger)
+    }
+}
@@ -0,0 +1,217 @@
+//! An [`sqlx`](https://docs.rs/sqlx) integration that tracks executed statements as SQL
+//! dependencies.
+//!
+//! `sqlx` reports every statement it executes with a [`log`](https://docs.rs/log) record of the
+//! `sqlx::query` target that carries a statement text, numbers of rows affected and returned and
+//! time elapsed. The [`AppInsightsLogger`](crate::log::AppInsightsLogger) turns those records into
+//! [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry) items of `SQL` type
+//! instead of traces, so the integration does not depend on a version of `sqlx` an application
+//! uses. Literals are removed from submitted statements the same way
+//! [`sanitize_sql`](crate::telemetry::RemoteDependencyTelemetry::sanitize_sql) does.
+//!
+//! Both a server and a database name default to `sqlx`. Set them with
+//! [`with_sqlx_database`](crate::log::AppInsightsLogger::with_sqlx_database) to tell databases
+//! apart in the portal. Statements are logged at `Info` level unless `sqlx` is configured
+//! otherwise, so the logger should be enabled for it.
+//!
+//! ```rust, no_run
+//! use appinsights::{log::AppInsightsLogger, TelemetryClient};
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! AppInsightsLogger::new(client)
+//!     .with_sqlx_database("db.example.com", "orders")
+//!     .init()
+//!     .expect("no other logger installed");
+//! ```
+use std::time::Duration;
+
+use log::Record;
+
+use crate::{telemetry::RemoteDependencyTelemetry, time};
+
+/// A name of this integration reported to telemetry processors.
+pub(crate) const INTEGRATION: &str = "sqlx";
+
+/// A target of log records `sqlx` reports executed statements with.
+const TARGET: &str = "sqlx::query";
+
+/// A default name of a server and a database statements are tracked against.
+const DEFAULT_NAME: &str = "sqlx";
+
+/// A server and a database that statements executed by `sqlx` are tracked against.
+#[derive(Debug, Clone)]
+pub(crate) struct Database {
+    server: String,
+    database: String,
+}
+
+impl Database {
+    pub(crate) fn new(server: String, database: String) -> Self {
+        Self { server, database }
+    }
+
+    /// Returns a dependency telemetry item for a record of a statement executed by `sqlx` if it is
+    /// one.
+    pub(crate) fn dependency(&self, record: &Record<'_>) -> Option<RemoteDependencyTelemetry> {
+        if record.target() != TARGET {
+            return None;
+        }
+
+        let statement = Statement::parse(&record.args().to_string())?;
+        let mut telemetry = RemoteDependencyTelemetry::sql(
+            self.server.as_str(),
+            self.database.as_str(),
+            statement.command,
+            statement.elapsed,
+            true,
+        );
+        telemetry.sanitize_sql();
+        *telemetry.timestamp_mut() =
+            time::now() - chrono::Duration::from_std(statement.elapsed).unwrap_or_else(|_| chrono::Duration::zero());
+
+        let measurements = telemetry.measurements_mut();
+        measurements.insert("rows_affected".into(), statement.rows_affected as f64);
+        measurements.insert("rows_returned".into(), statement.rows_returned as f64);
+        Some(telemetry)
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new(DEFAULT_NAME.into(), DEFAULT_NAME.into())
+    }
+}
+
+/// A statement reported by `sqlx`, i.e.
+/// `<summary>; rows affected: <n>, rows returned: <n>, elapsed: <duration>[\n\n<statement>]` where
+/// the complete statement follows only when the summary is abbreviated.
+#[derive(Debug, PartialEq)]
+struct Statement {
+    command: String,
+    rows_affected: u64,
+    rows_returned: u64,
+    elapsed: Duration,
+}
+
+impl Statement {
+    fn parse(message: &str) -> Option<Self> {
+        let (head, statement) = match message.split_once("\n\n") {
+            Some((head, statement)) => (head, Some(statement.trim())),
+            None => (message, None),
+        };
+
+        let (summary, stats) = head.rsplit_once("; rows affected: ")?;
+        let (rows_affected, stats) = stats.split_once(", rows returned: ")?;
+        let (rows_returned, elapsed) = stats.split_once(", elapsed: ")?;
+
+        Some(Self {
+            command: statement.unwrap_or(summary).to_string(),
+            rows_affected: rows_affected.parse().ok()?,
+            rows_returned: rows_returned.parse().ok()?,
+            elapsed: parse_duration(elapsed.trim())?,
+        })
+    }
+}
+
+/// Parses a duration printed with `{:?}`, e.g. `1.234ms`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let units = [("ns", 1.0), ("µs", 1e3), ("ms", 1e6), ("s", 1e9)];
+    let (number, nanos) = units
+        .iter()
+        .find_map(|(unit, nanos)| value.strip_suffix(unit).map(|number| (number, nanos)))?;
+    let nanos = (number.parse::<f64>().ok()? * nanos).round();
+    (nanos.is_finite() && nanos >= 0.0).then(|| Duration::from_nanos(nanos as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crossbeam_queue::SegQueue;
+    use log::{Level, Log};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        contracts::{Base, Data},
+        log::tests::create_logger,
+    };
+
+    #[test]
+    fn it_tracks_statement_as_sql_dependency() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(events.clone()).with_sqlx_database("db.example.com", "orders");
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!(
+                    "SELECT id, total FROM …; rows affected: 0, rows returned: 2, elapsed: 1.500ms\n\n\
+                     SELECT\n  id,\n  total\nFROM\n  orders\nWHERE\n  customer = 'John'\n"
+                ))
+                .level(Level::Info)
+                .target("sqlx::query")
+                .build(),
+        );
+
+        assert_eq!(events.len(), 1);
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "orders");
+                assert_eq!(data.type_, Some("SQL".into()));
+                assert_eq!(data.target, Some("db.example.com | orders".into()));
+                assert_eq!(
+                    data.data,
+                    Some("SELECT id, total FROM orders WHERE customer = ?".into())
+                );
+                assert_eq!(data.duration, "0.00:00:00.0015000");
+                assert_eq!(data.success, Some(true));
+                let measurements = data.measurements.unwrap();
+                assert_eq!(measurements.get("rows_returned"), Some(&2.0));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_tracks_other_records_as_traces() {
+        let events = Arc::new(SegQueue::default());
+        let logger = create_logger(events.clone());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("pool exhausted"))
+                .level(Level::Warn)
+                .target("sqlx::pool")
+                .build(),
+        );
+
+        assert!(matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::MessageData(_)))
+        ));
+    }
+
+    #[test]
+    fn it_parses_statement_with_summary_only() {
+        let statement = Statement::parse("BEGIN; rows affected: 0, rows returned: 0, elapsed: 12.345µs").unwrap();
+
+        assert_eq!(
+            statement,
+            Statement {
+                command: "BEGIN".into(),
+                rows_affected: 0,
+                rows_returned: 0,
+                elapsed: Duration::from_nanos(12345),
+            }
+        );
+    }
+
+    #[test_case("12.000ns", Some(Duration::from_nanos(12)); "nanoseconds")]
+    #[test_case("1.250ms", Some(Duration::from_micros(1250)); "milliseconds")]
+    #[test_case("2.000s", Some(Duration::from_secs(2)); "seconds")]
+    #[test_case("2 minutes", None; "unknown unit")]
+    fn it_parses_duration(value: &str, expected: Option<Duration>) {
+        assert_eq!(parse_duration(value), expected);
+    }
+}
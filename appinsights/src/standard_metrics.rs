@@ -0,0 +1,534 @@
+use std::{
+    collections::BTreeMap,
+    mem,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::{
+    clock::SharedClock,
+    contracts::{names, tags, Base, Data, DataPoint, DataPointType, Envelope, MetricData, StringMap},
+    time, TelemetryConfig,
+};
+
+/// A period durations of requests and dependencies are aggregated for.
+const AGGREGATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often a client checks whether a period is over, so metrics of a period are sent even when
+/// no request or dependency is tracked after it.
+pub(crate) const DRAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A property that marks a raw item whose duration is already reported by a standard metric, so
+/// the server does not aggregate it once again.
+const PROCESSED_BY_EXTRACTORS: &str = "_MS.ProcessedByMetricExtractors";
+
+/// Upper bounds of performance buckets in milliseconds along with their names.
+const PERFORMANCE_BUCKETS: &[(f64, &str)] = &[
+    (250.0, "<250ms"),
+    (500.0, "250ms-500ms"),
+    (1_000.0, "500ms-1sec"),
+    (3_000.0, "1sec-3sec"),
+    (7_000.0, "3sec-7sec"),
+    (15_000.0, "7sec-15sec"),
+    (30_000.0, "15sec-30sec"),
+    (60_000.0, "30sec-1min"),
+    (120_000.0, "1min-2min"),
+    (300_000.0, "2min-5min"),
+];
+
+/// A name of a performance bucket of durations longer than all others.
+const LONGEST_PERFORMANCE_BUCKET: &str = ">=5min";
+
+/// A kind of a standard metric along with the way it is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum StandardMetric {
+    RequestDuration,
+    DependencyDuration,
+}
+
+impl StandardMetric {
+    /// Returns a name of a metric displayed in the portal.
+    fn name(self) -> &'static str {
+        match self {
+            Self::RequestDuration => "Server response time",
+            Self::DependencyDuration => "Dependency duration",
+        }
+    }
+
+    /// Returns an identifier the portal recognizes a metric by.
+    fn id(self) -> &'static str {
+        match self {
+            Self::RequestDuration => "requests/duration",
+            Self::DependencyDuration => "dependencies/duration",
+        }
+    }
+
+    /// Returns a marker of an extractor that reports a metric.
+    fn extractor(self) -> &'static str {
+        match self {
+            Self::RequestDuration => "(Name:'Requests', Ver:'1.1')",
+            Self::DependencyDuration => "(Name:'Dependencies', Ver:'1.1')",
+        }
+    }
+}
+
+/// A set of dimension values durations of a single metric are aggregated by.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Series {
+    metric: StandardMetric,
+    i_key: Option<String>,
+    tags: BTreeMap<String, String>,
+    dimensions: BTreeMap<&'static str, String>,
+}
+
+/// Durations aggregated for a single series.
+#[derive(Debug)]
+struct Aggregate {
+    count: i32,
+    sum: f64,
+    sum_of_squares: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Aggregate {
+    fn new(value: f64) -> Self {
+        Self {
+            count: 1,
+            sum: value,
+            sum_of_squares: value * value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.sum_of_squares += value * value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn std_dev(&self) -> f64 {
+        let mean = self.sum / f64::from(self.count);
+        (self.sum_of_squares / f64::from(self.count) - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Durations aggregated since a period started.
+#[derive(Debug, Default)]
+struct Period {
+    started_at: Option<DateTime<Utc>>,
+    series: BTreeMap<Series, Aggregate>,
+}
+
+impl Period {
+    /// Determines whether durations are aggregated for the whole aggregation interval already.
+    fn is_over(&self, now: DateTime<Utc>) -> bool {
+        self.started_at.is_some_and(|started_at| {
+            (now - started_at)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= AGGREGATION_INTERVAL)
+        })
+    }
+}
+
+/// Pre-aggregates durations of requests and dependencies into the standard metrics portal
+/// performance charts are built from.
+///
+/// Raw items are aggregated before processors see them, so the metrics count every request and
+/// dependency even if processors sample raw items out. Metrics of a period are emitted once the
+/// period is over, either when an item is tracked or when a client checks it every
+/// [`DRAIN_INTERVAL`], and when pending items are flushed.
+#[derive(Debug)]
+pub(crate) struct StandardMetrics {
+    clock: Option<SharedClock>,
+    period: Mutex<Period>,
+}
+
+impl StandardMetrics {
+    /// Creates a new aggregation of standard metrics. Returns `None` if extraction is disabled.
+    pub fn from_config(config: &TelemetryConfig) -> Option<Self> {
+        if !config.are_standard_metrics_extracted() {
+            return None;
+        }
+
+        Some(Self {
+            clock: config.clock().cloned(),
+            period: Mutex::default(),
+        })
+    }
+
+    /// Aggregates a duration of a request or a dependency and marks an envelope as processed.
+    /// Returns metrics of a previous period if it is over.
+    pub fn extract(&self, envelope: &mut Envelope) -> Vec<Envelope> {
+        let now = self.now();
+        let mut period = self.period.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let metrics = if period.is_over(now) {
+            drain(&mut period, now)
+        } else {
+            Vec::new()
+        };
+
+        if let Some((series, duration)) = observe(envelope) {
+            period.started_at.get_or_insert(now);
+            period
+                .series
+                .entry(series)
+                .and_modify(|aggregate| aggregate.add(duration))
+                .or_insert_with(|| Aggregate::new(duration));
+        }
+
+        metrics
+    }
+
+    /// Returns metrics of the current period and starts a new one if the period is over.
+    pub fn take_expired(&self) -> Vec<Envelope> {
+        let now = self.now();
+        let mut period = self.period.lock().unwrap_or_else(PoisonError::into_inner);
+        if period.is_over(now) {
+            drain(&mut period, now)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Returns metrics aggregated since the current period started and starts a new one.
+    pub fn take(&self) -> Vec<Envelope> {
+        let now = self.now();
+        let mut period = self.period.lock().unwrap_or_else(PoisonError::into_inner);
+        drain(&mut period, now)
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.as_ref().map_or_else(time::now, SharedClock::now)
+    }
+}
+
+/// Converts durations aggregated in a period into metrics and starts a new period.
+fn drain(period: &mut Period, now: DateTime<Utc>) -> Vec<Envelope> {
+    let started_at = match period.started_at.take() {
+        Some(started_at) => started_at,
+        None => return Vec::new(),
+    };
+    let interval_ms = (now - started_at).num_milliseconds().max(0);
+
+    mem::take(&mut period.series)
+        .into_iter()
+        .map(|(series, aggregate)| metric(series, &aggregate, started_at, interval_ms))
+        .collect()
+}
+
+/// Creates a metric envelope that reports durations aggregated for a series.
+fn metric(series: Series, aggregate: &Aggregate, started_at: DateTime<Utc>, interval_ms: i64) -> Envelope {
+    let mut properties: BTreeMap<String, String> = series
+        .dimensions
+        .into_iter()
+        .map(|(name, value)| (name.into(), value))
+        .collect();
+    properties.insert("_MS.MetricId".into(), series.metric.id().into());
+    properties.insert("_MS.IsAutocollected".into(), "True".into());
+    properties.insert("_MS.AggregationIntervalMs".into(), interval_ms.to_string());
+
+    Envelope {
+        name: names::METRIC.into(),
+        time: started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        i_key: series.i_key,
+        tags: Some(series.tags.into()),
+        data: Some(Base::Data(Data::MetricData(MetricData {
+            metrics: vec![DataPoint {
+                name: series.metric.name().into(),
+                kind: Some(DataPointType::Aggregation),
+                value: aggregate.sum,
+                count: Some(aggregate.count),
+                min: Some(aggregate.min),
+                max: Some(aggregate.max),
+                std_dev: Some(aggregate.std_dev()),
+                ..DataPoint::default()
+            }],
+            properties: Some(properties.into()),
+            ..MetricData::default()
+        }))),
+        ..Envelope::default()
+    }
+}
+
+/// Returns a series and a duration in milliseconds of a request or a dependency and marks its
+/// envelope as processed by an extractor. Returns `None` for other items.
+fn observe(envelope: &mut Envelope) -> Option<(Series, f64)> {
+    let (metric, duration, mut dimensions) = match envelope.data.as_ref()? {
+        Base::Data(Data::RequestData(data)) => {
+            let duration = duration_ms(&data.duration)?;
+            let mut dimensions = BTreeMap::new();
+            dimensions.insert("Request.Success", bool_value(data.success));
+            dimensions.insert("request/resultCode", data.response_code.clone());
+            dimensions.insert("request/performanceBucket", performance_bucket(duration).into());
+            (StandardMetric::RequestDuration, duration, dimensions)
+        }
+        Base::Data(Data::RemoteDependencyData(data)) => {
+            let duration = duration_ms(&data.duration)?;
+            let mut dimensions = BTreeMap::new();
+            dimensions.insert("Dependency.Success", bool_value(data.success.unwrap_or(true)));
+            dimensions.insert("Dependency.Type", data.type_.clone().unwrap_or_default());
+            dimensions.insert("dependency/target", data.target.clone().unwrap_or_default());
+            dimensions.insert("dependency/resultCode", data.result_code.clone().unwrap_or_default());
+            dimensions.insert("dependency/performanceBucket", performance_bucket(duration).into());
+            (StandardMetric::DependencyDuration, duration, dimensions)
+        }
+        _ => return None,
+    };
+
+    let tag = |key: &str| envelope.tags.as_ref().and_then(|tags| tags.get(key)).cloned();
+    let role = tag(tags::CLOUD_ROLE);
+    let role_instance = tag(tags::CLOUD_ROLE_INSTANCE);
+    let synthetic = tag(tags::OPERATION_SYNTHETIC_SOURCE).is_some_and(|source| !source.is_empty());
+    dimensions.insert("cloud/roleName", role.clone().unwrap_or_default());
+    dimensions.insert("cloud/roleInstance", role_instance.clone().unwrap_or_default());
+    dimensions.insert("operation/synthetic", bool_value(synthetic));
+
+    let mut series_tags = BTreeMap::new();
+    series_tags.extend(role.map(|role| (tags::CLOUD_ROLE.to_string(), role)));
+    series_tags.extend(role_instance.map(|instance| (tags::CLOUD_ROLE_INSTANCE.to_string(), instance)));
+
+    if let Some(Base::Data(data)) = envelope.data.as_mut() {
+        data.properties_mut()
+            .get_or_insert_with(StringMap::default)
+            .insert(PROCESSED_BY_EXTRACTORS.into(), metric.extractor().into());
+    }
+
+    let series = Series {
+        metric,
+        i_key: envelope.i_key.clone(),
+        tags: series_tags,
+        dimensions,
+    };
+    Some((series, duration))
+}
+
+/// Formats a flag the way dimensions of standard metrics expect.
+fn bool_value(value: bool) -> String {
+    if value { "True" } else { "False" }.into()
+}
+
+/// Returns a name of a performance bucket a duration in milliseconds falls into.
+fn performance_bucket(duration_ms: f64) -> &'static str {
+    PERFORMANCE_BUCKETS
+        .iter()
+        .find(|(bound, _)| duration_ms < *bound)
+        .map_or(LONGEST_PERFORMANCE_BUCKET, |(_, name)| name)
+}
+
+/// Parses a duration formatted as `d.hh:mm:ss.fffffff` into milliseconds.
+fn duration_ms(duration: &str) -> Option<f64> {
+    let (days, rest) = duration.split_once('.')?;
+    let (clock, fraction) = rest.split_once('.')?;
+    let mut clock = clock.split(':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = match (clock.next(), clock.next(), clock.next(), clock.next()) {
+        (Some(Ok(hours)), Some(Ok(minutes)), Some(Ok(seconds)), None) => (hours, minutes, seconds),
+        _ => return None,
+    };
+    let ticks: u64 = fraction.parse().ok()?;
+
+    let seconds = days.parse::<u64>().ok()? * 86_400 + hours * 3_600 + minutes * 60 + seconds;
+    Some(seconds as f64 * 1_000.0 + ticks as f64 / 10_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration as StdDuration;
+
+    use chrono::TimeZone;
+    use test_case::test_case;
+
+    use super::*;
+    use crate::{
+        context::TelemetryContext,
+        telemetry::{ContextTags, EventTelemetry, Properties, RemoteDependencyTelemetry, RequestTelemetry},
+    };
+
+    #[test]
+    fn it_aggregates_request_durations_by_dimensions() {
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let metrics = create_metrics();
+
+        let mut first = request(120, "200");
+        assert!(metrics.extract(&mut first).is_empty());
+        assert!(metrics.extract(&mut request(80, "200")).is_empty());
+        assert!(metrics.extract(&mut request(2500, "500")).is_empty());
+
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 30));
+        let envelopes = metrics.take();
+
+        assert_eq!(
+            properties(&first)[PROCESSED_BY_EXTRACTORS],
+            "(Name:'Requests', Ver:'1.1')"
+        );
+        assert_eq!(envelopes.len(), 2);
+
+        let (succeeded, failed): (Vec<_>, Vec<_>) = envelopes
+            .iter()
+            .partition(|envelope| properties(envelope)["Request.Success"] == "True");
+        let envelope = succeeded[0];
+        assert_eq!(envelope.time, "2023-05-01T12:00:00.000Z");
+        assert_eq!(envelope.i_key.as_deref(), Some("instrumentation"));
+        assert_eq!(envelope.tags.as_ref().unwrap()[tags::CLOUD_ROLE], "api");
+        match &envelope.data {
+            Some(Base::Data(Data::MetricData(data))) => {
+                let point = &data.metrics[0];
+                assert_eq!(point.name, "Server response time");
+                assert_eq!(point.value, 200.0);
+                assert_eq!(point.count, Some(2));
+                assert_eq!(point.min, Some(80.0));
+                assert_eq!(point.max, Some(120.0));
+                assert_eq!(point.std_dev, Some(20.0));
+
+                let properties = data.properties.as_ref().unwrap();
+                assert_eq!(properties["_MS.MetricId"], "requests/duration");
+                assert_eq!(properties["_MS.IsAutocollected"], "True");
+                assert_eq!(properties["_MS.AggregationIntervalMs"], "30000");
+                assert_eq!(properties["Request.Success"], "True");
+                assert_eq!(properties["request/resultCode"], "200");
+                assert_eq!(properties["request/performanceBucket"], "<250ms");
+                assert_eq!(properties["cloud/roleName"], "api");
+                assert_eq!(properties["operation/synthetic"], "False");
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(properties(failed[0])["request/resultCode"], "500");
+        assert_eq!(properties(failed[0])["request/performanceBucket"], "1sec-3sec");
+    }
+
+    #[test]
+    fn it_aggregates_dependency_durations_by_target() {
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let metrics = create_metrics();
+
+        let mut envelope = dependency("db.local");
+        metrics.extract(&mut envelope);
+        metrics.extract(&mut dependency("cache.local"));
+
+        let envelopes = metrics.take();
+
+        assert_eq!(
+            properties(&envelope)[PROCESSED_BY_EXTRACTORS],
+            "(Name:'Dependencies', Ver:'1.1')"
+        );
+        assert_eq!(envelopes.len(), 2);
+        let properties = properties(&envelopes[1]);
+        assert_eq!(properties["_MS.MetricId"], "dependencies/duration");
+        assert_eq!(properties["Dependency.Type"], "SQL");
+        assert_eq!(properties["dependency/target"], "db.local");
+        assert_eq!(properties["Dependency.Success"], "True");
+    }
+
+    #[test]
+    fn it_emits_metrics_of_previous_period_once_it_is_over() {
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let metrics = create_metrics();
+
+        metrics.extract(&mut request(100, "200"));
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 59));
+        assert!(metrics.extract(&mut request(100, "200")).is_empty());
+
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 1, 0));
+        let envelopes = metrics.extract(&mut request(100, "200"));
+
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(properties(&envelopes[0])["_MS.AggregationIntervalMs"], "60000");
+        assert_eq!(metrics.take().len(), 1);
+        assert!(metrics.take().is_empty());
+    }
+
+    #[test]
+    fn it_takes_metrics_of_period_only_once_it_is_over() {
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 0));
+        let metrics = create_metrics();
+        assert!(metrics.take_expired().is_empty());
+
+        metrics.extract(&mut request(100, "200"));
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 0, 59));
+        assert!(metrics.take_expired().is_empty());
+
+        time::set(Utc.ymd(2023, 5, 1).and_hms(12, 1, 0));
+        let envelopes = metrics.take_expired();
+
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(properties(&envelopes[0])["_MS.AggregationIntervalMs"], "60000");
+        assert!(metrics.take().is_empty());
+    }
+
+    #[test]
+    fn it_ignores_other_items() {
+        let metrics = create_metrics();
+
+        let mut envelope = Envelope::from((context(), EventTelemetry::new("event")));
+        metrics.extract(&mut envelope);
+
+        assert!(!properties(&envelope).contains_key(PROCESSED_BY_EXTRACTORS));
+        assert!(metrics.take().is_empty());
+    }
+
+    #[test]
+    fn it_does_not_extract_metrics_by_default() {
+        assert!(StandardMetrics::from_config(&TelemetryConfig::new("instrumentation".into())).is_none());
+    }
+
+    #[test_case("0.00:00:00.1200000",   Some(120.0)     ; "fraction of a second")]
+    #[test_case("1.02:03:04.0000000",   Some(93_784_000.0) ; "all units")]
+    #[test_case("0.00:00:00",           None            ; "missing fraction")]
+    #[test_case("0.00:00.0000000",      None            ; "missing seconds")]
+    fn it_parses_duration(duration: &str, expected: Option<f64>) {
+        assert_eq!(duration_ms(duration), expected);
+    }
+
+    #[test_case(0.0,        "<250ms"        ; "zero")]
+    #[test_case(250.0,      "250ms-500ms"   ; "lower bound")]
+    #[test_case(59_999.0,   "30sec-1min"    ; "upper bound")]
+    #[test_case(300_000.0,  ">=5min"        ; "longest")]
+    fn it_determines_performance_bucket(duration: f64, expected: &str) {
+        assert_eq!(performance_bucket(duration), expected);
+    }
+
+    fn create_metrics() -> StandardMetrics {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .extract_standard_metrics(true)
+            .build();
+        StandardMetrics::from_config(&config).unwrap()
+    }
+
+    fn context() -> TelemetryContext {
+        let mut context =
+            TelemetryContext::new("instrumentation".into(), ContextTags::default(), Properties::default());
+        context.tags_mut().cloud_mut().set_role("api".into());
+        context
+    }
+
+    fn request(duration_ms: u64, response_code: &str) -> Envelope {
+        let telemetry = RequestTelemetry::new(
+            "GET /".into(),
+            "https://example.com/".parse().unwrap(),
+            StdDuration::from_millis(duration_ms),
+            response_code,
+        );
+        Envelope::from((context(), telemetry))
+    }
+
+    fn dependency(target: &str) -> Envelope {
+        let telemetry = RemoteDependencyTelemetry::new("SELECT", "SQL", StdDuration::from_millis(10), target, true);
+        Envelope::from((context(), telemetry))
+    }
+
+    fn properties(envelope: &Envelope) -> BTreeMap<String, String> {
+        match envelope.data.clone() {
+            Some(Base::Data(mut data)) => data.properties_mut().take().unwrap_or_default().into(),
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
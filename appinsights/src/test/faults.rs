@@ -0,0 +1,237 @@
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    io,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use http::StatusCode;
+
+use crate::{test::transmission_content, transmitter::HttpResponse, Result};
+
+/// A script of faults injected into submissions of a real telemetry pipeline, so applications can
+/// chaos-test how alerting that depends on telemetry behaves when the pipeline degrades.
+///
+/// Each scripted fault applies to the next submission attempt of a batch, retries included, in the
+/// order faults were added. Once the script is exhausted, batches are sent to the configured
+/// endpoint as usual. Every clone shares the same script, so a test can check how many faults are
+/// [remaining](FaultScript::remaining) while telemetry flows.
+///
+/// The script is configured with
+/// [`TelemetryConfig::builder`](crate::TelemetryConfig::builder) when the `test-util` feature is
+/// enabled.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// use std::time::Duration;
+///
+/// use appinsights::{test::FaultScript, TelemetryConfig};
+/// use http::StatusCode;
+///
+/// let faults = FaultScript::new()
+///     // the endpoint is unreachable twice in a row
+///     .fail()
+///     .fail()
+///     // the next batch is sent after a 10 seconds delay
+///     .latency(Duration::from_secs(10))
+///     // items at index 0 and 2 of the next batch are rejected with a retryable error
+///     .partial([0, 2])
+///     // the next batch is rejected altogether
+///     .status(StatusCode::SERVICE_UNAVAILABLE);
+///
+/// let config = TelemetryConfig::builder()
+///     .i_key("<instrumentation key>")
+///     .fault_injection(faults)
+///     .build();
+/// ```
+#[derive(Clone, Default)]
+pub struct FaultScript(Arc<Mutex<VecDeque<Fault>>>);
+
+impl FaultScript {
+    /// Creates a new empty script that lets all batches through.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the next batch through to the endpoint untouched.
+    pub fn pass(self) -> Self {
+        self.push(Fault::Pass)
+    }
+
+    /// Fails the next batch as if the endpoint was unreachable, so the batch is retried.
+    pub fn fail(self) -> Self {
+        self.push(Fault::Fail)
+    }
+
+    /// Delays the next batch by a given period before it is sent to the endpoint. The delay counts
+    /// towards the [request timeout](crate::TelemetryConfig::request_timeout) if any.
+    pub fn latency(self, delay: Duration) -> Self {
+        self.push(Fault::Latency(delay))
+    }
+
+    /// Responds to the next batch with `206 Partial Content` without sending it to the endpoint.
+    /// Items at given indices are rejected with a retryable `500 Internal Server Error` status, the
+    /// rest of items are considered accepted.
+    pub fn partial(self, indices: impl IntoIterator<Item = usize>) -> Self {
+        let mut indices: Vec<_> = indices.into_iter().collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        self.push(Fault::Partial(indices))
+    }
+
+    /// Responds to the next batch with a given status code and an empty body without sending it to
+    /// the endpoint.
+    pub fn status(self, status: StatusCode) -> Self {
+        self.push(Fault::Status(status))
+    }
+
+    /// Returns the number of faults that are not injected yet.
+    pub fn remaining(&self) -> usize {
+        self.faults().len()
+    }
+
+    /// Injects the next scripted fault into a submission of a batch with a given number of items.
+    /// Returns `None` when the batch should be sent to the endpoint.
+    pub(crate) async fn inject(&self, received: usize) -> Option<Result<HttpResponse>> {
+        let fault = self.faults().pop_front()?;
+        match fault {
+            Fault::Pass => None,
+            Fault::Fail => Some(Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "connection refused by fault injection",
+            )
+            .into())),
+            Fault::Latency(delay) => {
+                tokio::time::sleep(delay).await;
+                None
+            }
+            Fault::Partial(indices) => {
+                let indices: Vec<_> = indices.into_iter().filter(|index| *index < received).collect();
+                let content = transmission_content(received, &indices, StatusCode::INTERNAL_SERVER_ERROR);
+                Some(Ok(HttpResponse::new(
+                    StatusCode::PARTIAL_CONTENT,
+                    content.to_string().into_bytes(),
+                )))
+            }
+            Fault::Status(status) => Some(Ok(HttpResponse::new(status, Vec::default()))),
+        }
+    }
+
+    fn push(self, fault: Fault) -> Self {
+        self.faults().push_back(fault);
+        self
+    }
+
+    fn faults(&self) -> MutexGuard<'_, VecDeque<Fault>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Debug for FaultScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("FaultScript").field(&*self.faults()).finish()
+    }
+}
+
+impl PartialEq for FaultScript {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A fault injected into a single submission.
+#[derive(Debug)]
+enum Fault {
+    Pass,
+    Fail,
+    Latency(Duration),
+    Partial(Vec<usize>),
+    Status(StatusCode),
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        contracts::Envelope,
+        internal_logger::InternalLogger,
+        scheduler::Scheduler,
+        test::FakeIngestion,
+        transmitter::{Response, Transmitter},
+        TelemetryClient, TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_injects_scripted_faults_into_submissions() {
+        let mut ingestion = FakeIngestion::start();
+        let faults = FaultScript::new()
+            .fail()
+            .partial([1, 7])
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .latency(Duration::from_millis(10));
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(ingestion.url())
+            .fault_injection(faults.clone())
+            .build();
+        let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Retry(items()));
+        assert_eq!(
+            transmitter.send(items()).await.unwrap(),
+            Response::Retry(vec![items().remove(1)])
+        );
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Retry(items()));
+        assert_eq!(faults.remaining(), 1);
+        assert_eq!(ingestion.next_batch(Duration::from_millis(100)).await, None);
+
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Success);
+        assert_eq!(transmitter.send(items()).await.unwrap(), Response::Success);
+        assert_eq!(faults.remaining(), 0);
+        assert_eq!(ingestion.next_batch(Duration::from_secs(1)).await, Some(items()));
+        assert_eq!(ingestion.next_batch(Duration::from_secs(1)).await, Some(items()));
+    }
+
+    #[tokio::test]
+    async fn it_delivers_items_after_scripted_failures() {
+        let mut ingestion = FakeIngestion::start();
+        let faults = FaultScript::new().fail().fail();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(ingestion.url())
+            .scheduler(ShortScheduler)
+            .fault_injection(faults.clone())
+            .build();
+        let client = TelemetryClient::from_config(config);
+
+        client.track_event("--event--");
+
+        let batch = ingestion.next_batch(Duration::from_secs(5)).await.unwrap();
+        assert_eq!(faults.remaining(), 0);
+        assert_eq!(batch.len(), 1);
+    }
+
+    /// A scheduler that completes every period shortly, so retries don't take seconds.
+    struct ShortScheduler;
+
+    #[async_trait]
+    impl Scheduler for ShortScheduler {
+        async fn sleep(&self, _: Duration) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    fn items() -> Vec<Envelope> {
+        (0..3)
+            .map(|i| Envelope {
+                name: format!("event {}", i),
+                ..Envelope::default()
+            })
+            .collect()
+    }
+}
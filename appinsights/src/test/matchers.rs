@@ -0,0 +1,356 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use crate::contracts::{Base, Data, Envelope, StringMap};
+
+/// Selects captured telemetry items by the fields of their data.
+pub trait Matcher: Debug {
+    /// Returns `true` if a telemetry item satisfies all conditions of this matcher.
+    fn matches(&self, envelope: &Envelope) -> bool;
+}
+
+/// Returns the first telemetry item that satisfies a given matcher.
+pub fn find<'a>(envelopes: &'a [Envelope], matcher: &impl Matcher) -> Option<&'a Envelope> {
+    envelopes.iter().find(|envelope| matcher.matches(envelope))
+}
+
+/// Asserts that at least one of captured telemetry items satisfies a given matcher and returns
+/// the first one of them.
+///
+/// # Panics
+///
+/// Panics with a list of captured telemetry item names if no item satisfies the matcher.
+#[track_caller]
+pub fn assert_captured(envelopes: &[Envelope], matcher: impl Matcher) -> &Envelope {
+    match find(envelopes, &matcher) {
+        Some(envelope) => envelope,
+        None => panic!(
+            "no captured telemetry item matches {:?}\ncaptured: {:?}",
+            matcher,
+            envelopes
+                .iter()
+                .map(|envelope| envelope.name.as_str())
+                .collect::<Vec<_>>()
+        ),
+    }
+}
+
+/// Matches event telemetry items.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::test::{find, Envelope, EventMatcher};
+///
+/// let envelopes: Vec<Envelope> = Vec::new();
+/// let matcher = EventMatcher::default().name("Application started").property("component", "worker");
+/// assert!(find(&envelopes, &matcher).is_none());
+/// ```
+#[derive(Debug, Default)]
+pub struct EventMatcher {
+    name: Option<String>,
+    properties: BTreeMap<String, String>,
+}
+
+impl EventMatcher {
+    /// Matches events with a given name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Matches events with a custom property of a given value.
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl Matcher for EventMatcher {
+    fn matches(&self, envelope: &Envelope) -> bool {
+        match &envelope.data {
+            Some(Base::Data(Data::EventData(data))) => {
+                is_equal(&self.name, Some(&data.name)) && contains(&data.properties, &self.properties)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Matches request telemetry items.
+#[derive(Debug, Default)]
+pub struct RequestMatcher {
+    name: Option<String>,
+    url: Option<String>,
+    response_code: Option<String>,
+    success: Option<bool>,
+    properties: BTreeMap<String, String>,
+}
+
+impl RequestMatcher {
+    /// Matches requests with a given name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Matches requests with a given URL.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Matches requests with a given response code.
+    pub fn response_code(mut self, response_code: impl Into<String>) -> Self {
+        self.response_code = Some(response_code.into());
+        self
+    }
+
+    /// Matches successful or failed requests.
+    pub fn success(mut self, success: bool) -> Self {
+        self.success = Some(success);
+        self
+    }
+
+    /// Matches requests with a custom property of a given value.
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl Matcher for RequestMatcher {
+    fn matches(&self, envelope: &Envelope) -> bool {
+        match &envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                is_equal(&self.name, data.name.as_ref())
+                    && is_equal(&self.url, data.url.as_ref())
+                    && is_equal(&self.response_code, Some(&data.response_code))
+                    && is_equal(&self.success, Some(&data.success))
+                    && contains(&data.properties, &self.properties)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Matches remote dependency telemetry items.
+#[derive(Debug, Default)]
+pub struct DependencyMatcher {
+    name: Option<String>,
+    dependency_type: Option<String>,
+    target: Option<String>,
+    result_code: Option<String>,
+    success: Option<bool>,
+    properties: BTreeMap<String, String>,
+}
+
+impl DependencyMatcher {
+    /// Matches dependencies with a given name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Matches dependencies of a given type, e.g. `HTTP` or `SQL`.
+    pub fn dependency_type(mut self, dependency_type: impl Into<String>) -> Self {
+        self.dependency_type = Some(dependency_type.into());
+        self
+    }
+
+    /// Matches dependencies with a given target site.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Matches dependencies with a given result code.
+    pub fn result_code(mut self, result_code: impl Into<String>) -> Self {
+        self.result_code = Some(result_code.into());
+        self
+    }
+
+    /// Matches successful or failed dependencies.
+    pub fn success(mut self, success: bool) -> Self {
+        self.success = Some(success);
+        self
+    }
+
+    /// Matches dependencies with a custom property of a given value.
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+}
+
+impl Matcher for DependencyMatcher {
+    fn matches(&self, envelope: &Envelope) -> bool {
+        match &envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                is_equal(&self.name, Some(&data.name))
+                    && is_equal(&self.dependency_type, data.type_.as_ref())
+                    && is_equal(&self.target, data.target.as_ref())
+                    && is_equal(&self.result_code, data.result_code.as_ref())
+                    && is_equal(&self.success, data.success.as_ref())
+                    && contains(&data.properties, &self.properties)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns `true` if an expected value is not specified or equals to an actual one.
+fn is_equal<T: PartialEq>(expected: &Option<T>, actual: Option<&T>) -> bool {
+    expected.as_ref().is_none_or(|expected| actual == Some(expected))
+}
+
+/// Returns `true` if actual properties contain all expected key-value pairs.
+fn contains(actual: &Option<StringMap>, expected: &BTreeMap<String, String>) -> bool {
+    expected
+        .iter()
+        .all(|(key, value)| actual.as_ref().and_then(|actual| actual.get(key)) == Some(value))
+}
+
+/// Asserts that at least one of captured telemetry items is an event with given fields and returns
+/// the first one of them. Fields are named after methods of [`EventMatcher`](crate::test::EventMatcher)
+/// and custom properties are listed in a `props contains` clause.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # async fn run() {
+/// use std::time::Duration;
+///
+/// use appinsights::{assert_event, test::FakeIngestion};
+///
+/// let mut ingestion = FakeIngestion::start();
+/// // ...
+/// let envelopes = ingestion.wait_for_envelopes(1, Duration::from_secs(10)).await;
+/// assert_event!(envelopes, name = "Application started", props contains { "component" => "worker" });
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_event {
+    ($envelopes:expr, $($fields:tt)*) => {
+        $crate::test::assert_captured(&$envelopes, $crate::__matcher!($crate::test::EventMatcher::default(); $($fields)*))
+    };
+}
+
+/// Asserts that at least one of captured telemetry items is a request with given fields and returns
+/// the first one of them. Fields are named after methods of [`RequestMatcher`](crate::test::RequestMatcher)
+/// and custom properties are listed in a `props contains` clause.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// assert_request!(envelopes, name = "GET /api/items", response_code = "200", success = true);
+/// ```
+#[macro_export]
+macro_rules! assert_request {
+    ($envelopes:expr, $($fields:tt)*) => {
+        $crate::test::assert_captured(&$envelopes, $crate::__matcher!($crate::test::RequestMatcher::default(); $($fields)*))
+    };
+}
+
+/// Asserts that at least one of captured telemetry items is a remote dependency with given fields
+/// and returns the first one of them. Fields are named after methods of
+/// [`DependencyMatcher`](crate::test::DependencyMatcher) and custom properties are listed in a
+/// `props contains` clause.
+///
+/// # Examples
+///
+/// ```rust, ignore
+/// assert_dependency!(envelopes, dependency_type = "HTTP", target = "example.com", success = true);
+/// ```
+#[macro_export]
+macro_rules! assert_dependency {
+    ($envelopes:expr, $($fields:tt)*) => {
+        $crate::test::assert_captured(&$envelopes, $crate::__matcher!($crate::test::DependencyMatcher::default(); $($fields)*))
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __matcher {
+    ($matcher:expr;) => {
+        $matcher
+    };
+    ($matcher:expr; props contains { $($key:expr => $value:expr),* $(,)? } $(, $($rest:tt)*)?) => {
+        $crate::__matcher!($matcher $(.property($key, $value))*; $($($rest)*)?)
+    };
+    ($matcher:expr; $field:ident = $value:expr $(, $($rest:tt)*)?) => {
+        $crate::__matcher!($matcher.$field($value); $($($rest)*)?)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::Uri;
+
+    use super::*;
+    use crate::{
+        telemetry::{EventTelemetry, RemoteDependencyTelemetry, RequestTelemetry, Telemetry},
+        TelemetryContext,
+    };
+
+    #[test]
+    fn it_asserts_captured_telemetry_items() {
+        let envelopes = create_envelopes();
+
+        let envelope = assert_event!(envelopes, name = "--event--", props contains { "component" => "worker" });
+        assert_eq!(envelope.name, "Microsoft.ApplicationInsights.Event");
+
+        assert_request!(envelopes, name = "GET /items", response_code = "200", success = true);
+        assert_dependency!(
+            envelopes,
+            dependency_type = "HTTP",
+            target = "example.com",
+            success = false,
+            props contains { "attempt" => "1" },
+        );
+    }
+
+    #[test]
+    fn it_skips_telemetry_items_with_different_fields() {
+        let envelopes = create_envelopes();
+
+        assert!(find(&envelopes, &EventMatcher::default().name("--other--")).is_none());
+        assert!(find(&envelopes, &EventMatcher::default().property("component", "other")).is_none());
+        assert!(find(&envelopes, &EventMatcher::default().property("unknown", "worker")).is_none());
+        assert!(find(&envelopes, &RequestMatcher::default().response_code("500")).is_none());
+        assert!(find(&envelopes, &DependencyMatcher::default().name("--event--")).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "no captured telemetry item matches")]
+    fn it_panics_when_no_telemetry_item_matches() {
+        let envelopes = create_envelopes();
+
+        assert_event!(envelopes, name = "--event--", props contains { "component" => "other" });
+    }
+
+    fn create_envelopes() -> Vec<Envelope> {
+        let context = TelemetryContext::new("instrumentation".into(), Default::default(), Default::default());
+
+        let mut event = EventTelemetry::new("--event--");
+        event.properties_mut().insert("component".into(), "worker".into());
+
+        let request = RequestTelemetry::new(
+            "GET /items".into(),
+            "https://example.com/items".parse::<Uri>().unwrap(),
+            Duration::from_millis(10),
+            "200",
+        );
+
+        let mut dependency =
+            RemoteDependencyTelemetry::new("GET /", "HTTP", Duration::from_millis(10), "example.com", false);
+        dependency.properties_mut().insert("attempt".into(), "1".into());
+
+        vec![
+            (context.clone(), event).into(),
+            (context.clone(), request).into(),
+            (context, dependency).into(),
+        ]
+    }
+}
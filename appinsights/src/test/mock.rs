@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use async_trait::async_trait;
+
+use crate::{
+    channel::{ShutdownReport, TelemetryChannel},
+    contracts::{
+        AvailabilityData, Base, Data, Envelope, EventData, ExceptionData, MessageData, MetricData, PageViewData,
+        PageViewPerfData, RemoteDependencyData, RequestData,
+    },
+    test::{assert_captured, EventMatcher},
+    TelemetryClient, TelemetryConfig,
+};
+
+/// An in-memory telemetry channel that records telemetry items instead of sending them, so unit
+/// tests can assert on telemetry without a fake ingestion server.
+///
+/// Clones share recorded items, so a test keeps one clone and hands a client created with
+/// [`client`](MockChannel::client) to code under test.
+///
+/// # Examples
+///
+/// ```rust
+/// # #[tokio::main]
+/// # async fn main() {
+/// use appinsights::{
+///     test::{MockChannel, RequestData},
+///     TelemetryConfig,
+/// };
+///
+/// let channel = MockChannel::default();
+/// let client = channel.client(TelemetryConfig::new("<instrumentation key>".to_string()));
+///
+/// client.track_event("Application started");
+/// client.track_request(
+///     "GET /items".to_string(),
+///     "https://example.com/items".parse().unwrap(),
+///     std::time::Duration::from_millis(10),
+///     "200",
+/// );
+///
+/// channel.assert_event_named("Application started");
+/// let requests = channel.items_of_type::<RequestData>();
+/// assert_eq!(requests[0].response_code, "200");
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockChannel {
+    envelopes: Arc<Mutex<Vec<Envelope>>>,
+}
+
+impl MockChannel {
+    /// Creates a new telemetry client that records telemetry items into this channel. Processors
+    /// and other client side settings of a configuration are applied as usual.
+    pub fn client(&self, config: TelemetryConfig) -> TelemetryClient {
+        TelemetryClient::create(&config, self.clone())
+    }
+
+    /// Returns all recorded telemetry items in the order they were tracked.
+    pub fn envelopes(&self) -> Vec<Envelope> {
+        self.lock().clone()
+    }
+
+    /// Returns data of recorded telemetry items of a given type, e.g. [`RequestData`].
+    pub fn items_of_type<T: TelemetryData>(&self) -> Vec<T> {
+        self.lock()
+            .iter()
+            .filter_map(|envelope| match &envelope.data {
+                Some(Base::Data(data)) => T::from_data(data).cloned(),
+                None => None,
+            })
+            .collect()
+    }
+
+    /// Asserts that an event with a given name was recorded and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no event with a given name was recorded.
+    #[track_caller]
+    pub fn assert_event_named(&self, name: &str) -> Envelope {
+        let envelopes = self.lock();
+        assert_captured(&envelopes, EventMatcher::default().name(name)).clone()
+    }
+
+    /// Discards all recorded telemetry items.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Envelope>> {
+        self.envelopes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl TelemetryChannel for MockChannel {
+    fn send(&self, envelop: Envelope) {
+        self.lock().push(envelop);
+    }
+
+    fn flush(&self) {}
+
+    async fn close(&self) -> ShutdownReport {
+        ShutdownReport::default()
+    }
+
+    async fn terminate(&self) -> ShutdownReport {
+        ShutdownReport::default()
+    }
+}
+
+/// Data of a specific telemetry type that can be selected from recorded telemetry items.
+pub trait TelemetryData: Clone {
+    /// Returns data of this type if telemetry data is of this type.
+    fn from_data(data: &Data) -> Option<&Self>;
+}
+
+macro_rules! impl_telemetry_data {
+    ($($name:ident),*) => {
+        $(
+            impl TelemetryData for $name {
+                fn from_data(data: &Data) -> Option<&Self> {
+                    match data {
+                        Data::$name(data) => Some(data),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_telemetry_data!(
+    AvailabilityData,
+    EventData,
+    ExceptionData,
+    MessageData,
+    MetricData,
+    PageViewData,
+    PageViewPerfData,
+    RemoteDependencyData,
+    RequestData
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::SeverityLevel;
+
+    #[tokio::test]
+    async fn it_records_tracked_telemetry_items() {
+        let channel = MockChannel::default();
+        let client = channel.client(TelemetryConfig::new("instrumentation".into()));
+
+        client.track_event("--event--");
+        client.track_trace("--trace--", SeverityLevel::Information);
+
+        assert_eq!(channel.envelopes().len(), 2);
+        channel.assert_event_named("--event--");
+
+        let traces = channel.items_of_type::<MessageData>();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].message, "--trace--");
+        assert!(channel.items_of_type::<RequestData>().is_empty());
+
+        channel.clear();
+        assert!(channel.envelopes().is_empty());
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no captured telemetry item matches")]
+    async fn it_panics_when_event_was_not_recorded() {
+        let channel = MockChannel::default();
+        let client = channel.client(TelemetryConfig::new("instrumentation".into()));
+
+        client.track_event("--event--");
+
+        channel.assert_event_named("--other--");
+    }
+}
@@ -0,0 +1,422 @@
+//! Utilities to test applications instrumented with Application Insights.
+//!
+//! [`FakeIngestion`] is a local HTTP server that mimics the ingestion endpoint. Point a client at it
+//! with [`TelemetryConfig::endpoint`](crate::TelemetryConfig::endpoint), script its responses to
+//! exercise retries and throttling, and assert on captured telemetry items as typed [`Envelope`]s.
+//!
+//! ```rust, no_run
+//! # async fn run() {
+//! use std::time::Duration;
+//!
+//! use appinsights::{
+//!     test::{Base, Data, FakeIngestion},
+//!     TelemetryClient, TelemetryConfig,
+//! };
+//!
+//! // reject the first batch with 429 and accept everything afterwards
+//! let mut ingestion = FakeIngestion::builder().throttled(Duration::from_secs(1)).start();
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .endpoint(ingestion.url())
+//!     .build();
+//! let client = TelemetryClient::from_config(config);
+//!
+//! client.track_event("Application started");
+//! client.flush_channel();
+//!
+//! let envelopes = ingestion.wait_for_envelopes(1, Duration::from_secs(10)).await;
+//! match &envelopes[0].data {
+//!     Some(Base::Data(Data::EventData(event))) => assert_eq!(event.name, "Application started"),
+//!     data => panic!("unexpected data: {:?}", data),
+//! }
+//! # }
+//! ```
+//!
+//! Captured telemetry items can be asserted with [`assert_event!`](crate::assert_event),
+//! [`assert_request!`](crate::assert_request) and [`assert_dependency!`](crate::assert_dependency)
+//! macros instead of matching them by hand, or selected with one of [`Matcher`] implementations.
+//!
+//! ```rust, ignore
+//! assert_event!(envelopes, name = "Application started", props contains { "component" => "worker" });
+//! assert_request!(envelopes, name = "GET /api/items", response_code = "200");
+//! assert_dependency!(envelopes, dependency_type = "SQL", success = true);
+//! ```
+//!
+//! Unit tests that only need to know which telemetry was tracked can record it with a
+//! [`MockChannel`] instead of running a fake ingestion endpoint.
+//!
+//! Faults like unreachable endpoints, slow responses or partially accepted batches can be injected
+//! into submissions of a real telemetry pipeline with a [`FaultScript`] to chaos-test how an
+//! application behaves when its telemetry degrades.
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use http::{header::RETRY_AFTER, StatusCode};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server,
+};
+use serde_json::json;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+#[cfg(feature = "test-util")]
+mod faults;
+#[cfg(feature = "test-util")]
+pub use faults::FaultScript;
+
+mod matchers;
+pub use matchers::{assert_captured, find, DependencyMatcher, EventMatcher, Matcher, RequestMatcher};
+
+mod mock;
+pub use mock::{MockChannel, TelemetryData};
+
+mod temp;
+pub use temp::{temp_path, TempPath};
+
+pub use crate::contracts::{
+    AvailabilityData, Base, Data, DataPoint, DataPointType, Envelope, EventData, ExceptionData, ExceptionDetails,
+    MessageData, MetricData, PageViewData, PageViewPerfData, RemoteDependencyData, RequestData, SessionState,
+    SeverityLevel, StackFrame,
+};
+use crate::time;
+
+/// A local HTTP server that mimics the Application Insights ingestion endpoint.
+///
+/// It responds to submitted batches with scripted responses in the order they were added to the
+/// [`FakeIngestionBuilder`] and accepts every batch once the script is exhausted. Every received
+/// batch is captured regardless of a response, so retried items are captured several times.
+///
+/// The server shuts down when this value is dropped.
+pub struct FakeIngestion {
+    url: String,
+    batches: UnboundedReceiver<Vec<Envelope>>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl FakeIngestion {
+    /// Creates a new builder to script responses of the fake ingestion endpoint.
+    pub fn builder() -> FakeIngestionBuilder {
+        FakeIngestionBuilder::default()
+    }
+
+    /// Starts a fake ingestion endpoint that accepts all submitted telemetry.
+    /// It must be called within a tokio runtime.
+    pub fn start() -> Self {
+        Self::builder().start()
+    }
+
+    /// Returns an endpoint URL to configure a telemetry client with.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Waits for the next submitted batch of telemetry items. Returns `None` if no batch was
+    /// received within a given timeout.
+    pub async fn next_batch(&mut self, timeout: Duration) -> Option<Vec<Envelope>> {
+        tokio::time::timeout(timeout, self.batches.recv()).await.ok().flatten()
+    }
+
+    /// Waits until at least a given number of telemetry items is received or a timeout expires.
+    /// Returns all telemetry items received so far in the order they were submitted.
+    pub async fn wait_for_envelopes(&mut self, count: usize, timeout: Duration) -> Vec<Envelope> {
+        let mut envelopes = Vec::new();
+        let _ = tokio::time::timeout(timeout, async {
+            while envelopes.len() < count {
+                match self.batches.recv().await {
+                    Some(batch) => envelopes.extend(batch),
+                    None => break,
+                }
+            }
+        })
+        .await;
+        envelopes
+    }
+}
+
+/// Scripts responses of a [`FakeIngestion`] endpoint.
+///
+/// # Examples
+///
+/// ```rust, no_run
+/// # async fn run() {
+/// use std::time::Duration;
+///
+/// use appinsights::test::FakeIngestion;
+/// use http::StatusCode;
+///
+/// let ingestion = FakeIngestion::builder()
+///     // accept the first batch
+///     .ok()
+///     // reject items at index 1 and 3 of the second batch with a retryable error
+///     .partial([1, 3])
+///     // ask to retry the third batch in 5 seconds
+///     .throttled(Duration::from_secs(5))
+///     // fail the fourth batch
+///     .status(StatusCode::SERVICE_UNAVAILABLE)
+///     .start();
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct FakeIngestionBuilder {
+    responses: VecDeque<FakeResponse>,
+    addr: Option<SocketAddr>,
+}
+
+impl FakeIngestionBuilder {
+    /// Accepts all telemetry items of a batch with `200 OK`.
+    pub fn ok(mut self) -> Self {
+        self.responses.push_back(FakeResponse::Ok);
+        self
+    }
+
+    /// Responds with `206 Partial Content` and rejects items at given indices of a batch with a
+    /// retryable `500 Internal Server Error` status.
+    pub fn partial(mut self, indices: impl IntoIterator<Item = usize>) -> Self {
+        let mut indices: Vec<_> = indices.into_iter().collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        self.responses.push_back(FakeResponse::Partial(indices));
+        self
+    }
+
+    /// Rejects all telemetry items of a batch with `429 Too Many Requests` and asks to retry them
+    /// after a given delay with a `Retry-After` header.
+    pub fn throttled(mut self, retry_after: Duration) -> Self {
+        self.responses.push_back(FakeResponse::Throttled(retry_after));
+        self
+    }
+
+    /// Responds with a given status code and an empty body.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.responses.push_back(FakeResponse::Status(status));
+        self
+    }
+
+    /// Binds the endpoint to a given address instead of a random port of `127.0.0.1`, e.g. to
+    /// `[::1]:0` to exercise IPv6 endpoints.
+    pub fn bind(mut self, addr: impl Into<SocketAddr>) -> Self {
+        self.addr = Some(addr.into());
+        self
+    }
+
+    /// Starts a fake ingestion endpoint on a random local port unless another address is
+    /// [bound](FakeIngestionBuilder::bind). It must be called within a tokio runtime.
+    pub fn start(self) -> FakeIngestion {
+        let (shutdown_send, shutdown_recv) = oneshot::channel::<()>();
+        let (batch_send, batch_recv) = mpsc::unbounded_channel();
+        let responses = Arc::new(Mutex::new(self.responses));
+
+        let make_service = make_service_fn(move |_| {
+            let batch_send = batch_send.clone();
+            let responses = responses.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle(request, batch_send.clone(), responses.clone())
+                }))
+            }
+        });
+
+        let addr = self.addr.unwrap_or_else(|| ([127, 0, 0, 1], 0).into());
+        let server = Server::bind(&addr).serve(make_service);
+        let url = format!("http://{}/v2/track", server.local_addr());
+
+        let graceful = server.with_graceful_shutdown(async {
+            shutdown_recv.await.ok();
+        });
+
+        tokio::spawn(async move {
+            if let Err(err) = graceful.await {
+                log::error!("Fake ingestion server error: {}", err);
+            }
+        });
+
+        FakeIngestion {
+            url,
+            batches: batch_recv,
+            _shutdown: shutdown_send,
+        }
+    }
+}
+
+/// A scripted response to a batch of telemetry items.
+#[derive(Debug)]
+enum FakeResponse {
+    Ok,
+    Partial(Vec<usize>),
+    Throttled(Duration),
+    Status(StatusCode),
+}
+
+async fn handle(
+    request: Request<Body>,
+    batch_send: UnboundedSender<Vec<Envelope>>,
+    responses: Arc<Mutex<VecDeque<FakeResponse>>>,
+) -> Result<Response<Body>, hyper::Error> {
+    let body = hyper::body::to_bytes(request.into_body()).await?;
+    let batch: Vec<Envelope> = match serde_json::from_slice(&body) {
+        Ok(batch) => batch,
+        Err(err) => {
+            log::error!("Unable to parse telemetry items: {}", err);
+            return Ok(Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(err.to_string()))
+                .unwrap());
+        }
+    };
+
+    let received = batch.len();
+    let _ = batch_send.send(batch);
+
+    let response = responses
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop_front()
+        .unwrap_or(FakeResponse::Ok);
+
+    let response = match response {
+        FakeResponse::Ok => transmission(StatusCode::OK, received, &[], StatusCode::OK),
+        FakeResponse::Partial(indices) => {
+            let indices: Vec<_> = indices.into_iter().filter(|index| *index < received).collect();
+            transmission(
+                StatusCode::PARTIAL_CONTENT,
+                received,
+                &indices,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+        FakeResponse::Throttled(retry_after) => {
+            let indices: Vec<_> = (0..received).collect();
+            let mut response = transmission(
+                StatusCode::TOO_MANY_REQUESTS,
+                received,
+                &indices,
+                StatusCode::TOO_MANY_REQUESTS,
+            );
+            let retry_after =
+                time::now() + chrono::Duration::from_std(retry_after).unwrap_or_else(|_| chrono::Duration::zero());
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.to_rfc2822().parse().unwrap());
+            response
+        }
+        FakeResponse::Status(status) => Response::builder().status(status).body(Body::empty()).unwrap(),
+    };
+
+    Ok(response)
+}
+
+/// Creates a response that describes accepted and rejected telemetry items.
+fn transmission(status: StatusCode, received: usize, rejected: &[usize], item_status: StatusCode) -> Response<Body> {
+    let content = transmission_content(received, rejected, item_status);
+
+    Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(content.to_string()))
+        .unwrap()
+}
+
+/// Creates a content of a response that describes accepted and rejected telemetry items.
+pub(crate) fn transmission_content(received: usize, rejected: &[usize], item_status: StatusCode) -> serde_json::Value {
+    let errors: Vec<_> = rejected
+        .iter()
+        .map(|index| {
+            json!({
+                "index": index,
+                "statusCode": item_status.as_u16(),
+                "message": item_status.canonical_reason().unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    json!({
+        "itemsReceived": received,
+        "itemsAccepted": received - rejected.len(),
+        "errors": errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TelemetryClient, TelemetryConfig};
+
+    #[tokio::test]
+    async fn it_captures_typed_telemetry_items() {
+        let mut ingestion = FakeIngestion::start();
+        let client = create_client(&ingestion);
+
+        client.track_event("--event--");
+        client.flush_channel();
+
+        let envelopes = ingestion.wait_for_envelopes(1, Duration::from_secs(10)).await;
+        assert_eq!(envelopes.len(), 1);
+        match &envelopes[0].data {
+            Some(Base::Data(Data::EventData(event))) => assert_eq!(event.name, "--event--"),
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_captures_telemetry_items_sent_to_ipv6_endpoint() {
+        let mut ingestion = FakeIngestion::builder().bind(([0, 0, 0, 0, 0, 0, 0, 1], 0)).start();
+        assert!(ingestion.url().starts_with("http://[::1]:"));
+
+        let client = create_client(&ingestion);
+
+        client.track_event("--event--");
+        client.flush_channel();
+
+        let envelopes = ingestion.wait_for_envelopes(1, Duration::from_secs(10)).await;
+        assert_eq!(envelopes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_responds_with_scripted_responses() {
+        let mut ingestion = FakeIngestion::builder()
+            .partial([2, 0, 5])
+            .throttled(Duration::from_secs(5))
+            .start();
+        let batch = serde_json::to_string(&vec![Envelope::default(); 3]).unwrap();
+        let http = reqwest::Client::new();
+
+        let response = http.post(ingestion.url()).body(batch.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content: serde_json::Value = serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+        assert_eq!(content["itemsReceived"], 3);
+        assert_eq!(content["itemsAccepted"], 1);
+        assert_eq!(content["errors"][0]["index"], 0);
+        assert_eq!(content["errors"][1]["index"], 2);
+
+        let response = http.post(ingestion.url()).body(batch.clone()).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(RETRY_AFTER));
+
+        let response = http.post(ingestion.url()).body(batch).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for _ in 0..3 {
+            let batch = ingestion.next_batch(Duration::from_secs(1)).await.unwrap();
+            assert_eq!(batch.len(), 3);
+        }
+    }
+
+    fn create_client(ingestion: &FakeIngestion) -> TelemetryClient {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation key")
+            .endpoint(ingestion.url())
+            .build();
+        TelemetryClient::from_config(config)
+    }
+}
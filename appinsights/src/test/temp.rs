@@ -0,0 +1,59 @@
+use std::{
+    fs, io,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// A path of a temporary file with a unique name that is removed along with the path, so a test
+/// doesn't leave files behind even when it fails.
+///
+/// The file itself is not created, so it can be handed over to a store or a sink that creates it.
+///
+/// # Examples
+///
+/// ```rust
+/// use appinsights::{sequence::FileSequenceStore, test::temp_path};
+///
+/// let path = temp_path("seq");
+/// let store = FileSequenceStore::new(&path);
+/// ```
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+/// Returns a path of a temporary file with a given extension that is removed when the path is
+/// dropped.
+pub fn temp_path(extension: &str) -> TempPath {
+    let name = format!("appinsights-{}.{}", crate::uuid::new().as_simple(), extension);
+    TempPath(std::env::temp_dir().join(name))
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<&TempPath> for PathBuf {
+    fn from(path: &TempPath) -> Self {
+        path.0.clone()
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.0) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                log::warn!("Unable to remove temporary file {}: {}", self.0.display(), err)
+            }
+            _ => {}
+        }
+    }
+}
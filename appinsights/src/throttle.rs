@@ -0,0 +1,187 @@
+//! Persistence of throttle windows across restarts.
+//!
+//! When the server throttles submissions, it tells when to send them again with a `Retry-After`
+//! header. A submission worker holds back batches until then, but the deadline is kept in memory
+//! only, so an application restarted in the meantime sends telemetry right away and the server
+//! throttles it again.
+//!
+//! With a [`ThrottleStore`] configured with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder), every throttle deadline is saved
+//! to the store. A worker started before a saved deadline is over waits for it before it sends the
+//! first batch.
+//!
+//! ```rust, no_run
+//! use appinsights::{throttle::FileThrottleStore, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .throttle_store(FileThrottleStore::new("/var/lib/my-app/telemetry.throttle"))
+//!     .build();
+//! ```
+use std::{fmt::Debug, fs, io, path::PathBuf, sync::Arc};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{
+    file,
+    internal_logger::{InternalEvent, InternalLogger},
+    shared::Shared,
+};
+
+/// A persistent storage of the time until which the server throttles submissions.
+pub trait ThrottleStore: Send + Sync {
+    /// Returns the throttle deadline saved before or `None` if nothing has been saved yet.
+    fn load(&self) -> io::Result<Option<DateTime<Utc>>>;
+
+    /// Saves the time until which the server throttles submissions.
+    fn save(&self, until: DateTime<Utc>) -> io::Result<()>;
+}
+
+/// A throttle store that keeps a throttle deadline in a file as an RFC 3339 timestamp.
+///
+/// A new deadline is written to a temporary file with a unique name next to the target one first
+/// and then renamed, so the file always contains a complete timestamp.
+#[derive(Debug, Clone)]
+pub struct FileThrottleStore {
+    path: PathBuf,
+}
+
+impl FileThrottleStore {
+    /// Creates a new throttle store that keeps a throttle deadline in a file at a given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ThrottleStore for FileThrottleStore {
+    fn load(&self) -> io::Result<Option<DateTime<Utc>>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => DateTime::parse_from_rfc3339(content.trim())
+                .map(|until| Some(until.with_timezone(&Utc)))
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&self, until: DateTime<Utc>) -> io::Result<()> {
+        let content = until.to_rfc3339_opts(SecondsFormat::Millis, true);
+        file::write_atomically(&self.path, content.as_bytes(), false)
+    }
+}
+
+/// A throttle store shared between a configuration and a channel. It makes a store comparable and
+/// printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SharedThrottleStore {
+    store: Shared<dyn ThrottleStore>,
+    saving: Shared<AsyncMutex<()>>,
+}
+
+impl SharedThrottleStore {
+    pub(crate) fn new(store: impl ThrottleStore + 'static) -> Self {
+        let store: Arc<dyn ThrottleStore> = Arc::new(store);
+        Self {
+            store: store.into(),
+            saving: Shared::new(AsyncMutex::new(())),
+        }
+    }
+
+    /// Returns a saved throttle deadline if any. A deadline that cannot be loaded is reported.
+    pub(crate) fn load(&self, logger: &InternalLogger) -> Option<DateTime<Utc>> {
+        self.store.load().unwrap_or_else(|err| {
+            logger.log(InternalEvent::ThrottleNotLoaded { error: err.to_string() });
+            None
+        })
+    }
+
+    /// Saves a throttle deadline. A store is called on a blocking thread, one save at a time, so
+    /// batches throttled concurrently don't write the same file at once. A deadline that cannot be
+    /// saved is reported.
+    pub(crate) async fn save(&self, until: DateTime<Utc>, logger: &InternalLogger) {
+        let _saving = self.saving.lock().await;
+
+        let store = self.store.clone();
+        let saved = tokio::task::spawn_blocking(move || store.save(until))
+            .await
+            .unwrap_or_else(|err| Err(io::Error::other(err)));
+        if let Err(err) = saved {
+            logger.log(InternalEvent::ThrottleNotSaved {
+                until: until.to_rfc3339_opts(SecondsFormat::Millis, true),
+                error: err.to_string(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::TelemetryConfig;
+
+    #[test]
+    fn it_keeps_throttle_deadline_in_file() {
+        let path = crate::test::temp_path("throttle");
+        let store = FileThrottleStore::new(&path);
+        let until = Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 30, 250);
+
+        assert_eq!(store.load().unwrap(), None);
+        store.save(until).unwrap();
+        assert_eq!(store.load().unwrap(), Some(until));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn it_saves_deadlines_one_at_a_time() {
+        let store = SharedThrottleStore::new(SlowStore::default());
+        let logger = InternalLogger::from_config(&TelemetryConfig::new("instrumentation".into()));
+        let until = Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 30, 250);
+
+        let saves: Vec<_> = (0..4)
+            .map(|_| {
+                let (store, logger) = (store.clone(), logger.clone());
+                tokio::spawn(async move { store.save(until, &logger).await })
+            })
+            .collect();
+        for save in saves {
+            save.await.unwrap();
+        }
+
+        assert_eq!(store.load(&logger), Some(until));
+    }
+
+    /// A store that fails a save that overlaps with another one.
+    #[derive(Default)]
+    struct SlowStore {
+        saving: AtomicBool,
+        saved: AtomicUsize,
+        until: Mutex<Option<DateTime<Utc>>>,
+    }
+
+    impl ThrottleStore for SlowStore {
+        fn load(&self) -> io::Result<Option<DateTime<Utc>>> {
+            assert_eq!(self.saved.load(Ordering::SeqCst), 4);
+            Ok(*self.until.lock().unwrap())
+        }
+
+        fn save(&self, until: DateTime<Utc>) -> io::Result<()> {
+            assert!(!self.saving.swap(true, Ordering::SeqCst), "saves overlap");
+            thread::sleep(Duration::from_millis(10));
+            *self.until.lock().unwrap() = Some(until);
+            self.saved.fetch_add(1, Ordering::SeqCst);
+            self.saving.store(false, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
@@ -19,39 +19,49 @@ mod imp {
 
     use lazy_static::lazy_static;
     use parking_lot::Mutex;
-    use tokio::{sync::Notify, time::Instant};
+    use tokio::{sync::Semaphore, time::Instant};
 
     lazy_static! {
-        static ref CHANNEL: Mutex<Option<Arc<Notify>>> = Mutex::new(None);
+        static ref CHANNEL: Mutex<Option<Arc<Semaphore>>> = Mutex::new(None);
+        static ref PERIODS: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
     }
 
     /// Initializes a channel which emulates timeout expiration event. External code should run
     /// [`expire`](#method.expire) method in order to emulate timeout expiration.
     pub fn init() {
         let mut channel = CHANNEL.lock();
-        *channel = Some(Arc::new(Notify::new()));
+        *channel = Some(Arc::new(Semaphore::new(0)));
+        PERIODS.lock().clear();
     }
 
     /// Creates a copy of a receiver that delivers a current time stamp in order to emulate
     /// timeout expiration for tests.
     pub async fn sleep(duration: Duration) {
-        let maybe_notify = CHANNEL.lock().clone();
+        let maybe_expirations = CHANNEL.lock().clone();
 
-        if let Some(notify) = maybe_notify {
-            notify.notified().await;
+        if let Some(expirations) = maybe_expirations {
+            PERIODS.lock().push(duration);
+            // an expiration emulated before a timeout is awaited completes it at once
+            if let Ok(expiration) = expirations.acquire().await {
+                expiration.forget();
+            }
         } else {
             let timeout = Instant::now() + duration;
             tokio::time::sleep_until(timeout).await;
         }
     }
 
+    /// Returns periods of emulated timeouts requested since a channel was initialized.
+    pub fn periods() -> Vec<Duration> {
+        PERIODS.lock().clone()
+    }
+
     /// Emulates timeout expiration event.
     /// It sends a current time stamp to receiver in order to trigger an action if a channel was
     /// initialized in advance. Does nothing otherwise.
     pub fn expire() {
-        if let Some(notify) = CHANNEL.lock().clone() {
-            log::error!("notify_one");
-            notify.notify_one();
+        if let Some(expirations) = CHANNEL.lock().clone() {
+            expirations.add_permits(1);
         }
     }
 
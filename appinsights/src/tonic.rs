@@ -0,0 +1,582 @@
+//! [`tonic`](https://docs.rs/tonic) middlewares that track inbound and outbound gRPC calls.
+//!
+//! gRPC servers and channels built with `tonic` are `tower` services, so calls are tracked with
+//! layers, the same way HTTP requests are tracked by the [`tower`](crate::tower) middleware.
+//!
+//! * [`AppInsightsGrpcRequestLayer`] submits a [`RequestTelemetry`](crate::telemetry::RequestTelemetry)
+//!   for every inbound call of a server. It is named by the full method name, e.g.
+//!   `/helloworld.Greeter/SayHello`, and the response code is the gRPC status code of the call.
+//!   The [`TraceParent`] of the call is inserted into request extensions, so handlers can correlate
+//!   their own telemetry and outbound calls with it.
+//! * [`AppInsightsGrpcDependencyLayer`] submits a
+//!   [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry) of `GRPC` type for
+//!   every outbound call of a channel, and sends `traceparent` and `Request-Id` metadata, so the
+//!   remote side correlates its telemetry with the current operation.
+//!
+//! The gRPC status of a call is read from trailers once a response body is consumed, or from
+//! headers of a response without a body. A call which response future or body is dropped before
+//! its status is received is reported as cancelled.
+//!
+//! ```rust, no_run
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! use appinsights::{
+//!     tonic::{AppInsightsGrpcDependencyLayer, AppInsightsGrpcRequestLayer},
+//!     TelemetryClient,
+//! };
+//! use tonic::transport::{Channel, Server};
+//! use tower::ServiceBuilder;
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! // track inbound calls of a server
+//! let server = Server::builder().layer(AppInsightsGrpcRequestLayer::new(client.clone()));
+//!
+//! // track outbound calls of a client
+//! let channel = Channel::from_static("http://[::1]:50051").connect().await?;
+//! let channel = ServiceBuilder::new()
+//!     .layer(AppInsightsGrpcDependencyLayer::new(client))
+//!     .service(channel);
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use chrono::{DateTime, Utc};
+use http::{HeaderMap, HeaderValue, Request, Response, Uri};
+use http_body::{Body, SizeHint};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    processor::ProcessingContext,
+    telemetry::{RemoteDependencyTelemetry, RequestTelemetry, Telemetry, TraceParent},
+    time, TelemetryClient,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "tonic";
+
+/// The name of a header or a trailer that carries a gRPC status code of a call.
+const GRPC_STATUS: &str = "grpc-status";
+
+/// The gRPC status code of a call which response was dropped before its status was received.
+const CANCELLED: &str = "1";
+
+/// The gRPC status code of a call which status is not known.
+const UNKNOWN: &str = "2";
+
+/// The name of the legacy header that carries request id of the caller.
+const REQUEST_ID_HEADER: &str = "Request-Id";
+
+/// A [`Layer`] that wraps gRPC server services to track inbound calls.
+#[derive(Clone)]
+pub struct AppInsightsGrpcRequestLayer {
+    client: TelemetryClient,
+}
+
+impl AppInsightsGrpcRequestLayer {
+    /// Creates a new layer that submits request telemetry with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self { client }
+    }
+}
+
+impl<S> Layer<S> for AppInsightsGrpcRequestLayer {
+    type Service = AppInsightsGrpcRequestService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AppInsightsGrpcRequestService {
+            inner,
+            client: self.client.clone(),
+        }
+    }
+}
+
+/// A middleware service that submits a [`RequestTelemetry`](crate::telemetry::RequestTelemetry)
+/// for every call handled by the inner gRPC service.
+#[derive(Clone)]
+pub struct AppInsightsGrpcRequestService<S> {
+    inner: S,
+    client: TelemetryClient,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AppInsightsGrpcRequestService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = Response<GrpcBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let incoming = request
+            .headers()
+            .get(TraceParent::HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<TraceParent>().ok());
+        let trace_parent = incoming.as_ref().map_or_else(TraceParent::new, TraceParent::child);
+
+        let tracker = CallTracker {
+            client: self.client.clone(),
+            kind: CallKind::Inbound(request.uri().clone()),
+            name: request.uri().path().into(),
+            started: time::now(),
+            parent_id: incoming.map(|incoming| incoming.span_id().to_string()),
+            trace_parent: trace_parent.clone(),
+        };
+
+        request.extensions_mut().insert(trace_parent);
+
+        ResponseFuture {
+            inner: self.inner.call(request),
+            tracker: Some(tracker),
+        }
+    }
+}
+
+/// A [`Layer`] that wraps gRPC channels to track outbound calls.
+#[derive(Clone)]
+pub struct AppInsightsGrpcDependencyLayer {
+    client: TelemetryClient,
+}
+
+impl AppInsightsGrpcDependencyLayer {
+    /// Creates a new layer that submits dependency telemetry with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self { client }
+    }
+}
+
+impl<S> Layer<S> for AppInsightsGrpcDependencyLayer {
+    type Service = AppInsightsGrpcDependencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AppInsightsGrpcDependencyService {
+            inner,
+            client: self.client.clone(),
+        }
+    }
+}
+
+/// A middleware service that submits a
+/// [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry) for every call sent
+/// by the inner gRPC channel. When request extensions contain a [`TraceParent`], for instance the
+/// one inserted by [`AppInsightsGrpcRequestLayer`] for an inbound call, the dependency becomes a
+/// part of that operation. Otherwise every call starts a new distributed trace.
+#[derive(Clone)]
+pub struct AppInsightsGrpcDependencyService<S> {
+    inner: S,
+    client: TelemetryClient,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AppInsightsGrpcDependencyService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = Response<GrpcBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let parent = request.extensions().get::<TraceParent>().cloned();
+        let trace_parent = parent.as_ref().map_or_else(TraceParent::new, TraceParent::child);
+
+        let headers = request.headers_mut();
+        if let Ok(value) = HeaderValue::from_str(&trace_parent.to_string()) {
+            headers.insert(TraceParent::HEADER, value);
+        }
+        let request_id = format!("|{}.{}.", trace_parent.trace_id(), trace_parent.span_id());
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            headers.insert(REQUEST_ID_HEADER, value);
+        }
+
+        let tracker = CallTracker {
+            client: self.client.clone(),
+            kind: CallKind::Outbound(request.uri().clone()),
+            name: request.uri().path().into(),
+            started: time::now(),
+            parent_id: parent.map(|parent| parent.span_id().to_string()),
+            trace_parent,
+        };
+
+        ResponseFuture {
+            inner: self.inner.call(request),
+            tracker: Some(tracker),
+        }
+    }
+}
+
+pin_project! {
+    /// Response future of gRPC middleware services.
+    ///
+    /// A call is tracked as cancelled if the future is dropped before a response is received.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        tracker: Option<CallTracker>,
+    }
+
+    impl<F> PinnedDrop for ResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(tracker) = this.project().tracker.take() {
+                tracker.track(CANCELLED);
+            }
+        }
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = Result<Response<GrpcBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let tracker = this.tracker.take();
+        Poll::Ready(match result {
+            Ok(response) => {
+                // a response without a body carries the status in headers instead of trailers
+                let tracker = match (tracker, grpc_status(response.headers())) {
+                    (Some(tracker), Some(status)) => {
+                        tracker.track(&status);
+                        None
+                    }
+                    (tracker, _) => tracker,
+                };
+                Ok(response.map(|inner| GrpcBody { inner, tracker }))
+            }
+            Err(err) => {
+                if let Some(tracker) = tracker {
+                    tracker.track(UNKNOWN);
+                }
+                Err(err)
+            }
+        })
+    }
+}
+
+pin_project! {
+    /// A response body of a gRPC call that submits a telemetry item once the status of the call
+    /// is received with trailers.
+    pub struct GrpcBody<B> {
+        #[pin]
+        inner: B,
+        tracker: Option<CallTracker>,
+    }
+
+    impl<B> PinnedDrop for GrpcBody<B> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(tracker) = this.project().tracker.take() {
+                tracker.track(CANCELLED);
+            }
+        }
+    }
+}
+
+impl<B: Body> Body for GrpcBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let poll = this.inner.poll_data(cx);
+        if let Poll::Ready(Some(Err(_))) = &poll {
+            if let Some(tracker) = this.tracker.take() {
+                tracker.track(UNKNOWN);
+            }
+        }
+        poll
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let poll = this.inner.poll_trailers(cx);
+        if let Poll::Ready(result) = &poll {
+            if let Some(tracker) = this.tracker.take() {
+                let status = match result {
+                    Ok(Some(trailers)) => grpc_status(trailers),
+                    _ => None,
+                };
+                tracker.track(status.as_deref().unwrap_or(UNKNOWN));
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Reads a gRPC status code of a call from headers or trailers.
+fn grpc_status(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(GRPC_STATUS)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+/// A direction of a tracked call along with its URI.
+enum CallKind {
+    Inbound(Uri),
+    Outbound(Uri),
+}
+
+/// Collects call data required to submit a telemetry item when the status of the call is known.
+pub struct CallTracker {
+    client: TelemetryClient,
+    kind: CallKind,
+    name: String,
+    started: DateTime<Utc>,
+    parent_id: Option<String>,
+    trace_parent: TraceParent,
+}
+
+impl CallTracker {
+    fn track(self, status: &str) {
+        let duration = (time::now() - self.started).to_std().unwrap_or_default();
+        let success = status == "0";
+
+        let mut processing = ProcessingContext::new(Some(INTEGRATION));
+        processing.extensions_mut().insert(self.trace_parent.clone());
+
+        match self.kind {
+            CallKind::Inbound(uri) => {
+                let mut telemetry = RequestTelemetry::new(self.name, uri, duration, status);
+                *telemetry.timestamp_mut() = self.started;
+                telemetry.set_success(success);
+                telemetry.set_id(self.trace_parent.span_id());
+
+                let mut operation = telemetry.tags_mut().operation_mut();
+                operation.set_id(self.trace_parent.trace_id().into());
+                if let Some(parent_id) = self.parent_id {
+                    operation.set_parent_id(parent_id);
+                }
+                self.client.track_with_context(telemetry, processing);
+            }
+            CallKind::Outbound(uri) => {
+                let target = uri
+                    .authority()
+                    .map(|authority| authority.to_string())
+                    .unwrap_or_default();
+                let mut telemetry = RemoteDependencyTelemetry::new(self.name, "GRPC", duration, target, success);
+                *telemetry.timestamp_mut() = self.started;
+                *telemetry.result_code_mut() = Some(status.into());
+                telemetry.set_id(self.trace_parent.span_id());
+                telemetry.set_data(uri.to_string());
+
+                let mut operation = telemetry.tags_mut().operation_mut();
+                operation.set_id(self.trace_parent.trace_id().into());
+                if let Some(parent_id) = self.parent_id {
+                    operation.set_parent_id(parent_id);
+                }
+                self.client.track_with_context(telemetry, processing);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, future::Ready, sync::Arc};
+
+    use crossbeam_queue::SegQueue;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_tracks_inbound_call_with_status_of_trailers() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsGrpcRequestLayer::new(create_client(events.clone())).layer(TestService(None));
+
+        let request = Request::post("http://localhost:50051/helloworld.Greeter/SayHello")
+            .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let mut body = service.call(request).await.unwrap().into_body();
+        assert_eq!(events.len(), 0);
+
+        while body.data().await.is_some() {}
+        body.trailers().await.unwrap();
+        assert_eq!(events.len(), 1);
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("/helloworld.Greeter/SayHello".into()));
+                assert_eq!(data.response_code, "5");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_tracks_inbound_call_with_status_of_headers() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsGrpcRequestLayer::new(create_client(events.clone())).layer(TestService(Some("0")));
+
+        let request = Request::post("/helloworld.Greeter/SayHello")
+            .body(hyper::Body::empty())
+            .unwrap();
+        service.call(request).await.unwrap();
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.response_code, "0");
+                assert!(data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_tracks_outbound_call_cancelled_before_status_received() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsGrpcDependencyLayer::new(create_client(events.clone())).layer(TestService(None));
+
+        let current: TraceParent = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+            .parse()
+            .unwrap();
+        let mut request = Request::post("http://localhost:50051/helloworld.Greeter/SayHello")
+            .body(hyper::Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(current);
+        let response = service.call(request).await.unwrap();
+
+        let trace_parent: TraceParent = response.headers()["x-traceparent"].to_str().unwrap().parse().unwrap();
+        drop(response);
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "/helloworld.Greeter/SayHello");
+                assert_eq!(data.type_, Some("GRPC".into()));
+                assert_eq!(data.target, Some("localhost:50051".into()));
+                assert_eq!(data.result_code, Some(CANCELLED.into()));
+                assert_eq!(data.success, Some(false));
+                assert_eq!(data.id, Some(trace_parent.span_id().into()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_tracks_inbound_call_cancelled_before_response_produced() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsGrpcRequestLayer::new(create_client(events.clone())).layer(PendingService);
+
+        let request = Request::post("/helloworld.Greeter/SayHello")
+            .body(hyper::Body::empty())
+            .unwrap();
+        let response = service.call(request);
+        assert_eq!(events.len(), 0);
+
+        drop(response);
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("/helloworld.Greeter/SayHello".into()));
+                assert_eq!(data.response_code, CANCELLED);
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(events.len(), 0);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+
+    /// Responds with a given status in headers, or with a body which trailers carry `NOT_FOUND`
+    /// status otherwise. Echoes a `traceparent` header as `x-traceparent`.
+    struct TestService(Option<&'static str>);
+
+    impl Service<Request<hyper::Body>> for TestService {
+        type Response = Response<hyper::Body>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<hyper::Body>) -> Self::Future {
+            let mut response = Response::builder();
+            if let Some(trace_parent) = request.headers().get(TraceParent::HEADER) {
+                response = response.header("x-traceparent", trace_parent);
+            }
+            let response = match self.0 {
+                Some(status) => response.header(GRPC_STATUS, status).body(hyper::Body::empty()),
+                None => {
+                    let (mut sender, body) = hyper::Body::channel();
+                    let mut trailers = HeaderMap::new();
+                    trailers.insert(GRPC_STATUS, HeaderValue::from_static("5"));
+                    sender.try_send_data("message".into()).unwrap();
+                    tokio::spawn(async move { sender.send_trailers(trailers).await });
+                    response.body(body)
+                }
+            };
+            std::future::ready(Ok(response.unwrap()))
+        }
+    }
+
+    /// Never responds.
+    struct PendingService;
+
+    impl Service<Request<hyper::Body>> for PendingService {
+        type Response = Response<hyper::Body>;
+        type Error = Infallible;
+        type Future = std::future::Pending<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<hyper::Body>) -> Self::Future {
+            std::future::pending()
+        }
+    }
+}
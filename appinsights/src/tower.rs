@@ -0,0 +1,448 @@
+//! A [`tower`](https://docs.rs/tower) middleware that tracks incoming HTTP requests.
+//!
+//! [`AppInsightsRequestLayer`] measures the time spent to serve each request and submits a
+//! [`RequestTelemetry`](crate::telemetry::RequestTelemetry) item once a response is produced. It
+//! works with any `tower` based HTTP stack such as `axum`, `tonic` or `hyper`.
+//!
+//! When a request contains a `traceparent` header, the telemetry is correlated with the caller
+//! operation. The [`TraceParent`](crate::telemetry::TraceParent) of the current request is inserted
+//! into request extensions so that handlers can correlate their own telemetry with it.
+//!
+//! Requests of health probes, availability tests or web crawlers can be marked as synthetic traffic
+//! by their `User-Agent` header with [`with_synthetic_traffic`](AppInsightsRequestLayer::with_synthetic_traffic).
+//!
+//! A request whose response future is dropped before a response is produced, e.g. because a client
+//! closed the connection or a timeout elapsed, is tracked with `499` response code.
+//!
+//! ```rust, no_run
+//! use appinsights::{tower::AppInsightsRequestLayer, TelemetryClient};
+//! use axum::{routing::get, Router};
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! let app = Router::new()
+//!     .route("/", get(|| async { "Hello, World!" }))
+//!     .layer(AppInsightsRequestLayer::new(client));
+//! ```
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use chrono::{DateTime, Utc};
+use http::{Extensions, Method, Request, Response, Uri};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{
+    processor::ProcessingContext,
+    telemetry::{RequestTelemetry, SyntheticTraffic, Telemetry, TraceParent},
+    time, TelemetryClient,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "tower";
+
+/// A response code of requests cancelled before a response is produced, as reported by `nginx`
+/// for requests closed by a client.
+const CANCELLED: &str = "499";
+
+type NameFn = Arc<dyn Fn(&Method, &Uri, &Extensions) -> String + Send + Sync>;
+
+/// A [`Layer`] that wraps services with [`AppInsightsRequestService`] to track incoming requests.
+#[derive(Clone)]
+pub struct AppInsightsRequestLayer {
+    client: TelemetryClient,
+    name: NameFn,
+    synthetic: Arc<SyntheticTraffic>,
+}
+
+impl AppInsightsRequestLayer {
+    /// Creates a new layer that submits request telemetry with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self {
+            client,
+            name: Arc::new(|method, uri, _| format!("{} {}", method, uri.path())),
+            synthetic: Arc::default(),
+        }
+    }
+
+    /// Overrides how a request telemetry name is built. By default it is a method followed by a
+    /// request path. A route template is a better choice to keep cardinality low, for instance
+    /// the value of `axum::extract::MatchedPath` found in request extensions.
+    ///
+    /// Note that `axum` inserts `MatchedPath` into request extensions only once a request is routed,
+    /// so the layer must be added with `Router::route_layer` rather than `Router::layer` to see it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{tower::AppInsightsRequestLayer, TelemetryClient};
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use axum::{extract::MatchedPath, routing::get, Router};
+    ///
+    /// let layer = AppInsightsRequestLayer::new(client).with_name(|method, uri, extensions| {
+    ///     let route = extensions.get::<MatchedPath>().map_or(uri.path(), |path| path.as_str());
+    ///     format!("{} {}", method, route)
+    /// });
+    ///
+    /// let app = Router::new()
+    ///     .route("/users/:id", get(|| async { "Hello, World!" }))
+    ///     .route_layer(layer);
+    /// ```
+    pub fn with_name<F>(mut self, name: F) -> Self
+    where
+        F: Fn(&Method, &Uri, &Extensions) -> String + Send + Sync + 'static,
+    {
+        self.name = Arc::new(name);
+        self
+    }
+
+    /// Marks requests recognized by given rules as synthetic traffic. By default no request is
+    /// considered synthetic.
+    ///
+    /// # Examples
+    ///
+    /// ```rust, no_run
+    /// # use appinsights::{tower::AppInsightsRequestLayer, TelemetryClient};
+    /// # let client = TelemetryClient::new("<instrumentation key>".to_string());
+    /// use appinsights::telemetry::SyntheticTraffic;
+    ///
+    /// let synthetic = SyntheticTraffic::well_known().user_agent("load-tester", "Load Test");
+    /// let layer = AppInsightsRequestLayer::new(client).with_synthetic_traffic(synthetic);
+    /// ```
+    pub fn with_synthetic_traffic(mut self, synthetic: SyntheticTraffic) -> Self {
+        self.synthetic = Arc::new(synthetic);
+        self
+    }
+}
+
+impl<S> Layer<S> for AppInsightsRequestLayer {
+    type Service = AppInsightsRequestService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AppInsightsRequestService {
+            inner,
+            client: self.client.clone(),
+            name: self.name.clone(),
+            synthetic: self.synthetic.clone(),
+        }
+    }
+}
+
+/// A middleware service that submits a [`RequestTelemetry`](crate::telemetry::RequestTelemetry)
+/// for every request handled by the inner service.
+#[derive(Clone)]
+pub struct AppInsightsRequestService<S> {
+    inner: S,
+    client: TelemetryClient,
+    name: NameFn,
+    synthetic: Arc<SyntheticTraffic>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AppInsightsRequestService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        let incoming = request
+            .headers()
+            .get(TraceParent::HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<TraceParent>().ok());
+        let trace_parent = incoming.as_ref().map_or_else(TraceParent::new, TraceParent::child);
+
+        let name = (self.name)(request.method(), request.uri(), request.extensions());
+        let synthetic_source = request
+            .headers()
+            .get(http::header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|user_agent| self.synthetic.detect(user_agent))
+            .map(String::from);
+        let tracker = RequestTracker {
+            client: self.client.clone(),
+            name,
+            uri: request.uri().clone(),
+            started: time::now(),
+            parent_id: incoming.map(|incoming| incoming.span_id().to_string()),
+            synthetic_source,
+            trace_parent: trace_parent.clone(),
+        };
+
+        request.extensions_mut().insert(trace_parent);
+
+        ResponseFuture {
+            inner: self.inner.call(request),
+            tracker: Some(tracker),
+        }
+    }
+}
+
+pin_project! {
+    /// Response future of [`AppInsightsRequestService`].
+    ///
+    /// A request is tracked as cancelled if the future is dropped before it completes.
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        tracker: Option<RequestTracker>,
+    }
+
+    impl<F> PinnedDrop for ResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            if let Some(tracker) = this.project().tracker.take() {
+                tracker.track(CANCELLED.into());
+            }
+        }
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let Some(tracker) = this.tracker.take() {
+            let response_code = match &result {
+                Ok(response) => response.status().as_str().to_string(),
+                Err(_) => http::StatusCode::INTERNAL_SERVER_ERROR.as_str().to_string(),
+            };
+            tracker.track(response_code);
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+/// Collects request data required to submit a telemetry item when a response is ready.
+struct RequestTracker {
+    client: TelemetryClient,
+    name: String,
+    uri: Uri,
+    started: DateTime<Utc>,
+    parent_id: Option<String>,
+    synthetic_source: Option<String>,
+    trace_parent: TraceParent,
+}
+
+impl RequestTracker {
+    fn track(self, response_code: String) {
+        let duration = (time::now() - self.started).to_std().unwrap_or_default();
+
+        let mut telemetry = RequestTelemetry::new(self.name, self.uri, duration, response_code);
+        *telemetry.timestamp_mut() = self.started;
+        telemetry.set_id(self.trace_parent.span_id());
+
+        let mut operation = telemetry.tags_mut().operation_mut();
+        operation.set_id(self.trace_parent.trace_id().into());
+        if let Some(parent_id) = self.parent_id {
+            operation.set_parent_id(parent_id);
+        }
+        if let Some(synthetic_source) = self.synthetic_source {
+            operation.set_synthetic_source(synthetic_source);
+        }
+
+        let mut processing = ProcessingContext::new(Some(INTEGRATION));
+        processing.extensions_mut().insert(self.trace_parent);
+        self.client.track_with_context(telemetry, processing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, future::Ready, sync::Arc};
+
+    use crossbeam_queue::SegQueue;
+    use http::StatusCode;
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[tokio::test]
+    async fn it_tracks_request_with_response_status() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsRequestLayer::new(create_client(events.clone())).layer(TestService);
+
+        let request = Request::get("https://example.com/users/42?q=1").body(()).unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(events.len(), 1);
+
+        let envelope = events.pop().unwrap();
+        let tags = envelope.tags.unwrap();
+        match envelope.data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /users/42".into()));
+                assert_eq!(data.response_code, "404");
+                assert!(!data.success);
+                assert_eq!(data.url, Some("https://example.com/users/42".into()));
+                assert_eq!(response.headers().get("x-span-id").unwrap(), data.id.as_str());
+                assert_eq!(
+                    tags.get("ai.operation.id").map(String::as_str),
+                    response.headers().get("x-trace-id").map(|id| id.to_str().unwrap())
+                );
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(tags.get("ai.operation.parentId"), None);
+    }
+
+    #[tokio::test]
+    async fn it_correlates_request_with_incoming_trace_parent() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsRequestLayer::new(create_client(events.clone())).layer(TestService);
+
+        let request = Request::get("https://example.com/")
+            .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .body(())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+
+        assert_eq!(
+            response.headers().get("x-trace-id").unwrap(),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.id"),
+            Some(&"0af7651916cd43dd8448eb211c80319c".to_string())
+        );
+        assert_eq!(tags.get("ai.operation.parentId"), Some(&"b7ad6b7169203331".to_string()));
+    }
+
+    #[tokio::test]
+    async fn it_uses_custom_request_name() {
+        let events = Arc::new(SegQueue::default());
+        let layer = AppInsightsRequestLayer::new(create_client(events.clone()))
+            .with_name(|method, _, _| format!("{} /users/{{id}}", method));
+        let mut service = layer.layer(TestService);
+
+        let request = Request::post("https://example.com/users/42").body(()).unwrap();
+        service.call(request).await.unwrap();
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => assert_eq!(data.name, Some("POST /users/{id}".into())),
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_marks_synthetic_traffic_by_user_agent() {
+        let events = Arc::new(SegQueue::default());
+        let layer = AppInsightsRequestLayer::new(create_client(events.clone()))
+            .with_synthetic_traffic(SyntheticTraffic::well_known());
+        let mut service = layer.layer(TestService);
+
+        let probe = Request::get("https://example.com/health")
+            .header("user-agent", "kube-probe/1.27")
+            .body(())
+            .unwrap();
+        service.call(probe).await.unwrap();
+        let user = Request::get("https://example.com/")
+            .header("user-agent", "Mozilla/5.0 (X11; Linux x86_64)")
+            .body(())
+            .unwrap();
+        service.call(user).await.unwrap();
+
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(
+            tags.get("ai.operation.syntheticSource"),
+            Some(&"Health Probe".to_string())
+        );
+        let tags = events.pop().unwrap().tags.unwrap();
+        assert_eq!(tags.get("ai.operation.syntheticSource"), None);
+    }
+
+    #[tokio::test]
+    async fn it_tracks_request_as_cancelled_when_response_future_dropped() {
+        let events = Arc::new(SegQueue::default());
+        let mut service = AppInsightsRequestLayer::new(create_client(events.clone())).layer(PendingService);
+
+        let request = Request::get("https://example.com/slow").body(()).unwrap();
+        let response = service.call(request);
+        assert_eq!(events.len(), 0);
+
+        drop(response);
+
+        match events.pop().unwrap().data {
+            Some(Base::Data(Data::RequestData(data))) => {
+                assert_eq!(data.name, Some("GET /slow".into()));
+                assert_eq!(data.response_code, "499");
+                assert!(!data.success);
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+        assert_eq!(events.len(), 0);
+    }
+
+    fn create_client(events: Arc<SegQueue<Envelope>>) -> TelemetryClient {
+        let config = TelemetryConfig::new("instrumentation".into());
+        TelemetryClient::create(&config, TestChannel::new(events))
+    }
+
+    /// Responds with 404 and echoes trace parent found in request extensions via response headers.
+    struct TestService;
+
+    impl Service<Request<()>> for TestService {
+        type Response = Response<()>;
+        type Error = Infallible;
+        type Future = Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: Request<()>) -> Self::Future {
+            let trace_parent = request.extensions().get::<TraceParent>().unwrap();
+            let response = Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .header("x-trace-id", trace_parent.trace_id())
+                .header("x-span-id", trace_parent.span_id())
+                .body(())
+                .unwrap();
+            std::future::ready(Ok(response))
+        }
+    }
+
+    /// Never responds.
+    struct PendingService;
+
+    impl Service<Request<()>> for PendingService {
+        type Response = Response<()>;
+        type Error = Infallible;
+        type Future = std::future::Pending<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: Request<()>) -> Self::Future {
+            std::future::pending()
+        }
+    }
+}
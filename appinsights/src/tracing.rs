@@ -0,0 +1,448 @@
+//! A [`tracing`](https://docs.rs/tracing) integration that tracks spans as in-process dependencies.
+//!
+//! [`AppInsightsLayer`] is a [`Layer`] that submits a
+//! [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry) item of `InProc` type
+//! for every span of a configured level or above once the span is closed. A name of an item
+//! follows a name of the span, and its duration is the time between the span was created and
+//! closed. A target and fields recorded on the span are submitted as custom properties. Spans of
+//! the `appinsights` crate itself are never tracked, and events are not tracked at all. The layer
+//! does not filter spans and events for other layers, so it is combined with formatting, filtering
+//! or any other layers of [`tracing_subscriber`](https://docs.rs/tracing-subscriber) as usual.
+//!
+//! Fine-grained instrumentation may produce more spans than it is worth to submit one by one. With
+//! a [threshold](AppInsightsLayer::with_threshold) only spans that took at least that long are
+//! tracked as dependencies, while shorter ones are counted into an
+//! [`AggregateMetricTelemetry`](crate::telemetry::AggregateMetricTelemetry) item named
+//! `Spans below threshold` for every span name instead. Its count is the number of spans, and its
+//! values are their durations in milliseconds. Metrics are aggregated for a minute and submitted
+//! when a span is closed after that or when the layer is [flushed](AppInsightsLayer::flush).
+//!
+//! ```rust, no_run
+//! use std::time::Duration;
+//!
+//! use appinsights::{tracing::AppInsightsLayer, TelemetryClient};
+//! use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+//!
+//! let client = TelemetryClient::new("<instrumentation key>".to_string());
+//!
+//! tracing_subscriber::registry()
+//!     .with(EnvFilter::from_default_env())
+//!     .with(tracing_subscriber::fmt::layer())
+//!     .with(AppInsightsLayer::new(client).with_threshold(Duration::from_millis(10)))
+//!     .init();
+//!
+//! tracing::info_span!("load_orders", customer = 42).in_scope(|| {
+//!     // submitted as a dependency only if it takes 10ms or longer
+//! });
+//!
+//! // submit spans counted below the threshold so far, e.g. before an application exits
+//! tracing::dispatcher::get_default(|dispatch| {
+//!     if let Some(layer) = dispatch.downcast_ref::<AppInsightsLayer>() {
+//!         layer.flush();
+//!     }
+//! });
+//! ```
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    mem,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span::{Attributes, Id, Record},
+    Metadata, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::{
+    processor::ProcessingContext,
+    telemetry::{AggregateMetricTelemetry, RemoteDependencyTelemetry, Telemetry},
+    time, TelemetryClient,
+};
+
+/// A name of this integration reported to telemetry processors.
+const INTEGRATION: &str = "tracing";
+
+/// A target prefix of spans of this crate that are never tracked.
+const OWN_TARGET: &str = "appinsights";
+
+/// A type of dependencies spans are tracked as.
+const DEPENDENCY_TYPE: &str = "InProc";
+
+/// A name of a metric spans shorter than a threshold are counted into.
+const BELOW_THRESHOLD_METRIC: &str = "Spans below threshold";
+
+/// A period spans shorter than a threshold are counted for.
+const AGGREGATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A layer that submits a [`RemoteDependencyTelemetry`](crate::telemetry::RemoteDependencyTelemetry)
+/// for every closed span.
+pub struct AppInsightsLayer {
+    client: TelemetryClient,
+    level: LevelFilter,
+    threshold: Option<Duration>,
+    below_threshold: Mutex<Period>,
+}
+
+impl AppInsightsLayer {
+    /// Creates a new layer that submits spans of `Info` level and above with a given client.
+    pub fn new(client: TelemetryClient) -> Self {
+        Self {
+            client,
+            level: LevelFilter::INFO,
+            threshold: None,
+            below_threshold: Mutex::default(),
+        }
+    }
+
+    /// Sets a maximum level of spans to submit. Spans of other levels are still handled by other
+    /// layers.
+    pub fn with_level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets a minimum duration of spans to submit as dependencies. Shorter spans are counted into
+    /// an aggregated metric instead. Every span is submitted by default.
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Submits spans counted below a threshold so far and flushes pending telemetry items.
+    pub fn flush(&self) {
+        let metrics = self.lock_period().take();
+        self.track_metrics(metrics);
+        self.client.flush_channel();
+    }
+
+    /// Determines whether a span is tracked.
+    fn is_tracked(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level && !metadata.target().starts_with(OWN_TARGET) && self.client.is_enabled()
+    }
+
+    /// Submits a span as a dependency or counts it below a threshold.
+    fn report(&self, span: Span) {
+        let ended_at = time::now();
+        let duration = (ended_at - span.started_at).to_std().unwrap_or_default();
+
+        if self.threshold.is_some_and(|threshold| duration < threshold) {
+            let metrics = self.lock_period().add(span.name, duration, ended_at);
+            self.track_metrics(metrics);
+            return;
+        }
+
+        let mut telemetry = RemoteDependencyTelemetry::new(span.name, DEPENDENCY_TYPE, duration, "", true);
+        *telemetry.timestamp_mut() = span.started_at;
+        let properties = telemetry.properties_mut();
+        properties.insert("target".into(), span.target.into());
+        properties.extend(span.fields);
+
+        self.client
+            .track_with_context(telemetry, ProcessingContext::new(Some(INTEGRATION)));
+    }
+
+    fn track_metrics(&self, metrics: Vec<AggregateMetricTelemetry>) {
+        for telemetry in metrics {
+            self.client
+                .track_with_context(telemetry, ProcessingContext::new(Some(INTEGRATION)));
+        }
+    }
+
+    fn lock_period(&self) -> std::sync::MutexGuard<'_, Period> {
+        self.below_threshold.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<S> Layer<S> for AppInsightsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let metadata = attributes.metadata();
+        if !self.is_tracked(metadata) {
+            return;
+        }
+
+        let mut span = Span {
+            name: metadata.name(),
+            target: metadata.target(),
+            started_at: time::now(),
+            fields: BTreeMap::default(),
+        };
+        attributes.record(&mut Fields(&mut span.fields));
+
+        // data of a span is kept along with the span by a registry until it is closed
+        if let Some(data) = ctx.span(id) {
+            data.extensions_mut().insert(span);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(data) = ctx.span(id) {
+            if let Some(span) = data.extensions_mut().get_mut::<Span>() {
+                values.record(&mut Fields(&mut span.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).and_then(|data| data.extensions_mut().remove::<Span>());
+        if let Some(span) = span {
+            self.report(span);
+        }
+    }
+}
+
+/// A span open so far.
+struct Span {
+    name: &'static str,
+    target: &'static str,
+    started_at: DateTime<Utc>,
+    fields: BTreeMap<String, String>,
+}
+
+/// Records fields of a span as custom properties.
+struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().into(), format!("{:?}", value));
+    }
+}
+
+/// Durations of spans shorter than a threshold counted since a period started.
+#[derive(Default)]
+struct Period {
+    started_at: Option<DateTime<Utc>>,
+    metrics: BTreeMap<&'static str, AggregateMetricTelemetry>,
+}
+
+impl Period {
+    /// Counts a duration of a span with a given name. Returns metrics of a previous period if it
+    /// is over.
+    fn add(&mut self, name: &'static str, duration: Duration, now: DateTime<Utc>) -> Vec<AggregateMetricTelemetry> {
+        let is_over = self.started_at.is_some_and(|started_at| {
+            (now - started_at)
+                .to_std()
+                .is_ok_and(|elapsed| elapsed >= AGGREGATION_INTERVAL)
+        });
+        let metrics = if is_over { self.take() } else { Vec::new() };
+
+        self.started_at.get_or_insert(now);
+        self.metrics
+            .entry(name)
+            .or_insert_with(|| {
+                let mut telemetry = AggregateMetricTelemetry::new(BELOW_THRESHOLD_METRIC);
+                telemetry.properties_mut().insert("span".into(), name.into());
+                telemetry
+            })
+            .stats_mut()
+            .add_data(&[duration.as_secs_f64() * 1000.0]);
+
+        metrics
+    }
+
+    /// Returns metrics counted since the period started and starts a new one.
+    fn take(&mut self) -> Vec<AggregateMetricTelemetry> {
+        self.started_at = None;
+        mem::take(&mut self.metrics).into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use chrono::TimeZone;
+    use crossbeam_queue::SegQueue;
+    use tracing::Dispatch;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    use super::*;
+    use crate::{
+        client::tests::TestChannel,
+        contracts::{Base, Data, Envelope},
+        TelemetryConfig,
+    };
+
+    #[test]
+    fn it_tracks_span_as_dependency() {
+        let events = Arc::new(SegQueue::default());
+        let dispatch = Dispatch::new(Registry::default().with(create_layer(events.clone())));
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            time::set(Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 0, 0));
+            let span = tracing::info_span!(target: "orders", "load_orders", customer = 42, region = "eu");
+            time::set(Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 0, 150));
+            drop(span);
+            time::reset();
+        });
+
+        assert_eq!(events.len(), 1);
+        let envelope = events.pop().unwrap();
+        assert_eq!(envelope.time, "2023-05-01T12:00:00.000Z");
+        match envelope.data {
+            Some(Base::Data(Data::RemoteDependencyData(data))) => {
+                assert_eq!(data.name, "load_orders");
+                assert_eq!(data.type_, Some("InProc".into()));
+                assert_eq!(data.duration, "0.00:00:00.1500000");
+                let properties = data.properties.unwrap();
+                assert_eq!(properties.get("target"), Some(&"orders".to_string()));
+                assert_eq!(properties.get("customer"), Some(&"42".to_string()));
+                assert_eq!(properties.get("region"), Some(&"eu".to_string()));
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+
+    #[test]
+    fn it_tracks_span_once_all_handles_are_closed() {
+        let events = Arc::new(SegQueue::default());
+        let dispatch = Dispatch::new(Registry::default().with(create_layer(events.clone())));
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            let span = tracing::info_span!(target: "orders", "load_orders");
+            let clone = span.clone();
+            drop(span);
+            assert!(events.is_empty());
+            drop(clone);
+        });
+
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn it_counts_spans_below_threshold() {
+        let events = Arc::new(SegQueue::default());
+        let layer = create_layer(events.clone()).with_threshold(Duration::from_millis(10));
+        let dispatch = Dispatch::new(Registry::default().with(layer));
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for (name, duration) in [("fast", 2), ("fast", 4), ("slow", 20), ("fast", 6)] {
+                time::set(Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 0, 0));
+                let span = match name {
+                    "fast" => tracing::info_span!(target: "orders", "fast"),
+                    _ => tracing::info_span!(target: "orders", "slow"),
+                };
+                time::set(Utc.ymd(2023, 5, 1).and_hms_milli(12, 0, 0, duration));
+                drop(span);
+            }
+            time::reset();
+        });
+
+        // only a span above the threshold is tracked as a dependency
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events.pop().unwrap().data,
+            Some(Base::Data(Data::RemoteDependencyData(data))) if data.name == "slow"
+        ));
+
+        dispatch.downcast_ref::<AppInsightsLayer>().unwrap().flush();
+
+        assert_eq!(events.len(), 1);
+        let (name, count, max, properties) = metric(events.pop().unwrap());
+        assert_eq!(name, "Spans below threshold");
+        assert_eq!(count, 3);
+        assert_eq!(max, 6.0);
+        assert_eq!(properties.get("span"), Some(&"fast".to_string()));
+    }
+
+    #[test]
+    fn it_submits_spans_below_threshold_when_period_is_over() {
+        let events = Arc::new(SegQueue::default());
+        let layer = create_layer(events.clone()).with_threshold(Duration::from_millis(10));
+        let dispatch = Dispatch::new(Registry::default().with(layer));
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            for minute in [0, 0, 1] {
+                time::set(Utc.ymd(2023, 5, 1).and_hms_milli(12, minute, 0, 0));
+                drop(tracing::info_span!(target: "orders", "fast"));
+            }
+            time::reset();
+        });
+
+        // spans of the first minute are submitted once a span of the next one is closed
+        assert_eq!(events.len(), 1);
+        let (_, count, _, _) = metric(events.pop().unwrap());
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn it_skips_own_spans_and_events() {
+        let events = Arc::new(SegQueue::default());
+        let dispatch = Dispatch::new(Registry::default().with(create_layer(events.clone())));
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            drop(tracing::info_span!(target: "appinsights::pipeline", "appinsights.batch"));
+            drop(tracing::debug_span!(target: "orders", "cache_lookup"));
+            tracing::info!(target: "orders", "order loaded");
+        });
+
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn it_leaves_spans_and_events_to_other_layers() {
+        #[derive(Default)]
+        struct Counter(Arc<AtomicUsize>);
+
+        impl<S: Subscriber> Layer<S> for Counter {
+            fn on_new_span(&self, _attributes: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+
+            fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let events = Arc::new(SegQueue::default());
+        let counter = Counter::default();
+        let count = counter.0.clone();
+        let subscriber = Registry::default().with(create_layer(events.clone())).with(counter);
+        let dispatch = Dispatch::new(subscriber);
+
+        tracing::dispatcher::with_default(&dispatch, || {
+            drop(tracing::debug_span!(target: "orders", "cache_lookup"));
+            tracing::info!(target: "orders", "order loaded");
+            drop(tracing::info_span!(target: "orders", "load_orders"));
+        });
+
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(events.len(), 1);
+    }
+
+    fn create_layer(events: Arc<SegQueue<Envelope>>) -> AppInsightsLayer {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let client = TelemetryClient::create(&config, TestChannel::new(events));
+        AppInsightsLayer::new(client)
+    }
+
+    fn metric(envelope: Envelope) -> (String, i32, f64, BTreeMap<String, String>) {
+        match envelope.data {
+            Some(Base::Data(Data::MetricData(mut data))) => {
+                let point = data.metrics.remove(0);
+                (
+                    point.name,
+                    point.count.unwrap(),
+                    point.max.unwrap(),
+                    data.properties.unwrap().into(),
+                )
+            }
+            data => panic!("unexpected data: {:?}", data),
+        }
+    }
+}
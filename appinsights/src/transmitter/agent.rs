@@ -0,0 +1,131 @@
+use std::{convert::TryFrom, io};
+
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use crate::agent::AgentEndpoint;
+
+/// A connection to an agent.
+type Connection = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Forwards payloads to a local agent as length-prefixed frames over a connection kept open
+/// between batches.
+pub struct AgentClient {
+    endpoint: AgentEndpoint,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl AgentClient {
+    /// Creates a new client that forwards payloads to a given agent.
+    pub fn new(endpoint: AgentEndpoint) -> Self {
+        Self {
+            endpoint,
+            connection: Mutex::default(),
+        }
+    }
+
+    /// Writes a payload to the agent. A connection is established if there is none yet, and it is
+    /// dropped when a payload cannot be written, so the next payload is written to a new one.
+    /// Errors of a connection are retryable, so a batch that fails to be written is retried as a
+    /// whole.
+    pub async fn forward(&self, payload: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "payload is too large for a frame"))?;
+
+        let mut connection = self.connection.lock().await;
+        let stream = match connection.as_mut() {
+            Some(stream) => stream,
+            None => connection.insert(connect(&self.endpoint).await?),
+        };
+
+        let result = write_frame(stream, len, payload).await;
+        if result.is_err() {
+            *connection = None;
+        }
+        result
+    }
+}
+
+/// Establishes a new connection to an agent.
+async fn connect(endpoint: &AgentEndpoint) -> io::Result<Connection> {
+    match endpoint {
+        AgentEndpoint::Tcp(address) => {
+            let stream = TcpStream::connect(address.as_str()).await?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        #[cfg(unix)]
+        AgentEndpoint::Unix(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+    }
+}
+
+/// Writes a frame that consists of a big-endian length of a payload followed by the payload.
+async fn write_frame(stream: &mut Connection, len: u32, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&len.to_be_bytes()).await?;
+    stream.write_all(payload).await?;
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_forwards_length_prefixed_payloads_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = AgentClient::new(AgentEndpoint::tcp(listener.local_addr().unwrap().to_string()));
+
+        client.forward(b"[1]").await.unwrap();
+        client.forward(b"[2,3]").await.unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(read_frame(&mut stream).await, b"[1]");
+        assert_eq!(read_frame(&mut stream).await, b"[2,3]");
+    }
+
+    #[tokio::test]
+    async fn it_reconnects_when_agent_is_not_available() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let client = AgentClient::new(AgentEndpoint::tcp(address.to_string()));
+
+        assert!(client.forward(b"[1]").await.is_err());
+
+        let listener = TcpListener::bind(address).await.unwrap();
+        client.forward(b"[1]").await.unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(read_frame(&mut stream).await, b"[1]");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn it_forwards_payloads_over_unix_socket() {
+        let path = crate::test::temp_path("sock");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let client = AgentClient::new(AgentEndpoint::unix(&path));
+
+        client.forward(b"[]").await.unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        assert_eq!(read_frame(&mut stream).await, b"[]");
+    }
+
+    async fn read_frame(stream: &mut (impl AsyncRead + Unpin)) -> Vec<u8> {
+        let len = stream.read_u32().await.unwrap();
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        payload
+    }
+}
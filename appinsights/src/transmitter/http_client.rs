@@ -0,0 +1,491 @@
+use std::{error::Error as StdError, io, slice};
+#[cfg(feature = "reqwest")]
+use std::{net::ToSocketAddrs, sync::Arc};
+
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+#[cfg(feature = "reqwest")]
+use hyper::client::connect::dns::Name;
+#[cfg(feature = "reqwest")]
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "hyper-client")]
+use crate::transmitter::tunnel::TunnelConnector;
+use crate::{Error, InvalidConfig, Result, TelemetryConfig};
+
+/// Maximum number of bytes a response body that describes which telemetry items the server
+/// accepted may take per item sent, on top of a configured size. An entry of an item the server
+/// rejected is an index, a status code and a short message.
+const MAX_BODY_BYTES_PER_ITEM: usize = 1024;
+
+/// Sends requests to the ingestion endpoint with either `reqwest` or a lightweight `hyper` based
+/// client. Response bodies are read up to a size bounded by a configuration and the number of
+/// items sent only.
+pub struct HttpClient {
+    inner: Inner,
+    max_error_body_bytes: usize,
+}
+
+enum Inner {
+    // a client of an application may send a user agent of its own, so it is set per request
+    #[cfg(feature = "reqwest")]
+    Reqwest(reqwest::Client, http::HeaderValue),
+    // reqwest takes precedence when both clients are available
+    #[cfg(feature = "hyper-client")]
+    #[cfg_attr(feature = "reqwest", allow(dead_code))]
+    Hyper(
+        Box<hyper::Client<hyper_rustls::HttpsConnector<TunnelConnector>>>,
+        http::HeaderValue,
+    ),
+    // a client could not be created with given settings, so every submission fails
+    #[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+    Unavailable(InvalidConfig),
+}
+
+impl HttpClient {
+    /// Creates a new client configured with specified configuration. A `reqwest` client is used
+    /// when it is available.
+    #[cfg(feature = "reqwest")]
+    pub fn from_config(config: &TelemetryConfig) -> Self {
+        Self::reqwest(config)
+    }
+
+    /// Creates a new client configured with specified configuration.
+    #[cfg(not(feature = "reqwest"))]
+    pub fn from_config(config: &TelemetryConfig) -> Self {
+        Self::hyper(config)
+    }
+
+    /// Creates a new `reqwest` client that uses a custom DNS resolver and customizations if any, or
+    /// reuses a client of an application.
+    #[cfg(feature = "reqwest")]
+    pub fn reqwest(config: &TelemetryConfig) -> Self {
+        let client = match config.shared_http_client() {
+            Some(client) => client.clone(),
+            None => {
+                let builder = reqwest::Client::builder().user_agent(config.user_agent_header());
+                let resolver = config.dns_resolver().unwrap_or_else(|| Arc::new(SystemResolver));
+                let builder = builder.dns_resolver(Arc::new(MarkingResolver(resolver)));
+                let builder = config.configure_http_client(builder);
+                match builder.build() {
+                    Ok(client) => client,
+                    Err(err) => {
+                        let err = InvalidConfig::new(format!("unable to create HTTP client: {}", err));
+                        log::error!("{}", err);
+                        return Self {
+                            inner: Inner::Unavailable(err),
+                            max_error_body_bytes: config.max_error_body_bytes(),
+                        };
+                    }
+                }
+            }
+        };
+        Self {
+            inner: Inner::Reqwest(client, config.user_agent_header()),
+            max_error_body_bytes: config.max_error_body_bytes(),
+        }
+    }
+
+    /// Creates a new `hyper` client that verifies server certificates with Mozilla root
+    /// certificates and connects through a proxy configured with environment variables if any.
+    #[cfg(feature = "hyper-client")]
+    #[cfg_attr(feature = "reqwest", allow(dead_code))]
+    pub fn hyper(config: &TelemetryConfig) -> Self {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(TunnelConnector::from_env(config.endpoint()));
+        let client = hyper::Client::builder().build(connector);
+        Self {
+            inner: Inner::Hyper(Box::new(client), config.user_agent_header()),
+            max_error_body_bytes: config.max_error_body_bytes(),
+        }
+    }
+
+    /// Sends a payload of a given number of telemetry items to a given URL and reads the response.
+    /// A body is cut at a limit, which grows with the number of items for a body that can describe
+    /// which of them the server accepted.
+    pub async fn post(&self, url: &str, payload: Vec<u8>, count: usize) -> Result<HttpResponse> {
+        match &self.inner {
+            #[cfg(feature = "reqwest")]
+            Inner::Reqwest(client, user_agent) => {
+                let mut response = client
+                    .post(url)
+                    .header(http::header::USER_AGENT, user_agent)
+                    .body(payload)
+                    .send()
+                    .await?;
+                let mut body = ResponseBody::new(self.body_limit(response.status(), count));
+                while let Some(chunk) = response.chunk().await? {
+                    if !body.push(chunk) {
+                        break;
+                    }
+                }
+                Ok(HttpResponse {
+                    status: response.status(),
+                    headers: response.headers().clone(),
+                    body: body.chunks,
+                })
+            }
+            #[cfg(feature = "hyper-client")]
+            Inner::Hyper(client, user_agent) => {
+                use hyper::body::HttpBody;
+
+                let request = hyper::Request::post(url)
+                    .header(http::header::USER_AGENT, user_agent)
+                    .body(hyper::Body::from(payload))?;
+                let (parts, mut stream) = client.request(request).await?.into_parts();
+                let mut body = ResponseBody::new(self.body_limit(parts.status, count));
+                while let Some(chunk) = stream.data().await {
+                    if !body.push(chunk?) {
+                        break;
+                    }
+                }
+                Ok(HttpResponse {
+                    status: parts.status,
+                    headers: parts.headers,
+                    body: body.chunks,
+                })
+            }
+            Inner::Unavailable(err) => Err(Error::Config(err.clone())),
+        }
+    }
+
+    /// Returns the maximum number of bytes of a response body with a given status to read for a
+    /// submission of a given number of items.
+    fn body_limit(&self, status: StatusCode, count: usize) -> usize {
+        if reports_transmission(status) {
+            self.max_error_body_bytes
+                .saturating_add(count.saturating_mul(MAX_BODY_BYTES_PER_ITEM))
+        } else {
+            self.max_error_body_bytes
+        }
+    }
+}
+
+/// A failure to resolve the endpoint host. It tells resolution errors apart from other connection
+/// errors, which are wrapped by HTTP clients in types of their own.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub(crate) struct ResolutionError(#[source] pub(crate) Box<dyn StdError + Send + Sync>);
+
+/// Resolves names with a given resolver and marks its failures as [`ResolutionError`].
+#[cfg(feature = "reqwest")]
+struct MarkingResolver(Arc<dyn Resolve>);
+
+#[cfg(feature = "reqwest")]
+impl Resolve for MarkingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolving = self.0.resolve(name);
+        Box::pin(async move { resolving.await.map_err(|err| ResolutionError(err).into()) })
+    }
+}
+
+/// Resolves names with the system resolver on a blocking thread, as `reqwest` does by default.
+#[cfg(feature = "reqwest")]
+struct SystemResolver;
+
+#[cfg(feature = "reqwest")]
+impl Resolve for SystemResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs = tokio::task::spawn_blocking(move || (name.as_str(), 0).to_socket_addrs()).await??;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// Returns `true` if a response with a given status can describe which telemetry items the server
+/// accepted, so its body may grow with the number of items sent.
+fn reports_transmission(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::PARTIAL_CONTENT
+            | StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+    )
+}
+
+/// Chunks of a response body read so far, up to a limit.
+struct ResponseBody {
+    chunks: Vec<Bytes>,
+    len: usize,
+    limit: usize,
+}
+
+impl ResponseBody {
+    fn new(limit: usize) -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+            limit,
+        }
+    }
+
+    /// Keeps a chunk as is without copying it, or the part of it that fits into the limit.
+    /// Returns `false` once the limit is reached and the rest of the body is to be discarded.
+    fn push(&mut self, mut chunk: Bytes) -> bool {
+        let remaining = self.limit - self.len;
+        if chunk.len() >= remaining {
+            chunk.truncate(remaining);
+            self.len = self.limit;
+            self.chunks.push(chunk);
+            return false;
+        }
+        self.len += chunk.len();
+        self.chunks.push(chunk);
+        true
+    }
+}
+
+/// A response of the server. A body is kept in chunks it was received in.
+pub struct HttpResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<Bytes>,
+}
+
+impl HttpResponse {
+    /// Creates a response with a given status code and body and no headers.
+    #[cfg(any(test, feature = "test-util", feature = "agent"))]
+    pub fn new(status: StatusCode, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::default(),
+            body: vec![body.into()],
+        }
+    }
+
+    /// Returns a status code of the response.
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Returns headers of the response.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Deserializes a body of the response as JSON. The body is parsed straight from chunks it was
+    /// received in, so a large body is not copied into a contiguous buffer first.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_reader(ChunksReader::new(&self.body))?)
+    }
+
+    /// Returns a body of the response as a text.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body.concat()).into_owned()
+    }
+}
+
+/// Reads chunks of a response body one after another.
+struct ChunksReader<'a> {
+    current: &'a [u8],
+    rest: slice::Iter<'a, Bytes>,
+}
+
+impl<'a> ChunksReader<'a> {
+    fn new(chunks: &'a [Bytes]) -> Self {
+        Self {
+            current: &[],
+            rest: chunks.iter(),
+        }
+    }
+}
+
+impl io::Read for ChunksReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rest.next() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+        self.current.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::contracts::Transmission;
+
+    #[test]
+    fn it_cuts_body_at_limit() {
+        let mut body = ResponseBody::new(5);
+        assert!(body.push(Bytes::from_static(b"abc")));
+        assert!(!body.push(Bytes::from_static(b"defgh")));
+        assert_eq!(body.chunks.concat(), b"abcde");
+
+        let mut body = ResponseBody::new(9);
+        assert!(body.push(Bytes::from_static(b"abc")));
+        assert!(body.push(Bytes::from_static(b"defgh")));
+        assert_eq!(body.chunks.concat(), b"abcdefgh");
+    }
+
+    #[test]
+    fn it_limits_body_describing_transmission_by_number_of_items() {
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .max_error_body_bytes(1024)
+            .build();
+        let client = HttpClient::from_config(&config);
+
+        assert_eq!(client.body_limit(StatusCode::BAD_REQUEST, 10), 1024);
+        for status in [
+            StatusCode::PARTIAL_CONTENT,
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ] {
+            assert_eq!(client.body_limit(status, 10), 1024 + 10 * MAX_BODY_BYTES_PER_ITEM);
+        }
+        assert_eq!(client.body_limit(StatusCode::PARTIAL_CONTENT, usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn it_parses_body_split_into_chunks() {
+        let errors: Vec<_> = (0..1000)
+            .map(|index| json!({ "index": index, "statusCode": 500, "message": "Internal Server Error" }))
+            .collect();
+        let content =
+            serde_json::to_vec(&json!({ "itemsReceived": 1000, "itemsAccepted": 0, "errors": errors })).unwrap();
+        let response = HttpResponse {
+            status: StatusCode::PARTIAL_CONTENT,
+            headers: HeaderMap::default(),
+            body: content.chunks(7).map(Bytes::copy_from_slice).collect(),
+        };
+
+        let transmission: Transmission = response.json().unwrap();
+
+        assert_eq!(transmission.items_received, 1000);
+        assert_eq!(transmission.errors.len(), 1000);
+        assert_eq!(transmission.errors[999].index, 999);
+    }
+
+    #[cfg(feature = "hyper-client")]
+    #[tokio::test]
+    async fn it_posts_payload_with_hyper_client() {
+        let url = echo_server();
+
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(&url)
+            .user_agent("vendored/1.0.0")
+            .build();
+        let client = HttpClient::hyper(&config);
+
+        let response = client.post(&url, br#"{"name":"event"}"#.to_vec(), 1).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()["x-length"], "16");
+        assert_eq!(response.headers()["x-user-agent"], "vendored/1.0.0");
+        assert_eq!(response.json::<Value>().unwrap(), json!({ "name": "event" }));
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn it_posts_payload_with_shared_reqwest_client() {
+        let url = echo_server();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-application", http::HeaderValue::from_static("shared"));
+        let shared = reqwest::Client::builder()
+            .user_agent("application/2.0.0")
+            .default_headers(headers)
+            .build()
+            .unwrap();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(&url)
+            .user_agent("vendored/1.0.0")
+            .http_client(shared)
+            .build();
+        let client = HttpClient::reqwest(&config);
+
+        let response = client.post(&url, br#"{"name":"event"}"#.to_vec(), 1).await.unwrap();
+
+        assert_eq!(response.headers()["x-application"], "shared");
+        assert_eq!(response.headers()["x-user-agent"], "vendored/1.0.0");
+        assert_eq!(response.json::<Value>().unwrap(), json!({ "name": "event" }));
+    }
+
+    #[cfg(feature = "hyper-client")]
+    #[tokio::test]
+    async fn it_marks_failures_to_resolve_host_with_hyper_client() {
+        let url = "http://ingestion.invalid/v2/track";
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(url)
+            .build();
+        let client = HttpClient::hyper(&config);
+
+        let err = client.post(url, Vec::default(), 0).await.err().unwrap();
+
+        assert!(crate::transmitter::is_resolution_error(&err), "{:?}", err);
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn it_marks_failures_to_resolve_host_with_reqwest_client() {
+        let url = "http://ingestion.invalid/v2/track";
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(url)
+            .build();
+        let client = HttpClient::reqwest(&config);
+
+        let err = client.post(url, Vec::default(), 0).await.err().unwrap();
+
+        assert!(crate::transmitter::is_resolution_error(&err), "{:?}", err);
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn it_fails_with_config_error_when_client_cannot_be_created() {
+        let url = echo_server();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .endpoint(&url)
+            .http_client_with(|builder| builder.user_agent("invalid\n"))
+            .build();
+        let client = HttpClient::reqwest(&config);
+
+        let err = client.post(&url, Vec::default(), 0).await.err().unwrap();
+
+        assert!(matches!(err, Error::Config(_)), "{:?}", err);
+        assert!(!err.is_retryable());
+    }
+
+    /// Starts a server that responds with a received payload and reports its headers.
+    fn echo_server() -> String {
+        let make_service = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|request: Request<Body>| async move {
+                let user_agent = request.headers()[http::header::USER_AGENT].clone();
+                let application = request.headers().get("x-application").cloned();
+                let body = hyper::body::to_bytes(request.into_body()).await?;
+                let mut response = Response::builder()
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header("x-length", body.len())
+                    .header("x-user-agent", user_agent);
+                if let Some(application) = application {
+                    response = response.header("x-application", application);
+                }
+                Ok::<_, hyper::Error>(response.body(Body::from(body)).unwrap())
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}/v2/track", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+}
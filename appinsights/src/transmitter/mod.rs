@@ -0,0 +1,1536 @@
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    io, mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "agent")]
+mod agent;
+mod http_client;
+#[cfg(feature = "hyper-client")]
+#[cfg_attr(feature = "reqwest", allow(dead_code))]
+mod tunnel;
+
+use chrono::{DateTime, Utc};
+use http::{
+    header::{DATE, RETRY_AFTER},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use log::debug;
+use serde::Serialize;
+use serde_json::value::RawValue;
+
+#[cfg(feature = "agent")]
+use self::agent::AgentClient;
+pub(crate) use self::http_client::HttpResponse;
+use self::http_client::{HttpClient, ResolutionError};
+use crate::{
+    clock::{self, SharedClock},
+    config::{RejectedItem, RejectionHandler},
+    contracts::{Base, Envelope, Transmission, TransmissionItem},
+    dead_letter::{DeadLetter, DeadLetterReason, SharedDeadLetterSink},
+    instrumentation,
+    internal_logger::{InternalEvent, InternalLogger},
+    telemetry::{Priority, TelemetryKind},
+    time, Error, Result, TelemetryConfig,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Response<T = Envelope> {
+    Success,
+    Retry(Vec<T>),
+    Throttled(DateTime<Utc>, Vec<T>),
+    ResolutionFailed(Vec<T>),
+    NoRetry,
+}
+
+/// A telemetry item of a batch to send. A caller may keep something along with every item, e.g. a
+/// priority to queue it again with, and get it back with items the server is to receive again.
+pub(crate) trait BatchItem {
+    /// Returns the telemetry item.
+    fn envelope(&self) -> &Envelope;
+
+    /// Returns the mutable telemetry item.
+    fn envelope_mut(&mut self) -> &mut Envelope;
+
+    /// Discards everything kept along with the telemetry item and returns it.
+    fn into_envelope(self) -> Envelope;
+}
+
+impl BatchItem for Envelope {
+    fn envelope(&self) -> &Envelope {
+        self
+    }
+
+    fn envelope_mut(&mut self) -> &mut Envelope {
+        self
+    }
+
+    fn into_envelope(self) -> Envelope {
+        self
+    }
+}
+
+impl BatchItem for (Priority, Envelope) {
+    fn envelope(&self) -> &Envelope {
+        &self.1
+    }
+
+    fn envelope_mut(&mut self) -> &mut Envelope {
+        &mut self.1
+    }
+
+    fn into_envelope(self) -> Envelope {
+        self.1
+    }
+}
+
+/// Sends telemetry items to the server.
+pub struct Transmitter {
+    url: String,
+    client: HttpClient,
+    #[cfg(feature = "agent")]
+    agent: Option<AgentClient>,
+    #[cfg(feature = "metrics")]
+    metrics_recorder: crate::pipeline_metrics::SharedMetricsRecorder,
+    request_timeout: Option<Duration>,
+    rejection_handler: Option<RejectionHandler>,
+    dead_letter_sink: Option<SharedDeadLetterSink>,
+    max_retry_ages: Vec<(TelemetryKind, Duration)>,
+    clock: Option<SharedClock>,
+    key_rotations: Arc<KeyRotations>,
+    clock_skew: ClockSkew,
+    clock_skew_corrected: bool,
+    payload_buffer: PayloadBuffer,
+    max_batch_bytes: Option<usize>,
+    #[cfg(feature = "test-util")]
+    faults: Option<crate::test::FaultScript>,
+    logger: InternalLogger,
+}
+
+impl Transmitter {
+    /// Creates a new instance of telemetry items sender configured with specified configuration.
+    pub fn from_config(config: &TelemetryConfig, logger: InternalLogger) -> Self {
+        Self {
+            url: config.endpoint().into(),
+            client: HttpClient::from_config(config),
+            #[cfg(feature = "agent")]
+            agent: config.agent_endpoint().cloned().map(AgentClient::new),
+            #[cfg(feature = "metrics")]
+            metrics_recorder: config.metrics_recorder().cloned().unwrap_or_default(),
+            request_timeout: config.request_timeout(),
+            rejection_handler: config.rejection_handler().cloned(),
+            dead_letter_sink: config.dead_letter_sink().cloned(),
+            max_retry_ages: config.max_retry_ages().to_vec(),
+            clock: config.clock().cloned(),
+            key_rotations: Arc::default(),
+            clock_skew: ClockSkew::default(),
+            clock_skew_corrected: config.is_clock_skew_corrected(),
+            payload_buffer: PayloadBuffer::new(config.max_batch_bytes()),
+            max_batch_bytes: config.max_batch_bytes(),
+            #[cfg(feature = "test-util")]
+            faults: config.fault_injection().cloned(),
+            logger,
+        }
+    }
+
+    /// Returns instrumentation keys to replace in telemetry items when they are sent.
+    pub fn key_rotations(&self) -> Arc<KeyRotations> {
+        self.key_rotations.clone()
+    }
+
+    /// Sends a telemetry items to the server.
+    #[cfg(test)]
+    pub async fn send(&self, mut items: Vec<Envelope>) -> Result<Response> {
+        self.send_from(&mut items, &mut Vec::new()).await
+    }
+
+    /// Sends telemetry items of a batch to the server. Items that cannot be serialized are dropped
+    /// and reported, so they don't prevent the rest of items from being sent. A batch that fails
+    /// with a retryable transport error, e.g. when the endpoint is unreachable, is retried like a
+    /// batch the server did not accept. Items are taken from the batch only once a response is
+    /// known, so they remain in the batch when sending fails with an error or panics.
+    ///
+    /// When a size of a batch is limited, items are serialized into a request until the next one
+    /// does not fit into it anymore. Items that don't fit are moved to `rest` before the request is
+    /// sent, so the caller can send them in another batch.
+    pub(crate) async fn send_from<T: BatchItem>(&self, items: &mut Vec<T>, rest: &mut Vec<T>) -> Result<Response<T>> {
+        self.key_rotations.restamp(items);
+
+        // items are sent with times of the server clock but kept with local times for retries
+        let offset = Some(self.clock_skew.offset()).filter(|offset| self.clock_skew_corrected && !offset.is_zero());
+        if let Some(offset) = offset {
+            items
+                .iter_mut()
+                .for_each(|item| clock::shift(item.envelope_mut(), offset));
+        }
+        let buffer = self.payload_buffer.take(items.len());
+        let (buffer, errors, cut) = serialize_envelopes(items, buffer, self.max_batch_bytes);
+        let payload = self.payload_buffer.finish(buffer, items.len());
+        rest.extend(cut);
+        if let Some(offset) = offset {
+            items
+                .iter_mut()
+                .for_each(|item| clock::shift(item.envelope_mut(), -offset));
+            rest.iter_mut()
+                .for_each(|item| clock::shift(item.envelope_mut(), -offset));
+        }
+        if let Some(error) = errors.first() {
+            self.logger.log(InternalEvent::SerializationFailed {
+                count: errors.len(),
+                error: error.to_string(),
+            });
+        }
+
+        if items.is_empty() {
+            debug!("No telemetry items left to send");
+            return Ok(Response::NoRetry);
+        }
+
+        let sent = self.now();
+        let request = self.post(payload, items.len());
+        let result = match self.request_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, request).await {
+                Ok(result) => result,
+                Err(_) => {
+                    debug!(
+                        "Request timed out after {:?}. Retry sending {} items",
+                        timeout,
+                        items.len()
+                    );
+                    return Ok(self.skip_stale_retries(Response::Retry(mem::take(items))));
+                }
+            },
+            None => request.await,
+        };
+
+        let response = match result {
+            Ok(response) => response,
+            Err(err) if is_resolution_error(&err) => {
+                debug!(
+                    "Unable to resolve endpoint host: {}. Retry sending {} items",
+                    err,
+                    items.len()
+                );
+                return Ok(self.skip_stale_retries(Response::ResolutionFailed(mem::take(items))));
+            }
+            Err(err) if err.is_retryable() => {
+                self.logger.log(InternalEvent::TransmissionFailed {
+                    count: items.len(),
+                    error: err.to_string(),
+                });
+                return Ok(self.skip_stale_retries(Response::Retry(mem::take(items))));
+            }
+            Err(err) => return Err(err),
+        };
+        self.clock_skew
+            .observe(response.headers(), sent, self.now(), &self.logger);
+
+        let response = match response.status() {
+            StatusCode::OK => {
+                debug!("Successfully sent {} items", items.len());
+                #[cfg(feature = "pool")]
+                crate::pool::recycle(items.drain(..).map(BatchItem::into_envelope));
+                Response::Success
+            }
+            StatusCode::PARTIAL_CONTENT => {
+                let content: Transmission = response.json()?;
+                let log_prefix = format!(
+                    "Successfully sent {}/{} telemetry items",
+                    content.items_accepted, content.items_received
+                );
+                if content.items_received == content.items_accepted {
+                    debug!("{}", log_prefix);
+                    #[cfg(feature = "pool")]
+                    crate::pool::recycle(items.drain(..).map(BatchItem::into_envelope));
+                    Response::Success
+                } else {
+                    self.reject(retain_retry_items(items, content));
+                    if items.is_empty() {
+                        debug!("{}. Nothing to re-send", log_prefix);
+                        Response::NoRetry
+                    } else {
+                        debug!("{}. Retry sending {} items", log_prefix, items.len());
+                        Response::Retry(mem::take(items))
+                    }
+                }
+            }
+            status @ StatusCode::TOO_MANY_REQUESTS | status @ StatusCode::REQUEST_TIMEOUT => {
+                let retry_after = response.headers().get(RETRY_AFTER).cloned();
+
+                let transmission = response.json::<Transmission>().ok();
+                if let Some(content) = transmission.clone() {
+                    self.reject(retain_retry_items(items, content));
+                }
+
+                if let Some(retry_after) = retry_after {
+                    let retry_after = parse_retry_after(&retry_after).ok_or_else(|| Error::Response {
+                        status,
+                        message: format!("invalid Retry-After header {:?}", retry_after),
+                        transmission,
+                    })?;
+                    let retry_after = self.clock_skew.to_local(retry_after);
+                    debug!(
+                        "Some items were discarded. Retry sending {} items after {}",
+                        items.len(),
+                        retry_after
+                    );
+                    Response::Throttled(retry_after, mem::take(items))
+                } else {
+                    debug!("Some items were discarded. Retry sending {} items", items.len());
+                    Response::Retry(mem::take(items))
+                }
+            }
+            StatusCode::SERVICE_UNAVAILABLE => {
+                debug!("Service unavailable. Retry sending {} items", items.len());
+                Response::Retry(mem::take(items))
+            }
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                if let Ok(content) = response.json::<Transmission>() {
+                    self.reject(retain_retry_items(items, content));
+                    if items.is_empty() {
+                        debug!("Service error. Nothing to re-send");
+                        Response::NoRetry
+                    } else {
+                        debug!("Service error. Retry sending {} items", items.len());
+                        Response::Retry(mem::take(items))
+                    }
+                } else {
+                    debug!("Service error. Retry sending {} items", items.len());
+                    Response::Retry(mem::take(items))
+                }
+            }
+            status => {
+                let message = response.text();
+                debug!("Unknown status: {}. {}. Nothing to re-send", status, message);
+                self.reject(
+                    items
+                        .drain(..)
+                        .map(|item| (item.into_envelope(), status.as_u16(), message.clone()))
+                        .collect(),
+                );
+                Response::NoRetry
+            }
+        };
+
+        Ok(self.skip_stale_retries(response))
+    }
+
+    /// Sends a payload of a given number of items to the server unless a scripted fault replaces
+    /// the response. When a local agent is configured, the payload is forwarded to it instead and
+    /// it is accepted as a whole once it is written.
+    /// The request is reported to the pipeline instrumentation and a metrics recorder along with
+    /// its latency.
+    async fn post(&self, payload: Vec<u8>, count: usize) -> Result<HttpResponse> {
+        let bytes = payload.len();
+        let started = Instant::now();
+        let result = self.post_uninstrumented(payload, count).await;
+        let latency = started.elapsed();
+        instrumentation::record_request(count, bytes, latency, &result);
+        #[cfg(feature = "metrics")]
+        self.metrics_recorder.record_request(count, bytes, latency, &result);
+        result
+    }
+
+    /// Sends a payload without reporting it to the pipeline instrumentation.
+    async fn post_uninstrumented(&self, payload: Vec<u8>, count: usize) -> Result<HttpResponse> {
+        #[cfg(feature = "test-util")]
+        if let Some(response) = match &self.faults {
+            Some(faults) => faults.inject(count).await,
+            None => None,
+        } {
+            return response;
+        }
+
+        #[cfg(feature = "agent")]
+        if let Some(agent) = &self.agent {
+            agent.forward(&payload).await?;
+            return Ok(HttpResponse::new(StatusCode::OK, Vec::new()));
+        }
+
+        self.client.post(&self.url, payload, count).await
+    }
+
+    /// Drops telemetry items the server should receive again when they are older than a maximum
+    /// age to retry items of their category.
+    fn skip_stale_retries<T: BatchItem>(&self, response: Response<T>) -> Response<T> {
+        if self.max_retry_ages.is_empty() {
+            return response;
+        }
+
+        match response {
+            Response::Retry(items) => Response::Retry(self.retain_fresh_items(items)),
+            Response::Throttled(retry_after, items) => Response::Throttled(retry_after, self.retain_fresh_items(items)),
+            Response::ResolutionFailed(items) => Response::ResolutionFailed(self.retain_fresh_items(items)),
+            response => response,
+        }
+    }
+
+    /// Filters out telemetry items that are too old to be retried and deposits them to a dead
+    /// letter sink if any.
+    fn retain_fresh_items<T: BatchItem>(&self, items: Vec<T>) -> Vec<T> {
+        let now = self.now();
+        let (fresh, stale) = items.into_iter().partition::<Vec<_>, _>(|item| {
+            let item = item.envelope();
+            let max_age = TelemetryKind::of(item)
+                .and_then(|kind| self.max_retry_ages.iter().find(|(retry_kind, _)| *retry_kind == kind));
+            max_age.is_none_or(|(_, age)| !age.is_zero() && !is_expired(item, *age, now))
+        });
+
+        if !stale.is_empty() {
+            self.logger.log(InternalEvent::RetriesSkipped { count: stale.len() });
+            if let Some(sink) = &self.dead_letter_sink {
+                let stale = stale.into_iter().map(BatchItem::into_envelope).collect();
+                sink.deposit(DeadLetterReason::RetriesExhausted, stale, &self.logger);
+            }
+        }
+
+        fresh
+    }
+
+    /// Returns the current time of a custom clock if any or the system clock otherwise.
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.as_ref().map_or_else(time::now, SharedClock::now)
+    }
+
+    /// Hands telemetry items rejected by the server over to a configured handler and deposits them
+    /// to a dead letter sink if any.
+    fn reject(&self, items: Vec<RejectedItem>) {
+        if items.is_empty() {
+            return;
+        }
+
+        if let Some(sink) = &self.dead_letter_sink {
+            let letters = items
+                .iter()
+                .map(|(envelope, status_code, message)| {
+                    let reason = DeadLetterReason::Rejected {
+                        status_code: *status_code,
+                        message: message.clone(),
+                    };
+                    DeadLetter::new(reason, envelope.clone())
+                })
+                .collect();
+            sink.deposit_letters(letters, &self.logger);
+        }
+
+        if let Some(handler) = &self.rejection_handler {
+            handler.handle(items);
+        }
+    }
+}
+
+/// Instrumentation keys replaced by newer ones. Telemetry items queued with a replaced key are
+/// stamped with the newer one right before they are sent, including items waiting for a retry.
+#[derive(Debug, Default)]
+pub struct KeyRotations(RwLock<HashMap<String, String>>);
+
+impl KeyRotations {
+    /// Replaces an instrumentation key with a new one in all telemetry items sent from now on.
+    pub fn rotate(&self, from: &str, to: &str) {
+        let mut keys = self.0.write().unwrap_or_else(PoisonError::into_inner);
+
+        // items stamped with any of previously replaced keys are stamped with the newest one
+        for key in keys.values_mut().filter(|key| *key == from) {
+            *key = to.to_string();
+        }
+        keys.remove(to);
+        if from != to {
+            keys.insert(from.to_string(), to.to_string());
+        }
+    }
+
+    /// Stamps telemetry items having a replaced instrumentation key with a new one.
+    fn restamp<T: BatchItem>(&self, items: &mut [T]) {
+        let keys = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        if keys.is_empty() {
+            return;
+        }
+
+        for item in items.iter_mut().map(BatchItem::envelope_mut) {
+            if let Some(key) = item.i_key.as_ref().and_then(|key| keys.get(key)) {
+                item.i_key = Some(key.clone());
+            }
+        }
+    }
+}
+
+/// The difference between the server clock and the local one learned from the `Date` header of
+/// server responses.
+///
+/// The server stamps a response somewhere between the moment a request was sent and the moment
+/// the response was received, so every sample is measured against the midpoint of the request.
+/// Samples are smoothed with an exponential moving average, so a single slow request or a response
+/// that waited in a proxy doesn't shift timestamps of all items sent afterwards.
+#[derive(Debug, Default)]
+pub struct ClockSkew(Mutex<Skew>);
+
+/// A smoothed estimate of the clock skew along with the offset applied to timestamps.
+#[derive(Debug, Default)]
+struct Skew {
+    estimate_ms: Option<f64>,
+    offset_ms: i64,
+}
+
+/// The `Date` header has a resolution of one second, so smaller differences are not considered a
+/// skew of the local clock.
+const MIN_CLOCK_SKEW_MS: f64 = 2000.0;
+
+/// A weight of a new sample in the moving average of the clock skew.
+const CLOCK_SKEW_SMOOTHING: f64 = 0.25;
+
+impl ClockSkew {
+    /// Updates the difference with the time of the server a response to a request sent and
+    /// received at given times was sent at, if any. A skew is reported once it exceeds the
+    /// resolution of the header, not with every response.
+    fn observe(&self, headers: &HeaderMap, sent: DateTime<Utc>, received: DateTime<Utc>, logger: &InternalLogger) {
+        let server_time = headers
+            .get(DATE)
+            .and_then(|date| date.to_str().ok())
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+        if let Some(server_time) = server_time {
+            let midpoint = sent + (received - sent) / 2;
+            if let Some(skew_ms) = self.update(server_time.with_timezone(&Utc), midpoint) {
+                logger.log(InternalEvent::ClockSkewDetected { skew_ms });
+            }
+        }
+    }
+
+    /// Adds a sample of the server time at a given local time to the estimate. Returns the offset
+    /// when it has just exceeded the resolution of the header, so it is reported only once until
+    /// the clocks agree again.
+    fn update(&self, server_time: DateTime<Utc>, local_time: DateTime<Utc>) -> Option<i64> {
+        let sample = (server_time - local_time).num_milliseconds() as f64;
+
+        let mut skew = self.skew();
+        let estimate = skew
+            .estimate_ms
+            .map_or(sample, |estimate| estimate + CLOCK_SKEW_SMOOTHING * (sample - estimate));
+        skew.estimate_ms = Some(estimate);
+
+        let offset_ms = if estimate.abs() < MIN_CLOCK_SKEW_MS {
+            0
+        } else {
+            estimate.round() as i64
+        };
+        let previous = mem::replace(&mut skew.offset_ms, offset_ms);
+        (previous == 0 && offset_ms != 0).then_some(offset_ms)
+    }
+
+    /// Returns the time to add to the local time to get the server time.
+    fn offset(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds(self.skew().offset_ms)
+    }
+
+    /// Translates a time of the server clock to the local one.
+    fn to_local(&self, server_time: DateTime<Utc>) -> DateTime<Utc> {
+        server_time - self.offset()
+    }
+
+    fn skew(&self) -> MutexGuard<'_, Skew> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Serializes telemetry items into a JSON array one by one. Items that fail to serialize are
+/// removed from the list, and their errors are returned alongside the payload.
+#[cfg(test)]
+fn serialize<T: Serialize>(items: &mut Vec<T>) -> (Vec<u8>, Vec<serde_json::Error>) {
+    let (payload, errors, _) = serialize_with(items, Vec::new(), None, |payload, items, index| {
+        serde_json::to_writer(payload, &items[index])
+    });
+    (payload, errors)
+}
+
+/// Serializes telemetry items into a JSON array one by one and formats context tags shared by
+/// several items only once. Items tracked by the same client usually carry the same tags, so the
+/// rest of them copy already formatted tags instead of escaping every tag again. Items that fail to
+/// serialize are removed from the list, and their errors are returned alongside the payload. The
+/// payload stays the same, since the wire format has no way to reference tags of another item.
+/// Items that don't fit into a payload of a given maximum size are split off and returned as well.
+fn serialize_envelopes<T: BatchItem>(
+    items: &mut Vec<T>,
+    payload: Vec<u8>,
+    max_bytes: Option<usize>,
+) -> (Vec<u8>, Vec<serde_json::Error>, Vec<T>) {
+    // formatted tags along with an index of the item they were formatted for
+    let mut shared: Option<(usize, Box<RawValue>)> = None;
+
+    serialize_with(items, payload, max_bytes, |payload, items, index| {
+        let envelope = items[index].envelope();
+        let tags = match &envelope.tags {
+            Some(tags) => {
+                let reusable =
+                    matches!(&shared, Some((owner, _)) if items[*owner].envelope().tags.as_ref() == Some(tags));
+                if !reusable {
+                    shared = Some((index, serde_json::value::to_raw_value(tags)?));
+                }
+                shared.as_ref().map(|(_, tags)| tags.as_ref())
+            }
+            None => None,
+        };
+
+        serde_json::to_writer(payload, &EnvelopePayload::new(envelope, tags))
+    })
+}
+
+/// Serializes telemetry items into a JSON array with a given function that writes an item at a
+/// given index into a given payload buffer, which is cleared first. Items that fail to serialize
+/// are removed from the list, and their errors are returned alongside the payload.
+///
+/// Every item is serialized once. When an item makes a payload exceed a given maximum size, it is
+/// discarded from the payload, and it is split off along with the rest of items to be returned.
+/// A payload holds at least one item even if it exceeds the limit alone.
+fn serialize_with<T, F>(
+    items: &mut Vec<T>,
+    mut payload: Vec<u8>,
+    max_bytes: Option<usize>,
+    mut write: F,
+) -> (Vec<u8>, Vec<serde_json::Error>, Vec<T>)
+where
+    F: FnMut(&mut Vec<u8>, &[T], usize) -> serde_json::Result<()>,
+{
+    payload.clear();
+    payload.push(b'[');
+    let mut errors = Vec::new();
+    let mut serialized = Vec::with_capacity(items.len());
+
+    for index in 0..items.len() {
+        let len = payload.len();
+        if len > 1 {
+            payload.push(b',');
+        }
+
+        match write(&mut payload, items, index) {
+            // a closing bracket is yet to be written
+            Ok(()) if len > 1 && max_bytes.is_some_and(|max| payload.len() + 1 > max) => {
+                payload.truncate(len);
+                break;
+            }
+            Ok(()) => serialized.push(true),
+            Err(err) => {
+                // discard partially written item
+                payload.truncate(len);
+                errors.push(err);
+                serialized.push(false);
+            }
+        }
+    }
+
+    let rest = items.split_off(serialized.len());
+    let mut serialized = serialized.into_iter();
+    items.retain(|_| serialized.next().unwrap_or_default());
+
+    payload.push(b']');
+    (payload, errors, rest)
+}
+
+/// The largest capacity reserved for a payload and kept between batches when a batch size is not
+/// limited. A larger payload still grows as needed, but its buffer is not kept for the next batch.
+const MAX_PAYLOAD_CAPACITY: usize = 4 * 1024 * 1024;
+
+/// A buffer telemetry items are serialized into, kept between batches along with a moving average
+/// of the number of bytes a serialized item takes.
+///
+/// The buffer reserves enough room for a whole batch up front instead of growing and copying
+/// already written items over and over while items are written to it. A reserved capacity never
+/// exceeds the maximum size of a batch, so a single batch of unusually large items doesn't make
+/// the next ones reserve more memory than they can take. A request body is copied out of the
+/// buffer, so it takes exactly as much memory as it needs, while the buffer is reused by the next
+/// batch. Batches sent concurrently serialize into a buffer of their own.
+#[derive(Debug)]
+struct PayloadBuffer {
+    buffer: Mutex<Vec<u8>>,
+    per_item: AtomicUsize,
+    max_capacity: usize,
+}
+
+impl PayloadBuffer {
+    /// Creates a new buffer for payloads of a given maximum size.
+    fn new(max_bytes: Option<usize>) -> Self {
+        Self {
+            buffer: Mutex::default(),
+            per_item: AtomicUsize::default(),
+            max_capacity: max_bytes.map_or(MAX_PAYLOAD_CAPACITY, |max| max.min(MAX_PAYLOAD_CAPACITY)),
+        }
+    }
+
+    /// Returns a capacity of a payload for a given number of items. It leaves some room for items
+    /// larger than the average ones.
+    fn capacity(&self, count: usize) -> usize {
+        let per_item = self.per_item.load(Ordering::Relaxed);
+        (per_item + per_item / 8)
+            .saturating_mul(count)
+            .saturating_add(2)
+            .min(self.max_capacity)
+    }
+
+    /// Takes the buffer with enough room for a given number of items out, or allocates a new one
+    /// when another batch is being serialized into it.
+    fn take(&self, count: usize) -> Vec<u8> {
+        let mut buffer = mem::take(&mut *self.buffer.lock().unwrap_or_else(PoisonError::into_inner));
+        buffer.reserve(self.capacity(count));
+        buffer
+    }
+
+    /// Learns the size of an item from a payload of a given number of items, puts the buffer back
+    /// and returns a copy of the payload to send.
+    fn finish(&self, buffer: Vec<u8>, count: usize) -> Vec<u8> {
+        if let Some(sample) = buffer.len().checked_div(count) {
+            let average = match self.per_item.load(Ordering::Relaxed) {
+                0 => sample,
+                average => (average * 3 + sample) / 4,
+            };
+            self.per_item.store(average, Ordering::Relaxed);
+        }
+
+        let payload = buffer.clone();
+        if buffer.capacity() <= self.max_capacity {
+            let mut kept = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+            if buffer.capacity() > kept.capacity() {
+                *kept = buffer;
+            }
+        }
+        payload
+    }
+}
+
+/// A telemetry item with context tags formatted in advance. It is serialized exactly as an
+/// [`Envelope`] is.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvelopePayload<'a> {
+    ver: &'a Option<i32>,
+    name: &'a str,
+    time: &'a str,
+    sample_rate: &'a Option<f64>,
+    seq: &'a Option<String>,
+    i_key: &'a Option<String>,
+    flags: &'a Option<i64>,
+    tags: Option<&'a RawValue>,
+    data: &'a Option<Base>,
+}
+
+impl<'a> EnvelopePayload<'a> {
+    fn new(envelope: &'a Envelope, tags: Option<&'a RawValue>) -> Self {
+        Self {
+            ver: &envelope.ver,
+            name: &envelope.name,
+            time: &envelope.time,
+            sample_rate: &envelope.sample_rate,
+            seq: &envelope.seq,
+            i_key: &envelope.i_key,
+            flags: &envelope.flags,
+            tags,
+            data: &envelope.data,
+        }
+    }
+}
+
+/// Calculates the number of bytes a telemetry item takes in a serialized batch, including a
+/// separator from the previous item. Items that cannot be serialized take no space, because they
+/// are dropped when a batch is sent.
+pub fn serialized_len<T: Serialize>(item: &T) -> usize {
+    let mut counter = ByteCounter(0);
+    match serde_json::to_writer(&mut counter, item) {
+        Ok(()) => counter.0 + 1,
+        Err(_) => 0,
+    }
+}
+
+/// Counts bytes written to it instead of storing them.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Determines whether a request failed because the endpoint host could not be resolved.
+fn is_resolution_error(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if err.is::<ResolutionError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Parses a `Retry-After` header that holds a date of the server clock.
+fn parse_retry_after(retry_after: &HeaderValue) -> Option<DateTime<Utc>> {
+    let retry_after = retry_after.to_str().ok()?;
+    DateTime::parse_from_rfc2822(retry_after)
+        .ok()
+        .map(|retry_after| retry_after.with_timezone(&Utc))
+}
+
+/// Determines whether a telemetry item was created earlier than a given time to live ago.
+pub fn is_expired(item: &Envelope, time_to_live: Duration, now: DateTime<Utc>) -> bool {
+    match DateTime::parse_from_rfc3339(&item.time) {
+        Ok(time) => matches!((now - time.with_timezone(&Utc)).to_std(), Ok(age) if age > time_to_live),
+        Err(_) => false,
+    }
+}
+
+/// Filters out those telemetry items that cannot be re-sent and returns items the server rejected
+/// with a status code and a message of the server for each of them.
+///
+/// Errors are matched with items by their indices in any order. An index out of range of the
+/// batch is ignored, so is any error of an item reported more than once but the first one.
+fn retain_retry_items<T: BatchItem>(items: &mut Vec<T>, content: Transmission) -> Vec<RejectedItem> {
+    let mut errors: Vec<Option<TransmissionItem>> = vec![None; items.len()];
+    for error in content.errors {
+        if let Some(slot) = errors.get_mut(error.index) {
+            slot.get_or_insert(error);
+        }
+    }
+
+    let mut retry_items = Vec::default();
+    let mut rejected = Vec::default();
+    for (item, error) in items.drain(..).zip(errors) {
+        match error {
+            Some(error) if can_retry_item(&error) => retry_items.push(item),
+            Some(error) => rejected.push((item.into_envelope(), error.status_code, error.message)),
+            None => {}
+        }
+    }
+
+    *items = retry_items;
+    rejected
+}
+
+/// Determines that a telemetry item can be re-send corresponding to this submission status
+/// descriptor.
+fn can_retry_item(item: &TransmissionItem) -> bool {
+    item.status_code == StatusCode::PARTIAL_CONTENT
+        || item.status_code == StatusCode::REQUEST_TIMEOUT
+        || item.status_code == StatusCode::INTERNAL_SERVER_ERROR
+        || item.status_code == StatusCode::SERVICE_UNAVAILABLE
+        || item.status_code == StatusCode::TOO_MANY_REQUESTS
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "reqwest")]
+    use std::net::SocketAddr;
+    use std::{collections::BTreeMap, sync::Arc};
+
+    use chrono::TimeZone;
+    use http::{Request, StatusCode};
+    #[cfg(feature = "reqwest")]
+    use hyper::client::connect::dns::Name;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Server,
+    };
+    use matches::assert_matches;
+    #[cfg(feature = "reqwest")]
+    use reqwest::dns::{Addrs, Resolve, Resolving};
+    use serde_json::{json, Value};
+    use test_case::test_case;
+
+    use super::*;
+    use crate::contracts::{Data, EventData};
+
+    #[test_case(items(), StatusCode::OK, None, Some(all_accepted()), Response::Success; "success")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()), Response::Retry(retry_items()); "partial. resend some items")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(partial_no_retries()), Response::NoRetry; "partial. nothing to resend")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(none_accepted()), Response::Retry(items()); "partial. resend everything")]
+    #[test_case(items(), StatusCode::PARTIAL_CONTENT, None, Some(all_accepted()), Response::Success; "partial. everything accepted")]
+    #[test_case(items(), StatusCode::BAD_REQUEST, None, None, Response::NoRetry; "bad request. no retry")]
+    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, None, Response::Retry(items()); "timeout. resend everything")]
+    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, Some(retry_after_str()), None, Response::Throttled(retry_after(), items()); "timeout. throttled")]
+    #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, None, None,Response::Retry(items()); "too many requests. no retry-after. resend everything")]
+    #[test_case(items(), StatusCode::TOO_MANY_REQUESTS, Some(retry_after_str()), None, Response::Throttled(retry_after(), items()); "too many requests. retry-after. throttled")]
+    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, None, Response::Retry(items()); "server error. resend everything")]
+    #[test_case(items(), StatusCode::SERVICE_UNAVAILABLE, None, None, Response::Retry(items()); "service unavailable. resend everything")]
+    #[test_case(items(), StatusCode::UNAUTHORIZED, None, None, Response::NoRetry; "unauthorized. no retry")]
+    #[test_case(items(), StatusCode::REQUEST_TIMEOUT, None, Some(partial_some_retries()), Response::Retry(retry_items()); "timeout. resend some items")]
+    #[test_case(items(), StatusCode::INTERNAL_SERVER_ERROR, None, Some(partial_some_retries()), Response::Retry(retry_items()); "server error. resend some items")]
+    fn it_sends_telemetry_and_handles_server_response(
+        items: Vec<Envelope>,
+        status_code: StatusCode,
+        retry_after: Option<&'static str>,
+        body: Option<Value>,
+        expected: Response,
+    ) {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(status_code, retry_after, body);
+
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("{}/track", url))
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items).await.unwrap();
+
+            assert_eq!(response, expected);
+        });
+    }
+
+    #[cfg(feature = "agent")]
+    #[tokio::test]
+    async fn it_forwards_items_to_agent_instead_of_endpoint() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .agent_endpoint(crate::agent::AgentEndpoint::tcp(
+                listener.local_addr().unwrap().to_string(),
+            ))
+            .build();
+        let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+        let response = transmitter.send(items()).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut payload = vec![0; stream.read_u32().await.unwrap() as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        let payload: Vec<Envelope> = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(payload.len(), items().len());
+    }
+
+    #[cfg(feature = "agent")]
+    #[tokio::test]
+    async fn it_retries_items_when_agent_closes_connection_mid_batch() {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = TelemetryConfig::builder()
+            .i_key("instrumentation")
+            .agent_endpoint(crate::agent::AgentEndpoint::tcp(
+                listener.local_addr().unwrap().to_string(),
+            ))
+            .build();
+        let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+        // a batch larger than socket buffers, so it is still being written when the agent goes away
+        let batch: Vec<_> = (0..64)
+            .map(|i| Envelope {
+                name: format!("event {} {}", i, "x".repeat(256 * 1024)),
+                ..Envelope::default()
+            })
+            .collect();
+
+        let agent = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.read_u32().await.unwrap();
+            stream.read_exact(&mut [0; 1024]).await.unwrap();
+            listener
+        });
+        let response = transmitter.send(batch.clone()).await.unwrap();
+        let listener = agent.await.unwrap();
+
+        assert_eq!(response, Response::Retry(batch.clone()));
+
+        let agent = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut payload = vec![0; stream.read_u32().await.unwrap() as usize];
+            stream.read_exact(&mut payload).await.unwrap();
+            serde_json::from_slice::<Vec<Envelope>>(&payload).unwrap()
+        });
+        let response = transmitter.send(batch.clone()).await.unwrap();
+
+        assert_eq!(response, Response::Success);
+        assert_eq!(agent.await.unwrap().len(), batch.len());
+    }
+
+    #[test]
+    fn it_translates_throttle_deadline_to_local_clock() {
+        struct LaggingClock;
+
+        impl clock::Clock for LaggingClock {
+            fn now(&self) -> Option<DateTime<Utc>> {
+                Some(Utc::now() - chrono::Duration::hours(1))
+            }
+        }
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(StatusCode::TOO_MANY_REQUESTS, Some(retry_after_str()), None);
+
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("{}/track", url))
+                .clock(LaggingClock)
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            // the `Date` header has a resolution of one second
+            match response {
+                Response::Throttled(until, _) => {
+                    let error = until - (retry_after() - chrono::Duration::hours(1));
+                    assert!(error.num_milliseconds().abs() <= 1000, "unexpected deadline: {}", until);
+                }
+                response => panic!("unexpected response: {:?}", response),
+            }
+        });
+    }
+
+    #[test]
+    fn it_learns_clock_skew_from_date_header() {
+        let config = TelemetryConfig::new("instrumentation".into());
+        let logger = InternalLogger::from_config(&config);
+        let skew = ClockSkew::default();
+        let mut headers = HeaderMap::new();
+        headers.insert(DATE, "Wed, 09 Aug 2017 23:43:57 GMT".parse().unwrap());
+
+        // the server responded at the midpoint of the request
+        let sent = Utc.ymd(2017, 8, 9).and_hms(23, 40, 56);
+        let received = Utc.ymd(2017, 8, 9).and_hms(23, 40, 58);
+        skew.observe(&headers, sent, received, &logger);
+        assert_eq!(skew.offset(), chrono::Duration::minutes(3));
+        assert_eq!(skew.to_local(retry_after()), Utc.ymd(2017, 8, 9).and_hms(23, 40, 57));
+
+        skew.observe(&HeaderMap::new(), sent, received, &logger);
+        assert_eq!(skew.offset(), chrono::Duration::minutes(3));
+    }
+
+    #[test]
+    fn it_ignores_clock_skew_below_header_resolution() {
+        let skew = ClockSkew::default();
+        let local_time = Utc.ymd(2017, 8, 9).and_hms_milli(23, 43, 56, 500);
+
+        assert_eq!(skew.update(Utc.ymd(2017, 8, 9).and_hms(23, 43, 57), local_time), None);
+        assert_eq!(skew.offset(), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn it_smooths_clock_skew_samples() {
+        let skew = ClockSkew::default();
+        let local_time = Utc.ymd(2017, 8, 9).and_hms(23, 40, 57);
+
+        skew.update(local_time + chrono::Duration::minutes(3), local_time);
+        assert_eq!(skew.offset(), chrono::Duration::minutes(3));
+
+        // a single response that agrees with the local clock moves the offset only a quarter
+        skew.update(local_time, local_time);
+        assert_eq!(skew.offset(), chrono::Duration::seconds(135));
+    }
+
+    #[test]
+    fn it_reports_clock_skew_only_when_it_exceeds_header_resolution() {
+        let skew = ClockSkew::default();
+        let local_time = Utc.ymd(2017, 8, 9).and_hms(23, 40, 57);
+
+        assert_eq!(
+            skew.update(local_time + chrono::Duration::minutes(3), local_time),
+            Some(180_000)
+        );
+        assert_eq!(skew.update(local_time + chrono::Duration::minutes(4), local_time), None);
+
+        // the skew is reported again once the clocks agreed in the meantime
+        for _ in 0..20 {
+            assert_eq!(skew.update(local_time, local_time), None);
+        }
+        assert_eq!(skew.offset(), chrono::Duration::zero());
+        assert_matches!(
+            skew.update(local_time - chrono::Duration::minutes(60), local_time),
+            Some(offset) if offset < 0
+        );
+    }
+
+    #[test]
+    fn it_hands_rejected_items_over_to_handler() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(StatusCode::PARTIAL_CONTENT, None, Some(partial_some_retries()));
+
+            let rejected = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("{}/track", url))
+                .on_items_rejected({
+                    let rejected = rejected.clone();
+                    move |items| rejected.lock().unwrap().extend(items)
+                })
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::Retry(retry_items()));
+            assert_eq!(
+                *rejected.lock().unwrap(),
+                vec![(
+                    Envelope {
+                        name: "event 2".into(),
+                        ..Envelope::default()
+                    },
+                    400,
+                    "Bad 1".to_string()
+                )]
+            );
+        });
+    }
+
+    #[test]
+    fn it_does_not_retry_items_older_than_max_retry_age_of_their_category() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(StatusCode::SERVICE_UNAVAILABLE, None, None);
+
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("{}/track", url))
+                .max_retry_age(TelemetryKind::Availability, Duration::ZERO)
+                .max_retry_age(TelemetryKind::Metric, Duration::from_secs(60))
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let items = vec![
+                item("availability", 0, Data::AvailabilityData(Default::default())),
+                item("stale metric", 120, Data::MetricData(Default::default())),
+                item("fresh metric", 0, Data::MetricData(Default::default())),
+                item("stale exception", 120, Data::ExceptionData(Default::default())),
+            ];
+            let response = transmitter.send(items).await.unwrap();
+
+            match response {
+                Response::Retry(items) => {
+                    let names: Vec<_> = items.iter().map(|item| item.name.as_str()).collect();
+                    assert_eq!(names, vec!["fresh metric", "stale exception"]);
+                }
+                response => panic!("unexpected response: {:?}", response),
+            }
+        });
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[test]
+    fn it_uses_custom_dns_resolver() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let url = create_server(StatusCode::OK, None, Some(all_accepted()));
+            let addr: SocketAddr = url.trim_start_matches("http://").parse().unwrap();
+
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("http://ingestion.test:{}/track", addr.port()))
+                .dns_resolver(Arc::new(TestResolver(Some(([127, 0, 0, 1], addr.port()).into()))))
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::Success);
+        });
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[test]
+    fn it_returns_items_back_when_host_cannot_be_resolved() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint("http://ingestion.test/track")
+                .dns_resolver(Arc::new(TestResolver(None)))
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::ResolutionFailed(items()));
+        });
+    }
+
+    #[test]
+    fn it_returns_items_back_when_request_times_out() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        rt.block_on(async {
+            // the server accepts connections but never responds
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+            let config = TelemetryConfig::builder()
+                .i_key("instrumentation")
+                .endpoint(format!("http://{}/track", listener.local_addr().unwrap()))
+                .request_timeout(Duration::from_millis(100))
+                .build();
+            let transmitter = Transmitter::from_config(&config, InternalLogger::from_config(&config));
+
+            let response = transmitter.send(items()).await.unwrap();
+
+            assert_eq!(response, Response::Retry(items()));
+        });
+    }
+
+    #[test]
+    fn it_restamps_items_with_the_newest_instrumentation_key() {
+        let rotations = KeyRotations::default();
+        rotations.rotate("first", "second");
+        rotations.rotate("second", "third");
+
+        let mut items: Vec<_> = ["first", "second", "third", "other"]
+            .iter()
+            .map(|key| Envelope {
+                i_key: Some(key.to_string()),
+                ..Envelope::default()
+            })
+            .collect();
+        rotations.restamp(&mut items);
+
+        let keys: Vec<_> = items.iter().filter_map(|item| item.i_key.as_deref()).collect();
+        assert_eq!(keys, vec!["third", "third", "third", "other"]);
+    }
+
+    #[test]
+    fn it_skips_items_that_cannot_be_serialized() {
+        let mut items = vec![Item(Some(1)), Item(None), Item(Some(3)), Item(None)];
+
+        let (payload, errors) = serialize(&mut items);
+
+        assert_eq!(String::from_utf8(payload).unwrap(), r#"[{"value":1},{"value":3}]"#);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(items, vec![Item(Some(1)), Item(Some(3))]);
+    }
+
+    #[test]
+    fn it_retains_retry_items_reported_in_any_order() {
+        let mut items = items();
+        let content = Transmission {
+            items_received: 5,
+            items_accepted: 1,
+            errors: vec![
+                error(3, 429),
+                error(1, 400),
+                error(3, 400),
+                error(42, 500),
+                error(0, 500),
+                error(4, 503),
+            ],
+        };
+
+        let rejected = retain_retry_items(&mut items, content);
+
+        let names: Vec<_> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["event 0", "event 3", "event 4"]);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!((rejected[0].0.name.as_str(), rejected[0].1), ("event 1", 400));
+    }
+
+    #[test]
+    fn it_calculates_serialized_len_of_items_in_batch() {
+        let mut items = vec![Item(Some(1)), Item(None), Item(Some(300))];
+        let len: usize = items.iter().map(serialized_len).sum();
+
+        let (payload, _) = serialize(&mut items);
+
+        // brackets of an array without a separator in front of the first item
+        assert_eq!(payload.len(), len + 1);
+    }
+
+    #[test]
+    fn it_splits_off_items_that_exceed_max_payload_size() {
+        let write = |payload: &mut Vec<u8>, items: &[Item], index: usize| serde_json::to_writer(payload, &items[index]);
+        let mut items = vec![Item(Some(1)), Item(None), Item(Some(2)), Item(Some(3))];
+
+        // `[{"value":1},{"value":2}]` takes 25 bytes
+        let (payload, errors, rest) = serialize_with(&mut items, Vec::new(), Some(25), write);
+
+        assert_eq!(payload, br#"[{"value":1},{"value":2}]"#);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(items, vec![Item(Some(1)), Item(Some(2))]);
+        assert_eq!(rest, vec![Item(Some(3))]);
+
+        // a payload holds at least one item even if it exceeds the limit alone
+        let (payload, _, rest) = serialize_with(&mut items, Vec::new(), Some(5), write);
+
+        assert_eq!(payload, br#"[{"value":1}]"#);
+        assert_eq!(items, vec![Item(Some(1))]);
+        assert_eq!(rest, vec![Item(Some(2))]);
+    }
+
+    #[test]
+    fn it_serializes_envelopes_with_shared_tags_as_is() {
+        let tags = |role: &str| Some(BTreeMap::from([("ai.cloud.role".to_string(), role.to_string())]).into());
+        let mut items: Vec<_> = vec![
+            tags("web"),
+            tags("web"),
+            None,
+            tags("web"),
+            tags("\"worker\""),
+            tags("web"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, tags)| Envelope {
+            name: format!("event {}", i),
+            tags,
+            data: Some(Base::Data(Data::EventData(EventData {
+                name: format!("event {}", i),
+                ..EventData::default()
+            }))),
+            ..Envelope::default()
+        })
+        .collect();
+        let expected = serde_json::to_vec(&items).unwrap();
+
+        let (payload, errors, _) = serialize_envelopes(&mut items, Vec::new(), None);
+
+        assert_eq!(
+            String::from_utf8(payload).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
+        assert!(errors.is_empty());
+        assert_eq!(items.len(), 6);
+    }
+
+    #[test]
+    fn it_allocates_payload_once_for_batch_of_similar_items() {
+        let buffer = PayloadBuffer::new(None);
+        let (payload, _, _) = serialize_envelopes(&mut items(), buffer.take(5), None);
+        buffer.finish(payload, 5);
+
+        let mut batch: Vec<_> = (0..50).flat_map(|_| items()).collect();
+        let capacity = buffer.capacity(batch.len());
+        let payload = buffer.take(batch.len());
+        assert!(payload.capacity() >= capacity);
+        let reserved = payload.capacity();
+        let (payload, _, _) = serialize_envelopes(&mut batch, payload, None);
+
+        assert!(payload.len() <= capacity);
+        assert_eq!(payload.capacity(), reserved);
+    }
+
+    #[test]
+    fn it_reuses_payload_buffer_between_batches() {
+        let buffer = PayloadBuffer::new(None);
+        let (payload, _, _) = serialize_envelopes(&mut items(), buffer.take(5), None);
+        let sent = buffer.finish(payload, 5);
+        let reused = buffer.take(5);
+
+        assert_eq!(sent.capacity(), sent.len());
+        assert!(reused.capacity() >= sent.len());
+
+        // a batch serialized concurrently allocates a buffer of its own
+        assert_eq!(buffer.buffer.lock().unwrap().capacity(), 0);
+    }
+
+    #[test]
+    fn it_limits_payload_capacity_to_max_batch_size() {
+        let buffer = PayloadBuffer::new(Some(1024));
+        buffer.finish(vec![b' '; 1000], 1);
+
+        assert_eq!(buffer.capacity(1_000_000), 1024);
+        let unlimited = PayloadBuffer::new(None);
+        unlimited.finish(vec![b' '; 1000], 1);
+        assert_eq!(unlimited.capacity(1_000_000), MAX_PAYLOAD_CAPACITY);
+
+        // a buffer larger than a batch can take is not kept
+        buffer.finish(vec![b' '; 2000], 1);
+        assert_eq!(buffer.buffer.lock().unwrap().capacity(), 1000);
+    }
+
+    #[test]
+    fn it_averages_item_size_over_batches() {
+        let buffer = PayloadBuffer::new(None);
+        buffer.finish(vec![b' '; 1000], 10);
+        buffer.finish(vec![b' '; 5000], 10);
+
+        // an outlier moves the average only by a quarter of a difference
+        assert_eq!(buffer.per_item.load(Ordering::Relaxed), 200);
+    }
+
+    /// An item that fails to serialize when it has no value.
+    #[derive(Debug, PartialEq)]
+    struct Item(Option<i32>);
+
+    impl Serialize for Item {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            use serde::ser::{Error, SerializeStruct};
+
+            let mut state = serializer.serialize_struct("Item", 1)?;
+            let value = self.0.ok_or_else(|| S::Error::custom("no value"))?;
+            state.serialize_field("value", &value)?;
+            state.end()
+        }
+    }
+
+    /// Resolves any name to a given address or fails when there is no address.
+    #[cfg(feature = "reqwest")]
+    struct TestResolver(Option<SocketAddr>);
+
+    #[cfg(feature = "reqwest")]
+    impl Resolve for TestResolver {
+        fn resolve(&self, _: Name) -> Resolving {
+            let addr = self.0;
+            Box::pin(async move {
+                match addr {
+                    Some(addr) => Ok(Box::new(std::iter::once(addr)) as Addrs),
+                    None => Err("no such host".into()),
+                }
+            })
+        }
+    }
+
+    fn create_server(status_code: StatusCode, retry_after: Option<&'static str>, body: Option<Value>) -> String {
+        let make_service = make_service_fn(move |_| {
+            let retry_after = retry_after.map(ToString::to_string);
+            let body = body.clone();
+            async move {
+                Ok::<_, hyper::Error>(service_fn(move |_: Request<Body>| {
+                    let retry_after = retry_after.clone();
+                    let body = body.clone();
+                    async move {
+                        let mut builder = hyper::Response::builder().status(status_code);
+
+                        if let Some(retry_after) = retry_after {
+                            builder = builder.header("Retry-After", retry_after);
+                        }
+
+                        let body = body.map(move |body| Body::from(body.to_string())).unwrap_or_default();
+
+                        builder.body(body)
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&([0, 0, 0, 0], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+
+        tokio::spawn(server);
+
+        url
+    }
+
+    fn partial_no_retries() -> Value {
+        json!({
+            "itemsAccepted": 3,
+            "itemsReceived": 5,
+            "errors": [
+                {
+                    "index": 2,
+                    "statusCode": 400,
+                    "message": "Bad 1"
+                },
+                {
+                    "index": 4,
+                    "statusCode": 400,
+                    "message": "Bad 2"
+                },
+            ],
+        })
+    }
+
+    fn partial_some_retries() -> Value {
+        json!({
+            "itemsAccepted": 2,
+            "itemsReceived": 5,
+            "errors": [
+                {
+                    "index": 2,
+                    "statusCode": 400,
+                    "message": "Bad 1"
+                },
+                {
+                    "index": 4,
+                    "statusCode": 408,
+                    "message": "OK Later"
+                },
+            ],
+        })
+    }
+
+    fn none_accepted() -> Value {
+        json!({
+            "itemsAccepted": 0,
+            "itemsReceived": 5,
+            "errors": [
+                {
+                    "index": 0,
+                    "statusCode": 500,
+                    "message": "Bad 1"
+                },
+                {
+                    "index": 1,
+                    "statusCode": 500,
+                    "message": "Bad 2"
+                },
+                {
+                    "index": 2,
+                    "statusCode": 500,
+                    "message": "Bad 3"
+                },
+                {
+                    "index": 3,
+                    "statusCode": 500,
+                    "message": "Bad 4"
+                },
+                {
+                    "index": 4,
+                    "statusCode": 500,
+                    "message": "Bad 5"
+                },
+            ],
+        })
+    }
+
+    fn all_accepted() -> Value {
+        json!({
+            "itemsAccepted": 5,
+            "itemsReceived": 5,
+            "errors": [],
+        })
+    }
+
+    fn retry_after_str() -> &'static str {
+        "Wed, 09 Aug 2017 23:43:57 GMT"
+    }
+
+    fn retry_after() -> DateTime<Utc> {
+        Utc.ymd(2017, 8, 9).and_hms(23, 43, 57)
+    }
+
+    fn items() -> Vec<Envelope> {
+        (0..5)
+            .map(|i| Envelope {
+                name: format!("event {}", i),
+                ..Envelope::default()
+            })
+            .collect()
+    }
+
+    fn item(name: &str, age_secs: i64, data: Data) -> Envelope {
+        Envelope {
+            name: name.into(),
+            time: (Utc::now() - chrono::Duration::seconds(age_secs)).to_rfc3339(),
+            data: Some(Base::Data(data)),
+            ..Envelope::default()
+        }
+    }
+
+    fn error(index: usize, status_code: u16) -> TransmissionItem {
+        TransmissionItem {
+            index,
+            status_code,
+            message: "error".into(),
+        }
+    }
+
+    fn retry_items() -> Vec<Envelope> {
+        vec![Envelope {
+            name: "event 4".into(),
+            ..Envelope::default()
+        }]
+    }
+}
@@ -0,0 +1,276 @@
+use std::{
+    error::Error,
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use http::Uri;
+use hyper::{
+    client::{
+        connect::dns::{GaiAddrs, GaiResolver, Name},
+        HttpConnector,
+    },
+    service::Service,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::transmitter::http_client::ResolutionError;
+
+/// Maximum size of a proxy response to a tunnel request.
+const MAX_RESPONSE_LEN: usize = 8192;
+
+/// Connects to the server either directly or through a tunnel established by an HTTP proxy with
+/// the `CONNECT` method.
+#[derive(Debug, Clone)]
+pub struct TunnelConnector {
+    http: HttpConnector<Resolver>,
+    proxy: Option<Uri>,
+}
+
+impl TunnelConnector {
+    /// Creates a new connector that connects through a given proxy if any.
+    pub fn new(proxy: Option<Uri>) -> Self {
+        let mut http = HttpConnector::new_with_resolver(Resolver(GaiResolver::new()));
+        http.enforce_http(false);
+        Self { http, proxy }
+    }
+
+    /// Creates a new connector that connects to an endpoint through a proxy configured with
+    /// `HTTPS_PROXY` or `HTTP_PROXY` environment variables depending on an endpoint scheme unless
+    /// the endpoint host is listed in `NO_PROXY` environment variable.
+    pub fn from_env(endpoint: &str) -> Self {
+        let proxy = endpoint
+            .parse()
+            .ok()
+            .and_then(|endpoint| proxy_for(&endpoint, |name| std::env::var(name).ok()));
+        Self::new(proxy)
+    }
+}
+
+impl Service<Uri> for TunnelConnector {
+    type Response = TcpStream;
+    type Error = Box<dyn Error + Send + Sync>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.http.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let mut http = self.http.clone();
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            match proxy {
+                Some(proxy) => {
+                    let mut stream = http.call(proxy).await?;
+                    tunnel(&mut stream, &dst).await?;
+                    Ok(stream)
+                }
+                None => Ok(http.call(dst).await?),
+            }
+        })
+    }
+}
+
+/// Resolves names with the system resolver and marks its failures as [`ResolutionError`].
+#[derive(Debug, Clone)]
+pub(crate) struct Resolver(GaiResolver);
+
+impl Service<Name> for Resolver {
+    type Response = GaiAddrs;
+    type Error = ResolutionError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx).map_err(|err| ResolutionError(err.into()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.0.call(name);
+        Box::pin(async move { resolving.await.map_err(|err| ResolutionError(err.into())) })
+    }
+}
+
+/// Asks a proxy to establish a tunnel to a destination host and waits until it is established.
+async fn tunnel(stream: &mut TcpStream, dst: &Uri) -> io::Result<()> {
+    let host = dst
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "destination has no host"))?;
+    let port = dst
+        .port_u16()
+        .unwrap_or(if dst.scheme_str() == Some("http") { 80 } else { 443 });
+
+    let request = format!("CONNECT {0}:{1} HTTP/1.1\r\nHost: {0}:{1}\r\n\r\n", host, port);
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::new();
+    let mut buf = [0; 1024];
+    while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+        if response.len() > MAX_RESPONSE_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "proxy response is too large",
+            ));
+        }
+
+        let len = stream.read(&mut buf).await?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "proxy closed connection before tunnel was established",
+            ));
+        }
+        response.extend_from_slice(&buf[..len]);
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        _ => Err(io::Error::other(format!(
+            "proxy refused to establish tunnel: {}",
+            status_line
+        ))),
+    }
+}
+
+/// Finds a proxy to connect to an endpoint through with environment variables looked up by a
+/// given function.
+fn proxy_for(endpoint: &Uri, var: impl Fn(&str) -> Option<String>) -> Option<Uri> {
+    let host = endpoint.host()?;
+    let no_proxy = var("NO_PROXY").or_else(|| var("no_proxy")).unwrap_or_default();
+    if no_proxy
+        .split(',')
+        .any(|pattern| matches_no_proxy(host, pattern.trim()))
+    {
+        return None;
+    }
+
+    let names = match endpoint.scheme_str() {
+        Some("http") => ["HTTP_PROXY", "http_proxy"],
+        _ => ["HTTPS_PROXY", "https_proxy"],
+    };
+    let proxy = names
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|proxy| !proxy.is_empty())?;
+
+    // a proxy is often configured as a host and port only
+    if proxy.contains("://") {
+        proxy.parse().ok()
+    } else {
+        format!("http://{}", proxy).parse().ok()
+    }
+}
+
+/// Determines whether a host matches a `NO_PROXY` entry, which is either `*`, a host name or a
+/// domain name that matches all its subdomains.
+fn matches_no_proxy(host: &str, pattern: &str) -> bool {
+    let domain = pattern.trim_start_matches('.');
+    match pattern {
+        "" => false,
+        "*" => true,
+        _ => {
+            host.eq_ignore_ascii_case(domain)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use test_case::test_case;
+    use tokio::{io::copy_bidirectional, net::TcpListener};
+
+    use super::*;
+
+    #[test_case("https://dc.example.com/v2/track", &[("HTTPS_PROXY", "proxy:3128")], Some("http://proxy:3128/") ; "https proxy")]
+    #[test_case("https://dc.example.com/v2/track", &[("https_proxy", "https://proxy:3128")], Some("https://proxy:3128/") ; "lowercase https proxy")]
+    #[test_case("http://dc.example.com/v2/track", &[("HTTPS_PROXY", "proxy:3128")], None ; "no http proxy")]
+    #[test_case("http://dc.example.com/v2/track", &[("HTTP_PROXY", "proxy:3128")], Some("http://proxy:3128/") ; "http proxy")]
+    #[test_case("https://dc.example.com/v2/track", &[("HTTPS_PROXY", "proxy:3128"), ("NO_PROXY", "localhost, .example.com")], None ; "no proxy for domain")]
+    #[test_case("https://dc.example.com/v2/track", &[("HTTPS_PROXY", "proxy:3128"), ("NO_PROXY", "*")], None ; "no proxy for any host")]
+    #[test_case("https://dc.example.com/v2/track", &[("HTTPS_PROXY", "proxy:3128"), ("no_proxy", "ample.com")], Some("http://proxy:3128/") ; "no proxy for other domain")]
+    fn it_finds_proxy_for_endpoint(endpoint: &str, vars: &[(&str, &str)], expected: Option<&str>) {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+
+        let proxy = proxy_for(&endpoint.parse().unwrap(), |name| {
+            vars.get(name).map(ToString::to_string)
+        });
+
+        assert_eq!(proxy.map(|proxy| proxy.to_string()), expected.map(ToString::to_string));
+    }
+
+    #[tokio::test]
+    async fn it_connects_to_server_through_proxy_tunnel() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = server.accept().await.unwrap();
+            stream.write_all(b"hello").await.unwrap();
+        });
+
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        let requests = tokio::spawn(async move {
+            let (mut client, _) = proxy.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let len = client.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..len]).into_owned();
+
+            client
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            let mut server = TcpStream::connect(server_addr).await.unwrap();
+            let _ = copy_bidirectional(&mut client, &mut server).await;
+            request
+        });
+
+        let mut connector = TunnelConnector::new(Some(format!("http://{}", proxy_addr).parse().unwrap()));
+        let mut stream = connector
+            .call(format!("https://{}/v2/track", server_addr).parse().unwrap())
+            .await
+            .unwrap();
+
+        let mut greeting = String::new();
+        stream.read_to_string(&mut greeting).await.unwrap();
+        drop(stream);
+
+        assert_eq!(greeting, "hello");
+        assert_eq!(
+            requests.await.unwrap(),
+            format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n\r\n", server_addr)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_when_proxy_refuses_tunnel() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = proxy.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut client, _) = proxy.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let _ = client.read(&mut buf).await.unwrap();
+            client.write_all(b"HTTP/1.1 403 Forbidden\r\n\r\n").await.unwrap();
+        });
+
+        let mut connector = TunnelConnector::new(Some(format!("http://{}", proxy_addr).parse().unwrap()));
+        let result = connector.call("https://dc.example.com/v2/track".parse().unwrap()).await;
+
+        let err = result.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "proxy refused to establish tunnel: HTTP/1.1 403 Forbidden"
+        );
+    }
+}
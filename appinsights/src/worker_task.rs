@@ -0,0 +1,130 @@
+//! Custom maintenance tasks run on the schedule of a submission worker.
+//!
+//! A submission worker wakes up every [submission interval](crate::TelemetryConfig::interval) to
+//! send pending telemetry items. A [`WorkerTask`] registered with
+//! [`TelemetryConfig::builder`](crate::TelemetryConfig::builder) runs every n-th elapsed interval,
+//! so extensions like queue statistics or compaction of files piggyback on the existing timer
+//! instead of spawning their own loops.
+//!
+//! Tasks run on the worker itself and delay submission of telemetry items while they run, so
+//! a task that takes long should hand the work over to a separate thread or task. A task that
+//! panics is reported to diagnostics and does not stop the worker.
+//!
+//! ```rust
+//! use appinsights::{worker_task::WorkerTick, TelemetryConfig};
+//!
+//! let config = TelemetryConfig::builder()
+//!     .i_key("<instrumentation key>")
+//!     .worker_task(10, |tick: &WorkerTick| {
+//!         println!("{} telemetry items pending after {} intervals", tick.pending(), tick.ticks());
+//!     })
+//!     .build();
+//! ```
+use std::sync::Arc;
+
+use crate::shared::Shared;
+
+/// A task that runs periodically on the schedule of a submission worker.
+pub trait WorkerTask: Send + Sync {
+    /// Runs the task once the scheduled number of submission intervals elapsed.
+    fn run(&self, tick: &WorkerTick);
+}
+
+impl<F> WorkerTask for F
+where
+    F: Fn(&WorkerTick) + Send + Sync,
+{
+    fn run(&self, tick: &WorkerTick) {
+        self(tick)
+    }
+}
+
+/// A state of a submission worker at the moment a task runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerTick {
+    ticks: u64,
+    pending: usize,
+}
+
+impl WorkerTick {
+    /// Creates a new state of a submission worker.
+    pub(crate) fn new(ticks: u64, pending: usize) -> Self {
+        Self { ticks, pending }
+    }
+
+    /// Returns a number of submission intervals elapsed since the worker has started.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Returns a number of telemetry items waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+}
+
+/// A task scheduled to run every n-th elapsed submission interval. It makes a task comparable and
+/// printable as part of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ScheduledTask {
+    every: u64,
+    task: Shared<dyn WorkerTask>,
+}
+
+impl ScheduledTask {
+    pub(crate) fn new(every: u64, task: impl WorkerTask + 'static) -> Self {
+        let task: Arc<dyn WorkerTask> = Arc::new(task);
+        Self {
+            every: every.max(1),
+            task: task.into(),
+        }
+    }
+
+    /// Runs the task if it is scheduled for a given tick.
+    pub(crate) fn run(&self, tick: &WorkerTick) {
+        if tick.ticks.is_multiple_of(self.every) {
+            self.task.run(tick);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn it_runs_task_every_nth_tick() {
+        let runs = Arc::new(AtomicU64::default());
+        let task = ScheduledTask::new(3, {
+            let runs = runs.clone();
+            move |tick: &WorkerTick| {
+                runs.fetch_add(tick.ticks(), Ordering::SeqCst);
+            }
+        });
+
+        for ticks in 1..=7 {
+            task.run(&WorkerTick::new(ticks, 0));
+        }
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3 + 6);
+    }
+
+    #[test]
+    fn it_runs_task_every_tick_when_scheduled_for_zero_intervals() {
+        let runs = Arc::new(AtomicU64::default());
+        let task = ScheduledTask::new(0, {
+            let runs = runs.clone();
+            move |_: &WorkerTick| {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        for ticks in 1..=3 {
+            task.run(&WorkerTick::new(ticks, 0));
+        }
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}